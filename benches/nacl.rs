@@ -45,7 +45,7 @@ fn ewald_energy_computation(c: &mut Criterion) {
 
 fn ewald_monte_carlo_cache(c: &mut Criterion) {
     let mut system = utils::get_system("nacl");
-    system.set_coulomb_potential(Box::new(get_ewald()));
+    system.set_coulomb_potential(Box::new(get_ewald())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -56,7 +56,7 @@ fn ewald_monte_carlo_cache(c: &mut Criterion) {
     ));
 
     let mut system = utils::get_system("nacl");
-    system.set_coulomb_potential(Box::new(get_ewald()));
+    system.set_coulomb_potential(Box::new(get_ewald())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -97,7 +97,7 @@ fn wolf_energy_computation(c: &mut Criterion) {
 
 fn wolf_monte_carlo_cache(c: &mut Criterion) {
     let mut system = utils::get_system("nacl");
-    system.set_coulomb_potential(Box::new(get_wolf()));
+    system.set_coulomb_potential(Box::new(get_wolf())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -108,7 +108,7 @@ fn wolf_monte_carlo_cache(c: &mut Criterion) {
     ));
 
     let mut system = utils::get_system("nacl");
-    system.set_coulomb_potential(Box::new(get_wolf()));
+    system.set_coulomb_potential(Box::new(get_wolf())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
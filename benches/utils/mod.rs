@@ -2,7 +2,7 @@
 // Copyright (C) Lumol's contributors — BSD license
 #![allow(dead_code, clippy::needless_return)]
 
-use lumol::{System, TrajectoryBuilder, Vector3D};
+use lumol::{System, TrajectoryBuilder, UnitCell, Matrix3, Vector3D};
 use lumol::input::InteractionsInput;
 use std::path::Path;
 
@@ -54,3 +54,19 @@ pub fn move_all_rigid_molecule(mut system: System) -> System {
 
     return system;
 }
+
+pub fn scale_all_rigid_molecule(system: &System) -> (UnitCell, Vec<Vector3D>) {
+    let mut rng = get_rng();
+
+    let new_cell = system.cell.scale(Matrix3::one() * rng.gen_range(0.98..1.02));
+    let mut positions = Vec::new();
+    for molecule in system.molecules() {
+        let com = molecule.center_of_mass();
+        let delta = new_cell.cartesian(&system.cell.fractional(&com)) - com;
+        for position in molecule.particles().position {
+            positions.push(position + delta);
+        }
+    }
+
+    return (new_cell, positions);
+}
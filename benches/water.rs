@@ -47,9 +47,31 @@ fn ewald_energy_computation(c: &mut Criterion) {
     }));
 }
 
+fn ewald_serial_computation(c: &mut Criterion) {
+    // Run the same computations as above on a single thread, to check the
+    // speedup coming from the parallel k-space sums
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+    let system = utils::get_system("water");
+    let ewald = get_ewald();
+    c.bench_function("water::ewald::energy::serial", |b| b.iter(|| {
+        pool.install(|| {
+            let _ = ewald.energy(&system);
+        })
+    }));
+
+    let system = utils::get_system("water");
+    let ewald = get_ewald();
+    c.bench_function("water::ewald::force::serial", |b| b.iter_batched_ref(
+        || vec![Vector3D::zero(); system.size()],
+        |forces| pool.install(|| ewald.forces(&system, forces)),
+        BatchSize::SmallInput
+    ));
+}
+
 fn ewald_monte_carlo_cache(c: &mut Criterion) {
     let mut system = utils::get_system("water");
-    system.set_coulomb_potential(Box::new(get_ewald()));
+    system.set_coulomb_potential(Box::new(get_ewald())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -60,7 +82,7 @@ fn ewald_monte_carlo_cache(c: &mut Criterion) {
     ));
 
     let mut system = utils::get_system("water");
-    system.set_coulomb_potential(Box::new(get_ewald()));
+    system.set_coulomb_potential(Box::new(get_ewald())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -69,6 +91,17 @@ fn ewald_monte_carlo_cache(c: &mut Criterion) {
         |system| cache.move_all_molecules_cost(system),
         BatchSize::SmallInput
     ));
+
+    let mut system = utils::get_system("water");
+    system.set_coulomb_potential(Box::new(get_ewald())).unwrap();
+    let mut cache = EnergyCache::new();
+    cache.init(&system);
+
+    c.bench_function("water::ewald::move_volume_cost", move |b| b.iter_batched(
+        || utils::scale_all_rigid_molecule(&system),
+        |(cell, positions)| cache.move_volume_cost(&system, cell, &positions),
+        BatchSize::SmallInput
+    ));
 }
 
 fn wolf_energy_computation(c: &mut Criterion) {
@@ -101,7 +134,7 @@ fn wolf_energy_computation(c: &mut Criterion) {
 
 fn wolf_monte_carlo_cache(c: &mut Criterion) {
     let mut system = utils::get_system("water");
-    system.set_coulomb_potential(Box::new(get_wolf()));
+    system.set_coulomb_potential(Box::new(get_wolf())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -112,7 +145,7 @@ fn wolf_monte_carlo_cache(c: &mut Criterion) {
     ));
 
     let mut system = utils::get_system("water");
-    system.set_coulomb_potential(Box::new(get_wolf()));
+    system.set_coulomb_potential(Box::new(get_wolf())).unwrap();
     let mut cache = EnergyCache::new();
     cache.init(&system);
 
@@ -121,9 +154,20 @@ fn wolf_monte_carlo_cache(c: &mut Criterion) {
         |system| cache.move_all_molecules_cost(system),
         BatchSize::SmallInput
     ));
+
+    let mut system = utils::get_system("water");
+    system.set_coulomb_potential(Box::new(get_wolf())).unwrap();
+    let mut cache = EnergyCache::new();
+    cache.init(&system);
+
+    c.bench_function("water::wolf::move_volume_cost", move |b| b.iter_batched(
+        || utils::scale_all_rigid_molecule(&system),
+        |(cell, positions)| cache.move_volume_cost(&system, cell, &positions),
+        BatchSize::SmallInput
+    ));
 }
 
-criterion_group!(ewald, ewald_energy_computation, ewald_monte_carlo_cache);
+criterion_group!(ewald, ewald_energy_computation, ewald_serial_computation, ewald_monte_carlo_cache);
 criterion_group!(wolf, wolf_energy_computation, wolf_monte_carlo_cache);
 
 criterion_main!(ewald, wolf);
@@ -35,7 +35,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     system.set_pair_potential(
         ("Ar", "Ar"),
         PairInteraction::new(lj, units::from(8.5, "A")?),
-    );
+    )?;
 
     let mut velocities = BoltzmannVelocities::new(units::from(300.0, "K")?);
     velocities.seed(129);
@@ -100,21 +100,25 @@ impl TableComputation {
     /// assert_eq!(table.energy(3.0), 0.0);
     /// ```
     pub fn new(potential: Box<dyn PairPotential>, size: usize, max: f64) -> TableComputation {
-        let delta = max / (size as f64);
-        let mut energy_table = Vec::with_capacity(size);
-        let mut force_table = Vec::with_capacity(size);
-        for i in 0..size {
-            let r = i as f64 * delta;
-            energy_table.push(potential.energy(r));
-            force_table.push(potential.force(r));
-        }
-
-        TableComputation {
-            delta: delta,
+        let mut table = TableComputation {
+            delta: max / (size as f64),
             cutoff: max,
-            energy_table: energy_table,
-            force_table: force_table,
+            energy_table: Vec::with_capacity(size),
+            force_table: Vec::with_capacity(size),
             potential: potential,
+        };
+        table.fill(size);
+        return table;
+    }
+
+    /// Fill the energy and force tables with `size` values from the potential
+    fn fill(&mut self, size: usize) {
+        self.energy_table.clear();
+        self.force_table.clear();
+        for i in 0..size {
+            let r = i as f64 * self.delta;
+            self.energy_table.push(self.potential.energy(r));
+            self.force_table.push(self.potential.force(r));
         }
     }
 }
@@ -171,12 +175,26 @@ impl PairPotential for TableComputation {
         }
         return self.potential.tail_virial(cutoff);
     }
+
+    fn set_lambda(&mut self, lambda: f64) {
+        self.potential.set_lambda(lambda);
+        let size = self.energy_table.len();
+        self.fill(size);
+    }
+
+    fn d_energy_d_lambda(&self, r: f64) -> f64 {
+        if r < self.cutoff {
+            self.potential.d_energy_d_lambda(r)
+        } else {
+            0.0
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Harmonic, LennardJones};
+    use crate::{Harmonic, LennardJones, SoftCoreLennardJones};
     use crate::PairPotential;
 
     #[test]
@@ -209,4 +227,21 @@ mod test {
         assert_eq!(table.tail_energy(5.0), lj.tail_energy(5.0));
         assert_eq!(table.tail_virial(5.0), lj.tail_virial(5.0));
     }
+
+    #[test]
+    fn table_lambda() {
+        let mut soft_core = SoftCoreLennardJones::new(2.0, 50.0, 0.5);
+        let mut table = TableComputation::new(Box::new(soft_core), 1000, 4.0);
+        assert!(table.compute_energy(2.5) < 0.0);
+
+        table.set_lambda(0.5);
+        soft_core.set_lambda(0.5);
+        assert_eq!(table.energy_table.len(), 1000);
+        assert_eq!(table.compute_energy(2.0), soft_core.energy(2.0));
+        assert_eq!(table.d_energy_d_lambda(2.5), soft_core.d_energy_d_lambda(2.5));
+        assert_eq!(table.d_energy_d_lambda(4.5), 0.0);
+
+        table.set_lambda(0.0);
+        assert_eq!(table.compute_energy(2.5), 0.0);
+    }
 }
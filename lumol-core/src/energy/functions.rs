@@ -106,6 +106,71 @@ impl PairPotential for LennardJones {
     }
 }
 
+/// 12-6-4 Lennard-Jones potential, used for the interactions of metal ions
+/// with water.
+///
+/// $$ V(r) = 4 \epsilon \left[\left(\frac \sigma r \right)^{12} -
+///    \left(\frac \sigma r \right)^6 \right] - \frac{C_4}{r^4} $$
+///
+/// where $\sigma$ is the Lennard-Jones distance constant, $\epsilon$ the
+/// Lennard-Jones energetic constant, and $C_4$ the strength of the attraction
+/// between the charge of an ion and the dipole it induces in its neighbors.
+/// The $r^{-12}$ repulsion still dominates at short distances, but the
+/// $r^{-4}$ attraction decays slowly with the distance, and is not negligible
+/// at usual cutoff distances. The potential should be used with tail
+/// corrections or energy shifting.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::LennardJones1264;
+/// let potential = LennardJones1264 { sigma: 2.0, epsilon: 10.0, c4: 16.0 };
+/// assert_eq!(potential.energy(2.0), -1.0);
+/// assert_eq!(potential.force(2.0), 118.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct LennardJones1264 {
+    /// Distance constant of the Lennard-Jones potential
+    pub sigma: f64,
+    /// Energy constant of the Lennard-Jones potential
+    pub epsilon: f64,
+    /// Strength of the charge-induced dipole attraction
+    pub c4: f64,
+}
+
+impl Potential for LennardJones1264 {
+    fn energy(&self, r: f64) -> f64 {
+        let s6 = f64::powi(self.sigma / r, 6);
+        let r4 = f64::powi(r, 4);
+        4.0 * self.epsilon * (f64::powi(s6, 2) - s6) - self.c4 / r4
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        let s6 = f64::powi(self.sigma / r, 6);
+        let r5 = f64::powi(r, 5);
+        -24.0 * self.epsilon * (s6 - 2.0 * f64::powi(s6, 2)) / r - 4.0 * self.c4 / r5
+    }
+}
+
+impl PairPotential for LennardJones1264 {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        let s3 = self.sigma * self.sigma * self.sigma;
+        let rc3 = cutoff * cutoff * cutoff;
+        let s9 = s3 * s3 * s3;
+        let rc9 = rc3 * rc3 * rc3;
+        4.0 / 3.0 * self.epsilon * s3 * (1.0 / 3.0 * s9 / rc9 - s3 / rc3) - self.c4 / cutoff
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        let s3 = self.sigma * self.sigma * self.sigma;
+        let rc3 = cutoff * cutoff * cutoff;
+        let s9 = s3 * s3 * s3;
+        let rc9 = rc3 * rc3 * rc3;
+        8.0 * self.epsilon * s3 * (2.0 / 3.0 * s9 / rc9 - s3 / rc3) - 4.0 * self.c4 / cutoff
+    }
+}
+
 /// Harmonic potential.
 ///
 /// $$ V(x) = \frac{1}{2} k (x - x_0)^2 $$
@@ -163,7 +228,9 @@ impl DihedralPotential for Harmonic {}
 ///
 /// $$ V(x) = \frac{1}{2} k \left[\cos(x) - \cos(x_0) \right]^2 $$
 ///
-/// where $x_0$ is the equilibrium value, and $k$ the elastic constant.
+/// where $x_0$ is the equilibrium value, and $k$ the elastic constant. When
+/// used for angles, the force vanishes for linear geometries, avoiding the
+/// singularity of the harmonic potential at $x = \pi$.
 ///
 /// # Examples
 ///
@@ -257,6 +324,61 @@ impl Potential for Torsion {
 
 impl DihedralPotential for Torsion {}
 
+/// Harmonic dihedral potential.
+///
+/// This potential is intended for use with dihedral angles, for example to
+/// keep groups of atoms planar (improper dihedrals).
+///
+/// $$ V(\phi) = \frac{1}{2} k (\phi - \phi_0)^2 $$
+///
+/// where $k$ is the force constant and $\phi_0$ the equilibrium angle. The
+/// difference $\phi - \phi_0$ is wrapped in $[-\pi, \pi]$, making the
+/// potential periodic and continuous when the dihedral angle crosses the
+/// $\pm \pi$ boundary.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::Potential;
+/// # use lumol_core::energy::HarmonicDihedral;
+/// # use std::f64::consts::PI;
+/// let potential = HarmonicDihedral { k: 10.0, phi0: PI };
+/// assert_eq!(potential.energy(PI), 0.0);
+/// assert_eq!(potential.energy(PI - 0.5), 1.25);
+///
+/// // -PI + 0.5 is close to PI
+/// assert!(f64::abs(potential.energy(-PI + 0.5) - 1.25) < 1e-12);
+/// ```
+#[derive(Clone, Copy)]
+pub struct HarmonicDihedral {
+    /// Force constant
+    pub k: f64,
+    /// Equilibrium value
+    pub phi0: f64,
+}
+
+impl HarmonicDihedral {
+    /// Get the difference between `phi` and the equilibrium value, wrapped
+    /// in $[-\pi, \pi]$
+    fn delta(&self, phi: f64) -> f64 {
+        let delta = phi - self.phi0;
+        delta - 2.0 * PI * f64::round(delta / (2.0 * PI))
+    }
+}
+
+impl Potential for HarmonicDihedral {
+    fn energy(&self, phi: f64) -> f64 {
+        let delta = self.delta(phi);
+        0.5 * self.k * delta * delta
+    }
+
+    fn force(&self, phi: f64) -> f64 {
+        -self.k * self.delta(phi)
+    }
+}
+
+impl DihedralPotential for HarmonicDihedral {}
+
 /// Buckingham potential.
 ///
 /// $$ V(x) = A \exp \left(\frac{\sigma - r}{\rho} \right) - \frac{C}{r^6} $$
@@ -387,10 +509,11 @@ impl PairPotential for BornMayerHuggins {
 
 /// Morse potential
 ///
-/// $$ V(x) = \text{depth} * \left( 1 - \exp(a (x_0 - x))^2 \right)$$
+/// $$ V(x) = \text{depth} * \left( 1 - \exp(a (x_0 - x)) \right)^2$$
 ///
 /// where the parameters are $x_0$ for the equilibrium value, `depth` for the
-/// well depth, and $a$ for the well width.
+/// well depth, and $a$ for the well width. When used as a bond potential,
+/// `depth` is the dissociation energy of the bond.
 ///
 /// # Examples
 ///
@@ -399,7 +522,7 @@ impl PairPotential for BornMayerHuggins {
 /// # use lumol_core::energy::Morse;
 /// let potential = Morse { a: 2.0, x0: 1.3, depth: 4.0 };
 /// assert_eq!(potential.energy(1.0), 2.703517287822119);
-/// assert_eq!(potential.force(1.0), -37.12187076378477);
+/// assert_eq!(potential.force(1.0), 23.96796995753662);
 /// ```
 #[derive(Clone, Copy)]
 pub struct Morse {
@@ -419,7 +542,7 @@ impl Potential for Morse {
 
     fn force(&self, r: f64) -> f64 {
         let exp = f64::exp((self.x0 - r) * self.a);
-        2.0 * self.depth * (1.0 - exp * exp) * self.a
+        -2.0 * self.depth * self.a * exp * (1.0 - exp)
     }
 }
 
@@ -595,12 +718,137 @@ impl PairPotential for Mie {
     }
 }
 
+/// Soft-core Lennard-Jones potential, used to couple and decouple particles
+/// in free energy computations.
+///
+/// $$ V(r) = 4 \lambda \epsilon \left[ \frac{1}{\left(\alpha (1 - \lambda) +
+///    (r / \sigma)^6 \right)^2} - \frac{1}{\alpha (1 - \lambda) + (r / \sigma)^6}
+///    \right] $$
+///
+/// where $\sigma$ is the Lennard-Jones distance constant, $\epsilon$ the
+/// energetic constant, $\lambda$ the coupling parameter, and $\alpha$ the
+/// soft-core parameter. With $\lambda = 1$, this is the usual Lennard-Jones
+/// potential, and with $\lambda = 0$ the particles do not interact. For
+/// intermediate values, the energy stays finite when $r$ goes to 0. The
+/// coupling parameter is 1 when creating the potential, and can be changed
+/// with `PairPotential::set_lambda`.
+///
+/// The tail corrections are approximated by the ones of the Lennard-Jones
+/// potential scaled by $\lambda$, which is valid when the cutoff is large
+/// compared to $\sigma$.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{Potential, PairPotential};
+/// # use lumol_core::energy::SoftCoreLennardJones;
+/// let mut potential = SoftCoreLennardJones::new(/*sigma*/ 2.0, /*epsilon*/ 10.0, /*alpha*/ 0.5);
+/// assert_eq!(potential.energy(2.0), 0.0);
+/// assert_eq!(potential.energy(3.0), -3.203365942785746);
+///
+/// potential.set_lambda(0.5);
+/// assert_eq!(potential.energy(0.0), 240.0);
+///
+/// potential.set_lambda(0.0);
+/// assert_eq!(potential.energy(3.0), 0.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct SoftCoreLennardJones {
+    /// Distance constant of the Lennard-Jones potential
+    sigma: f64,
+    /// Energy constant of the Lennard-Jones potential
+    epsilon: f64,
+    /// Soft-core parameter
+    alpha: f64,
+    /// Coupling parameter
+    lambda: f64,
+}
+
+impl SoftCoreLennardJones {
+    /// Create a new soft-core Lennard-Jones potential with the given `sigma`,
+    /// `epsilon` and soft-core parameter `alpha`, and a coupling parameter of
+    /// 1.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is negative.
+    pub fn new(sigma: f64, epsilon: f64, alpha: f64) -> SoftCoreLennardJones {
+        assert!(alpha >= 0.0, "alpha must be positive in soft-core Lennard-Jones potential");
+        SoftCoreLennardJones {
+            sigma: sigma,
+            epsilon: epsilon,
+            alpha: alpha,
+            lambda: 1.0,
+        }
+    }
+
+    /// Get the current value of the coupling parameter $\lambda$
+    pub fn lambda(&self) -> f64 {
+        self.lambda
+    }
+
+    /// Get the soft-core distance $s = \alpha (1 - \lambda) + (r / \sigma)^6$
+    /// and $(r / \sigma)^6$ at the distance `r`
+    fn soft_distance(&self, r: f64) -> (f64, f64) {
+        let r6 = f64::powi(r / self.sigma, 6);
+        return (self.alpha * (1.0 - self.lambda) + r6, r6);
+    }
+}
+
+impl Potential for SoftCoreLennardJones {
+    fn energy(&self, r: f64) -> f64 {
+        let (s, _) = self.soft_distance(r);
+        4.0 * self.lambda * self.epsilon * (1.0 / (s * s) - 1.0 / s)
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        let (s, r6) = self.soft_distance(r);
+        let s2 = s * s;
+        24.0 * self.lambda * self.epsilon * (2.0 / (s2 * s) - 1.0 / s2) * r6 / r
+    }
+}
+
+impl PairPotential for SoftCoreLennardJones {
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        let lj = LennardJones {
+            sigma: self.sigma,
+            epsilon: self.epsilon,
+        };
+        self.lambda * lj.tail_energy(cutoff)
+    }
+
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        let lj = LennardJones {
+            sigma: self.sigma,
+            epsilon: self.epsilon,
+        };
+        self.lambda * lj.tail_virial(cutoff)
+    }
+
+    fn set_lambda(&mut self, lambda: f64) {
+        assert!(
+            (0.0..=1.0).contains(&lambda),
+            "lambda must be between 0 and 1 in soft-core Lennard-Jones potential"
+        );
+        self.lambda = lambda;
+    }
+
+    fn d_energy_d_lambda(&self, r: f64) -> f64 {
+        let (s, _) = self.soft_distance(r);
+        let s2 = s * s;
+        let coupling = 4.0 * self.epsilon * (1.0 / s2 - 1.0 / s);
+        let softening = 4.0 * self.lambda * self.epsilon * self.alpha * (2.0 / (s2 * s) - 1.0 / s2);
+        return coupling + softening;
+    }
+}
+
 
 #[cfg(test)]
 #[allow(clippy::unreadable_literal)]
 mod tests {
     use super::*;
-    use crate::{PairPotential, Potential};
+    use crate::{PairPotential, Potential, Vector3D};
+    use crate::units;
     use approx::{assert_ulps_eq, assert_relative_eq};
 
     const EPS: f64 = 1e-9;
@@ -647,6 +895,40 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, lj.force(4.0), epsilon = 1e-6);
     }
 
+    #[test]
+    fn lj_12_6_4() {
+        let lj = LennardJones1264 {
+            sigma: 2.0,
+            epsilon: 0.8,
+            c4: 5.0,
+        };
+        assert_eq!(lj.energy(2.0), -5.0 / 16.0);
+        assert_ulps_eq!(lj.force(2.0), 9.6 - 20.0 / 32.0);
+
+        for &r in &[1.8, 2.0, 2.5, 4.0, 10.0] {
+            let e0 = lj.energy(r);
+            let e1 = lj.energy(r + EPS);
+            assert_relative_eq!((e0 - e1) / EPS, lj.force(r), epsilon = 1e-6, max_relative = 1e-6);
+        }
+
+        // The r^-4 term is still noticeable at the cutoff, but the energy
+        // and force stay finite
+        let cutoff = 10.0;
+        let lennard_jones = LennardJones { sigma: 2.0, epsilon: 0.8 };
+        assert!(lj.energy(cutoff).is_finite() && lj.force(cutoff).is_finite());
+        assert!(f64::abs(lj.energy(cutoff)) > 2.0 * f64::abs(lennard_jones.energy(cutoff)));
+
+        // Tail corrections are the Lennard-Jones ones plus the integral of
+        // the r^-4 term, which converges
+        assert_ulps_eq!(lj.tail_energy(cutoff), lennard_jones.tail_energy(cutoff) - 5.0 / cutoff);
+        assert_ulps_eq!(lj.tail_virial(cutoff), lennard_jones.tail_virial(cutoff) - 20.0 / cutoff);
+
+        // Without c4, this is the Lennard-Jones potential
+        let lj = LennardJones1264 { sigma: 2.0, epsilon: 0.8, c4: 0.0 };
+        assert_eq!(lj.energy(3.0), lennard_jones.energy(3.0));
+        assert_eq!(lj.force(3.0), lennard_jones.force(3.0));
+    }
+
     #[test]
     fn harmonic() {
         let harmonic = Harmonic { k: 50.0, x0: 2.0 };
@@ -698,6 +980,74 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, torsion.force(4.0), epsilon = 1e-6);
     }
 
+    #[test]
+    fn torsion_minima() {
+        let torsion = Torsion {
+            k: 5.0,
+            n: 3,
+            delta: 0.0,
+        };
+
+        let npoints = 3600;
+        let dphi = 2.0 * PI / npoints as f64;
+        let energy = |i: usize| torsion.energy((i % npoints) as f64 * dphi);
+
+        let mut minima = Vec::new();
+        for i in 0..npoints {
+            let previous = energy(i + npoints - 1);
+            let current = energy(i);
+            let next = energy(i + 1);
+            if current < previous && current < next {
+                minima.push(i as f64 * dphi);
+            }
+        }
+
+        assert_eq!(minima.len(), 3);
+        assert_relative_eq!(minima[0], PI / 3.0, epsilon = 1e-10);
+        assert_relative_eq!(minima[1], PI, epsilon = 1e-10);
+        assert_relative_eq!(minima[2], 5.0 * PI / 3.0, epsilon = 1e-10);
+        for &phi in &minima {
+            assert_relative_eq!(torsion.energy(phi), 0.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn harmonic_dihedral() {
+        let potential = HarmonicDihedral { k: 5.0, phi0: 1.0 };
+        assert_eq!(potential.energy(1.0), 0.0);
+        assert_eq!(potential.force(1.0), 0.0);
+        assert_relative_eq!(potential.energy(1.5), 0.625, epsilon = 1e-12);
+        assert_relative_eq!(potential.force(1.5), -2.5, epsilon = 1e-12);
+
+        let e0 = potential.energy(-2.0);
+        let e1 = potential.energy(-2.0 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, potential.force(-2.0), epsilon = 1e-6, max_relative = 1e-6);
+
+        // Periodicity
+        assert_relative_eq!(potential.energy(1.5 + 2.0 * PI), potential.energy(1.5), epsilon = 1e-12);
+        assert_relative_eq!(potential.force(1.5 - 2.0 * PI), potential.force(1.5), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn harmonic_dihedral_continuity() {
+        // The equilibrium value is close to the +/- pi boundary of the
+        // dihedral angles
+        let phi0 = units::from(175.0, "deg").unwrap();
+        let potential = HarmonicDihedral { k: 5.0, phi0: phi0 };
+
+        let below = PI - 1e-9;
+        let above = -PI + 1e-9;
+        assert_relative_eq!(potential.energy(below), potential.energy(above), epsilon = 1e-7);
+        assert_relative_eq!(potential.force(below), potential.force(above), epsilon = 1e-7);
+
+        // Crossing the boundary moves further away from the equilibrium, and
+        // the force keeps pushing back towards phi0
+        let delta = units::from(5.0, "deg").unwrap();
+        assert_relative_eq!(potential.force(below), -5.0 * delta, epsilon = 1e-7);
+        let force = potential.force(units::from(-170.0, "deg").unwrap());
+        assert_relative_eq!(force, -5.0 * 3.0 * delta, epsilon = 1e-10);
+    }
+
     #[test]
     fn buckingham() {
         let buckingham = Buckingham {
@@ -740,6 +1090,39 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, born.force(4.0), epsilon = 1e-6);
     }
 
+    #[test]
+    fn born_repulsive_wall() {
+        // Tosi-Fumi parameters for Na-Cl, in eV and A
+        let born = BornMayerHuggins {
+            a: 0.2110,
+            c: 6.99055303,
+            d: -8.6757,
+            sigma: 2.755,
+            rho: 0.317,
+        };
+
+        // The repulsion dominates at short distances
+        let mut previous = born.energy(3.0);
+        for &r in &[2.5, 2.0, 1.5, 1.0] {
+            let energy = born.energy(r);
+            assert!(energy > previous);
+            assert!(born.force(r) > 0.0);
+            previous = energy;
+        }
+        assert!(born.energy(1.0) > 10.0);
+
+        // The dispersion dominates at long distances
+        assert!(born.energy(8.0) < 0.0);
+        assert!(born.force(8.0) < 0.0);
+
+        for &r in &[1.0, 2.0, 2.755, 4.0] {
+            let e0 = born.energy(r);
+            let e1 = born.energy(r + EPS);
+            assert_relative_eq!((e0 - e1) / EPS, born.force(r), epsilon = 1e-5, max_relative = 1e-6);
+            assert_relative_eq!(born.virial(&Vector3D::new(r, 0.0, 0.0))[0][0], r * born.force(r));
+        }
+    }
+
     #[test]
     fn morse() {
         let morse = Morse {
@@ -750,7 +1133,7 @@ mod tests {
 
         // Comparing to externally computed values
         assert_eq!(morse.energy(1.0), 2.703517287822119);
-        assert_eq!(morse.force(1.0), -37.12187076378477);
+        assert_ulps_eq!(morse.force(1.0), 23.96796995753662);
 
         assert_eq!(morse.tail_energy(1.0), 0.0);
         assert_eq!(morse.tail_virial(1.0), 0.0);
@@ -760,6 +1143,28 @@ mod tests {
         assert_relative_eq!((e0 - e1) / EPS, morse.force(1.3), epsilon = 1e-6);
     }
 
+    #[test]
+    fn morse_bond() {
+        let morse = Morse {
+            a: 2.0,
+            x0: 1.3,
+            depth: 4.0,
+        };
+
+        for &r in &[0.9, 1.2, 1.3, 1.7, 2.5, 4.0] {
+            let e0 = morse.energy(r);
+            let e1 = morse.energy(r + EPS);
+            assert_relative_eq!((e0 - e1) / EPS, morse.force(r), epsilon = 1e-5, max_relative = 1e-5);
+        }
+
+        // The minimum of the energy is at the equilibrium distance, and the
+        // dissociation energy of the bond is the well depth
+        assert_eq!(morse.energy(1.3), 0.0);
+        assert_eq!(morse.force(1.3), 0.0);
+        assert_relative_eq!(morse.energy(30.0) - morse.energy(1.3), 4.0, epsilon = 1e-12);
+        assert!(morse.force(30.0).abs() < 1e-12);
+    }
+
     #[test]
     fn gaussian() {
         let gaussian = Gaussian::new(8.0, 2.0);
@@ -816,4 +1221,47 @@ mod tests {
         assert_eq!(mie.tail_energy(2.0), 0.0);
         assert_eq!(mie.tail_virial(2.0), 0.0);
     }
+
+    #[test]
+    fn soft_core_lj() {
+        let lj = LennardJones {
+            epsilon: 0.8,
+            sigma: 2.0,
+        };
+        let mut soft = SoftCoreLennardJones::new(2.0, 0.8, 0.5);
+        assert_eq!(soft.lambda(), 1.0);
+        for &r in &[1.5, 2.0, 2.5, 4.0] {
+            assert_relative_eq!(soft.energy(r), lj.energy(r), epsilon = 1e-12);
+            assert_relative_eq!(soft.force(r), lj.force(r), epsilon = 1e-12);
+        }
+        assert_eq!(soft.tail_energy(14.42), lj.tail_energy(14.42));
+        assert_eq!(soft.tail_virial(14.42), lj.tail_virial(14.42));
+
+        soft.set_lambda(0.0);
+        assert_eq!(soft.energy(0.0), 0.0);
+        assert_eq!(soft.energy(2.5), 0.0);
+        assert_eq!(soft.force(2.5), 0.0);
+        assert_eq!(soft.tail_energy(14.42), 0.0);
+
+        soft.set_lambda(0.3);
+        assert!(soft.energy(0.0).is_finite());
+
+        let e0 = soft.energy(2.5);
+        let e1 = soft.energy(2.5 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, soft.force(2.5), epsilon = 1e-6);
+
+        for &r in &[0.5, 2.0, 3.0] {
+            let mut forward = soft;
+            forward.set_lambda(0.3 + EPS);
+            let de = (forward.energy(r) - soft.energy(r)) / EPS;
+            assert_relative_eq!(de, soft.d_energy_d_lambda(r), max_relative = 1e-6);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "lambda must be between 0 and 1 in soft-core Lennard-Jones potential")]
+    fn soft_core_lj_lambda() {
+        let mut soft = SoftCoreLennardJones::new(2.0, 0.8, 0.5);
+        soft.set_lambda(1.2);
+    }
 }
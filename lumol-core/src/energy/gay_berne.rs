@@ -0,0 +1,594 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Gay-Berne potential between uniaxial anisotropic particles
+use crate::{Configuration, UnitCell};
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Gay-Berne potential between two uniaxial ellipsoidal particles.
+///
+/// $$ V(\vec r, \hat u_i, \hat u_j) = 4 \epsilon(\hat r, \hat u_i, \hat u_j)
+///    \left[ \left(\frac{\sigma_0}{r - \sigma(\hat r, \hat u_i, \hat u_j) +
+///    \sigma_0}\right)^{12} - \left(\frac{\sigma_0}{r - \sigma(\hat r, \hat
+///    u_i, \hat u_j) + \sigma_0}\right)^6 \right] $$
+///
+/// where $\hat u_i$ and $\hat u_j$ are the unit vectors along the symmetry
+/// axis of the particles, $\vec r$ the vector between the particles, and
+///
+/// $$ \sigma = \sigma_0 \left[1 - \frac{\chi}{2} \left(\frac{(\hat r \cdot
+///    \hat u_i + \hat r \cdot \hat u_j)^2}{1 + \chi \hat u_i \cdot \hat u_j} +
+///    \frac{(\hat r \cdot \hat u_i - \hat r \cdot \hat u_j)^2}{1 - \chi \hat
+///    u_i \cdot \hat u_j}\right)\right]^{-1/2} $$
+///
+/// $$ \epsilon = \epsilon_0 \left[1 - \chi^2 (\hat u_i \cdot \hat
+///    u_j)^2\right]^{-\nu/2} \left[1 - \frac{\chi'}{2} \left(\frac{(\hat r
+///    \cdot \hat u_i + \hat r \cdot \hat u_j)^2}{1 + \chi' \hat u_i \cdot \hat
+///    u_j} + \frac{(\hat r \cdot \hat u_i - \hat r \cdot \hat u_j)^2}{1 - \chi'
+///    \hat u_i \cdot \hat u_j}\right)\right]^\mu $$
+///
+/// The anisotropy parameters are $\chi = (\kappa^2 - 1) / (\kappa^2 + 1)$ and
+/// $\chi' = (\kappa'^{1/\mu} - 1) / (\kappa'^{1/\mu} + 1)$, where $\kappa$ is
+/// the ratio of the end-to-end and side-by-side contact distances, and
+/// $\kappa'$ the ratio of the side-by-side and end-to-end well depths.
+///
+/// This potential depends on the orientation of the particles, and can not
+/// be used as a [`PairPotential`]. It provides the energy, the force and the
+/// torques for a given pair of orientations, and is used in simulations
+/// through a [`GayBerneInteraction`].
+///
+/// [`PairPotential`]: trait.PairPotential.html
+/// [`GayBerneInteraction`]: struct.GayBerneInteraction.html
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::GayBerne;
+/// # use lumol_core::Vector3D;
+/// let potential = GayBerne::new(
+///     /*sigma_0*/ 1.0, /*epsilon_0*/ 1.0, /*kappa*/ 3.0, /*kappa'*/ 5.0,
+///     /*mu*/ 2.0, /*nu*/ 1.0
+/// );
+///
+/// let axis = Vector3D::new(0.0, 0.0, 1.0);
+/// // two aligned particles side by side, at contact distance
+/// let r = Vector3D::new(1.0, 0.0, 0.0);
+/// assert_eq!(potential.energy(&r, &axis, &axis), 0.0);
+/// // two aligned particles end to end, at contact distance
+/// let r = Vector3D::new(0.0, 0.0, 3.0);
+/// assert!(f64::abs(potential.energy(&r, &axis, &axis)) < 1e-12);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct GayBerne {
+    /// Side-by-side contact distance
+    sigma_0: f64,
+    /// Energetic constant
+    epsilon_0: f64,
+    /// Exponent of the orientation dependent part of the well depth
+    mu: f64,
+    /// Exponent of the orientation dependent part of the well depth
+    nu: f64,
+    /// Shape anisotropy parameter, computed from kappa
+    chi: f64,
+    /// Energetic anisotropy parameter, computed from kappa' and mu
+    chi_prime: f64,
+}
+
+/// Derivatives of the Gay-Berne energy with respect to the distance $r$,
+/// $a = \hat r \cdot \hat u_i$, $b = \hat r \cdot \hat u_j$ and $c = \hat u_i
+/// \cdot \hat u_j$.
+struct Derivatives {
+    dr: f64,
+    da: f64,
+    db: f64,
+    dc: f64,
+}
+
+/// Compute the anisotropic function shared by the range and strength
+/// parameters, together with its derivatives with respect to `a`, `b` and
+/// `c`.
+///
+/// $$ g(x) = 1 - \frac{x}{2} \left(\frac{(a + b)^2}{1 + x c} + \frac{(a -
+///    b)^2}{1 - x c}\right) $$
+fn anisotropy(x: f64, a: f64, b: f64, c: f64) -> (f64, f64, f64, f64) {
+    let plus = 1.0 + x * c;
+    let minus = 1.0 - x * c;
+    let sum = a + b;
+    let difference = a - b;
+
+    let g = 1.0 - 0.5 * x * (sum * sum / plus + difference * difference / minus);
+    let dg_da = -x * (sum / plus + difference / minus);
+    let dg_db = -x * (sum / plus - difference / minus);
+    let dg_dc = 0.5 * x * x * (sum * sum / (plus * plus) - difference * difference / (minus * minus));
+    return (g, dg_da, dg_db, dg_dc);
+}
+
+impl GayBerne {
+    /// Create a new Gay-Berne potential with the side-by-side contact
+    /// distance `sigma_0`, the energetic constant `epsilon_0`, the ratio
+    /// `kappa` of the end-to-end and side-by-side contact distances, the ratio
+    /// `kappa_prime` of the side-by-side and end-to-end well depths, and the
+    /// `mu` and `nu` exponents.
+    ///
+    /// # Panics
+    ///
+    /// If `sigma_0`, `kappa`, `kappa_prime` or `mu` is not positive.
+    pub fn new(sigma_0: f64, epsilon_0: f64, kappa: f64, kappa_prime: f64, mu: f64, nu: f64) -> GayBerne {
+        assert!(sigma_0 > 0.0, "sigma_0 must be positive in Gay-Berne potential");
+        assert!(kappa > 0.0 && kappa_prime > 0.0, "kappa and kappa' must be positive in Gay-Berne potential");
+        assert!(mu > 0.0, "mu must be positive in Gay-Berne potential");
+        let chi = (kappa * kappa - 1.0) / (kappa * kappa + 1.0);
+        let kappa_prime = f64::powf(kappa_prime, 1.0 / mu);
+        let chi_prime = (kappa_prime - 1.0) / (kappa_prime + 1.0);
+        GayBerne {
+            sigma_0: sigma_0,
+            epsilon_0: epsilon_0,
+            mu: mu,
+            nu: nu,
+            chi: chi,
+            chi_prime: chi_prime,
+        }
+    }
+
+    /// Get the energy of two particles with orientations `u_i` and `u_j`,
+    /// separated by the vector `r`. The orientations must be unit vectors.
+    pub fn energy(&self, r: &Vector3D, u_i: &Vector3D, u_j: &Vector3D) -> f64 {
+        let distance = r.norm();
+        let rn = r / distance;
+        let (a, b, c) = (rn * u_i, rn * u_j, u_i * u_j);
+
+        let (h, _, _, _) = anisotropy(self.chi, a, b, c);
+        let sigma = self.sigma_0 / f64::sqrt(h);
+
+        let (epsilon_2, _, _, _) = anisotropy(self.chi_prime, a, b, c);
+        let epsilon_1 = 1.0 / f64::sqrt(1.0 - self.chi * self.chi * c * c);
+        let epsilon = self.epsilon_0 * f64::powf(epsilon_1, self.nu) * f64::powf(epsilon_2, self.mu);
+
+        let rho = self.sigma_0 / (distance - sigma + self.sigma_0);
+        let rho6 = rho.powi(6);
+        return 4.0 * epsilon * (rho6 * rho6 - rho6);
+    }
+
+    /// Compute the derivatives of the energy with respect to the distance and
+    /// the scalar products between the separation vector and the
+    /// orientations.
+    fn derivatives(&self, distance: f64, a: f64, b: f64, c: f64) -> Derivatives {
+        let (h, dh_da, dh_db, dh_dc) = anisotropy(self.chi, a, b, c);
+        let sigma = self.sigma_0 / f64::sqrt(h);
+        // d sigma / d h
+        let dsigma = -0.5 * sigma / h;
+
+        let (epsilon_2, de2_da, de2_db, de2_dc) = anisotropy(self.chi_prime, a, b, c);
+        let epsilon_1 = 1.0 / f64::sqrt(1.0 - self.chi * self.chi * c * c);
+        let de1_dc = self.chi * self.chi * c * epsilon_1 * epsilon_1 * epsilon_1;
+        let epsilon = self.epsilon_0 * f64::powf(epsilon_1, self.nu) * f64::powf(epsilon_2, self.mu);
+
+        let rho = self.sigma_0 / (distance - sigma + self.sigma_0);
+        let rho6 = rho.powi(6);
+        let lj = 4.0 * (rho6 * rho6 - rho6);
+        // derivative of the Lennard-Jones part with respect to the distance,
+        // the derivative with respect to sigma is the opposite
+        let dlj = -24.0 * (2.0 * rho6 * rho6 - rho6) * rho / self.sigma_0;
+
+        let depsilon_da = epsilon * self.mu * de2_da / epsilon_2;
+        let depsilon_db = epsilon * self.mu * de2_db / epsilon_2;
+        let depsilon_dc = epsilon * (self.nu * de1_dc / epsilon_1 + self.mu * de2_dc / epsilon_2);
+
+        Derivatives {
+            dr: epsilon * dlj,
+            da: depsilon_da * lj - epsilon * dlj * dsigma * dh_da,
+            db: depsilon_db * lj - epsilon * dlj * dsigma * dh_db,
+            dc: depsilon_dc * lj - epsilon * dlj * dsigma * dh_dc,
+        }
+    }
+
+    /// Get the force acting on the particle `j` and the torques acting on
+    /// the particles `i` and `j`, in this order, for two particles with
+    /// orientations `u_i` and `u_j` separated by the vector `r` going from `i`
+    /// to `j`. The force acting on the particle `i` is the opposite of the
+    /// force acting on the particle `j`. The orientations must be unit
+    /// vectors.
+    pub fn force_and_torques(&self, r: &Vector3D, u_i: &Vector3D, u_j: &Vector3D) -> (Vector3D, Vector3D, Vector3D) {
+        let distance = r.norm();
+        let rn = r / distance;
+        let (a, b, c) = (rn * u_i, rn * u_j, u_i * u_j);
+        let derivatives = self.derivatives(distance, a, b, c);
+
+        let gradient = derivatives.dr * rn
+                     + derivatives.da / distance * (u_i - a * rn)
+                     + derivatives.db / distance * (u_j - b * rn);
+        let gradient_i = derivatives.da * rn + derivatives.dc * u_j;
+        let gradient_j = derivatives.db * rn + derivatives.dc * u_i;
+
+        let force = -gradient;
+        let torque_i = -(u_i ^ gradient_i);
+        let torque_j = -(u_j ^ gradient_j);
+        return (force, torque_i, torque_j);
+    }
+}
+
+/// Gay-Berne interactions between all the pairs of particles with a given
+/// name, using the [`GayBerne`] potential and the orientations of the
+/// particles (see `Particle::orientation`).
+///
+/// This is a global potential, to be added to a system with
+/// `System::add_global_potential`. It contributes to the energy, the forces,
+/// the virial and the torques (see `System::torques`) of the system. Only
+/// pairs of particles in different molecules and closer than the cutoff
+/// distance interact, and the energy is not shifted at the cutoff. The
+/// orientations of the particles are not changed by the simulation
+/// propagators.
+///
+/// [`GayBerne`]: struct.GayBerne.html
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::{GayBerne, GayBerneInteraction};
+/// # use lumol_core::sys::{System, Particle, Molecule, UnitCell};
+/// # use lumol_core::types::Vector3D;
+/// let mut system = System::with_cell(UnitCell::cubic(20.0));
+/// for &x in &[0.0, 1.0] {
+///     let mut particle = Particle::with_position("GB", Vector3D::new(x, 0.0, 0.0));
+///     particle.orientation = Vector3D::new(0.0, 0.0, 1.0);
+///     system.add_molecule(Molecule::new(particle));
+/// }
+///
+/// let potential = GayBerne::new(1.0, 1.0, 3.0, 5.0, 2.0, 1.0);
+/// system.add_global_potential(Box::new(GayBerneInteraction::new("GB", potential, 5.0)));
+/// // two aligned particles side by side, at contact distance
+/// assert!(f64::abs(system.potential_energy()) < 1e-12);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GayBerneInteraction {
+    /// Name of the interacting particles
+    name: String,
+    /// The potential to use
+    potential: GayBerne,
+    /// Cutoff distance of the interactions
+    cutoff: f64,
+}
+
+impl GayBerneInteraction {
+    /// Create a new `GayBerneInteraction` between all the particles called
+    /// `name`, using the given `potential` and `cutoff` distance.
+    ///
+    /// # Panics
+    ///
+    /// If `cutoff` is not positive.
+    pub fn new<S: Into<String>>(name: S, potential: GayBerne, cutoff: f64) -> GayBerneInteraction {
+        assert!(cutoff > 0.0, "the cutoff must be positive in Gay-Berne interactions");
+        GayBerneInteraction {
+            name: name.into(),
+            potential: potential,
+            cutoff: cutoff,
+        }
+    }
+
+    /// Get the indexes of the interacting particles in the `configuration`
+    fn particles(&self, configuration: &Configuration) -> Vec<usize> {
+        let names = configuration.particles().name;
+        (0..configuration.size()).filter(|&i| names[i] == self.name).collect()
+    }
+
+    /// Call `function` with the indexes `i` and `j` and the vector going
+    /// from `i` to `j` for all the interacting pairs with the given `cell`
+    /// and `positions`.
+    fn for_each_pair<F>(&self, configuration: &Configuration, cell: &UnitCell, positions: &[Vector3D], mut function: F)
+    where
+        F: FnMut(usize, usize, Vector3D),
+    {
+        let particles = self.particles(configuration);
+        for (a, &i) in particles.iter().enumerate() {
+            for &j in &particles[(a + 1)..] {
+                if configuration.are_in_same_molecule(i, j) {
+                    continue;
+                }
+                let mut r = positions[j] - positions[i];
+                cell.vector_image(&mut r);
+                if r.norm() < self.cutoff {
+                    function(i, j, r);
+                }
+            }
+        }
+    }
+
+    /// Get the energy with the given `cell` and `positions`
+    fn energy_at(&self, configuration: &Configuration, cell: &UnitCell, positions: &[Vector3D]) -> f64 {
+        let orientations = configuration.particles().orientation;
+        let mut energy = 0.0;
+        self.for_each_pair(configuration, cell, positions, |i, j, r| {
+            energy += self.potential.energy(&r, &orientations[i], &orientations[j]);
+        });
+        return energy;
+    }
+}
+
+impl GlobalCache for GayBerneInteraction {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let old_positions = configuration.particles().position;
+        let orientations = configuration.particles().orientation;
+        let names = configuration.particles().name;
+        let molecule = configuration.molecule(molecule_id);
+        let others = self.particles(configuration).into_iter()
+            .filter(|&j| !molecule.contains(j))
+            .collect::<Vec<_>>();
+
+        let cell = &configuration.cell;
+        let mut cost = 0.0;
+        for (i, part_i) in molecule.indexes().enumerate() {
+            if names[part_i] != self.name {
+                continue;
+            }
+            for &part_j in &others {
+                for &(position, sign) in &[(new_positions[i], 1.0), (old_positions[part_i], -1.0)] {
+                    let mut r = old_positions[part_j] - position;
+                    cell.vector_image(&mut r);
+                    if r.norm() < self.cutoff {
+                        cost += sign * self.potential.energy(&r, &orientations[part_i], &orientations[part_j]);
+                    }
+                }
+            }
+        }
+        return cost;
+    }
+
+    fn move_volume_cost(
+        &self,
+        configuration: &Configuration,
+        new_cell: &UnitCell,
+        new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        let old_positions = configuration.particles().position;
+        let new = self.energy_at(configuration, new_cell, new_positions);
+        return Some(new - self.energy_at(configuration, &configuration.cell, old_positions));
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for GayBerneInteraction {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let positions = configuration.particles().position;
+        return self.energy_at(configuration, &configuration.cell, positions);
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        let positions = configuration.particles().position;
+        let orientations = configuration.particles().orientation;
+        self.for_each_pair(configuration, &configuration.cell, positions, |i, j, r| {
+            let (force, _, _) = self.potential.force_and_torques(&r, &orientations[i], &orientations[j]);
+            forces[i] -= force;
+            forces[j] += force;
+        });
+    }
+
+    fn torques(&self, configuration: &Configuration, torques: &mut [Vector3D]) {
+        assert_eq!(torques.len(), configuration.size());
+        let positions = configuration.particles().position;
+        let orientations = configuration.particles().orientation;
+        self.for_each_pair(configuration, &configuration.cell, positions, |i, j, r| {
+            let (_, torque_i, torque_j) = self.potential.force_and_torques(&r, &orientations[i], &orientations[j]);
+            torques[i] += torque_i;
+            torques[j] += torque_j;
+        });
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let positions = configuration.particles().position;
+        let orientations = configuration.particles().orientation;
+        let mut virial = Matrix3::zero();
+        self.for_each_pair(configuration, &configuration.cell, positions, |i, j, r| {
+            let (force, _, _) = self.potential.force_and_torques(&r, &orientations[i], &orientations[j]);
+            virial += force.tensorial(&r);
+        });
+        return virial;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unreadable_literal)]
+mod tests {
+    use super::*;
+    use crate::{Molecule, Particle, System};
+    use approx::assert_relative_eq;
+
+    const EPS: f64 = 1e-6;
+
+    fn gay_berne() -> GayBerne {
+        GayBerne::new(3.0, 2.5, 3.0, 5.0, 2.0, 1.0)
+    }
+
+    #[test]
+    fn aligned_ellipsoids() {
+        let potential = gay_berne();
+        let (sigma_0, epsilon_0) = (3.0, 2.5);
+        let (kappa, kappa_prime) = (3.0, 5.0);
+        let chi: f64 = (kappa * kappa - 1.0) / (kappa * kappa + 1.0);
+        let axis = Vector3D::new(0.0, 0.0, 1.0);
+
+        // side by side, the minimum is at the same distance as Lennard-Jones
+        let epsilon_ss = epsilon_0 / f64::sqrt(1.0 - chi * chi);
+        let r_min = f64::powf(2.0, 1.0 / 6.0) * sigma_0;
+        let r = Vector3D::new(r_min, 0.0, 0.0);
+        assert_relative_eq!(potential.energy(&r, &axis, &axis), -epsilon_ss, max_relative = 1e-12);
+        let r = Vector3D::new(0.0, sigma_0, 0.0);
+        assert_relative_eq!(potential.energy(&r, &axis, &axis), 0.0, epsilon = 1e-12);
+
+        // end to end, the contact distance is kappa times larger and the well
+        // depth kappa' times smaller
+        let r_min = kappa * sigma_0 - sigma_0 + f64::powf(2.0, 1.0 / 6.0) * sigma_0;
+        let r = Vector3D::new(0.0, 0.0, r_min);
+        assert_relative_eq!(potential.energy(&r, &axis, &axis), -epsilon_ss / kappa_prime, max_relative = 1e-12);
+        let r = Vector3D::new(0.0, 0.0, -kappa * sigma_0);
+        assert_relative_eq!(potential.energy(&r, &axis, &axis), 0.0, epsilon = 1e-12);
+
+        // The potential does not depend on the sign of the orientations
+        let r = Vector3D::new(0.0, 0.0, r_min);
+        assert_relative_eq!(
+            potential.energy(&r, &axis, &-axis),
+            potential.energy(&r, &axis, &axis),
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn forces_and_torques() {
+        let potential = gay_berne();
+        let r = Vector3D::new(1.5, 2.8, 3.4);
+        let u_i = Vector3D::new(0.3, -0.2, 1.0).normalized();
+        let u_j = Vector3D::new(1.0, 0.4, 0.2).normalized();
+        let (force, torque_i, torque_j) = potential.force_and_torques(&r, &u_i, &u_j);
+
+        for axis in 0..3 {
+            let mut delta = Vector3D::zero();
+            delta[axis] = EPS;
+
+            let forward = potential.energy(&(r + delta), &u_i, &u_j);
+            let backward = potential.energy(&(r - delta), &u_i, &u_j);
+            assert_relative_eq!(-(forward - backward) / (2.0 * EPS), force[axis], epsilon = 1e-6);
+
+            // rotating the particles around `delta`
+            let rotate = |u: &Vector3D, angle: f64| (u + angle * (delta.normalized() ^ u)).normalized();
+            let forward = potential.energy(&r, &rotate(&u_i, EPS), &u_j);
+            let backward = potential.energy(&r, &rotate(&u_i, -EPS), &u_j);
+            assert_relative_eq!(-(forward - backward) / (2.0 * EPS), torque_i[axis], epsilon = 1e-6);
+
+            let forward = potential.energy(&r, &u_i, &rotate(&u_j, EPS));
+            let backward = potential.energy(&r, &u_i, &rotate(&u_j, -EPS));
+            assert_relative_eq!(-(forward - backward) / (2.0 * EPS), torque_j[axis], epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "mu must be positive in Gay-Berne potential")]
+    fn negative_mu() {
+        let _ = GayBerne::new(3.0, 2.5, 3.0, 5.0, -1.0, 1.0);
+    }
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        let particles = [
+            (Vector3D::new(0.0, 0.0, 0.0), Vector3D::new(0.3, -0.2, 1.0)),
+            (Vector3D::new(2.0, 3.6, 4.4), Vector3D::new(1.0, 0.4, 0.2)),
+            (Vector3D::new(16.0, 1.0, 3.0), Vector3D::new(0.0, 1.0, 0.5)),
+        ];
+        for &(position, orientation) in &particles {
+            let mut particle = Particle::with_position("GB", position);
+            particle.orientation = orientation.normalized();
+            system.add_molecule(Molecule::new(particle));
+        }
+        // Other particles do not interact with the Gay-Berne potential
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.5, 0.5, 0.5))));
+        system.add_global_potential(Box::new(GayBerneInteraction::new("GB", gay_berne(), 9.0)));
+        system
+    }
+
+    #[test]
+    fn interaction() {
+        let system = testing_system();
+        let potential = gay_berne();
+        let orientations = system.particles().orientation;
+
+        let pairs = [
+            (0, 1, Vector3D::new(2.0, 3.6, 4.4)),
+            (0, 2, Vector3D::new(-4.0, 1.0, 3.0)),
+            (1, 2, Vector3D::new(-6.0, -2.6, -1.4)),
+        ];
+        let mut energy = 0.0;
+        let mut forces = vec![Vector3D::zero(); 4];
+        let mut torques = vec![Vector3D::zero(); 4];
+        for &(i, j, r) in &pairs {
+            energy += potential.energy(&r, &orientations[i], &orientations[j]);
+            let (force, torque_i, torque_j) = potential.force_and_torques(&r, &orientations[i], &orientations[j]);
+            forces[i] -= force;
+            forces[j] += force;
+            torques[i] += torque_i;
+            torques[j] += torque_j;
+        }
+
+        assert_relative_eq!(system.potential_energy(), energy, max_relative = 1e-12);
+        for (actual, expected) in system.forces().iter().zip(&forces) {
+            assert_relative_eq!(actual, expected, max_relative = 1e-12);
+        }
+        for (actual, expected) in system.torques().iter().zip(&torques) {
+            assert_relative_eq!(actual, expected, max_relative = 1e-12);
+        }
+        assert_eq!(system.forces()[3], Vector3D::zero());
+
+        // The forces are the derivatives of the energy
+        let forces = system.forces();
+        for i in 0..3 {
+            for axis in 0..3 {
+                let mut forward = system.clone();
+                forward.particles_mut().position[i][axis] += EPS;
+                let mut backward = system.clone();
+                backward.particles_mut().position[i][axis] -= EPS;
+                let finite = -(forward.potential_energy() - backward.potential_energy()) / (2.0 * EPS);
+                assert_relative_eq!(finite, forces[i][axis], epsilon = 1e-6, max_relative = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn intramolecular() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        let mut molecule = Molecule::new(Particle::with_position("GB", Vector3D::new(0.0, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("GB", Vector3D::new(2.0, 0.0, 0.0)));
+        system.add_molecule(molecule);
+        system.add_global_potential(Box::new(GayBerneInteraction::new("GB", gay_berne(), 9.0)));
+        assert_eq!(system.potential_energy(), 0.0);
+    }
+
+    #[test]
+    fn virial() {
+        let system = testing_system();
+        let interaction = &system.global_potentials()[0];
+
+        // The virial is the derivative of the energy when scaling the cell
+        let virial = interaction.atomic_virial(&system);
+        let energy = |factor: f64| {
+            let mut scaled = system.clone();
+            scaled.cell = UnitCell::cubic(20.0 * factor);
+            for position in scaled.particles_mut().position {
+                *position *= factor;
+            }
+            interaction.energy(&scaled)
+        };
+        let finite = -(energy(1.0 + EPS) - energy(1.0 - EPS)) / (2.0 * EPS);
+        assert_relative_eq!(virial.trace(), finite, epsilon = 1e-6, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn monte_carlo_costs() {
+        let system = testing_system();
+        let interaction = &system.global_potentials()[0];
+
+        let new_positions = [Vector3D::new(2.0, 3.0, 1.0)];
+        let mut moved = system.clone();
+        moved.particles_mut().position[1] = new_positions[0];
+        let cost = interaction.move_molecule_cost(&system, 1, &new_positions);
+        assert_relative_eq!(cost, interaction.energy(&moved) - interaction.energy(&system), max_relative = 1e-12);
+
+        let new_positions = system.particles().position.iter().map(|&r| 1.1 * r).collect::<Vec<_>>();
+        let cell = UnitCell::cubic(22.0);
+        let mut scaled = system.clone();
+        scaled.cell = cell;
+        scaled.particles_mut().position.copy_from_slice(&new_positions);
+        let cost = interaction.move_volume_cost(&system, &cell, &new_positions).unwrap();
+        assert_relative_eq!(cost, interaction.energy(&scaled) - interaction.energy(&system), max_relative = 1e-12);
+    }
+}
@@ -0,0 +1,224 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use crate::{MoleculeHash, PairRestriction};
+use crate::{Configuration, UnitCell};
+use crate::{Matrix3, Vector3D};
+
+use super::{CoulombicPotential, GlobalCache, GlobalPotential, TholeDamping};
+use super::move_volume_cost;
+
+/// Combination of two coulombic potentials, for example a short-range
+/// real-space term and a long-range reciprocal space term in a custom
+/// Ewald-like splitting of the electrostatic interactions.
+///
+/// A system can only use a single coulombic potential, set with
+/// [`System::set_coulomb_potential`]. This potential can be used to sum the
+/// contributions of two different potentials to the energy, forces and
+/// virial. The pair restrictions are used by both potentials, while the Thole
+/// damping only applies to the short-range potential.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::sys::{Particle, Molecule, UnitCell, System};
+/// # use lumol_core::energy::{CombinedCoulomb, GlobalPotential, Wolf};
+/// # use lumol_core::types::Vector3D;
+/// let mut system = System::with_cell(UnitCell::cubic(30.0));
+///
+/// let mut na = Particle::new("Na");
+/// na.charge = 1.0;
+/// system.add_molecule(Molecule::new(na));
+///
+/// let mut cl = Particle::new("Cl");
+/// cl.charge = -1.0;
+/// cl.position = Vector3D::new(2.0, 0.0, 0.0);
+/// system.add_molecule(Molecule::new(cl));
+///
+/// let short = Wolf::new(8.0);
+/// let long = Wolf::new(12.0);
+/// let expected = short.energy(&system) + long.energy(&system);
+///
+/// let combined = CombinedCoulomb::new(Box::new(short), Box::new(long));
+/// system.set_coulomb_potential(Box::new(combined)).unwrap();
+/// assert!(f64::abs(system.potential_energy() - expected) < 1e-12);
+/// ```
+///
+/// [`System::set_coulomb_potential`]: ../sys/struct.System.html#method.set_coulomb_potential
+#[derive(Clone)]
+pub struct CombinedCoulomb {
+    /// Short-range part of the potential
+    short_range: Box<dyn CoulombicPotential>,
+    /// Long-range part of the potential
+    long_range: Box<dyn CoulombicPotential>,
+}
+
+impl CombinedCoulomb {
+    /// Create a new `CombinedCoulomb` potential, summing the contributions
+    /// of the `short_range` and `long_range` potentials.
+    pub fn new(
+        short_range: Box<dyn CoulombicPotential>,
+        long_range: Box<dyn CoulombicPotential>,
+    ) -> CombinedCoulomb {
+        CombinedCoulomb {
+            short_range: short_range,
+            long_range: long_range,
+        }
+    }
+
+    /// Get the short-range part of this potential
+    pub fn short_range(&self) -> &dyn CoulombicPotential {
+        &*self.short_range
+    }
+
+    /// Get the long-range part of this potential
+    pub fn long_range(&self) -> &dyn CoulombicPotential {
+        &*self.long_range
+    }
+}
+
+impl GlobalPotential for CombinedCoulomb {
+    fn cutoff(&self) -> Option<f64> {
+        match (self.short_range.cutoff(), self.long_range.cutoff()) {
+            (Some(short), Some(long)) => Some(f64::max(short, long)),
+            (Some(cutoff), None) | (None, Some(cutoff)) => Some(cutoff),
+            (None, None) => None,
+        }
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        self.short_range.energy(configuration) + self.long_range.energy(configuration)
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        self.short_range.forces(configuration, forces);
+        self.long_range.forces(configuration, forces);
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        self.short_range.atomic_virial(configuration) + self.long_range.atomic_virial(configuration)
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        self.short_range.molecular_virial(configuration) + self.long_range.molecular_virial(configuration)
+    }
+}
+
+impl GlobalCache for CombinedCoulomb {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let short = self.short_range.move_molecule_cost(configuration, molecule_id, new_positions);
+        let long = self.long_range.move_molecule_cost(configuration, molecule_id, new_positions);
+        return short + long;
+    }
+
+    fn move_volume_cost(
+        &self,
+        configuration: &Configuration,
+        new_cell: &UnitCell,
+        new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        let short = move_volume_cost(&*self.short_range, configuration, new_cell, new_positions);
+        let long = move_volume_cost(&*self.long_range, configuration, new_cell, new_positions);
+        return Some(short + long);
+    }
+
+    fn update(&self) {
+        self.short_range.update();
+        self.long_range.update();
+    }
+}
+
+impl CoulombicPotential for CombinedCoulomb {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.short_range.set_restriction(restriction);
+        self.long_range.set_restriction(restriction);
+    }
+
+    fn set_molecule_restriction(&mut self, molecule: MoleculeHash, restriction: PairRestriction) {
+        self.short_range.set_molecule_restriction(molecule, restriction);
+        self.long_range.set_molecule_restriction(molecule, restriction);
+    }
+
+    fn set_thole_damping(&mut self, damping: Option<TholeDamping>) {
+        self.short_range.set_thole_damping(damping);
+    }
+
+    fn requires_periodicity(&self) -> bool {
+        self.short_range.requires_periodicity() || self.long_range.requires_periodicity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ewald, SharedEwald, System, Wolf};
+    use crate::utils::system_from_xyz;
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "4
+            cell: 20.0
+            Cl 0.0 0.0 0.0
+            Na 1.5 0.0 0.0
+            Cl 5.0 3.0 0.0
+            Na 4.0 6.0 2.0
+            ",
+        );
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        system.particles_mut().charge[2] = -1.0;
+        system.particles_mut().charge[3] = 1.0;
+        return system;
+    }
+
+    fn parts() -> (Wolf, SharedEwald) {
+        (Wolf::new(8.0), SharedEwald::new(Ewald::new(9.0, 7, None)))
+    }
+
+    #[test]
+    fn energy() {
+        let system = testing_system();
+        let (short, long) = parts();
+        let expected = short.energy(&system) + long.energy(&system);
+
+        let combined = CombinedCoulomb::new(Box::new(short), Box::new(long));
+        assert_relative_eq!(combined.energy(&system), expected, max_relative = 1e-12);
+        assert_eq!(combined.cutoff(), Some(9.0));
+        assert!(combined.requires_periodicity());
+    }
+
+    #[test]
+    fn forces_and_virial() {
+        let system = testing_system();
+        let (short, long) = parts();
+
+        let mut expected = vec![Vector3D::zero(); system.size()];
+        short.forces(&system, &mut expected);
+        long.forces(&system, &mut expected);
+        let expected_virial = short.atomic_virial(&system) + long.atomic_virial(&system);
+
+        let combined = CombinedCoulomb::new(Box::new(short), Box::new(long));
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        combined.forces(&system, &mut forces);
+        for (force, expected) in forces.iter().zip(&expected) {
+            assert_relative_eq!(*force, *expected, epsilon = 1e-12);
+        }
+        assert_relative_eq!(combined.atomic_virial(&system), expected_virial, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn system() {
+        let mut system = testing_system();
+        let (short, long) = parts();
+        let expected = short.energy(&system) + long.energy(&system);
+
+        system.set_coulomb_potential(Box::new(CombinedCoulomb::new(Box::new(short), Box::new(long)))).unwrap();
+        assert_relative_eq!(system.potential_energy(), expected, max_relative = 1e-12);
+    }
+}
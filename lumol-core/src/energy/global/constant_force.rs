@@ -0,0 +1,244 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use crate::{Configuration, Permutation, UnitCell};
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Constant external force acting on a selection of particles.
+///
+/// This potential applies the same `force` to all the selected particles,
+/// which is useful for steered (or pulling) molecular dynamics. The
+/// corresponding potential energy is $U = - \sum_i \vec F \cdot \vec r_i$,
+/// where the sum runs over the selected particles.
+///
+/// The energy depends on the absolute positions of the particles, and is not
+/// invariant by translation. It is only continuous if the particles are not
+/// wrapped inside the unit cell during the simulation. The virial of an
+/// external force depends on the choice of the origin, so this potential does
+/// not contribute to the virial, stress and pressure of the system, and a
+/// warning is emitted when computing them.
+///
+/// The indexes of the selected particles must be updated with
+/// [`apply_permutations`](#method.apply_permutations) if the particles are
+/// moved in the system, for example when adding bonds between molecules.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::ConstantForce;
+/// # use lumol_core::sys::{System, Particle, Molecule, UnitCell};
+/// # use lumol_core::types::Vector3D;
+/// let mut system = System::with_cell(UnitCell::cubic(10.0));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(1.0, 0.0, 0.0))));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(2.0, 0.0, 0.0))));
+///
+/// // Pull the second particle along the x axis
+/// let force = Vector3D::new(0.5, 0.0, 0.0);
+/// system.add_global_potential(Box::new(ConstantForce::new(force, vec![1])));
+///
+/// assert_eq!(system.potential_energy(), -1.0);
+/// assert_eq!(system.forces(), vec![Vector3D::zero(), force]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConstantForce {
+    /// The force acting on the selected particles
+    force: Vector3D,
+    /// Sorted indexes of the selected particles
+    indexes: Vec<usize>,
+}
+
+impl ConstantForce {
+    /// Create a new `ConstantForce` applying the given `force` to all the
+    /// particles at `indexes`.
+    pub fn new(force: Vector3D, mut indexes: Vec<usize>) -> ConstantForce {
+        indexes.sort_unstable();
+        indexes.dedup();
+        ConstantForce {
+            force: force,
+            indexes: indexes,
+        }
+    }
+
+    /// Get the force acting on the selected particles
+    pub fn force(&self) -> Vector3D {
+        self.force
+    }
+
+    /// Get the indexes of the selected particles
+    pub fn indexes(&self) -> &[usize] {
+        &self.indexes
+    }
+
+    /// Update the indexes of the selected particles after the particles were
+    /// moved in the system, using the `permutations` returned by
+    /// [`Configuration::add_bond`] or [`Configuration::defragment`].
+    ///
+    /// [`Configuration::add_bond`]: ../sys/struct.Configuration.html#method.add_bond
+    /// [`Configuration::defragment`]: ../sys/struct.Configuration.html#method.defragment
+    pub fn apply_permutations(&mut self, permutations: &[Permutation]) {
+        for index in &mut self.indexes {
+            if let Some(permutation) = permutations.iter().find(|permutation| permutation.old == *index) {
+                *index = permutation.new;
+            }
+        }
+        self.indexes.sort_unstable();
+    }
+
+    /// Check that all the selected particles are inside the `configuration`
+    fn check(&self, configuration: &Configuration) {
+        if let Some(&last) = self.indexes.last() {
+            assert!(
+                last < configuration.size(),
+                "can not apply a constant force to particle {}, the system only contains {} particles",
+                last, configuration.size()
+            );
+        }
+    }
+
+    /// Get the energy of the selected particles at the given `positions`
+    fn energy_at(&self, positions: &[Vector3D]) -> f64 {
+        -self.indexes.iter().map(|&i| self.force * positions[i]).sum::<f64>()
+    }
+}
+
+impl GlobalCache for ConstantForce {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        self.check(configuration);
+        let positions = configuration.particles().position;
+        let mut cost = 0.0;
+        for (i, part_i) in configuration.molecule(molecule_id).indexes().enumerate() {
+            if self.indexes.binary_search(&part_i).is_ok() {
+                cost -= self.force * (new_positions[i] - positions[part_i]);
+            }
+        }
+        return cost;
+    }
+
+    fn move_volume_cost(
+        &self,
+        configuration: &Configuration,
+        _: &UnitCell,
+        new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        self.check(configuration);
+        let positions = configuration.particles().position;
+        return Some(self.energy_at(new_positions) - self.energy_at(positions));
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for ConstantForce {
+    fn cutoff(&self) -> Option<f64> {
+        None
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        self.check(configuration);
+        return self.energy_at(configuration.particles().position);
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        self.check(configuration);
+        for &i in &self.indexes {
+            forces[i] += self.force;
+        }
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        // The virial of an external force depends on the origin
+        self.check(configuration);
+        return Matrix3::zero();
+    }
+
+    fn is_translation_invariant(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Molecule, Particle, System};
+
+    use approx::assert_ulps_eq;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(1.0, 2.0, 3.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(-1.0, 0.0, 4.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 5.0, 0.0))));
+        system
+    }
+
+    #[test]
+    fn energy_forces_virial() {
+        let system = testing_system();
+        let force = Vector3D::new(1.0, 0.0, -2.0);
+        let potential = ConstantForce::new(force, vec![1, 0, 1]);
+        assert_eq!(potential.indexes(), &[0, 1]);
+
+        assert_ulps_eq!(potential.energy(&system), -(1.0 - 6.0) - (-1.0 - 8.0));
+
+        let mut forces = vec![Vector3D::zero(); 3];
+        potential.forces(&system, &mut forces);
+        assert_eq!(forces, vec![force, force, Vector3D::zero()]);
+
+        assert_eq!(potential.atomic_virial(&system), Matrix3::zero());
+        assert!(!potential.is_translation_invariant());
+    }
+
+    #[test]
+    fn permutations() {
+        let mut system = testing_system();
+        let force = Vector3D::new(1.0, 0.0, -2.0);
+        let mut potential = ConstantForce::new(force, vec![1, 2]);
+        let energy = potential.energy(&system);
+
+        // Particle 2 is moved to index 1, and particle 1 to index 2
+        let permutations = system.add_bond(0, 2);
+        assert!(!permutations.is_empty());
+        potential.apply_permutations(&permutations);
+        assert_eq!(potential.indexes(), &[1, 2]);
+        assert_ulps_eq!(potential.energy(&system), energy);
+
+        let mut potential = ConstantForce::new(force, vec![2]);
+        potential.apply_permutations(&permutations);
+        assert_eq!(potential.indexes(), &[1]);
+    }
+
+    #[test]
+    fn monte_carlo_costs() {
+        let system = testing_system();
+        let potential = ConstantForce::new(Vector3D::new(1.0, 0.0, -2.0), vec![0, 1]);
+
+        let new_positions = [Vector3D::new(2.0, 2.0, 2.0)];
+        let cost = potential.move_molecule_cost(&system, 0, &new_positions);
+        assert_ulps_eq!(cost, -(1.0 + 2.0));
+
+        // Moving a particle without external force is free
+        let new_positions = [Vector3D::new(2.0, 2.0, 2.0)];
+        assert_eq!(potential.move_molecule_cost(&system, 2, &new_positions), 0.0);
+
+        let new_positions = system.particles().position.iter().map(|&r| 2.0 * r).collect::<Vec<_>>();
+        let cost = potential.move_volume_cost(&system, &UnitCell::cubic(20.0), &new_positions).unwrap();
+        assert_ulps_eq!(cost, potential.energy(&system));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not apply a constant force to particle 3, the system only contains 3 particles")]
+    fn out_of_bounds() {
+        let system = testing_system();
+        let potential = ConstantForce::new(Vector3D::new(1.0, 0.0, 0.0), vec![3]);
+        let _ = potential.energy(&system);
+    }
+}
@@ -1,5 +1,6 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
 use std::ops::{Index, IndexMut, Deref, Range};
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::f64::consts::{PI, FRAC_2_SQRT_PI};
@@ -13,13 +14,14 @@ use log::{warn, info};
 use log_once::warn_once;
 
 use crate::math::{erf, erfc};
-use crate::{Configuration, UnitCell, CellShape};
+use crate::{Configuration, MoleculeHash, UnitCell, CellShape};
 use crate::{Matrix3, Vector3D, Array3, Complex};
 use crate::consts::FOUR_PI_EPSILON_0;
 use crate::{PairRestriction, RestrictionInfo};
 use crate::utils::ThreadLocalVec;
 
-use super::{GlobalPotential, CoulombicPotential, GlobalCache};
+use super::{GlobalPotential, CoulombicPotential, GlobalCache, TholeDamping};
+use super::{changed_configuration, MoleculeRestrictions};
 
 /// 3D array with negative indexing on the first dimension, for use in Ewald
 /// phase factors.
@@ -221,7 +223,7 @@ fn compute_ewald_factors_triclinic(factors: &mut EwaldFactorVec, cell: &UnitCell
 /// system.add_molecule(Molecule::new(cl));
 ///
 /// // Use Ewald summation for electrostatic interactions
-/// system.set_coulomb_potential(Box::new(ewald));
+/// system.set_coulomb_potential(Box::new(ewald)).unwrap();
 ///
 /// println!("energy is {}", system.potential_energy());
 /// ```
@@ -232,6 +234,10 @@ pub struct Ewald {
     factors: EwaldFactorVec,
     /// Restriction scheme
     restriction: PairRestriction,
+    /// Restriction schemes overriding `restriction` inside some molecules
+    molecule_restrictions: BTreeMap<MoleculeHash, PairRestriction>,
+    /// Optional damping of the real-space interactions
+    damping: Option<TholeDamping>,
     /// Cached phase factors (e^{i k r})
     eikr: Ewald3DArray,
     /// Fourier transform of the electrostatic density (\sum q_i e^{i k r})
@@ -245,20 +251,47 @@ pub struct Ewald {
     field: Vec<Vector3D>,
     /// Guard for cache invalidation of `self.factors`
     previous_cell: Option<UnitCell>,
+    /// k-space quantities for the unit cell of the last volume move, which
+    /// replace the current ones if the move is accepted
+    scratch: KSpaceScratch,
     /// Update the cached quantities
     updater: Option<Box<dyn Fn(&mut Ewald) + Sync + Send>>,
 }
 
+/// Buffers for the k-space quantities of a trial unit cell
+struct KSpaceScratch {
+    /// Ewald pre-factors for the trial cell
+    factors: EwaldFactorVec,
+    /// Phase factors for the trial configuration
+    eikr: Ewald3DArray,
+    /// Fourier transform of the electrostatic density for the trial
+    /// configuration
+    rho: Vec<Complex>,
+}
+
+impl KSpaceScratch {
+    fn new() -> KSpaceScratch {
+        KSpaceScratch {
+            factors: EwaldFactorVec::new(),
+            eikr: Ewald3DArray::zeros((0..0, 0, 0)),
+            rho: Vec::new(),
+        }
+    }
+}
+
 impl Clone for Ewald {
     fn clone(&self) -> Ewald {
         Ewald {
             parameters: self.parameters.clone(),
             factors: self.factors.clone(),
             restriction: self.restriction,
+            molecule_restrictions: self.molecule_restrictions.clone(),
+            damping: self.damping,
             eikr: self.eikr.clone(),
             rho: self.rho.clone(),
             field: self.field.clone(),
             previous_cell: self.previous_cell,
+            scratch: KSpaceScratch::new(),
             updater: None,
         }
     }
@@ -295,11 +328,14 @@ impl Ewald {
         Ewald {
             parameters: parameters,
             restriction: PairRestriction::None,
+            molecule_restrictions: BTreeMap::new(),
+            damping: None,
             factors: EwaldFactorVec::new(),
             eikr: Ewald3DArray::zeros((0..0, 0, 0)),
             rho: Vec::new(),
             field: Vec::new(),
             previous_cell: None,
+            scratch: KSpaceScratch::new(),
             updater: None,
         }
     }
@@ -358,9 +394,7 @@ impl Ewald {
             }
         }
         self.previous_cell = Some(*cell);
-
-        let max = cell.k_vector([1.0, 1.0, 1.0]).max() * self.parameters.kmax as f64;
-        self.parameters.kmax2 = 1.0001 * max * max;
+        self.parameters.kmax2 = self.kmax2(cell);
 
         let half_min_length = cell.lengths().min() / 2.0;
         if self.parameters.rc > half_min_length {
@@ -376,10 +410,22 @@ You can manually set alpha to a slightly higher value (current alpha is {})",
 
         compute_ewald_factors(&mut self.factors, cell, &self.parameters);
     }
+
+    /// Get the spherical cutoff in k-space for the given `cell`
+    fn kmax2(&self, cell: &UnitCell) -> f64 {
+        let max = cell.k_vector([1.0, 1.0, 1.0]).max() * self.parameters.kmax as f64;
+        return 1.0001 * max * max;
+    }
 }
 
 /// Real space part of the summation
 impl Ewald {
+    /// Get the restrictions to use for the pairs of particles in the
+    /// molecules of `configuration`
+    fn restrictions(&self, configuration: &Configuration) -> MoleculeRestrictions {
+        MoleculeRestrictions::new(configuration, self.restriction, &self.molecule_restrictions)
+    }
+
     /// Get the real-space energy for one pair at distance `r` with charges `qi`
     /// and `qj` ; and with restriction information for this pair in `info`.
     #[allow(clippy::float_cmp)]  // checking info.scaling
@@ -396,7 +442,8 @@ impl Ewald {
             // from k-space
             - qiqj / FOUR_PI_EPSILON_0 * erf(self.alpha * r) / r
         } else {
-            qiqj / FOUR_PI_EPSILON_0 * erfc(self.alpha * r) / r
+            let damping = self.damping.map_or(0.0, |damping| damping.energy(r));
+            qiqj / FOUR_PI_EPSILON_0 * (erfc(self.alpha * r) / r + damping)
         }
     }
 
@@ -420,9 +467,10 @@ impl Ewald {
                 - erf(self.alpha * r) / r
             )
         } else {
+            let damping = self.damping.map_or(0.0, |damping| damping.force(r));
             qiqj / (FOUR_PI_EPSILON_0 * r * r) * (
                 self.alpha * FRAC_2_SQRT_PI * f64::exp(-self.alpha * self.alpha * r * r)
-                + erfc(self.alpha * r) / r
+                + erfc(self.alpha * r) / r + r * damping
             )
         }
     }
@@ -430,7 +478,8 @@ impl Ewald {
     /// Real space contribution to the energy
     fn real_space_energy(&self, configuration: &Configuration) -> f64 {
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
 
         let energies = (0..natoms).into_par_iter().map(|i| {
             let mut local_energy = 0.0;
@@ -445,8 +494,7 @@ impl Ewald {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
 
                 let r = configuration.distance(i, j);
                 local_energy += self.real_space_energy_pair(info, qi * qj, r);
@@ -462,7 +510,8 @@ impl Ewald {
         assert_eq!(forces.len(), configuration.size());
 
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
         // Each thread (and not each iteration of the loop below) get its own
         // storage in a `ThreadLocalVec`.
         let thread_local_forces = ThreadLocalVec::with_size(natoms);
@@ -483,8 +532,7 @@ impl Ewald {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
 
                 let rij = configuration.nearest_image(i, j);
                 let force = self.real_space_force_pair(info, qi * qj, rij.norm()) * rij;
@@ -502,7 +550,8 @@ impl Ewald {
     /// Real space contribution to the atomic virial
     fn real_space_atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
 
         let virial = (0..natoms).into_par_iter().map(|i| {
             let qi = charges[i];
@@ -517,8 +566,7 @@ impl Ewald {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
 
                 let rij = configuration.nearest_image(i, j);
                 let force = self.real_space_force_pair(info, qi * qj, rij.norm()) * rij;
@@ -531,7 +579,8 @@ impl Ewald {
 
     /// Real space contribution to the molecular virial
     fn real_space_molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
         let virial = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
             let mut local_virial = Matrix3::zero();
             let ri = molecule_i.center_of_mass();
@@ -553,8 +602,7 @@ impl Ewald {
                             continue;
                         }
 
-                        let path = configuration.bond_path(part_a, part_b);
-                        let info = self.restriction.information(path);
+                        let info = restrictions.information(configuration, part_a, part_b);
 
                         let r_ab = configuration.nearest_image(part_a, part_b);
                         let force = self.real_space_force_pair(info, q_a * q_b, r_ab.norm()) * r_ab;
@@ -578,7 +626,8 @@ impl Ewald {
          let mut old_energy = 0.0;
          let mut new_energy = 0.0;
 
-         let charges = configuration.particles().charge;
+         let charges = configuration.coupled_charges();
+         let restrictions = self.restrictions(configuration);
          let positions = configuration.particles().position;
 
          // Iterate over all interactions between a particle in the moved
@@ -600,13 +649,29 @@ impl Ewald {
                     let old_r = configuration.distance(part_i, part_j);
                     let new_r = configuration.cell.distance(&new_positions[i], &positions[part_j]);
 
-                    let path = configuration.bond_path(part_i, part_j);
-                    let info = self.restriction.information(path);
+                    let info = restrictions.information(configuration, part_i, part_j);
 
                     old_energy += self.real_space_energy_pair(info, qi * qj, old_r);
                     new_energy += self.real_space_energy_pair(info, qi * qj, new_r);
                 }
             }
+
+            // Iterate over the interactions inside the moved molecule, in
+            // case it is not moved rigidly
+            for (j, part_j) in molecule.indexes().enumerate().skip(i + 1) {
+                let qj = charges[part_j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let old_r = configuration.distance(part_i, part_j);
+                let new_r = configuration.cell.distance(&new_positions[i], &new_positions[j]);
+
+                let info = restrictions.information(configuration, part_i, part_j);
+
+                old_energy += self.real_space_energy_pair(info, qi * qj, old_r);
+                new_energy += self.real_space_energy_pair(info, qi * qj, new_r);
+            }
         }
 
         return new_energy - old_energy;
@@ -617,8 +682,7 @@ impl Ewald {
 impl Ewald {
     /// Self-interaction contribution to the energy
     fn self_energy(&self, configuration: &Configuration) -> f64 {
-        let q2 = configuration.particles()
-                              .charge
+        let q2 = configuration.coupled_charges()
                               .iter()
                               .map(|q| q * q)
                               .sum::<f64>();
@@ -631,46 +695,9 @@ impl Ewald {
 impl Ewald {
     /// Compute the Fourier transform of the electrostatic density
     fn eik_dot_r(&mut self, configuration: &Configuration) {
-        let natoms = configuration.size();
-        let range = -self.kmax..(self.kmax + 1);
-        self.eikr.resize_if_different((range, 3, natoms));
-        self.rho.clear();
-
-        let positions = configuration.particles().position;
-        let charges = configuration.particles().charge;
-
-        // do the k = -1, 0, 1 cases first
-        for spatial in 0..3 {
-            let mut k_idx = [0.0, 0.0, 0.0];
-            k_idx[spatial] = 1.0;
-            let k_vector = configuration.cell.k_vector(k_idx);
-            for i in 0..natoms {
-                self.eikr[(0, spatial, i)] = Complex::cartesian(1.0, 0.0);
-                self.eikr[(1, spatial, i)] = Complex::polar(1.0, k_vector * positions[i]);
-                self.eikr[(-1, spatial, i)] = self.eikr[(1, spatial, i)].conj();
-            }
-        }
-
-        // compute the other values of k by recursion
-        for spatial in 0..3 {
-            for k in 2..(self.kmax + 1) {
-                for i in 0..natoms {
-                    self.eikr[(k, spatial, i)] = self.eikr[(k - 1, spatial, i)] * self.eikr[(1, spatial, i)];
-                    self.eikr[(-k, spatial, i)] = self.eikr[(k, spatial, i)].conj();
-                }
-            }
-        }
-
-        for &(ikx, iky, ikz) in &self.factors.index {
-            let mut partial = Complex::zero();
-            for i in 0..natoms {
-                let phi = self.eikr[(ikx, 0, i)] *
-                          self.eikr[(iky, 1, i)] *
-                          self.eikr[(ikz, 2, i)];
-                partial += charges[i] * phi;
-            }
-            self.rho.push(partial);
-        }
+        compute_eik_dot_r(
+            self.parameters.kmax, &self.factors.index, configuration, &mut self.eikr, &mut self.rho
+        );
     }
 
     /// k-space contribution to the energy
@@ -713,8 +740,8 @@ impl Ewald {
 
             thread_local_field.sum_into(&mut self.field);
 
-        let charges = configuration.particles().charge;
-        for (force, &charge, field) in zip!(&mut *forces, charges, &self.field) {
+        let charges = configuration.coupled_charges();
+        for (force, &charge, field) in zip!(&mut *forces, &*charges, &self.field) {
             *force += charge * field / FOUR_PI_EPSILON_0;
         }
     }
@@ -754,13 +781,14 @@ impl Ewald {
 
     /// Compute the Fourier transform of the electrostatic density changes
     /// while moving the molecule with the given `molecule_id` to
-    /// `new_positions`
+    /// `new_positions`. This function also returns the phase factors for the
+    /// new positions of the particles in the molecule.
     fn delta_rho_move_rigid_molecules(
         &mut self,
         configuration: &Configuration,
         molecule_id: usize,
         new_positions: &[Vector3D],
-    ) -> Vec<Complex> {
+    ) -> (Vec<Complex>, Ewald3DArray) {
         let molecule = configuration.molecule(molecule_id);
         let mut new_energy_ikr = Ewald3DArray::zeros((-self.kmax..(self.kmax + 1), 3, molecule.size()));
 
@@ -787,7 +815,7 @@ impl Ewald {
         }
 
         let mut delta = Vec::new();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
         for &(ikx, iky, ikz) in &self.factors.index {
             let mut partial = Complex::zero();
             for (i, part_i) in molecule.indexes().enumerate() {
@@ -804,7 +832,7 @@ impl Ewald {
             delta.push(partial);
         }
 
-        return delta;
+        return (delta, new_energy_ikr);
     }
 
     fn k_space_move_molecule_cost(
@@ -819,7 +847,7 @@ impl Ewald {
         }
         old_energy /= FOUR_PI_EPSILON_0;
 
-        let delta_rho = self.delta_rho_move_rigid_molecules(
+        let (delta_rho, new_eikr) = self.delta_rho_move_rigid_molecules(
             configuration, molecule_id, new_positions
         );
 
@@ -829,16 +857,124 @@ impl Ewald {
         }
         new_energy /= FOUR_PI_EPSILON_0;
 
+        let indexes = configuration.molecule(molecule_id).indexes();
         self.updater = Some(Box::new(move |ewald: &mut Ewald| {
             for (rho, &delta) in zip!(&mut ewald.rho, &delta_rho) {
                 *rho += delta;
             }
+
+            // Update the cached phases of the moved particles, so that the
+            // next move of the same molecule removes the right contribution
+            // from the structure factors
+            let kmax = ewald.kmax;
+            for (i, part_i) in indexes.clone().enumerate() {
+                for spatial in 0..3 {
+                    for k in -kmax..(kmax + 1) {
+                        ewald.eikr[(k, spatial, part_i)] = new_eikr[(k, spatial, i)];
+                    }
+                }
+            }
+        }));
+
+        return new_energy - old_energy;
+    }
+
+    /// Get the k-space cost for changing the unit cell and all the positions
+    /// to the ones in `new_configuration`. The k-vectors, phase factors and
+    /// structure factors for the new configuration are computed in scratch
+    /// buffers, which replace the current ones if the move is accepted.
+    fn k_space_move_volume_cost(&mut self, new_configuration: &Configuration) -> f64 {
+        let mut old_energy = 0.0;
+        for (factor, &rho) in zip!(&self.factors.energy, &self.rho) {
+            old_energy += factor * rho.norm2();
+        }
+        old_energy /= FOUR_PI_EPSILON_0;
+
+        let new_cell = new_configuration.cell;
+        let mut parameters = self.parameters.clone();
+        parameters.kmax2 = self.kmax2(&new_cell);
+
+        let scratch = &mut self.scratch;
+        compute_ewald_factors(&mut scratch.factors, &new_cell, &parameters);
+        compute_eik_dot_r(
+            parameters.kmax, &scratch.factors.index, new_configuration, &mut scratch.eikr, &mut scratch.rho
+        );
+        let mut new_energy = 0.0;
+        for (factor, &rho) in zip!(&scratch.factors.energy, &scratch.rho) {
+            new_energy += factor * rho.norm2();
+        }
+        new_energy /= FOUR_PI_EPSILON_0;
+
+        let kmax2 = parameters.kmax2;
+        self.updater = Some(Box::new(move |ewald: &mut Ewald| {
+            let Ewald { factors, eikr, rho, scratch, .. } = ewald;
+            std::mem::swap(factors, &mut scratch.factors);
+            std::mem::swap(eikr, &mut scratch.eikr);
+            std::mem::swap(rho, &mut scratch.rho);
+            ewald.parameters.kmax2 = kmax2;
+            ewald.previous_cell = Some(new_cell);
         }));
 
         return new_energy - old_energy;
     }
 }
 
+/// Compute the phase factors `eikr` and the Fourier transform of the
+/// electrostatic density `rho` of the `configuration`, for the k-vectors at
+/// `indexes` with at most `kmax` points in each direction.
+fn compute_eik_dot_r(
+    kmax: isize,
+    indexes: &[(isize, isize, isize)],
+    configuration: &Configuration,
+    eikr: &mut Ewald3DArray,
+    rho: &mut Vec<Complex>,
+) {
+    let natoms = configuration.size();
+    let range = -kmax..(kmax + 1);
+    eikr.resize_if_different((range, 3, natoms));
+    rho.clear();
+
+    let positions = configuration.particles().position;
+    let charges = configuration.coupled_charges();
+
+    // do the k = -1, 0, 1 cases first
+    for spatial in 0..3 {
+        let mut k_idx = [0.0, 0.0, 0.0];
+        k_idx[spatial] = 1.0;
+        let k_vector = configuration.cell.k_vector(k_idx);
+        for i in 0..natoms {
+            eikr[(0, spatial, i)] = Complex::cartesian(1.0, 0.0);
+            eikr[(1, spatial, i)] = Complex::polar(1.0, k_vector * positions[i]);
+            eikr[(-1, spatial, i)] = eikr[(1, spatial, i)].conj();
+        }
+    }
+
+    // compute the other values of k by recursion
+    for spatial in 0..3 {
+        for k in 2..(kmax + 1) {
+            for i in 0..natoms {
+                eikr[(k, spatial, i)] = eikr[(k - 1, spatial, i)] * eikr[(1, spatial, i)];
+                eikr[(-k, spatial, i)] = eikr[(k, spatial, i)].conj();
+            }
+        }
+    }
+
+    // the structure factor for each k-vector only depends on the cached
+    // phases, so all k-vectors can be computed in parallel
+    let eikr = &*eikr;
+    let structure_factors = indexes
+        .par_iter()
+        .map(|&(ikx, iky, ikz)| {
+            let mut partial = Complex::zero();
+            for i in 0..natoms {
+                let phi = eikr[(ikx, 0, i)] * eikr[(iky, 1, i)] * eikr[(ikz, 2, i)];
+                partial += charges[i] * phi;
+            }
+            partial
+        });
+    rho.par_extend(structure_factors);
+}
+
 /// Thread-sade wrapper around Ewald implementing `CoulombicPotential`.
 ///
 /// This wrapper allow to share a Ewald solver between threads (make it `Send
@@ -927,6 +1063,18 @@ impl CoulombicPotential for SharedEwald {
     fn set_restriction(&mut self, restriction: PairRestriction) {
         self.write().restriction = restriction;
     }
+
+    fn set_molecule_restriction(&mut self, molecule: MoleculeHash, restriction: PairRestriction) {
+        let _ = self.write().molecule_restrictions.insert(molecule, restriction);
+    }
+
+    fn set_thole_damping(&mut self, damping: Option<TholeDamping>) {
+        self.write().damping = damping;
+    }
+
+    fn requires_periodicity(&self) -> bool {
+        true
+    }
 }
 
 impl GlobalCache for SharedEwald {
@@ -944,6 +1092,22 @@ impl GlobalCache for SharedEwald {
         return real + k_space;
     }
 
+    fn move_volume_cost(
+        &self,
+        configuration: &Configuration,
+        new_cell: &UnitCell,
+        new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        let new_configuration = changed_configuration(configuration, new_cell, new_positions);
+        let mut ewald = self.write();
+        ewald.prepare(&configuration.cell);
+
+        let real = ewald.real_space_energy(&new_configuration) - ewald.real_space_energy(configuration);
+        /* No self cost, the self energy does not depend on the cell */
+        let k_space = ewald.k_space_move_volume_cost(&new_configuration);
+        return Some(real + k_space);
+    }
+
     fn update(&self) {
         let mut ewald = self.write();
         if ewald.updater.is_some() {
@@ -1070,6 +1234,33 @@ mod tests {
             assert_relative_eq!((e - e1) / eps, forces[0][0], epsilon=1e-6);
         }
 
+        #[test]
+        fn thole_damping() {
+            let mut system = nacl_pair();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.prepare(&system.cell);
+            let undamped = ewald.real_space_energy(&system);
+
+            ewald.damping = Some(TholeDamping::new(2.0));
+            let e = ewald.real_space_energy(&system);
+            assert!(e > undamped);
+
+            let eps = 1e-9;
+            system.particles_mut().position[0][0] += eps;
+
+            let e1 = ewald.real_space_energy(&system);
+            let mut forces = vec![Vector3D::zero(); 2];
+            ewald.real_space_forces(&system, &mut forces);
+            assert_relative_eq!((e - e1) / eps, forces[0][0], epsilon=1e-6);
+
+            // The damping vanishes at large separation
+            system.particles_mut().position[1] = Vector3D::new(7.5, 0.0, 0.0);
+            ewald.damping = Some(TholeDamping::new(5.0));
+            let damped = ewald.real_space_energy(&system);
+            ewald.damping = None;
+            assert_ulps_eq!(damped, ewald.real_space_energy(&system), epsilon=1e-12);
+        }
+
         #[test]
         fn k_space_forces_finite_differences() {
             let mut system = nacl_pair();
@@ -1191,6 +1382,81 @@ mod tests {
             let force = forces[0][0];
             assert_relative_eq!((e - e1) / eps, force, epsilon = 1e-6);
         }
+
+        fn water_and_hydrogen_sulfide() -> System {
+            let mut system = system_from_xyz("6
+            cell: 20.0
+            O  0.0  0.0  0.0
+            H -0.7 -0.7  0.3
+            H  0.3 -0.3 -0.8
+            S  3.0  3.0  0.0
+            H  2.1  2.4  0.3
+            H  3.5  2.6 -0.9
+            ");
+            assert!(system.add_bond(0, 1).is_empty());
+            assert!(system.add_bond(0, 2).is_empty());
+            assert!(system.add_bond(3, 4).is_empty());
+            assert!(system.add_bond(3, 5).is_empty());
+            assert!(system.molecules().count() == 2);
+
+            for particle in system.particles_mut() {
+                if particle.name == "H" {
+                    *particle.charge = 0.4238;
+                } else {
+                    *particle.charge = -0.8476;
+                }
+            }
+            return system;
+        }
+
+        #[test]
+        fn molecule_restrictions() {
+            let system = water_and_hydrogen_sulfide();
+            let mut ewald = Ewald::new(8.0, 10, None);
+            ewald.restriction = PairRestriction::Exclude12;
+            // Exclude all the intramolecular interactions in water, the H-H
+            // interaction in hydrogen sulfide uses the default restriction
+            let _ = ewald.molecule_restrictions.insert(system.molecule(0).hash(), PairRestriction::InterMolecular);
+            ewald.prepare(&system.cell);
+
+            let charges = system.particles().charge;
+            let mut expected = 0.0;
+            for i in 0..system.size() {
+                for j in (i + 1)..system.size() {
+                    let restriction = if system.molecule_id(i) == 0 {
+                        PairRestriction::InterMolecular
+                    } else {
+                        PairRestriction::Exclude12
+                    };
+                    let info = restriction.information(system.bond_path(i, j));
+                    expected += ewald.real_space_energy_pair(info, charges[i] * charges[j], system.distance(i, j));
+                }
+            }
+            assert_relative_eq!(ewald.real_space_energy(&system), expected, max_relative = 1e-12);
+
+            // Without override, the H-H interaction in water is included
+            let mut default = ewald.clone();
+            default.molecule_restrictions.clear();
+            assert!(f64::abs(default.real_space_energy(&system) - expected) > 1e-4);
+        }
+
+        #[test]
+        fn molecule_restrictions_forces() {
+            let mut system = water_and_hydrogen_sulfide();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+            ewald.set_restriction(PairRestriction::Exclude12);
+            ewald.set_molecule_restriction(system.molecule(0).hash(), PairRestriction::InterMolecular);
+            ewald.set_molecule_restriction(system.molecule(1).hash(), PairRestriction::Scale14(0.5));
+
+            let mut forces = vec![Vector3D::zero(); 6];
+            ewald.forces(&system, &mut forces);
+
+            let eps = 1e-9;
+            let e = ewald.energy(&system);
+            system.particles_mut().position[4][0] += eps;
+            let e1 = ewald.energy(&system);
+            assert_relative_eq!((e - e1) / eps, forces[4][0], epsilon = 1e-6);
+        }
     }
 
     mod atomic_virial {
@@ -1376,6 +1642,138 @@ mod tests {
         );
     }
 
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn incremental_structure_factors() {
+        let mut system = system_from_xyz("6
+        cell: 20.0
+        H  0.3 -0.3 -0.8
+        O  0.0  0.0  0.0
+        H -0.7 -0.7  0.3
+        H  2.3  1.7 -0.8
+        O  2.0  2.0  0.0
+        H  1.3  1.3  0.3
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(4, 5).is_empty());
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let mut ewald = Ewald::new(2.0, 10, None);
+        ewald.restriction = PairRestriction::InterMolecular;
+        ewald.prepare(&system.cell);
+        // Initialize cached values
+        let mut energy = ewald.k_space_energy(&system);
+
+        // Move the same molecule multiple times, accepting all the moves
+        let moves = [
+            Vector3D::new(0.3, 1.2, -0.4),
+            Vector3D::new(-2.1, 0.5, 0.8),
+            Vector3D::new(1.7, -0.9, 2.2),
+        ];
+        for delta in &moves {
+            let new_positions = system.molecule(0).indexes()
+                .map(|i| system.particles().position[i] + *delta)
+                .collect::<Vec<_>>();
+            let cost = ewald.k_space_move_molecule_cost(&system, 0, &new_positions);
+
+            let updater = ewald.updater.take().unwrap();
+            updater(&mut ewald);
+            for (i, position) in new_positions.iter().enumerate() {
+                system.particles_mut().position[i] = *position;
+            }
+
+            let mut check = Ewald::new(2.0, 10, None);
+            check.restriction = PairRestriction::InterMolecular;
+            check.prepare(&system.cell);
+            let new_energy = check.k_space_energy(&system);
+            assert_relative_eq!(cost, new_energy - energy, max_relative = 1e-9);
+            energy = new_energy;
+
+            // Incrementally updated structure factors match the ones
+            // computed from scratch
+            for (rho, expected) in ewald.rho.iter().zip(&check.rho) {
+                assert_relative_eq!(rho.real(), expected.real(), epsilon = 1e-10);
+                assert_relative_eq!(rho.imag(), expected.imag(), epsilon = 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn move_volume() {
+        let mut system = system_from_xyz("6
+        cell: 20.0
+        H  0.3 -0.3 -0.8
+        O  0.0  0.0  0.0
+        H -0.7 -0.7  0.3
+        H  2.3  1.7 -0.8
+        O  2.0  2.0  0.0
+        H  1.3  1.3  0.3
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(4, 5).is_empty());
+
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let mut ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+        ewald.set_restriction(PairRestriction::InterMolecular);
+        let check = ewald.clone();
+
+        // Initialize cached values
+        let old_energy = ewald.energy(&system);
+
+        // Scale the cell and the molecules centers of mass
+        let new_cell = system.cell.scale(Matrix3::one() * 1.1);
+        let mut new_positions = system.particles().position.to_vec();
+        for molecule in system.molecules() {
+            let com = molecule.center_of_mass();
+            let delta = new_cell.cartesian(&system.cell.fractional(&com)) - com;
+            for i in molecule.indexes() {
+                new_positions[i] += delta;
+            }
+        }
+
+        let cost = ewald.move_volume_cost(&system, &new_cell, &new_positions).unwrap();
+        system.cell = new_cell;
+        for (position, new_position) in system.particles_mut().position.iter_mut().zip(&new_positions) {
+            *position = *new_position;
+        }
+        let new_energy = check.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-12);
+
+        // Check that the cached values are updated
+        ewald.update();
+        let new_positions = &[
+            Vector3D::new(0.41727, 2.29401, -0.0558),
+            Vector3D::new(0.5097743599026461, 3.194114034722624, -0.020364564697826326),
+            Vector3D::new(-0.2501317777731211, 3.562366060753896, -0.6178033542374419),
+        ];
+        let cost = ewald.move_molecule_cost(&system, 0, new_positions);
+        let old_energy = new_energy;
+        for (position, new_position) in system.particles_mut().position.iter_mut().zip(new_positions) {
+            *position = *new_position;
+        }
+        let new_energy = check.energy(&system);
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-12);
+    }
+
     // Comparing the value for each component of Ewald energy with the NIST
     // reference. See `tests/nist-spce.rs` for more information. These tests
     // check values that are not accessible from the outside of lumol-core.
@@ -1461,6 +1859,33 @@ mod tests {
             assert_eq!(ewald.kmax, 8);
         }
 
+        #[test]
+        fn parallel_k_space() {
+            let system = get_system("spce-4.xyz");
+            let mut ewald = Ewald::new(9.0, 8, None);
+            ewald.restriction = PairRestriction::InterMolecular;
+            ewald.prepare(&system.cell);
+
+            let serial = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+            let (serial_energy, serial_forces) = serial.install(|| {
+                let mut forces = vec![Vector3D::zero(); system.size()];
+                ewald.k_space_forces(&system, &mut forces);
+                (ewald.k_space_energy(&system), forces)
+            });
+
+            let parallel = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+            let (energy, forces) = parallel.install(|| {
+                let mut forces = vec![Vector3D::zero(); system.size()];
+                ewald.k_space_forces(&system, &mut forces);
+                (ewald.k_space_energy(&system), forces)
+            });
+
+            assert_relative_eq!(energy, serial_energy, max_relative = 1e-12);
+            for (force, serial_force) in forces.iter().zip(&serial_forces) {
+                assert_relative_eq!(*force, *serial_force, epsilon = 1e-12, max_relative = 1e-10);
+            }
+        }
+
         #[allow(clippy::unreadable_literal)]
         mod cutoff_9 {
             use super::*;
@@ -5,8 +5,12 @@
 //!
 //! They can be coulombic potentials, or external provided potential function
 //! for example.
-use crate::PairRestriction;
-use crate::Configuration;
+use std::collections::BTreeMap;
+
+use soa_derive::soa_zip;
+
+use crate::{BondPath, PairRestriction, RestrictionInfo};
+use crate::{Configuration, MoleculeHash, UnitCell};
 use crate::{Matrix3, Vector3D};
 
 /// A potential acting on the whole [System][System] at once.
@@ -93,10 +97,34 @@ pub trait GlobalPotential: GlobalCache + BoxCloneGlobal + Send + Sync {
     /// configuration.
     fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]);
 
+    /// Compute the torque contribution of this potential, for potentials
+    /// depending on the orientation of the particles. The torque acting on
+    /// each particle in the configuration should be added to `torques`. The
+    /// default implementation does nothing, for potentials which do not
+    /// depend on the orientations.
+    fn torques(&self, _: &Configuration, _: &mut [Vector3D]) {}
+
     /// Compute the total virial contribution of this potential, using the
-    /// atomic virial definition
+    /// atomic virial definition. This contribution is included in the
+    /// virial, stress and pressure of the system.
+    ///
+    /// The virial should be equal to $- \underline H \partial U / \partial
+    /// \underline H$ when scaling the cell and the positions together.
+    /// Potentials depending on the absolute positions of the particles (such
+    /// as external fields or positional restraints) are not invariant by
+    /// translation, and the pressure is ill-defined when using them. Such
+    /// potentials should return `false` from
+    /// [`is_translation_invariant`](#method.is_translation_invariant), and a
+    /// warning is emitted when computing the pressure or the stress.
     fn atomic_virial(&self, configuration: &Configuration) -> Matrix3;
 
+    /// Check if this potential is invariant by translation of all the
+    /// particles, i.e. if it does not depend on the choice of the origin.
+    /// The default implementation returns `true`.
+    fn is_translation_invariant(&self) -> bool {
+        true
+    }
+
     /// Compute the total virial contribution of this potential, using the
     /// molecular virial definition. This default to `atomic_virial`.
     fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
@@ -125,7 +153,7 @@ impl_box_clone!(GlobalPotential, BoxCloneGlobal, box_clone_gobal);
 /// ```
 /// use lumol_core::energy::{GlobalPotential, GlobalCache};
 /// use lumol_core::types::{Vector3D, Matrix3};
-/// use lumol_core::sys::Configuration;
+/// use lumol_core::sys::{Configuration, UnitCell};
 ///
 /// /// Shift the energy of all the particles by a given delta.
 /// #[derive(Clone)]
@@ -160,6 +188,11 @@ impl_box_clone!(GlobalPotential, BoxCloneGlobal, box_clone_gobal);
 ///         return 0.0
 ///     }
 ///
+///     fn move_volume_cost(&self, _: &Configuration, _: &UnitCell, _: &[Vector3D]) -> Option<f64> {
+///         // Same thing when changing the unit cell
+///         return Some(0.0)
+///     }
+///
 ///     fn update(&self) {
 ///         // We are not storing anything in the ShiftAll struct, so this
 ///         // function is a no-op.
@@ -167,12 +200,13 @@ impl_box_clone!(GlobalPotential, BoxCloneGlobal, box_clone_gobal);
 /// }
 /// ```
 pub trait GlobalCache {
-    /// Get the cost of moving a rigid molecule in the system.
+    /// Get the cost of moving a molecule in the system.
     ///
     /// This function is passed the current `configuration`, the index of the
     /// molecule in the configuration; and the `new_positions` of the
     /// particles. The previous positions of the particles are still in the
-    /// system.
+    /// system. The molecule is not necessarily moved rigidly, and the
+    /// intramolecular contributions to the energy can also change.
     fn move_molecule_cost(
         &self,
         configuration: &Configuration,
@@ -180,7 +214,28 @@ pub trait GlobalCache {
         new_positions: &[Vector3D],
     ) -> f64;
 
-    /// Update the cache as needed after a call to `move_molecule_cost`.
+    /// Get the cost of changing the unit cell of the system.
+    ///
+    /// This function is passed the current `configuration`, the `new_cell`
+    /// and the `new_positions` of all the particles in the system, usually
+    /// obtained by scaling the current positions. The previous cell and
+    /// positions are still in the configuration.
+    ///
+    /// The default implementation returns `None`, and the cost is computed by
+    /// recomputing the full energy of the potential with the new cell and
+    /// positions. Potentials with a cheaper way to get this cost should
+    /// return it here.
+    fn move_volume_cost(
+        &self,
+        _configuration: &Configuration,
+        _new_cell: &UnitCell,
+        _new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        None
+    }
+
+    /// Update the cache as needed after a call to `move_molecule_cost` or
+    /// `move_volume_cost`.
     ///
     /// If the Monte Carlo move is accepted, this function will be called and
     /// should update any cached quantity so that further call to
@@ -199,12 +254,127 @@ pub trait CoulombicPotential: GlobalPotential + BoxCloneCoulombic {
     /// future call to `GlobalPotential::energy`, `GlobalPotential::force` or
     /// `GlobalPotential::virial` should use this restriction.
     fn set_restriction(&mut self, restriction: PairRestriction);
+
+    /// Use the given `restriction` instead of the one set with
+    /// `set_restriction` for pairs of particles inside molecules with the
+    /// `molecule` hash. Pairs of particles in different molecules, and pairs
+    /// inside other molecules are not affected. The default implementation
+    /// panics, for solvers which do not support per-molecule restrictions.
+    fn set_molecule_restriction(&mut self, _molecule: MoleculeHash, _restriction: PairRestriction) {
+        panic!("this coulombic potential does not support per-molecule restrictions");
+    }
+
+    /// Set the Thole damping to use for the real-space interactions between
+    /// pairs of charges, or disable it if `damping` is `None`. Excluded pairs
+    /// are not affected by the damping. The default implementation panics
+    /// when enabling the damping, for solvers which do not support it.
+    fn set_thole_damping(&mut self, damping: Option<TholeDamping>) {
+        assert!(damping.is_none(), "this coulombic potential does not support Thole damping");
+    }
+
+    /// Does this solver require periodic boundary conditions? Solvers
+    /// requiring periodicity can not be used with an infinite unit cell.
+    fn requires_periodicity(&self) -> bool {
+        false
+    }
 }
 
 impl_box_clone!(CoulombicPotential, BoxCloneCoulombic, box_clone_coulombic);
 
+/// Restriction schemes to use for the pairs of particles inside each molecule
+/// of a configuration, precomputed from the per-molecule overrides of a
+/// coulombic potential.
+struct MoleculeRestrictions {
+    /// Restriction for pairs without override
+    default: PairRestriction,
+    /// Restriction for the pairs inside each molecule, indexed by molecule
+    /// id. This is empty if there are no overrides.
+    molecules: Vec<PairRestriction>,
+}
+
+impl MoleculeRestrictions {
+    /// Get the restrictions for the molecules in `configuration`. Pairs inside
+    /// a molecule with an entry in `overrides` use the corresponding
+    /// restriction, all the other pairs use the `default` restriction.
+    fn new(
+        configuration: &Configuration,
+        default: PairRestriction,
+        overrides: &BTreeMap<MoleculeHash, PairRestriction>,
+    ) -> MoleculeRestrictions {
+        let molecules = if overrides.is_empty() {
+            Vec::new()
+        } else {
+            configuration.molecules()
+                .map(|molecule| overrides.get(&molecule.hash()).copied().unwrap_or(default))
+                .collect()
+        };
+        MoleculeRestrictions {
+            default: default,
+            molecules: molecules,
+        }
+    }
+
+    /// Get the restriction information for the pair of particles `i` and `j`
+    /// in the `configuration`.
+    #[inline]
+    fn information(&self, configuration: &Configuration, i: usize, j: usize) -> RestrictionInfo {
+        let path = configuration.bond_path(i, j);
+        if path != BondPath::None && !self.molecules.is_empty() {
+            return self.molecules[configuration.molecule_id(i)].information(path);
+        }
+        return self.default.information(path);
+    }
+}
+
+/// Get the cost of changing the cell of `configuration` to `new_cell` and
+/// moving the particles to `new_positions` for the global `potential`, using
+/// `GlobalCache::move_volume_cost` if the potential provides it, and
+/// recomputing the full energy otherwise.
+pub(crate) fn move_volume_cost<P: GlobalPotential + ?Sized>(
+    potential: &P,
+    configuration: &Configuration,
+    new_cell: &UnitCell,
+    new_positions: &[Vector3D],
+) -> f64 {
+    if let Some(cost) = potential.move_volume_cost(configuration, new_cell, new_positions) {
+        return cost;
+    }
+    let new_configuration = changed_configuration(configuration, new_cell, new_positions);
+    return potential.energy(&new_configuration) - potential.energy(configuration);
+}
+
+/// Get a copy of `configuration` using the `new_cell` and `new_positions`
+fn changed_configuration(
+    configuration: &Configuration,
+    new_cell: &UnitCell,
+    new_positions: &[Vector3D],
+) -> Configuration {
+    assert_eq!(new_positions.len(), configuration.size());
+    let mut new_configuration = configuration.clone();
+    new_configuration.cell = *new_cell;
+    for (position, new_position) in soa_zip!(new_configuration.particles_mut(), [mut position], new_positions) {
+        *position = *new_position;
+    }
+    return new_configuration;
+}
+
+mod thole;
+pub use self::thole::TholeDamping;
+
 mod wolf;
 pub use self::wolf::Wolf;
 
 mod ewald;
 pub use self::ewald::{Ewald, SharedEwald};
+
+mod combined;
+pub use self::combined::CombinedCoulomb;
+
+mod truncated;
+pub use self::truncated::TruncatedCoulomb;
+
+mod constant_force;
+pub use self::constant_force::ConstantForce;
+
+mod restraint;
+pub use self::restraint::{CenterOfMassRestraint, DistanceRestraint};
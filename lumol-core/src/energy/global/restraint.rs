@@ -0,0 +1,316 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use crate::{Configuration, UnitCell};
+use crate::{Matrix3, Vector3D};
+
+use super::{GlobalCache, GlobalPotential};
+
+/// Harmonic restraint on the distance between the centers of mass of two
+/// groups of particles.
+///
+/// The corresponding potential energy is $U = \frac{1}{2} k (d - d_0)^2$,
+/// where $d$ is the distance between the centers of mass of the two groups,
+/// using the minimum image convention. This potential is used as the biasing
+/// potential in umbrella sampling simulations.
+///
+/// The center of mass of each group is computed using the nearest image of
+/// each particle relative to the first particle of the group, so that groups
+/// crossing the boundaries of the cell are handled correctly. The extent of
+/// each group should be smaller than half of the cell for this to hold. The
+/// force acting on the center of mass of a group is distributed to its
+/// particles by mass fraction.
+///
+/// This potential is also available under the [`CenterOfMassRestraint`]
+/// name.
+///
+/// [`CenterOfMassRestraint`]: type.CenterOfMassRestraint.html
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::DistanceRestraint;
+/// # use lumol_core::sys::{System, Particle, Molecule, UnitCell};
+/// # use lumol_core::types::Vector3D;
+/// let mut system = System::with_cell(UnitCell::cubic(10.0));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.0, 0.0, 0.0))));
+///
+/// // Restrain the distance between the two particles around 2 A
+/// let restraint = DistanceRestraint::new(vec![0], vec![1], 4.0, 2.0);
+/// assert_eq!(restraint.distance(&system), 3.0);
+///
+/// system.add_global_potential(Box::new(restraint));
+/// assert_eq!(system.potential_energy(), 2.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DistanceRestraint {
+    /// Indexes of the particles in the first group
+    first: Vec<usize>,
+    /// Indexes of the particles in the second group
+    second: Vec<usize>,
+    /// Force constant of the restraint
+    k: f64,
+    /// Reference distance of the restraint
+    d0: f64,
+}
+
+impl DistanceRestraint {
+    /// Create a new `DistanceRestraint` between the centers of mass of the
+    /// particles at indexes `first` and the particles at indexes `second`,
+    /// with the force constant `k` and the reference distance `d0`.
+    ///
+    /// # Panics
+    ///
+    /// If any of the groups is empty, or if `k` or `d0` is negative.
+    pub fn new(first: Vec<usize>, second: Vec<usize>, k: f64, d0: f64) -> DistanceRestraint {
+        assert!(!first.is_empty() && !second.is_empty(), "groups can not be empty in distance restraint");
+        assert!(k >= 0.0, "force constant must be positive in distance restraint, got {}", k);
+        assert!(d0 >= 0.0, "reference distance must be positive in distance restraint, got {}", d0);
+        DistanceRestraint {
+            first: first,
+            second: second,
+            k: k,
+            d0: d0,
+        }
+    }
+
+    /// Get the force constant of this restraint
+    pub fn k(&self) -> f64 {
+        self.k
+    }
+
+    /// Get the reference distance of this restraint
+    pub fn d0(&self) -> f64 {
+        self.d0
+    }
+
+    /// Get the current distance between the centers of mass of the two
+    /// groups in the `configuration`.
+    pub fn distance(&self, configuration: &Configuration) -> f64 {
+        let positions = configuration.particles().position;
+        return self.separation(configuration, &configuration.cell, positions).norm();
+    }
+
+    /// Get the vector going from the center of mass of the first group to
+    /// the center of mass of the second group, using the given `positions`.
+    fn separation(&self, configuration: &Configuration, cell: &UnitCell, positions: &[Vector3D]) -> Vector3D {
+        let masses = configuration.particles().mass;
+        let center = |group: &[usize]| {
+            for &i in group {
+                assert!(
+                    i < configuration.size(),
+                    "can not restrain particle {}, the system only contains {} particles",
+                    i, configuration.size()
+                );
+            }
+
+            let mut total_mass = 0.0;
+            let mut center = Vector3D::zero();
+            let reference = positions[group[0]];
+            for &i in group {
+                let mut delta = positions[i] - reference;
+                cell.minimum_image(&mut delta);
+                total_mass += masses[i];
+                center += masses[i] * delta;
+            }
+            assert!(total_mass > 0.0, "the total mass of a group must be positive in distance restraint");
+            reference + center / total_mass
+        };
+
+        let mut separation = center(&self.second) - center(&self.first);
+        cell.minimum_image(&mut separation);
+        return separation;
+    }
+
+    /// Get the energy of the restraint with the given `cell` and `positions`
+    fn energy_at(&self, configuration: &Configuration, cell: &UnitCell, positions: &[Vector3D]) -> f64 {
+        let delta = self.separation(configuration, cell, positions).norm() - self.d0;
+        return 0.5 * self.k * delta * delta;
+    }
+
+    /// Get the force acting on the center of mass of the second group. The
+    /// force acting on the first group is the opposite of this one.
+    fn force(&self, configuration: &Configuration) -> Vector3D {
+        let positions = configuration.particles().position;
+        let separation = self.separation(configuration, &configuration.cell, positions);
+        let distance = separation.norm();
+        if distance == 0.0 {
+            return Vector3D::zero();
+        }
+        return -self.k * (distance - self.d0) / distance * separation;
+    }
+}
+
+/// Harmonic restraint on the distance between the centers of mass of two
+/// groups of particles, see [`DistanceRestraint`].
+///
+/// [`DistanceRestraint`]: struct.DistanceRestraint.html
+pub type CenterOfMassRestraint = DistanceRestraint;
+
+impl GlobalCache for DistanceRestraint {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let old_positions = configuration.particles().position;
+        let mut positions = old_positions.to_vec();
+        for (i, part_i) in configuration.molecule(molecule_id).indexes().enumerate() {
+            positions[part_i] = new_positions[i];
+        }
+
+        let cell = &configuration.cell;
+        return self.energy_at(configuration, cell, &positions) - self.energy_at(configuration, cell, old_positions);
+    }
+
+    fn move_volume_cost(
+        &self,
+        configuration: &Configuration,
+        new_cell: &UnitCell,
+        new_positions: &[Vector3D],
+    ) -> Option<f64> {
+        let old_positions = configuration.particles().position;
+        let new = self.energy_at(configuration, new_cell, new_positions);
+        return Some(new - self.energy_at(configuration, &configuration.cell, old_positions));
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for DistanceRestraint {
+    fn cutoff(&self) -> Option<f64> {
+        None
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let positions = configuration.particles().position;
+        return self.energy_at(configuration, &configuration.cell, positions);
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+        let force = self.force(configuration);
+        let masses = configuration.particles().mass;
+        for (group, force) in &[(&self.first, -force), (&self.second, force)] {
+            let total_mass = group.iter().map(|&i| masses[i]).sum::<f64>();
+            for &i in group.iter() {
+                forces[i] += masses[i] / total_mass * *force;
+            }
+        }
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        // The restraint is invariant by translation, and only depends on the
+        // separation between the centers of mass
+        let positions = configuration.particles().position;
+        let separation = self.separation(configuration, &configuration.cell, positions);
+        return self.force(configuration).tensorial(&separation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Molecule, Particle, System};
+
+    use approx::assert_relative_eq;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("H", Vector3D::new(1.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("O", Vector3D::new(0.0, 1.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(9.0, 2.0, 1.0))));
+        system
+    }
+
+    #[test]
+    fn energy_forces() {
+        let system = testing_system();
+        let restraint = DistanceRestraint::new(vec![0, 1], vec![2], 3.0, 2.0);
+
+        let masses = system.particles().mass;
+        let center = (masses[0] * Vector3D::new(1.0, 0.0, 0.0) + masses[1] * Vector3D::new(0.0, 1.0, 0.0)) / (masses[0] + masses[1]);
+        // Minimum image of the third particle
+        let distance = (Vector3D::new(-1.0, 2.0, 1.0) - center).norm();
+        assert_relative_eq!(restraint.distance(&system), distance, max_relative = 1e-12);
+        assert_relative_eq!(restraint.energy(&system), 1.5 * (distance - 2.0) * (distance - 2.0), max_relative = 1e-12);
+
+        // Forces are the derivatives of the energy
+        let mut forces = vec![Vector3D::zero(); 3];
+        restraint.forces(&system, &mut forces);
+        assert_relative_eq!((forces[0] + forces[1] + forces[2]).norm(), 0.0, epsilon = 1e-12);
+        assert!(restraint.is_translation_invariant());
+
+        let eps = 1e-6;
+        for i in 0..3 {
+            for direction in 0..3 {
+                let mut moved = system.clone();
+                moved.particles_mut().position[i][direction] += eps;
+                let finite = -(restraint.energy(&moved) - restraint.energy(&system)) / eps;
+                assert_relative_eq!(finite, forces[i][direction], epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_groups() {
+        // Two groups crossing the boundaries of the cell
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        for &x in &[9.5, 0.5, 2.5, 3.5] {
+            system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(x, 0.0, 0.0))));
+        }
+        let restraint = CenterOfMassRestraint::new(vec![0, 1], vec![2, 3], 2.0, 3.0);
+        assert_relative_eq!(restraint.distance(&system), 3.0, max_relative = 1e-12);
+        assert_relative_eq!(restraint.energy(&system), 0.0, epsilon = 1e-12);
+
+        // Pulling the second group stretches the restraint
+        for i in 2..4 {
+            system.particles_mut().position[i][0] += 1.0;
+        }
+        assert_relative_eq!(restraint.distance(&system), 4.0, max_relative = 1e-12);
+        assert_relative_eq!(restraint.energy(&system), 1.0, max_relative = 1e-12);
+
+        let mut forces = vec![Vector3D::zero(); 4];
+        restraint.forces(&system, &mut forces);
+        let total = forces.iter().fold(Vector3D::zero(), |total, &force| total + force);
+        assert_relative_eq!(total.norm(), 0.0, epsilon = 1e-12);
+
+        // The force is distributed by mass fraction, pushing the groups
+        // back to the reference distance
+        assert_relative_eq!(forces[0], Vector3D::new(1.0, 0.0, 0.0), epsilon = 1e-12);
+        assert_relative_eq!(forces[1], Vector3D::new(1.0, 0.0, 0.0), epsilon = 1e-12);
+        assert_relative_eq!(forces[2], Vector3D::new(-1.0, 0.0, 0.0), epsilon = 1e-12);
+        assert_relative_eq!(forces[3], Vector3D::new(-1.0, 0.0, 0.0), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn monte_carlo_costs() {
+        let system = testing_system();
+        let restraint = DistanceRestraint::new(vec![0, 1], vec![2], 3.0, 2.0);
+
+        let new_positions = [Vector3D::new(8.0, 3.0, 1.0)];
+        let mut moved = system.clone();
+        moved.particles_mut().position[2] = new_positions[0];
+        let cost = restraint.move_molecule_cost(&system, 2, &new_positions);
+        assert_relative_eq!(cost, restraint.energy(&moved) - restraint.energy(&system), max_relative = 1e-12);
+
+        let new_positions = system.particles().position.iter().map(|&r| 2.0 * r).collect::<Vec<_>>();
+        let cell = UnitCell::cubic(20.0);
+        let mut scaled = system.clone();
+        scaled.cell = cell;
+        scaled.particles_mut().position.copy_from_slice(&new_positions);
+        let cost = restraint.move_volume_cost(&system, &cell, &new_positions).unwrap();
+        assert_relative_eq!(cost, restraint.energy(&scaled) - restraint.energy(&system), max_relative = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not restrain particle 3, the system only contains 3 particles")]
+    fn out_of_bounds() {
+        let system = testing_system();
+        let restraint = DistanceRestraint::new(vec![0], vec![3], 1.0, 1.0);
+        let _ = restraint.energy(&system);
+    }
+}
@@ -0,0 +1,112 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+/// Thole damping of short-range electrostatic interactions.
+///
+/// Thole damping replaces point charges by smeared charge densities
+/// $\rho(r) = \frac{a^3}{8 \pi} e^{-a r}$, which screens the coulombic
+/// interaction at short distances and prevents the polarization catastrophe
+/// [Thole1981]. The $1 / r$ coulombic interaction becomes
+///
+/// $$ \frac{1}{r} \left[1 - \left(1 + \frac{a r}{2}\right) e^{-a r}\right],$$
+///
+/// which goes to the finite value of $a / 2$ at contact, and to the bare
+/// coulombic interaction at large distances.
+///
+/// This struct only computes the correction to the coulombic interaction,
+/// and is used by the [coulombic solvers][CoulombicPotential] to damp the
+/// real-space part of the interaction between pairs of charges.
+///
+/// [Thole1981]: Thole, B. T. Chem. Phys. 59, 341 (1981).
+/// [CoulombicPotential]: trait.CoulombicPotential.html
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::energy::TholeDamping;
+/// let damping = TholeDamping::new(2.0);
+/// assert_eq!(damping.a(), 2.0);
+///
+/// // The damped interaction is finite at contact
+/// let r = 1e-6;
+/// let damped = 1.0 / r + damping.energy(r);
+/// assert!(f64::abs(damped - 1.0) < 1e-5);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TholeDamping {
+    /// Damping parameter, in inverse distance units
+    a: f64,
+}
+
+impl TholeDamping {
+    /// Create a new Thole damping with the damping parameter `a`.
+    pub fn new(a: f64) -> TholeDamping {
+        assert!(a > 0.0, "Thole damping parameter must be positive, got {}", a);
+        TholeDamping { a: a }
+    }
+
+    /// Get the damping parameter of this Thole damping
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+
+    /// Get the correction to the $1 / r$ coulombic interaction at distance
+    /// `r`, i.e. $- \left(1 + a r / 2\right) e^{-a r} / r$. This should be
+    /// multiplied by $q_i q_j / 4 \pi \epsilon_0$ to get an energy.
+    #[inline]
+    pub fn energy(&self, r: f64) -> f64 {
+        let ar = self.a * r;
+        -(1.0 + 0.5 * ar) * f64::exp(-ar) / r
+    }
+
+    /// Get the correction to the norm of the $1 / r^2$ coulombic force at
+    /// distance `r`, i.e. the opposite of the derivative of `energy`. This
+    /// should be multiplied by $q_i q_j / 4 \pi \epsilon_0$ to get a force.
+    #[inline]
+    pub fn force(&self, r: f64) -> f64 {
+        let ar = self.a * r;
+        -(1.0 + ar + 0.5 * ar * ar) * f64::exp(-ar) / (r * r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn damping() {
+        let damping = TholeDamping::new(2.5);
+
+        // Approaches the undamped value at large distances
+        let r = 20.0;
+        assert!(f64::abs(damping.energy(r)) < 1e-15);
+        assert!(f64::abs(damping.force(r)) < 1e-15);
+
+        // Finite and reduced at contact
+        for &r in &[1e-4, 0.01, 0.1, 0.5, 1.0] {
+            let damped = 1.0 / r + damping.energy(r);
+            assert!(damped.is_finite());
+            assert!(damped > 0.0 && damped < 1.0 / r);
+            assert!(damped <= 2.5 / 2.0);
+
+            let damped = 1.0 / (r * r) + damping.force(r);
+            assert!(damped.is_finite());
+            assert!(damped > 0.0 && damped < 1.0 / (r * r));
+        }
+        assert_relative_eq!(1.0 / 1e-4 + damping.energy(1e-4), 1.25, epsilon = 1e-3);
+
+        // Force is the derivative of the energy
+        const EPS: f64 = 1e-9;
+        for &r in &[0.1, 0.5, 1.0, 2.0] {
+            let finite = (damping.energy(r) - damping.energy(r + EPS)) / EPS;
+            assert_relative_eq!(finite, damping.force(r), epsilon = 1e-6, max_relative = 1e-6);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Thole damping parameter must be positive, got -1")]
+    fn negative() {
+        let _ = TholeDamping::new(-1.0);
+    }
+}
@@ -0,0 +1,388 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+
+use crate::consts::FOUR_PI_EPSILON_0;
+use crate::{MoleculeHash, PairRestriction};
+use crate::utils::ThreadLocalVec;
+use crate::Configuration;
+use crate::{Matrix3, Vector3D};
+
+use super::{CoulombicPotential, GlobalCache, GlobalPotential, TholeDamping};
+use super::MoleculeRestrictions;
+
+/// Bare coulombic interactions, truncated at a given cutoff.
+///
+/// This directly sums the $q_i q_j / 4 \pi \epsilon_0 r_{ij}$ interactions
+/// between all the pairs of charges closer than the cutoff, using the nearest
+/// image of each pair in periodic systems. The energy is not shifted, and
+/// there is no correction for the interactions after the cutoff. This is
+/// usually a bad approximation of the electrostatic interactions, but can be
+/// used as a reference for small isolated systems, or to debug the other
+/// coulombic solvers.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::sys::{Particle, Molecule, System};
+/// # use lumol_core::energy::TruncatedCoulomb;
+/// # use lumol_core::types::Vector3D;
+/// # use lumol_core::consts::FOUR_PI_EPSILON_0;
+/// // An isolated NaCl pair
+/// let mut system = System::new();
+///
+/// let mut na = Particle::new("Na");
+/// na.charge = 1.0;
+/// system.add_molecule(Molecule::new(na));
+///
+/// let mut cl = Particle::new("Cl");
+/// cl.charge = -1.0;
+/// cl.position = Vector3D::new(2.0, 0.0, 0.0);
+/// system.add_molecule(Molecule::new(cl));
+///
+/// system.set_coulomb_potential(Box::new(TruncatedCoulomb::new(10.0))).unwrap();
+/// let expected = -1.0 / (2.0 * FOUR_PI_EPSILON_0);
+/// assert!(f64::abs(system.potential_energy() - expected) < 1e-12);
+/// ```
+#[derive(Clone)]
+pub struct TruncatedCoulomb {
+    /// Cutoff radius
+    cutoff: f64,
+    /// Restriction scheme
+    restriction: PairRestriction,
+    /// Restriction schemes overriding `restriction` inside some molecules
+    molecule_restrictions: BTreeMap<MoleculeHash, PairRestriction>,
+    /// Optional damping of the short-range interactions
+    damping: Option<TholeDamping>,
+}
+
+impl TruncatedCoulomb {
+    /// Create a new truncated coulombic potential, using a cutoff of
+    /// `cutoff`.
+    pub fn new(cutoff: f64) -> TruncatedCoulomb {
+        assert!(cutoff > 0.0, "Got a negative cutoff in TruncatedCoulomb");
+        TruncatedCoulomb {
+            cutoff: cutoff,
+            restriction: PairRestriction::None,
+            molecule_restrictions: BTreeMap::new(),
+            damping: None,
+        }
+    }
+
+    /// Get the restrictions to use for the pairs of particles in the
+    /// molecules of `configuration`
+    fn restrictions(&self, configuration: &Configuration) -> MoleculeRestrictions {
+        MoleculeRestrictions::new(configuration, self.restriction, &self.molecule_restrictions)
+    }
+
+    /// Compute the energy for the pair of particles with charges product
+    /// `qiqj`, at the distance `rij`.
+    #[inline]
+    fn energy_pair(&self, qiqj: f64, rij: f64) -> f64 {
+        if rij > self.cutoff {
+            0.0
+        } else {
+            let damping = self.damping.map_or(0.0, |damping| damping.energy(rij));
+            qiqj * (1.0 / rij + damping) / FOUR_PI_EPSILON_0
+        }
+    }
+
+    /// Compute the force over the distance for the pair of particles with
+    /// charges product `qiqj`, at the distance `rij`.
+    #[inline]
+    fn force_pair(&self, qiqj: f64, rij: f64) -> f64 {
+        if rij > self.cutoff {
+            0.0
+        } else {
+            let damping = self.damping.map_or(0.0, |damping| damping.force(rij));
+            qiqj * (1.0 / (rij * rij) + damping) / (rij * FOUR_PI_EPSILON_0)
+        }
+    }
+
+    /// Get the force acting on particle `i` from particle `j`, taking the
+    /// `restrictions` into account.
+    #[inline]
+    fn force(
+        &self,
+        configuration: &Configuration,
+        restrictions: &MoleculeRestrictions,
+        charges: &[f64],
+        i: usize,
+        j: usize,
+    ) -> Vector3D {
+        let info = restrictions.information(configuration, i, j);
+        if info.excluded {
+            return Vector3D::zero();
+        }
+        let rij = configuration.nearest_image(i, j);
+        return info.scaling * self.force_pair(charges[i] * charges[j], rij.norm()) * rij;
+    }
+}
+
+impl GlobalCache for TruncatedCoulomb {
+    fn move_molecule_cost(
+        &self,
+        configuration: &Configuration,
+        molecule_id: usize,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let mut old_energy = 0.0;
+        let mut new_energy = 0.0;
+
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
+        let positions = configuration.particles().position;
+
+        let molecule = configuration.molecule(molecule_id);
+        for (i, part_i) in molecule.indexes().enumerate() {
+            let qi = charges[part_i];
+            if qi == 0.0 {
+                continue;
+            }
+
+            for part_j in 0..configuration.size() {
+                let qj = charges[part_j];
+                let inside = molecule.contains(part_j);
+                // Pairs inside the moved molecule are only counted once
+                if qj == 0.0 || (inside && part_j <= part_i) {
+                    continue;
+                }
+
+                let info = restrictions.information(configuration, part_i, part_j);
+                if info.excluded {
+                    continue;
+                }
+
+                let new_position_j = if inside {
+                    new_positions[part_j - molecule.start()]
+                } else {
+                    positions[part_j]
+                };
+                let old_r = configuration.distance(part_i, part_j);
+                let new_r = configuration.cell.distance(&new_positions[i], &new_position_j);
+
+                old_energy += info.scaling * self.energy_pair(qi * qj, old_r);
+                new_energy += info.scaling * self.energy_pair(qi * qj, new_r);
+            }
+        }
+
+        return new_energy - old_energy;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+impl GlobalPotential for TruncatedCoulomb {
+    fn cutoff(&self) -> Option<f64> {
+        Some(self.cutoff)
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        let natoms = configuration.size();
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
+
+        let energies = (0..natoms).into_par_iter().map(|i| {
+            let qi = charges[i];
+            if qi == 0.0 {
+                return 0.0;
+            }
+
+            let mut energy = 0.0;
+            for j in i + 1..natoms {
+                let qj = charges[j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let info = restrictions.information(configuration, i, j);
+                if info.excluded {
+                    continue;
+                }
+
+                let rij = configuration.distance(i, j);
+                energy += info.scaling * self.energy_pair(qi * qj, rij);
+            }
+            return energy;
+        });
+        return energies.sum();
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        assert_eq!(forces.len(), configuration.size());
+
+        let natoms = configuration.size();
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
+        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+
+        (0..natoms).into_par_iter().for_each(|i| {
+            if charges[i] == 0.0 {
+                return;
+            }
+
+            let mut forces = thread_local_forces.borrow_mut();
+            for j in i + 1..natoms {
+                let force = self.force(configuration, &restrictions, &charges, i, j);
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        });
+
+        thread_local_forces.sum_into(forces)
+    }
+
+    fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let natoms = configuration.size();
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
+
+        let virials = (0..natoms).into_par_iter().map(|i| {
+            let mut local_virial = Matrix3::zero();
+            if charges[i] == 0.0 {
+                return local_virial;
+            }
+
+            for j in i + 1..natoms {
+                let force = self.force(configuration, &restrictions, &charges, i, j);
+                local_virial += force.tensorial(&configuration.nearest_image(i, j));
+            }
+            local_virial
+        });
+        return virials.sum();
+    }
+
+    fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
+        let virials = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
+            let mut local_virial = Matrix3::zero();
+            let ri = molecule_i.center_of_mass();
+
+            for molecule_j in configuration.molecules().skip(i + 1) {
+                let mut r_ij = ri - molecule_j.center_of_mass();
+                configuration.cell.minimum_image(&mut r_ij);
+
+                for part_a in molecule_i.indexes() {
+                    if charges[part_a] == 0.0 {
+                        continue;
+                    }
+
+                    for part_b in molecule_j.indexes() {
+                        let r_ab = configuration.nearest_image(part_a, part_b);
+                        let force = self.force(configuration, &restrictions, &charges, part_a, part_b);
+                        let w_ab = force.tensorial(&r_ab);
+                        local_virial += w_ab * (r_ab * r_ij) / r_ab.norm2();
+                    }
+                }
+            }
+            return local_virial;
+        });
+        return virials.sum();
+    }
+}
+
+impl CoulombicPotential for TruncatedCoulomb {
+    fn set_restriction(&mut self, restriction: PairRestriction) {
+        self.restriction = restriction;
+    }
+
+    fn set_molecule_restriction(&mut self, molecule: MoleculeHash, restriction: PairRestriction) {
+        let _ = self.molecule_restrictions.insert(molecule, restriction);
+    }
+
+    fn set_thole_damping(&mut self, damping: Option<TholeDamping>) {
+        self.damping = damping;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Molecule, Particle, System};
+    use crate::utils::system_from_xyz;
+
+    use approx::{assert_relative_eq, assert_ulps_eq};
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz(
+            "2
+            cell: 20.0
+            Cl 0.0 0.0 0.0
+            Na 1.5 0.0 0.0
+            ",
+        );
+        system.particles_mut().charge[0] = -1.0;
+        system.particles_mut().charge[1] = 1.0;
+        return system;
+    }
+
+    #[test]
+    fn energy() {
+        let mut system = testing_system();
+        let coulomb = TruncatedCoulomb::new(8.0);
+        assert_ulps_eq!(coulomb.energy(&system), -1.0 / (1.5 * FOUR_PI_EPSILON_0));
+
+        // Nothing after the cutoff
+        system.particles_mut().position[1] = Vector3D::new(9.0, 0.0, 0.0);
+        assert_eq!(coulomb.energy(&system), 0.0);
+        // Nearest image convention
+        system.particles_mut().position[1] = Vector3D::new(17.0, 0.0, 0.0);
+        assert_ulps_eq!(coulomb.energy(&system), -1.0 / (3.0 * FOUR_PI_EPSILON_0));
+    }
+
+    #[test]
+    fn derivatives() {
+        let mut system = testing_system();
+        system.add_molecule(Molecule::new(Particle::with_position("Cl", Vector3D::new(0.3, 2.0, 1.0))));
+        system.particles_mut().charge[2] = -0.5;
+        system.set_coulomb_potential(Box::new(TruncatedCoulomb::new(8.0))).unwrap();
+
+        let forces = system.forces();
+        let numerical = system.numerical_forces(1e-6);
+        for (force, numerical) in forces.iter().zip(&numerical) {
+            assert_relative_eq!(*force, *numerical, epsilon = 1e-6);
+        }
+
+        let pressure = system.pressure();
+        assert_relative_eq!(pressure, system.numerical_pressure(1e-3), max_relative = 1e-5);
+    }
+
+    #[test]
+    fn restriction() {
+        let mut system = testing_system();
+        assert!(system.add_bond(0, 1).is_empty());
+
+        let mut coulomb = TruncatedCoulomb::new(8.0);
+        coulomb.set_restriction(PairRestriction::InterMolecular);
+        assert_eq!(coulomb.energy(&system), 0.0);
+
+        let mut forces = vec![Vector3D::zero(); 2];
+        coulomb.forces(&system, &mut forces);
+        assert_eq!(forces[0], Vector3D::zero());
+
+        coulomb.set_restriction(PairRestriction::Scale14(0.5));
+        assert_eq!(coulomb.energy(&system), 0.0);
+        coulomb.set_restriction(PairRestriction::None);
+        assert_ulps_eq!(coulomb.energy(&system), -1.0 / (1.5 * FOUR_PI_EPSILON_0));
+    }
+
+    #[test]
+    fn move_molecule_cost() {
+        let mut system = testing_system();
+        system.add_molecule(Molecule::new(Particle::with_position("Cl", Vector3D::new(0.3, 2.0, 1.0))));
+        system.particles_mut().charge[2] = -0.5;
+        let coulomb = TruncatedCoulomb::new(8.0);
+
+        let old_energy = coulomb.energy(&system);
+        let new_positions = [Vector3D::new(2.5, -1.0, 0.5)];
+        let cost = coulomb.move_molecule_cost(&system, 1, &new_positions);
+
+        system.particles_mut().position[1] = new_positions[0];
+        assert_ulps_eq!(cost, coulomb.energy(&system) - old_energy, epsilon = 1e-12);
+    }
+}
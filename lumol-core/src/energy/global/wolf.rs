@@ -1,17 +1,19 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
+use std::collections::BTreeMap;
 use std::f64::consts::{PI, FRAC_2_SQRT_PI};
 
 use rayon::prelude::*;
 
 use crate::math::erfc;
 use crate::consts::FOUR_PI_EPSILON_0;
-use crate::PairRestriction;
+use crate::{MoleculeHash, PairRestriction};
 use crate::utils::ThreadLocalVec;
 use crate::Configuration;
 use crate::{Matrix3, Vector3D};
 
-use super::{CoulombicPotential, GlobalCache, GlobalPotential};
+use super::{CoulombicPotential, GlobalCache, GlobalPotential, TholeDamping};
+use super::MoleculeRestrictions;
 
 /// Wolf summation for coulombic interactions.
 ///
@@ -43,7 +45,7 @@ use super::{CoulombicPotential, GlobalCache, GlobalPotential};
 /// system.add_molecule(Molecule::new(cl));
 ///
 /// // Use Wolf summation for electrostatic interactions
-/// system.set_coulomb_potential(Box::new(wolf));
+/// system.set_coulomb_potential(Box::new(wolf)).unwrap();
 ///
 /// assert_eq!(system.potential_energy(), -0.0729290269539354);
 /// ```
@@ -61,6 +63,10 @@ pub struct Wolf {
     force_constant: f64,
     /// Restriction scheme
     restriction: PairRestriction,
+    /// Restriction schemes overriding `restriction` inside some molecules
+    molecule_restrictions: BTreeMap<MoleculeHash, PairRestriction>,
+    /// Optional damping of the short-range interactions
+    damping: Option<TholeDamping>,
 }
 
 impl Wolf {
@@ -80,9 +86,17 @@ impl Wolf {
             energy_constant: energy_constant,
             force_constant: force_constant,
             restriction: PairRestriction::None,
+            molecule_restrictions: BTreeMap::new(),
+            damping: None,
         }
     }
 
+    /// Get the restrictions to use for the pairs of particles in the
+    /// molecules of `configuration`
+    fn restrictions(&self, configuration: &Configuration) -> MoleculeRestrictions {
+        MoleculeRestrictions::new(configuration, self.restriction, &self.molecule_restrictions)
+    }
+
     /// Compute the energy for the pair of particles with charge `qi` and `qj`,
     /// at the distance of `rij`. The `scaling` parameter comes from the
     /// restriction associated with this potential.
@@ -91,7 +105,8 @@ impl Wolf {
         if rij > self.cutoff {
             0.0
         } else {
-            qiqj * (erfc(self.alpha * rij) / rij - self.energy_constant) / FOUR_PI_EPSILON_0
+            let damping = self.damping.map_or(0.0, |damping| damping.energy(rij));
+            qiqj * (erfc(self.alpha * rij) / rij - self.energy_constant + damping) / FOUR_PI_EPSILON_0
         }
     }
 
@@ -112,7 +127,8 @@ impl Wolf {
             let alpha_rij = self.alpha * rij;
             let exp_alpha_rij = f64::exp(-alpha_rij * alpha_rij);
             let factor = erfc(alpha_rij) / rij2 + self.alpha * FRAC_2_SQRT_PI * exp_alpha_rij / rij;
-            return qiqj * (factor - self.force_constant) / (rij * FOUR_PI_EPSILON_0);
+            let damping = self.damping.map_or(0.0, |damping| damping.force(rij));
+            return qiqj * (factor - self.force_constant + damping) / (rij * FOUR_PI_EPSILON_0);
         }
     }
 }
@@ -127,7 +143,8 @@ impl GlobalCache for Wolf {
         let mut old_energy = 0.0;
         let mut new_energy = 0.0;
 
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
         let positions = configuration.particles().position;
 
         // Iterate over all interactions between a particle in the moved
@@ -146,8 +163,7 @@ impl GlobalCache for Wolf {
                         continue;
                     }
 
-                    let path = configuration.bond_path(part_i, part_j);
-                    let info = self.restriction.information(path);
+                    let info = restrictions.information(configuration, part_i, part_j);
                     if info.excluded {
                         continue;
                     }
@@ -159,6 +175,26 @@ impl GlobalCache for Wolf {
                     new_energy += info.scaling * self.energy_pair(qi * qj, new_r);
                 }
             }
+
+            // Iterate over the interactions inside the moved molecule, in
+            // case it is not moved rigidly
+            for (j, part_j) in molecule.indexes().enumerate().skip(i + 1) {
+                let qj = charges[part_j];
+                if qj == 0.0 {
+                    continue;
+                }
+
+                let info = restrictions.information(configuration, part_i, part_j);
+                if info.excluded {
+                    continue;
+                }
+
+                let old_r = configuration.distance(part_i, part_j);
+                let new_r = configuration.cell.distance(&new_positions[i], &new_positions[j]);
+
+                old_energy += info.scaling * self.energy_pair(qi * qj, old_r);
+                new_energy += info.scaling * self.energy_pair(qi * qj, new_r);
+            }
         }
 
         return new_energy - old_energy;
@@ -176,7 +212,8 @@ impl GlobalPotential for Wolf {
 
     fn energy(&self, configuration: &Configuration) -> f64 {
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
 
         let energies = (0..natoms).into_par_iter().map(|i| {
             let mut energy = 0.0;
@@ -191,8 +228,7 @@ impl GlobalPotential for Wolf {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
                 if info.excluded {
                     continue;
                 }
@@ -210,7 +246,8 @@ impl GlobalPotential for Wolf {
         assert_eq!(forces.len(), configuration.size());
 
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
         // To avoid race conditions, each thread needs its own local forces Vec
         let thread_local_forces = ThreadLocalVec::with_size(natoms);
 
@@ -229,8 +266,7 @@ impl GlobalPotential for Wolf {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
                 if info.excluded {
                     continue;
                 }
@@ -250,7 +286,8 @@ impl GlobalPotential for Wolf {
 
     fn atomic_virial(&self, configuration: &Configuration) -> Matrix3 {
         let natoms = configuration.size();
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
 
         let virials = (0..natoms).into_par_iter().map(|i| {
             let qi = charges[i];
@@ -265,8 +302,7 @@ impl GlobalPotential for Wolf {
                     continue;
                 }
 
-                let path = configuration.bond_path(i, j);
-                let info = self.restriction.information(path);
+                let info = restrictions.information(configuration, i, j);
                 if info.excluded {
                     continue;
                 }
@@ -283,7 +319,8 @@ impl GlobalPotential for Wolf {
     }
 
     fn molecular_virial(&self, configuration: &Configuration) -> Matrix3 {
-        let charges = configuration.particles().charge;
+        let charges = configuration.coupled_charges();
+        let restrictions = self.restrictions(configuration);
         let virials = configuration.molecules().enumerate().par_bridge().map(|(i, molecule_i)| {
             let mut local_virial = Matrix3::zero();
             let ri = molecule_i.center_of_mass();
@@ -291,7 +328,7 @@ impl GlobalPotential for Wolf {
             for molecule_j in configuration.molecules().skip(i + 1) {
                 let rj = molecule_j.center_of_mass();
                 let mut r_ij = ri - rj;
-                configuration.cell.vector_image(&mut r_ij);
+                configuration.cell.minimum_image(&mut r_ij);
 
                 for part_a in molecule_i.indexes() {
                     let q_a = charges[part_a];
@@ -305,8 +342,7 @@ impl GlobalPotential for Wolf {
                             continue;
                         }
 
-                        let path = configuration.bond_path(part_a, part_b);
-                        let info = self.restriction.information(path);
+                        let info = restrictions.information(configuration, part_a, part_b);
                         if info.excluded {
                             continue;
                         }
@@ -328,13 +364,21 @@ impl CoulombicPotential for Wolf {
     fn set_restriction(&mut self, restriction: PairRestriction) {
         self.restriction = restriction;
     }
+
+    fn set_molecule_restriction(&mut self, molecule: MoleculeHash, restriction: PairRestriction) {
+        let _ = self.molecule_restrictions.insert(molecule, restriction);
+    }
+
+    fn set_thole_damping(&mut self, damping: Option<TholeDamping>) {
+        self.damping = damping;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     pub use super::*;
-    use crate::{System, Matrix3};
-    use crate::GlobalPotential;
+    use crate::{System, Matrix3, UnitCell};
+    use crate::{CoulombicPotential, GlobalPotential};
     use crate::utils::system_from_xyz;
 
     use approx::{assert_ulps_eq, assert_relative_eq};
@@ -438,6 +482,181 @@ mod tests {
         assert_relative_eq!(virial, finite_diff, epsilon = 1e-5);
     }
 
+    #[test]
+    fn numerical_derivatives() {
+        let mut system = testing_system();
+        system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap();
+
+        let forces = system.forces();
+        let numerical = system.numerical_forces(1e-6);
+        assert_relative_eq!(forces[0], numerical[0], epsilon = 1e-6);
+        assert_relative_eq!(forces[1], numerical[1], epsilon = 1e-6);
+
+        let pressure = system.pressure();
+        assert_relative_eq!(pressure, system.numerical_pressure(1e-3), max_relative = 1e-5);
+    }
+
+    #[test]
+    fn thole_damping() {
+        let mut system = testing_system();
+        let mut wolf = Wolf::new(8.0);
+        let undamped = wolf.energy(&system);
+
+        // Damping reduces the attraction between the charges
+        wolf.set_thole_damping(Some(TholeDamping::new(2.0)));
+        let damped = wolf.energy(&system);
+        assert!(damped > undamped);
+
+        // Finite and reduced energy at contact
+        system.particles_mut().position[1] = Vector3D::new(1e-5, 0.0, 0.0);
+        assert!(wolf.energy(&system).is_finite());
+        wolf.set_thole_damping(None);
+        let contact = wolf.energy(&system);
+        wolf.set_thole_damping(Some(TholeDamping::new(2.0)));
+        assert!(wolf.energy(&system) > contact);
+
+        // The damping vanishes at large separation
+        system.particles_mut().position[1] = Vector3D::new(7.5, 0.0, 0.0);
+        wolf.set_thole_damping(Some(TholeDamping::new(5.0)));
+        let damped = wolf.energy(&system);
+        wolf.set_thole_damping(None);
+        assert_ulps_eq!(damped, wolf.energy(&system), epsilon = 1e-12);
+
+        // Forces and virial are consistent with the energy
+        system.particles_mut().position[1] = Vector3D::new(0.6, 0.0, 0.0);
+        wolf.set_thole_damping(Some(TholeDamping::new(2.0)));
+        system.set_coulomb_potential(Box::new(wolf)).unwrap();
+        let forces = system.forces();
+        let numerical = system.numerical_forces(1e-6);
+        assert_relative_eq!(forces[0], numerical[0], epsilon = 1e-6);
+        assert_relative_eq!(forces[1], numerical[1], epsilon = 1e-6);
+
+        let pressure = system.pressure();
+        assert_relative_eq!(pressure, system.numerical_pressure(1e-3), max_relative = 1e-5);
+    }
+
+    #[test]
+    fn molecule_restrictions() {
+        use crate::{BondPath, Molecule, Particle};
+
+        // Two molecule types, with five particles bonded in a chain
+        let chain = |name: &str, origin: Vector3D| {
+            let mut particle = Particle::with_position(name, origin);
+            particle.charge = -0.3;
+            let mut molecule = Molecule::new(particle);
+            for i in 1..5 {
+                let mut particle = Particle::with_position(name, origin + Vector3D::new(1.2 * i as f64, 0.3 * (i % 2) as f64, 0.0));
+                particle.charge = if i % 2 == 0 { 0.5 } else { -0.5 };
+                molecule.add_particle_bonded_to(i - 1, particle);
+            }
+            molecule
+        };
+
+        let mut system = System::with_cell(UnitCell::cubic(30.0));
+        system.add_molecule(chain("A", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_molecule(chain("B", Vector3D::new(0.0, 4.0, 1.0)));
+
+        let mut wolf = Wolf::new(8.0);
+        wolf.set_restriction(PairRestriction::Exclude12);
+        // Exclude all intramolecular interactions in the first molecule
+        wolf.set_molecule_restriction(system.molecule(0).hash(), PairRestriction::InterMolecular);
+        // Scale the 1-4 interactions in the second molecule
+        wolf.set_molecule_restriction(system.molecule(1).hash(), PairRestriction::Scale14(0.5));
+
+        let charges = system.particles().charge;
+        let mut expected = 0.0;
+        for i in 0..system.size() {
+            expected -= wolf.energy_self(charges[i]);
+            for j in (i + 1)..system.size() {
+                let path = system.bond_path(i, j);
+                let scaling = if path == BondPath::None {
+                    1.0
+                } else if system.molecule_id(i) == 0 {
+                    0.0
+                } else {
+                    match path {
+                        BondPath::OneBond | BondPath::TwoBonds => 0.0,
+                        BondPath::ThreeBonds => 0.5,
+                        _ => 1.0,
+                    }
+                };
+                expected += scaling * wolf.energy_pair(charges[i] * charges[j], system.distance(i, j));
+            }
+        }
+        assert_ulps_eq!(wolf.energy(&system), expected, epsilon = 1e-12);
+
+        // Molecules without override use the default restriction
+        let mut default = wolf.clone();
+        default.molecule_restrictions.clear();
+        assert!(f64::abs(default.energy(&system) - expected) > 1e-4);
+
+        system.set_coulomb_potential(Box::new(wolf)).unwrap();
+        let forces = system.forces();
+        let numerical = system.numerical_forces(1e-6);
+        for (force, numerical) in forces.iter().zip(&numerical) {
+            assert_relative_eq!(*force, *numerical, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn triclinic_madelung() {
+        use crate::{Molecule, Particle};
+
+        // NaCl rock-salt crystal, with 4x4x4 conventional cubic cells
+        const LATTICE: f64 = 5.64;
+        let nacl = |cell: UnitCell| {
+            let mut system = System::with_cell(cell);
+            for i in 0..8 {
+                for j in 0..8 {
+                    for k in 0..8 {
+                        let mut particle = if (i + j + k) % 2 == 0 {
+                            let mut particle = Particle::new("Na");
+                            particle.charge = 1.0;
+                            particle
+                        } else {
+                            let mut particle = Particle::new("Cl");
+                            particle.charge = -1.0;
+                            particle
+                        };
+                        particle.position = 0.5 * LATTICE * Vector3D::new(f64::from(i), f64::from(j), f64::from(k));
+                        system.add_molecule(Molecule::new(particle));
+                    }
+                }
+            }
+            system
+        };
+
+        let length = 4.0 * LATTICE;
+        let cubic = nacl(UnitCell::cubic(length));
+        // The same periodic lattice, described with a strongly skewed cell.
+        // The cutoff is smaller than half the distance between opposite faces
+        // of both cells, so the same pairs should be included.
+        let triclinic = nacl(UnitCell::from_vectors(
+            Vector3D::new(length, 0.0, 0.0),
+            Vector3D::new(length, length, 0.0),
+            Vector3D::new(0.0, length, length),
+        ));
+        assert_relative_eq!(cubic.volume(), triclinic.volume(), max_relative = 1e-12);
+
+        let wolf = Wolf::new(6.0);
+        let energy = wolf.energy(&cubic);
+        assert!(energy < 0.0);
+        assert_relative_eq!(wolf.energy(&triclinic), energy, max_relative = 1e-10);
+
+        let mut cubic_forces = vec![Vector3D::zero(); cubic.size()];
+        wolf.forces(&cubic, &mut cubic_forces);
+        let mut triclinic_forces = vec![Vector3D::zero(); triclinic.size()];
+        wolf.forces(&triclinic, &mut triclinic_forces);
+        for (force, expected) in triclinic_forces.iter().zip(&cubic_forces) {
+            assert_relative_eq!(*force, *expected, epsilon = 1e-10);
+        }
+
+        let virial = wolf.atomic_virial(&cubic);
+        assert_relative_eq!(wolf.atomic_virial(&triclinic), virial, epsilon = 1e-10, max_relative = 1e-10);
+        let virial = wolf.molecular_virial(&cubic);
+        assert_relative_eq!(wolf.molecular_virial(&triclinic), virial, epsilon = 1e-10, max_relative = 1e-10);
+    }
+
     mod cache {
         use super::*;
         use crate::{CoulombicPotential, GlobalCache, GlobalPotential, PairRestriction};
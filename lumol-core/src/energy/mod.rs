@@ -50,7 +50,9 @@
 //! once. They are defined by implementing the [`GlobalPotential`]
 //! [GlobalPotential] trait. [`CoulombicPotential`][CoulombicPotential] are a
 //! specific version of global potentials used to compute electrostatic
-//! interactions.
+//! interactions. [`ConstantForce`][ConstantForce] is a global potential
+//! applying an external force to some particles, for steered molecular
+//! dynamics.
 //!
 //! [Potential]: trait.Potential.html
 //! [PairPotential]: trait.PairPotential.html
@@ -59,6 +61,7 @@
 //! [DihedralPotential]: trait.DihedralPotential.html
 //! [GlobalPotential]: trait.GlobalPotential.html
 //! [CoulombicPotential]: trait.CoulombicPotential.html
+//! [ConstantForce]: struct.ConstantForce.html
 use crate::{Matrix3, Vector3D};
 
 /// A potential for force and energy computations.
@@ -160,6 +163,20 @@ pub trait PairPotential: Potential + BoxClonePair {
     /// If this integral does not converge for the current potential, this
     /// function should then return 0.0 to disable tail corrections.
     fn tail_virial(&self, cutoff: f64) -> f64;
+
+    /// Set the coupling parameter $\lambda$ of this potential, used in free
+    /// energy computations. $\lambda = 0$ corresponds to a fully decoupled
+    /// interaction, and $\lambda = 1$ to the full interaction. The default
+    /// implementation does nothing, for potentials not depending on
+    /// $\lambda$.
+    fn set_lambda(&mut self, _lambda: f64) {}
+
+    /// Compute the derivative of the energy with respect to the coupling
+    /// parameter $\lambda$ at the distance `r`. The default implementation
+    /// returns 0, for potentials not depending on $\lambda$.
+    fn d_energy_d_lambda(&self, _r: f64) -> f64 {
+        0.0
+    }
 }
 impl_box_clone!(PairPotential, BoxClonePair, box_clone_pair);
 
@@ -240,8 +257,11 @@ impl_box_clone!(DihedralPotential, BoxCloneDihedral, box_clone_dihedral);
 
 mod functions;
 pub use self::functions::{BornMayerHuggins, Buckingham, Gaussian, Morse, Torsion};
-pub use self::functions::{CosineHarmonic, Harmonic, LennardJones, NullPotential};
-pub use self::functions::Mie;
+pub use self::functions::{CosineHarmonic, Harmonic, HarmonicDihedral, LennardJones, NullPotential};
+pub use self::functions::{LennardJones1264, Mie, SoftCoreLennardJones};
+
+mod gay_berne;
+pub use self::gay_berne::{GayBerne, GayBerneInteraction};
 
 mod computations;
 pub use self::computations::{Computation, TableComputation};
@@ -251,7 +271,9 @@ pub use self::restrictions::{PairRestriction, RestrictionInfo, BondPath};
 
 mod global;
 pub use self::global::{CoulombicPotential, GlobalCache, GlobalPotential};
-pub use self::global::{Ewald, SharedEwald, Wolf};
+pub use self::global::{CombinedCoulomb, Ewald, SharedEwald, TholeDamping, TruncatedCoulomb, Wolf};
+pub use self::global::{CenterOfMassRestraint, ConstantForce, DistanceRestraint};
+pub(crate) use self::global::move_volume_cost;
 
 mod pairs;
 pub use self::pairs::PairInteraction;
@@ -164,6 +164,29 @@ impl PairInteraction {
     pub fn cutoff(&self) -> f64 {
         self.cutoff
     }
+
+    /// Set the coupling parameter $\lambda$ of the potential in this
+    /// interaction, updating the energy shift if needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::SoftCoreLennardJones;
+    ///
+    /// let potential = Box::new(SoftCoreLennardJones::new(1.0, 2.0, 0.5));
+    /// let mut interaction = PairInteraction::shifted(potential, 2.0);
+    /// assert!(interaction.energy(1.2) < 0.0);
+    ///
+    /// interaction.set_lambda(0.0);
+    /// assert_eq!(interaction.energy(1.2), 0.0);
+    /// ```
+    pub fn set_lambda(&mut self, lambda: f64) {
+        self.potential.set_lambda(lambda);
+        if let PairComputation::Shifted(_) = self.computation {
+            self.computation = PairComputation::Shifted(self.potential.energy(self.cutoff));
+        }
+    }
 }
 
 impl PairInteraction {
@@ -194,6 +217,37 @@ impl PairInteraction {
         }
     }
 
+    /// Get the derivative of the energy for this pair interaction with
+    /// respect to the coupling parameter $\lambda$ at the distance `r`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::{Harmonic, SoftCoreLennardJones};
+    ///
+    /// let potential = Box::new(Harmonic{x0: 0.5, k: 4.2});
+    /// let interaction = PairInteraction::new(potential, 2.0);
+    /// assert_eq!(interaction.d_energy_d_lambda(1.0), 0.0);
+    ///
+    /// let potential = Box::new(SoftCoreLennardJones::new(1.0, 2.0, 0.5));
+    /// let interaction = PairInteraction::new(potential, 2.0);
+    /// assert!(interaction.d_energy_d_lambda(1.5) < 0.0);
+    /// // derivative at and after the cutoff is zero
+    /// assert_eq!(interaction.d_energy_d_lambda(2.0), 0.0);
+    /// ```
+    pub fn d_energy_d_lambda(&self, r: f64) -> f64 {
+        if r >= self.cutoff {
+            0.0
+        } else {
+            let derivative = self.potential.d_energy_d_lambda(r);
+            match self.computation {
+                PairComputation::Cutoff => derivative,
+                PairComputation::Shifted(_) => derivative - self.potential.d_energy_d_lambda(self.cutoff),
+            }
+        }
+    }
+
     /// Get the norm of the force for this pair interaction at the distance `r`.
     ///
     /// # Examples
@@ -217,6 +271,41 @@ impl PairInteraction {
         }
     }
 
+    /// Sample the energy and force of this pair interaction at `n` evenly
+    /// spaced distances between `r_min` and `r_max` (both included). The
+    /// returned vector contains `(r, energy, force)` tuples, which can be used
+    /// to plot and check the shape of the potential.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is smaller than 2, or if `r_min` is not smaller than `r_max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lumol_core::energy::PairInteraction;
+    /// use lumol_core::energy::Harmonic;
+    ///
+    /// let potential = Box::new(Harmonic{x0: 0.5, k: 4.2});
+    /// let interaction = PairInteraction::new(potential, 2.0);
+    ///
+    /// let samples = interaction.sample(0.5, 2.5, 5);
+    /// assert_eq!(samples.len(), 5);
+    /// assert_eq!(samples[0], (0.5, 0.0, 0.0));
+    /// assert_eq!(samples[1], (1.0, 0.525, -2.1));
+    /// // energy and force after the cutoff are zero
+    /// assert_eq!(samples[4], (2.5, 0.0, 0.0));
+    /// ```
+    pub fn sample(&self, r_min: f64, r_max: f64, n: usize) -> Vec<(f64, f64, f64)> {
+        assert!(n >= 2, "can not sample a pair interaction with less than 2 points");
+        assert!(r_min < r_max, "r_min must be smaller than r_max when sampling a pair interaction");
+        let dr = (r_max - r_min) / (n - 1) as f64;
+        (0..n).map(|i| {
+            let r = r_min + i as f64 * dr;
+            (r, self.energy(r), self.force(r))
+        }).collect()
+    }
+
     /// Get the virial contribution for this pair interaction at the distance
     /// `r`.
     ///
@@ -358,4 +447,31 @@ mod tests {
         assert_eq!(pairs.tail_energy(), -0.041663275824652776);
         assert_ulps_eq!(pairs.tail_virial().trace(), -0.24995930989583334);
     }
+
+    #[test]
+    fn sample() {
+        let lj = LennardJones {
+            sigma: 3.4,
+            epsilon: 2.0,
+        };
+        let pairs = PairInteraction::new(Box::new(lj), 12.0);
+
+        let samples = pairs.sample(3.0, 13.0, 10001);
+        assert_eq!(samples.len(), 10001);
+        assert_eq!(samples[0].0, 3.0);
+        assert_ulps_eq!(samples[10000].0, 13.0);
+        for &(r, energy, force) in &samples {
+            assert_eq!(energy, pairs.energy(r));
+            assert_eq!(force, pairs.force(r));
+        }
+
+        // The energy minimum is at 2^(1/6) sigma, where the force changes sign
+        let minimum = samples.iter().cloned().fold((0.0, f64::INFINITY, 0.0), |min, sample| {
+            if sample.1 < min.1 { sample } else { min }
+        });
+        let expected = f64::powf(2.0, 1.0 / 6.0) * 3.4;
+        assert!(f64::abs(minimum.0 - expected) <= 1e-3);
+        assert!(f64::abs(minimum.1 + 2.0) < 1e-5);
+        assert!(samples.iter().all(|&(r, _, force)| r > expected || force > 0.0));
+    }
 }
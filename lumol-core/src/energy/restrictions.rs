@@ -47,6 +47,9 @@ pub enum BondPath {
     ThreeBonds,
     /// The two particles are in the same molecule and separated by more than three bonds
     Far,
+    /// The pair of particles was explicitly excluded from the non-bonded
+    /// interactions, and is excluded regardless of the restriction.
+    Excluded,
 }
 
 /// Restriction information attached to a pair of `Particles` in a `System`.
@@ -81,8 +84,19 @@ impl PairRestriction {
     /// assert_eq!(restriction.information(BondPath::ThreeBonds).excluded, false);
     /// assert_eq!(restriction.information(BondPath::TwoBonds).scaling, 1.0);
     /// assert_eq!(restriction.information(BondPath::ThreeBonds).scaling, 0.5);
+    ///
+    /// // Explicitly excluded pairs are always excluded
+    /// let restriction = PairRestriction::None;
+    /// assert_eq!(restriction.information(BondPath::Excluded).excluded, true);
     /// ```
     pub fn information(&self, path: BondPath) -> RestrictionInfo {
+        if path == BondPath::Excluded {
+            return RestrictionInfo {
+                excluded: true,
+                scaling: 1.0,
+            };
+        }
+
         let are_in_same_molecule = path != BondPath::None;
         let excluded = match *self {
             PairRestriction::None => false,
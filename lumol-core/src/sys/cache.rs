@@ -7,10 +7,12 @@
 //! In most of Monte Carlo moves, only a very small subset of the system changes.
 //! We can use that property to remove the need of recomputing most of the
 //! energy components, by storing them and providing update callbacks.
+use std::f64::consts::PI;
 use std::mem;
 
-use crate::System;
+use crate::{BondPath, Composition, Particle, System, UnitCell};
 use crate::{Array2, Vector3D};
+use crate::energy::move_volume_cost;
 
 /// Callback for updating a cache. It also take an `&mut System` argument for
 /// updating the cache inside the global potentials.
@@ -18,8 +20,60 @@ type UpdateCallback = Box<dyn Fn(&mut EnergyCache, &mut System) + Send + Sync>;
 
 /// This is a cache for energy computation.
 ///
-/// Cache integrity is left up to the user of this structure: any function with
-/// `update_` prefix must be called as needed to ensure cache consistency.
+/// Cache integrity is left up to the user of this structure. The cache is
+/// used with the following lifecycle:
+///
+/// 1. the cache is initialized with [`EnergyCache::init`][init], and is then
+///    only usable with the same system;
+/// 2. a trial move is proposed by calling one of the `*_cost` functions, which
+///    return the energy difference between the trial configuration and the
+///    current one. The system is not modified by these functions;
+/// 3. if the move is accepted, the system must be modified accordingly and
+///    [`EnergyCache::update`][update] must be called with the updated system
+///    to commit the pending changes in the cache;
+/// 4. if the move is rejected, the system must be left unchanged and
+///    [`EnergyCache::reject`][reject] should be called to discard the pending
+///    changes.
+///
+/// Only one trial move can be pending at a time: calling a `*_cost` function
+/// replaces any previous pending change. If the system is modified without
+/// going through a `*_cost` function, [`EnergyCache::unused`][unused] must be
+/// called, followed by `EnergyCache::update`.
+///
+/// [init]: struct.EnergyCache.html#method.init
+/// [update]: struct.EnergyCache.html#method.update
+/// [reject]: struct.EnergyCache.html#method.reject
+/// [unused]: struct.EnergyCache.html#method.unused
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::{System, Molecule, Particle, UnitCell, Vector3D};
+/// # use lumol_core::{EnergyCache, PairInteraction, LennardJones};
+/// let mut system = System::with_cell(UnitCell::cubic(10.0));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", [0.0, 0.0, 0.0].into())));
+/// system.add_molecule(Molecule::new(Particle::with_position("Ar", [0.0, 0.0, 3.0].into())));
+/// let lj = Box::new(LennardJones{sigma: 3.4, epsilon: 1.0});
+/// system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 4.0)).unwrap();
+///
+/// let mut cache = EnergyCache::new();
+/// cache.init(&system);
+///
+/// // Propose to move the second molecule, and reject the move
+/// let new_positions = [Vector3D::new(0.0, 0.0, 3.5)];
+/// let delta = cache.move_molecule_cost(&system, 1, &new_positions);
+/// assert!(delta < 0.0);
+/// cache.reject();
+///
+/// // Propose the same move, and accept it this time
+/// let delta = cache.move_molecule_cost(&system, 1, &new_positions);
+/// let old_energy = cache.energy();
+/// system.particles_mut().position[1] = new_positions[0];
+/// cache.update(&mut system);
+///
+/// assert!(f64::abs(cache.energy() - (old_energy + delta)) < 1e-12);
+/// assert!(f64::abs(cache.energy() - system.potential_energy()) < 1e-12);
+/// ```
 pub struct EnergyCache {
     /// 2-D array containing the pairs interactions between particles `i` and
     /// `j` at index `i, j`
@@ -40,6 +94,11 @@ pub struct EnergyCache {
     global: f64,
     /// Callback to be called to update the cache if the system is modified
     updater: Option<UpdateCallback>,
+    /// Minimal distance between particles in different molecules. Moves
+    /// bringing two particles closer than this have an infinite cost.
+    hard_core: f64,
+    /// Generation of the system interactions used to compute the cache
+    generation: u64,
 }
 
 impl EnergyCache {
@@ -55,9 +114,21 @@ impl EnergyCache {
             coulomb: 0.0,
             global: 0.0,
             updater: None,
+            hard_core: 0.0,
+            generation: 0,
         }
     }
 
+    /// Set the hard-core distance used to reject overlapping configurations
+    /// early. When a trial move brings two particles in different molecules
+    /// closer than `distance`, the `*_cost` functions return `+infinity`
+    /// without computing the remaining energy terms. The default value of 0
+    /// disables this check.
+    pub fn set_hard_core(&mut self, distance: f64) {
+        assert!(distance >= 0.0, "hard-core distance must be positive, got {}", distance);
+        self.hard_core = distance;
+    }
+
     /// Clear all values in the cache by setting them to 0
     fn clear(&mut self) {
         self.pairs_cache.fill(0.0);
@@ -75,6 +146,7 @@ impl EnergyCache {
     /// the associated system, one must call this function again.
     pub fn init(&mut self, system: &System) {
         self.clear();
+        self.generation = system.interactions_generation();
         self.pairs_cache.resize_if_different((system.size(), system.size()));
 
         let evaluator = system.energy_evaluator();
@@ -98,6 +170,14 @@ impl EnergyCache {
         self.global = evaluator.global();
     }
 
+    /// Check if this cache is outdated with respect to the interactions of
+    /// the `system`, for example after a change of the coupling parameter
+    /// with `System::set_lambda`. Outdated caches should be re-initialized
+    /// with [`EnergyCache::init`](#method.init).
+    pub fn is_outdated(&self, system: &System) -> bool {
+        self.generation != system.interactions_generation()
+    }
+
     /// Get the cached energy
     pub fn energy(&self) -> f64 {
         let mut energy = 0.0;
@@ -115,7 +195,12 @@ impl EnergyCache {
     }
 
     /// Update the cache after a call to a `EnergyCache::*_cost` function or
-    /// `EnergyCache::unused`.
+    /// `EnergyCache::unused`. The `system` must already contain the new
+    /// configuration corresponding to the accepted move.
+    ///
+    /// # Panics
+    ///
+    /// If there is no pending change in the cache.
     pub fn update(&mut self, system: &mut System) {
         let updater = mem::replace(&mut self.updater, None);
         if let Some(updater) = updater {
@@ -127,6 +212,20 @@ impl EnergyCache {
         }
     }
 
+    /// Discard the pending changes from the last call to a
+    /// `EnergyCache::*_cost` function, when the corresponding move is
+    /// rejected. The cache is left unchanged, and still correspond to the
+    /// system before the move.
+    pub fn reject(&mut self) {
+        self.updater = None;
+    }
+
+    /// Check if there are pending changes in this cache, waiting for a call
+    /// to `EnergyCache::update` or `EnergyCache::reject`.
+    pub fn is_pending(&self) -> bool {
+        self.updater.is_some()
+    }
+
     /// This function should be called whenever the cache is not used, but one
     /// still want it to be updated. Future call to `EnergyCache::update` will
     /// recompute the full cache.
@@ -138,9 +237,16 @@ impl EnergyCache {
 }
 
 impl EnergyCache {
-    /// Get the cost of moving a rigid molecule at `molecule_id` in the system
+    /// Get the cost of moving the molecule at `molecule_id` in the system
     /// to `new_positions`.
     ///
+    /// The molecule does not need to be moved rigidly: the intramolecular
+    /// pairs, bonds, angles and dihedral angles energies are also updated.
+    /// If the moved molecule overlaps with another one (see
+    /// [`EnergyCache::set_hard_core`][set_hard_core]), the cost is infinite.
+    ///
+    /// [set_hard_core]: struct.EnergyCache.html#method.set_hard_core
+    ///
     /// This function ***DOES NOT*** update the cache, the `update` function
     /// MUST be called if the particles are effectively moved.
     pub fn move_molecule_cost(
@@ -162,6 +268,9 @@ impl EnergyCache {
             for (_, other_molecule) in system.molecules().enumerate().filter(|(id, _)| molecule_id != *id) {
                 for part_j in other_molecule.indexes() {
                     let r = system.cell.distance(&positions[part_j], &new_positions[i]);
+                    if r < self.hard_core {
+                        return self.overlap();
+                    }
                     let path = system.bond_path(part_i, part_j);
                     let energy = evaluator.pair(path, r, part_i, part_j);
 
@@ -174,9 +283,52 @@ impl EnergyCache {
             }
         }
 
+        // Iterate over all interactions between particles in the moved
+        // molecule. The corresponding energy is unchanged for rigid moves.
+        for (i, part_i) in molecule.indexes().enumerate() {
+            for (j, part_j) in molecule.indexes().enumerate().skip(i + 1) {
+                let r = system.cell.distance(&new_positions[i], &new_positions[j]);
+                let path = system.bond_path(part_i, part_j);
+                let energy = evaluator.pair(path, r, part_i, part_j);
+
+                pairs_delta += energy - self.pairs_cache[(part_i, part_j)];
+                new_pairs[(part_i, part_j)] = energy;
+                new_pairs[(part_j, part_i)] = energy;
+            }
+        }
+
         // Pairs tail correction do not change when moving a single molecule
 
-        // Bonds / Angles / Dihedrals terms do not change
+        // Bonds / Angles / Dihedrals terms only change if the molecule is
+        // deformed
+        let first = molecule.start();
+        let new_position = |i: usize| &new_positions[i - first];
+
+        let mut bonds_delta = 0.0;
+        for bond in molecule.bonds() {
+            let (i, j) = (bond.i(), bond.j());
+            let old_r = system.nearest_image(i, j).norm();
+            let new_r = system.cell.distance(new_position(i), new_position(j));
+            bonds_delta += evaluator.bond(new_r, i, j) - evaluator.bond(old_r, i, j);
+        }
+
+        let mut angles_delta = 0.0;
+        for angle in molecule.angles() {
+            let (i, j, k) = (angle.i(), angle.j(), angle.k());
+            let old_theta = system.angle(i, j, k);
+            let new_theta = system.cell.angle(new_position(i), new_position(j), new_position(k));
+            angles_delta += evaluator.angle(new_theta, i, j, k) - evaluator.angle(old_theta, i, j, k);
+        }
+
+        let mut dihedrals_delta = 0.0;
+        for dihedral in molecule.dihedrals() {
+            let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+            let old_phi = system.dihedral(i, j, k, m);
+            let new_phi = system.cell.dihedral(
+                new_position(i), new_position(j), new_position(k), new_position(m)
+            );
+            dihedrals_delta += evaluator.dihedral(new_phi, i, j, k, m) - evaluator.dihedral(old_phi, i, j, k, m);
+        }
 
         let coulomb_delta = system.coulomb_potential()
             .map_or(0.0, |coulomb| coulomb.move_molecule_cost(system, molecule_id, new_positions));
@@ -186,10 +338,13 @@ impl EnergyCache {
             global_delta += global.move_molecule_cost(system, molecule_id, new_positions);
         }
 
-        let cost = pairs_delta + coulomb_delta + global_delta;
+        let cost = pairs_delta + bonds_delta + angles_delta + dihedrals_delta + coulomb_delta + global_delta;
 
         self.updater = Some(Box::new(move |cache, system| {
             cache.pairs += pairs_delta;
+            cache.bonds += bonds_delta;
+            cache.angles += angles_delta;
+            cache.dihedrals += dihedrals_delta;
             cache.coulomb += coulomb_delta;
             cache.global += global_delta;
 
@@ -200,9 +355,6 @@ impl EnergyCache {
             let molecule = system.molecule(molecule_id);
             for i in molecule.indexes() {
                 for j in 0..n {
-                    if molecule.contains(j) {
-                        continue;
-                    }
                     cache.pairs_cache[(i, j)] = new_pairs[(i, j)];
                     cache.pairs_cache[(j, i)] = new_pairs[(i, j)];
                 }
@@ -232,7 +384,8 @@ impl EnergyCache {
     ///
     /// It **DOES NOT** recompute bonds, angles and dihedral interactions. You
     /// must not use this function when the intramolecular configuration
-    /// changed.
+    /// changed. As for `move_molecule_cost`, the cost is infinite if two
+    /// molecules overlap.
     ///
     /// This function ***DOES NOT*** update the cache, the `update` function
     /// MUST be called if the molecules are effectively moved.
@@ -248,6 +401,9 @@ impl EnergyCache {
                 for part_i in mol_i.indexes() {
                     for part_j in mol_j.indexes() {
                         let r = system.distance(part_i, part_j);
+                        if r < self.hard_core {
+                            return self.overlap();
+                        }
                         let path = system.bond_path(part_i, part_j);
                         let energy = evaluator.pair(path, r, part_i, part_j);
                         pairs_delta += energy;
@@ -275,21 +431,251 @@ impl EnergyCache {
             cache.coulomb = new_coulomb;
             cache.global = new_global;
 
-            let (n, m) = new_pairs.dim();
-            debug_assert_eq!(n, m);
-            debug_assert_eq!((n, m), cache.pairs_cache.dim());
-            for (i, mol_i) in system.molecules().enumerate() {
-                for mol_j in system.molecules().skip(i + 1) {
-                    for part_i in mol_i.indexes() {
-                        for part_j in mol_j.indexes() {
-                            cache.pairs_cache[(part_i, part_j)] = new_pairs[(part_i, part_j)];
-                            cache.pairs_cache[(part_j, part_i)] = new_pairs[(part_i, part_j)];
+            update_intermolecular_pairs(&mut cache.pairs_cache, &new_pairs, system);
+        }));
+        cost
+    }
+
+    /// Return the cost for changing the simulation cell of the system to
+    /// `new_cell`, and moving all the particles to `new_positions`.
+    ///
+    /// This function is intended for use with volume changes in NPT Monte
+    /// Carlo, where `new_positions` are obtained by scaling the centers of
+    /// mass of the molecules according to the cell change, without changing
+    /// the intramolecular configuration. It recomputes:
+    ///
+    /// - non bonded pairs interactions between different molecules;
+    /// - the pairs tail correction, rescaled with the volume;
+    /// - Coulomb interactions;
+    /// - global interactions;
+    ///
+    /// It **DOES NOT** recompute bonds, angles, dihedrals and intramolecular
+    /// pairs interactions. You must not use this function when the
+    /// intramolecular configuration changed. Global potentials use
+    /// `GlobalCache::move_volume_cost` when they provide it, which can skip
+    /// terms that do not depend on the cell, and recompute their full energy
+    /// otherwise. As for `move_molecule_cost`, the cost is infinite if two
+    /// molecules overlap.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called if the system is effectively modified.
+    pub fn move_volume_cost(
+        &mut self,
+        system: &System,
+        new_cell: UnitCell,
+        new_positions: &[Vector3D],
+    ) -> f64 {
+        let evaluator = system.energy_evaluator();
+
+        let mut new_pairs = Array2::<f64>::zeros((system.size(), system.size()));
+        let mut pairs_delta = 0.0;
+        for (i, mol_i) in system.molecules().enumerate() {
+            for mol_j in system.molecules().skip(i + 1) {
+                for part_i in mol_i.indexes() {
+                    for part_j in mol_j.indexes() {
+                        let r = new_cell.distance(&new_positions[part_i], &new_positions[part_j]);
+                        if r < self.hard_core {
+                            return self.overlap();
                         }
+                        let path = system.bond_path(part_i, part_j);
+                        let energy = evaluator.pair(path, r, part_i, part_j);
+                        pairs_delta += energy - self.pairs_cache[(part_i, part_j)];
+                        new_pairs[(part_i, part_j)] = energy;
+                        new_pairs[(part_j, part_i)] = energy;
                     }
                 }
             }
+        }
+
+        // The tail correction only depends on the density
+        let pairs_tail = if new_cell.is_infinite() {
+            0.0
+        } else {
+            self.pairs_tail * system.volume() / new_cell.volume()
+        };
+
+        let coulomb_delta = system.coulomb_potential()
+            .map_or(0.0, |coulomb| move_volume_cost(coulomb, system, &new_cell, new_positions));
+
+        let mut global_delta = 0.0;
+        for global in system.global_potentials() {
+            global_delta += move_volume_cost(&**global, system, &new_cell, new_positions);
+        }
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail) + coulomb_delta + global_delta;
+
+        self.updater = Some(Box::new(move |cache, system| {
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+            cache.coulomb += coulomb_delta;
+            cache.global += global_delta;
+            update_intermolecular_pairs(&mut cache.pairs_cache, &new_pairs, system);
+
+            // Update the cache for the global potentials
+            if let Some(coulomb) = system.coulomb_potential() {
+                coulomb.update();
+            }
+
+            for global in system.global_potentials() {
+                global.update();
+            }
         }));
-        cost
+        return cost;
+    }
+
+    /// Get the cost of adding `particle` to the system, as a new molecule
+    /// containing only this particle.
+    ///
+    /// This function is intended for particles insertion and transfer moves.
+    /// Only the pairs interactions and the pairs tail correction are
+    /// recomputed, so the system must not contain Coulomb or global
+    /// interactions. As for `move_molecule_cost`, the cost is infinite if the
+    /// new particle overlaps with another one.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called after adding the particle at the end of the system
+    /// with `System::add_molecule`.
+    ///
+    /// # Panics
+    ///
+    /// If the system contains Coulomb or global interactions.
+    pub fn insert_particle_cost(&mut self, system: &System, particle: &Particle) -> f64 {
+        assert!(
+            system.coulomb_potential().is_none() && system.global_potentials().is_empty(),
+            "EnergyCache::insert_particle_cost only supports pair interactions"
+        );
+
+        let mut new_pairs = vec![0.0; system.size()];
+        let mut pairs_delta = 0.0;
+        let mut composition = system.composition();
+        if let Some(kind) = system.interactions().kind(&particle.name) {
+            for (j, (&other, position)) in system.particles().kind.iter().zip(system.particles().position).enumerate() {
+                let r = system.cell.distance(&particle.position, position);
+                if r < self.hard_core {
+                    return self.overlap();
+                }
+                if let Some(potential) = system.interactions().pair((kind, other)) {
+                    let info = potential.restriction().information(BondPath::None);
+                    if !info.excluded {
+                        let coupling = f64::sqrt(particle.coupling * system.particles().coupling[j]);
+                        let energy = info.scaling * coupling * potential.energy(r);
+                        new_pairs[j] = energy;
+                        pairs_delta += energy;
+                    }
+                }
+            }
+            composition.add_particle(kind);
+        }
+        let pairs_tail = pairs_tail(system, &composition);
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail);
+        self.updater = Some(Box::new(move |cache, system| {
+            let n = new_pairs.len();
+            debug_assert_eq!(system.size(), n + 1);
+            let mut pairs_cache = Array2::zeros((n + 1, n + 1));
+            for i in 0..n {
+                for j in 0..n {
+                    pairs_cache[(i, j)] = cache.pairs_cache[(i, j)];
+                }
+                pairs_cache[(i, n)] = new_pairs[i];
+                pairs_cache[(n, i)] = new_pairs[i];
+            }
+            cache.pairs_cache = pairs_cache;
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+        }));
+        return cost;
+    }
+
+    /// Get the cost of removing the molecule at `molecule_id` from the
+    /// system. This molecule must contain a single particle.
+    ///
+    /// This function is intended for particles deletion and transfer moves.
+    /// Only the pairs interactions and the pairs tail correction are
+    /// recomputed, so the system must not contain Coulomb or global
+    /// interactions.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called after removing the molecule with
+    /// `System::remove_molecule`.
+    ///
+    /// # Panics
+    ///
+    /// If the molecule contains more than one particle, or if the system
+    /// contains Coulomb or global interactions.
+    pub fn remove_particle_cost(&mut self, system: &System, molecule_id: usize) -> f64 {
+        assert!(
+            system.coulomb_potential().is_none() && system.global_potentials().is_empty(),
+            "EnergyCache::remove_particle_cost only supports pair interactions"
+        );
+        let molecule = system.molecule(molecule_id);
+        assert_eq!(molecule.size(), 1, "EnergyCache::remove_particle_cost only supports single particle molecules");
+        let removed = molecule.start();
+
+        let pairs_delta = -(0..system.size()).map(|j| self.pairs_cache[(removed, j)]).sum::<f64>();
+        let mut composition = system.composition();
+        composition.remove_particle(system.particles().kind[removed]);
+        let pairs_tail = pairs_tail(system, &composition);
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail);
+        self.updater = Some(Box::new(move |cache, system| {
+            let n = system.size();
+            let kept = (0..=n).filter(|&i| i != removed).collect::<Vec<_>>();
+            let mut pairs_cache = Array2::zeros((n, n));
+            for (i, &old_i) in kept.iter().enumerate() {
+                for (j, &old_j) in kept.iter().enumerate() {
+                    pairs_cache[(i, j)] = cache.pairs_cache[(old_i, old_j)];
+                }
+            }
+            cache.pairs_cache = pairs_cache;
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+        }));
+        return cost;
+    }
+
+    /// Get the cost of a move creating an overlap between two molecules. The
+    /// cache is fully recomputed if such a move is accepted anyway.
+    fn overlap(&mut self) -> f64 {
+        self.unused();
+        return f64::INFINITY;
+    }
+}
+
+/// Get the energy due to long range corrections for the pairs in `system`,
+/// using the given `composition` instead of the system composition
+fn pairs_tail(system: &System, composition: &Composition) -> f64 {
+    if system.cell.is_infinite() {
+        return 0.0;
+    }
+    let mut energy = 0.0;
+    let volume = system.volume();
+    for (i, ni) in composition.all_particles() {
+        for (j, nj) in composition.all_particles() {
+            let two_pi_density = 2.0 * PI * (ni as f64) * (nj as f64) / volume;
+            if let Some(potential) = system.interactions().pair((i, j)) {
+                energy += two_pi_density * potential.tail_energy();
+            }
+        }
+    }
+    return energy;
+}
+
+/// Copy the pairs energies between particles in different molecules of the
+/// `system` from `new_pairs` to `pairs_cache`
+fn update_intermolecular_pairs(pairs_cache: &mut Array2<f64>, new_pairs: &Array2<f64>, system: &System) {
+    let (n, m) = new_pairs.dim();
+    debug_assert_eq!(n, m);
+    debug_assert_eq!((n, m), pairs_cache.dim());
+    for (i, mol_i) in system.molecules().enumerate() {
+        for mol_j in system.molecules().skip(i + 1) {
+            for part_i in mol_i.indexes() {
+                for part_j in mol_j.indexes() {
+                    pairs_cache[(part_i, part_j)] = new_pairs[(part_i, part_j)];
+                    pairs_cache[(part_j, part_i)] = new_pairs[(part_i, part_j)];
+                }
+            }
+        }
     }
 }
 
@@ -297,7 +683,7 @@ impl EnergyCache {
 mod tests {
     use super::*;
     use crate::{Harmonic, LennardJones, NullPotential, Wolf};
-    use crate::PairInteraction;
+    use crate::{Molecule, PairInteraction};
     use crate::System;
     use crate::Vector3D;
     use crate::utils::system_from_xyz;
@@ -336,9 +722,9 @@ mod tests {
                 }),
                 3.0,
             ),
-        );
+        ).unwrap();
 
-        system.set_pair_potential(("O", "O"), PairInteraction::new(Box::new(NullPotential), 3.0));
+        system.set_pair_potential(("O", "O"), PairInteraction::new(Box::new(NullPotential), 3.0)).unwrap();
 
         system.set_pair_potential(
             ("O", "H"),
@@ -349,7 +735,7 @@ mod tests {
                 }),
                 3.0,
             ),
-        );
+        ).unwrap();
 
         system.set_bond_potential(
             ("O", "O"),
@@ -383,7 +769,7 @@ mod tests {
             }),
         );
 
-        system.set_coulomb_potential(Box::new(Wolf::new(5.0)));
+        system.set_coulomb_potential(Box::new(Wolf::new(5.0))).unwrap();
 
         for particle in system.particles_mut() {
             if particle.name == "O" {
@@ -404,6 +790,19 @@ mod tests {
         assert_ulps_eq!(cache.energy(), system.potential_energy());
     }
 
+    #[test]
+    fn outdated() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        assert!(!cache.is_outdated(&system));
+
+        system.set_lambda(0.5);
+        assert!(cache.is_outdated(&system));
+        cache.init(&system);
+        assert!(!cache.is_outdated(&system));
+    }
+
     #[test]
     #[allow(clippy::unreadable_literal)]
     fn move_molecule() {
@@ -448,6 +847,193 @@ mod tests {
         assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
     }
 
+    #[test]
+    fn move_molecule_non_rigid() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        let old_energy = system.potential_energy();
+        cache.init(&system);
+
+        // Only move some of the atoms in the molecule, changing all the
+        // bonds, angles and dihedral angles energies
+        let mut new_positions = system.molecule(0).particles().position.to_vec();
+        new_positions[0] += Vector3D::new(0.1, 0.3, -0.2);
+        new_positions[3] += Vector3D::new(-0.2, 0.5, 0.1);
+        let cost = cache.move_molecule_cost(&system, 0, &new_positions);
+
+        for (position, new) in system.molecule_mut(0).particles_mut().position.iter_mut().zip(&new_positions) {
+            *position = *new;
+        }
+        let new_energy = system.potential_energy();
+        assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+
+        cache.update(&mut system);
+        assert_relative_eq!(cache.energy(), new_energy, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn hard_core() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.set_hard_core(0.8);
+        cache.init(&system);
+        let old_energy = cache.energy();
+
+        // Move the second molecule on top of the first one
+        let new_positions = system.molecule(1).particles().position.iter()
+            .map(|position| position - Vector3D::new(2.5, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        let cost = cache.move_molecule_cost(&system, 1, &new_positions);
+        assert_eq!(cost, f64::INFINITY);
+        assert_eq!(f64::exp(-cost), 0.0);
+        cache.reject();
+        assert_eq!(cache.energy(), old_energy);
+
+        // Moves without overlap are computed as usual
+        let new_positions = system.molecule(1).particles().position.iter()
+            .map(|position| position + Vector3D::new(0.5, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        let cost = cache.move_molecule_cost(&system, 1, &new_positions);
+        assert!(cost.is_finite());
+
+        // Accepting an overlapping move anyway recomputes the whole cache
+        let new_positions = system.molecule(1).particles().position.iter()
+            .map(|position| position - Vector3D::new(2.5, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        let cost = cache.move_molecule_cost(&system, 1, &new_positions);
+        assert_eq!(cost, f64::INFINITY);
+        for (position, new) in system.molecule_mut(1).particles_mut().position.iter_mut().zip(&new_positions) {
+            *position = *new;
+        }
+        cache.update(&mut system);
+        assert_relative_eq!(cache.energy(), system.potential_energy(), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn reject() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        let old_energy = cache.energy();
+        assert!(!cache.is_pending());
+
+        let mut new_positions = system.molecule(0).particles().position.to_vec();
+        for position in &mut new_positions {
+            *position += Vector3D::new(0.0, 0.5, 0.0);
+        }
+        let _ = cache.move_molecule_cost(&system, 0, &new_positions);
+        assert!(cache.is_pending());
+        cache.reject();
+        assert!(!cache.is_pending());
+        assert_eq!(cache.energy(), old_energy);
+
+        // The cache is still usable after a rejection
+        let cost = cache.move_molecule_cost(&system, 0, &new_positions);
+        for (position, new) in system.molecule_mut(0).particles_mut().position.iter_mut().zip(&new_positions) {
+            *position = *new;
+        }
+        cache.update(&mut system);
+        assert_relative_eq!(cache.energy(), old_energy + cost, max_relative = 1e-12);
+        assert_relative_eq!(cache.energy(), system.potential_energy(), max_relative = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "called EnergyCache::update without call a `*_cost` function first")]
+    fn update_after_reject() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        let positions = system.molecule(0).particles().position.to_vec();
+        let _ = cache.move_molecule_cost(&system, 0, &positions);
+        cache.reject();
+        cache.update(&mut system);
+    }
+
+    fn scaled_positions(system: &System, new_cell: &UnitCell) -> Vec<Vector3D> {
+        let mut new_positions = Vec::new();
+        for molecule in system.molecules() {
+            let com = molecule.center_of_mass();
+            let delta = new_cell.cartesian(&system.cell.fractional(&com)) - com;
+            for position in molecule.particles().position {
+                new_positions.push(position + delta);
+            }
+        }
+        return new_positions;
+    }
+
+    #[test]
+    fn move_volume() {
+        use crate::{CoulombicPotential, Ewald, Matrix3, SharedEwald, PairRestriction};
+
+        let mut ewald = SharedEwald::new(Ewald::new(4.0, 5, None));
+        ewald.set_restriction(PairRestriction::InterMolecular);
+
+        let mut systems = vec![testing_system(), testing_system()];
+        systems[1].set_coulomb_potential(Box::new(ewald)).unwrap();
+
+        for mut system in systems {
+            let mut cache = EnergyCache::new();
+            let old_energy = system.potential_energy();
+            cache.init(&system);
+
+            // Scale molecules centers of mass
+            let new_cell = system.cell.scale(Matrix3::one() * 1.05);
+            let new_positions = scaled_positions(&system, &new_cell);
+            let cost = cache.move_volume_cost(&system, new_cell, &new_positions);
+
+            let mut new_system = system.clone();
+            new_system.cell = new_cell;
+            new_system.particles_mut().position.copy_from_slice(&new_positions);
+            let new_energy = new_system.potential_energy();
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+            cache.update(&mut new_system);
+            assert_relative_eq!(cache.energy(), new_energy, max_relative = 1e-9);
+
+            // Compress the cell
+            let old_energy = new_energy;
+            let new_cell = new_system.cell.scale(Matrix3::one() * 0.98);
+            let new_positions = scaled_positions(&new_system, &new_cell);
+            let cost = cache.move_volume_cost(&new_system, new_cell, &new_positions);
+
+            system = new_system.clone();
+            system.cell = new_cell;
+            system.particles_mut().position.copy_from_slice(&new_positions);
+            let new_energy = system.potential_energy();
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+            cache.update(&mut system);
+            assert_relative_eq!(cache.energy(), new_energy, max_relative = 1e-9);
+
+            // Check that the cache is still usable for other moves
+            let old_energy = new_energy;
+            let mut new_positions = system.molecule(0).particles().position.to_vec();
+            for position in &mut new_positions {
+                *position += Vector3D::new(0.2, -0.3, 0.1);
+            }
+            let cost = cache.move_molecule_cost(&system, 0, &new_positions);
+            system.molecule_mut(0).particles_mut().position.copy_from_slice(&new_positions);
+            let new_energy = system.potential_energy();
+            assert_relative_eq!(cost, new_energy - old_energy, max_relative = 1e-9);
+        }
+    }
+
+    #[test]
+    fn move_volume_overlap() {
+        use crate::Matrix3;
+
+        let system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        cache.set_hard_core(0.8);
+
+        // The centers of mass of the molecules are 3 A apart, and only 0.6 A
+        // apart after scaling
+        let new_cell = system.cell.scale(Matrix3::one() * 0.2);
+        let new_positions = scaled_positions(&system, &new_cell);
+        let cost = cache.move_volume_cost(&system, new_cell, &new_positions);
+        assert_eq!(cost, f64::INFINITY);
+    }
+
     #[test]
     fn move_all_molecules() {
         let system = testing_system();
@@ -483,4 +1069,83 @@ mod tests {
         let new_energy = new_system.potential_energy();
         assert_ulps_eq!(cost, new_energy - old_energy, epsilon = 1e-12);
     }
+
+    fn argon_system() -> System {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            Ar    0.000000     0.000000     0.000000
+            Ar    3.500000     0.000000     0.000000
+            Ar    0.000000     4.000000     0.000000
+            Ar    0.000000     0.000000     3.800000",
+        );
+        let mut lj = PairInteraction::new(
+            Box::new(LennardJones {
+                sigma: 3.4,
+                epsilon: units::from(1.0, "kJ/mol").unwrap(),
+            }),
+            4.5,
+        );
+        lj.enable_tail_corrections();
+        system.set_pair_potential(("Ar", "Ar"), lj).unwrap();
+        system
+    }
+
+    #[test]
+    fn insert_particle() {
+        let mut system = argon_system();
+        let mut cache = EnergyCache::new();
+        let old_energy = system.potential_energy();
+        cache.init(&system);
+
+        let particle = Particle::with_position("Ar", Vector3D::new(3.0, 3.0, 0.5));
+        let cost = cache.insert_particle_cost(&system, &particle);
+        system.add_molecule(Molecule::new(particle));
+        let new_energy = system.potential_energy();
+        assert_relative_eq!(cost, new_energy - old_energy, epsilon = 1e-12);
+        cache.update(&mut system);
+        assert_relative_eq!(cache.energy(), new_energy, epsilon = 1e-12);
+
+        // Check that the pairs cache is correctly updated
+        let new_position = Vector3D::new(3.5, 3.0, 1.0);
+        let cost = cache.move_molecule_cost(&system, 4, &[new_position]);
+        system.particles_mut().position[4] = new_position;
+        assert_relative_eq!(cost, system.potential_energy() - new_energy, epsilon = 1e-12);
+
+        // Overlapping particles
+        cache.reject();
+        cache.set_hard_core(0.8);
+        let particle = Particle::with_position("Ar", Vector3D::new(3.5, 0.5, 0.0));
+        assert_eq!(cache.insert_particle_cost(&system, &particle), f64::INFINITY);
+    }
+
+    #[test]
+    fn remove_particle() {
+        let mut system = argon_system();
+        let mut cache = EnergyCache::new();
+        let old_energy = system.potential_energy();
+        cache.init(&system);
+
+        let cost = cache.remove_particle_cost(&system, 1);
+        system.remove_molecule(1);
+        let new_energy = system.potential_energy();
+        assert_relative_eq!(cost, new_energy - old_energy, epsilon = 1e-12);
+        cache.update(&mut system);
+        assert_relative_eq!(cache.energy(), new_energy, epsilon = 1e-12);
+
+        // Check that the pairs cache is correctly updated
+        let new_position = Vector3D::new(0.0, 3.5, 0.5);
+        let cost = cache.move_molecule_cost(&system, 1, &[new_position]);
+        system.particles_mut().position[1] = new_position;
+        assert_relative_eq!(cost, system.potential_energy() - new_energy, epsilon = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "EnergyCache::insert_particle_cost only supports pair interactions")]
+    fn insert_particle_coulomb() {
+        let system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        let _ = cache.insert_particle_cost(&system, &Particle::new("H"));
+    }
 }
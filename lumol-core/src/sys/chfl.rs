@@ -17,6 +17,7 @@ impl<'a> From<&'a chemfiles::Atom> for Particle {
         let name = atom.atomic_type();
         let mut particle = Particle::new(name);
         particle.mass = atom.mass();
+        particle.charge = atom.charge();
         return particle;
     }
 }
@@ -97,6 +98,7 @@ impl<'a> From<ParticleRef<'a>> for chemfiles::Atom {
     fn from(particle: ParticleRef<'a>) -> chemfiles::Atom {
         let mut atom = chemfiles::Atom::new(&**particle.name);
         atom.set_mass(*particle.mass);
+        atom.set_charge(*particle.charge);
         return atom;
     }
 }
@@ -129,7 +131,7 @@ impl<'a> From<&'a System> for chemfiles::Frame {
         }
 
         frame.add_velocities();
-        for (velocity, chfl_velocity) in soa_zip!(system.particles(), [position], frame.positions_mut()) {
+        for (velocity, chfl_velocity) in soa_zip!(system.particles(), [velocity], frame.velocities_mut()) {
             *chfl_velocity = **velocity;
         }
 
@@ -313,6 +315,49 @@ impl Trajectory {
         return Ok(frame.into());
     }
 
+    /// Read the next step of the trajectory, and guess the bonds of the
+    /// resulting [`System`][struct.System.html] using the covalent radii of
+    /// the atoms. Two atoms `i` and `j` are bonded if their distance is
+    /// smaller than `tolerance * (r_i + r_j)`, where `r_i` and `r_j` are the
+    /// covalent radii of the atoms. Any bond already present in the file is
+    /// removed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use lumol_core::sys::TrajectoryBuilder;
+    /// let mut trajectory = TrajectoryBuilder::new()
+    ///     .open("file.nc")
+    ///     .unwrap();
+    ///
+    /// let system = trajectory.read_guess_bonds_with_tolerance(1.2).unwrap();
+    /// ```
+    pub fn read_guess_bonds_with_tolerance(&mut self, tolerance: f64) -> Result<System, chemfiles::Error> {
+        let mut frame = chemfiles::Frame::new();
+        self.0.read(&mut frame)?;
+        guess_bonds_with_tolerance(&mut frame, tolerance)?;
+        return Ok(frame.into());
+    }
+
+    /// Get the number of steps (the number of frames) in this trajectory.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use lumol_core::sys::TrajectoryBuilder;
+    /// let mut trajectory = TrajectoryBuilder::new()
+    ///     .open("file.xyz")
+    ///     .unwrap();
+    ///
+    /// let mut systems = Vec::new();
+    /// for _ in 0..trajectory.nsteps().unwrap() {
+    ///     systems.push(trajectory.read().unwrap());
+    /// }
+    /// ```
+    pub fn nsteps(&mut self) -> Result<u64, chemfiles::Error> {
+        self.0.nsteps()
+    }
+
     /// Write the system to the trajectory.
     ///
     /// # Examples
@@ -375,6 +420,56 @@ impl Trajectory {
     }
 }
 
+impl System {
+    /// Write the current configuration of this system as a single frame to
+    /// the file at the given `path`, including the unit cell, the particles
+    /// names and the bonds between particles. The format is guessed from the
+    /// file extension, and any existing file is overwritten.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use lumol_core::{System, UnitCell};
+    /// let system = System::with_cell(UnitCell::cubic(10.0));
+    /// system.write_to("final.pdb").unwrap();
+    /// ```
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), chemfiles::Error> {
+        let mut trajectory = TrajectoryBuilder::new().mode(OpenMode::Write).open(path)?;
+        return trajectory.write(self);
+    }
+}
+
+/// Replace the bonds in the `frame` by the bonds between atoms closer than
+/// `tolerance` times the sum of their covalent radii.
+fn guess_bonds_with_tolerance(frame: &mut chemfiles::Frame, tolerance: f64) -> Result<(), chemfiles::Error> {
+    let natoms = frame.size() as usize;
+    let mut radii = Vec::with_capacity(natoms);
+    for i in 0..natoms {
+        let atom = frame.atom(i as u64);
+        let radius = atom.covalent_radius();
+        if radius == 0.0 {
+            return Err(chemfiles::Error {
+                status: chemfiles::Status::ChemfilesError,
+                message: format!("missing covalent radius for '{}'", atom.atomic_type()),
+            });
+        }
+        radii.push(radius);
+    }
+
+    for bond in frame.topology().bonds() {
+        frame.remove_bond(bond[0] as usize, bond[1] as usize);
+    }
+
+    for i in 0..natoms {
+        for j in (i + 1)..natoms {
+            if frame.distance(i, j) < tolerance * (radii[i] + radii[j]) {
+                frame.add_bond(i as u64, j as u64);
+            }
+        }
+    }
+    return Ok(());
+}
+
 /// Read a the first molecule from the file at `path`. If no bond information
 /// exists in the file, bonds are guessed.
 pub fn read_molecule<P: AsRef<Path>>(path: P) -> Result<Molecule, chemfiles::Error> {
@@ -412,6 +507,8 @@ mod tests {
     use crate::{Angle, Bond, MoleculeHash};
     use std::io::prelude::*;
 
+    use approx::assert_relative_eq;
+
     static WATER: &str = "3
 
 O 0.0 0.0 0.0
@@ -502,6 +599,120 @@ END
         assert_eq!(system.particles().name[5], "H");
     }
 
+    #[test]
+    fn guess_bonds_tolerance() {
+        let mut file = tempfile::Builder::new().suffix(".xyz").tempfile().unwrap();
+        write!(file, "{}", WATER).unwrap();
+
+        let mut trajectory = TrajectoryBuilder::new().open(file.path()).unwrap();
+        let system = trajectory.read_guess_bonds_with_tolerance(0.5).unwrap();
+        assert_eq!(system.molecules().count(), 3);
+
+        let mut trajectory = TrajectoryBuilder::new().open(file.path()).unwrap();
+        let system = trajectory.read_guess_bonds_with_tolerance(1.2).unwrap();
+        assert_eq!(system.molecules().count(), 1);
+        let molecule = system.molecule(0);
+        assert_eq!(molecule.bonds().len(), 2);
+        assert!(molecule.bonds().contains(&Bond::new(0, 1)));
+        assert!(molecule.bonds().contains(&Bond::new(0, 2)));
+
+        // With a very large tolerance, the hydrogen atoms are also bonded
+        let mut trajectory = TrajectoryBuilder::new().open(file.path()).unwrap();
+        let system = trajectory.read_guess_bonds_with_tolerance(3.0).unwrap();
+        assert_eq!(system.molecule(0).bonds().len(), 3);
+
+        let mut file = tempfile::Builder::new().suffix(".xyz").tempfile().unwrap();
+        write!(file, "1\n\nXx 0.0 0.0 0.0\n").unwrap();
+        let mut trajectory = TrajectoryBuilder::new().open(file.path()).unwrap();
+        let error = trajectory.read_guess_bonds_with_tolerance(1.2).err().unwrap();
+        assert_eq!(error.message, "missing covalent radius for 'Xx'");
+    }
+
+    #[test]
+    fn write_to() {
+        let mut file = tempfile::Builder::new().suffix(".pdb").tempfile().unwrap();
+        write!(file, "{}", PDB_WATER).unwrap();
+        let mut system = TrajectoryBuilder::new().open(&file).unwrap().read().unwrap();
+        system.particles_mut().velocity[2] = Vector3D::new(1.0, 2.0, 3.0);
+
+        let frame = chemfiles::Frame::from(&system);
+        for i in 0..system.size() {
+            assert_eq!(frame.positions()[i], *system.particles().position[i]);
+            assert_eq!(frame.velocities()[i], *system.particles().velocity[i]);
+        }
+
+        let output = tempfile::Builder::new().suffix(".pdb").tempfile().unwrap();
+        system.write_to(output.path()).unwrap();
+        let read = TrajectoryBuilder::new().open(output.path()).unwrap().read().unwrap();
+        assert_eq!(read.size(), 6);
+        assert_eq!(read.cell, UnitCell::cubic(28.0));
+        assert_eq!(read.molecules().count(), 4);
+        assert!(read.molecule(0).bonds().contains(&Bond::new(0, 2)));
+        assert_eq!(read.particles().name[3], "O");
+
+        // XYZ files do not store the unit cell
+        let output = tempfile::Builder::new().suffix(".xyz").tempfile().unwrap();
+        system.write_to(output.path()).unwrap();
+        let read = TrajectoryBuilder::new().open(output.path()).unwrap().read().unwrap();
+        assert_eq!(read.size(), 6);
+        assert!(read.cell.is_infinite());
+        assert_eq!(read.particles().name[1], "H");
+        assert_eq!(read.particles().position[2], system.particles().position[2]);
+
+        // GRO files store the unit cell and the velocities, in nm and nm/ps
+        // with a fixed precision
+        let output = tempfile::Builder::new().suffix(".gro").tempfile().unwrap();
+        system.write_to(output.path()).unwrap();
+        let read = TrajectoryBuilder::new().open(output.path()).unwrap().read().unwrap();
+        assert_eq!(read.size(), 6);
+        assert_relative_eq!(read.cell.a(), 28.0, epsilon = 1e-4);
+        assert_relative_eq!(read.cell.b(), 28.0, epsilon = 1e-4);
+        assert_relative_eq!(read.cell.c(), 28.0, epsilon = 1e-4);
+        assert_eq!(read.particles().name[3], "O");
+        for i in 0..system.size() {
+            for k in 0..3 {
+                let position = read.particles().position[i][k];
+                assert_relative_eq!(position, system.particles().position[i][k], epsilon = 1e-2);
+                let velocity = read.particles().velocity[i][k];
+                assert_relative_eq!(velocity, system.particles().velocity[i][k], epsilon = 1e-3);
+            }
+        }
+        assert_relative_eq!(read.particles().velocity[2][2], 3.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn write_charges_and_bonds() {
+        let mut file = tempfile::Builder::new().suffix(".xyz").tempfile().unwrap();
+        write!(file, "{}", WATER).unwrap();
+        let molecule = read_molecule(file.path()).unwrap();
+
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_molecule(molecule);
+        system.particles_mut().charge[0] = -0.8476;
+        system.particles_mut().charge[1] = 0.4238;
+        system.particles_mut().charge[2] = 0.4238;
+
+        let frame = chemfiles::Frame::from(&system);
+        assert_eq!(frame.atom(0).charge(), -0.8476);
+        assert_eq!(frame.atom(1).charge(), 0.4238);
+        assert_eq!(frame.topology().bonds(), vec![[0, 1], [0, 2]]);
+
+        // MOL2 files store both partial charges and bonds
+        let output = tempfile::Builder::new().suffix(".mol2").tempfile().unwrap();
+        system.write_to(output.path()).unwrap();
+        let read = TrajectoryBuilder::new().open(output.path()).unwrap().read().unwrap();
+        assert_eq!(read.size(), 3);
+        assert_eq!(read.molecules().count(), 1);
+        for (read, expected) in read.particles().charge.iter().zip(system.particles().charge) {
+            assert_relative_eq!(read, expected, epsilon = 1e-6);
+        }
+
+        let molecule = read.molecule(0);
+        assert_eq!(molecule.bonds().len(), 2);
+        assert!(molecule.bonds().contains(&Bond::new(0, 1)));
+        assert!(molecule.bonds().contains(&Bond::new(0, 2)));
+    }
+
     #[test]
     #[allow(clippy::unreadable_literal)]
     fn read_propane() {
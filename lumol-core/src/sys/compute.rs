@@ -3,6 +3,7 @@
 
 //! Algorithm to compute physical properties of a System
 
+use std::collections::BTreeMap;
 use std::f64::consts::PI;
 
 use rayon::prelude::*;
@@ -11,7 +12,7 @@ use log_once::warn_once;
 
 use crate::consts::K_BOLTZMANN;
 use crate::{Matrix3, Vector3D};
-use crate::{System, DegreesOfFreedom};
+use crate::{System, DegreesOfFreedom, MoleculeRef, ParticleKind};
 
 use crate::utils::ThreadLocalVec;
 
@@ -26,84 +27,203 @@ pub trait Compute {
 }
 
 /// Compute all the forces acting on the system, and return a vector of
-/// force acting on each particles
+/// force acting on each particles. The forces acting on virtual sites are
+/// moved to their parent particles.
 pub struct Forces;
 impl Compute for Forces {
     type Output = Vec<Vector3D>;
     fn compute(&self, system: &System) -> Vec<Vector3D> {
-        let natoms = system.size();
-        let thread_local_forces = ThreadLocalVec::with_size(natoms);
+        let mut forces = pairs_forces(system);
+        add_bonded_forces(system, &mut forces);
+        add_electrostatic_and_global_forces(system, &mut forces);
+        system.spread_virtual_forces(&mut forces);
+        return forces;
+    }
+}
 
-        (0..natoms).into_par_iter().for_each(|i| {
-            let mut forces = thread_local_forces.borrow_mut();
-            let mut force_i = Vector3D::zero();
-            for j in (i + 1)..system.size() {
-                let path = system.bond_path(i, j);
-                let d = system.nearest_image(i, j);
-                let dn = d.normalized();
-                let r = d.norm();
-                if let Some(potential) = system.pair_potential(i, j) {
-                    let info = potential.restriction().information(path);
-                    if !info.excluded {
-                        let force = info.scaling * potential.force(r) * dn;
-                        force_i += force;
-                        forces[j] -= force;
-                    }
-                }
-            }
-            forces[i] += force_i;
-        });
+/// Compute all the torques acting on the orientations of the particles in
+/// the system, and return a vector of torques acting on each particle. Only
+/// the global interactions depending on the orientations (such as
+/// [`GayBerneInteraction`]) contribute to the torques.
+///
+/// [`GayBerneInteraction`]: ../energy/struct.GayBerneInteraction.html
+pub struct Torques;
+impl Compute for Torques {
+    type Output = Vec<Vector3D>;
+    fn compute(&self, system: &System) -> Vec<Vector3D> {
+        let mut torques = vec![Vector3D::zero(); system.size()];
+        for global in system.global_potentials() {
+            global.torques(system, &mut torques);
+        }
+        return torques;
+    }
+}
 
-        // At this point all the forces are computed, but the results are
-        // scattered across all thread local Vecs, here we gather them.
-        let mut forces = vec![Vector3D::zero(); natoms];
-        thread_local_forces.sum_into(&mut forces);
+/// Compute the forces acting on the system coming from the bonded
+/// interactions (bonds, angles and dihedral angles) only. These forces
+/// usually vary faster than the ones coming from non-bonded interactions.
+pub struct BondedForces;
+impl Compute for BondedForces {
+    type Output = Vec<Vector3D>;
+    fn compute(&self, system: &System) -> Vec<Vector3D> {
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        add_bonded_forces(system, &mut forces);
+        system.spread_virtual_forces(&mut forces);
+        return forces;
+    }
+}
 
-        for molecule in system.molecules() {
-            for bond in molecule.bonds() {
-                let (i, j) = (bond.i(), bond.j());
-                let d = system.nearest_image(i, j);
-                let dn = d.normalized();
-                let r = d.norm();
-                if let Some(potential) = system.bond_potential(i, j) {
-                    let force = potential.force(r) * dn;
-                    forces[i] += force;
+/// Compute the forces acting on the system coming from the non-bonded
+/// interactions only: pairs, coulombic and global interactions. The sum of
+/// [`BondedForces`] and `NonBondedForces` is equal to [`Forces`].
+///
+/// [`BondedForces`]: struct.BondedForces.html
+/// [`Forces`]: struct.Forces.html
+pub struct NonBondedForces;
+impl Compute for NonBondedForces {
+    type Output = Vec<Vector3D>;
+    fn compute(&self, system: &System) -> Vec<Vector3D> {
+        let mut forces = pairs_forces(system);
+        add_electrostatic_and_global_forces(system, &mut forces);
+        system.spread_virtual_forces(&mut forces);
+        return forces;
+    }
+}
+
+/// Compute the forces coming from the pair interactions
+fn pairs_forces(system: &System) -> Vec<Vector3D> {
+    let natoms = system.size();
+    let thread_local_forces = ThreadLocalVec::with_size(natoms);
+
+    (0..natoms).into_par_iter().for_each(|i| {
+        let mut forces = thread_local_forces.borrow_mut();
+        let mut force_i = Vector3D::zero();
+        for j in (i + 1)..system.size() {
+            let path = system.bond_path(i, j);
+            let d = system.nearest_image(i, j);
+            let dn = d.normalized();
+            let r = d.norm();
+            if let Some(potential) = system.pair_potential(i, j) {
+                let info = potential.restriction().information(path);
+                if !info.excluded {
+                    let force = info.scaling * system.coupling(i, j) * potential.force(r) * dn;
+                    force_i += force;
                     forces[j] -= force;
                 }
             }
+        }
+        forces[i] += force_i;
+    });
+
+    // At this point all the forces are computed, but the results are
+    // scattered across all thread local Vecs, here we gather them.
+    let mut forces = vec![Vector3D::zero(); natoms];
+    thread_local_forces.sum_into(&mut forces);
+    return forces;
+}
 
-            for angle in molecule.angles() {
-                let (i, j, k) = (angle.i(), angle.j(), angle.k());
-                let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
-                if let Some(potential) = system.angle_potential(i, j, k) {
-                    let force = potential.force(theta);
-                    forces[i] += force * d1;
-                    forces[j] += force * d2;
-                    forces[k] += force * d3;
-                }
+/// Add the forces coming from bonds, angles and dihedral angles to `forces`
+fn add_bonded_forces(system: &System, forces: &mut [Vector3D]) {
+    for molecule in system.molecules() {
+        for bond in molecule.bonds() {
+            let (i, j) = (bond.i(), bond.j());
+            let d = system.nearest_image(i, j);
+            let dn = d.normalized();
+            let r = d.norm();
+            if let Some(potential) = system.bond_potential(i, j) {
+                let force = potential.force(r) * dn;
+                forces[i] += force;
+                forces[j] -= force;
             }
+        }
 
-            for dihedral in molecule.dihedrals() {
-                let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
-                let (phi, d1, d2, d3, d4) = system.dihedral_and_derivatives(i, j, k, m);
-                if let Some(potential) = system.dihedral_potential(i, j, k, m) {
-                    let force = potential.force(phi);
-                    forces[i] += force * d1;
-                    forces[j] += force * d2;
-                    forces[k] += force * d3;
-                    forces[m] += force * d4;
-                }
+        for angle in molecule.angles() {
+            let (i, j, k) = (angle.i(), angle.j(), angle.k());
+            let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
+            if let Some(potential) = system.angle_potential(i, j, k) {
+                let force = potential.force(theta);
+                forces[i] += force * d1;
+                forces[j] += force * d2;
+                forces[k] += force * d3;
             }
         }
 
-        if let Some(coulomb) = system.coulomb_potential() {
-            coulomb.forces(system, &mut forces);
+        for dihedral in molecule.dihedrals() {
+            let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+            let (phi, d1, d2, d3, d4) = system.dihedral_and_derivatives(i, j, k, m);
+            if let Some(potential) = system.dihedral_potential(i, j, k, m) {
+                let force = potential.force(phi);
+                forces[i] += force * d1;
+                forces[j] += force * d2;
+                forces[k] += force * d3;
+                forces[m] += force * d4;
+            }
         }
+    }
+}
 
-        for global in system.global_potentials() {
-            global.forces(system, &mut forces);
+/// Add the forces coming from the coulombic and global interactions to
+/// `forces`
+fn add_electrostatic_and_global_forces(system: &System, forces: &mut [Vector3D]) {
+    if let Some(coulomb) = system.coulomb_potential() {
+        coulomb.forces(system, forces);
+    }
+
+    for global in system.global_potentials() {
+        global.forces(system, forces);
+    }
+}
+
+/// Contributions of the different kinds of interactions to the potential
+/// energy of a system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnergyComponents {
+    /// Energy of the pair interactions
+    pub pairs: f64,
+    /// Energy of the long range corrections for pair interactions
+    pub pairs_tail: f64,
+    /// Energy of the bonds interactions
+    pub bonds: f64,
+    /// Energy of the angles interactions
+    pub angles: f64,
+    /// Energy of the dihedral angles interactions
+    pub dihedrals: f64,
+    /// Energy of the coulombic interactions
+    pub coulomb: f64,
+    /// Energy of the global interactions
+    pub global: f64,
+}
+
+impl EnergyComponents {
+    /// Get the total potential energy, *i.e.* the sum of all the components
+    pub fn total(&self) -> f64 {
+        let mut energy = self.pairs;
+        energy += self.pairs_tail;
+        energy += self.bonds;
+        energy += self.angles;
+        energy += self.dihedrals;
+        energy += self.coulomb;
+        energy += self.global;
+        return energy;
+    }
+}
+
+/// Compute the contributions of the different kinds of interactions to the
+/// potential energy of the system
+pub struct PotentialEnergyComponents;
+impl Compute for PotentialEnergyComponents {
+    type Output = EnergyComponents;
+    fn compute(&self, system: &System) -> EnergyComponents {
+        let evaluator = system.energy_evaluator();
+        EnergyComponents {
+            pairs: evaluator.pairs(),
+            pairs_tail: evaluator.pairs_tail(),
+            bonds: evaluator.bonds(),
+            angles: evaluator.angles(),
+            dihedrals: evaluator.dihedrals(),
+            coulomb: evaluator.coulomb(),
+            global: evaluator.global(),
         }
-        return forces;
     }
 }
 
@@ -112,21 +232,42 @@ pub struct PotentialEnergy;
 impl Compute for PotentialEnergy {
     type Output = f64;
     fn compute(&self, system: &System) -> f64 {
-        let evaluator = system.energy_evaluator();
-
-        let mut energy = evaluator.pairs();
-        energy += evaluator.pairs_tail();
-        energy += evaluator.bonds();
-        energy += evaluator.angles();
-        energy += evaluator.dihedrals();
-        energy += evaluator.coulomb();
-        energy += evaluator.global();
-
+        let energy = PotentialEnergyComponents.compute(system).total();
         assert!(energy.is_finite(), "Potential energy is infinite!");
         return energy;
     }
 }
 
+/// Compute the derivative of the potential energy of the system with respect
+/// to the coupling parameter $\lambda$ of the pair potentials, as used in
+/// thermodynamic integration.
+///
+/// $$ \frac{\partial U}{\partial \lambda} = \sum_{i < j}
+///    \frac{\partial V_{ij}(r_{ij})}{\partial \lambda} $$
+///
+/// Tail corrections are not included in this derivative.
+pub struct LambdaDerivative;
+impl Compute for LambdaDerivative {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        let cutoff = match system.maximum_cutoff() {
+            Some(cutoff) => cutoff,
+            None => return 0.0,
+        };
+
+        let mut derivative = 0.0;
+        for (i, j, r) in system.pairs_within(cutoff) {
+            if let Some(potential) = system.pair_potential(i, j) {
+                let info = potential.restriction().information(system.bond_path(i, j));
+                if !info.excluded {
+                    derivative += info.scaling * system.coupling(i, j) * potential.d_energy_d_lambda(r);
+                }
+            }
+        }
+        return derivative;
+    }
+}
+
 /// Compute the kinetic energy of the system
 ///
 /// $$ K = \sum_i m_i \vec v_i \cdot \vec v_i $$
@@ -171,6 +312,126 @@ impl Compute for Temperature {
     }
 }
 
+/// Contributions of the different kinds of motion of the molecules to the
+/// instantaneous temperature of a system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TemperatureComponents {
+    /// Temperature of the translation of the molecules center-of-mass
+    pub translational: f64,
+    /// Temperature of the rotation of the molecules around their
+    /// center-of-mass
+    pub rotational: f64,
+    /// Temperature of the remaining internal motion of the molecules
+    pub vibrational: f64,
+}
+
+/// Compute the contributions of the translation, rotation and vibration of
+/// the molecules to the instantaneous temperature of the system.
+///
+/// The kinetic energy of each molecule is split in the kinetic energy of its
+/// center-of-mass, the rotational kinetic energy $\frac 12 \vec L \cdot
+/// \underline{I}^{-1} \vec L$ computed from the angular momentum $\vec L$ and
+/// the inertia tensor $\underline{I}$ around the center-of-mass, and the
+/// remaining vibrational kinetic energy. Each component of the temperature is
+/// then $T_x = 2 K_x / (k_B N_x)$, where $N_x$ is the number of degrees of
+/// freedom of this kind: 3 translational degrees of freedom by molecule, 2
+/// rotational degrees of freedom for linear molecules and 3 for non-linear
+/// ones, and the remaining degrees of freedom are vibrational.
+///
+/// Virtual sites (particles without mass) do not contribute to the degrees of
+/// freedom, and a component is zero if there are no corresponding degrees of
+/// freedom in the system.
+pub struct PartitionedTemperature;
+impl Compute for PartitionedTemperature {
+    type Output = TemperatureComponents;
+    fn compute(&self, system: &System) -> TemperatureComponents {
+        let mut translational = (0.0, 0);
+        let mut rotational = (0.0, 0);
+        let mut vibrational = (0.0, 0);
+        let mut positions = Vec::new();
+        for molecule in system.molecules() {
+            let particles = molecule.particles();
+            // Use the nearest image of all the particles with respect to the
+            // first one, to get the molecule in one piece.
+            positions.clear();
+            let mut total_mass = 0.0;
+            let mut count = 0;
+            let mut kinetic = 0.0;
+            let mut com = Vector3D::zero();
+            let mut com_velocity = Vector3D::zero();
+            for (&mass, position, velocity) in soa_zip!(particles, [mass, position, velocity]) {
+                let mut delta = position - particles.position[0];
+                system.cell.vector_image(&mut delta);
+                positions.push(delta);
+                if mass > 0.0 {
+                    count += 1;
+                }
+                total_mass += mass;
+                kinetic += 0.5 * mass * velocity.norm2();
+                com += mass * delta;
+                com_velocity += mass * velocity;
+            }
+
+            if count == 0 {
+                continue;
+            }
+            com /= total_mass;
+            com_velocity /= total_mass;
+            let translation = 0.5 * total_mass * com_velocity.norm2();
+
+            let mut inertia = Matrix3::zero();
+            let mut angular_momentum = Vector3D::zero();
+            for (i, (&mass, velocity)) in soa_zip!(particles, [mass, velocity]).enumerate() {
+                let delta = positions[i] - com;
+                inertia += mass * delta.norm2() * Matrix3::one();
+                inertia -= mass * delta.tensorial(&delta);
+                angular_momentum += mass * (delta ^ (velocity - com_velocity));
+            }
+
+            let (rotation, rotational_dof) = if count == 1 {
+                (0.0, 0)
+            } else if is_linear(&inertia) {
+                // The two non-zero principal moments of inertia of linear
+                // molecules are equal, and the angular momentum is
+                // perpendicular to the molecule axis.
+                let moment = 0.5 * inertia.trace();
+                (0.5 * angular_momentum.norm2() / moment, 2)
+            } else {
+                let velocity = inertia.inverse() * angular_momentum;
+                (0.5 * angular_momentum * velocity, 3)
+            };
+
+            translational.0 += translation;
+            translational.1 += 3;
+            rotational.0 += rotation;
+            rotational.1 += rotational_dof;
+            vibrational.0 += kinetic - translation - rotation;
+            vibrational.1 += 3 * count - 3 - rotational_dof;
+        }
+
+        let temperature = |(kinetic, dof): (f64, usize)| {
+            if dof == 0 {
+                0.0
+            } else {
+                2.0 * kinetic / (dof as f64 * K_BOLTZMANN)
+            }
+        };
+
+        TemperatureComponents {
+            translational: temperature(translational),
+            rotational: temperature(rotational),
+            vibrational: temperature(vibrational),
+        }
+    }
+}
+
+/// Check if an inertia tensor corresponds to a linear molecule, *i.e.* if it
+/// is singular.
+fn is_linear(inertia: &Matrix3) -> bool {
+    let scale = inertia.trace() / 3.0;
+    return f64::abs(inertia.determinant()) < 1e-8 * scale * scale * scale;
+}
+
 /// Compute the volume of the system
 pub struct Volume;
 impl Compute for Volume {
@@ -182,6 +443,63 @@ impl Compute for Volume {
 }
 
 
+/// Contributions of the different kinds of interactions to the virial tensor
+/// of a system. Angles and dihedral angles potentials do not contribute to
+/// the virial.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VirialComponents {
+    /// Virial of the pair interactions
+    pub pairs: Matrix3,
+    /// Virial of the long range corrections for pair interactions
+    pub pairs_tail: Matrix3,
+    /// Virial of the bonds interactions
+    pub bonds: Matrix3,
+    /// Virial of the coulombic interactions
+    pub coulomb: Matrix3,
+    /// Virial of the global interactions
+    pub global: Matrix3,
+}
+
+impl VirialComponents {
+    /// Get a set of virial components with all components set to zero
+    fn zero() -> VirialComponents {
+        VirialComponents {
+            pairs: Matrix3::zero(),
+            pairs_tail: Matrix3::zero(),
+            bonds: Matrix3::zero(),
+            coulomb: Matrix3::zero(),
+            global: Matrix3::zero(),
+        }
+    }
+
+    /// Get the total virial, *i.e.* the sum of all the components
+    pub fn total(&self) -> Matrix3 {
+        let mut virial = self.pairs;
+        virial += self.pairs_tail;
+        virial += self.bonds;
+        virial += self.coulomb;
+        virial += self.global;
+        return virial;
+    }
+}
+
+/// Compute the contribution of the tail corrections of the pair potentials to
+/// the virial of the `system`
+fn pairs_tail_virial(system: &System) -> Matrix3 {
+    let mut virial = Matrix3::zero();
+    let volume = system.cell.volume();
+    let composition = system.composition();
+    for (i, ni) in composition.all_particles() {
+        for (j, nj) in composition.all_particles() {
+            let two_pi_density = 2.0 * PI * (ni as f64) * (nj as f64) / volume;
+            if let Some(potential) = system.interactions().pair((i, j)) {
+                virial += two_pi_density * potential.tail_virial();
+            }
+        }
+    }
+    return virial;
+}
+
 /// Compute the virial tensor of the system using the atomic definition.
 ///
 /// $$ \underline{W} = \sum_i \vec r_i \otimes \vec f_i - \underline H
@@ -196,6 +514,16 @@ pub struct AtomicVirial;
 impl Compute for AtomicVirial {
     type Output = Matrix3;
     fn compute(&self, system: &System) -> Matrix3 {
+        AtomicVirialComponents.compute(system).total()
+    }
+}
+
+/// Compute the contributions of the different kinds of interactions to the
+/// [`AtomicVirial`](struct.AtomicVirial.html) of the system.
+pub struct AtomicVirialComponents;
+impl Compute for AtomicVirialComponents {
+    type Output = VirialComponents;
+    fn compute(&self, system: &System) -> VirialComponents {
         assert!(!system.cell.is_infinite(), "Can not compute virial for infinite cell");
 
         // Pair potentials contributions
@@ -207,25 +535,17 @@ impl Compute for AtomicVirial {
                     let info = potential.restriction().information(path);
                     if !info.excluded {
                         let d = system.nearest_image(i, j);
-                        local_virial += info.scaling * potential.virial(&d);
+                        local_virial += info.scaling * system.coupling(i, j) * potential.virial(&d);
                     }
                 }
             }
             return local_virial;
         });
-        let mut virial = pair_virials.sum();
+        let mut virial = VirialComponents::zero();
+        virial.pairs = pair_virials.sum();
 
         // Tail correction for pair potentials contribution
-        let volume = system.cell.volume();
-        let composition = system.composition();
-        for (i, ni) in composition.all_particles() {
-            for (j, nj) in composition.all_particles() {
-                let two_pi_density = 2.0 * PI * (ni as f64) * (nj as f64) / volume;
-                if let Some(potential) = system.interactions().pair((i, j)) {
-                    virial += two_pi_density * potential.tail_virial();
-                }
-            }
-        }
+        virial.pairs_tail = pairs_tail_virial(system);
 
         // Bond potentials contributions
         for molecule in system.molecules() {
@@ -233,7 +553,7 @@ impl Compute for AtomicVirial {
                 let (i, j) = (bond.i(), bond.j());
                 let r = system.nearest_image(i, j);
                 if let Some(potential) = system.bond_potential(i, j) {
-                    virial += potential.virial(&r);
+                    virial.bonds += potential.virial(&r);
                 }
             }
         }
@@ -243,17 +563,90 @@ impl Compute for AtomicVirial {
         // CCP5 Information Quarterly, 39, 14. 18, 21, 24).
 
         if let Some(coulomb) = system.coulomb_potential() {
-            virial += coulomb.atomic_virial(system);
+            virial.coulomb = coulomb.atomic_virial(system);
         }
 
         for global in system.global_potentials() {
-            virial += global.atomic_virial(system);
+            virial.global += global.atomic_virial(system);
         }
 
         return virial;
     }
 }
 
+/// Compute the contribution of each particle to the
+/// [`AtomicVirial`](struct.AtomicVirial.html) of the system, which can be
+/// used to study the local stress around defects or at interfaces.
+///
+/// The virial of each pair and bond interaction is split equally between
+/// the two particles involved:
+///
+/// $$ \underline{W}_i = \frac{1}{2} \sum_{j \neq i} \vec r_{ij} \otimes
+///    \vec f_{ij} $$
+///
+/// The contributions that can not be attributed to a single pair of particles
+/// (long range corrections, coulombic and global interactions) are
+/// distributed equally between all the particles, so that the sum of the
+/// per-atom virials is equal to the atomic virial of the system. The per-atom
+/// stress is obtained by dividing these values by a volume associated with
+/// each particle.
+pub struct PerAtomStress;
+impl Compute for PerAtomStress {
+    type Output = Vec<Matrix3>;
+    fn compute(&self, system: &System) -> Vec<Matrix3> {
+        assert!(!system.cell.is_infinite(), "Can not compute virial for infinite cell");
+        let natoms = system.size();
+        let mut virials = vec![Matrix3::zero(); natoms];
+        if natoms == 0 {
+            return virials;
+        }
+
+        // Pair potentials contributions
+        for i in 0..natoms {
+            for j in (i + 1)..natoms {
+                let path = system.bond_path(i, j);
+                if let Some(potential) = system.pair_potential(i, j) {
+                    let info = potential.restriction().information(path);
+                    if !info.excluded {
+                        let d = system.nearest_image(i, j);
+                        let w = 0.5 * info.scaling * system.coupling(i, j) * potential.virial(&d);
+                        virials[i] += w;
+                        virials[j] += w;
+                    }
+                }
+            }
+        }
+
+        // Bond potentials contributions
+        for molecule in system.molecules() {
+            for bond in molecule.bonds() {
+                let (i, j) = (bond.i(), bond.j());
+                let r = system.nearest_image(i, j);
+                if let Some(potential) = system.bond_potential(i, j) {
+                    let w = 0.5 * potential.virial(&r);
+                    virials[i] += w;
+                    virials[j] += w;
+                }
+            }
+        }
+
+        // Non local contributions
+        let mut non_local = pairs_tail_virial(system);
+        if let Some(coulomb) = system.coulomb_potential() {
+            non_local += coulomb.atomic_virial(system);
+        }
+        for global in system.global_potentials() {
+            non_local += global.atomic_virial(system);
+        }
+        let non_local = non_local / natoms as f64;
+        for virial in &mut virials {
+            *virial += non_local;
+        }
+
+        return virials;
+    }
+}
+
 /// Compute the virial tensor of the system using the molecular definition
 ///
 /// This differs from the [`AtomicVirial`](struct.AtomicVirial.html) when using
@@ -279,6 +672,16 @@ pub struct MolecularVirial;
 impl Compute for MolecularVirial {
     type Output = Matrix3;
     fn compute(&self, system: &System) -> Matrix3 {
+        MolecularVirialComponents.compute(system).total()
+    }
+}
+
+/// Compute the contributions of the different kinds of interactions to the
+/// [`MolecularVirial`](struct.MolecularVirial.html) of the system.
+pub struct MolecularVirialComponents;
+impl Compute for MolecularVirialComponents {
+    type Output = VirialComponents;
+    fn compute(&self, system: &System) -> VirialComponents {
         assert!(!system.cell.is_infinite(), "Can not compute virial for infinite cell");
 
         // Pair potentials contributions, using the molecular virial definition
@@ -299,7 +702,7 @@ impl Compute for MolecularVirial {
                         if let Some(potential) = system.pair_potential(part_a, part_b) {
                             let info = potential.restriction().information(path);
                             if !info.excluded {
-                                let w_ab = info.scaling * potential.virial(&r_ab);
+                                let w_ab = info.scaling * system.coupling(part_a, part_b) * potential.virial(&r_ab);
                                 local_virial += w_ab * (r_ab * r_ij) / r_ab.norm2();
                             }
                         }
@@ -308,19 +711,11 @@ impl Compute for MolecularVirial {
             }
             return local_virial;
         });
-        let mut virial = pair_virials.sum();
+        let mut virial = VirialComponents::zero();
+        virial.pairs = pair_virials.sum();
 
         // Tail correction for pair potentials contribution
-        let volume = system.cell.volume();
-        let composition = system.composition();
-        for (i, ni) in composition.all_particles() {
-            for (j, nj) in composition.all_particles() {
-                let two_pi_density = 2.0 * PI * (ni as f64) * (nj as f64) / volume;
-                if let Some(potential) = system.interactions().pair((i, j)) {
-                    virial += two_pi_density * potential.tail_virial();
-                }
-            }
-        }
+        virial.pairs_tail = pairs_tail_virial(system);
 
         // Bond potentials contributions
         for molecule in system.molecules() {
@@ -352,11 +747,11 @@ impl Compute for MolecularVirial {
         // CCP5 Information Quarterly, 39, 14. 18, 21, 24).
 
         if let Some(coulomb) = system.coulomb_potential() {
-            virial += coulomb.molecular_virial(system);
+            virial.coulomb = coulomb.molecular_virial(system);
         }
 
         for global in system.global_potentials() {
-            virial += global.molecular_virial(system);
+            virial.global += global.molecular_virial(system);
         }
 
         return virial;
@@ -365,7 +760,8 @@ impl Compute for MolecularVirial {
 
 /// Compute the virial tensor of the system, picking between [`AtomicVirial`]
 /// and [`MolecularVirial`] depending on the number of degrees of freedom
-/// simulated on the system.
+/// simulated on the system. The virial of the constraint forces set by the
+/// propagator in `System::constraints_virial` is added to the atomic virial.
 ///
 /// [`AtomicVirial`]: struct.AtomicVirial.html
 /// [`MolecularVirial`]: struct.MolecularVirial.html
@@ -375,7 +771,22 @@ impl Compute for Virial {
     fn compute(&self, system: &System) -> Matrix3 {
         match system.simulated_degrees_of_freedom {
             DegreesOfFreedom::Molecules => MolecularVirial.compute(system),
-            DegreesOfFreedom::Particles | DegreesOfFreedom::Frozen(_) => AtomicVirial.compute(system),
+            DegreesOfFreedom::Particles | DegreesOfFreedom::Frozen(_) => {
+                AtomicVirial.compute(system) + system.constraints_virial
+            }
+        }
+    }
+}
+
+/// Compute the contributions of the different kinds of interactions to the
+/// [`Virial`](struct.Virial.html) of the system.
+pub struct PartitionedVirial;
+impl Compute for PartitionedVirial {
+    type Output = VirialComponents;
+    fn compute(&self, system: &System) -> VirialComponents {
+        match system.simulated_degrees_of_freedom {
+            DegreesOfFreedom::Molecules => MolecularVirialComponents.compute(system),
+            DegreesOfFreedom::Particles | DegreesOfFreedom::Frozen(_) => AtomicVirialComponents.compute(system),
         }
     }
 }
@@ -400,6 +811,7 @@ impl Compute for PressureAtTemperature {
     fn compute(&self, system: &System) -> f64 {
         assert!(!system.cell.is_infinite(), "Can not compute pressure for infinite cell");
         assert!(self.temperature >= 0.0);
+        check_translation_invariance(system);
         let virial = system.virial().trace();
         let volume = system.volume();
         let dof = system.degrees_of_freedom() as f64;
@@ -407,19 +819,41 @@ impl Compute for PressureAtTemperature {
     }
 }
 
+/// Warn if any global potential in the `system` depends on the absolute
+/// positions of the particles, making the pressure and the stress ill-defined
+fn check_translation_invariance(system: &System) {
+    if system.global_potentials().iter().any(|global| !global.is_translation_invariant()) {
+        warn_once!(
+            "The pressure and stress are ill-defined with global potentials depending \
+             on the absolute positions of the particles, such as external forces"
+        );
+    }
+}
+
 /// Compute the pressure of the system using the virial definition.
 ///
-/// $$ p = \sum_i m_i \vec v_i \cdot \vec v_i + \frac{Tr(\underline{W})}{3V} $$
+/// $$ p = \frac{1}{3V} \left(\sum_i m_i \vec v_i \cdot \vec v_i + Tr(\underline{W}) \right) $$
 ///
 /// where $m_i$ is the mass of particle $i$, $\vec v_i$ the velocity of particle
 /// $i$, $V$ the simulation volume, $Tr$ is the matricial trace, and
-/// $\underline{W}$ the [`Virial`].
+/// $\underline{W}$ the [`Virial`]. When simulating rigid molecules, the
+/// kinetic term uses the masses and center-of-mass velocities of the
+/// molecules instead of the particles, consistently with the molecular
+/// virial.
 ///
 /// [`Virial`]: struct.Virial.html
 pub struct Pressure;
 impl Compute for Pressure {
     type Output = f64;
     fn compute(&self, system: &System) -> f64 {
+        if system.simulated_degrees_of_freedom == DegreesOfFreedom::Molecules {
+            assert!(!system.cell.is_infinite(), "Can not compute pressure for infinite cell");
+            check_translation_invariance(system);
+            let kinetic = KineticTensor.compute(system).trace();
+            let virial = system.virial().trace();
+            return (kinetic + virial) / (3.0 * system.volume());
+        }
+
         let pressure = PressureAtTemperature {
             temperature: system.temperature(),
         };
@@ -446,6 +880,7 @@ impl Compute for StressAtTemperature {
     fn compute(&self, system: &System) -> Matrix3 {
         assert!(self.temperature >= 0.0);
         assert!(!system.cell.is_infinite(), "Can not compute stress for infinite cell");
+        check_translation_invariance(system);
         let virial = system.virial();
         let volume = system.volume();
         let dof = system.degrees_of_freedom() as f64;
@@ -458,37 +893,472 @@ impl Compute for StressAtTemperature {
 ///
 /// $$ \underline{\sigma} = \frac{1}{V} \left( \sum_i m_i \vec v_i \otimes \vec v_i + \underline{W} \right) $$
 ///
-/// where $N_f$ is the number of degrees of freedom in the system, $k_B$ is the
-/// Boltzman constant, $T$ the instantaneous system temperature, $V$ the
-/// simulation volume, $Tr$ is the matricial trace, and $\underline{W}$ the
-/// [`Virial`].
+/// where $m_i$ is the mass of particle $i$, $\vec v_i$ the velocity of particle
+/// $i$, $V$ the simulation volume, and $\underline{W}$ the [`Virial`]. When
+/// simulating rigid molecules, the kinetic term uses the masses and
+/// center-of-mass velocities of the molecules instead of the particles.
 pub struct Stress;
 impl Compute for Stress {
     type Output = Matrix3;
     fn compute(&self, system: &System) -> Matrix3 {
         assert!(!system.cell.is_infinite(), "Can not compute stress for infinite cell");
+        check_translation_invariance(system);
+        let kinetic = KineticTensor.compute(system);
+        let volume = system.volume();
+        let virial = system.virial();
+        return (kinetic + virial) / volume;
+    }
+}
 
+/// Compute the kinetic energy tensor of the system, i.e. the kinetic
+/// contribution to the stress tensor.
+///
+/// $$ \underline{K} = \sum_i m_i \vec v_i \otimes \vec v_i $$
+///
+/// The trace of this tensor is twice the [`KineticEnergy`] of the particles.
+/// When simulating rigid molecules, the sum runs over the molecules center-of-mass momenta,
+/// since the [`MolecularVirial`] already accounts for the constraints keeping
+/// the molecules rigid.
+///
+/// [`KineticEnergy`]: struct.KineticEnergy.html
+/// [`MolecularVirial`]: struct.MolecularVirial.html
+pub struct KineticTensor;
+impl Compute for KineticTensor {
+    type Output = Matrix3;
+    fn compute(&self, system: &System) -> Matrix3 {
         let mut kinetic = Matrix3::zero();
-        for (&mass, velocity) in soa_zip!(system.particles(), [mass, velocity]) {
-            kinetic += mass * velocity.tensorial(velocity);
+        if system.simulated_degrees_of_freedom == DegreesOfFreedom::Molecules {
+            for molecule in system.molecules() {
+                let mut total_mass = 0.0;
+                let mut momentum = Vector3D::zero();
+                for (&mass, velocity) in soa_zip!(molecule.particles(), [mass, velocity]) {
+                    total_mass += mass;
+                    momentum += mass * velocity;
+                }
+                if total_mass > 0.0 {
+                    kinetic += momentum.tensorial(&momentum) / total_mass;
+                }
+            }
+        } else {
+            for (&mass, velocity) in soa_zip!(system.particles(), [mass, velocity]) {
+                kinetic += mass * velocity.tensorial(velocity);
+            }
+        }
+        return kinetic;
+    }
+}
+
+/// Compute the forces acting on all the particles in the system using finite
+/// differences of the potential energy.
+///
+/// $$ f_{i,\alpha} = - \frac{U(r_{i,\alpha} + \delta) - U(r_{i,\alpha} -
+///    \delta)}{2 \delta} $$
+///
+/// This is much slower than the [`Forces`] computation, as it needs two
+/// energy evaluations for each degree of freedom in the system; but it can be
+/// used to check the forces implementation of new potentials.
+///
+/// [`Forces`]: struct.Forces.html
+pub struct NumericalForces {
+    /// Displacement used for the finite differences
+    pub delta: f64,
+}
+
+impl Compute for NumericalForces {
+    type Output = Vec<Vector3D>;
+    fn compute(&self, system: &System) -> Vec<Vector3D> {
+        assert!(self.delta > 0.0, "delta must be positive in NumericalForces");
+        let mut system = system.clone();
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        for (i, force) in forces.iter_mut().enumerate() {
+            for alpha in 0..3 {
+                let initial = system.particles().position[i][alpha];
+
+                system.particles_mut().position[i][alpha] = initial + self.delta;
+                let forward = system.potential_energy();
+                system.particles_mut().position[i][alpha] = initial - self.delta;
+                let backward = system.potential_energy();
+                system.particles_mut().position[i][alpha] = initial;
+
+                force[alpha] = - (forward - backward) / (2.0 * self.delta);
+            }
+        }
+        return forces;
+    }
+}
+
+/// Compute the pressure of the system using finite differences of the
+/// potential energy with respect to the volume.
+///
+/// $$ p = \frac{N_f k_B T}{3 V} - \frac{U(V + \delta) - U(V - \delta)}{2
+///    \delta} $$
+///
+/// where $N_f$ is the number of degrees of freedom in the system, $k_B$ is the
+/// Boltzman constant, $T$ the system temperature, and $V$ the simulation
+/// volume. The cell is scaled isotropically, together with the positions of
+/// the particles, or the centers of mass of the molecules when simulating
+/// rigid molecules. In the latter case, the kinetic term uses the
+/// center-of-mass velocities of the molecules, as in [`Pressure`].
+///
+/// This is much slower than the [`Pressure`] computation, but can be used to
+/// check the virial implementation of new potentials.
+///
+/// [`Pressure`]: struct.Pressure.html
+pub struct NumericalPressure {
+    /// Volume change used for the finite differences
+    pub delta: f64,
+}
+
+impl NumericalPressure {
+    /// Get a copy of `system` with the cell scaled to reach the given `volume`
+    fn scaled(system: &System, volume: f64) -> System {
+        let mut system = system.clone();
+        let factor = f64::cbrt(volume / system.volume());
+        let old_cell = system.cell;
+        let new_cell = old_cell.scale(factor * Matrix3::one());
+
+        if system.simulated_degrees_of_freedom == DegreesOfFreedom::Molecules {
+            let mut deltas = Vec::new();
+            for molecule in system.molecules() {
+                let com = molecule.center_of_mass();
+                let new_com = new_cell.cartesian(&old_cell.fractional(&com));
+                deltas.push(new_com - com);
+            }
+            for (i, delta) in deltas.iter().enumerate() {
+                for position in system.molecule_mut(i).particles_mut().position {
+                    *position += *delta;
+                }
+            }
+        } else {
+            for position in system.particles_mut().position {
+                *position = new_cell.cartesian(&old_cell.fractional(position));
+            }
         }
 
+        system.cell = new_cell;
+        return system;
+    }
+}
+
+impl Compute for NumericalPressure {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        assert!(!system.cell.is_infinite(), "Can not compute pressure for infinite cell");
+        assert!(self.delta > 0.0, "delta must be positive in NumericalPressure");
         let volume = system.volume();
-        let virial = system.virial();
-        return (kinetic + virial) / volume;
+        let forward = NumericalPressure::scaled(system, volume + self.delta).potential_energy();
+        let backward = NumericalPressure::scaled(system, volume - self.delta).potential_energy();
+
+        let kinetic = if system.simulated_degrees_of_freedom == DegreesOfFreedom::Molecules {
+            KineticTensor.compute(system).trace() / (3.0 * volume)
+        } else {
+            let dof = system.degrees_of_freedom() as f64;
+            dof * K_BOLTZMANN * system.temperature() / (3.0 * volume)
+        };
+        return kinetic - (forward - backward) / (2.0 * self.delta);
+    }
+}
+
+/// Compute the radial distribution function (RDF) between the particles of
+/// two kinds in a single configuration of the system.
+///
+/// The output contains the value of $g_{ab}(r)$ for `bins` bins of equal
+/// width between 0 and `max`, the value at index `i` corresponding to the
+/// distance `(i + 0.5) * max / bins`. Distances are computed using
+/// the nearest image convention, so `max` should not be larger than half the
+/// smallest width of the unit cell. The RDF should usually be averaged over
+/// many configurations.
+///
+/// To compute the RDF between all the pairs of particles kinds at once, use
+/// [`RdfMatrix`].
+///
+/// [`RdfMatrix`]: struct.RdfMatrix.html
+pub struct Rdf {
+    /// Kinds of the particles pairs to use
+    pub kinds: (ParticleKind, ParticleKind),
+    /// Maximal distance of the histogram
+    pub max: f64,
+    /// Number of bins in the histogram
+    pub bins: usize,
+}
+
+impl Compute for Rdf {
+    type Output = Vec<f64>;
+    fn compute(&self, system: &System) -> Vec<f64> {
+        check_rdf_parameters(system, self.max, self.bins);
+        let (a, b) = self.kinds;
+        let kinds = system.particles().kind;
+
+        let mut histogram = vec![0.0; self.bins];
+        for (i, j, r) in system.pairs_within(self.max) {
+            if (kinds[i], kinds[j]) == (a, b) || (kinds[i], kinds[j]) == (b, a) {
+                add_to_rdf_histogram(&mut histogram, r, self.max);
+            }
+        }
+
+        let count_a = kinds.iter().filter(|&&kind| kind == a).count();
+        let count_b = kinds.iter().filter(|&&kind| kind == b).count();
+        return normalize_rdf(histogram, self.max, system.volume(), a == b, count_a, count_b);
+    }
+}
+
+/// Compute the radial distribution functions (RDF) between all the pairs of
+/// particles kinds in a single configuration of the system.
+///
+/// This gives the same results as multiple [`Rdf`] computations, but loops
+/// over the pairs of particles only once. The output associates each pair of
+/// kinds `(a, b)` with `a <= b` to the corresponding RDF, using the same
+/// histogram convention as [`Rdf`].
+///
+/// [`Rdf`]: struct.Rdf.html
+pub struct RdfMatrix {
+    /// Maximal distance of the histograms
+    pub max: f64,
+    /// Number of bins in the histograms
+    pub bins: usize,
+}
+
+impl Compute for RdfMatrix {
+    type Output = BTreeMap<(ParticleKind, ParticleKind), Vec<f64>>;
+    fn compute(&self, system: &System) -> BTreeMap<(ParticleKind, ParticleKind), Vec<f64>> {
+        check_rdf_parameters(system, self.max, self.bins);
+        let kinds = system.particles().kind;
+
+        let mut counts = BTreeMap::new();
+        for &kind in kinds {
+            *counts.entry(kind).or_insert(0) += 1;
+        }
+
+        let mut histograms = BTreeMap::new();
+        for &a in counts.keys() {
+            for &b in counts.keys().filter(|&&b| b >= a) {
+                let _ = histograms.insert((a, b), vec![0.0; self.bins]);
+            }
+        }
+
+        for (i, j, r) in system.pairs_within(self.max) {
+            let pair = if kinds[i] <= kinds[j] { (kinds[i], kinds[j]) } else { (kinds[j], kinds[i]) };
+            let histogram = histograms.get_mut(&pair).expect("missing pair in RDF histograms");
+            add_to_rdf_histogram(histogram, r, self.max);
+        }
+
+        let volume = system.volume();
+        return histograms.into_iter().map(|((a, b), histogram)| {
+            let rdf = normalize_rdf(histogram, self.max, volume, a == b, counts[&a], counts[&b]);
+            ((a, b), rdf)
+        }).collect();
+    }
+}
+
+/// Compute the potential of mean force between the particles of two kinds
+/// from their radial distribution function at the given temperature.
+///
+/// $$ w_{ab}(r) = -k_B T \ln g_{ab}(r) $$
+///
+/// The output uses the same bins as [`Rdf`]. Bins where $g_{ab}(r) = 0$,
+/// i.e. where no pair of particles was found, contain an infinite value.
+/// As the RDF should usually be averaged over many configurations before
+/// being converted, [`PotentialOfMeanForce::from_rdf`] can also be used
+/// directly on an averaged RDF.
+///
+/// [`Rdf`]: struct.Rdf.html
+/// [`PotentialOfMeanForce::from_rdf`]: struct.PotentialOfMeanForce.html#method.from_rdf
+pub struct PotentialOfMeanForce {
+    /// Parameters of the radial distribution function computation
+    pub rdf: Rdf,
+    /// Temperature of the system
+    pub temperature: f64,
+}
+
+impl PotentialOfMeanForce {
+    /// Convert the radial distribution function `rdf` computed at the given
+    /// `temperature` to a potential of mean force. Bins with a zero value in
+    /// the RDF give an infinite potential of mean force.
+    pub fn from_rdf(rdf: &[f64], temperature: f64) -> Vec<f64> {
+        assert!(temperature > 0.0, "temperature must be positive in PotentialOfMeanForce");
+        let kt = K_BOLTZMANN * temperature;
+        return rdf.iter().map(|&g| {
+            if g > 0.0 {
+                -kt * f64::ln(g)
+            } else {
+                f64::INFINITY
+            }
+        }).collect();
+    }
+}
+
+impl Compute for PotentialOfMeanForce {
+    type Output = Vec<f64>;
+    fn compute(&self, system: &System) -> Vec<f64> {
+        let rdf = self.rdf.compute(system);
+        return PotentialOfMeanForce::from_rdf(&rdf, self.temperature);
+    }
+}
+
+/// Check the parameters of RDF computations
+fn check_rdf_parameters(system: &System, max: f64, bins: usize) {
+    assert!(!system.cell.is_infinite(), "Can not compute RDF for infinite cell");
+    assert!(max > 0.0, "max must be positive in RDF");
+    assert!(bins > 0, "bins must be positive in RDF");
+}
+
+/// Add a pair of particles at distance `r` to the RDF `histogram`
+fn add_to_rdf_histogram(histogram: &mut [f64], r: f64, max: f64) {
+    if r < max {
+        let bin = (r / max * histogram.len() as f64) as usize;
+        // Guard against rounding errors for r very close to max
+        if let Some(value) = histogram.get_mut(bin) {
+            *value += 1.0;
+        }
+    }
+}
+
+/// Normalize an histogram containing each pair of particles once into a RDF.
+/// `same_kinds` indicates that the histogram was computed between particles
+/// of the same kind, and `count_a`/`count_b` are the number of particles of
+/// each kind.
+fn normalize_rdf(histogram: Vec<f64>, max: f64, volume: f64, same_kinds: bool, count_a: usize, count_b: usize) -> Vec<f64> {
+    let npairs = if same_kinds {
+        // each pair is counted once, instead of twice in the ordered sum
+        (count_a * count_a.saturating_sub(1)) as f64 / 2.0
+    } else {
+        (count_a * count_b) as f64
+    };
+    if npairs == 0.0 {
+        return histogram;
+    }
+
+    let dr = max / histogram.len() as f64;
+    let density = npairs / volume;
+    return histogram.into_iter().enumerate().map(|(i, count)| {
+        let r_min = i as f64 * dr;
+        let r_max = r_min + dr;
+        let shell = 4.0 / 3.0 * PI * (r_max * r_max * r_max - r_min * r_min * r_min);
+        count / (density * shell)
+    }).collect();
+}
+
+/// Rule used to get the orientation of a molecule in [`OrderParameter`].
+///
+/// [`OrderParameter`]: struct.OrderParameter.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MolecularAxis {
+    /// Use the vector going from the `i`-th to the `j`-th particle of the
+    /// molecule, with indexes relative to the first particle of the molecule.
+    Particles(usize, usize),
+    /// Use the principal axis of inertia with the smallest moment of inertia,
+    /// which corresponds to the long axis of elongated molecules.
+    Inertia,
+}
+
+/// Compute the nematic order parameter $S$ of the molecules in the system.
+///
+/// The orientation $\vec u$ of each molecule is given by the [`axis`] rule,
+/// and is used to build the $\underline{Q}$ tensor
+///
+/// $$ \underline{Q} = \frac 1N \sum_i \frac 32 \vec u_i \otimes \vec u_i -
+///    \frac 12 \underline{I} $$
+///
+/// The director is the eigenvector associated with the largest eigenvalue of
+/// $\underline{Q}$, and this eigenvalue is the order parameter $S = \langle
+/// P_2(\cos \theta) \rangle$, where $\theta$ is the angle between the
+/// molecules and the director. $S$ is 1 for perfectly aligned molecules and
+/// close to 0 for randomly oriented ones.
+///
+/// All the molecules in the system are used, except for molecules with a
+/// single particle when using [`MolecularAxis::Inertia`].
+///
+/// [`axis`]: enum.MolecularAxis.html
+/// [`MolecularAxis::Inertia`]: enum.MolecularAxis.html#variant.Inertia
+pub struct OrderParameter {
+    /// Rule used to get the orientation of each molecule
+    pub axis: MolecularAxis,
+}
+
+impl OrderParameter {
+    /// Get the director of the molecules in the `system`, *i.e.* the
+    /// normalized eigenvector associated with the largest eigenvalue of the
+    /// $\underline{Q}$ tensor. The sign of the director is arbitrary.
+    pub fn director(&self, system: &System) -> Vector3D {
+        let (_, vectors) = self.q_tensor(system).symmetric_eigen();
+        return Vector3D::new(vectors[0][2], vectors[1][2], vectors[2][2]);
+    }
+
+    /// Compute the $\underline{Q}$ tensor of the molecules in the `system`
+    fn q_tensor(&self, system: &System) -> Matrix3 {
+        let mut q_tensor = Matrix3::zero();
+        let mut count = 0_usize;
+        for molecule in system.molecules() {
+            let particles = molecule.particles();
+            let axis = match self.axis {
+                MolecularAxis::Particles(i, j) => {
+                    assert!(
+                        i < particles.len() && j < particles.len(),
+                        "particle index is out of bounds for a molecule of size {} in order parameter",
+                        particles.len()
+                    );
+                    let mut axis = particles.position[j] - particles.position[i];
+                    system.cell.vector_image(&mut axis);
+                    axis
+                }
+                MolecularAxis::Inertia => {
+                    if particles.len() < 2 {
+                        continue;
+                    }
+                    long_inertia_axis(system, &molecule)
+                }
+            };
+
+            let norm = axis.norm();
+            assert!(norm > 0.0, "the axis of a molecule has zero length in order parameter");
+            let axis = axis / norm;
+            q_tensor += 1.5 * axis.tensorial(&axis) - 0.5 * Matrix3::one();
+            count += 1;
+        }
+
+        if count != 0 {
+            q_tensor /= count as f64;
+        }
+        return q_tensor;
+    }
+}
+
+impl Compute for OrderParameter {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        let (values, _) = self.q_tensor(system).symmetric_eigen();
+        return values[2];
     }
 }
 
+/// Get the principal axis of inertia of a `molecule` with the smallest moment
+/// of inertia, using the nearest image of all the particles with respect to
+/// the first one.
+fn long_inertia_axis(system: &System, molecule: &MoleculeRef<'_>) -> Vector3D {
+    let mut molecule = molecule.to_owned();
+    let first = molecule.particles().position[0];
+    for position in molecule.particles_mut().position {
+        let mut delta = *position - first;
+        system.cell.vector_image(&mut delta);
+        *position = first + delta;
+    }
+
+    let (_, vectors) = molecule.inertia().symmetric_eigen();
+    return Vector3D::new(vectors[0][0], vectors[1][0], vectors[2][0]);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::System;
     use crate::consts::K_BOLTZMANN;
-    use crate::{Harmonic, NullPotential, PairInteraction};
+    use crate::{CosineHarmonic, Harmonic, NullPotential, PairInteraction, PairRestriction, VirtualSite, Wolf};
+    use crate::{CoulombicPotential, LennardJones, SoftCoreLennardJones};
+    use crate::{Molecule, Particle, UnitCell};
     use crate::utils::system_from_xyz;
     use crate::units;
 
-    use approx::assert_ulps_eq;
+    use approx::{assert_ulps_eq, assert_relative_eq};
 
     fn test_pairs_system() -> System {
         let mut system = system_from_xyz(
@@ -507,10 +1377,10 @@ mod test {
             5.0,
         );
         interaction.enable_tail_corrections();
-        system.set_pair_potential(("F", "F"), interaction);
+        system.set_pair_potential(("F", "F"), interaction).unwrap();
 
         // unused interaction to check that we do handle this right
-        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0));
+        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0)).unwrap();
 
 
         return system;
@@ -532,7 +1402,7 @@ mod test {
         assert_eq!(system.molecules().count(), 1);
         assert_eq!(system.molecule(0).bonds().len(), 3);
 
-        system.set_pair_potential(("F", "F"), PairInteraction::new(Box::new(NullPotential), 0.0));
+        system.set_pair_potential(("F", "F"), PairInteraction::new(Box::new(NullPotential), 0.0)).unwrap();
 
         system.set_bond_potential(
             ("F", "F"),
@@ -559,7 +1429,7 @@ mod test {
         );
 
         // unused interaction to check that we do handle this right
-        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0));
+        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0)).unwrap();
 
         return system;
     }
@@ -590,6 +1460,129 @@ mod test {
         assert_ulps_eq!(forces_tot.norm2(), 0.0);
     }
 
+    #[test]
+    fn bonded_and_non_bonded_forces() {
+        let mut system = test_molecular_system();
+        system.set_pair_potential(
+            ("F", "F"),
+            PairInteraction::new(Box::new(Harmonic { k: 0.1, x0: 1.2 }), 5.0),
+        ).unwrap();
+        system.particles_mut().charge[0] = 0.5;
+        system.particles_mut().charge[3] = -0.5;
+        system.set_coulomb_potential(Box::new(Wolf::new(4.0))).unwrap();
+
+        let forces = Forces.compute(&system);
+        let bonded = BondedForces.compute(&system);
+        let non_bonded = NonBondedForces.compute(&system);
+        for i in 0..system.size() {
+            assert!(bonded[i].norm() > 1e-3);
+            assert!(non_bonded[i].norm() > 1e-3);
+            assert_relative_eq!(bonded[i] + non_bonded[i], forces[i], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn virtual_sites_forces() {
+        // Two TIP4P/2005 water molecules, with the M-site as the last
+        // particle of each molecule
+        let create_system = || {
+            let mut system = system_from_xyz(
+                "8
+                cell: 20.0
+                O 0.0 0.0 0.0
+                H 0.9572 0.0 0.0
+                H -0.2399872 0.9266272 0.0
+                M 0.0 0.0 0.0
+                O 2.1 1.9 0.4
+                H 2.8 2.5 0.2
+                H 1.3 2.4 0.1
+                M 0.0 0.0 0.0
+                ",
+            );
+            for &(i, j) in &[(0, 1), (0, 2), (0, 3), (4, 5), (4, 6), (4, 7)] {
+                assert!(system.add_bond(i, j).is_empty());
+            }
+            system.set_pair_potential(
+                ("O", "O"),
+                PairInteraction::new(Box::new(Harmonic { k: 0.1, x0: 3.2 }), 8.0),
+            ).unwrap();
+            system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap();
+            let charges = [0.0, 0.5564, 0.5564, -1.1128];
+            for (i, charge) in system.particles_mut().charge.iter_mut().enumerate() {
+                *charge = charges[i % 4];
+            }
+            system
+        };
+
+        let gamma = 0.1546 / 0.5858;
+        let weights = [1.0 - gamma, 0.5 * gamma, 0.5 * gamma];
+        let mut system = create_system();
+        system.add_virtual_site(VirtualSite::new(3, [0, 1, 2], weights));
+        system.add_virtual_site(VirtualSite::new(7, [4, 5, 6], weights));
+        assert_eq!(system.particles().mass[3], 0.0);
+        assert_relative_eq!((system.particles().position[3] - system.particles().position[0]).norm(), 0.1546, epsilon = 1e-4);
+
+        // Same positions, without virtual sites
+        let mut reference = create_system();
+        reference.particles_mut().position.copy_from_slice(system.particles().position);
+
+        let forces = Forces.compute(&system);
+        let reference = Forces.compute(&reference);
+        assert_eq!(forces[3], Vector3D::zero());
+        assert_eq!(forces[7], Vector3D::zero());
+        assert!(reference[3].norm() > 1e-3);
+
+        let total = |forces: &[Vector3D]| forces.iter().fold(Vector3D::zero(), |acc, &force| acc + force);
+        assert_relative_eq!(total(&forces), total(&reference), epsilon = 1e-12);
+
+        let torque = |forces: &[Vector3D]| {
+            soa_zip!(system.particles(), [position], forces).fold(Vector3D::zero(), |acc, (position, force)| {
+                acc + (*position ^ *force)
+            })
+        };
+        assert_relative_eq!(torque(&forces), torque(&reference), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn forces_linear_angle() {
+        let mut system = system_from_xyz(
+            "3
+            cell: 10.0
+            C 0.0 0.0 0.0
+            C 1.5 0.0 0.0
+            C 3.0 0.0 0.0
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(1, 2).is_empty());
+        system.set_pair_potential(("C", "C"), PairInteraction::new(Box::new(NullPotential), 0.0)).unwrap();
+        system.set_angle_potential(
+            ("C", "C", "C"),
+            Box::new(CosineHarmonic::new(
+                units::from(100.0, "kJ/mol").unwrap(),
+                units::from(120.0, "deg").unwrap(),
+            )),
+        );
+
+        // The force vanishes for a linear molecule
+        let forces = system.forces();
+        for force in &forces {
+            assert_eq!(*force, Vector3D::zero());
+        }
+
+        // Close to linear geometry, the forces are well behaved
+        for &dy in &[1e-3, 1e-5] {
+            system.particles_mut().position[2][1] = dy;
+            let forces = system.forces();
+            let expected = system.numerical_forces(1e-8);
+            for i in 0..3 {
+                assert!(forces[i].norm().is_finite());
+                assert_relative_eq!(forces[i], expected[i], epsilon = 1e-8);
+            }
+        }
+    }
+
     #[test]
     #[allow(clippy::unreadable_literal)]
     fn energy_pairs() {
@@ -612,6 +1605,22 @@ mod test {
         assert_ulps_eq!(PotentialEnergy.compute(&system), units::from(1800.0, "kJ/mol").unwrap());
     }
 
+    #[test]
+    fn energy_components() {
+        let system = test_molecular_system();
+        let components = PotentialEnergyComponents.compute(&system);
+        assert_eq!(components, system.energy_components());
+
+        assert_eq!(components.pairs, 0.0);
+        assert_eq!(components.pairs_tail, 0.0);
+        assert_ulps_eq!(components.bonds, units::from(150.0, "kJ/mol").unwrap());
+        assert_ulps_eq!(components.angles, units::from(400.0, "kJ/mol").unwrap());
+        assert_ulps_eq!(components.dihedrals, units::from(1250.0, "kJ/mol").unwrap(), epsilon = 1e-12);
+        assert_eq!(components.coulomb, 0.0);
+        assert_eq!(components.global, 0.0);
+        assert_eq!(components.total(), PotentialEnergy.compute(&system));
+    }
+
     #[test]
     fn temperature() {
         let system = &test_pairs_system();
@@ -620,6 +1629,74 @@ mod test {
         assert_eq!(temperature, system.temperature());
     }
 
+    #[test]
+    fn temperature_components() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        // A diatomic molecule, split across the periodic boundaries
+        let mut molecule = Molecule::new(Particle::with_position("O", Vector3D::new(9.5, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("O", Vector3D::new(0.5, 0.0, 0.0)));
+        system.add_molecule(molecule);
+        let mass = system.particles().mass[0];
+
+        let velocity = Vector3D::new(1e-3, 2e-3, 3e-3);
+        system.particles_mut().velocity[0] = velocity;
+        system.particles_mut().velocity[1] = velocity;
+        let components = system.temperature_components();
+        assert_ulps_eq!(components.translational, 2.0 * mass * velocity.norm2() / (3.0 * K_BOLTZMANN));
+        assert_ulps_eq!(components.rotational, 0.0);
+        assert_ulps_eq!(components.vibrational, 0.0, epsilon = 1e-12);
+
+        // Rotation around the z axis
+        system.particles_mut().velocity[0] = Vector3D::new(0.0, -1e-3, 0.0);
+        system.particles_mut().velocity[1] = Vector3D::new(0.0, 1e-3, 0.0);
+        let components = PartitionedTemperature.compute(&system);
+        assert_ulps_eq!(components.translational, 0.0);
+        assert_ulps_eq!(components.rotational, 2.0 * mass * 1e-6 / (2.0 * K_BOLTZMANN));
+        assert_ulps_eq!(components.vibrational, 0.0, epsilon = 1e-12);
+
+        // Stretching of the bond
+        system.particles_mut().velocity[0] = Vector3D::new(-1e-3, 0.0, 0.0);
+        system.particles_mut().velocity[1] = Vector3D::new(1e-3, 0.0, 0.0);
+        let components = PartitionedTemperature.compute(&system);
+        assert_ulps_eq!(components.translational, 0.0);
+        assert_ulps_eq!(components.rotational, 0.0);
+        assert_ulps_eq!(components.vibrational, 2.0 * mass * 1e-6 / K_BOLTZMANN);
+
+        // Single atoms only have translational degrees of freedom
+        let mut system = System::new();
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::zero())));
+        system.particles_mut().velocity[0] = velocity;
+        let components = PartitionedTemperature.compute(&system);
+        assert_ulps_eq!(components.translational, Temperature.compute(&system));
+        assert_eq!(components.rotational, 0.0);
+        assert_eq!(components.vibrational, 0.0);
+    }
+
+    #[test]
+    fn temperature_components_non_linear() {
+        let mut system = System::new();
+        let mut molecule = Molecule::new(Particle::with_position("O", Vector3D::new(0.0, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", Vector3D::new(0.8, 0.6, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", Vector3D::new(-0.8, 0.6, 0.1)));
+        system.add_molecule(molecule);
+
+        // Rigid rotation around the center of mass
+        let omega = Vector3D::new(1e-3, -2e-3, 3e-3);
+        let com = system.molecule(0).center_of_mass();
+        for i in 0..3 {
+            let delta = system.particles().position[i] - com;
+            system.particles_mut().velocity[i] = omega ^ delta;
+        }
+
+        let inertia = system.molecule(0).inertia();
+        let expected = 2.0 * (0.5 * omega * (inertia * omega)) / (3.0 * K_BOLTZMANN);
+        let components = PartitionedTemperature.compute(&system);
+        assert_ulps_eq!(components.translational, 0.0, epsilon = 1e-12);
+        assert_relative_eq!(components.rotational, expected, max_relative = 1e-12);
+        assert_ulps_eq!(components.vibrational, 0.0, epsilon = 1e-9);
+        assert_relative_eq!(components.rotational, 3.0 * Temperature.compute(&system), max_relative = 1e-12);
+    }
+
     #[test]
     fn volume() {
         let system = &test_pairs_system();
@@ -661,6 +1738,94 @@ mod test {
         assert_eq!(virial, system.virial());
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn virial_components() {
+        let mut system = system_from_xyz(
+            "6
+            cell: 20.0
+            O  0.0  0.0  0.0
+            H -0.7 -0.7  0.3
+            H  0.3 -0.3 -0.8
+            O  2.0  2.0  0.0
+            H  1.3  1.3  0.3
+            H  2.3  1.7 -0.8
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        assert!(system.add_bond(3, 4).is_empty());
+        assert!(system.add_bond(3, 5).is_empty());
+        for particle in system.particles_mut() {
+            if particle.name == "O" {
+                *particle.charge = -0.8476;
+            } else if particle.name == "H" {
+                *particle.charge = 0.4238;
+            }
+        }
+
+        let mut interaction = PairInteraction::new(Box::new(LennardJones { sigma: 3.16, epsilon: 0.065 }), 8.0);
+        interaction.enable_tail_corrections();
+        system.set_pair_potential(("O", "O"), interaction).unwrap();
+        system.set_bond_potential(("O", "H"), Box::new(Harmonic { k: 100.0, x0: 1.0 }));
+        let mut wolf = Wolf::new(8.0);
+        wolf.set_restriction(PairRestriction::InterMolecular);
+        system.set_coulomb_potential(Box::new(wolf)).unwrap();
+
+        let components = AtomicVirialComponents.compute(&system);
+        assert!(components.pairs.norm() > 1e-6);
+        assert!(components.pairs_tail.norm() > 1e-6);
+        assert!(components.bonds.norm() > 1e-6);
+        assert!(components.coulomb.norm() > 1e-6);
+        assert_eq!(components.global, Matrix3::zero());
+        assert_eq!(components.total(), AtomicVirial.compute(&system));
+        assert_eq!(components.total(), system.virial());
+        assert_eq!(components, system.virial_components());
+
+        system.simulated_degrees_of_freedom = DegreesOfFreedom::Molecules;
+        let components = MolecularVirialComponents.compute(&system);
+        assert!(components.pairs.norm() > 1e-6);
+        assert!(components.coulomb.norm() > 1e-6);
+        // Bonds are not included in the molecular virial
+        assert_eq!(components.bonds, Matrix3::zero());
+        assert_eq!(components.total(), MolecularVirial.compute(&system));
+        assert_eq!(components.total(), system.virial());
+        assert_eq!(components, system.virial_components());
+    }
+
+    #[test]
+    fn virial_global_potential_finite_differences() {
+        // Scale the cell and the positions along the `i` axis
+        fn scaled(system: &System, i: usize, eps: f64) -> System {
+            let mut scaling = Matrix3::one();
+            scaling[i][i] += eps;
+            let mut system = system.clone();
+            let old_cell = system.cell;
+            let new_cell = system.cell.scale(scaling);
+            for position in system.particles_mut().position {
+                *position = new_cell.cartesian(&old_cell.fractional(position));
+            }
+            system.cell = new_cell;
+            return system;
+        }
+
+        let mut system = test_pairs_system();
+        system.particles_mut().charge[0] = 0.5;
+        system.particles_mut().charge[1] = -0.5;
+        system.add_global_potential(Box::new(Wolf::new(4.0)));
+
+        let virial = system.virial();
+        let energy = system.potential_energy();
+        // The global potential contributes to the virial
+        assert!((virial - AtomicVirial.compute(&test_pairs_system())).norm() > 1e-3);
+
+        let eps = 1e-7;
+        for i in 0..3 {
+            let finite_diff = (energy - scaled(&system, i, eps).potential_energy()) / eps;
+            assert_relative_eq!(virial[i][i], finite_diff, epsilon = 1e-6, max_relative = 1e-5);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn pressure_at_temperature_negative_temperature() {
@@ -746,6 +1911,82 @@ mod test {
         assert_eq!(stress, system.stress());
     }
 
+    #[test]
+    fn per_atom_stress() {
+        let system = test_pairs_system();
+        let virials = PerAtomStress.compute(&system);
+        assert_eq!(virials.len(), 2);
+        assert_ulps_eq!(virials[0], virials[1]);
+        assert_relative_eq!(virials[0] + virials[1], AtomicVirial.compute(&system), epsilon = 1e-12);
+
+        let system = test_molecular_system();
+        let virials = PerAtomStress.compute(&system);
+        let total = virials.iter().cloned().sum::<Matrix3>();
+        assert_relative_eq!(total, AtomicVirial.compute(&system), epsilon = 1e-12);
+        // The first and last particles are only part of a single bond
+        assert_relative_eq!(virials[0], virials[3], epsilon = 1e-12);
+
+        // Coulombic and global interactions
+        let mut system = test_molecular_system();
+        system.particles_mut().charge[0] = 0.5;
+        system.particles_mut().charge[3] = -0.5;
+        system.set_coulomb_potential(Box::new(Wolf::new(4.0))).unwrap();
+        system.add_global_potential(Box::new(Wolf::new(4.0)));
+        let virials = PerAtomStress.compute(&system);
+        let total = virials.iter().copied().sum::<Matrix3>();
+        assert_relative_eq!(total, AtomicVirial.compute(&system), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn per_atom_stress_lj_fluid() {
+        let mut system = System::with_cell(UnitCell::cubic(17.0));
+        let mut seed = 0.0;
+        for i in 0..5 {
+            for j in 0..5 {
+                for k in 0..5 {
+                    // Small deterministic displacements around a cubic lattice
+                    seed += 1.0;
+                    let jitter = 0.01 * Vector3D::new(f64::sin(seed), f64::sin(2.0 * seed), f64::sin(3.0 * seed));
+                    let position = 3.4 * Vector3D::new(i as f64, j as f64, k as f64) + jitter;
+                    system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+                }
+            }
+        }
+        let mut interaction = PairInteraction::new(Box::new(LennardJones {
+            sigma: units::from(3.4, "A").unwrap(),
+            epsilon: units::from(1.0, "kJ/mol").unwrap(),
+        }), 8.0);
+        interaction.enable_tail_corrections();
+        system.set_pair_potential(("Ar", "Ar"), interaction).unwrap();
+
+        let virials = PerAtomStress.compute(&system);
+        assert_eq!(virials.len(), 125);
+
+        let total = virials.iter().cloned().sum::<Matrix3>();
+        let expected = AtomicVirial.compute(&system);
+        assert_relative_eq!(total, expected, max_relative = 1e-10);
+
+        // All the particles have roughly the same environment
+        let mean = expected.trace() / 125.0;
+        for virial in &virials {
+            assert!(f64::abs(virial.trace() - mean) < 0.5 * f64::abs(mean));
+        }
+    }
+
+    #[test]
+    fn kinetic_tensor() {
+        let system = &test_pairs_system();
+        let tensor = KineticTensor.compute(system);
+        assert_ulps_eq!(tensor.trace(), 2.0 * KineticEnergy.compute(system));
+        assert_eq!(tensor, system.kinetic_tensor());
+
+        let particles = system.particles();
+        let expected = particles.mass[0] * particles.velocity[0][0] * particles.velocity[0][1]
+                     + particles.mass[1] * particles.velocity[1][0] * particles.velocity[1][1];
+        assert_ulps_eq!(tensor[0][1], expected);
+        assert_eq!(tensor[0][1], tensor[1][0]);
+    }
+
     #[test]
     #[should_panic]
     fn stress_infinite_cell() {
@@ -784,4 +2025,250 @@ mod test {
         assert_ulps_eq!(pressure, expected);
         assert_eq!(pressure, system.pressure());
     }
+
+    #[test]
+    fn lambda_derivative() {
+        let mut system = system_from_xyz(
+            "3
+            cell: 10.0
+            Ar 0.0 0.0 0.0
+            Ar 1.5 0.0 0.0
+            Ar 0.0 3.0 0.0
+            ",
+        );
+        let potential = Box::new(SoftCoreLennardJones::new(3.4, 1.0, 0.5));
+        system.set_pair_potential(("Ar", "Ar"), PairInteraction::shifted(potential, 4.0)).unwrap();
+        assert_eq!(LambdaDerivative.compute(&test_pairs_system()), 0.0);
+
+        let delta = 1e-6;
+        system.set_lambda(0.4 + delta);
+        let forward = system.potential_energy();
+        system.set_lambda(0.4 - delta);
+        let backward = system.potential_energy();
+        system.set_lambda(0.4);
+
+        let derivative = LambdaDerivative.compute(&system);
+        assert_relative_eq!(derivative, (forward - backward) / (2.0 * delta), max_relative = 1e-6);
+        assert_eq!(derivative, system.d_energy_d_lambda());
+    }
+
+    #[test]
+    fn numerical_forces() {
+        let system = &test_pairs_system();
+        let forces = NumericalForces { delta: 1e-6 }.compute(system);
+        assert_relative_eq!(forces[0], system.forces()[0], epsilon = 1e-7);
+        assert_relative_eq!(forces[1], system.forces()[1], epsilon = 1e-7);
+        assert_eq!(forces, system.numerical_forces(1e-6));
+
+        let mut system = test_molecular_system();
+        // Move out of the plane, where the dihedral angle is not continuous
+        system.particles_mut().position[3][2] = 0.3;
+        let forces = system.numerical_forces(1e-6);
+        let expected = system.forces();
+        for i in 0..system.size() {
+            assert_relative_eq!(forces[i], expected[i], epsilon = 1e-7);
+        }
+    }
+
+    #[test]
+    fn numerical_pressure() {
+        let system = &test_pairs_system();
+        let pressure = NumericalPressure { delta: 1e-3 }.compute(system);
+        assert_relative_eq!(pressure, system.pressure(), max_relative = 1e-6);
+        assert_eq!(pressure, system.numerical_pressure(1e-3));
+
+        let system = &test_molecular_system();
+        let pressure = system.numerical_pressure(1e-3);
+        assert_relative_eq!(pressure, system.pressure(), epsilon = 1e-10, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn rigid_molecules_pressure() {
+        let mut system = system_from_xyz(
+            "4
+            cell: 10.0
+            F 0.0 0.0 0.0
+            F 1.2 0.0 0.0
+            F 0.3 2.5 0.5
+            F 0.3 3.7 0.5
+            ",
+        );
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(2, 3).is_empty());
+        assert_eq!(system.molecules().count(), 2);
+        system.simulated_degrees_of_freedom = DegreesOfFreedom::Molecules;
+
+        let mut interaction = PairInteraction::new(
+            Box::new(Harmonic {
+                k: units::from(300.0, "kJ/mol/A^2").unwrap(),
+                x0: units::from(3.0, "A").unwrap(),
+            }),
+            5.0,
+        );
+        interaction.set_restriction(PairRestriction::InterMolecular);
+        system.set_pair_potential(("F", "F"), interaction).unwrap();
+
+        // -dE/dV at zero temperature, scaling the molecules centers of mass
+        let potential = NumericalPressure { delta: 1e-6 }.compute(&system);
+
+        // Both molecules rotate around their center of mass, and the second
+        // one also translates
+        let rotation = Vector3D::new(0.0, 0.0, 1e-3);
+        let translation = Vector3D::new(2e-3, -1e-3, 3e-3);
+        system.particles_mut().velocity[0] = -rotation;
+        system.particles_mut().velocity[1] = rotation;
+        system.particles_mut().velocity[2] = translation - rotation;
+        system.particles_mut().velocity[3] = translation + rotation;
+
+        // Only the translation of the molecules contributes to the kinetic
+        // part of the pressure
+        let mass = 2.0 * system.particles().mass[0];
+        let kinetic = mass * translation.norm2() / (3.0 * system.volume());
+
+        let pressure = Pressure.compute(&system);
+        assert_relative_eq!(pressure, potential + kinetic, max_relative = 1e-6);
+        assert_relative_eq!(pressure, system.numerical_pressure(1e-6), max_relative = 1e-6);
+
+        // This is the pressure at the temperature of the centers of mass
+        let temperature = mass * translation.norm2() / (system.degrees_of_freedom() as f64 * K_BOLTZMANN);
+        let at_temperature = PressureAtTemperature { temperature: temperature }.compute(&system);
+        assert_relative_eq!(pressure, at_temperature, max_relative = 1e-12);
+
+        let stress = Stress.compute(&system);
+        assert_relative_eq!(stress.trace() / 3.0, pressure, max_relative = 1e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn numerical_pressure_infinite_cell() {
+        let _ = System::new().numerical_pressure(1e-3);
+    }
+
+    #[test]
+    fn rdf() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(1.5, 0.0, 0.0))));
+        let kind = system.particles().kind[0];
+
+        let rdf = Rdf { kinds: (kind, kind), max: 5.0, bins: 5 }.compute(&system);
+        let shell = 4.0 / 3.0 * PI * (8.0 - 1.0);
+        assert_eq!(rdf[0], 0.0);
+        assert_ulps_eq!(rdf[1], 1000.0 / shell);
+        assert_eq!(rdf[2], 0.0);
+    }
+
+    #[test]
+    fn rdf_matrix() {
+        // A binary mixture with three kinds of pairs
+        let mut system = System::with_cell(UnitCell::cubic(12.0));
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    let name = if (i + j + k) % 3 == 0 { "Na" } else { "Cl" };
+                    let n = f64::from(16 * i + 4 * j + k);
+                    let position = Vector3D::new(
+                        3.0 * f64::from(i) + f64::sin(n),
+                        3.0 * f64::from(j) + f64::cos(2.0 * n),
+                        3.0 * f64::from(k) + f64::sin(3.0 * n),
+                    );
+                    system.add_molecule(Molecule::new(Particle::with_position(name, position)));
+                }
+            }
+        }
+
+        let matrix = RdfMatrix { max: 6.0, bins: 30 }.compute(&system);
+        assert_eq!(matrix.len(), 3);
+        for (&(a, b), rdf) in &matrix {
+            assert!(a <= b);
+            let expected = Rdf { kinds: (a, b), max: 6.0, bins: 30 }.compute(&system);
+            assert_eq!(rdf, &expected);
+            // The order of the kinds does not matter
+            let expected = Rdf { kinds: (b, a), max: 6.0, bins: 30 }.compute(&system);
+            assert_eq!(rdf, &expected);
+            assert!(rdf.iter().any(|&value| value > 0.0));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Can not compute RDF for infinite cell")]
+    fn rdf_infinite_cell() {
+        let _ = RdfMatrix { max: 6.0, bins: 30 }.compute(&System::new());
+    }
+
+    #[test]
+    fn potential_of_mean_force() {
+        let temperature = 300.0;
+        let kt = K_BOLTZMANN * temperature;
+        let pmf = PotentialOfMeanForce::from_rdf(&[0.0, 1.0, 2.0], temperature);
+        assert_eq!(pmf[0], f64::INFINITY);
+        assert_eq!(pmf[1], 0.0);
+        assert_ulps_eq!(pmf[2], -kt * f64::ln(2.0));
+
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(1.5, 0.0, 0.0))));
+        let kind = system.particles().kind[0];
+
+        let rdf = Rdf { kinds: (kind, kind), max: 5.0, bins: 5 };
+        let expected = rdf.compute(&system);
+        let pmf = PotentialOfMeanForce { rdf: rdf, temperature: temperature }.compute(&system);
+        assert_eq!(pmf[0], f64::INFINITY);
+        assert_ulps_eq!(pmf[1], -kt * f64::ln(expected[1]));
+        assert_eq!(pmf[2], f64::INFINITY);
+    }
+
+    /// Create a system containing linear molecules with three particles,
+    /// oriented along each one of the `axes`
+    fn rods_system(axes: &[Vector3D]) -> System {
+        let mut system = System::with_cell(UnitCell::cubic(100.0));
+        for (i, axis) in axes.iter().enumerate() {
+            let center = Vector3D::new(3.0 * (i % 30) as f64, 3.0 * (i / 30 % 30) as f64, 3.0 * (i / 900) as f64);
+            let mut molecule = Molecule::new(Particle::with_position("C", center - axis));
+            molecule.add_particle_bonded_to(0, Particle::with_position("C", center));
+            molecule.add_particle_bonded_to(1, Particle::with_position("O", center + axis));
+            system.add_molecule(molecule);
+        }
+        return system;
+    }
+
+    #[test]
+    fn order_parameter_aligned() {
+        let axis = Vector3D::new(1.0, 1.0, 0.0) / f64::sqrt(2.0);
+        let system = rods_system(&vec![axis; 50]);
+
+        for &rule in &[MolecularAxis::Particles(0, 2), MolecularAxis::Inertia] {
+            let order = OrderParameter { axis: rule };
+            assert_relative_eq!(order.compute(&system), 1.0, epsilon = 1e-12);
+            let director = order.director(&system);
+            assert_relative_eq!(f64::abs(director * axis), 1.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn order_parameter_random() {
+        // Uniformly distributed orientations on the unit sphere, using a
+        // Fibonacci lattice
+        let n = 2000;
+        let golden_angle = PI * (3.0 - f64::sqrt(5.0));
+        let axes = (0..n).map(|i| {
+            let z = 1.0 - 2.0 * (f64::from(i) + 0.5) / f64::from(n);
+            let radius = f64::sqrt(1.0 - z * z);
+            let phi = golden_angle * f64::from(i);
+            Vector3D::new(radius * f64::cos(phi), radius * f64::sin(phi), z)
+        }).collect::<Vec<_>>();
+        let system = rods_system(&axes);
+
+        for &rule in &[MolecularAxis::Particles(0, 2), MolecularAxis::Inertia] {
+            let order = OrderParameter { axis: rule }.compute(&system);
+            assert!(order.abs() < 1e-2, "order parameter is {}", order);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "particle index is out of bounds for a molecule of size 3 in order parameter")]
+    fn order_parameter_bad_index() {
+        let system = rods_system(&[Vector3D::new(0.0, 0.0, 1.0)]);
+        let _ = OrderParameter { axis: MolecularAxis::Particles(0, 3) }.compute(&system);
+    }
 }
@@ -176,11 +176,21 @@ impl Bonding {
         self.rebuild_connections();
     }
 
-    /// Translate all indexes in this molecule by `delta`.
+    /// Translate all indexes in this molecule by `delta`. A negative `delta`
+    /// can move the molecule down to the first index, i.e. `delta` can be
+    /// equal to `-self.start()`.
+    ///
+    /// # Panics
+    ///
+    /// If the translation would create negative indexes.
     pub fn translate_by(&mut self, delta: isize) {
         if delta < 0 {
-            // We should not create negative indexes
-            assert!((delta.abs() as usize) < self.start());
+            // We should not create negative indexes, but the molecule can
+            // start at 0 after the translation
+            assert!(
+                delta.unsigned_abs() <= self.start(),
+                "can not translate a molecule starting at {} by {}", self.start(), delta
+            );
         }
 
         // The wrapping_add are necessary here, and produce the right result,
@@ -319,6 +329,18 @@ mod test {
         assert!(bonding.bonds().contains(&Bond::new(4, 5)));
         assert!(bonding.angles().contains(&Angle::new(3, 4, 5)));
         assert!(bonding.dihedrals().contains(&Dihedral::new(2, 3, 4, 5)));
+
+        bonding.translate_by(-2);
+        assert_eq!(bonding.start(), 0);
+        assert_eq!(bonding.end(), 4);
+        assert!(bonding.bonds().contains(&Bond::new(2, 3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not translate a molecule starting at 2 by -3")]
+    fn translate_by_negative_index() {
+        let mut bonding = Bonding::new(2);
+        bonding.translate_by(-3);
     }
 
     #[test]
@@ -36,6 +36,11 @@ pub struct UnitCell {
     inv: Matrix3,
     /// Unit cell shape
     shape: CellShape,
+    /// Shear rate for Lees-Edwards boundary conditions
+    shear_rate: f64,
+    /// Displacement along x of the periodic images in the y direction, for
+    /// Lees-Edwards boundary conditions
+    shear_offset: f64,
 }
 
 impl UnitCell {
@@ -45,6 +50,8 @@ impl UnitCell {
             cell: Matrix3::zero(),
             inv: Matrix3::zero(),
             shape: CellShape::Infinite,
+            shear_rate: 0.0,
+            shear_offset: 0.0,
         }
     }
     /// Create an orthorhombic unit cell, with side lengths `a, b, c`.
@@ -55,6 +62,8 @@ impl UnitCell {
             cell: cell,
             inv: cell.inverse(),
             shape: CellShape::Orthorhombic,
+            shear_rate: 0.0,
+            shear_offset: 0.0,
         }
     }
     /// Create a cubic unit cell, with side lengths `length, length, length`.
@@ -65,6 +74,8 @@ impl UnitCell {
             cell: cell,
             inv: cell.inverse(),
             shape: CellShape::Orthorhombic,
+            shear_rate: 0.0,
+            shear_offset: 0.0,
         }
     }
     /// Create a triclinic unit cell, with side lengths `a, b, c` and angles
@@ -88,6 +99,55 @@ impl UnitCell {
             cell: cell,
             inv: cell.inverse(),
             shape: CellShape::Triclinic,
+            shear_rate: 0.0,
+            shear_offset: 0.0,
+        }
+    }
+
+    /// Create an unit cell from the three cell vectors `a`, `b` and `c`. The
+    /// cell is orthorhombic if the vectors are aligned with the x, y and z
+    /// axis respectively, and triclinic otherwise.
+    ///
+    /// # Panics
+    ///
+    /// If the vectors are not linearly independent, or do not form a
+    /// right-handed basis, *i.e.* if the volume of the cell is not positive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{UnitCell, CellShape, Vector3D};
+    /// let a = Vector3D::new(4.0, 0.0, 0.0);
+    /// let b = Vector3D::new(1.0, 4.0, 0.0);
+    /// let c = Vector3D::new(0.0, 0.5, 3.0);
+    /// let cell = UnitCell::from_vectors(a, b, c);
+    ///
+    /// assert_eq!(cell.shape(), CellShape::Triclinic);
+    /// assert_eq!(cell.volume(), 48.0);
+    /// ```
+    pub fn from_vectors(a: Vector3D, b: Vector3D, c: Vector3D) -> UnitCell {
+        let cell = Matrix3::new([[a[0], b[0], c[0]], [a[1], b[1], c[1]], [a[2], b[2], c[2]]]);
+        let volume = cell.determinant();
+        assert!(
+            volume > 1e-12 * a.norm() * b.norm() * c.norm(),
+            "Cell vectors must be linearly independent and form a right-handed basis"
+        );
+
+        let is_diagonal = a[1] == 0.0 && a[2] == 0.0 &&
+                          b[0] == 0.0 && b[2] == 0.0 &&
+                          c[0] == 0.0 && c[1] == 0.0;
+        let shape = if is_diagonal {
+            CellShape::Orthorhombic
+        } else {
+            CellShape::Triclinic
+        };
+
+        UnitCell {
+            cell: cell,
+            inv: cell.inverse(),
+            shape: shape,
+            shear_rate: 0.0,
+            shear_offset: 0.0,
         }
     }
 
@@ -216,6 +276,8 @@ impl UnitCell {
             cell: cell,
             inv: cell.inverse(),
             shape: self.shape,
+            shear_rate: self.shear_rate,
+            shear_offset: self.shear_offset,
         }
     }
 
@@ -230,6 +292,13 @@ impl UnitCell {
         self.cell
     }
 
+    /// Get the inverse of the matricial representation of the unit cell, used
+    /// to convert Cartesian coordinates to fractional coordinates. This is a
+    /// zero matrix for infinite cells.
+    pub fn inverse_matrix(&self) -> Matrix3 {
+        self.inv
+    }
+
     /// Get the first vector of the cell
     fn vect_a(&self) -> Vector3D {
         let x = self.cell[0][0];
@@ -255,17 +324,97 @@ impl UnitCell {
     }
 }
 
+/// Lees-Edwards boundary conditions, used to simulate a planar shear flow
+impl UnitCell {
+    /// Use Lees-Edwards (sliding brick) boundary conditions with the given
+    /// `shear_rate`. The flow is along the x axis, with a velocity gradient
+    /// along the y axis: the periodic images of the cell in the y direction
+    /// are displaced along x by an offset growing by `shear_rate * b` per
+    /// unit of time, where `b` is the length of the cell along y. The offset
+    /// is advanced with [`advance_shear`](#method.advance_shear).
+    ///
+    /// # Panics
+    ///
+    /// If the cell is not orthorhombic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{UnitCell, Vector3D};
+    /// let mut cell = UnitCell::cubic(10.0);
+    /// cell.set_shear_rate(0.1);
+    /// cell.advance_shear(3.0);
+    /// assert_eq!(cell.shear_offset(), 3.0);
+    ///
+    /// // The image of the second point across the y boundary is displaced
+    /// // by the offset along x
+    /// let u = Vector3D::new(0.0, 0.5, 0.0);
+    /// let v = Vector3D::new(3.0, 9.5, 0.0);
+    /// assert_eq!(cell.distance(&u, &v), 1.0);
+    /// ```
+    pub fn set_shear_rate(&mut self, shear_rate: f64) {
+        assert!(
+            self.shape == CellShape::Orthorhombic,
+            "Lees-Edwards boundary conditions are only implemented for orthorhombic cells"
+        );
+        self.shear_rate = shear_rate;
+    }
+
+    /// Get the shear rate of Lees-Edwards boundary conditions. This is zero
+    /// if the cell does not use Lees-Edwards boundary conditions.
+    pub fn shear_rate(&self) -> f64 {
+        self.shear_rate
+    }
+
+    /// Get the current displacement along x of the periodic images in the y
+    /// direction, in `[0, a)`.
+    pub fn shear_offset(&self) -> f64 {
+        self.shear_offset
+    }
+
+    /// Set the displacement along x of the periodic images in the y
+    /// direction to `offset`, for example to restart a simulation.
+    ///
+    /// # Panics
+    ///
+    /// If the cell is not orthorhombic and `offset` is not zero.
+    pub fn set_shear_offset(&mut self, offset: f64) {
+        if offset == 0.0 {
+            self.shear_offset = 0.0;
+            return;
+        }
+        assert!(
+            self.shape == CellShape::Orthorhombic,
+            "Lees-Edwards boundary conditions are only implemented for orthorhombic cells"
+        );
+        self.shear_offset = offset - f64::floor(offset / self.a()) * self.a();
+    }
+
+    /// Advance the displacement of the periodic images in the y direction by
+    /// the given `time`. This does nothing if the shear rate is zero.
+    pub fn advance_shear(&mut self, time: f64) {
+        if self.shear_rate != 0.0 {
+            let offset = self.shear_offset + self.shear_rate * self.b() * time;
+            self.set_shear_offset(offset);
+        }
+    }
+}
+
 /// Geometric operations using periodic boundary conditions
 impl UnitCell {
     /// Wrap a vector in the unit cell, obeying the periodic boundary conditions.
     /// For a cubic cell of side length `L`, this produce a vector with all
-    /// components in `[0, L)`.
+    /// components in `[0, L)`. With Lees-Edwards boundary conditions, the
+    /// vector is also displaced along x by the shear offset for each image
+    /// crossed in the y direction.
     pub fn wrap_vector(&self, vect: &mut Vector3D) {
         match self.shape {
             CellShape::Infinite => (),
             CellShape::Orthorhombic => {
+                let images = f64::floor(vect[1] / self.b());
+                vect[0] -= images * self.shear_offset;
+                vect[1] -= images * self.b();
                 vect[0] -= f64::floor(vect[0] / self.a()) * self.a();
-                vect[1] -= f64::floor(vect[1] / self.b()) * self.b();
                 vect[2] -= f64::floor(vect[2] / self.c()) * self.c();
             }
             CellShape::Triclinic => {
@@ -280,13 +429,17 @@ impl UnitCell {
 
     /// Find the image of a vector in the unit cell, obeying the periodic
     /// boundary conditions. For a cubic cell of side length `L`, this produce a
-    /// vector with all components in `[-L/2, L/2)`.
+    /// vector with all components in `[-L/2, L/2)`. With Lees-Edwards boundary
+    /// conditions, the images in the y direction are displaced along x by the
+    /// shear offset.
     pub fn vector_image(&self, vect: &mut Vector3D) {
         match self.shape {
             CellShape::Infinite => (),
             CellShape::Orthorhombic => {
+                let images = f64::round(vect[1] / self.b());
+                vect[0] -= images * self.shear_offset;
+                vect[1] -= images * self.b();
                 vect[0] -= f64::round(vect[0] / self.a()) * self.a();
-                vect[1] -= f64::round(vect[1] / self.b()) * self.b();
                 vect[2] -= f64::round(vect[2] / self.c()) * self.c();
             }
             CellShape::Triclinic => {
@@ -299,23 +452,57 @@ impl UnitCell {
         }
     }
 
-    /// Get the fractional representation of the `vector` in this cell
+    /// Find the shortest image of a vector, obeying the periodic boundary
+    /// conditions. This is the same as `vector_image` for infinite and
+    /// orthorhombic cells. For triclinic cells, rounding the fractional
+    /// coordinates does not always give the shortest image when the cell is
+    /// strongly skewed, so all the neighboring images are also checked.
+    pub fn minimum_image(&self, vect: &mut Vector3D) {
+        self.vector_image(vect);
+        if self.shape != CellShape::Triclinic {
+            return;
+        }
+
+        let mut minimum = *vect;
+        for i in -1..2 {
+            for j in -1..2 {
+                for k in -1..2 {
+                    let shift = Vector3D::new(f64::from(i), f64::from(j), f64::from(k));
+                    let image = *vect + self.cartesian(&shift);
+                    if image.norm2() < minimum.norm2() {
+                        minimum = image;
+                    }
+                }
+            }
+        }
+        *vect = minimum;
+    }
+
+    /// Get the fractional representation of the `vector` in this cell. For
+    /// infinite cells, the vector is returned unchanged.
     #[inline]
     pub fn fractional(&self, vector: &Vector3D) -> Vector3D {
+        if self.is_infinite() {
+            return *vector;
+        }
         return self.inv * vector;
     }
 
     /// Get the Cartesian representation of the `fractional` vector in this
-    /// cell
+    /// cell. For infinite cells, the vector is returned unchanged.
     #[inline]
     pub fn cartesian(&self, fractional: &Vector3D) -> Vector3D {
+        if self.is_infinite() {
+            return *fractional;
+        }
         return self.cell * fractional;
     }
 
-    /// Periodic boundary conditions distance between the point `u` and the point `v`
+    /// Periodic boundary conditions distance between the point `u` and the
+    /// point `v`, using the shortest image of the vector between them.
     pub fn distance(&self, u: &Vector3D, v: &Vector3D) -> f64 {
         let mut d = v - u;
-        self.vector_image(&mut d);
+        self.minimum_image(&mut d);
         return d.norm();
     }
 
@@ -327,7 +514,9 @@ impl UnitCell {
         let mut r23 = r3 - r2;
         self.vector_image(&mut r23);
 
-        return f64::acos(r12 * r23 / (r12.norm() * r23.norm()));
+        let cos = r12 * r23 / (r12.norm() * r23.norm());
+        // Rounding errors can bring the cosine slightly outside of [-1, 1]
+        return f64::acos(f64::clamp(cos, -1.0, 1.0));
     }
 
     /// Get the angle formed by the points at `r1`, `r2` and `r3` using periodic
@@ -348,11 +537,21 @@ impl UnitCell {
         let r12n = r12 / r12_norm;
         let r23n = r23 / r23_norm;
 
-        let cos = r12n * r23n;
-        let sin_inv = 1.0 / f64::sqrt(1.0 - cos * cos);
-
-        let d1 = sin_inv * (cos * r12n - r23n) / r12_norm;
-        let d3 = sin_inv * (cos * r23n - r12n) / r23_norm;
+        let cos = f64::clamp(r12n * r23n, -1.0, 1.0);
+        // Using the cross product instead of `sqrt(1 - cos^2)` for the sine
+        // keeps full precision for nearly linear angles. The derivatives are
+        // then `-(normal ^ r12n) / (sin * |r12|)` and `-(r23n ^ normal) / (sin
+        // * |r23|)`, and the ratio of the cross products to the sine is a
+        // unit vector, even when the sine goes to zero.
+        let normal = r12n ^ r23n;
+        let sin = normal.norm();
+        // The derivatives are not defined for linear angles, where the
+        // direction of bending is arbitrary. Use zero in this case, which is
+        // the right limit for potentials with a vanishing force at 0 and pi.
+        let sin_inv = if sin > 0.0 { 1.0 / sin } else { 0.0 };
+
+        let d1 = -sin_inv * (normal ^ r12n) / r12_norm;
+        let d3 = -sin_inv * (r23n ^ normal) / r23_norm;
         let d2 = -(d1 + d3);
 
         return (f64::acos(cos), d1, d2, d3);
@@ -529,6 +728,64 @@ mod tests {
         assert_relative_eq!(cell.volume(), 55.410529, epsilon = 1e-6);
     }
 
+    #[test]
+    fn from_vectors() {
+        let cell = UnitCell::from_vectors(
+            Vector3D::new(3.0, 0.0, 0.0),
+            Vector3D::new(0.0, 4.0, 0.0),
+            Vector3D::new(0.0, 0.0, 5.0),
+        );
+        assert_eq!(cell, UnitCell::ortho(3.0, 4.0, 5.0));
+
+        let a = Vector3D::new(4.2, 0.0, 0.0);
+        let b = Vector3D::new(-1.3, 3.9, 0.0);
+        let c = Vector3D::new(0.8, -1.1, 5.3);
+        let cell = UnitCell::from_vectors(a, b, c);
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        assert_eq!(cell.vect_a(), a);
+        assert_eq!(cell.vect_b(), b);
+        assert_eq!(cell.vect_c(), c);
+        assert_ulps_eq!(cell.matrix() * cell.inverse_matrix(), Matrix3::one(), epsilon = 1e-15);
+
+        // Round-trip through lengths and angles
+        let other = UnitCell::triclinic(cell.a(), cell.b(), cell.c(), cell.alpha(), cell.beta(), cell.gamma());
+        assert_ulps_eq!(other.vect_a(), a, epsilon = 1e-12);
+        assert_ulps_eq!(other.vect_b(), b, epsilon = 1e-12);
+        assert_ulps_eq!(other.vect_c(), c, epsilon = 1e-12);
+        assert_ulps_eq!(other.volume(), cell.volume(), epsilon = 1e-12);
+
+        // Rotated vectors give the same lengths, angles and volume
+        let rotation = Matrix3::rotation(&Vector3D::new(1.0, 2.0, -0.5), 0.7);
+        let rotated = UnitCell::from_vectors(rotation * a, rotation * b, rotation * c);
+        assert_ulps_eq!(rotated.a(), cell.a(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.b(), cell.b(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.c(), cell.c(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.alpha(), cell.alpha(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.beta(), cell.beta(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.gamma(), cell.gamma(), epsilon = 1e-12);
+        assert_ulps_eq!(rotated.volume(), cell.volume(), epsilon = 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cell vectors must be linearly independent and form a right-handed basis")]
+    fn from_coplanar_vectors() {
+        let _ = UnitCell::from_vectors(
+            Vector3D::new(3.0, 0.0, 0.0),
+            Vector3D::new(1.0, 4.0, 0.0),
+            Vector3D::new(4.0, 4.0, 0.0),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cell vectors must be linearly independent and form a right-handed basis")]
+    fn from_left_handed_vectors() {
+        let _ = UnitCell::from_vectors(
+            Vector3D::new(3.0, 0.0, 0.0),
+            Vector3D::new(0.0, 4.0, 0.0),
+            Vector3D::new(0.0, 0.0, -5.0),
+        );
+    }
+
     #[test]
     fn lengths() {
         let ortho = UnitCell::ortho(3.0, 4.0, 5.0);
@@ -678,6 +935,88 @@ mod tests {
         assert_ulps_eq!(v[2], res[2], max_ulps = 5);
     }
 
+    #[test]
+    fn minimum_image() {
+        // Same as vector_image for orthorhombic cells
+        let cell = UnitCell::ortho(3.0, 4.0, 5.0);
+        let mut v = Vector3D::new(1.0, 1.5, 6.0);
+        cell.minimum_image(&mut v);
+        assert_eq!(v, Vector3D::new(1.0, 1.5, 1.0));
+
+        // Strongly skewed triclinic cell, where rounding the fractional
+        // coordinates does not give the shortest image
+        let cell = UnitCell::from_vectors(
+            Vector3D::new(10.0, 0.0, 0.0),
+            Vector3D::new(10.0, 10.0, 0.0),
+            Vector3D::new(0.0, 10.0, 10.0),
+        );
+        let mut v = Vector3D::new(4.0, -4.0, 4.0);
+        cell.vector_image(&mut v);
+        assert!(v.norm() > 8.0);
+
+        let mut v = Vector3D::new(4.0, -4.0, 4.0);
+        cell.minimum_image(&mut v);
+        assert!(f64::abs(v.norm() - f64::sqrt(48.0)) < 1e-12);
+    }
+
+    #[test]
+    fn lees_edwards() {
+        let mut cell = UnitCell::ortho(10.0, 8.0, 6.0);
+        cell.set_shear_rate(0.25);
+        assert_eq!(cell.shear_rate(), 0.25);
+        assert_eq!(cell.shear_offset(), 0.0);
+
+        cell.advance_shear(2.0);
+        assert_eq!(cell.shear_offset(), 4.0);
+        // The offset is kept in [0, a)
+        cell.advance_shear(4.0);
+        assert_eq!(cell.shear_offset(), 2.0);
+        cell.set_shear_offset(-3.0);
+        assert_eq!(cell.shear_offset(), 7.0);
+
+        // A pair straddling the sheared boundary
+        cell.set_shear_offset(3.0);
+        let u = Vector3D::new(1.0, 0.5, 0.0);
+        let v = Vector3D::new(4.5, 7.5, 0.0);
+        let mut d = v - u;
+        cell.vector_image(&mut d);
+        assert_eq!(d, Vector3D::new(0.5, -1.0, 0.0));
+        assert_eq!(cell.distance(&u, &v), f64::sqrt(1.25));
+        assert_eq!(cell.distance(&v, &u), f64::sqrt(1.25));
+
+        // Without offset, the image is not displaced
+        let mut d = v - u;
+        UnitCell::ortho(10.0, 8.0, 6.0).vector_image(&mut d);
+        assert_eq!(d, Vector3D::new(3.5, -1.0, 0.0));
+
+        // Pairs not crossing the boundary along y are not affected
+        let u = Vector3D::new(1.0, 1.0, 0.0);
+        let v = Vector3D::new(9.5, 2.0, 5.5);
+        let mut d = v - u;
+        cell.vector_image(&mut d);
+        assert_eq!(d, Vector3D::new(-1.5, 1.0, -0.5));
+
+        // Wrapping across the y boundary also applies the offset
+        let mut v = Vector3D::new(1.0, 9.0, 0.0);
+        cell.wrap_vector(&mut v);
+        assert_eq!(v, Vector3D::new(8.0, 1.0, 0.0));
+        let mut v = Vector3D::new(1.0, -1.0, 0.0);
+        cell.wrap_vector(&mut v);
+        assert_eq!(v, Vector3D::new(4.0, 7.0, 0.0));
+
+        // Scaling the cell keeps the Lees-Edwards parameters
+        let scaled = cell.scale(Matrix3::one());
+        assert_eq!(scaled.shear_rate(), 0.25);
+        assert_eq!(scaled.shear_offset(), 3.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Lees-Edwards boundary conditions are only implemented for orthorhombic cells")]
+    fn lees_edwards_triclinic() {
+        let mut cell = UnitCell::triclinic(3.0, 4.0, 5.0, 80.0, 90.0, 90.0);
+        cell.set_shear_rate(0.1);
+    }
+
     #[test]
     fn fractional_cartesian() {
         let cell = UnitCell::cubic(5.0);
@@ -695,6 +1034,11 @@ mod tests {
             let transformed = cell.cartesian(&cell.fractional(test));
             assert_ulps_eq!(test, &transformed, epsilon = 1e-15);
         }
+
+        let cell = UnitCell::infinite();
+        let vector = Vector3D::new(-5.0, 12.0, 4.9);
+        assert_eq!(cell.fractional(&vector), vector);
+        assert_eq!(cell.cartesian(&vector), vector);
     }
 
     #[test]
@@ -743,6 +1087,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn near_linear_angle_derivatives() {
+        const EPS: f64 = 1e-6;
+        let cell = UnitCell::infinite();
+        let a = Vector3D::new(1.5, 0.0, 0.0);
+        let b = Vector3D::zero();
+
+        for &degrees in &[179.0, 179.9, 179.99] {
+            let theta = f64::to_radians(degrees);
+            let c = Vector3D::new(1.2 * f64::cos(theta), 1.2 * f64::sin(theta), 0.0);
+
+            let (angle, d1, d2, d3) = cell.angle_and_derivatives(&a, &b, &c);
+            assert_relative_eq!(angle, theta, epsilon = 1e-10);
+
+            // The derivatives do not diverge when the angle gets close to
+            // pi, their norm is 1 / r for the outer points
+            assert_relative_eq!(d1.norm(), 1.0 / 1.5, epsilon = 1e-10);
+            assert_relative_eq!(d3.norm(), 1.0 / 1.2, epsilon = 1e-10);
+            assert!(d2.norm().is_finite());
+
+            // Check by comparison to centered finite differences
+            for i in 0..3 {
+                let (mut p, mut m) = (a, a);
+                p[i] += EPS;
+                m[i] -= EPS;
+                let finite = (cell.angle(&p, &b, &c) - cell.angle(&m, &b, &c)) / (2.0 * EPS);
+                assert_relative_eq!(finite, d1[i], epsilon = 1e-5);
+            }
+
+            for i in 0..3 {
+                let (mut p, mut m) = (c, c);
+                p[i] += EPS;
+                m[i] -= EPS;
+                let finite = (cell.angle(&a, &b, &p) - cell.angle(&a, &b, &m)) / (2.0 * EPS);
+                assert_relative_eq!(finite, d3[i], epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_angle_derivatives() {
+        let cell = UnitCell::infinite();
+        let a = Vector3D::new(1.0, 0.0, 0.0);
+        let b = Vector3D::zero();
+        let c = Vector3D::new(-2.0, 0.0, 0.0);
+
+        let (angle, d1, d2, d3) = cell.angle_and_derivatives(&a, &b, &c);
+        assert_eq!(angle, PI);
+        assert_eq!(angle, cell.angle(&a, &b, &c));
+        assert_eq!(d1, Vector3D::zero());
+        assert_eq!(d2, Vector3D::zero());
+        assert_eq!(d3, Vector3D::zero());
+
+        let (angle, d1, d2, d3) = cell.angle_and_derivatives(&a, &b, &a);
+        assert_eq!(angle, 0.0);
+        assert!(d1.norm().is_finite() && d2.norm().is_finite() && d3.norm().is_finite());
+    }
+
     #[test]
     fn dihedrals() {
         let cell = UnitCell::infinite();
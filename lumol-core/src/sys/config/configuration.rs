@@ -1,14 +1,18 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) 2015-2016 Lumol's contributors — BSD license
 
+use std::borrow::Cow;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 use std::marker::PhantomData;
 
 use log::trace;
 use log_once::warn_once;
 
 use crate::Vector3D;
-use crate::{BondDistances, Bonding, ParticleKind, UnitCell};
+use crate::{BondDistances, Bonding, ParticleKind, UnitCell, VirtualSite};
 use crate::{ParticleSlice, ParticleSliceMut, ParticleVec, ParticlePtr, ParticlePtrMut};
 use crate::{Molecule, MoleculeRef, MoleculeRefMut};
 use crate::BondPath;
@@ -33,6 +37,69 @@ impl Permutation {
     }
 }
 
+/// Possible inconsistencies in the molecules of a `Configuration`, as reported
+/// by [`Configuration::validate`].
+///
+/// [`Configuration::validate`]: struct.Configuration.html#method.validate
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TopologyError {
+    /// The molecule does not start right after the previous one
+    NonContiguous {
+        /// Index of the molecule
+        molecule: usize,
+        /// Expected index of the first particle in the molecule
+        expected: usize,
+        /// Actual index of the first particle in the molecule
+        start: usize,
+    },
+    /// Some particles at the end of the configuration are not part of any
+    /// molecule
+    Uncovered {
+        /// Index of the first particle not part of any molecule
+        first: usize,
+    },
+    /// The molecule index associated with a particle does not match the
+    /// molecule containing this particle
+    MoleculeId {
+        /// Index of the particle
+        particle: usize,
+        /// Index of the molecule containing the particle
+        expected: usize,
+        /// Molecule index associated with the particle
+        actual: usize,
+    },
+    /// A bond in the molecule involves a particle outside of the molecule
+    ForeignBond {
+        /// Index of the molecule
+        molecule: usize,
+        /// First particle in the bond
+        i: usize,
+        /// Second particle in the bond
+        j: usize,
+    },
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TopologyError::NonContiguous { molecule, expected, start } => write!(f,
+                "molecule {} starts at particle {} instead of particle {}", molecule, start, expected
+            ),
+            TopologyError::Uncovered { first } => write!(f,
+                "particles starting at index {} are not part of any molecule", first
+            ),
+            TopologyError::MoleculeId { particle, expected, actual } => write!(f,
+                "particle {} is in molecule {} but is associated with molecule {}", particle, expected, actual
+            ),
+            TopologyError::ForeignBond { molecule, i, j } => write!(f,
+                "the bond {}-{} in molecule {} involves particles outside of the molecule", i, j, molecule
+            ),
+        }
+    }
+}
+
+impl Error for TopologyError {}
+
 /// The `Configuration` contains the physical data of the system:
 ///
 /// - The unit cell;
@@ -48,6 +115,11 @@ pub struct Configuration {
     bondings: Vec<Bonding>,
     /// Molecules indexes for all the particles
     molecule_ids: Vec<usize>,
+    /// For each particle, the sorted list of particles explicitly excluded
+    /// from the non-bonded interactions with it
+    exclusions: Vec<Vec<usize>>,
+    /// Virtual interaction sites in the system
+    virtual_sites: Vec<VirtualSite>,
 }
 
 impl Configuration {
@@ -57,6 +129,8 @@ impl Configuration {
             particles: ParticleVec::new(),
             bondings: Vec::new(),
             molecule_ids: Vec::new(),
+            exclusions: Vec::new(),
+            virtual_sites: Vec::new(),
             cell: UnitCell::infinite(),
         }
     }
@@ -120,10 +194,14 @@ impl Configuration {
     /// Get the length of the shortest bond path to go from the particle `i` to
     /// the particle `j`. If the particles are not in the same molecule, the
     /// length is -1. Else, this length is 0 if `i == j`, 1 if there is a bond
-    /// between `i` and `j`, etc.
+    /// between `i` and `j`, etc. Pairs explicitly excluded with
+    /// [`add_exclusion`](#method.add_exclusion) always give
+    /// `BondPath::Excluded`.
     pub fn bond_path(&self, i: usize, j: usize) -> BondPath {
         assert!(i < self.size() && j < self.size());
-        if !(self.are_in_same_molecule(i, j)) {
+        if self.is_excluded(i, j) {
+            BondPath::Excluded
+        } else if !(self.are_in_same_molecule(i, j)) {
             BondPath::None
         } else if i == j {
             BondPath::SameParticle
@@ -143,6 +221,156 @@ impl Configuration {
         }
     }
 
+    /// Get an iterator over all the bonds in this configuration, as `(i, j)`
+    /// pairs of particles indexes with `i < j`. The bonds are yielded molecule
+    /// by molecule, in no particular order inside a molecule.
+    pub fn bonds<'a>(&'a self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.bondings.iter().flat_map(|bonding| {
+            bonding.bonds().iter().map(|bond| (bond.i(), bond.j()))
+        })
+    }
+
+    /// Get an iterator over all the angles in this configuration, as `(i, j,
+    /// k)` triplets of particles indexes with `j` the central particle and
+    /// `i < k`. The angles are yielded molecule by molecule, in no particular
+    /// order inside a molecule.
+    pub fn angles<'a>(&'a self) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        self.bondings.iter().flat_map(|bonding| {
+            bonding.angles().iter().map(|angle| (angle.i(), angle.j(), angle.k()))
+        })
+    }
+
+    /// Get an iterator over all the dihedral angles in this configuration, as
+    /// `(i, j, k, m)` quadruplets of particles indexes, with `j - k` the
+    /// central bond. The dihedral angles are yielded molecule by molecule, in
+    /// no particular order inside a molecule.
+    pub fn dihedrals<'a>(&'a self) -> impl Iterator<Item = (usize, usize, usize, usize)> + 'a {
+        self.bondings.iter().flat_map(|bonding| {
+            bonding.dihedrals().iter().map(|dihedral| {
+                (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m())
+            })
+        })
+    }
+
+    /// Exclude the pair of particles at indexes `i` and `j` from all the
+    /// non-bonded interactions (pairs potentials and electrostatic
+    /// interactions), in addition to the exclusions coming from the pair
+    /// restrictions. This can be used for pairs which are not bonded
+    /// together, for example with virtual sites.
+    ///
+    /// The exclusions are updated when particles are moved around by
+    /// [`add_bond`](#method.add_bond) or
+    /// [`remove_molecule`](#method.remove_molecule).
+    ///
+    /// # Panics
+    ///
+    /// If `i` or `j` are out of bounds, or if `i == j`.
+    pub fn add_exclusion(&mut self, i: usize, j: usize) {
+        assert!(i < self.size() && j < self.size(), "particle index out of bounds in exclusion");
+        assert_ne!(i, j, "can not exclude a particle from interacting with itself");
+        for &(i, j) in &[(i, j), (j, i)] {
+            if let Err(position) = self.exclusions[i].binary_search(&j) {
+                self.exclusions[i].insert(position, j);
+            }
+        }
+    }
+
+    /// Check if the pair of particles at indexes `i` and `j` was explicitly
+    /// excluded from the non-bonded interactions with
+    /// [`add_exclusion`](#method.add_exclusion).
+    #[inline]
+    pub fn is_excluded(&self, i: usize, j: usize) -> bool {
+        let excluded = &self.exclusions[i];
+        !excluded.is_empty() && excluded.binary_search(&j).is_ok()
+    }
+
+    /// Get the list of explicitly excluded pairs of particles, with the
+    /// smallest index first in each pair.
+    pub fn exclusions(&self) -> Vec<(usize, usize)> {
+        self.exclusions.iter().enumerate().flat_map(|(i, excluded)| {
+            excluded.iter().filter(move |&&j| i < j).map(move |&j| (i, j))
+        }).collect()
+    }
+
+    /// Add a virtual interaction `site` to this configuration. The mass of
+    /// the site particle is set to zero, and its position and velocity are
+    /// set from the parent particles.
+    ///
+    /// The virtual sites are updated when particles are moved around by
+    /// [`add_bond`](#method.add_bond) or
+    /// [`remove_molecule`](#method.remove_molecule).
+    ///
+    /// # Panics
+    ///
+    /// If the site or one of its parents is out of bounds, if one of the
+    /// parents is not in the same molecule as the site, or if the particle is
+    /// already a virtual site.
+    pub fn add_virtual_site(&mut self, site: VirtualSite) {
+        let natoms = self.size();
+        assert!(
+            site.site() < natoms && site.parents().iter().all(|&i| i < natoms),
+            "particle index out of bounds in virtual site"
+        );
+        assert!(
+            site.parents().iter().all(|&i| self.are_in_same_molecule(i, site.site())),
+            "the parents of a virtual site must be in the same molecule as the site"
+        );
+        assert!(
+            self.virtual_sites.iter().all(|other| other.site() != site.site()),
+            "particle {} is already a virtual site", site.site()
+        );
+
+        self.particles.mass[site.site()] = 0.0;
+        self.virtual_sites.push(site);
+        self.update_virtual_sites();
+    }
+
+    /// Get the virtual interaction sites in this configuration
+    pub fn virtual_sites(&self) -> &[VirtualSite] {
+        &self.virtual_sites
+    }
+
+    /// Update the positions and velocities of all the virtual sites from the
+    /// positions and velocities of their parents. This should be called
+    /// every time the particles are moved.
+    pub fn update_virtual_sites(&mut self) {
+        for site in &self.virtual_sites {
+            let position = site.position(&self.cell, &self.particles.position);
+            let velocity = site.velocity(&self.particles.velocity);
+            self.particles.position[site.site()] = position;
+            self.particles.velocity[site.site()] = velocity;
+        }
+    }
+
+    /// Move the forces acting on the virtual sites to their parents, setting
+    /// the force on the sites to zero. The total force and torque are
+    /// conserved.
+    pub fn spread_virtual_forces(&self, forces: &mut [Vector3D]) {
+        for site in &self.virtual_sites {
+            site.spread_force(forces);
+        }
+    }
+
+    /// Update the exclusions and virtual sites by replacing each particle
+    /// index with the result of `update`, removing the exclusions and sites
+    /// where it returns `None`.
+    fn update_indexes<F>(&mut self, update: F) where F: Fn(usize) -> Option<usize> {
+        let pairs = self.exclusions();
+        if pairs.is_empty() {
+            self.exclusions.truncate(self.size());
+        } else {
+            self.exclusions.clear();
+            self.exclusions.resize(self.size(), Vec::new());
+            for (i, j) in pairs {
+                if let (Some(i), Some(j)) = (update(i), update(j)) {
+                    self.add_exclusion(i, j);
+                }
+            }
+        }
+
+        self.virtual_sites = self.virtual_sites.iter().filter_map(|site| site.update_indexes(&update)).collect();
+    }
+
     /// Remove the molecule at index `i`
     pub fn remove_molecule(&mut self, molid: usize) {
         let molecule = self.bondings.remove(molid);
@@ -161,6 +389,16 @@ impl Configuration {
         for molid in self.molecule_ids.iter_mut().skip(first) {
             *molid -= 1;
         }
+
+        self.update_indexes(|i| {
+            if i < first {
+                Some(i)
+            } else if i < first + size {
+                None
+            } else {
+                Some(i - size)
+            }
+        });
     }
 
     /// Add a bond between the particles at indexes `i` and `j`. The particles
@@ -230,6 +468,11 @@ impl Configuration {
 
         assert_eq!(self.molecule_ids[particle_i], self.molecule_ids[particle_j]);
         self.bondings[self.molecule_ids[particle_i]].add_bond(particle_i, particle_j);
+
+        self.update_indexes(|i| {
+            let new = permutations.iter().find(|permutation| permutation.old == i);
+            Some(new.map_or(i, |permutation| permutation.new))
+        });
         return permutations;
     }
 
@@ -250,10 +493,156 @@ impl Configuration {
         bonding.translate_by(self.particles.len() as isize);
 
         self.molecule_ids.append(&mut vec![self.bondings.len(); bonding.size()]);
+        self.exclusions.resize(self.exclusions.len() + bonding.size(), Vec::new());
         self.bondings.push(bonding);
         self.particles.append(&mut molecule.particles);
     }
 
+    /// Check the molecules bookkeeping in this configuration: the particles
+    /// in each molecule must be contiguous, the molecules must cover all the
+    /// particles in order, and the bonds must stay inside their molecule.
+    ///
+    /// The functions modifying the configuration maintain these invariants,
+    /// this function is intended to check code manipulating the topology at
+    /// a lower level. Use [`defragment`](#method.defragment) to restore the
+    /// invariants.
+    pub fn validate(&self) -> Result<(), TopologyError> {
+        let mut expected = 0;
+        for (molid, bonding) in self.bondings.iter().enumerate() {
+            if bonding.start() != expected {
+                return Err(TopologyError::NonContiguous {
+                    molecule: molid,
+                    expected: expected,
+                    start: bonding.start(),
+                });
+            }
+            expected = bonding.end();
+
+            for bond in bonding.bonds() {
+                if !bonding.contains(bond.i()) || !bonding.contains(bond.j()) {
+                    return Err(TopologyError::ForeignBond {
+                        molecule: molid,
+                        i: bond.i(),
+                        j: bond.j(),
+                    });
+                }
+            }
+        }
+
+        if expected != self.size() {
+            return Err(TopologyError::Uncovered { first: min(expected, self.size()) });
+        }
+
+        for (molid, bonding) in self.bondings.iter().enumerate() {
+            for i in bonding.indexes() {
+                if self.molecule_ids[i] != molid {
+                    return Err(TopologyError::MoleculeId {
+                        particle: i,
+                        expected: molid,
+                        actual: self.molecule_ids[i],
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reorder the particles to make all molecules contiguous again.
+    ///
+    /// The molecules are rebuilt from the molecule index associated with each
+    /// particle and from the bonds: particles with the same molecule index,
+    /// or bonded together, end up in the same molecule. The molecules are
+    /// sorted by their first particle, and particles keep their relative
+    /// order inside a molecule. Exclusions and virtual sites are updated
+    /// accordingly.
+    ///
+    /// This function returns the list of permutations applied to the
+    /// particles, which is empty if the configuration was already valid.
+    pub fn defragment(&mut self) -> Vec<Permutation> {
+        let natoms = self.size();
+        assert_eq!(self.molecule_ids.len(), natoms, "missing molecule index for some particles");
+
+        // Find the molecules with an union-find structure, always using the
+        // smallest particle index as the root
+        fn root(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            return i;
+        }
+        fn union(parents: &mut [usize], i: usize, j: usize) {
+            let (root_i, root_j) = (root(parents, i), root(parents, j));
+            parents[max(root_i, root_j)] = min(root_i, root_j);
+        }
+        let mut parents = (0..natoms).collect::<Vec<_>>();
+
+        let mut first_in_molecule = BTreeMap::new();
+        for i in 0..natoms {
+            let first = *first_in_molecule.entry(self.molecule_ids[i]).or_insert(i);
+            union(&mut parents, first, i);
+        }
+
+        let mut bonds = Vec::new();
+        for bonding in &self.bondings {
+            for bond in bonding.bonds() {
+                assert!(bond.i() < natoms && bond.j() < natoms, "bond index out of bounds in configuration");
+                union(&mut parents, bond.i(), bond.j());
+                bonds.push((bond.i(), bond.j()));
+            }
+        }
+
+        // Since the root is always the smallest index, sorting the particles
+        // by root gives the new order of the particles
+        let mut order = (0..natoms).collect::<Vec<_>>();
+        let roots = (0..natoms).map(|i| root(&mut parents, i)).collect::<Vec<_>>();
+        order.sort_by_key(|&i| (roots[i], i));
+
+        let mut new_indexes = vec![0; natoms];
+        for (new, &old) in order.iter().enumerate() {
+            new_indexes[old] = new;
+        }
+
+        // Reorder the particles
+        let mut particles = Vec::with_capacity(natoms);
+        while let Some(particle) = self.particles.pop() {
+            particles.push(Some(particle));
+        }
+        particles.reverse();
+        for &old in &order {
+            let particle = particles[old].take().expect("particle used twice");
+            self.particles.push(particle);
+        }
+
+        // Rebuild the molecules
+        self.bondings.clear();
+        self.molecule_ids.clear();
+        for (new, &old) in order.iter().enumerate() {
+            if new == 0 || roots[old] != roots[order[new - 1]] {
+                self.bondings.push(Bonding::new(new));
+            } else {
+                let last = self.bondings.last_mut().expect("missing molecule");
+                last.merge_with(Bonding::new(new));
+            }
+            self.molecule_ids.push(self.bondings.len() - 1);
+        }
+        for (i, j) in bonds {
+            let (i, j) = (new_indexes[i], new_indexes[j]);
+            let molid = self.molecule_ids[i];
+            self.bondings[molid].add_bond(i, j);
+        }
+
+        self.update_indexes(|i| Some(new_indexes[i]));
+        debug_assert!(self.validate().is_ok());
+
+        return new_indexes.iter()
+            .enumerate()
+            .filter(|&(old, &new)| old != new)
+            .map(|(old, &new)| Permutation::new(old, new))
+            .collect();
+    }
+
     /// Get the number of particles in this configuration
     #[inline]
     pub fn size(&self) -> usize {
@@ -266,6 +655,35 @@ impl Configuration {
         self.particles.is_empty()
     }
 
+    /// Get the scaling factor for the non-bonded interactions between the
+    /// particles `i` and `j`, i.e. the geometric mean of the particles
+    /// couplings.
+    #[inline]
+    pub fn coupling(&self, i: usize, j: usize) -> f64 {
+        let couplings = &self.particles.coupling;
+        let (coupling_i, coupling_j) = (couplings[i], couplings[j]);
+        if coupling_i == 1.0 && coupling_j == 1.0 {
+            1.0
+        } else {
+            f64::sqrt(coupling_i * coupling_j)
+        }
+    }
+
+    /// Get the charges of all the particles, scaled by the square root of the
+    /// particles couplings. Using these charges in the coulombic interactions
+    /// scales the interaction between particles `i` and `j` by the same
+    /// factor as [`coupling`](#method.coupling).
+    pub fn coupled_charges(&self) -> Cow<'_, [f64]> {
+        let particles = self.particles();
+        if particles.coupling.iter().all(|&coupling| coupling == 1.0) {
+            Cow::Borrowed(particles.charge)
+        } else {
+            Cow::Owned(particles.charge.iter().zip(particles.coupling).map(|(charge, coupling)| {
+                charge * f64::sqrt(*coupling)
+            }).collect())
+        }
+    }
+
     /// Return the center-of-mass of the configuration
     pub fn center_of_mass(&self) -> Vector3D {
         let mut total_mass = 0.0;
@@ -395,13 +813,47 @@ impl Configuration {
     }
 
     /// Get the vector between the nearest image of particle `j` with respect to
-    /// particle `i`.
+    /// particle `i`. This is the shortest image, even in strongly skewed
+    /// triclinic cells.
     pub fn nearest_image(&self, i: usize, j: usize) -> Vector3D {
         let mut res = self.particles.position[i] - self.particles.position[j];
-        self.cell.vector_image(&mut res);
+        self.cell.minimum_image(&mut res);
         return res;
     }
 
+    /// Get the positions of all the particles in fractional coordinates of
+    /// the unit cell. For infinite cells, the positions are returned
+    /// unchanged.
+    pub fn scaled_positions(&self) -> Vec<Vector3D> {
+        self.particles.position.iter().map(|position| self.cell.fractional(position)).collect()
+    }
+
+    /// Get an iterator over all the pairs of particles closer than `cutoff`,
+    /// using the minimum image convention. The iterator yields `(i, j,
+    /// distance)` tuples with `i < j`, and each pair is only yielded once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{System, Molecule, Particle, UnitCell, Vector3D};
+    /// let mut system = System::with_cell(UnitCell::cubic(10.0));
+    /// for &x in &[0.0, 1.5, 8.0] {
+    ///     let particle = Particle::with_position("Ar", Vector3D::new(x, 0.0, 0.0));
+    ///     system.add_molecule(Molecule::new(particle));
+    /// }
+    ///
+    /// let pairs = system.pairs_within(3.0).collect::<Vec<_>>();
+    /// assert_eq!(pairs, [(0, 1, 1.5), (0, 2, 2.0)]);
+    /// ```
+    pub fn pairs_within(&self, cutoff: f64) -> PairsWithin<'_> {
+        PairsWithin {
+            configuration: self,
+            cutoff: cutoff,
+            i: 0,
+            j: 1,
+        }
+    }
+
     /// Get the angle between the particles `i`, `j` and `k`
     pub fn angle(&self, i: usize, j: usize, k: usize) -> f64 {
         self.cell.angle(
@@ -454,6 +906,36 @@ impl Configuration {
     }
 }
 
+/// An iterator over all the pairs of particles within a given cutoff distance
+/// in a `Configuration`, created by `Configuration::pairs_within`.
+pub struct PairsWithin<'a> {
+    configuration: &'a Configuration,
+    cutoff: f64,
+    i: usize,
+    j: usize,
+}
+
+impl<'a> Iterator for PairsWithin<'a> {
+    type Item = (usize, usize, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = self.configuration.size();
+        while self.i + 1 < size {
+            while self.j < size {
+                let (i, j) = (self.i, self.j);
+                self.j += 1;
+                let distance = self.configuration.distance(i, j);
+                if distance < self.cutoff {
+                    return Some((i, j, distance));
+                }
+            }
+            self.i += 1;
+            self.j = self.i + 1;
+        }
+        return None;
+    }
+}
+
 /// An iterator over all the molecules in a `Configuration`
 pub struct MoleculeIter<'a> {
     bondings: ::std::slice::Iter<'a, Bonding>,
@@ -577,7 +1059,7 @@ impl<'a> DoubleEndedIterator for MoleculeIterMut<'a> {
 mod tests {
     use super::*;
     use crate::Vector3D;
-    use crate::{Angle, Bond, Dihedral, Particle, Molecule};
+    use crate::{Angle, Bond, Dihedral, Particle, Molecule, VirtualSite};
     use crate::BondPath;
 
     use lazy_static::lazy_static;
@@ -629,6 +1111,86 @@ mod tests {
         assert_eq!(configuration.size(), 0);
     }
 
+    #[test]
+    fn topology() {
+        let mut configuration = Configuration::new();
+        configuration.add_molecule(Molecule::new(particle("Ar")));
+
+        let mut water = Molecule::new(particle("O"));
+        water.add_particle_bonded_to(0, particle("H"));
+        water.add_particle_bonded_to(0, particle("H"));
+        configuration.add_molecule(water);
+
+        let mut bonds = configuration.bonds().collect::<Vec<_>>();
+        bonds.sort_unstable();
+        assert_eq!(bonds, [(1, 2), (1, 3)]);
+
+        let angles = configuration.angles().collect::<Vec<_>>();
+        assert_eq!(angles, [(2, 1, 3)]);
+
+        assert_eq!(configuration.dihedrals().count(), 0);
+
+        // Merging molecules creates new angles and dihedral angles
+        let _ = configuration.add_bond(0, 2);
+        assert_eq!(configuration.bonds().count(), 3);
+        assert_eq!(configuration.angles().count(), 2);
+        assert_eq!(configuration.dihedrals().count(), 1);
+    }
+
+    #[test]
+    fn pairs_within() {
+        // Simple cubic lattice with 3 particles in each direction
+        let mut configuration = Configuration::new();
+        configuration.cell = UnitCell::cubic(6.0);
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    let mut particle = particle("Ar");
+                    particle.position = Vector3D::new(i as f64, j as f64, k as f64) * 2.0;
+                    configuration.add_molecule(Molecule::new(particle));
+                }
+            }
+        }
+
+        assert_eq!(configuration.pairs_within(1.9).count(), 0);
+        // 6 first neighbors for each of the 27 particles
+        assert_eq!(configuration.pairs_within(2.1).count(), 27 * 6 / 2);
+        // 12 second neighbors at 2√2
+        assert_eq!(configuration.pairs_within(2.9).count(), 27 * 18 / 2);
+
+        for (i, j, distance) in configuration.pairs_within(2.9) {
+            assert!(i < j);
+            assert_eq!(distance, configuration.distance(i, j));
+            assert!(distance < 2.9);
+        }
+
+        // Without periodic boundary conditions
+        configuration.cell = UnitCell::infinite();
+        // 2 edges between 3 particles in 9 lines for the 3 directions
+        assert_eq!(configuration.pairs_within(2.1).count(), 3 * 9 * 2);
+    }
+
+    #[test]
+    fn scaled_positions() {
+        let mut configuration = Configuration::new();
+        for &position in &[Vector3D::new(1.0, 2.0, -3.0), Vector3D::new(12.0, 5.5, 0.2)] {
+            let mut particle = particle("Ar");
+            particle.position = position;
+            configuration.add_molecule(Molecule::new(particle));
+        }
+
+        let positions = configuration.particles().position.to_vec();
+        assert_eq!(configuration.scaled_positions(), positions);
+
+        configuration.cell = UnitCell::triclinic(5.0, 6.0, 3.6, 90.0, 53.0, 77.0);
+        let scaled = configuration.scaled_positions();
+        for (position, scaled) in positions.iter().zip(&scaled) {
+            assert_eq!(*scaled, configuration.cell.fractional(position));
+            let cartesian = configuration.cell.cartesian(scaled);
+            assert!((cartesian - position).norm() < 1e-12);
+        }
+    }
+
     mod iterators {
         use super::super::Configuration;
         use super::particle;
@@ -768,6 +1330,157 @@ mod tests {
         assert_eq!(configuration.bond_path(0, 5), BondPath::None);
     }
 
+    #[test]
+    fn precomputed_bond_path() {
+        // Shortest bond path between all pairs of particles, walking the
+        // bond graph
+        fn traversal(configuration: &Configuration, i: usize, j: usize) -> BondPath {
+            let mut distances = vec![usize::MAX; configuration.size()];
+            let mut queue = std::collections::VecDeque::new();
+            distances[i] = 0;
+            queue.push_back(i);
+            while let Some(current) = queue.pop_front() {
+                for (a, b) in configuration.bonds() {
+                    let next = if a == current {
+                        b
+                    } else if b == current {
+                        a
+                    } else {
+                        continue;
+                    };
+                    if distances[next] == usize::MAX {
+                        distances[next] = distances[current] + 1;
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            match distances[j] {
+                0 => BondPath::SameParticle,
+                1 => BondPath::OneBond,
+                2 => BondPath::TwoBonds,
+                3 => BondPath::ThreeBonds,
+                usize::MAX => BondPath::None,
+                _ => BondPath::Far,
+            }
+        }
+
+        let mut configuration = Configuration::new();
+        // 2,2,4-trimethylpentane, with hydrogens on the branching carbons
+        let mut molecule = Molecule::new(particle("C"));
+        molecule.add_particle_bonded_to(0, particle("C"));
+        molecule.add_particle_bonded_to(1, particle("C"));
+        molecule.add_particle_bonded_to(1, particle("C"));
+        molecule.add_particle_bonded_to(1, particle("C"));
+        molecule.add_particle_bonded_to(4, particle("C"));
+        molecule.add_particle_bonded_to(5, particle("C"));
+        molecule.add_particle_bonded_to(5, particle("C"));
+        molecule.add_particle_bonded_to(5, particle("H"));
+        molecule.add_particle_bonded_to(6, particle("H"));
+        configuration.add_molecule(Molecule::new(particle("Ar")));
+        configuration.add_molecule(molecule);
+        configuration.add_molecule(Molecule::new(particle("Ar")));
+
+        // Merging molecules moves particles around, and the precomputed data
+        // should be updated
+        let _ = configuration.add_bond(0, 11);
+
+        let size = configuration.size();
+        for i in 0..size {
+            for j in 0..size {
+                assert_eq!(configuration.bond_path(i, j), traversal(&configuration, i, j), "between {} and {}", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn exclusions() {
+        let mut configuration = Configuration::new();
+        configuration.add_molecule(Molecule::new(particle("C")));
+        configuration.add_molecule(Molecule::new(particle("H")));
+        configuration.add_molecule(Molecule::new(particle("O")));
+        configuration.add_molecule(Molecule::new(particle("H")));
+        configuration.add_molecule(Molecule::new(particle("H")));
+
+        configuration.add_exclusion(3, 1);
+        configuration.add_exclusion(2, 4);
+        assert_eq!(configuration.exclusions(), vec![(1, 3), (2, 4)]);
+        assert!(configuration.is_excluded(1, 3));
+        assert!(configuration.is_excluded(3, 1));
+        assert!(!configuration.is_excluded(1, 2));
+        assert_eq!(configuration.bond_path(3, 1), BondPath::Excluded);
+        assert_eq!(configuration.bond_path(1, 2), BondPath::None);
+
+        // Merging molecules moves the particle 3 to index 1
+        assert_eq!(configuration.add_bond(0, 3), vec![Permutation::new(3, 1), Permutation::new(1, 2), Permutation::new(2, 3)]);
+        assert_eq!(configuration.exclusions(), vec![(1, 2), (3, 4)]);
+        assert_eq!(configuration.particles().name[1], "H");
+        assert_eq!(configuration.particles().name[3], "O");
+
+        // Removing a molecule removes the corresponding exclusions, and
+        // shift the other ones
+        configuration.remove_molecule(1);
+        assert_eq!(configuration.exclusions(), vec![(2, 3)]);
+        assert_eq!(configuration.particles().name[2], "O");
+    }
+
+    #[test]
+    fn virtual_sites() {
+        let mut configuration = Configuration::new();
+        configuration.add_molecule(Molecule::new(particle("Zn")));
+        for _ in 0..2 {
+            let mut molecule = Molecule::new(particle("O"));
+            molecule.add_particle_bonded_to(0, particle("H"));
+            molecule.add_particle_bonded_to(0, particle("H"));
+            molecule.add_particle_bonded_to(0, particle("M"));
+            configuration.add_molecule(molecule);
+        }
+        configuration.particles_mut().position[2] = Vector3D::new(1.0, 0.0, 0.0);
+        configuration.particles_mut().position[3] = Vector3D::new(0.0, 1.0, 0.0);
+        configuration.particles_mut().velocity[2] = Vector3D::new(2.0, 0.0, 0.0);
+
+        configuration.add_virtual_site(VirtualSite::new(4, [1, 2, 3], [0.5, 0.25, 0.25]));
+        configuration.add_virtual_site(VirtualSite::new(8, [5, 6, 7], [0.5, 0.25, 0.25]));
+        assert_eq!(configuration.particles().mass[4], 0.0);
+        assert_eq!(configuration.particles().position[4], Vector3D::new(0.25, 0.25, 0.0));
+        assert_eq!(configuration.particles().velocity[4], Vector3D::new(0.5, 0.0, 0.0));
+
+        let mut forces = vec![Vector3D::zero(); configuration.size()];
+        forces[4] = Vector3D::new(4.0, 0.0, 0.0);
+        configuration.spread_virtual_forces(&mut forces);
+        assert_eq!(forces[1], Vector3D::new(2.0, 0.0, 0.0));
+        assert_eq!(forces[4], Vector3D::zero());
+
+        configuration.particles_mut().position[1] = Vector3D::new(0.0, 0.0, 4.0);
+        configuration.update_virtual_sites();
+        assert_eq!(configuration.particles().position[4], Vector3D::new(0.25, 0.25, 2.0));
+
+        // Removing molecules removes the corresponding sites, and shift the
+        // other ones
+        configuration.remove_molecule(1);
+        assert_eq!(configuration.virtual_sites(), &[VirtualSite::new(4, [1, 2, 3], [0.5, 0.25, 0.25])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the parents of a virtual site must be in the same molecule as the site")]
+    fn virtual_site_other_molecule() {
+        let mut configuration = Configuration::new();
+        let mut molecule = Molecule::new(particle("O"));
+        molecule.add_particle_bonded_to(0, particle("H"));
+        molecule.add_particle_bonded_to(0, particle("M"));
+        configuration.add_molecule(molecule);
+        configuration.add_molecule(Molecule::new(particle("H")));
+        configuration.add_virtual_site(VirtualSite::new(2, [0, 1, 3], [0.5, 0.25, 0.25]));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not exclude a particle from interacting with itself")]
+    fn self_exclusion() {
+        let mut configuration = Configuration::new();
+        configuration.add_molecule(Molecule::new(particle("C")));
+        configuration.add_exclusion(0, 0);
+    }
+
     #[test]
     fn add_bond_permutations() {
         let mut configuration = Configuration::new();
@@ -826,6 +1539,17 @@ mod tests {
 
         configuration.cell = UnitCell::infinite();
         assert_eq!(configuration.distance(0, 1), 9.0);
+
+        // Strongly skewed triclinic cell, the shortest image is used
+        configuration.cell = UnitCell::from_vectors(
+            Vector3D::new(10.0, 0.0, 0.0),
+            Vector3D::new(10.0, 10.0, 0.0),
+            Vector3D::new(0.0, 10.0, 10.0),
+        );
+        configuration.particles_mut().position[0] = Vector3D::new(4.0, -4.0, 4.0);
+        let rij = configuration.nearest_image(0, 1);
+        assert!(f64::abs(rij.norm() - f64::sqrt(48.0)) < 1e-12);
+        assert!(f64::abs(configuration.distance(0, 1) - f64::sqrt(48.0)) < 1e-12);
     }
 
     #[test]
@@ -867,4 +1591,38 @@ mod tests {
             configuration.molecule(4).hash()
         );
     }
+
+    #[test]
+    fn defragment() {
+        let mut configuration = Configuration::new();
+        let mut molecule = Molecule::new(particle("O"));
+        molecule.add_particle_bonded_to(0, particle("H"));
+        configuration.add_molecule(molecule);
+        configuration.add_molecule(Molecule::new(particle("Ar")));
+        configuration.add_molecule(Molecule::new(particle("H")));
+        configuration.add_exclusion(0, 3);
+
+        assert_eq!(configuration.validate(), Ok(()));
+        assert!(configuration.defragment().is_empty());
+
+        // Fragment the configuration: the last hydrogen belongs to the first
+        // molecule, but is stored after the argon
+        configuration.molecule_ids[3] = 0;
+        let error = configuration.validate().unwrap_err();
+        assert_eq!(error, TopologyError::MoleculeId { particle: 3, expected: 2, actual: 0 });
+        assert_eq!(error.to_string(), "particle 3 is in molecule 2 but is associated with molecule 0");
+
+        let permutations = configuration.defragment();
+        assert_eq!(permutations, [Permutation::new(2, 3), Permutation::new(3, 2)]);
+        assert_eq!(configuration.validate(), Ok(()));
+
+        assert_eq!(configuration.particles().name, ["O", "H", "H", "Ar"]);
+        assert_eq!(configuration.molecules().count(), 2);
+        assert_eq!(configuration.molecule(0).indexes(), 0..3);
+        assert!(configuration.molecule(0).bonds().contains(&Bond::new(0, 1)));
+        assert_eq!(configuration.molecule(1).indexes(), 3..4);
+        assert_eq!(configuration.molecule_id(2), 0);
+        assert_eq!(configuration.molecule_id(3), 1);
+        assert_eq!(configuration.exclusions(), [(0, 2)]);
+    }
 }
@@ -26,9 +26,13 @@ mod bonding;
 pub use self::bonding::Bonding;
 
 mod molecules;
-pub use self::molecules::{Molecule, MoleculeRef, MoleculeRefMut, MoleculeHash};
+pub use self::molecules::{Molecule, MoleculeBuilder, MoleculeRef, MoleculeRefMut, MoleculeHash};
+
+mod virtual_sites;
+pub use self::virtual_sites::VirtualSite;
 
 mod configuration;
 pub use self::configuration::Configuration;
 pub use self::configuration::Permutation;
-pub use self::configuration::{MoleculeIter, MoleculeIterMut};
+pub use self::configuration::TopologyError;
+pub use self::configuration::{MoleculeIter, MoleculeIterMut, PairsWithin};
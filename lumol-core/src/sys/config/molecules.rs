@@ -9,7 +9,7 @@ use soa_derive::soa_zip;
 
 use crate::{Particle, ParticleVec, ParticleSlice, ParticleSliceMut};
 use crate::{Bonding, UnitCell};
-use crate::Vector3D;
+use crate::{Matrix3, Vector3D};
 
 /// A molecule hash allow to identify a molecule from its atoms and bonds, and
 /// to know wether two molecules are the same without checking each atom and
@@ -128,6 +128,85 @@ impl Molecule {
     }
 }
 
+/// A builder to create a [`Molecule`] from a list of particles and bonds.
+///
+/// Bonds are checked against the particles added to the builder, and the
+/// angles and dihedrals of the molecule are inferred from the bonds when
+/// building the molecule.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::{MoleculeBuilder, Particle};
+/// let mut builder = MoleculeBuilder::new();
+/// let o = builder.add_particle(Particle::new("O"));
+/// let h1 = builder.add_particle(Particle::new("H"));
+/// let h2 = builder.add_particle(Particle::new("H"));
+/// builder.add_bond(o, h1);
+/// builder.add_bond(o, h2);
+///
+/// let water = builder.build();
+/// assert_eq!(water.size(), 3);
+/// assert_eq!(water.angles().len(), 1);
+/// ```
+///
+/// [`Molecule`]: struct.Molecule.html
+#[derive(Debug, Clone, Default)]
+pub struct MoleculeBuilder {
+    particles: Vec<Particle>,
+    bonds: Vec<(usize, usize)>,
+}
+
+impl MoleculeBuilder {
+    /// Create a new empty `MoleculeBuilder`
+    pub fn new() -> MoleculeBuilder {
+        MoleculeBuilder::default()
+    }
+
+    /// Add a `particle` to the molecule, and get the index of this particle
+    /// in the molecule.
+    pub fn add_particle(&mut self, particle: Particle) -> usize {
+        self.particles.push(particle);
+        return self.particles.len() - 1;
+    }
+
+    /// Add a bond between the particles at indexes `i` and `j` in the
+    /// molecule.
+    ///
+    /// # Panics
+    ///
+    /// If `i` or `j` do not refer to a particle already added to this
+    /// builder, or if `i` and `j` are the same particle.
+    pub fn add_bond(&mut self, i: usize, j: usize) {
+        let size = self.particles.len();
+        assert!(i < size, "particle {} is not in the molecule, which contains {} particles", i, size);
+        assert!(j < size, "particle {} is not in the molecule, which contains {} particles", j, size);
+        assert_ne!(i, j, "can not add a bond between a particle and itself");
+        self.bonds.push((i, j));
+    }
+
+    /// Build the molecule, inferring angles and dihedrals from the bonds.
+    ///
+    /// # Panics
+    ///
+    /// If no particles were added to this builder.
+    pub fn build(self) -> Molecule {
+        let mut particles = self.particles.into_iter();
+        let first = particles.next().expect("can not build a molecule without particles");
+        let mut molecule = Molecule::new(first);
+        for particle in particles {
+            molecule.particles.push(particle);
+            let i = molecule.particles.len() - 1;
+            molecule.bonding.merge_with(Bonding::new(i));
+        }
+
+        for (i, j) in self.bonds {
+            molecule.add_bond(i, j);
+        }
+        return molecule;
+    }
+}
+
 impl Deref for Molecule {
     type Target = Bonding;
 
@@ -214,6 +293,8 @@ impl<'a> MoleculeRefMut<'a> {
             charge: &mut self.particles.charge,
             position: &mut self.particles.position,
             velocity: &mut self.particles.velocity,
+            coupling: &mut self.particles.coupling,
+            orientation: &mut self.particles.orientation,
         }
     }
 
@@ -263,6 +344,25 @@ impl_on!(Molecule, MoleculeRef<'a>, MoleculeRefMut<'a>, => {
         com / total_mass
     }
 
+    /// Return the inertia tensor of a molecule, computed around its
+    /// center-of-mass.
+    ///
+    /// # Warning
+    ///
+    /// This function does not check for the particles' positions' nearest
+    /// images. To use this function properly, make sure that all particles of
+    /// the molecule are adjacent.
+    pub fn inertia(&self) -> Matrix3 {
+        let com = self.center_of_mass();
+        let mut inertia = Matrix3::zero();
+        for (&mass, position) in soa_zip!(&self.particles, [mass, position]) {
+            let delta = position - com;
+            inertia += mass * delta.norm2() * Matrix3::one();
+            inertia -= mass * delta.tensorial(&delta);
+        }
+        inertia
+    }
+
     /// Get a hash of this molecule. This is a hash of the particles names (in
     /// order), and the set of bonds in the molecule. This means that two
     /// molecules will have the same type if and only if they contains the same
@@ -281,6 +381,10 @@ impl_on!(Molecule, MoleculeRefMut<'a>, => {
     /// Move all particles of a molecule such that the molecules center-of-mass
     /// position resides inside the simulation cell.
     ///
+    /// With Lees-Edwards boundary conditions, the velocities of the particles
+    /// are also shifted by the relative velocity of the periodic images when
+    /// the molecule crosses the cell boundaries in the y direction.
+    ///
     /// # Note
     ///
     /// If the `CellShape` is `Infinite` there are no changes to the positions.
@@ -293,13 +397,20 @@ impl_on!(Molecule, MoleculeRefMut<'a>, => {
         for position in self.particles_mut().position.iter_mut() {
             *position += delta;
         }
+
+        let shear_velocity = cell.shear_rate() * delta[1];
+        if shear_velocity != 0.0 {
+            for velocity in self.particles_mut().velocity.iter_mut() {
+                velocity[0] += shear_velocity;
+            }
+        }
     }
 });
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ParticleKind;
+    use crate::{Angle, Bond, ParticleKind};
 
     use lazy_static::lazy_static;
 
@@ -319,6 +430,54 @@ mod tests {
         return particle;
     }
 
+    #[test]
+    fn builder() {
+        let mut builder = MoleculeBuilder::new();
+        let o = builder.add_particle(particle("O"));
+        let h1 = builder.add_particle(particle("H"));
+        let h2 = builder.add_particle(particle("H"));
+        builder.add_bond(o, h1);
+        builder.add_bond(o, h2);
+
+        let molecule = builder.build();
+        assert_eq!(molecule.size(), 3);
+        assert_eq!(molecule.particles().name[0], "O");
+        assert_eq!(molecule.particles().name[2], "H");
+        assert_eq!(molecule.bonds().len(), 2);
+        assert!(molecule.bonds().contains(&Bond::new(0, 1)));
+        assert!(molecule.bonds().contains(&Bond::new(0, 2)));
+
+        assert_eq!(molecule.angles().len(), 1);
+        assert!(molecule.angles().contains(&Angle::new(1, 0, 2)));
+        assert!(molecule.dihedrals().is_empty());
+    }
+
+    #[test]
+    fn builder_without_bonds() {
+        let mut builder = MoleculeBuilder::new();
+        let _ = builder.add_particle(particle("Ar"));
+        let _ = builder.add_particle(particle("Ar"));
+
+        let molecule = builder.build();
+        assert_eq!(molecule.size(), 2);
+        assert!(molecule.bonds().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "particle 3 is not in the molecule, which contains 2 particles")]
+    fn builder_bad_bond() {
+        let mut builder = MoleculeBuilder::new();
+        let _ = builder.add_particle(particle("O"));
+        let _ = builder.add_particle(particle("H"));
+        builder.add_bond(0, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not build a molecule without particles")]
+    fn builder_empty() {
+        let _ = MoleculeBuilder::new().build();
+    }
+
     #[test]
     fn center_of_mass() {
         let mut molecule = Molecule::new(particle("O"));
@@ -330,6 +489,28 @@ mod tests {
         assert_eq!(molecule.center_of_mass(), Vector3D::new(0.5, 0.0, 0.0));
     }
 
+    #[test]
+    fn inertia() {
+        let mut molecule = Molecule::new(particle("O"));
+        molecule.add_particle_bonded_to(0, particle("O"));
+
+        molecule.particles_mut().position[0] = Vector3D::new(1.0, 0.0, 0.0);
+        molecule.particles_mut().position[1] = Vector3D::new(-1.0, 0.0, 0.0);
+
+        let mass = molecule.particles().mass[0];
+        let inertia = molecule.inertia();
+        assert_eq!(inertia[0][0], 0.0);
+        assert_eq!(inertia[1][1], 2.0 * mass);
+        assert_eq!(inertia[2][2], 2.0 * mass);
+        for i in 0..3 {
+            for j in 0..3 {
+                if i != j {
+                    assert_eq!(inertia[i][j], 0.0);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_wrap_molecule() {
         let mut molecule = Molecule::new(particle("O"));
@@ -343,4 +524,21 @@ mod tests {
         assert_eq!(molecule.particles().position[1], Vector3D::new(5.0, 0.0, 0.0));
         assert_eq!(molecule.center_of_mass(), Vector3D::new(4.0, 0.0, 0.0))
     }
+
+    #[test]
+    fn wrap_lees_edwards() {
+        let mut molecule = Molecule::new(particle("O"));
+        molecule.particles_mut().position[0] = Vector3D::new(1.0, 6.0, 0.0);
+        molecule.particles_mut().velocity[0] = Vector3D::new(0.5, 0.0, 0.0);
+
+        let mut cell = UnitCell::cubic(5.0);
+        cell.set_shear_rate(0.2);
+        cell.set_shear_offset(2.0);
+        molecule.wrap(&cell);
+
+        // The image in the cell is displaced by the offset, and moves slower
+        // by shear_rate * b
+        assert_eq!(molecule.particles().position[0], Vector3D::new(4.0, 1.0, 0.0));
+        assert_eq!(molecule.particles().velocity[0], Vector3D::new(-0.5, 0.0, 0.0));
+    }
 }
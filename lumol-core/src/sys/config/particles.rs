@@ -41,19 +41,33 @@ pub struct Particle {
     pub position: Vector3D,
     /// Particle velocity, if needed
     pub velocity: Vector3D,
+    /// Coupling of the particle to the non-bonded interactions, between 0
+    /// (fully decoupled) and 1 (fully coupled). The pair and coulombic
+    /// interactions between particles `i` and `j` are scaled by the geometric
+    /// mean of their couplings, $\sqrt{\lambda_i \lambda_j}$. The long range
+    /// corrections of the pair potentials do not depend on the coupling.
+    pub coupling: f64,
+    /// Unit vector along the symmetry axis of the particle, used by
+    /// anisotropic potentials such as [`GayBerne`]. This is ignored by all the
+    /// other potentials.
+    ///
+    /// [`GayBerne`]: ../energy/struct.GayBerne.html
+    pub orientation: Vector3D,
 }
 
 impl Particle {
     /// Create a new `Particle` from a `name`, setting the mass to the atomic
     /// mass if the `name` can be found in the periodic table. The charge,
-    /// position, and velocity are set to 0.
+    /// position, and velocity are set to 0, the coupling to 1 and the
+    /// orientation to the z axis.
     pub fn new<S: Into<String>>(name: S) -> Particle {
         Particle::with_position(name, Vector3D::zero())
     }
 
     /// Create a new `Particle` from a `name` and a `position`, setting the
     /// mass to the atomic mass if the `name` can be found in the periodic
-    /// table. The charge and velocity are set to 0.
+    /// table. The charge and velocity are set to 0, the coupling to 1 and the
+    /// orientation to the z axis.
     pub fn with_position<S: Into<String>>(name: S, position: Vector3D) -> Particle {
         let name = name.into();
         let mass = get_atomic_mass(&name).unwrap_or(0.0);
@@ -64,6 +78,8 @@ impl Particle {
             kind: ParticleKind::invalid(),
             position: position,
             velocity: Vector3D::zero(),
+            coupling: 1.0,
+            orientation: Vector3D::new(0.0, 0.0, 1.0),
         }
     }
 }
@@ -89,6 +105,8 @@ mod tests {
         assert_eq!(particle.kind, ParticleKind::invalid());
         assert_eq!(particle.position, Vector3D::new(0.0, 0.0, 0.0));
         assert_eq!(particle.velocity, Vector3D::new(0.0, 0.0, 0.0));
+        assert_eq!(particle.coupling, 1.0);
+        assert_eq!(particle.orientation, Vector3D::new(0.0, 0.0, 1.0));
     }
 
     #[test]
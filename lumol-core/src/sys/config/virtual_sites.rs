@@ -0,0 +1,175 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use crate::{UnitCell, Vector3D};
+
+/// A `VirtualSite` is a massless interaction site, which position is a
+/// function of the positions of other particles. This is used for example in
+/// the TIP4P family of water models, where the negative charge sits on a
+/// massless site close to the oxygen.
+///
+/// The position of the site is a linear combination of the positions of three
+/// parent particles: $\vec r_s = \sum_k w_k \vec r_k$, where the weights $w_k$
+/// sum to 1. The forces acting on the site are redistributed to the parents
+/// with the same weights, which conserves the total force and torque.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::VirtualSite;
+/// // A TIP4P M-site (particle 3) on the bisector of the H-O-H angle of a
+/// // water molecule containing the particles 0 (oxygen), 1 and 2 (hydrogens)
+/// let gamma = 0.128;
+/// let site = VirtualSite::new(3, [0, 1, 2], [1.0 - gamma, 0.5 * gamma, 0.5 * gamma]);
+///
+/// assert_eq!(site.site(), 3);
+/// assert_eq!(site.parents(), [0, 1, 2]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualSite {
+    /// Index of the virtual site particle
+    site: usize,
+    /// Indexes of the parent particles
+    parents: [usize; 3],
+    /// Weights of the parents in the position of the site
+    weights: [f64; 3],
+}
+
+impl VirtualSite {
+    /// Create a new virtual site for the particle at index `site`, with a
+    /// position given by the linear combination of the positions of the
+    /// `parents` particles with the given `weights`.
+    ///
+    /// # Panics
+    ///
+    /// If the weights do not sum to 1, or if the site is one of its parents.
+    pub fn new(site: usize, parents: [usize; 3], weights: [f64; 3]) -> VirtualSite {
+        assert!(
+            f64::abs(weights.iter().sum::<f64>() - 1.0) < 1e-12,
+            "the weights of a virtual site must sum to 1"
+        );
+        assert!(!parents.contains(&site), "a virtual site can not be its own parent");
+        VirtualSite {
+            site: site,
+            parents: parents,
+            weights: weights,
+        }
+    }
+
+    /// Get the index of the virtual site particle
+    pub fn site(&self) -> usize {
+        self.site
+    }
+
+    /// Get the indexes of the parent particles
+    pub fn parents(&self) -> [usize; 3] {
+        self.parents
+    }
+
+    /// Get the weights of the parent particles
+    pub fn weights(&self) -> [f64; 3] {
+        self.weights
+    }
+
+    /// Get the position of the virtual site from the `positions` of all the
+    /// particles, using the nearest images of the parents in the `cell`.
+    pub fn position(&self, cell: &UnitCell, positions: &[Vector3D]) -> Vector3D {
+        let origin = positions[self.parents[0]];
+        let mut position = origin;
+        for (&parent, &weight) in self.parents.iter().zip(&self.weights).skip(1) {
+            let mut delta = positions[parent] - origin;
+            cell.vector_image(&mut delta);
+            position += weight * delta;
+        }
+        return position;
+    }
+
+    /// Get the velocity of the virtual site from the `velocities` of all the
+    /// particles.
+    pub fn velocity(&self, velocities: &[Vector3D]) -> Vector3D {
+        let mut velocity = Vector3D::zero();
+        for (&parent, &weight) in self.parents.iter().zip(&self.weights) {
+            velocity += weight * velocities[parent];
+        }
+        return velocity;
+    }
+
+    /// Move the force acting on the virtual site in `forces` to the parent
+    /// particles.
+    pub fn spread_force(&self, forces: &mut [Vector3D]) {
+        let force = forces[self.site];
+        forces[self.site] = Vector3D::zero();
+        for (&parent, &weight) in self.parents.iter().zip(&self.weights) {
+            forces[parent] += weight * force;
+        }
+    }
+
+    /// Update the particles indexes with the result of `update`, returning
+    /// `None` if the site or one of the parents was removed.
+    pub(crate) fn update_indexes<F>(&self, update: F) -> Option<VirtualSite> where F: Fn(usize) -> Option<usize> {
+        Some(VirtualSite {
+            site: update(self.site)?,
+            parents: [update(self.parents[0])?, update(self.parents[1])?, update(self.parents[2])?],
+            weights: self.weights,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let site = VirtualSite::new(3, [0, 1, 2], [0.5, 0.25, 0.25]);
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(2.0, 0.0, 0.0),
+            Vector3D::new(0.0, 4.0, 0.0),
+            Vector3D::zero(),
+        ];
+        let expected = Vector3D::new(0.5, 1.0, 0.0);
+        assert_eq!(site.position(&UnitCell::infinite(), &positions), expected);
+
+        // Using the nearest images of the parents
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(8.0, 0.0, 0.0),
+            Vector3D::new(0.0, 4.0, 0.0),
+            Vector3D::zero(),
+        ];
+        let expected = Vector3D::new(-0.5, 1.0, 0.0);
+        assert_eq!(site.position(&UnitCell::cubic(10.0), &positions), expected);
+
+        let velocities = [
+            Vector3D::new(1.0, 0.0, 0.0),
+            Vector3D::new(0.0, 2.0, 0.0),
+            Vector3D::new(0.0, 0.0, 4.0),
+            Vector3D::zero(),
+        ];
+        assert_eq!(site.velocity(&velocities), Vector3D::new(0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    fn spread_force() {
+        let site = VirtualSite::new(1, [0, 2, 3], [0.5, 0.25, 0.25]);
+        let mut forces = vec![Vector3D::new(1.0, 0.0, 0.0), Vector3D::new(4.0, 8.0, 0.0), Vector3D::zero(), Vector3D::zero()];
+        site.spread_force(&mut forces);
+        assert_eq!(forces[0], Vector3D::new(3.0, 4.0, 0.0));
+        assert_eq!(forces[1], Vector3D::zero());
+        assert_eq!(forces[2], Vector3D::new(1.0, 2.0, 0.0));
+        assert_eq!(forces[3], Vector3D::new(1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "the weights of a virtual site must sum to 1")]
+    fn bad_weights() {
+        let _ = VirtualSite::new(3, [0, 1, 2], [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a virtual site can not be its own parent")]
+    fn own_parent() {
+        let _ = VirtualSite::new(2, [0, 1, 2], [0.5, 0.25, 0.25]);
+    }
+}
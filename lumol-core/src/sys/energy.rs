@@ -9,9 +9,10 @@
 use std::f64::consts::PI;
 
 use rayon::prelude::*;
+use soa_derive::soa_zip;
 
 use crate::BondPath;
-use crate::System;
+use crate::{System, Vector3D};
 
 /// An helper struct to evaluate energy components of a system.
 pub struct EnergyEvaluator<'a> {
@@ -34,7 +35,7 @@ impl<'a> EnergyEvaluator<'a> {
             Some(potential) => {
                 let info = potential.restriction().information(path);
                 if !info.excluded {
-                    info.scaling * potential.energy(r)
+                    info.scaling * self.system.coupling(i, j) * potential.energy(r)
                 } else {
                     0.0
                 }
@@ -58,6 +59,36 @@ impl<'a> EnergyEvaluator<'a> {
         return energies.sum();
     }
 
+    /// Get the energy of all the pairs of particles interacting with a pair
+    /// potential, as a sparse list of `(i, j, energy)` entries with `i < j`.
+    ///
+    /// Pairs at a distance larger than the potential cutoff and pairs
+    /// excluded by the potential restriction are not part of the list. The
+    /// sum of all the entries energies is the same as `EnergyEvaluator::pairs`.
+    pub fn pair_energy_matrix(&self) -> Vec<(usize, usize, f64)> {
+        let mut entries = Vec::new();
+        let cutoff = match self.system.maximum_cutoff() {
+            Some(cutoff) => cutoff,
+            None => return entries,
+        };
+
+        for (i, j, r) in self.system.pairs_within(cutoff) {
+            if let Some(potential) = self.system.pair_potential(i, j) {
+                if r >= potential.cutoff() {
+                    continue;
+                }
+
+                let path = self.system.bond_path(i, j);
+                let info = potential.restriction().information(path);
+                if !info.excluded {
+                    let scaling = info.scaling * self.system.coupling(i, j);
+                    entries.push((i, j, scaling * potential.energy(r)));
+                }
+            }
+        }
+        return entries;
+    }
+
     /// Compute the energy due to long range corrections for the pairs
     #[inline]
     pub fn pairs_tail(&self) -> f64 {
@@ -78,6 +109,43 @@ impl<'a> EnergyEvaluator<'a> {
         return energy;
     }
 
+    /// Compute the interaction energy of a test particle named `name` at
+    /// `position` with all the particles in the system, without adding it to
+    /// the system. Only the pair interactions contribute to this energy,
+    /// including the change in tail corrections: the test particle is not
+    /// bonded to any other particle, and does not carry any charge.
+    pub fn insertion(&self, name: &str, position: &Vector3D) -> f64 {
+        let kind = match self.system.interactions().kind(name) {
+            Some(kind) => kind,
+            // There is no interaction involving this particle
+            None => return 0.0,
+        };
+
+        let mut energy = 0.0;
+        for (&other, other_position, &coupling) in soa_zip!(self.system.particles(), [kind, position, coupling]) {
+            if let Some(potential) = self.system.interactions().pair((kind, other)) {
+                let info = potential.restriction().information(BondPath::None);
+                if !info.excluded {
+                    let r = self.system.cell.distance(position, other_position);
+                    energy += info.scaling * f64::sqrt(coupling) * potential.energy(r);
+                }
+            }
+        }
+
+        if !self.system.cell.is_infinite() {
+            let two_pi_density = 2.0 * PI / self.system.volume();
+            for (other, n) in self.system.composition().all_particles() {
+                if let Some(potential) = self.system.interactions().pair((kind, other)) {
+                    energy += 2.0 * two_pi_density * (n as f64) * potential.tail_energy();
+                }
+            }
+            if let Some(potential) = self.system.interactions().pair((kind, kind)) {
+                energy += two_pi_density * potential.tail_energy();
+            }
+        }
+        return energy;
+    }
+
     /// Compute the energy associated with the bonded particles `i, j` at
     /// distance `r`
     #[inline]
@@ -89,12 +157,9 @@ impl<'a> EnergyEvaluator<'a> {
     /// Compute the energy of all the bonds in the system
     pub fn bonds(&self) -> f64 {
         let mut energy = 0.0;
-        for molecule in self.system.molecules() {
-            for bond in molecule.bonds() {
-                let (i, j) = (bond.i(), bond.j());
-                let r = self.system.nearest_image(i, j).norm();
-                energy += self.bond(r, i, j);
-            }
+        for (i, j) in self.system.bonds() {
+            let r = self.system.nearest_image(i, j).norm();
+            energy += self.bond(r, i, j);
         }
         return energy;
     }
@@ -109,12 +174,9 @@ impl<'a> EnergyEvaluator<'a> {
     /// Compute the energy of all the angles in the system
     pub fn angles(&self) -> f64 {
         let mut energy = 0.0;
-        for molecule in self.system.molecules() {
-            for angle in molecule.angles() {
-                let (i, j, k) = (angle.i(), angle.j(), angle.k());
-                let theta = self.system.angle(i, j, k);
-                energy += self.angle(theta, i, j, k);
-            }
+        for (i, j, k) in self.system.angles() {
+            let theta = self.system.angle(i, j, k);
+            energy += self.angle(theta, i, j, k);
         }
         return energy;
     }
@@ -130,12 +192,9 @@ impl<'a> EnergyEvaluator<'a> {
     /// Compute the energy of all the dihedral angles in the system
     pub fn dihedrals(&self) -> f64 {
         let mut energy = 0.0;
-        for molecule in self.system.molecules() {
-            for dihedral in molecule.dihedrals() {
-                let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
-                let phi = self.system.dihedral(i, j, k, m);
-                energy += self.dihedral(phi, i, j, k, m);
-            }
+        for (i, j, k, m) in self.system.dihedrals() {
+            let phi = self.system.dihedral(i, j, k, m);
+            energy += self.dihedral(phi, i, j, k, m);
         }
         return energy;
     }
@@ -161,12 +220,12 @@ impl<'a> EnergyEvaluator<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Harmonic, LennardJones, NullPotential, PairInteraction};
-    use crate::{System, UnitCell};
+    use crate::{Harmonic, LennardJones, NullPotential, PairInteraction, PairRestriction, Potential};
+    use crate::{Molecule, Particle, System, UnitCell, Vector3D, Wolf};
     use crate::utils::system_from_xyz;
     use crate::units;
 
-    use approx::assert_ulps_eq;
+    use approx::{assert_relative_eq, assert_ulps_eq};
 
     fn testing_system() -> System {
         let mut system = system_from_xyz(
@@ -191,7 +250,7 @@ mod tests {
         );
         pair.enable_tail_corrections();
 
-        system.set_pair_potential(("F", "F"), pair);
+        system.set_pair_potential(("F", "F"), pair).unwrap();
 
         system.set_bond_potential(
             ("F", "F"),
@@ -218,7 +277,7 @@ mod tests {
         );
 
         // unused interaction to check that we do handle this right
-        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0));
+        system.set_pair_potential(("H", "O"), PairInteraction::new(Box::new(NullPotential), 0.0)).unwrap();
 
         return system;
     }
@@ -232,6 +291,189 @@ mod tests {
         assert_ulps_eq!(evaluator.pairs_tail(), -0.0000028110338032153973);
     }
 
+    #[test]
+    fn pair_energy_matrix() {
+        let system = testing_system();
+        let evaluator = EnergyEvaluator::new(&system);
+        let entries = evaluator.pair_energy_matrix();
+        let pairs = entries.iter().map(|&(i, j, _)| (i, j)).collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]);
+
+        let total = entries.iter().map(|&(_, _, energy)| energy).sum::<f64>();
+        assert_relative_eq!(total, evaluator.pairs(), max_relative = 1e-12);
+
+        // Pairs outside of the cutoff are not included
+        let mut system = testing_system();
+        system.particles_mut().position[3] = Vector3D::new(6.0, 5.0, 0.0);
+        let evaluator = EnergyEvaluator::new(&system);
+        let entries = evaluator.pair_energy_matrix();
+        let pairs = entries.iter().map(|&(i, j, _)| (i, j)).collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 1), (0, 2), (1, 2)]);
+
+        let total = entries.iter().map(|&(_, _, energy)| energy).sum::<f64>();
+        assert_relative_eq!(total, evaluator.pairs(), max_relative = 1e-12);
+
+        // Pairs excluded by the restriction are not included
+        let mut system = testing_system();
+        let mut pair = PairInteraction::new(Box::new(Harmonic { k: 2.0, x0: 1.0 }), 5.0);
+        pair.set_restriction(PairRestriction::Exclude12);
+        system.set_pair_potential(("F", "F"), pair).unwrap();
+        let evaluator = EnergyEvaluator::new(&system);
+        let entries = evaluator.pair_energy_matrix();
+        let pairs = entries.iter().map(|&(i, j, _)| (i, j)).collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 2), (0, 3), (1, 3)]);
+
+        let total = entries.iter().map(|&(_, _, energy)| energy).sum::<f64>();
+        assert_relative_eq!(total, evaluator.pairs(), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn insertion() {
+        let system = testing_system();
+        let position = Vector3D::new(1.5, 0.7, 0.3);
+        let energy = system.energy_evaluator().insertion("F", &position);
+        assert!(energy != 0.0);
+
+        let mut inserted = system.clone();
+        inserted.add_molecule(Molecule::new(Particle::with_position("F", position)));
+        let expected = inserted.potential_energy() - system.potential_energy();
+        assert_relative_eq!(energy, expected, max_relative = 1e-10);
+
+        // No interaction for this particle
+        assert_eq!(system.energy_evaluator().insertion("Ar", &position), 0.0);
+    }
+
+    #[test]
+    fn pairs_with_different_cutoffs() {
+        let mut system = system_from_xyz(
+            "3
+            cell: 20.0
+            Ar 0.0 0.0 0.0
+            Kr 4.0 0.0 0.0
+            Ar 7.0 0.0 0.0
+            ",
+        );
+        let harmonic = Harmonic { k: 2.0, x0: 1.0 };
+        system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(harmonic), 8.0)).unwrap();
+        system.set_pair_potential(("Ar", "Kr"), PairInteraction::new(Box::new(harmonic), 3.5)).unwrap();
+        assert_eq!(system.maximum_cutoff(), Some(8.0));
+
+        // The Ar-Ar pair at 7 A is inside its cutoff, the Ar-Kr pair at 4 A is
+        // outside of the Ar-Kr cutoff, and the Ar-Kr pair at 3 A is inside.
+        let evaluator = EnergyEvaluator::new(&system);
+        let expected = harmonic.energy(7.0) + harmonic.energy(3.0);
+        assert_ulps_eq!(evaluator.pairs(), expected);
+
+        // Same thing for the forces
+        let forces = system.forces();
+        assert_ulps_eq!(forces[0][0], -harmonic.force(7.0));
+        assert_ulps_eq!(forces[1][0], -harmonic.force(3.0));
+        assert_ulps_eq!(forces[2][0], harmonic.force(3.0) + harmonic.force(7.0));
+    }
+
+    #[test]
+    fn explicit_exclusions() {
+        let harmonic = Harmonic { k: 2.0, x0: 1.0 };
+        // Two equivalent pairs of charged particles, far from one another
+        let create_system = || {
+            let mut system = system_from_xyz(
+                "4
+                cell: 30.0
+                Ar 0.0 0.0 0.0
+                Ar 3.0 0.0 0.0
+                Ar 15.0 0.0 0.0
+                Ar 18.0 0.0 0.0
+                ",
+            );
+            system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(harmonic), 8.0)).unwrap();
+            system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap();
+            system.particles_mut().charge.copy_from_slice(&[1.0, -1.0, 1.0, -1.0]);
+            system
+        };
+
+        let system = create_system();
+        let evaluator = EnergyEvaluator::new(&system);
+        assert_ulps_eq!(evaluator.pairs(), 2.0 * harmonic.energy(3.0));
+        let coulomb = evaluator.coulomb();
+
+        // The excluded pair does not contribute to the energy, while the
+        // equivalent 2-3 pair still does
+        let mut system = create_system();
+        system.add_exclusion(1, 0);
+        assert!(system.is_excluded(0, 1));
+        assert!(!system.is_excluded(2, 3));
+
+        let evaluator = EnergyEvaluator::new(&system);
+        assert_ulps_eq!(evaluator.pairs(), harmonic.energy(3.0));
+        let excluded_coulomb = evaluator.coulomb();
+        assert!(excluded_coulomb > coulomb);
+
+        let forces = system.forces();
+        assert_eq!(forces[0], Vector3D::zero());
+        assert_eq!(forces[1], Vector3D::zero());
+        assert_ne!(forces[2], Vector3D::zero());
+
+        // Excluding the other pair gives the same energy
+        let mut other = create_system();
+        other.add_exclusion(2, 3);
+        let evaluator = EnergyEvaluator::new(&other);
+        assert_ulps_eq!(evaluator.pairs(), harmonic.energy(3.0));
+        assert_ulps_eq!(evaluator.coulomb(), excluded_coulomb);
+    }
+
+    #[test]
+    fn coupling() {
+        let harmonic = Harmonic { k: 2.0, x0: 1.0 };
+        let create_system = |xyz| {
+            let mut system = system_from_xyz(xyz);
+            system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(harmonic), 8.0)).unwrap();
+            system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap();
+            for (i, charge) in system.particles_mut().charge.iter_mut().enumerate() {
+                *charge = if i % 2 == 0 { 1.0 } else { -1.0 };
+            }
+            system
+        };
+
+        let reference = create_system(
+            "2
+            cell: 30.0
+            Ar 0.0 0.0 0.0
+            Ar 3.0 0.0 0.0
+            ",
+        );
+        let mut system = create_system(
+            "3
+            cell: 30.0
+            Ar 0.0 0.0 0.0
+            Ar 3.0 0.0 0.0
+            Ar 5.0 0.0 0.0
+            ",
+        );
+
+        // A fully decoupled particle does not contribute to the non-bonded
+        // energy and forces
+        system.particles_mut().coupling[2] = 0.0;
+        let evaluator = EnergyEvaluator::new(&system);
+        let reference_evaluator = EnergyEvaluator::new(&reference);
+        assert_ulps_eq!(evaluator.pairs(), reference_evaluator.pairs());
+        assert_ulps_eq!(evaluator.coulomb(), reference_evaluator.coulomb());
+
+        let forces = system.forces();
+        let reference_forces = reference.forces();
+        assert_ulps_eq!(forces[0], reference_forces[0]);
+        assert_ulps_eq!(forces[1], reference_forces[1]);
+        assert_eq!(forces[2], Vector3D::zero());
+
+        // Partially coupled particles use the geometric mean of the couplings
+        system.particles_mut().coupling[1] = 0.25;
+        system.particles_mut().coupling[2] = 1.0;
+        assert_eq!(system.coupling(0, 1), 0.5);
+        assert_eq!(system.coupling(0, 2), 1.0);
+        let evaluator = EnergyEvaluator::new(&system);
+        let expected = 0.5 * harmonic.energy(3.0) + 0.5 * harmonic.energy(2.0) + harmonic.energy(5.0);
+        assert_ulps_eq!(evaluator.pairs(), expected);
+    }
+
     #[test]
     fn pairs_tail_infinite_cell() {
         let mut system = testing_system();
@@ -90,6 +90,11 @@ impl Interactions {
         }
     }
 
+    /// Get the kind associated with `name`, if any
+    pub(crate) fn kind(&self, name: &str) -> Option<ParticleKind> {
+        self.kinds.get(name).copied()
+    }
+
     /// Get the existing kind associated with `name` or create a new one
     pub(crate) fn get_kind(&mut self, name: &str) -> ParticleKind {
         if let Some(&kind) = self.kinds.get(name) {
@@ -134,6 +139,13 @@ impl Interactions {
             warn!("replaced dihedral angle potential for ({}, {}, {}, {})", i, j, k, m);
         }
     }
+
+    /// Set the coupling parameter `lambda` for all the pair interactions
+    pub fn set_lambda(&mut self, lambda: f64) {
+        for potential in self.pairs.values_mut() {
+            potential.set_lambda(lambda);
+        }
+    }
 }
 
 
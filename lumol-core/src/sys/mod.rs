@@ -9,6 +9,8 @@ pub use self::config::*;
 mod system;
 pub use self::system::System;
 pub use self::system::DegreesOfFreedom;
+pub use self::system::MissingInteractions;
+pub use self::system::PotentialError;
 
 mod interactions;
 pub use self::interactions::Interactions;
@@ -3,8 +3,12 @@
 
 use std::ops::{Deref, DerefMut};
 use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
 
 use soa_derive::soa_zip;
+use log::warn;
 use log_once::warn_once;
 
 use crate::{Matrix3, Vector3D};
@@ -12,6 +16,7 @@ use crate::{AnglePotential, BondPotential, DihedralPotential, PairInteraction};
 use crate::{CoulombicPotential, GlobalPotential};
 use crate::{Composition, EnergyEvaluator, Interactions};
 use crate::{Configuration, Molecule, UnitCell};
+use crate::sys::get_atomic_mass;
 
 /// The number of degrees of freedom simulated in a given system
 #[derive(Clone, PartialEq, Debug)]
@@ -45,11 +50,23 @@ pub struct System {
     interactions: Interactions,
     /// Externally managed temperature for the system
     external_temperature: Option<f64>,
+    /// Should `check` emit warnings for the missing interactions
+    warn_missing_interactions: bool,
+    /// Counter incremented every time the interactions are changed
+    interactions_generation: u64,
     /// Number of degrees of freedom simulated in the system. This default to
     /// `DegreesOfFreedom::Particles`, and is set in the simulation setup.
     pub simulated_degrees_of_freedom: DegreesOfFreedom,
     /// The current simulation step
     pub step: u64,
+    /// The current simulation time. This is updated during simulations using
+    /// a propagator with a timestep, such as molecular dynamics.
+    pub time: f64,
+    /// Virial of the constraint forces acting on the particles, for example
+    /// the forces keeping some molecules rigid in molecular dynamics. This
+    /// is set by the propagator after each step, and included in the
+    /// [`virial`](#method.virial) of the system.
+    pub constraints_virial: Matrix3,
 }
 
 impl System {
@@ -71,7 +88,11 @@ impl System {
             configuration: configuration,
             interactions: Interactions::new(),
             step: 0,
+            time: 0.0,
+            constraints_virial: Matrix3::zero(),
             external_temperature: None,
+            warn_missing_interactions: true,
+            interactions_generation: 0,
             simulated_degrees_of_freedom: DegreesOfFreedom::Particles,
         }
     }
@@ -96,6 +117,87 @@ impl System {
         return composition;
     }
 
+    /// Get the number of particles named `name` in the system, or 0 if there
+    /// is no such particle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{System, Molecule, Particle};
+    /// let mut system = System::new();
+    /// system.add_molecule(Molecule::new(Particle::new("Na")));
+    /// system.add_molecule(Molecule::new(Particle::new("Cl")));
+    /// system.add_molecule(Molecule::new(Particle::new("Na")));
+    ///
+    /// assert_eq!(system.count_by_name("Na"), 2);
+    /// assert_eq!(system.count_by_name("Cl"), 1);
+    /// assert_eq!(system.count_by_name("K"), 0);
+    /// ```
+    pub fn count_by_name(&self, name: &str) -> usize {
+        match self.interactions.kind(name) {
+            Some(kind) => self.composition().particles(kind),
+            None => 0,
+        }
+    }
+
+    /// Get the number of molecules in the system, indexed by their chemical
+    /// formula. The formula is written in the Hill order: carbon first,
+    /// hydrogen second, and then all the other particles names in
+    /// alphabetical order, e.g. `"CH4"` or `"H2O"`. If there is no carbon in
+    /// the molecule, all the names are in alphabetical order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{System, Molecule, Particle};
+    /// let mut system = System::new();
+    /// for _ in 0..3 {
+    ///     let mut water = Molecule::new(Particle::new("O"));
+    ///     water.add_particle_bonded_to(0, Particle::new("H"));
+    ///     water.add_particle_bonded_to(0, Particle::new("H"));
+    ///     system.add_molecule(water);
+    /// }
+    /// system.add_molecule(Molecule::new(Particle::new("Na")));
+    ///
+    /// let counts = system.molecule_count_by_formula();
+    /// assert_eq!(counts["H2O"], 3);
+    /// assert_eq!(counts["Na"], 1);
+    /// ```
+    pub fn molecule_count_by_formula(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for molecule in self.molecules() {
+            let formula = chemical_formula(molecule.particles().name);
+            *counts.entry(formula).or_insert(0) += 1;
+        }
+        return counts;
+    }
+
+    /// Get all the pairs of particles closer than `min_distance`, as `(i, j,
+    /// distance)` tuples with `i < j`. Such overlapping particles produce
+    /// huge forces, and usually indicate a corrupted configuration. All the
+    /// pairs are checked, including bonded ones, but virtual sites are
+    /// ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{System, Molecule, Particle, UnitCell, Vector3D};
+    /// let mut system = System::with_cell(UnitCell::cubic(10.0));
+    /// for &x in &[0.0, 0.1, 5.0] {
+    ///     let particle = Particle::with_position("Ar", Vector3D::new(x, 0.0, 0.0));
+    ///     system.add_molecule(Molecule::new(particle));
+    /// }
+    ///
+    /// assert_eq!(system.check_overlaps(0.5), [(0, 1, 0.1)]);
+    /// assert!(system.check_overlaps(0.05).is_empty());
+    /// ```
+    pub fn check_overlaps(&self, min_distance: f64) -> Vec<(usize, usize, f64)> {
+        let is_virtual = |i| self.virtual_sites().iter().any(|site| site.site() == i);
+        return self.pairs_within(min_distance)
+                   .filter(|&(i, j, _)| !is_virtual(i) && !is_virtual(j))
+                   .collect();
+    }
+
     /// Use an external temperature for all the system properties. Calling this
     /// with `Some(temperature)` will replace all the computation of the
     /// temperature from the velocities with the given values. Calling it with
@@ -108,6 +210,100 @@ impl System {
         }
         self.external_temperature = temperature;
     }
+
+    /// Repartition the masses between hydrogen atoms and the heavy atoms
+    /// they are bonded to, to allow using larger timesteps in molecular
+    /// dynamics. The mass of each hydrogen atom is increased up to `factor`
+    /// times the atomic mass of hydrogen, and the same amount of mass is
+    /// removed from the first heavy atom bonded to it. The total mass of each
+    /// molecule is conserved, and the molecules are translated to keep their
+    /// centers of mass at the same position.
+    ///
+    /// Hydrogen atoms are particles named `H`, or with a name starting with
+    /// `H` which is not in the periodic table (such as `HW` or `H1`).
+    /// Hydrogen atoms not bonded to any heavy atom are left unchanged.
+    ///
+    /// # Panics
+    ///
+    /// If `factor` is smaller than 1, or if the mass of an heavy atom would
+    /// become negative.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::{System, MoleculeBuilder, Particle};
+    /// let mut builder = MoleculeBuilder::new();
+    /// let o = builder.add_particle(Particle::new("O"));
+    /// let h1 = builder.add_particle(Particle::new("H"));
+    /// let h2 = builder.add_particle(Particle::new("H"));
+    /// builder.add_bond(o, h1);
+    /// builder.add_bond(o, h2);
+    ///
+    /// let mut system = System::new();
+    /// system.add_molecule(builder.build());
+    /// system.repartition_hydrogen_mass(3.0);
+    ///
+    /// assert_eq!(system.particles().mass[1], 3.0 * 1.008);
+    /// ```
+    pub fn repartition_hydrogen_mass(&mut self, factor: f64) {
+        assert!(factor >= 1.0, "hydrogen mass repartitioning factor must be bigger than 1");
+        let hydrogen_mass = get_atomic_mass("H").expect("missing hydrogen in the periodic table");
+        let target = factor * hydrogen_mass;
+
+        let is_hydrogen = self.particles().name.iter().map(|name| {
+            name == "H" || (name.starts_with('H') && get_atomic_mass(name).is_none())
+        }).collect::<Vec<_>>();
+
+        // First heavy atom bonded to each hydrogen atom
+        let mut heavy = vec![None; self.size()];
+        for (i, j) in self.bonds() {
+            if is_hydrogen[i] && !is_hydrogen[j] && heavy[i].is_none() {
+                heavy[i] = Some(j);
+            }
+            if is_hydrogen[j] && !is_hydrogen[i] && heavy[j].is_none() {
+                heavy[j] = Some(i);
+            }
+        }
+
+        // Compute all the new masses before modifying the system, so that
+        // the system is left unchanged if any of the masses is invalid
+        let mut masses = self.particles().mass.to_vec();
+        let mut displacements = vec![Vector3D::zero(); self.molecules().count()];
+        for i in (0..self.size()).filter(|&i| is_hydrogen[i]) {
+            let j = match heavy[i] {
+                Some(j) => j,
+                None => {
+                    warn!("hydrogen atom {} is not bonded to an heavy atom, its mass will not be changed", i);
+                    continue;
+                }
+            };
+
+            let delta = target - masses[i];
+            if delta <= 0.0 {
+                continue;
+            }
+            assert!(
+                masses[j] > delta,
+                "the mass of particle {} would become negative after hydrogen mass repartitioning", j
+            );
+            masses[j] -= delta;
+            masses[i] = target;
+            // moving `delta` from `j` to `i` moves the center of mass of the
+            // molecule by `delta * (r_i - r_j) / M`
+            displacements[self.molecule_id(i)] += delta * self.nearest_image(i, j);
+        }
+
+        self.particles_mut().mass.copy_from_slice(&masses);
+        for (mut molecule, displacement) in self.molecules_mut().zip(displacements) {
+            if displacement == Vector3D::zero() {
+                continue;
+            }
+            let total_mass = molecule.particles().mass.iter().sum::<f64>();
+            for position in molecule.particles_mut().position {
+                *position -= displacement / total_mass;
+            }
+        }
+    }
 }
 
 /// Functions related to interactions
@@ -117,21 +313,64 @@ impl System {
         EnergyEvaluator::new(self)
     }
 
-    /// Set the pair interaction `potential` for atoms with types `i` and `j`
-    pub fn set_pair_potential(&mut self, (i, j): (&str, &str), potential: PairInteraction) {
-        if self.cell.lengths().iter().any(|&d| 0.5 * d < potential.cutoff()) {
-            panic!(
-                "Can not add a potential with a cutoff bigger than half of the \
-                smallest cell length. Try increasing the cell size or decreasing \
-                the cutoff."
+    /// Set the pair interaction `potential` for atoms with types `i` and `j`.
+    ///
+    /// The cutoff of the potential must be smaller than half of the smallest
+    /// cell length, unless the cell is infinite. If this is not the case, the
+    /// potential is not added and an error is returned.
+    pub fn set_pair_potential(
+        &mut self,
+        (i, j): (&str, &str),
+        potential: PairInteraction,
+    ) -> Result<(), PotentialError> {
+        self.check_cutoff(potential.cutoff())?;
+        self.interactions.set_pair((i, j), potential);
+        self.interactions_generation += 1;
+        Ok(())
+    }
+
+    /// Set the pair interaction `potential` for atoms with types `i` and `j`,
+    /// without checking the cutoff against the cell size.
+    ///
+    /// Only the nearest image of each particle is used when computing the
+    /// interactions, so if the cutoff is bigger than half of the smallest
+    /// cell length, interactions with the other periodic images inside the
+    /// cutoff sphere are ignored, and a warning is emitted. This can be
+    /// useful for small clusters in big cells, but should be used with care.
+    pub fn set_pair_potential_unchecked(&mut self, (i, j): (&str, &str), potential: PairInteraction) {
+        if self.check_cutoff(potential.cutoff()).is_err() {
+            warn!(
+                "the cutoff of the pair potential for ({}, {}) is bigger than \
+                half of the smallest cell length, only the nearest image of \
+                each particle will be used to compute this interaction",
+                i, j
             );
         }
-        self.interactions.set_pair((i, j), potential)
+        self.interactions.set_pair((i, j), potential);
+        self.interactions_generation += 1;
+    }
+
+    /// Check that the `cutoff` of a potential is compatible with the minimum
+    /// image convention in the current cell
+    fn check_cutoff(&self, cutoff: f64) -> Result<(), PotentialError> {
+        if self.cell.is_infinite() {
+            return Ok(());
+        }
+
+        let smallest = self.cell.lengths().iter().cloned().fold(f64::INFINITY, f64::min);
+        if 0.5 * smallest < cutoff {
+            return Err(PotentialError::CutoffTooBig {
+                cutoff: cutoff,
+                half_length: 0.5 * smallest,
+            });
+        }
+        Ok(())
     }
 
     /// Set the bond interaction `potential` for atoms with types `i` and `j`
     pub fn set_bond_potential(&mut self, (i, j): (&str, &str), potential: Box<dyn BondPotential>) {
-        self.interactions.set_bond((i, j), potential)
+        self.interactions.set_bond((i, j), potential);
+        self.interactions_generation += 1;
     }
 
     /// Set the angle interaction `potential` for atoms with types `i`, `j`, and `k`
@@ -140,7 +379,8 @@ impl System {
         (i, j, k): (&str, &str, &str),
         potential: Box<dyn AnglePotential>,
     ) {
-        self.interactions.set_angle((i, j, k), potential)
+        self.interactions.set_angle((i, j, k), potential);
+        self.interactions_generation += 1;
     }
 
     /// Set the dihedral angle interaction `potential` for atoms with types
@@ -150,26 +390,77 @@ impl System {
         (i, j, k, m): (&str, &str, &str, &str),
         potential: Box<dyn DihedralPotential>,
     ) {
-        self.interactions.set_dihedral((i, j, k, m), potential)
-    }
-
-    /// Set the coulombic interaction for all pairs to `potential`
-    pub fn set_coulomb_potential(&mut self, potential: Box<dyn CoulombicPotential>) {
-        if let Some(cutoff) = potential.cutoff() {
-            if self.cell.lengths().iter().any(|&d| 0.5 * d < cutoff) {
-                panic!(
-                    "Can not add a potential with a cutoff bigger than half of the \
-                    smallest cell length. Try increasing the cell size or decreasing \
-                    the cutoff."
-                );
+        self.interactions.set_dihedral((i, j, k, m), potential);
+        self.interactions_generation += 1;
+    }
+
+    /// Set the coulombic interaction for all pairs to `potential`.
+    ///
+    /// The cutoff of the potential must be smaller than half of the smallest
+    /// cell length. If the cell is infinite, the cutoff is not checked but the
+    /// potential must not require periodic boundary conditions (like Ewald
+    /// summation does). If any of these conditions is not met, the potential
+    /// is not added and an error is returned.
+    ///
+    /// A system uses a single coulombic potential, and calling this function
+    /// again replaces the previous potential. Separate contributions to the
+    /// electrostatic interactions (for example short-range and long-range
+    /// terms) can be summed with [`CombinedCoulomb`].
+    ///
+    /// [`CombinedCoulomb`]: ../energy/struct.CombinedCoulomb.html
+    pub fn set_coulomb_potential(&mut self, potential: Box<dyn CoulombicPotential>) -> Result<(), PotentialError> {
+        if self.cell.is_infinite() {
+            if potential.requires_periodicity() {
+                return Err(PotentialError::RequiresPeriodicity);
             }
+        } else if let Some(cutoff) = potential.cutoff() {
+            self.check_cutoff(cutoff)?;
         }
         self.interactions.coulomb = Some(potential);
+        self.interactions_generation += 1;
+        Ok(())
+    }
+
+    /// Set the coupling parameter `lambda` of all the pair potentials in
+    /// this system, for free energy computations. This only affects
+    /// potentials depending on $\lambda$, such as [`SoftCoreLennardJones`].
+    ///
+    /// # Panics
+    ///
+    /// If `lambda` is not between 0 and 1.
+    ///
+    /// [`SoftCoreLennardJones`]: struct.SoftCoreLennardJones.html
+    pub fn set_lambda(&mut self, lambda: f64) {
+        assert!((0.0..=1.0).contains(&lambda), "lambda must be between 0 and 1");
+        self.interactions.set_lambda(lambda);
+        self.interactions_generation += 1;
+    }
+
+    /// Get the generation of the interactions in this system. This number
+    /// changes every time the interactions are modified, for example when
+    /// adding a potential or changing the coupling parameter with
+    /// [`set_lambda`](#method.set_lambda). It can be used to invalidate
+    /// energy caches.
+    pub fn interactions_generation(&self) -> u64 {
+        self.interactions_generation
     }
 
     /// Add the `potential` global interaction
     pub fn add_global_potential(&mut self, potential: Box<dyn GlobalPotential>) {
         self.interactions.globals.push(potential);
+        self.interactions_generation += 1;
+    }
+
+    /// Remove and return the global interaction at `index` in the
+    /// [`global_potentials`](#method.global_potentials) list.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn remove_global_potential(&mut self, index: usize) -> Box<dyn GlobalPotential> {
+        assert!(index < self.interactions.globals.len(), "global potential index out of bounds");
+        self.interactions_generation += 1;
+        self.interactions.globals.remove(index)
     }
 
     /// Get the pair potential acting between the particles at indexes `i` and `j`.
@@ -234,20 +525,27 @@ impl System {
 }
 
 use crate::compute::{KineticEnergy, PotentialEnergy, TotalEnergy};
+use crate::compute::KineticTensor;
+use crate::compute::LambdaDerivative;
+use crate::compute::{EnergyComponents, PotentialEnergyComponents};
+use crate::compute::{PartitionedTemperature, TemperatureComponents};
 use crate::compute::{Pressure, Stress, Virial};
+use crate::compute::{PartitionedVirial, VirialComponents};
 use crate::compute::{PressureAtTemperature, StressAtTemperature};
+use crate::compute::{NumericalForces, NumericalPressure};
 use crate::compute::Compute;
-use crate::compute::Forces;
+use crate::compute::{Forces, Torques};
 use crate::compute::Temperature;
 use crate::compute::Volume;
 
 /// Functions to get physical properties of a system.
 impl System {
-    /// Get the number of degrees of freedom in the system
+    /// Get the number of degrees of freedom in the system. Virtual sites do
+    /// not contribute to the degrees of freedom.
     pub fn degrees_of_freedom(&self) -> usize {
         match self.simulated_degrees_of_freedom {
-            DegreesOfFreedom::Particles => 3 * self.size(),
-            DegreesOfFreedom::Frozen(frozen) => 3 * self.size() - frozen,
+            DegreesOfFreedom::Particles => 3 * (self.size() - self.virtual_sites().len()),
+            DegreesOfFreedom::Frozen(frozen) => 3 * (self.size() - self.virtual_sites().len()) - frozen,
             DegreesOfFreedom::Molecules => 3 * self.molecules().count(),
         }
     }
@@ -257,11 +555,23 @@ impl System {
         KineticEnergy.compute(self)
     }
 
+    /// Get the kinetic energy tensor of the system, $\sum_i m_i \vec v_i
+    /// \otimes \vec v_i$.
+    pub fn kinetic_tensor(&self) -> Matrix3 {
+        KineticTensor.compute(self)
+    }
+
     /// Get the potential energy of the system.
     pub fn potential_energy(&self) -> f64 {
         PotentialEnergy.compute(self)
     }
 
+    /// Get the contributions of the different kinds of interactions to the
+    /// potential energy of the system.
+    pub fn energy_components(&self) -> EnergyComponents {
+        PotentialEnergyComponents.compute(self)
+    }
+
     /// Get the total energy of the system.
     pub fn total_energy(&self) -> f64 {
         TotalEnergy.compute(self)
@@ -275,6 +585,18 @@ impl System {
         }
     }
 
+    /// Get the contributions of the translation, rotation and vibration of
+    /// the molecules to the instantaneous temperature of the system.
+    pub fn temperature_components(&self) -> TemperatureComponents {
+        PartitionedTemperature.compute(self)
+    }
+
+    /// Get the derivative of the potential energy of the system with respect
+    /// to the coupling parameter $\lambda$ of the pair potentials.
+    pub fn d_energy_d_lambda(&self) -> f64 {
+        LambdaDerivative.compute(self)
+    }
+
     /// Get the volume of the system.
     pub fn volume(&self) -> f64 {
         Volume.compute(self)
@@ -285,6 +607,12 @@ impl System {
         Virial.compute(self)
     }
 
+    /// Get the contributions of the different kinds of interactions to the
+    /// virial tensor of the system.
+    pub fn virial_components(&self) -> VirialComponents {
+        PartitionedVirial.compute(self)
+    }
+
     /// Get the pressure of the system from the virial equation, at the system
     /// instantaneous temperature.
     pub fn pressure(&self) -> f64 {
@@ -314,6 +642,43 @@ impl System {
     pub fn forces(&self) -> Vec<Vector3D> {
         Forces.compute(self)
     }
+
+    /// Get the torques acting on the orientations of all the particles in
+    /// the system
+    pub fn torques(&self) -> Vec<Vector3D> {
+        Torques.compute(self)
+    }
+
+    /// Get the norm of the largest force acting on a particle in the system,
+    /// together with the index of this particle; or `None` if the system is
+    /// empty. This can be used to check the convergence of an energy
+    /// minimization, or to find the origin of an unstable simulation. This
+    /// function computes all the forces in the system.
+    pub fn max_force(&self) -> Option<(f64, usize)> {
+        let mut max_force: Option<(f64, usize)> = None;
+        for (i, force) in self.forces().iter().enumerate() {
+            let norm = force.norm();
+            if max_force.map_or(true, |(max, _)| norm > max) {
+                max_force = Some((norm, i));
+            }
+        }
+        return max_force;
+    }
+
+    /// Get the pressure of the system using finite differences of the
+    /// potential energy, changing the volume by `dv`. This is slow, and
+    /// should only be used to check the result of `System::pressure`.
+    pub fn numerical_pressure(&self, dv: f64) -> f64 {
+        NumericalPressure { delta: dv }.compute(self)
+    }
+
+    /// Get the forces acting on all the particles in the system using finite
+    /// differences of the potential energy, moving the particles by `dx`.
+    /// This is slow, and should only be used to check the result of
+    /// `System::forces`.
+    pub fn numerical_forces(&self, dx: f64) -> Vec<Vector3D> {
+        NumericalForces { delta: dx }.compute(self)
+    }
 }
 
 impl System {
@@ -322,17 +687,45 @@ impl System {
         self.check_potentials()
     }
 
-    fn check_potentials(&self) {
+    /// Enable or disable the warnings emitted by [`System::check`] for the
+    /// interactions without an associated potential. These warnings are
+    /// enabled by default. Systems where some pairs of particles
+    /// intentionally do not interact can disable them, and use
+    /// [`System::check_interactions`] to get the list of missing
+    /// interactions.
+    ///
+    /// [`System::check`]: struct.System.html#method.check
+    /// [`System::check_interactions`]: struct.System.html#method.check_interactions
+    pub fn set_missing_interactions_warnings(&mut self, enabled: bool) {
+        self.warn_missing_interactions = enabled;
+    }
+
+    /// Get a report of all the interactions needed by this system that do not
+    /// have an associated potential.
+    ///
+    /// The pairs are enumerated from the system composition, and the bonds,
+    /// angles and dihedral angles from the molecules topology. Each missing
+    /// interaction is reported only once, using the particles names sorted in
+    /// the same way as the `Interactions` storage.
+    pub fn check_interactions(&self) -> MissingInteractions {
+        let mut missing = MissingInteractions::default();
+
+        // Get the name associated with each particle kind
+        let mut names = BTreeMap::new();
+        for (kind, name) in soa_zip!(self.particles(), [kind, name]) {
+            let _ = names.entry(*kind).or_insert_with(|| name.as_str());
+        }
+
         // Check pairs
-        for i in 0..self.size() {
-            let kind_i = self.particles().kind[i];
-            for j in (i + 1)..self.size() {
-                let kind_j = self.particles().kind[j];
+        let composition = self.composition();
+        for (kind_i, count_i) in composition.all_particles() {
+            for (kind_j, _) in composition.all_particles().filter(|&(kind, _)| kind >= kind_i) {
+                if kind_i == kind_j && count_i < 2 {
+                    continue;
+                }
                 if self.interactions.pair((kind_i, kind_j)).is_none() {
-                    warn_once!(
-                        "no potential defined for the pair {:?}",
-                        self.sorted_names_pair(i, j)
-                    );
+                    let (name_i, name_j) = sorted_pair(names[&kind_i], names[&kind_j]);
+                    let _ = missing.pairs.insert((name_i.into(), name_j.into()));
                 }
             }
         }
@@ -343,10 +736,8 @@ impl System {
                 let kind_i = self.particles().kind[bond.i()];
                 let kind_j = self.particles().kind[bond.j()];
                 if self.interactions.bond((kind_i, kind_j)).is_none() {
-                    warn_once!(
-                        "no potential defined for the bond {:?}",
-                        self.sorted_names_pair(bond.i(), bond.j())
-                    );
+                    let (name_i, name_j) = self.sorted_names_pair(bond.i(), bond.j());
+                    let _ = missing.bonds.insert((name_i.into(), name_j.into()));
                 }
             }
 
@@ -355,10 +746,8 @@ impl System {
                 let kind_j = self.particles().kind[angle.j()];
                 let kind_k = self.particles().kind[angle.k()];
                 if self.interactions.angle((kind_i, kind_j, kind_k)).is_none() {
-                    warn_once!(
-                        "no potential defined for the angle {:?}",
-                        self.sorted_names_angle(angle.i(), angle.j(), angle.k())
-                    );
+                    let (name_i, name_j, name_k) = self.sorted_names_angle(angle.i(), angle.j(), angle.k());
+                    let _ = missing.angles.insert((name_i.into(), name_j.into(), name_k.into()));
                 }
             }
 
@@ -368,11 +757,11 @@ impl System {
                 let kind_k = self.particles().kind[dihedral.k()];
                 let kind_m = self.particles().kind[dihedral.m()];
                 if self.interactions.dihedral((kind_i, kind_j, kind_k, kind_m)).is_none() {
-                    warn_once!(
-                        "no potential defined for the dihedral angle {:?}",
-                        self.sorted_names_dihedral(
-                            dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m()
-                        )
+                    let (name_i, name_j, name_k, name_m) = self.sorted_names_dihedral(
+                        dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m()
+                    );
+                    let _ = missing.dihedrals.insert(
+                        (name_i.into(), name_j.into(), name_k.into(), name_m.into())
                     );
                 }
             }
@@ -380,20 +769,40 @@ impl System {
 
         // check the need for a coulombic potential
         let charge2 = self.particles().charge.iter().map(|q| q * q).sum::<f64>();
-        if charge2 > 1e-3 && self.interactions.coulomb.is_none() {
+        missing.coulomb = charge2 > 1e-3 && self.interactions.coulomb.is_none();
+
+        return missing;
+    }
+
+    fn check_potentials(&self) {
+        if !self.warn_missing_interactions {
+            return;
+        }
+
+        let missing = self.check_interactions();
+        for pair in &missing.pairs {
+            warn_once!("no potential defined for the pair {:?}", pair);
+        }
+
+        for bond in &missing.bonds {
+            warn_once!("no potential defined for the bond {:?}", bond);
+        }
+
+        for angle in &missing.angles {
+            warn_once!("no potential defined for the angle {:?}", angle);
+        }
+
+        for dihedral in &missing.dihedrals {
+            warn_once!("no potential defined for the dihedral angle {:?}", dihedral);
+        }
+
+        if missing.coulomb {
             warn_once!("no coulombic potential solver defined, but the system is charged");
         }
     }
 
     fn sorted_names_pair(&self, i: usize, j: usize) -> (&str, &str) {
-        // Use the same sorting as interactions
-        let name_i = &self.particles().name[i];
-        let name_j = &self.particles().name[j];
-        if name_i < name_j {
-            (name_i, name_j)
-        } else {
-            (name_j, name_i)
-        }
+        sorted_pair(&self.particles().name[i], &self.particles().name[j])
     }
 
     fn sorted_names_angle(&self, i: usize, j: usize, k: usize) -> (&str, &str, &str) {
@@ -429,6 +838,106 @@ impl System {
     }
 }
 
+/// Use the same sorting as interactions for a pair of names
+fn sorted_pair<'a>(name_i: &'a str, name_j: &'a str) -> (&'a str, &'a str) {
+    if name_i < name_j {
+        (name_i, name_j)
+    } else {
+        (name_j, name_i)
+    }
+}
+
+/// List of the interactions needed by a [`System`](struct.System.html) for
+/// which no potential is defined, as returned by
+/// [`System::check_interactions`](struct.System.html#method.check_interactions).
+///
+/// Interactions are identified by the names of the particles involved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MissingInteractions {
+    /// Pairs of particles without pair potential
+    pub pairs: BTreeSet<(String, String)>,
+    /// Bonds without bond potential
+    pub bonds: BTreeSet<(String, String)>,
+    /// Angles without angle potential
+    pub angles: BTreeSet<(String, String, String)>,
+    /// Dihedral angles without dihedral potential
+    pub dihedrals: BTreeSet<(String, String, String, String)>,
+    /// Is the system charged without a coulombic potential solver?
+    pub coulomb: bool,
+}
+
+impl MissingInteractions {
+    /// Check if no interaction is missing
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty() && self.bonds.is_empty() && self.angles.is_empty() &&
+        self.dihedrals.is_empty() && !self.coulomb
+    }
+}
+
+/// Possible errors when setting a potential in a [`System`].
+///
+/// [`System`]: struct.System.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum PotentialError {
+    /// The cutoff of the potential is bigger than half of the smallest cell
+    /// length, and the minimum image convention can not be used.
+    CutoffTooBig {
+        /// The cutoff of the potential
+        cutoff: f64,
+        /// Half of the smallest cell length
+        half_length: f64,
+    },
+    /// The potential requires periodic boundary conditions, but the cell is
+    /// infinite.
+    RequiresPeriodicity,
+}
+
+impl fmt::Display for PotentialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PotentialError::CutoffTooBig { cutoff, half_length } => write!(f,
+                "Can not add a potential with a cutoff bigger than half of the \
+                smallest cell length ({} > {}). Try increasing the cell size or \
+                decreasing the cutoff.", cutoff, half_length
+            ),
+            PotentialError::RequiresPeriodicity => write!(f,
+                "Can not use a coulombic potential requiring periodic boundary \
+                conditions (such as Ewald summation) with an infinite cell."
+            ),
+        }
+    }
+}
+
+impl Error for PotentialError {}
+
+/// Get the chemical formula in Hill order of a molecule containing particles
+/// with the given `names`
+fn chemical_formula(names: &[String]) -> String {
+    let mut counts = BTreeMap::new();
+    for name in names {
+        *counts.entry(name.as_str()).or_insert(0) += 1;
+    }
+
+    let mut formula = String::new();
+    let mut add = |name: &str, count: usize| {
+        formula.push_str(name);
+        if count > 1 {
+            formula.push_str(&count.to_string());
+        }
+    };
+
+    if let Some(carbon) = counts.remove("C") {
+        add("C", carbon);
+        if let Some(hydrogen) = counts.remove("H") {
+            add("H", hydrogen);
+        }
+    }
+    for (name, count) in counts {
+        add(name, count);
+    }
+    return formula;
+}
+
 impl Deref for System {
     type Target = Configuration;
 
@@ -445,7 +954,11 @@ impl DerefMut for System {
 
 #[cfg(test)]
 mod tests {
-    use crate::{System, Molecule, Particle, ParticleKind};
+    use crate::{System, Molecule, MoleculeBuilder, Particle, ParticleKind, UnitCell, Vector3D, VirtualSite};
+    use crate::PotentialError;
+    use crate::{DistanceRestraint, Ewald, Harmonic, NullPotential, PairInteraction, SharedEwald, Wolf};
+
+    use approx::assert_relative_eq;
 
     #[test]
     #[should_panic]
@@ -454,6 +967,202 @@ mod tests {
         system.simulated_temperature(Some(-1.0));
     }
 
+    #[test]
+    fn repartition_hydrogen_mass() {
+        // Methanol, without any symmetry
+        let mut builder = MoleculeBuilder::new();
+        let c = builder.add_particle(Particle::with_position("C", Vector3D::new(1.0, 1.0, 1.0)));
+        let o = builder.add_particle(Particle::with_position("O", Vector3D::new(2.43, 1.0, 1.0)));
+        builder.add_bond(c, o);
+        for &position in &[[0.64, 2.0, 1.0], [0.64, 0.5, 1.87], [0.64, 0.5, 0.13]] {
+            let h = builder.add_particle(Particle::with_position("H", position.into()));
+            builder.add_bond(c, h);
+        }
+        let h = builder.add_particle(Particle::with_position("H", Vector3D::new(2.75, 1.9, 1.0)));
+        builder.add_bond(o, h);
+
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(builder.build());
+        system.add_molecule(Molecule::new(Particle::with_position("H", Vector3D::new(5.0, 5.0, 5.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(7.0, 3.0, 2.0))));
+
+        let initial = system.particles().mass.to_vec();
+        let total_mass = initial.iter().sum::<f64>();
+        let center_of_mass = system.molecule(0).center_of_mass();
+        let distance = system.distance(0, 2);
+
+        system.repartition_hydrogen_mass(3.0);
+        let masses = system.particles().mass;
+        for &mass in &masses[2..6] {
+            assert_eq!(mass, 3.024);
+        }
+        assert_relative_eq!(masses[0], initial[0] - 3.0 * 2.016, epsilon = 1e-12);
+        assert_relative_eq!(masses[1], initial[1] - 2.016, epsilon = 1e-12);
+        // Free hydrogen and heavy atoms are not changed
+        assert_eq!(masses[6], 1.008);
+        assert_eq!(masses[7], 39.948);
+
+        assert_relative_eq!(system.particles().mass.iter().sum::<f64>(), total_mass, epsilon = 1e-12);
+        assert_relative_eq!(system.molecule(0).center_of_mass(), center_of_mass, epsilon = 1e-12);
+        // The molecule is only translated
+        assert_relative_eq!(system.distance(0, 2), distance, epsilon = 1e-12);
+        assert_eq!(system.particles().position[6], Vector3D::new(5.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn repartition_hydrogen_mass_unchanged_on_error() {
+        let mut builder = MoleculeBuilder::new();
+        let c = builder.add_particle(Particle::new("C"));
+        let h = builder.add_particle(Particle::new("H"));
+        builder.add_bond(c, h);
+        let li = builder.add_particle(Particle::new("Li"));
+        let h = builder.add_particle(Particle::new("H"));
+        builder.add_bond(c, li);
+        builder.add_bond(li, h);
+
+        let mut system = System::new();
+        system.add_molecule(builder.build());
+        let masses = system.particles().mass.to_vec();
+
+        // The carbon atom is heavy enough, but not the lithium atom
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            system.repartition_hydrogen_mass(8.0);
+        }));
+        assert!(result.is_err());
+        assert_eq!(system.particles().mass, &masses[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the mass of particle 0 would become negative after hydrogen mass repartitioning")]
+    fn repartition_hydrogen_mass_negative() {
+        let mut builder = MoleculeBuilder::new();
+        let c = builder.add_particle(Particle::new("C"));
+        let h = builder.add_particle(Particle::new("H"));
+        builder.add_bond(c, h);
+
+        let mut system = System::new();
+        system.add_molecule(builder.build());
+        system.repartition_hydrogen_mass(20.0);
+    }
+
+    #[test]
+    fn global_potentials() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.0, 0.0, 0.0))));
+
+        let generation = system.interactions_generation();
+        system.add_global_potential(Box::new(DistanceRestraint::new(vec![0], vec![1], 4.0, 2.0)));
+        assert_eq!(system.potential_energy(), 2.0);
+        assert!(system.interactions_generation() > generation);
+
+        let generation = system.interactions_generation();
+        let _ = system.remove_global_potential(0);
+        assert!(system.global_potentials().is_empty());
+        assert_eq!(system.potential_energy(), 0.0);
+        assert!(system.interactions_generation() > generation);
+    }
+
+    #[test]
+    fn max_force() {
+        let mut system = System::new();
+        assert_eq!(system.max_force(), None);
+
+        for i in 0..3 {
+            let position = Vector3D::new(2.0 * i as f64, 0.0, 0.0);
+            system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+        }
+        let potential = PairInteraction::new(Box::new(Harmonic { k: 10.0, x0: 2.0 }), 3.0);
+        system.set_pair_potential(("Ar", "Ar"), potential).unwrap();
+
+        let (force, _) = system.max_force().unwrap();
+        assert!(force < 1e-12);
+
+        system.particles_mut().position[1][0] = 2.3;
+        let (force, i) = system.max_force().unwrap();
+        assert_eq!(i, 1);
+        assert_relative_eq!(force, 6.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn infinite_cell_cutoffs() {
+        let mut system = System::new();
+        assert!(system.cell.is_infinite());
+
+        let potential = PairInteraction::new(Box::new(NullPotential), 1e6);
+        system.set_pair_potential(("Ar", "Ar"), potential).unwrap();
+        system.set_coulomb_potential(Box::new(Wolf::new(1e6))).unwrap();
+    }
+
+    #[test]
+    fn big_cutoff() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::new("Ar")));
+        system.add_molecule(Molecule::new(Particle::new("Ar")));
+
+        let potential = PairInteraction::new(Box::new(NullPotential), 6.0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            system.set_pair_potential(("Ar", "Ar"), potential)
+        }));
+        let error = result.expect("set_pair_potential should not panic").unwrap_err();
+        assert_eq!(error, PotentialError::CutoffTooBig { cutoff: 6.0, half_length: 5.0 });
+        assert!(system.pair_potential(0, 1).is_none());
+
+        // The check can be explicitly disabled
+        let potential = PairInteraction::new(Box::new(NullPotential), 6.0);
+        system.set_pair_potential_unchecked(("Ar", "Ar"), potential);
+        assert_eq!(system.pair_potential(0, 1).unwrap().cutoff(), 6.0);
+    }
+
+    #[test]
+    fn big_coulomb_cutoff() {
+        let mut system = System::with_cell(UnitCell::ortho(20.0, 12.0, 30.0));
+        let error = system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap_err();
+        assert_eq!(error, PotentialError::CutoffTooBig { cutoff: 8.0, half_length: 6.0 });
+        assert_eq!(
+            error.to_string(),
+            "Can not add a potential with a cutoff bigger than half of the smallest \
+            cell length (8 > 6). Try increasing the cell size or decreasing the cutoff."
+        );
+        assert!(system.interactions().coulomb.is_none());
+
+        system.set_coulomb_potential(Box::new(Wolf::new(5.0))).unwrap();
+        assert!(system.interactions().coulomb.is_some());
+    }
+
+    #[test]
+    fn ewald_infinite_cell() {
+        let mut system = System::new();
+        let ewald = SharedEwald::new(Ewald::new(8.0, 10, None));
+        let error = system.set_coulomb_potential(Box::new(ewald)).unwrap_err();
+        assert_eq!(error, PotentialError::RequiresPeriodicity);
+        assert!(error.to_string().starts_with(
+            "Can not use a coulombic potential requiring periodic boundary conditions"
+        ));
+    }
+
+    #[test]
+    fn check_overlaps() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 3.0, 0.0))));
+        assert!(system.check_overlaps(1.0).is_empty());
+
+        // Coincident particles are flagged
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.0, 0.0, 0.0))));
+        assert_eq!(system.check_overlaps(1.0), [(1, 3, 0.0)]);
+
+        // Using the nearest image
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 9.5, 0.0))));
+        assert_eq!(system.check_overlaps(1.0), [(0, 4, 0.5), (1, 3, 0.0)]);
+
+        // Virtual sites are ignored
+        assert!(system.add_bond(3, 4).is_empty());
+        system.add_virtual_site(VirtualSite::new(4, [3, 3, 3], [1.0, 0.0, 0.0]));
+        assert_eq!(system.check_overlaps(1.0), [(1, 3, 0.0)]);
+    }
+
     #[test]
     fn deref() {
         let mut system = System::new();
@@ -500,6 +1209,38 @@ mod tests {
         assert_eq!(composition.particles(ParticleKind(3)), 1);
     }
 
+    #[test]
+    fn count_by_name() {
+        let mut system = System::new();
+        for i in 0..5 {
+            let mut builder = MoleculeBuilder::new();
+            let o = builder.add_particle(Particle::with_position("O", Vector3D::new(3.0 * i as f64, 0.0, 0.0)));
+            let h1 = builder.add_particle(Particle::with_position("H", Vector3D::new(3.0 * i as f64 + 0.96, 0.0, 0.0)));
+            let h2 = builder.add_particle(Particle::with_position("H", Vector3D::new(3.0 * i as f64 - 0.24, 0.93, 0.0)));
+            builder.add_bond(o, h1);
+            builder.add_bond(o, h2);
+            system.add_molecule(builder.build());
+        }
+        assert_eq!(system.count_by_name("O"), 5);
+        assert_eq!(system.count_by_name("H"), 2 * system.count_by_name("O"));
+
+        system.add_molecule(Molecule::new(Particle::new("Na")));
+        let mut methane = Molecule::new(Particle::new("C"));
+        for _ in 0..4 {
+            methane.add_particle_bonded_to(0, Particle::new("H"));
+        }
+        system.add_molecule(methane);
+
+        assert_eq!(system.count_by_name("H"), 2 * 5 + 4);
+        assert_eq!(system.count_by_name("Cl"), 0);
+
+        let counts = system.molecule_count_by_formula();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["H2O"], 5);
+        assert_eq!(counts["Na"], 1);
+        assert_eq!(counts["CH4"], 1);
+    }
+
     #[test]
     fn missing_interaction() {
         let mut system = System::new();
@@ -513,6 +1254,47 @@ mod tests {
         assert!(system.dihedral_potential(0, 0, 0, 0).is_none());
     }
 
+    #[test]
+    fn check_interactions() {
+        use crate::{Harmonic, NullPotential, PairInteraction, UnitCell};
+
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for _ in 0..2 {
+            let i = system.size();
+            system.add_molecule(Molecule::new(Particle::new("O")));
+            system.add_molecule(Molecule::new(Particle::new("H")));
+            system.add_molecule(Molecule::new(Particle::new("H")));
+            let _ = system.add_bond(i, i + 1);
+            let _ = system.add_bond(i, i + 2);
+        }
+        assert_eq!(system.molecules().count(), 2);
+
+        let missing = system.check_interactions();
+        assert_eq!(missing.pairs.len(), 3);
+        assert_eq!(missing.bonds.len(), 1);
+        assert_eq!(missing.angles.len(), 1);
+        assert!(missing.dihedrals.is_empty());
+        assert!(!missing.coulomb);
+
+        for &(a, b) in &[("O", "O"), ("O", "H"), ("H", "H")] {
+            system.set_pair_potential((a, b), PairInteraction::new(Box::new(NullPotential), 3.0)).unwrap();
+        }
+        system.set_angle_potential(("H", "O", "H"), Box::new(Harmonic { k: 1.0, x0: 1.8 }));
+
+        let missing = system.check_interactions();
+        assert!(!missing.is_empty());
+        assert!(missing.pairs.is_empty());
+        assert!(missing.angles.is_empty());
+        assert_eq!(missing.bonds.len(), 1);
+        assert!(missing.bonds.contains(&(String::from("H"), String::from("O"))));
+
+        system.set_bond_potential(("O", "H"), Box::new(Harmonic { k: 1.0, x0: 1.0 }));
+        assert!(system.check_interactions().is_empty());
+
+        system.particles_mut().charge[0] = -0.8;
+        assert!(system.check_interactions().coulomb);
+    }
+
     #[test]
     fn check_potentials() {
         use std::sync::{Arc, Mutex};
@@ -563,10 +1345,26 @@ no potential defined for the angle ("Ar", "He", "He")
 no potential defined for the dihedral angle ("Ar", "Ar", "He", "He")
 "#;
 
-        let messages = message.lock().unwrap();
-        assert_eq!(expected_warnings.lines().count(), messages.lines().count());
-        for line in messages.lines() {
-            assert!(expected_warnings.contains(line));
+        {
+            let messages = message.lock().unwrap();
+            assert_eq!(expected_warnings.lines().count(), messages.lines().count());
+            for line in messages.lines() {
+                assert!(expected_warnings.contains(line));
+            }
         }
+
+        // No warnings when they are disabled, but the missing interactions
+        // are still reported by check_interactions
+        let mut system = System::new();
+        system.add_molecule(Molecule::new(Particle::new("Ne")));
+        system.add_molecule(Molecule::new(Particle::new("Kr")));
+        system.set_missing_interactions_warnings(false);
+        system.check();
+
+        assert!(!message.lock().unwrap().contains("Kr"));
+        assert!(system.pair_potential(0, 1).is_none());
+        let missing = system.check_interactions();
+        assert_eq!(missing.pairs.len(), 1);
+        assert!(missing.pairs.contains(&(String::from("Kr"), String::from("Ne"))));
     }
 }
@@ -297,6 +297,90 @@ impl Matrix3 {
             self[0][2] * self[0][2] + self[1][2] * self[1][2] + self[2][2] * self[2][2]
         )
     }
+
+    /// Compute the eigenvalues and eigenvectors of a symmetric matrix, using
+    /// the [Jacobi eigenvalue algorithm][Wiki]. The eigenvalues are sorted in
+    /// increasing order, and the normalized eigenvector associated with the
+    /// `i`-th eigenvalue is the `i`-th column of the returned matrix.
+    ///
+    /// Only the upper triangle of the matrix is used in the computation, the
+    /// matrix is assumed to be symmetric.
+    ///
+    /// [Wiki]: https://en.wikipedia.org/wiki/Jacobi_eigenvalue_algorithm
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_core::types::Matrix3;
+    /// let matrix = Matrix3::new([
+    ///     [2.0, 1.0, 0.0],
+    ///     [1.0, 2.0, 0.0],
+    ///     [0.0, 0.0, 5.0]
+    /// ]);
+    ///
+    /// let (values, vectors) = matrix.symmetric_eigen();
+    /// assert!(f64::abs(values[0] - 1.0) < 1e-12);
+    /// assert!(f64::abs(values[1] - 3.0) < 1e-12);
+    /// assert!(f64::abs(values[2] - 5.0) < 1e-12);
+    ///
+    /// // The last eigenvector is along the z axis
+    /// assert!(f64::abs(vectors[2][2].abs() - 1.0) < 1e-12);
+    /// ```
+    pub fn symmetric_eigen(&self) -> ([f64; 3], Matrix3) {
+        let mut matrix = Matrix3::new([
+            [self[0][0], self[0][1], self[0][2]],
+            [self[0][1], self[1][1], self[1][2]],
+            [self[0][2], self[1][2], self[2][2]],
+        ]);
+        let mut vectors = Matrix3::one();
+
+        let scale = matrix.norm() * matrix.norm();
+        for _ in 0..50 {
+            let off_diagonal = matrix[0][1] * matrix[0][1] + matrix[0][2] * matrix[0][2] + matrix[1][2] * matrix[1][2];
+            if off_diagonal <= 1e-32 * scale {
+                break;
+            }
+
+            for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+                if matrix[p][q] == 0.0 {
+                    continue;
+                }
+                // Rotation cancelling the (p, q) element of the matrix
+                let theta = (matrix[q][q] - matrix[p][p]) / (2.0 * matrix[p][q]);
+                let t = f64::signum(theta) / (f64::abs(theta) + f64::sqrt(theta * theta + 1.0));
+                let cos = 1.0 / f64::sqrt(t * t + 1.0);
+                let sin = t * cos;
+
+                for k in 0..3 {
+                    let (kp, kq) = (matrix[k][p], matrix[k][q]);
+                    matrix[k][p] = cos * kp - sin * kq;
+                    matrix[k][q] = sin * kp + cos * kq;
+                }
+                for k in 0..3 {
+                    let (pk, qk) = (matrix[p][k], matrix[q][k]);
+                    matrix[p][k] = cos * pk - sin * qk;
+                    matrix[q][k] = sin * pk + cos * qk;
+                }
+                for k in 0..3 {
+                    let (kp, kq) = (vectors[k][p], vectors[k][q]);
+                    vectors[k][p] = cos * kp - sin * kq;
+                    vectors[k][q] = sin * kp + cos * kq;
+                }
+            }
+        }
+
+        let mut order = [0, 1, 2];
+        order.sort_by(|&i, &j| matrix[i][i].total_cmp(&matrix[j][j]));
+
+        let values = [matrix[order[0]][order[0]], matrix[order[1]][order[1]], matrix[order[2]][order[2]]];
+        let mut sorted = Matrix3::zero();
+        for (column, &i) in order.iter().enumerate() {
+            for k in 0..3 {
+                sorted[k][column] = vectors[k][i];
+            }
+        }
+        return (values, sorted);
+    }
 }
 
 impl_arithmetic!(
@@ -784,4 +868,40 @@ mod tests {
         assert_eq!(Matrix3::zero().norm(), 0.0);
         assert_eq!(Matrix3::one().norm(), f64::sqrt(3.0));
     }
+
+    #[test]
+    fn symmetric_eigen() {
+        let matrix = Matrix3::new([
+            [4.0, -2.0, 1.5],
+            [-2.0, 1.0, 0.3],
+            [1.5, 0.3, -3.0],
+        ]);
+
+        let (values, vectors) = matrix.symmetric_eigen();
+        assert!(values[0] <= values[1] && values[1] <= values[2]);
+        assert_ulps_eq!(values[0] + values[1] + values[2], matrix.trace(), epsilon = 1e-12);
+        assert_ulps_eq!(values[0] * values[1] * values[2], matrix.determinant(), epsilon = 1e-12);
+
+        for i in 0..3 {
+            let vector = Vector3D::new(vectors[0][i], vectors[1][i], vectors[2][i]);
+            assert_ulps_eq!(vector.norm(), 1.0, epsilon = 1e-12);
+            let product = matrix * vector;
+            for k in 0..3 {
+                assert_ulps_eq!(product[k], values[i] * vector[k], epsilon = 1e-12);
+            }
+        }
+
+        // Already diagonal matrix
+        let (values, vectors) = Matrix3::new([
+            [3.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, 2.0],
+        ]).symmetric_eigen();
+        assert_eq!(values, [-1.0, 2.0, 3.0]);
+        assert_eq!(vectors, Matrix3::new([
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ]));
+    }
 }
@@ -24,7 +24,7 @@ use std::f64::consts::PI;
 
 use lazy_static::lazy_static;
 
-use crate::consts::{BOHR_RADIUS, AVOGADRO_NUMBER};
+use crate::consts::{BOHR_RADIUS, AVOGADRO_NUMBER, K_BOLTZMANN};
 
 // Atomic mass unit in kg
 const U_IN_KG: f64 = 1.660538782e-27;
@@ -82,6 +82,18 @@ lazy_static!{
 
         return map;
     };
+
+    /// A map of conversion factors from the units in reduced Lennard-Jones
+    /// units to lumol internal units. All the conversion factors are 1,
+    /// except for the temperature.
+    static ref REDUCED_CONVERSION_FACTORS: BTreeMap<&'static str, f64> = {
+        let mut map = BTreeMap::new();
+        for &unit in CONVERSION_FACTORS.keys() {
+            let _ = map.insert(unit, 1.0);
+        }
+        let _ = map.insert("K", 1.0 / K_BOLTZMANN);
+        return map;
+    };
 }
 
 /// Possible error causes when parsing an unit string.
@@ -287,9 +299,15 @@ impl UnitExpr {
 
     /// Parse a string, and generate the corresponding unit expression
     fn parse(unit: &str) -> Result<UnitExpr, ParseError> {
+        UnitExpr::parse_with_factors(unit, &CONVERSION_FACTORS)
+    }
+
+    /// Parse a string, and generate the corresponding unit expression using
+    /// the given conversion `factors`
+    fn parse_with_factors(unit: &str, factors: &BTreeMap<&'static str, f64>) -> Result<UnitExpr, ParseError> {
         let tokens = tokenize(unit);
         let mut stream = shunting_yard(tokens)?;
-        let ast = read_expr(&mut stream)?;
+        let ast = read_expr(&mut stream, factors)?;
         if stream.is_empty() {
             Ok(ast)
         } else {
@@ -301,31 +319,32 @@ impl UnitExpr {
     }
 }
 
-/// Read and pop (recursively) a single expression from the `stream`.
-/// The `stream` must be in reverse polish notation.
-fn read_expr(stream: &mut Vec<Token>) -> Result<UnitExpr, ParseError> {
+/// Read and pop (recursively) a single expression from the `stream`, using
+/// the given conversion `factors`. The `stream` must be in reverse polish
+/// notation.
+fn read_expr(stream: &mut Vec<Token>, factors: &BTreeMap<&'static str, f64>) -> Result<UnitExpr, ParseError> {
     if let Some(token) = stream.pop() {
         match token {
             Token::Value(unit) => {
-                match CONVERSION_FACTORS.get(&*unit) {
+                match factors.get(&*unit) {
                     Some(&value) => Ok(UnitExpr::Val(value)),
                     None => Err(ParseError::NotFound { unit: unit }),
                 }
             }
             Token::Mul => {
-                let rhs = read_expr(stream).map_err(|err| {
+                let rhs = read_expr(stream, factors).map_err(|err| {
                     ParseError::MalformedExpr(format!("Error in unit at the right of '*': {}", err))
                 })?;
-                let lhs = read_expr(stream).map_err(|err| {
+                let lhs = read_expr(stream, factors).map_err(|err| {
                     ParseError::MalformedExpr(format!("Error in unit at the left of '*': {}", err))
                 })?;
                 Ok(UnitExpr::Mul(Box::new(lhs), Box::new(rhs)))
             }
             Token::Div => {
-                let rhs = read_expr(stream).map_err(|err| {
+                let rhs = read_expr(stream, factors).map_err(|err| {
                     ParseError::MalformedExpr(format!("Error in unit at the right of '/': {}", err))
                 })?;
-                let lhs = read_expr(stream).map_err(|err| {
+                let lhs = read_expr(stream, factors).map_err(|err| {
                     ParseError::MalformedExpr(format!("Error in unit at the left of '/': {}", err))
                 })?;
                 Ok(UnitExpr::Div(Box::new(lhs), Box::new(rhs)))
@@ -348,7 +367,7 @@ fn read_expr(stream: &mut Vec<Token>) -> Result<UnitExpr, ParseError> {
                         )
                     }
                 };
-                let expr = read_expr(stream).map_err(|err| {
+                let expr = read_expr(stream, factors).map_err(|err| {
                     ParseError::MalformedExpr(format!("Error in unit at the left of '*': {}", err))
                 })?;
                 Ok(UnitExpr::Pow(Box::new(expr), pow))
@@ -405,6 +424,94 @@ pub fn to(value: f64, unit: &str) -> Result<f64, ParseError> {
     return Ok(value / unit.eval());
 }
 
+/// Unit system used for the inputs and outputs of a simulation.
+///
+/// In reduced Lennard-Jones units, the energy unit is the $\varepsilon$
+/// parameter, the distance unit is the $\sigma$ parameter, the mass unit is
+/// the mass of the particles and the Boltzmann constant is 1. Reduced
+/// quantities are usually denoted with a star: $T^* = k_B T / \varepsilon$,
+/// $P^* = P \sigma^3 / \varepsilon$, $\rho^* = \rho \sigma^3$, *etc.*
+///
+/// Lumol internal units are consistent, so reduced units are used by setting
+/// $\varepsilon$ to 1 internal energy unit, $\sigma$ to 1 A and the masses to
+/// 1 u. Reduced quantities then have the same numerical value as the
+/// corresponding internal quantities, except for temperatures since the
+/// Boltzmann constant is not 1 in internal units.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::units::{self, UnitSystem};
+/// # use lumol_core::consts::K_BOLTZMANN;
+/// let units = UnitSystem::Physical;
+/// assert_eq!(units.from(3.0, "nm").unwrap(), units::from(3.0, "nm").unwrap());
+///
+/// let units = UnitSystem::Reduced;
+/// assert_eq!(units.from(3.0, "nm").unwrap(), 3.0);
+/// assert_eq!(units.from(1.0, "K").unwrap(), 1.0 / K_BOLTZMANN);
+/// assert_eq!(units.to(1.0 / K_BOLTZMANN, "K").unwrap(), 1.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// Physical units, converted to and from the internal units
+    Physical,
+    /// Reduced Lennard-Jones units
+    Reduced,
+}
+
+impl UnitSystem {
+    /// Get the conversion factors used by this unit system
+    fn factors(self) -> &'static BTreeMap<&'static str, f64> {
+        match self {
+            UnitSystem::Physical => &CONVERSION_FACTORS,
+            UnitSystem::Reduced => &REDUCED_CONVERSION_FACTORS,
+        }
+    }
+
+    /// Convert the numeric value `value` from the unit `unit` to the internal
+    /// unit. In reduced units, `unit` is only used to get the dimension of
+    /// the value.
+    pub fn from(self, value: f64, unit: &str) -> Result<f64, ParseError> {
+        let unit = UnitExpr::parse_with_factors(unit, self.factors())?;
+        return Ok(unit.eval() * value);
+    }
+
+    /// Parse the string `value` and convert it to the internal unit. In
+    /// physical units, `value` must contain the unit (`"300 K"`) and the
+    /// `unit` parameter is ignored. In reduced units, `value` must be a
+    /// number without unit (`"1.5"`), and `unit` gives the dimension of the
+    /// value.
+    pub fn from_str(self, value: &str, unit: &str) -> Result<f64, ParseError> {
+        match self {
+            UnitSystem::Physical => from_str(value),
+            UnitSystem::Reduced => {
+                let number = value.trim().parse::<f64>().map_err(|_| ParseError::MalformedExpr(
+                    format!("expected a number without unit in reduced units, got '{}'", value)
+                ))?;
+                return self.from(number, unit);
+            }
+        }
+    }
+
+    /// Convert the numeric value `value` (in internal units) to the unit
+    /// `unit`. In reduced units, `unit` is only used to get the dimension of
+    /// the value.
+    pub fn to(self, value: f64, unit: &str) -> Result<f64, ParseError> {
+        let unit = UnitExpr::parse_with_factors(unit, self.factors())?;
+        return Ok(value / unit.eval());
+    }
+
+    /// Get the label to use in outputs for a quantity with the given `name`
+    /// and `unit`: `Temperature/K` in physical units and `Temperature*` in
+    /// reduced units.
+    pub fn label(self, name: &str, unit: &str) -> String {
+        match self {
+            UnitSystem::Physical => format!("{}/{}", name, unit),
+            UnitSystem::Reduced => format!("{}*", name),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -491,4 +598,31 @@ mod test {
         assert_eq!(to(25.0, "bar").unwrap(), 4.1513469550000005e9);
         assert_eq!(to(25.0, "kJ/mol").unwrap(), 249999.99982494753);
     }
+
+    #[test]
+    fn reduced_units() {
+        let units = UnitSystem::Reduced;
+        assert_eq!(units.from(2.0, "kJ/mol").unwrap(), 2.0);
+        assert_eq!(units.from(2.0, "bar").unwrap(), 2.0);
+        assert_eq!(units.to(2.0, "A^3").unwrap(), 2.0);
+
+        let temperature = 1.5 / K_BOLTZMANN;
+        assert_ulps_eq!(units.from(1.5, "K").unwrap(), temperature);
+        assert_ulps_eq!(units.from_str("1.5", "K").unwrap(), temperature);
+        assert_ulps_eq!(units.to(temperature, "K").unwrap(), 1.5);
+        // Heat capacity: energy / temperature
+        assert_ulps_eq!(units.to(3.0 * K_BOLTZMANN, "kJ/mol/K").unwrap(), 3.0);
+
+        assert!(units.from_str("300 K", "K").is_err());
+        assert_eq!(units.label("Temperature", "K"), "Temperature*");
+    }
+
+    #[test]
+    fn physical_units() {
+        let units = UnitSystem::Physical;
+        assert_eq!(units.from(2.0, "nm").unwrap(), 20.0);
+        assert_eq!(units.from_str("2 nm", "A").unwrap(), 20.0);
+        assert_eq!(units.to(25.0, "bar").unwrap(), to(25.0, "bar").unwrap());
+        assert_eq!(units.label("Temperature", "K"), "Temperature/K");
+    }
 }
@@ -5,7 +5,7 @@ use std::io;
 use std::path::PathBuf;
 
 use lumol_sim::output::CustomOutputError;
-use lumol_core::TrajectoryError;
+use lumol_core::{PotentialError, TrajectoryError};
 use lumol_core::units::ParseError;
 
 /// Possible causes of error when reading input files
@@ -21,6 +21,8 @@ pub enum Error {
     Config(String),
     /// Unit parsing error
     Unit(ParseError),
+    /// Error while setting a potential in the system
+    Potential(PotentialError),
     /// Specific error from the custom outputs
     CustomOutput(CustomOutputError),
 }
@@ -61,6 +63,12 @@ impl From<ParseError> for Error {
     }
 }
 
+impl From<PotentialError> for Error {
+    fn from(err: PotentialError) -> Error {
+        Error::Potential(err)
+    }
+}
+
 impl From<(CustomOutputError, PathBuf)> for Error {
     fn from((err, path): (CustomOutputError, PathBuf)) -> Error {
         match err {
@@ -90,6 +98,7 @@ impl fmt::Display for Error {
             Error::TOML(ref err) => write!(fmt, "{}", err),
             Error::Config(ref err) => write!(fmt, "{}", err),
             Error::Unit(ref err) => write!(fmt, "{}", err),
+            Error::Potential(ref err) => write!(fmt, "{}", err),
             Error::CustomOutput(ref err) => write!(fmt, "{}", err),
         }
     }
@@ -102,6 +111,7 @@ impl std::error::Error for Error {
             Error::Io(ref err, _) => Some(err),
             Error::Trajectory(ref err) => Some(err),
             Error::Unit(ref err) => Some(err),
+            Error::Potential(ref err) => Some(err),
             Error::CustomOutput(ref err) => Some(err),
         }
     }
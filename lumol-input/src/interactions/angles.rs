@@ -3,7 +3,7 @@
 use toml::value::Table;
 
 use lumol_core::energy::{AnglePotential, DihedralPotential};
-use lumol_core::energy::{CosineHarmonic, Harmonic, Morse, NullPotential, Torsion};
+use lumol_core::energy::{CosineHarmonic, Harmonic, HarmonicDihedral, Morse, NullPotential, Torsion};
 use lumol_core::System;
 
 use crate::{InteractionsInput, FromToml, Error};
@@ -89,6 +89,7 @@ fn read_dihedral_potential(table: &Table) -> Result<Box<dyn DihedralPotential>,
         "harmonic" => Ok(Box::new(Harmonic::from_toml(table)?)),
         "cosine-harmonic" => Ok(Box::new(CosineHarmonic::from_toml(table)?)),
         "torsion" => Ok(Box::new(Torsion::from_toml(table)?)),
+        "harmonic-dihedral" => Ok(Box::new(HarmonicDihedral::from_toml(table)?)),
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
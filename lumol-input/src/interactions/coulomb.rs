@@ -2,8 +2,8 @@
 // Copyright (C) Lumol's contributors — BSD license
 use toml::Value;
 
-use lumol_core::energy::{CoulombicPotential, Ewald, SharedEwald, Wolf};
-use lumol_core::System;
+use lumol_core::energy::{CoulombicPotential, Ewald, SharedEwald, TholeDamping, TruncatedCoulomb, Wolf};
+use lumol_core::{units, System};
 
 use log::{info, warn};
 
@@ -20,7 +20,7 @@ impl InteractionsInput {
 
         let coulomb = coulomb.as_table().ok_or(Error::from("the 'coulomb' section must be a table"))?;
 
-        let solvers = coulomb.keys().cloned().filter(|key| key != "restriction").collect::<Vec<_>>();
+        let solvers = coulomb.keys().cloned().filter(|key| key != "restriction" && key != "thole").collect::<Vec<_>>();
 
         if solvers.len() != 1 {
             return Err(Error::from(
@@ -32,6 +32,7 @@ impl InteractionsInput {
         if let Value::Table(ref table) = coulomb[key] {
             let mut potential: Box<dyn CoulombicPotential> = match key {
                 "wolf" => Box::new(Wolf::from_toml(table)?),
+                "truncated" => Box::new(TruncatedCoulomb::from_toml(table)?),
                 "ewald" => {
                     let ewald = Ewald::from_toml(table, &system)?;
                     Box::new(SharedEwald::new(ewald))
@@ -43,7 +44,16 @@ impl InteractionsInput {
                 potential.set_restriction(restriction);
             }
 
-            system.set_coulomb_potential(potential);
+            if let Some(thole) = coulomb.get("thole") {
+                let thole = thole.as_str().ok_or(Error::from("'thole' must be a string in coulomb section"))?;
+                let thole = units::from_str(thole)?;
+                if thole <= 0.0 {
+                    return Err(Error::from("'thole' must be positive in coulomb section"));
+                }
+                potential.set_thole_damping(Some(TholeDamping::new(thole)));
+            }
+
+            system.set_coulomb_potential(potential)?;
             Ok(())
         } else {
             Err(Error::from(format!("coulombic solver '{}' must be a table", key)))
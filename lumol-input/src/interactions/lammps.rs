@@ -0,0 +1,270 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Importing interactions from LAMMPS input scripts
+use toml::value::{Table, Value};
+
+use std::collections::BTreeMap;
+
+use log::warn;
+
+use crate::{Error, InteractionsInput};
+
+/// Units used in the LAMMPS input, as set by the `units` command
+#[derive(Clone, Copy)]
+enum LammpsUnits {
+    Real,
+    Metal,
+}
+
+impl LammpsUnits {
+    fn energy(self) -> &'static str {
+        match self {
+            LammpsUnits::Real => "kcal/mol",
+            LammpsUnits::Metal => "eV",
+        }
+    }
+}
+
+/// State of the LAMMPS importer, storing the commands seen so far
+struct LammpsImporter {
+    units: LammpsUnits,
+    /// Names associated with the numeric atom, bond and angle types by the
+    /// `labelmap` command
+    labels: BTreeMap<(&'static str, String), String>,
+    pair_style: Option<String>,
+    bond_style: Option<String>,
+    angle_style: Option<String>,
+    global: Table,
+    pairs: Table,
+    bonds: Table,
+    angles: Table,
+    charges: Table,
+}
+
+/// Create a TOML string value from a `value` and an `unit`
+fn with_unit(value: f64, unit: &str) -> Value {
+    Value::String(format!("{} {}", value, unit))
+}
+
+fn parse_number(value: &str, command: &str) -> Result<f64, Error> {
+    value.parse::<f64>().map_err(|_| Error::from(format!(
+        "expected a number in LAMMPS '{}' command, got '{}'", command, value
+    )))
+}
+
+impl LammpsImporter {
+    fn new() -> LammpsImporter {
+        LammpsImporter {
+            units: LammpsUnits::Real,
+            labels: BTreeMap::new(),
+            pair_style: None,
+            bond_style: None,
+            angle_style: None,
+            global: Table::new(),
+            pairs: Table::new(),
+            bonds: Table::new(),
+            angles: Table::new(),
+            charges: Table::new(),
+        }
+    }
+
+    /// Get the name of the atom, bond or angle (depending on `kind`) type
+    /// `label`, using the labels from `labelmap` for numeric types.
+    fn label(&self, kind: &'static str, label: &str) -> Result<String, Error> {
+        if let Some(name) = self.labels.get(&(kind, label.into())) {
+            return Ok(name.clone());
+        }
+        if label.parse::<usize>().is_ok() {
+            return Err(Error::from(format!(
+                "missing name for LAMMPS {} type {}, use the 'labelmap' command to name it", kind, label
+            )));
+        }
+        return Ok(label.into());
+    }
+
+    fn command(&mut self, words: &[&str]) -> Result<(), Error> {
+        let command = words[0];
+        let args = &words[1..];
+        match command {
+            "units" => {
+                self.units = match args {
+                    ["real"] => LammpsUnits::Real,
+                    ["metal"] => LammpsUnits::Metal,
+                    _ => return Err(Error::from(format!(
+                        "unsupported LAMMPS units '{}', only 'real' and 'metal' are supported", args.join(" ")
+                    ))),
+                };
+            }
+            "labelmap" => {
+                let kind = match args.first() {
+                    Some(&"atom") => "atom",
+                    Some(&"bond") => "bond",
+                    Some(&"angle") => "angle",
+                    _ => return Err(Error::from("LAMMPS 'labelmap' must be used with atom, bond or angle types")),
+                };
+                if args.len() % 2 != 1 {
+                    return Err(Error::from("expected pairs of type and label in LAMMPS 'labelmap' command"));
+                }
+                for pair in args[1..].chunks(2) {
+                    let _ = self.labels.insert((kind, pair[0].into()), pair[1].into());
+                }
+            }
+            "pair_style" => {
+                match args {
+                    ["lj/cut", cutoff] => {
+                        let cutoff = parse_number(cutoff, command)?;
+                        let _ = self.global.insert("cutoff".into(), with_unit(cutoff, "A"));
+                    }
+                    _ => return Err(Error::from(format!(
+                        "unsupported LAMMPS pair style '{}', only 'lj/cut' is supported", args.join(" ")
+                    ))),
+                }
+                self.pair_style = Some(args[0].into());
+            }
+            "pair_modify" => {
+                for option in args.chunks(2) {
+                    match option {
+                        ["shift", "yes"] => {
+                            let cutoff = self.global.remove("cutoff").ok_or(
+                                Error::from("LAMMPS 'pair_modify' must be used after 'pair_style'")
+                            )?;
+                            let mut shifted = Table::new();
+                            let _ = shifted.insert("shifted".into(), cutoff);
+                            let _ = self.global.insert("cutoff".into(), Value::Table(shifted));
+                        }
+                        ["tail", "yes"] => {
+                            let _ = self.global.insert("tail_correction".into(), Value::Boolean(true));
+                        }
+                        ["shift", "no"] | ["tail", "no"] => {}
+                        _ => return Err(Error::from(format!(
+                            "unsupported LAMMPS pair_modify option '{}'", option.join(" ")
+                        ))),
+                    }
+                }
+            }
+            "pair_coeff" => {
+                if self.pair_style.is_none() {
+                    return Err(Error::from("LAMMPS 'pair_coeff' must be used after 'pair_style'"));
+                }
+                if args.len() != 4 && args.len() != 5 {
+                    return Err(Error::from("expected 'pair_coeff I J epsilon sigma [cutoff]' in LAMMPS input"));
+                }
+                let i = self.label("atom", args[0])?;
+                let j = self.label("atom", args[1])?;
+                let mut pair = Table::new();
+                let _ = pair.insert("type".into(), Value::String("lj".into()));
+                let _ = pair.insert("epsilon".into(), with_unit(parse_number(args[2], command)?, self.units.energy()));
+                let _ = pair.insert("sigma".into(), with_unit(parse_number(args[3], command)?, "A"));
+                if let Some(cutoff) = args.get(4) {
+                    let _ = pair.insert("cutoff".into(), with_unit(parse_number(cutoff, command)?, "A"));
+                }
+                let _ = self.pairs.insert(format!("{}-{}", i, j), Value::Table(pair));
+            }
+            "bond_style" => {
+                if args != ["harmonic"] {
+                    return Err(Error::from(format!(
+                        "unsupported LAMMPS bond style '{}', only 'harmonic' is supported", args.join(" ")
+                    )));
+                }
+                self.bond_style = Some(args[0].into());
+            }
+            "bond_coeff" => {
+                if self.bond_style.is_none() {
+                    return Err(Error::from("LAMMPS 'bond_coeff' must be used after 'bond_style'"));
+                }
+                if args.len() != 3 {
+                    return Err(Error::from("expected 'bond_coeff N K r0' in LAMMPS input"));
+                }
+                // LAMMPS harmonic bonds are defined as K (r - r0)^2
+                let k = 2.0 * parse_number(args[1], command)?;
+                let mut bond = Table::new();
+                let _ = bond.insert("type".into(), Value::String("harmonic".into()));
+                let _ = bond.insert("k".into(), with_unit(k, &format!("{}/A^2", self.units.energy())));
+                let _ = bond.insert("x0".into(), with_unit(parse_number(args[2], command)?, "A"));
+                let name = self.label("bond", args[0])?;
+                let _ = self.bonds.insert(name, Value::Table(bond));
+            }
+            "angle_style" => {
+                if args != ["harmonic"] {
+                    return Err(Error::from(format!(
+                        "unsupported LAMMPS angle style '{}', only 'harmonic' is supported", args.join(" ")
+                    )));
+                }
+                self.angle_style = Some(args[0].into());
+            }
+            "angle_coeff" => {
+                if self.angle_style.is_none() {
+                    return Err(Error::from("LAMMPS 'angle_coeff' must be used after 'angle_style'"));
+                }
+                if args.len() != 3 {
+                    return Err(Error::from("expected 'angle_coeff N K theta0' in LAMMPS input"));
+                }
+                // LAMMPS harmonic angles are defined as K (theta - theta0)^2
+                let k = 2.0 * parse_number(args[1], command)?;
+                let mut angle = Table::new();
+                let _ = angle.insert("type".into(), Value::String("harmonic".into()));
+                let _ = angle.insert("k".into(), with_unit(k, &format!("{}/rad^2", self.units.energy())));
+                let _ = angle.insert("x0".into(), with_unit(parse_number(args[2], command)?, "deg"));
+                let name = self.label("angle", args[0])?;
+                let _ = self.angles.insert(name, Value::Table(angle));
+            }
+            "set" => {
+                match args {
+                    ["type", atom, "charge", charge] => {
+                        let charge = parse_number(charge, command)?;
+                        let name = self.label("atom", atom)?;
+                        let _ = self.charges.insert(name, Value::Float(charge));
+                    }
+                    _ => return Err(Error::from("only 'set type T charge q' is supported in LAMMPS input")),
+                }
+            }
+            other => warn!("ignoring unsupported LAMMPS command '{}'", other),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Table {
+        let mut config = Table::new();
+        insert_section(&mut config, "global", self.global);
+        insert_section(&mut config, "pairs", self.pairs);
+        insert_section(&mut config, "bonds", self.bonds);
+        insert_section(&mut config, "angles", self.angles);
+        insert_section(&mut config, "charges", self.charges);
+        return config;
+    }
+}
+
+/// Insert the `section` in the `config` with the given `name`, if it is not
+/// empty.
+fn insert_section(config: &mut Table, name: &str, section: Table) {
+    if !section.is_empty() {
+        let _ = config.insert(name.into(), Value::Table(section));
+    }
+}
+
+impl InteractionsInput {
+    /// Read the interactions from a LAMMPS input script.
+    ///
+    /// Only a subset of LAMMPS commands is supported: `units` (`real` or
+    /// `metal`), `labelmap` to name the numeric types, `pair_style lj/cut`
+    /// with `pair_coeff` and `pair_modify shift/tail`, `bond_style harmonic`
+    /// with `bond_coeff`, `angle_style harmonic` with `angle_coeff`, and `set
+    /// type T charge q`. Other commands are ignored with a warning.
+    ///
+    /// Bonds and angles types must be named after the atoms they contain,
+    /// for example `O-H` or `H-O-H`, either directly in the coefficients
+    /// commands or with `labelmap`.
+    pub fn from_lammps(content: &str) -> Result<InteractionsInput, Error> {
+        let mut importer = LammpsImporter::new();
+        for line in content.lines() {
+            let line = line.split('#').next().expect("split always returns a value");
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            if words.is_empty() {
+                continue;
+            }
+            importer.command(&words)?;
+        }
+        return Ok(InteractionsInput::from_toml(importer.finish()));
+    }
+}
@@ -17,6 +17,7 @@ mod potentials;
 mod pairs;
 mod angles;
 mod coulomb;
+mod lammps;
 
 /// Input file for reading interactions
 pub struct InteractionsInput {
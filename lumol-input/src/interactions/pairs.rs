@@ -7,7 +7,7 @@ use lumol_core::units;
 
 use lumol_core::energy::{BondPotential, PairInteraction, PairPotential};
 use lumol_core::energy::{BornMayerHuggins, Buckingham, Gaussian, Morse};
-use lumol_core::energy::{Harmonic, LennardJones, NullPotential, Mie};
+use lumol_core::energy::{Harmonic, LennardJones, LennardJones1264, NullPotential, Mie, SoftCoreLennardJones};
 use lumol_core::energy::TableComputation;
 
 use super::read_restriction;
@@ -135,7 +135,7 @@ impl InteractionsInput {
                 interaction.set_restriction(restriction);
             }
 
-            system.set_pair_potential((atoms[0], atoms[1]), interaction);
+            system.set_pair_potential((atoms[0], atoms[1]), interaction)?;
         }
         Ok(())
     }
@@ -182,6 +182,8 @@ fn read_pair_potential(table: &Table) -> Result<Box<dyn PairPotential>, Error> {
         "morse" => Ok(Box::new(Morse::from_toml(table)?)),
         "gaussian" => Ok(Box::new(Gaussian::from_toml(table)?)),
         "mie" => Ok(Box::new(Mie::from_toml(table)?)),
+        "soft-core-lj" => Ok(Box::new(SoftCoreLennardJones::from_toml(table)?)),
+        "lj-12-6-4" => Ok(Box::new(LennardJones1264::from_toml(table)?)),
         other => Err(Error::from(format!("unknown potential type '{}'", other))),
     }
 }
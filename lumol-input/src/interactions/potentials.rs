@@ -41,6 +41,24 @@ impl FromToml for LennardJones {
     }
 }
 
+impl FromToml for LennardJones1264 {
+    fn from_toml(table: &Table) -> Result<LennardJones1264, Error> {
+        let sigma = extract::str("sigma", table, "12-6-4 Lennard-Jones potential")?;
+        let epsilon = extract::str("epsilon", table, "12-6-4 Lennard-Jones potential")?;
+        let c4 = extract::str("c4", table, "12-6-4 Lennard-Jones potential")?;
+        let c4 = units::from_str(c4)?;
+        if c4 < 0.0 {
+            return Err(Error::from("'c4' must be positive in 12-6-4 Lennard-Jones potential"));
+        }
+
+        Ok(LennardJones1264 {
+            sigma: units::from_str(sigma)?,
+            epsilon: units::from_str(epsilon)?,
+            c4: c4,
+        })
+    }
+}
+
 impl FromToml for Mie {
     fn from_toml(table: &Table) -> Result<Mie, Error> {
         let sigma = extract::str("sigma", table, "Mie potential")?;
@@ -61,6 +79,28 @@ impl FromToml for Mie {
     }
 }
 
+impl FromToml for SoftCoreLennardJones {
+    fn from_toml(table: &Table) -> Result<SoftCoreLennardJones, Error> {
+        let sigma = extract::str("sigma", table, "soft-core Lennard-Jones potential")?;
+        let epsilon = extract::str("epsilon", table, "soft-core Lennard-Jones potential")?;
+        let alpha = if table.get("alpha").is_some() {
+            extract::number("alpha", table, "soft-core Lennard-Jones potential")?
+        } else {
+            0.5
+        };
+
+        if alpha < 0.0 {
+            return Err(Error::from("'alpha' must be positive in soft-core Lennard-Jones potential"));
+        }
+
+        Ok(SoftCoreLennardJones::new(
+            units::from_str(sigma)?,
+            units::from_str(epsilon)?,
+            alpha,
+        ))
+    }
+}
+
 impl FromToml for CosineHarmonic {
     fn from_toml(table: &Table) -> Result<CosineHarmonic, Error> {
         let k = extract::str("k", table, "cosine harmonic potential")?;
@@ -71,7 +111,13 @@ impl FromToml for CosineHarmonic {
 
 impl FromToml for Torsion {
     fn from_toml(table: &Table) -> Result<Torsion, Error> {
+        if let Some(&toml::Value::Float(_)) = table.get("n") {
+            return Err(Error::from("'n' must be an integer multiplicity in torsion potential"));
+        }
         let n = extract::uint("n", table, "torsion potential")?;
+        if n == 0 {
+            return Err(Error::from("'n' can not be zero in torsion potential"));
+        }
         let k = extract::str("k", table, "torsion potential")?;
         let delta = extract::str("delta", table, "torsion potential")?;
         Ok(Torsion {
@@ -82,6 +128,17 @@ impl FromToml for Torsion {
     }
 }
 
+impl FromToml for HarmonicDihedral {
+    fn from_toml(table: &Table) -> Result<HarmonicDihedral, Error> {
+        let k = extract::str("k", table, "harmonic dihedral potential")?;
+        let phi0 = extract::str("phi0", table, "harmonic dihedral potential")?;
+        Ok(HarmonicDihedral {
+            k: units::from_str(k)?,
+            phi0: units::from_str(phi0)?,
+        })
+    }
+}
+
 impl FromToml for Buckingham {
     fn from_toml(table: &Table) -> Result<Buckingham, Error> {
         let a = extract::str("A", table, "Buckingham potential")?;
@@ -161,6 +218,13 @@ impl FromToml for Wolf {
     }
 }
 
+impl FromToml for TruncatedCoulomb {
+    fn from_toml(table: &Table) -> Result<TruncatedCoulomb, Error> {
+        let cutoff = extract::str("cutoff", table, "truncated coulombic potential")?;
+        Ok(TruncatedCoulomb::new(units::from_str(cutoff)?))
+    }
+}
+
 impl FromTomlWithRefData for Ewald {
     type Data = Configuration;
 
@@ -176,6 +240,7 @@ impl FromTomlWithRefData for Ewald {
                 ));
             }
             let accuracy = extract::number("accuracy", table, "Ewald coulombic potential")?;
+            check_periodic_cell(configuration)?;
             return Ok(Ewald::with_accuracy(cutoff, accuracy, configuration));
         }
 
@@ -187,6 +252,15 @@ impl FromTomlWithRefData for Ewald {
         } else {
             None
         };
+        check_periodic_cell(configuration)?;
         Ok(Ewald::new(cutoff, kmax as usize, alpha))
     }
 }
+
+/// Ewald summation requires periodic boundary conditions
+fn check_periodic_cell(configuration: &Configuration) -> Result<(), Error> {
+    if configuration.cell.is_infinite() {
+        return Err(Error::from("Ewald coulombic potential can not be used with an infinite cell"));
+    }
+    Ok(())
+}
@@ -6,18 +6,18 @@ use std::path::PathBuf;
 use toml::value::Table;
 
 use lumol_sim::mc::*;
-use lumol_core::read_molecule;
-use lumol_core::units;
+use lumol_core::{read_molecule, System};
+use lumol_core::units::{self, UnitSystem};
 
 use crate::{Error, FromTomlWithData};
 use crate::extract;
 use crate::simulations::get_input_path;
 
 impl FromTomlWithData for MonteCarlo {
-    type Data = PathBuf;
-    fn from_toml(config: &Table, root: PathBuf) -> Result<MonteCarlo, Error> {
+    type Data = (PathBuf, UnitSystem);
+    fn from_toml(config: &Table, (root, units): (PathBuf, UnitSystem)) -> Result<MonteCarlo, Error> {
         let temperature = extract::str("temperature", config, "Monte Carlo propagator")?;
-        let temperature = units::from_str(temperature)?;
+        let temperature = units.from_str(temperature, "K")?;
         let has_update_frequency = config.get("update_frequency").is_some();
 
         let mut builder = MonteCarloBuilder::new(temperature);
@@ -32,6 +32,9 @@ impl FromTomlWithData for MonteCarlo {
             } else {
                 1.0
             };
+            if frequency < 0.0 {
+                return Err(Error::from("'frequency' must be positive in Monte Carlo move"));
+            }
 
             let target_acceptance = if mc_move.get("target_acceptance").is_some() {
                 Some(extract::number("target_acceptance", mc_move, "Monte Carlo move")?)
@@ -43,6 +46,7 @@ impl FromTomlWithData for MonteCarlo {
                 "Translate" => Box::new(Translate::from_toml(mc_move, root.clone())?),
                 "Rotate" => Box::new(Rotate::from_toml(mc_move, root.clone())?),
                 "Resize" => Box::new(Resize::from_toml(mc_move, root.clone())?),
+                "DihedralRotation" => Box::new(DihedralRotation::from_toml(mc_move, root.clone())?),
                 other => return Err(Error::from(format!("unknown Monte Carlo move '{}'", other))),
             };
 
@@ -70,6 +74,20 @@ impl FromTomlWithData for MonteCarlo {
             mc.set_amplitude_update_frequency(update_frequency);
         }
 
+        if config.get("report_frequency").is_some() {
+            let report_frequency = extract::uint("report_frequency", config, "Monte Carlo propagator")?;
+            mc.set_acceptance_report_frequency(report_frequency);
+        }
+
+        if config.get("hard_core").is_some() {
+            let hard_core = extract::str("hard_core", config, "Monte Carlo propagator")?;
+            let hard_core = units.from_str(hard_core, "A")?;
+            if hard_core < 0.0 {
+                return Err(Error::from("'hard_core' must be positive in Monte Carlo propagator"));
+            }
+            mc.set_hard_core(hard_core);
+        }
+
         return Ok(mc);
     }
 }
@@ -108,6 +126,23 @@ impl FromTomlWithData for Rotate {
     }
 }
 
+impl FromTomlWithData for DihedralRotation {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<DihedralRotation, Error> {
+        let delta = extract::str("delta", config, "DihedralRotation move")?;
+        let delta = units::from_str(delta)?;
+
+        if config.get("molecule").is_some() {
+            let molfile = extract::str("molecule", config, "DihedralRotation move")?;
+            let molfile = get_input_path(root, molfile);
+            let hash = read_molecule(molfile)?.as_ref().hash();
+            Ok(DihedralRotation::new(delta, hash))
+        } else {
+            Ok(DihedralRotation::new(delta, None))
+        }
+    }
+}
+
 impl FromTomlWithData for Resize {
     type Data = PathBuf;
     fn from_toml(config: &Table, _: PathBuf) -> Result<Resize, Error> {
@@ -117,6 +152,65 @@ impl FromTomlWithData for Resize {
         let delta = extract::str("delta", config, "Resize move")?;
         let delta = units::from_str(delta)?;
 
-        Ok(Resize::new(pressure, delta))
+        let mode = if config.get("mode").is_some() {
+            match extract::str("mode", config, "Resize move")? {
+                "isotropic" => ResizeMode::Isotropic,
+                "semi-isotropic" => ResizeMode::SemiIsotropic,
+                "anisotropic" => ResizeMode::Anisotropic,
+                "triclinic" => ResizeMode::Triclinic,
+                other => return Err(Error::from(format!("unknown Resize move mode '{}'", other))),
+            }
+        } else {
+            ResizeMode::Isotropic
+        };
+
+        Ok(Resize::with_mode(pressure, delta, mode))
+    }
+}
+
+impl FromTomlWithData for Gibbs {
+    type Data = (System, UnitSystem);
+    fn from_toml(config: &Table, (other, units): (System, UnitSystem)) -> Result<Gibbs, Error> {
+        let temperature = extract::str("temperature", config, "Gibbs propagator")?;
+        let temperature = units.from_str(temperature, "K")?;
+        if temperature <= 0.0 {
+            return Err(Error::from("'temperature' must be positive in Gibbs propagator"));
+        }
+
+        let mut gibbs = Gibbs::new(temperature, other);
+
+        if config.get("delta").is_some() {
+            let delta = extract::str("delta", config, "Gibbs propagator")?;
+            let delta = units.from_str(delta, "A")?;
+            if delta <= 0.0 {
+                return Err(Error::from("'delta' must be positive in Gibbs propagator"));
+            }
+            gibbs.set_translation_amplitude(delta);
+        }
+
+        if config.get("volume_delta").is_some() {
+            let delta = extract::number("volume_delta", config, "Gibbs propagator")?;
+            if delta <= 0.0 {
+                return Err(Error::from("'volume_delta' must be positive in Gibbs propagator"));
+            }
+            gibbs.set_volume_amplitude(delta);
+        }
+
+        if config.get("frequencies").is_some() {
+            let frequencies = extract::table("frequencies", config, "Gibbs propagator")?;
+            let mut values = [0.0; 3];
+            for (value, key) in values.iter_mut().zip(&["translate", "volume", "transfer"]) {
+                *value = extract::number(key, frequencies, "Gibbs propagator frequencies")?;
+                if *value < 0.0 {
+                    return Err(Error::from(format!("'{}' frequency must be positive in Gibbs propagator", key)));
+                }
+            }
+            if values.iter().sum::<f64>() == 0.0 {
+                return Err(Error::from("at least one frequency must be non-zero in Gibbs propagator"));
+            }
+            gibbs.set_move_frequencies(values[0], values[1], values[2]);
+        }
+
+        return Ok(gibbs);
     }
 }
@@ -5,25 +5,30 @@
 use toml::value::Table;
 
 use lumol_sim::md::*;
-use lumol_core::units;
+use lumol_core::units::{self, UnitSystem};
 
 use crate::alternator::Alternator;
 use crate::{Error, FromToml, FromTomlWithData};
 use crate::extract;
 
-impl FromToml for MolecularDynamics {
-    fn from_toml(config: &Table) -> Result<MolecularDynamics, Error> {
+impl FromTomlWithData for MolecularDynamics {
+    type Data = UnitSystem;
+    fn from_toml(config: &Table, units: UnitSystem) -> Result<MolecularDynamics, Error> {
         // Get the timestep of the simulation
         let timestep = extract::str("timestep", config, "molecular dynamics propagator")?;
         let timestep = units::from_str(timestep)?;
+        if timestep <= 0.0 {
+            return Err(Error::from("'timestep' must be positive in molecular dynamics propagator"));
+        }
 
         let mut md;
+        let supports_constraints;
         if let Some(integrator) = config.get("integrator") {
             let integrator = integrator.as_table().ok_or(
                 Error::from("'integrator' must be a table in molecular dynamics")
             )?;
 
-            let integrator: Box<dyn Integrator> = match extract::typ(integrator, "integrator")? {
+            let mut integrator: Box<dyn Integrator> = match extract::typ(integrator, "integrator")? {
                 "BerendsenBarostat" => {
                     Box::new(BerendsenBarostat::from_toml(integrator, timestep)?)
                 }
@@ -33,11 +38,15 @@ impl FromToml for MolecularDynamics {
                 "Verlet" => Box::new(Verlet::from_toml(integrator, timestep)?),
                 "VelocityVerlet" => Box::new(VelocityVerlet::from_toml(integrator, timestep)?),
                 "LeapFrog" => Box::new(LeapFrog::from_toml(integrator, timestep)?),
+                "RigidBody" => Box::new(RigidBody::from_toml(integrator, timestep)?),
+                "Respa" => Box::new(Respa::from_toml(integrator, timestep)?),
                 other => return Err(Error::from(format!("unknown integrator '{}'", other))),
             };
 
+            supports_constraints = integrator.constraints_mut().is_some();
             md = MolecularDynamics::from_integrator(integrator);
         } else {
+            supports_constraints = true;
             md = MolecularDynamics::new(timestep);
         }
 
@@ -47,9 +56,9 @@ impl FromToml for MolecularDynamics {
             )?;
 
             let thermostat: Box<dyn Thermostat> = match extract::typ(thermostat, "thermostat")? {
-                "Berendsen" => Box::new(BerendsenThermostat::from_toml(thermostat)?),
-                "Rescale" => Box::new(RescaleThermostat::from_toml(thermostat)?),
-                "CSVR" => Box::new(CSVRThermostat::from_toml(thermostat)?),
+                "Berendsen" => Box::new(BerendsenThermostat::from_toml(thermostat, units)?),
+                "Rescale" => Box::new(RescaleThermostat::from_toml(thermostat, units)?),
+                "CSVR" => Box::new(CSVRThermostat::from_toml(thermostat, units)?),
                 other => return Err(Error::from(format!("unknown thermostat type '{}'", other))),
             };
             md.set_thermostat(thermostat);
@@ -79,6 +88,38 @@ impl FromToml for MolecularDynamics {
             }
         }
 
+        if config.get("frozen").is_some() {
+            let frozen = extract::slice("frozen", config, "molecular dynamics")?;
+            let indexes = frozen.iter().map(|index| {
+                index.as_integer()
+                     .filter(|&index| index >= 0)
+                     .map(|index| index as usize)
+                     .ok_or(Error::from("'frozen' must be an array of positive integers in molecular dynamics"))
+            }).collect::<Result<Vec<_>, _>>()?;
+            if !supports_constraints {
+                return Err(Error::from(
+                    "'frozen' can only be used with integrators supporting constraints in molecular dynamics"
+                ));
+            }
+            md.freeze(&indexes);
+        }
+
+        if config.get("rigid").is_some() {
+            let rigid = extract::slice("rigid", config, "molecular dynamics")?;
+            let indexes = rigid.iter().map(|index| {
+                index.as_integer()
+                     .filter(|&index| index >= 0)
+                     .map(|index| index as usize)
+                     .ok_or(Error::from("'rigid' must be an array of positive integers in molecular dynamics"))
+            }).collect::<Result<Vec<_>, _>>()?;
+            if !supports_constraints {
+                return Err(Error::from(
+                    "'rigid' can only be used with integrators supporting constraints in molecular dynamics"
+                ));
+            }
+            md.set_rigid(&indexes);
+        }
+
         Ok(md)
     }
 }
@@ -92,8 +133,31 @@ impl FromTomlWithData for Verlet {
 
 impl FromTomlWithData for VelocityVerlet {
     type Data = f64;
-    fn from_toml(_: &Table, timestep: f64) -> Result<VelocityVerlet, Error> {
-        Ok(VelocityVerlet::new(timestep))
+    fn from_toml(config: &Table, timestep: f64) -> Result<VelocityVerlet, Error> {
+        let mut integrator = VelocityVerlet::new(timestep);
+        if config.get("min_timestep").is_some() || config.get("max_force").is_some() {
+            let min = extract::str("min_timestep", config, "velocity-Verlet integrator")?;
+            let min = units::from_str(min)?;
+            let max_force = extract::str("max_force", config, "velocity-Verlet integrator")?;
+            let max_force = units::from_str(max_force)?;
+            if min <= 0.0 || min > timestep {
+                return Err(Error::from(
+                    "'min_timestep' must be positive and smaller than the timestep in velocity-Verlet integrator"
+                ));
+            }
+            if max_force <= 0.0 {
+                return Err(Error::from("'max_force' must be positive in velocity-Verlet integrator"));
+            }
+            integrator.set_adaptive_timestep(min, timestep, max_force);
+        }
+        if config.get("max_displacement").is_some() {
+            let fraction = extract::number("max_displacement", config, "velocity-Verlet integrator")?;
+            if fraction <= 0.0 {
+                return Err(Error::from("'max_displacement' must be positive in velocity-Verlet integrator"));
+            }
+            integrator.set_max_displacement(fraction);
+        }
+        Ok(integrator)
     }
 }
 
@@ -104,6 +168,24 @@ impl FromTomlWithData for LeapFrog {
     }
 }
 
+impl FromTomlWithData for RigidBody {
+    type Data = f64;
+    fn from_toml(_: &Table, timestep: f64) -> Result<RigidBody, Error> {
+        Ok(RigidBody::new(timestep))
+    }
+}
+
+impl FromTomlWithData for Respa {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<Respa, Error> {
+        let inner = extract::uint("inner", config, "RESPA integrator")?;
+        if inner == 0 {
+            return Err(Error::from("'inner' can not be 0 in RESPA integrator"));
+        }
+        Ok(Respa::new(timestep, inner as usize))
+    }
+}
+
 impl FromTomlWithData for BerendsenBarostat {
     type Data = f64;
     fn from_toml(config: &Table, timestep: f64) -> Result<BerendsenBarostat, Error> {
@@ -124,25 +206,27 @@ impl FromTomlWithData for AnisoBerendsenBarostat {
     }
 }
 
-impl FromToml for BerendsenThermostat {
-    fn from_toml(config: &Table) -> Result<BerendsenThermostat, Error> {
+impl FromTomlWithData for BerendsenThermostat {
+    type Data = UnitSystem;
+    fn from_toml(config: &Table, units: UnitSystem) -> Result<BerendsenThermostat, Error> {
         let temperature = extract::str("temperature", config, "Berendsen thermostat")?;
-        let temperature = units::from_str(temperature)?;
+        let temperature = units.from_str(temperature, "K")?;
         let tau = extract::number("timestep", config, "Berendsen thermostat")?;
         Ok(BerendsenThermostat::new(temperature, tau))
     }
 }
 
-impl FromToml for RescaleThermostat {
-    fn from_toml(config: &Table) -> Result<RescaleThermostat, Error> {
+impl FromTomlWithData for RescaleThermostat {
+    type Data = UnitSystem;
+    fn from_toml(config: &Table, units: UnitSystem) -> Result<RescaleThermostat, Error> {
         let temperature = extract::str("temperature", config, "rescale thermostat")?;
-        let temperature = units::from_str(temperature)?;
+        let temperature = units.from_str(temperature, "K")?;
 
         if let Some(tolerance) = config.get("tolerance") {
             let tolerance = tolerance.as_str().ok_or(
                 Error::from("'tolerance' must be a string rescale thermostat")
             )?;
-            let tolerance = units::from_str(tolerance)?;
+            let tolerance = units.from_str(tolerance, "K")?;
 
             Ok(RescaleThermostat::with_tolerance(temperature, tolerance))
         } else {
@@ -151,10 +235,11 @@ impl FromToml for RescaleThermostat {
     }
 }
 
-impl FromToml for CSVRThermostat {
-    fn from_toml(config: &Table) -> Result<CSVRThermostat, Error> {
+impl FromTomlWithData for CSVRThermostat {
+    type Data = UnitSystem;
+    fn from_toml(config: &Table, units: UnitSystem) -> Result<CSVRThermostat, Error> {
         let temperature = extract::str("temperature", config, "CSVR thermostat")?;
-        let temperature = units::from_str(temperature)?;
+        let temperature = units.from_str(temperature, "K")?;
         let tau = extract::number("timestep", config, "CSVR thermostat")?;
         Ok(CSVRThermostat::new(temperature, tau))
     }
@@ -4,6 +4,8 @@
 
 use toml::value::Table;
 
+use std::time::Duration;
+
 use lumol_sim::min::*;
 use lumol_core::units;
 
@@ -30,7 +32,25 @@ impl FromToml for Minimization {
                 force2: units::from(1e-5, "kJ^2/mol^2/A^2").expect("bad unit"),
             }
         };
-        Ok(Minimization::new(minimizer, tolerance))
+        let mut minimization = Minimization::new(minimizer, tolerance);
+
+        if config.get("max_iterations").is_some() {
+            let max_iterations = extract::uint("max_iterations", config, "minimization propagator")?;
+            if max_iterations == 0 {
+                return Err(Error::from("'max_iterations' can not be 0 in minimization propagator"));
+            }
+            minimization.set_max_iterations(max_iterations);
+        }
+
+        if config.get("max_time").is_some() {
+            let max_time = extract::number("max_time", config, "minimization propagator")?;
+            if max_time <= 0.0 {
+                return Err(Error::from("'max_time' must be positive in minimization propagator"));
+            }
+            minimization.set_max_time(Duration::from_secs_f64(max_time));
+        }
+
+        Ok(minimization)
     }
 }
 
@@ -7,11 +7,13 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
-use lumol_sim::Simulation;
+use lumol_sim::{Simulation, ThermodynamicIntegration};
 use lumol_core::System;
+use lumol_core::units::UnitSystem;
 
 use crate::Error;
 use crate::validate;
+use crate::extract;
 
 mod logging;
 mod system;
@@ -35,6 +37,9 @@ pub struct Config {
     pub simulation: Simulation,
     /// The simulation duration
     pub nsteps: usize,
+    /// Thermodynamic integration driver, running the simulation at multiple
+    /// values of the coupling parameter instead of running it for `nsteps`
+    pub thermodynamic_integration: Option<ThermodynamicIntegration>,
 }
 
 /// An input file for Lumol.
@@ -71,13 +76,30 @@ impl Input {
         let system = self.read_system()?;
         let simulation = self.read_simulation()?;
         let nsteps = self.read_nsteps()?;
+        let thermodynamic_integration = self.read_thermodynamic_integration()?;
 
         Ok(Config {
             system: system,
             simulation: simulation,
             nsteps: nsteps,
+            thermodynamic_integration: thermodynamic_integration,
         })
     }
+
+    /// Get the unit system used in this input file, from the `units` key in
+    /// the `input` table. Physical units are used by default.
+    pub(crate) fn read_unit_system(&self) -> Result<UnitSystem, Error> {
+        let input = extract::table("input", &self.config, "input file")?;
+        if input.get("units").is_none() {
+            return Ok(UnitSystem::Physical);
+        }
+
+        match extract::str("units", input, "input")? {
+            "physical" => Ok(UnitSystem::Physical),
+            "reduced" => Ok(UnitSystem::Reduced),
+            other => Err(Error::from(format!("unknown unit system '{}'", other))),
+        }
+    }
 }
 
 fn get_input_path<P1: AsRef<Path>, P2: AsRef<Path>>(root: P1, path: P2) -> PathBuf {
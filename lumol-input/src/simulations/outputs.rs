@@ -3,9 +3,10 @@
 use std::path::PathBuf;
 use toml::value::Table;
 
-use lumol_sim::output::Output;
+use lumol_sim::output::{FileMode, NumberFormat, Output};
 use lumol_sim::output::{TrajectoryOutput, PropertiesOutput, EnergyOutput};
 use lumol_sim::output::{ForcesOutput, CellOutput, CustomOutput, StressOutput};
+use lumol_sim::output::{MomentumOutput, ProgressOutput, WidomInsertion};
 
 use crate::{Input, FromToml, Error};
 use crate::extract;
@@ -44,7 +45,10 @@ impl Input {
                     "stress" => Box::new(StressOutput::from_toml(output)?),
                     "forces" => Box::new(ForcesOutput::from_toml(output)?),
                     "cell" => Box::new(CellOutput::from_toml(output)?),
+                    "momentum" => Box::new(MomentumOutput::from_toml(output)?),
                     "custom" => Box::new(CustomOutput::from_toml(output)?),
+                    "widom" => Box::new(WidomInsertion::from_toml(output)?),
+                    "progress" => Box::new(ProgressOutput::new(self.read_nsteps()? as u64)),
                     other => return Err(Error::from(format!("unknown output type '{}'", other))),
                 };
 
@@ -65,10 +69,70 @@ fn get_file(config: &Table) -> Result<&str, Error> {
     file.as_str().ok_or(Error::from("'file' must be a string in output"))
 }
 
+fn get_mode(config: &Table) -> Result<FileMode, Error> {
+    match config.get("mode") {
+        Some(mode) => {
+            let mode = mode.as_str().ok_or(
+                Error::from("'mode' must be a string in output")
+            )?;
+            match &*mode.to_lowercase() {
+                "auto" => Ok(FileMode::Auto),
+                "truncate" => Ok(FileMode::Truncate),
+                "append" => Ok(FileMode::Append),
+                other => Err(Error::from(format!("unknown output mode '{}', expected 'auto', 'truncate' or 'append'", other))),
+            }
+        }
+        None => Ok(FileMode::Auto),
+    }
+}
+
+fn get_number_format(config: &Table) -> Result<NumberFormat, Error> {
+    let precision = match config.get("precision") {
+        Some(precision) => {
+            let precision = precision.as_integer().ok_or(
+                Error::from("'precision' must be a positive integer in output")
+            )?;
+            if precision < 0 {
+                return Err(Error::from("'precision' must be a positive integer in output"));
+            }
+            Some(precision as usize)
+        }
+        None => None,
+    };
+
+    let format = match config.get("format") {
+        Some(format) => format.as_str().ok_or(Error::from("'format' must be a string in output"))?,
+        None => {
+            return Ok(precision.map_or(NumberFormat::Default, NumberFormat::Fixed));
+        }
+    };
+
+    match &*format.to_lowercase() {
+        "default" => {
+            if precision.is_some() {
+                return Err(Error::from("'precision' can not be used with the default format in output"));
+            }
+            Ok(NumberFormat::Default)
+        }
+        "fixed" => {
+            let precision = precision.ok_or(Error::from("missing 'precision' key in output"))?;
+            Ok(NumberFormat::Fixed(precision))
+        }
+        "scientific" => {
+            let precision = precision.ok_or(Error::from("missing 'precision' key in output"))?;
+            Ok(NumberFormat::Scientific(precision))
+        }
+        other => Err(Error::from(format!(
+            "unknown output format '{}', expected 'default', 'fixed' or 'scientific'", other
+        ))),
+    }
+}
+
 impl FromToml for TrajectoryOutput {
     fn from_toml(config: &Table) -> Result<TrajectoryOutput, Error> {
         let path = get_file(config)?;
-        let output = TrajectoryOutput::new(path)?;
+        let mode = get_mode(config)?;
+        let output = TrajectoryOutput::with_mode(path, mode)?;
         Ok(output)
     }
 }
@@ -76,7 +140,17 @@ impl FromToml for TrajectoryOutput {
 impl FromToml for CellOutput {
     fn from_toml(config: &Table) -> Result<CellOutput, Error> {
         let path = get_file(config)?;
-        let output = try_io!(CellOutput::new(path), PathBuf::from(path));
+        let mode = get_mode(config)?;
+        let output = try_io!(CellOutput::with_mode(path, mode), PathBuf::from(path));
+        Ok(output)
+    }
+}
+
+impl FromToml for MomentumOutput {
+    fn from_toml(config: &Table) -> Result<MomentumOutput, Error> {
+        let path = get_file(config)?;
+        let mode = get_mode(config)?;
+        let output = try_io!(MomentumOutput::with_mode(path, mode), PathBuf::from(path));
         Ok(output)
     }
 }
@@ -84,7 +158,10 @@ impl FromToml for CellOutput {
 impl FromToml for EnergyOutput {
     fn from_toml(config: &Table) -> Result<EnergyOutput, Error> {
         let path = get_file(config)?;
-        let output = try_io!(EnergyOutput::new(path), PathBuf::from(path));
+        let mode = get_mode(config)?;
+        let format = get_number_format(config)?;
+        let mut output = try_io!(EnergyOutput::with_mode(path, mode), PathBuf::from(path));
+        output.set_number_format(format);
         Ok(output)
     }
 }
@@ -92,7 +169,10 @@ impl FromToml for EnergyOutput {
 impl FromToml for PropertiesOutput {
     fn from_toml(config: &Table) -> Result<PropertiesOutput, Error> {
         let path = get_file(config)?;
-        let output = try_io!(PropertiesOutput::new(path), PathBuf::from(path));
+        let mode = get_mode(config)?;
+        let format = get_number_format(config)?;
+        let mut output = try_io!(PropertiesOutput::with_mode(path, mode), PathBuf::from(path));
+        output.set_number_format(format);
         Ok(output)
     }
 }
@@ -100,7 +180,8 @@ impl FromToml for PropertiesOutput {
 impl FromToml for StressOutput {
     fn from_toml(config: &Table) -> Result<StressOutput, Error> {
         let path = get_file(config)?;
-        let output = try_io!(StressOutput::new(path), PathBuf::from(path));
+        let mode = get_mode(config)?;
+        let output = try_io!(StressOutput::with_mode(path, mode), PathBuf::from(path));
         Ok(output)
     }
 }
@@ -108,7 +189,14 @@ impl FromToml for StressOutput {
 impl FromToml for ForcesOutput {
     fn from_toml(config: &Table) -> Result<ForcesOutput, Error> {
         let path = get_file(config)?;
-        let output = try_io!(ForcesOutput::new(path), PathBuf::from(path));
+        let mode = get_mode(config)?;
+        let mut output = try_io!(ForcesOutput::with_mode(path, mode), PathBuf::from(path));
+        if let Some(positions) = config.get("positions") {
+            let positions = positions.as_bool().ok_or(
+                Error::from("'positions' must be a boolean in forces output")
+            )?;
+            output.set_positions(positions);
+        }
         Ok(output)
     }
 }
@@ -116,8 +204,23 @@ impl FromToml for ForcesOutput {
 impl FromToml for CustomOutput {
     fn from_toml(config: &Table) -> Result<CustomOutput, Error> {
         let path = get_file(config)?;
+        let mode = get_mode(config)?;
         let template = extract::str("template", config, "custom output")?;
-        let output = try_io!(CustomOutput::new(path, template), PathBuf::from(path));
+        let output = try_io!(CustomOutput::with_mode(path, template, mode), PathBuf::from(path));
+        Ok(output)
+    }
+}
+
+impl FromToml for WidomInsertion {
+    fn from_toml(config: &Table) -> Result<WidomInsertion, Error> {
+        let path = get_file(config)?;
+        let particle = extract::str("particle", config, "Widom output")?;
+        let insertions = extract::uint("insertions", config, "Widom output")?;
+        if insertions == 0 {
+            return Err(Error::from("'insertions' can not be 0 in Widom output"));
+        }
+        let mode = get_mode(config)?;
+        let output = try_io!(WidomInsertion::with_mode(path, particle, insertions as usize, mode), PathBuf::from(path));
         Ok(output)
     }
 }
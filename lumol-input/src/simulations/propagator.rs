@@ -1,20 +1,66 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use lumol_sim::{Minimization, MolecularDynamics, MonteCarlo, Propagator};
+use toml::value::Table;
+
+use lumol_sim::{Gibbs, Minimization, MolecularDynamics, MonteCarlo, Propagator, SinglePoint};
 
 use crate::Input;
 use crate::{FromToml, FromTomlWithData, Error};
 use crate::extract;
 
+/// A simulation stage, with the propagator and the number of steps
+pub type Stage = (Box<dyn Propagator>, usize);
+
 impl Input {
-    /// Get the the simulation propagator.
-    pub(crate) fn read_propagator(&self) -> Result<Box<dyn Propagator>, Error> {
+    /// Get the stages of the simulation. Simulations without a `stages` array
+    /// contain a single stage, using the `propagator` table.
+    pub(crate) fn read_stages(&self) -> Result<Vec<Stage>, Error> {
+        let config = self.simulation_table()?;
+        if config.get("stages").is_none() {
+            let propagator = extract::table("propagator", config, "simulation")?;
+            return Ok(vec![(self.read_propagator(propagator)?, self.read_nsteps()?)]);
+        }
+
+        let mut stages = Vec::new();
+        for stage in self.stages_tables()? {
+            let propagator = extract::table("propagator", stage, "simulation stage")?;
+            let nsteps = extract::uint("nsteps", stage, "simulation stage")?;
+            stages.push((self.read_propagator(propagator)?, nsteps as usize));
+        }
+        return Ok(stages);
+    }
+
+    /// Get the tables in the `stages` array of the simulation.
+    pub(crate) fn stages_tables(&self) -> Result<Vec<&Table>, Error> {
         let config = self.simulation_table()?;
-        let propagator = extract::table("propagator", config, "simulation")?;
+        if config.get("propagator").is_some() || config.get("nsteps").is_some() {
+            return Err(Error::from(
+                "'propagator' and 'nsteps' can not be used together with 'stages' in simulation"
+            ));
+        }
+
+        let stages = extract::slice("stages", config, "simulation")?;
+        if stages.is_empty() {
+            return Err(Error::from("'stages' can not be empty in simulation"));
+        }
+
+        stages.iter().map(|stage| {
+            stage.as_table().ok_or(Error::from("'stages' must be an array of tables in simulation"))
+        }).collect()
+    }
+
+    /// Get the propagator defined in the `propagator` table.
+    fn read_propagator(&self, propagator: &Table) -> Result<Box<dyn Propagator>, Error> {
+        let units = self.read_unit_system()?;
         match extract::typ(propagator, "propagator")? {
-            "MolecularDynamics" => Ok(Box::new(MolecularDynamics::from_toml(propagator)?)),
-            "MonteCarlo" => Ok(Box::new(MonteCarlo::from_toml(propagator, self.path.clone())?)),
+            "MolecularDynamics" => Ok(Box::new(MolecularDynamics::from_toml(propagator, units)?)),
+            "MonteCarlo" => Ok(Box::new(MonteCarlo::from_toml(propagator, (self.path.clone(), units))?)),
+            "Gibbs" => {
+                let other = self.read_gibbs_system()?;
+                Ok(Box::new(Gibbs::from_toml(propagator, (other, units))?))
+            }
             "Minimization" => Ok(Box::new(Minimization::from_toml(propagator)?)),
+            "SinglePoint" => Ok(Box::new(SinglePoint::new())),
             other => Err(Error::from(format!("unknown propagator type '{}'", other))),
         }
     }
@@ -1,6 +1,6 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use lumol_sim::Simulation;
+use lumol_sim::{Simulation, TemperatureRamp, TemperatureStrategy, RampMode, ThermodynamicIntegration};
 use toml::value::Table;
 
 use crate::{Input, Error};
@@ -9,18 +9,68 @@ use crate::extract;
 impl Input {
     /// Get the the simulation.
     pub fn read_simulation(&self) -> Result<Simulation, Error> {
-        let propagator = self.read_propagator()?;
+        let mut stages = self.read_stages()?;
+        let ramp = self.read_temperature_ramp()?;
+        if ramp.is_some() {
+            for (propagator, _) in &stages {
+                let strategy = propagator.temperature_strategy();
+                if strategy != TemperatureStrategy::None && !propagator.can_set_temperature() {
+                    return Err(Error::from(
+                        "temperature ramp can not be used with this propagator, \
+                         molecular dynamics simulations need a thermostat"
+                    ));
+                }
+            }
+        }
+
+        // The steps of the first stage are the ones not used by the other
+        // stages in `Simulation::run`
+        let (propagator, _) = stages.remove(0);
         let mut simulation = Simulation::new(propagator);
+        for (propagator, nsteps) in stages {
+            simulation.add_stage(propagator, nsteps);
+        }
+        simulation.set_unit_system(self.read_unit_system()?);
         for (output, frequency) in self.read_outputs()? {
             simulation.add_output_with_frequency(output, frequency);
         }
 
+        if let Some(seed) = self.read_seed()? {
+            simulation.set_seed(seed);
+        }
+
+        if let Some(ramp) = ramp {
+            simulation.set_temperature_ramp(ramp);
+        }
+
         Ok(simulation)
     }
 
-    /// Get the number of steps in the simulation.
+    /// Get the total number of steps in the simulation, including all the
+    /// stages.
     pub(crate) fn read_nsteps(&self) -> Result<usize, Error> {
         let simulation = self.simulation_table()?;
+        if let Some(integration) = self.read_thermodynamic_integration()? {
+            if simulation.get("nsteps").is_some() {
+                return Err(Error::from(
+                    "'nsteps' can not be used together with 'thermodynamic_integration' in simulation"
+                ));
+            }
+            return Ok(integration.total_steps());
+        }
+
+        if simulation.get("stages").is_some() {
+            let mut nsteps = 0;
+            for stage in self.stages_tables()? {
+                nsteps += extract::uint("nsteps", stage, "simulation stage")? as usize;
+            }
+            return Ok(nsteps);
+        }
+
+        if simulation.get("nsteps").is_none() && self.is_single_point()? {
+            return Ok(1);
+        }
+
         let nsteps = simulation.get("nsteps").ok_or(
             Error::from("missing 'nsteps' key in simulation")
         )?;
@@ -32,6 +82,134 @@ impl Input {
         Ok(nsteps as usize)
     }
 
+    /// Check if the propagator of this simulation is a single point energy
+    /// evaluation, for which the number of steps is optional.
+    fn is_single_point(&self) -> Result<bool, Error> {
+        let simulation = self.simulation_table()?;
+        let typ = simulation.get("propagator")
+                            .and_then(|propagator| propagator.get("type"))
+                            .and_then(|typ| typ.as_str());
+        return Ok(typ == Some("SinglePoint"));
+    }
+
+    /// Get the master seed for the random number generators of the
+    /// simulation, if any.
+    pub(crate) fn read_seed(&self) -> Result<Option<u64>, Error> {
+        if self.config.get("simulations").is_none() {
+            return Ok(None);
+        }
+
+        let simulation = self.simulation_table()?;
+        if simulation.get("seed").is_some() {
+            let seed = extract::uint("seed", simulation, "simulation")?;
+            Ok(Some(seed))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the temperature ramp to use in the simulation, if any.
+    fn read_temperature_ramp(&self) -> Result<Option<TemperatureRamp>, Error> {
+        let simulation = self.simulation_table()?;
+        if simulation.get("temperature_ramp").is_none() {
+            return Ok(None);
+        }
+
+        let units = self.read_unit_system()?;
+        let config = extract::table("temperature_ramp", simulation, "simulation")?;
+        let start = units.from_str(extract::str("start", config, "temperature ramp")?, "K")?;
+        let end = units.from_str(extract::str("end", config, "temperature ramp")?, "K")?;
+
+        let steps = extract::uint("steps", config, "temperature ramp")?;
+        if steps == 0 {
+            return Err(Error::from("'steps' can not be 0 in temperature ramp"));
+        }
+
+        let mode = if config.get("mode").is_some() {
+            match extract::str("mode", config, "temperature ramp")? {
+                "linear" => RampMode::Linear,
+                "exponential" => RampMode::Exponential,
+                other => return Err(Error::from(format!("unknown temperature ramp mode '{}'", other))),
+            }
+        } else {
+            RampMode::Linear
+        };
+
+        if start < 0.0 || end < 0.0 {
+            return Err(Error::from("temperatures must be positive in temperature ramp"));
+        }
+
+        if mode == RampMode::Exponential && (start == 0.0 || end == 0.0) {
+            return Err(Error::from("temperatures can not be zero in exponential temperature ramp"));
+        }
+
+        Ok(Some(TemperatureRamp::new(start, end, steps, mode)))
+    }
+
+    /// Get the thermodynamic integration driver to use with the simulation,
+    /// if any.
+    pub(crate) fn read_thermodynamic_integration(&self) -> Result<Option<ThermodynamicIntegration>, Error> {
+        let simulation = self.simulation_table()?;
+        if simulation.get("thermodynamic_integration").is_none() {
+            return Ok(None);
+        }
+
+        if simulation.get("stages").is_some() {
+            return Err(Error::from(
+                "'thermodynamic_integration' can not be used together with 'stages' in simulation"
+            ));
+        }
+        if simulation.get("temperature_ramp").is_some() {
+            return Err(Error::from(
+                "'thermodynamic_integration' can not be used together with 'temperature_ramp' in simulation"
+            ));
+        }
+
+        let config = extract::table("thermodynamic_integration", simulation, "simulation")?;
+        let mut lambdas = Vec::new();
+        for lambda in extract::slice("lambdas", config, "thermodynamic integration")? {
+            let lambda = match *lambda {
+                toml::Value::Integer(value) => value as f64,
+                toml::Value::Float(value) => value,
+                _ => return Err(Error::from("'lambdas' must be an array of numbers in thermodynamic integration")),
+            };
+            if !(0.0..=1.0).contains(&lambda) {
+                return Err(Error::from("'lambdas' values must be between 0 and 1 in thermodynamic integration"));
+            }
+            lambdas.push(lambda);
+        }
+
+        if lambdas.len() < 2 {
+            return Err(Error::from("'lambdas' must contain at least two values in thermodynamic integration"));
+        }
+        if lambdas.windows(2).any(|window| window[0] >= window[1]) {
+            return Err(Error::from("'lambdas' must be sorted in increasing order in thermodynamic integration"));
+        }
+
+        let steps = extract::uint("steps", config, "thermodynamic integration")? as usize;
+        if steps == 0 {
+            return Err(Error::from("'steps' can not be 0 in thermodynamic integration"));
+        }
+
+        let mut integration = ThermodynamicIntegration::new(lambdas, steps);
+        if config.get("equilibration").is_some() {
+            let equilibration = extract::uint("equilibration", config, "thermodynamic integration")?;
+            integration.set_equilibration(equilibration as usize);
+        }
+
+        if config.get("frequency").is_some() {
+            let frequency = extract::uint("frequency", config, "thermodynamic integration")? as usize;
+            if frequency == 0 || frequency > steps {
+                return Err(Error::from(
+                    "'frequency' must be between 1 and 'steps' in thermodynamic integration"
+                ));
+            }
+            integration.set_frequency(frequency);
+        }
+
+        Ok(Some(integration))
+    }
+
     /// Get the simulation TOML table.
     pub(crate) fn simulation_table(&self) -> Result<&Table, Error> {
         let simulations = extract::slice("simulations", &self.config, "input file")?;
@@ -2,58 +2,180 @@
 // Copyright (C) Lumol's contributors — BSD license
 use toml::value::{Table, Value};
 
-use lumol_core::{System, UnitCell, TrajectoryBuilder};
-use lumol_sim::{BoltzmannVelocities, InitVelocities};
+use lumol_core::{System, UnitCell, Trajectory, TrajectoryBuilder};
+use lumol_sim::{BoltzmannVelocities, InitVelocities, SeedSequence};
 use lumol_core::units;
 
-use log::warn;
+use log::{info, warn};
 
 use crate::{Input, InteractionsInput, Error};
 use crate::extract;
 use crate::simulations::get_input_path;
 
+/// Unit cell requested in the `cell` key of the system
+enum InputCell {
+    /// No `cell` key, the cell from the system file is used if any
+    Missing,
+    /// `cell = "file"`, explicitly use the cell from the system file
+    FromFile,
+    /// Use this cell instead of the one from the system file
+    Cell(UnitCell),
+}
+
 impl Input {
     /// Get the the simulated system.
     pub fn read_system(&self) -> Result<System, Error> {
+        let (mut trajectory, cell) = self.open_trajectory()?;
+        let system = self.read_frame(&mut trajectory)?;
+        return self.setup_system(system, cell);
+    }
+
+    /// Get one simulated system for each frame in the system file. The
+    /// interactions, masses and topology are set up once with the first
+    /// frame, and shared by all the systems. Each system then uses the
+    /// positions, velocities and unit cell of the corresponding frame.
+    pub fn read_systems(&self) -> Result<Vec<System>, Error> {
+        let (mut trajectory, cell) = self.open_trajectory()?;
+        let nsteps = trajectory.nsteps()?;
+
+        let first = self.read_frame(&mut trajectory)?;
+        let first = self.setup_system(first, cell)?;
+
+        let mut systems = Vec::with_capacity(nsteps as usize);
+        for step in 1..nsteps {
+            let frame = self.read_frame(&mut trajectory)?;
+            if frame.size() != first.size() {
+                return Err(Error::from(format!(
+                    "frame {} contains {} particles, but the first frame contains {} particles",
+                    step, frame.size(), first.size()
+                )));
+            }
+            self.check_overlaps(&frame)?;
+
+            let mut system = first.clone();
+            system.cell = frame.cell;
+            for (position, &new_position) in system.particles_mut().position.iter_mut().zip(frame.particles().position) {
+                *position = new_position;
+            }
+            for (velocity, &new_velocity) in system.particles_mut().velocity.iter_mut().zip(frame.particles().velocity) {
+                *velocity = new_velocity;
+            }
+            self.init_velocities(&mut system)?;
+            systems.push(system);
+        }
+        systems.insert(0, first);
+
+        Ok(systems)
+    }
+
+    /// Get the second simulation box of a Gibbs ensemble simulation, from
+    /// the second table in the `systems` array.
+    pub(crate) fn read_gibbs_system(&self) -> Result<System, Error> {
+        let systems = extract::slice("systems", &self.config, "input file")?;
+        if systems.len() != 2 {
+            return Err(Error::from("Gibbs ensemble Monte Carlo requires exactly two systems in input file"));
+        }
+
+        let mut config = self.config.clone();
+        let _ = config.remove("simulations");
+        let _ = config.insert("systems".into(), Value::Array(vec![systems[1].clone()]));
+        let input = Input {
+            path: self.path.clone(),
+            config: config,
+        };
+        return input.read_system();
+    }
+
+    /// Check if the simulation uses the Gibbs ensemble propagator, in the
+    /// `propagator` table or in one of the stages, which needs two systems.
+    /// Any error in the simulation is reported later.
+    fn is_gibbs(&self) -> bool {
+        let simulation = match self.simulation_table() {
+            Ok(simulation) => simulation,
+            Err(_) => return false,
+        };
+
+        let uses_gibbs = |table: &Table| {
+            table.get("propagator")
+                 .and_then(|propagator| propagator.get("type"))
+                 .and_then(|typ| typ.as_str()) == Some("Gibbs")
+        };
+
+        if uses_gibbs(simulation) {
+            return true;
+        }
+
+        match simulation.get("stages").and_then(|stages| stages.as_array()) {
+            Some(stages) => stages.iter().filter_map(|stage| stage.as_table()).any(uses_gibbs),
+            None => false,
+        }
+    }
+
+    /// Open the trajectory containing the system, returning it together with
+    /// the unit cell requested in the input.
+    fn open_trajectory(&self) -> Result<(Trajectory, InputCell), Error> {
         let config = self.system_table()?;
 
         let file = extract::str("file", config, "system")?;
         let file = get_input_path(&self.path, file);
         let mut trajectory = TrajectoryBuilder::new().open(file)?;
 
-        let with_cell = self.read_cell()?.map_or(false, |cell| {
-            trajectory.set_cell(&cell);
-            true
-        });
+        let cell = self.read_cell()?;
+        if let InputCell::Cell(ref cell) = cell {
+            trajectory.set_cell(cell);
+        }
 
         if config.get("topology").is_some() {
             let topology = extract::str("topology", config, "system")?;
             trajectory.set_topology_file(topology)?;
         }
 
-        let guess_bonds = if let Some(guess_bonds) = config.get("guess_bonds") {
-            guess_bonds.as_bool().ok_or(
-                Error::from("'guess_bonds' should be a boolean value in system")
-            )?
-        } else {
-            false
-        };
+        Ok((trajectory, cell))
+    }
 
-        let mut system = if guess_bonds {
-            trajectory.read_guess_bonds()?
-        } else {
-            trajectory.read()?
+    /// Read the next frame from the `trajectory`, guessing the bonds if
+    /// requested in the input.
+    fn read_frame(&self, trajectory: &mut Trajectory) -> Result<System, Error> {
+        let config = self.system_table()?;
+        let system = match config.get("guess_bonds") {
+            None | Some(Value::Boolean(false)) => trajectory.read()?,
+            Some(Value::Boolean(true)) => trajectory.read_guess_bonds()?,
+            Some(Value::Table(guess_bonds)) => {
+                let tolerance = extract::number("tolerance", guess_bonds, "guess_bonds")?;
+                if tolerance <= 0.0 {
+                    return Err(Error::from("'tolerance' must be positive in guess_bonds"));
+                }
+                trajectory.read_guess_bonds_with_tolerance(tolerance)?
+            }
+            Some(_) => {
+                return Err(Error::from("'guess_bonds' should be a boolean value or a table in system"));
+            }
         };
+        Ok(system)
+    }
 
+    /// Set up the `system` read from the first frame of the trajectory:
+    /// masses, exclusions, potentials and velocities.
+    fn setup_system(&self, mut system: System, cell: InputCell) -> Result<System, Error> {
+        self.check_overlaps(&system)?;
+        // masses must be set before initializing the velocities
+        self.read_masses(&mut system)?;
+        self.read_exclusions(&mut system)?;
         self.read_potentials(&mut system)?;
         self.init_velocities(&mut system)?;
 
-        if !with_cell && system.cell.is_infinite() {
-            warn!(
-                "No unit cell in the system, using an infinite unit cell.\n\
-                 You can get rid of this warning by using `cell = []` in the \
-                 input file if this is what you want."
-            );
+        if system.cell.is_infinite() {
+            match cell {
+                InputCell::Missing => warn!(
+                    "No unit cell in the system, using an infinite unit cell.\n\
+                     You can get rid of this warning by using `cell = []` in the \
+                     input file if this is what you want."
+                ),
+                InputCell::FromFile => return Err(Error::from(
+                    "'cell' is set to \"file\", but there is no unit cell in the system file"
+                )),
+                InputCell::Cell(_) => {}
+            }
         }
 
         Ok(system)
@@ -66,7 +188,11 @@ impl Input {
             return Err(Error::from("'systems' array should contain a system"));
         }
 
-        if systems.len() > 1 {
+        if self.is_gibbs() {
+            if systems.len() != 2 {
+                return Err(Error::from("Gibbs ensemble Monte Carlo requires exactly two systems in input file"));
+            }
+        } else if systems.len() > 1 {
             return Err(Error::from("only one system is supported in input file"));
         }
 
@@ -77,19 +203,19 @@ impl Input {
         return Ok(system);
     }
 
-    fn read_cell(&self) -> Result<Option<UnitCell>, Error> {
+    fn read_cell(&self) -> Result<InputCell, Error> {
         let config = self.system_table()?;
         if let Some(cell) = config.get("cell") {
             match *cell {
                 Value::Array(ref cell) => {
                     if cell.is_empty() {
-                        Ok(Some(UnitCell::infinite()))
+                        Ok(InputCell::Cell(UnitCell::infinite()))
                     } else if cell.len() == 3 {
                         let a = get_cell_number(&cell[0])?;
                         let b = get_cell_number(&cell[1])?;
                         let c = get_cell_number(&cell[2])?;
 
-                        Ok(Some(UnitCell::ortho(a, b, c)))
+                        Ok(InputCell::Cell(UnitCell::ortho(a, b, c)))
                     } else if cell.len() == 6 {
                         let a = get_cell_number(&cell[0])?;
                         let b = get_cell_number(&cell[1])?;
@@ -98,21 +224,143 @@ impl Input {
                         let beta = get_cell_number(&cell[4])?;
                         let gamma = get_cell_number(&cell[5])?;
 
-                        Ok(Some(UnitCell::triclinic(a, b, c, alpha, beta, gamma)))
+                        Ok(InputCell::Cell(UnitCell::triclinic(a, b, c, alpha, beta, gamma)))
                     } else {
                         Err(Error::from("'cell' array must have a size of 3 or 6"))
                     }
                 }
                 Value::Integer(lenght) => {
                     let lenght = lenght as f64;
-                    Ok(Some(UnitCell::cubic(lenght)))
+                    Ok(InputCell::Cell(UnitCell::cubic(lenght)))
                 }
-                Value::Float(lenght) => Ok(Some(UnitCell::cubic(lenght))),
-                _ => Err(Error::from("'cell' must be a number or an array in system")),
+                Value::Float(lenght) => Ok(InputCell::Cell(UnitCell::cubic(lenght))),
+                Value::String(ref value) if value == "file" => Ok(InputCell::FromFile),
+                _ => Err(Error::from("'cell' must be a number, an array or \"file\" in system")),
             }
         } else {
-            Ok(None)
+            Ok(InputCell::Missing)
+        }
+    }
+
+    fn read_masses(&self, system: &mut System) -> Result<(), Error> {
+        let config = self.system_table()?;
+        let masses = match config.get("masses") {
+            Some(masses) => masses,
+            None => return Ok(()),
+        };
+
+        let masses = masses.as_table().ok_or(
+            Error::from("'masses' must be a table in system")
+        )?;
+
+        for (name, mass) in masses {
+            let mass = mass.as_str().ok_or(
+                Error::from(format!("mass for '{}' must be a string in system", name))
+            )?;
+            let mass = units::from_str(mass)?;
+            if mass < 0.0 {
+                return Err(Error::from(format!("mass for '{}' must be positive in system", name)));
+            }
+
+            let mut nchanged = 0;
+            let mut overridden = false;
+            for particle in system.particles_mut() {
+                if particle.name == name {
+                    overridden |= *particle.mass > 0.0;
+                    *particle.mass = mass;
+                    nchanged += 1;
+                }
+            }
+
+            if nchanged == 0 {
+                warn!("No particle with name '{}' was found while setting the masses", name);
+            } else {
+                if overridden {
+                    warn!("Overriding the mass of {} particles from the configuration", name);
+                }
+                info!("Mass set to {} for {} {} particles", mass, nchanged, name);
+            }
         }
+        Ok(())
+    }
+
+    /// Check that no pair of particles is closer than the minimal distance
+    /// given in the input, if any.
+    fn check_overlaps(&self, system: &System) -> Result<(), Error> {
+        let config = self.system_table()?;
+        let (min_distance, error) = match config.get("check_overlaps") {
+            None => return Ok(()),
+            Some(Value::String(min_distance)) => (units::from_str(min_distance)?, true),
+            Some(Value::Table(table)) => {
+                let min_distance = extract::str("min_distance", table, "check_overlaps")?;
+                let error = match table.get("error") {
+                    None => true,
+                    Some(Value::Boolean(error)) => *error,
+                    Some(_) => return Err(Error::from("'error' must be a boolean in check_overlaps")),
+                };
+                (units::from_str(min_distance)?, error)
+            }
+            Some(_) => {
+                return Err(Error::from("'check_overlaps' must be a string or a table in system"));
+            }
+        };
+
+        if min_distance <= 0.0 {
+            return Err(Error::from("'min_distance' must be positive in check_overlaps"));
+        }
+
+        let overlaps = system.check_overlaps(min_distance);
+        for &(i, j, distance) in &overlaps {
+            warn!("particles {} and {} are overlapping, at a distance of {} A", i, j, distance);
+        }
+
+        if error && !overlaps.is_empty() {
+            return Err(Error::from(format!(
+                "found {} pairs of particles closer than {} A in the initial configuration",
+                overlaps.len(), min_distance
+            )));
+        }
+        Ok(())
+    }
+
+    /// Read the explicitly excluded pairs of particles
+    fn read_exclusions(&self, system: &mut System) -> Result<(), Error> {
+        let config = self.system_table()?;
+        if config.get("exclusions").is_none() {
+            return Ok(());
+        }
+
+        let exclusions = extract::slice("exclusions", config, "system")?;
+        for pair in exclusions {
+            let pair = pair.as_array().ok_or(
+                Error::from("'exclusions' must be an array of pairs of particles indexes in system")
+            )?;
+
+            if pair.len() != 2 {
+                return Err(Error::from("'exclusions' must be an array of pairs of particles indexes in system"));
+            }
+
+            let mut indexes = [0; 2];
+            for (index, value) in indexes.iter_mut().zip(pair) {
+                *index = match value.as_integer() {
+                    Some(value) if value >= 0 => value as usize,
+                    _ => return Err(Error::from("particles indexes must be positive integers in exclusions")),
+                };
+
+                if *index >= system.size() {
+                    return Err(Error::from(format!(
+                        "particle index {} in exclusions is out of bounds for a system with {} particles",
+                        index, system.size()
+                    )));
+                }
+            }
+
+            if indexes[0] == indexes[1] {
+                return Err(Error::from("can not exclude a particle from interacting with itself"));
+            }
+            system.add_exclusion(indexes[0], indexes[1]);
+        }
+        Ok(())
     }
 
     fn init_velocities(&self, system: &mut System) -> Result<(), Error> {
@@ -125,8 +373,11 @@ impl Input {
 
             if velocities.get("init").is_some() {
                 let temperature = extract::str("init", velocities, "velocities initializer")?;
-                let temperature = units::from_str(temperature)?;
+                let temperature = self.read_unit_system()?.from_str(temperature, "K")?;
                 let mut velocities = BoltzmannVelocities::new(temperature);
+                if let Some(seed) = self.read_seed()? {
+                    velocities.seed(SeedSequence::velocities(seed));
+                }
                 velocities.init(system);
             } else {
                 warn!("'velocities' key does nothing in this input file");
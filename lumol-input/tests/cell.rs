@@ -0,0 +1,50 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Using the unit cell from the system file
+use lumol_core::UnitCell;
+use lumol_input::Input;
+
+use std::path::PathBuf;
+
+fn read_system(cell: &str) -> lumol_core::System {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("simulation").join("good").join("cell.toml");
+    let content = format!("
+        [input]
+        version = 1
+
+        [[systems]]
+        file = \"../argon.pdb\"
+        {}
+
+        [[simulations]]
+        nsteps = 1
+        [simulations.propagator]
+        type = \"MolecularDynamics\"
+        timestep = \"1 fs\"
+    ", cell);
+    Input::from_str(path, &content).unwrap().read_system().unwrap()
+}
+
+#[test]
+fn cell_from_file() {
+    let reference = UnitCell::triclinic(25.0, 30.0, 35.0, 90.0, 80.0, 110.0);
+
+    let system = read_system("cell = \"file\"");
+    assert_eq!(system.size(), 2);
+    let cell = &system.cell;
+    for &(actual, expected) in &[
+        (cell.a(), reference.a()), (cell.b(), reference.b()), (cell.c(), reference.c()),
+        (cell.alpha(), reference.alpha()), (cell.beta(), reference.beta()), (cell.gamma(), reference.gamma()),
+    ] {
+        assert!(f64::abs(actual - expected) < 1e-9, "{} != {}", actual, expected);
+    }
+
+    // Same cell without the `cell` key
+    let system = read_system("");
+    assert_eq!(system.cell.matrix(), read_system("cell = \"file\"").cell.matrix());
+
+    // The cell in the input overrides the one from the file
+    let system = read_system("cell = 20.0");
+    assert_eq!(system.cell.matrix(), UnitCell::cubic(20.0).matrix());
+}
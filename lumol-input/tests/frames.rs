@@ -0,0 +1,64 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Reading one system for each frame of a trajectory
+use lumol_core::Vector3D;
+use lumol_input::Input;
+
+use std::path::PathBuf;
+
+#[test]
+fn read_systems() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("simulation").join("good").join("frames.toml");
+    let content = "
+        [input]
+        version = 1
+
+        [[systems]]
+        file = \"../frames.xyz\"
+        cell = 20.0
+        guess_bonds = true
+
+        [systems.potentials.global]
+        cutoff = \"10 A\"
+
+        [systems.potentials.pairs]
+        O-O = {type = \"lj\", sigma = \"3 A\", epsilon = \"5 kJ/mol\"}
+
+        [systems.potentials.bonds]
+        C-O = {type = \"harmonic\", x0 = \"1.2 A\", k = \"5 kJ/mol\"}
+
+        [[simulations]]
+        nsteps = 1
+        [simulations.propagator]
+        type = \"MolecularDynamics\"
+        timestep = \"1 fs\"
+    ";
+    let input = Input::from_str(path, content).unwrap();
+    let systems = input.read_systems().unwrap();
+    assert_eq!(systems.len(), 3);
+
+    assert_eq!(systems[0].particles().position[0], Vector3D::new(0.0, 1.0, 0.0));
+    assert_eq!(systems[1].particles().position[0], Vector3D::new(0.1, 1.1, 0.0));
+    assert_eq!(systems[2].particles().position[0], Vector3D::new(1.0, 0.0, 0.0));
+
+    // The first system is the same as the one from `read_system`
+    let system = input.read_system().unwrap();
+    assert_eq!(systems[0].particles().position, system.particles().position);
+
+    for system in &systems {
+        assert_eq!(system.particles().name, ["O", "C", "O"]);
+        assert_eq!(system.cell.lengths(), Vector3D::new(20.0, 20.0, 20.0));
+        assert_eq!(system.molecules().count(), 1);
+        // All systems share the same interactions
+        assert!(system.pair_potential(0, 2).is_some());
+        assert!(system.bond_potential(0, 1).is_some());
+    }
+
+    // All systems have distinct coordinates
+    for i in 0..3 {
+        for j in (i + 1)..3 {
+            assert_ne!(systems[i].particles().position, systems[j].particles().position);
+        }
+    }
+}
@@ -12,7 +12,7 @@ use walkdir::WalkDir;
 use rustc_test::{DynTestFn, DynTestName, TestDesc, TestDescAndFn};
 use rustc_test::ShouldPanic::No;
 
-use lumol_core::System;
+use lumol_core::{System, UnitCell};
 use lumol_input::{Error, Input, InteractionsInput};
 
 fn main() {
@@ -58,7 +58,8 @@ fn all_tests() -> Vec<TestDescAndFn> {
 
                 match result {
                     Err(Error::Config(reason)) => assert_eq!(reason, message),
-                    _ => panic!("This test should fail with a Config error"),
+                    Err(Error::Potential(error)) => assert_eq!(error.to_string(), message),
+                    _ => panic!("This test should fail with a Config or Potential error"),
                 }
             })
         }).expect("Could not generate the tests"),
@@ -67,7 +68,7 @@ fn all_tests() -> Vec<TestDescAndFn> {
     tests.extend(
         generate_tests("interactions/good", |_, content| {
             Box::new(move || {
-                let mut system = System::new();
+                let mut system = System::with_cell(UnitCell::cubic(400.0));
                 let input = InteractionsInput::from_str(&content).unwrap();
                 input.read(&mut system).unwrap();
             })
@@ -153,11 +154,13 @@ impl Drop for TestsCleanup {
             "energy.dat",
             "filename.xyz",
             "cell.dat",
+            "momentum.dat",
             "properties.dat",
             "file.log",
             "custom.dat",
             "stress.dat",
             "forces.xyz",
+            "widom.dat",
         ];
 
         for file in REMOVE {
@@ -0,0 +1,83 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Importing interactions from a LAMMPS input script
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+use lumol_input::InteractionsInput;
+
+const LAMMPS: &str = "
+# SPC/E water
+units real
+labelmap atom 1 O 2 H
+labelmap bond 1 O-H
+labelmap angle 1 H-O-H
+
+pair_style lj/cut 9.0
+pair_modify shift yes
+pair_coeff 1 1 0.1553 3.166
+pair_coeff 1 2 0.0 1.0
+pair_coeff H H 0.0 1.0
+
+bond_style harmonic
+bond_coeff 1 450.0 1.0
+angle_style harmonic
+angle_coeff H-O-H 55.0 109.47
+
+set type 1 charge -0.8476
+set type 2 charge 0.4238
+
+read_data water.data
+";
+
+fn water(system: &mut System, origin: Vector3D) {
+    let mut molecule = Molecule::new(Particle::with_position("O", origin));
+    molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(1.0, 0.0, 0.0)));
+    molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(-0.3, 0.95, 0.0)));
+    system.add_molecule(molecule);
+}
+
+#[test]
+fn spce_water() {
+    let mut system = System::with_cell(UnitCell::cubic(25.0));
+    water(&mut system, Vector3D::new(0.0, 0.0, 0.0));
+    water(&mut system, Vector3D::new(3.5, 0.0, 0.0));
+
+    let input = InteractionsInput::from_lammps(LAMMPS).unwrap();
+    input.read(&mut system).unwrap();
+
+    let kcal_mol = units::from(1.0, "kcal/mol").unwrap();
+
+    // Lennard-Jones between the oxygen atoms, shifted at the cutoff
+    let lj = system.pair_potential(0, 3).unwrap();
+    assert_eq!(lj.cutoff(), 9.0);
+    let lj_energy = |r: f64| 4.0 * 0.1553 * kcal_mol * (f64::powi(3.166 / r, 12) - f64::powi(3.166 / r, 6));
+    let expected = lj_energy(3.5) - lj_energy(9.0);
+    assert!(f64::abs(lj.energy(3.5) - expected) < 1e-10);
+    assert_eq!(system.pair_potential(1, 4).unwrap().energy(2.0), 0.0);
+
+    // LAMMPS harmonic bonds and angles do not include the 1/2 factor
+    let bond = system.bond_potential(0, 1).unwrap();
+    assert!(f64::abs(bond.energy(1.1) - 450.0 * kcal_mol * 0.01) < 1e-10);
+
+    let angle = system.angle_potential(1, 0, 2).unwrap();
+    let delta = f64::to_radians(100.0 - 109.47);
+    assert!(f64::abs(angle.energy(f64::to_radians(100.0)) - 55.0 * kcal_mol * delta * delta) < 1e-10);
+
+    assert_eq!(system.particles().charge, [-0.8476, 0.4238, 0.4238, -0.8476, 0.4238, 0.4238]);
+}
+
+#[test]
+fn errors() {
+    let error = InteractionsInput::from_lammps("pair_coeff 1 1 0.1 3.0").err().unwrap();
+    assert_eq!(error.to_string(), "LAMMPS 'pair_coeff' must be used after 'pair_style'");
+
+    let error = InteractionsInput::from_lammps("pair_style lj/cut 9.0\npair_coeff 1 1 0.1 3.0").err().unwrap();
+    assert_eq!(error.to_string(), "missing name for LAMMPS atom type 1, use the 'labelmap' command to name it");
+
+    let error = InteractionsInput::from_lammps("pair_style lj/cut/coul/long 9.0").err().unwrap();
+    assert_eq!(error.to_string(), "unsupported LAMMPS pair style 'lj/cut/coul/long 9.0', only 'lj/cut' is supported");
+
+    let error = InteractionsInput::from_lammps("units lj").err().unwrap();
+    assert_eq!(error.to_string(), "unsupported LAMMPS units 'lj', only 'real' and 'metal' are supported");
+}
@@ -0,0 +1,48 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Overriding the particles masses from the input file
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::Vector3D;
+use lumol_input::Input;
+
+use std::path::PathBuf;
+
+fn read_system(masses: &str) -> lumol_core::System {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("simulation").join("good").join("masses.toml");
+    let content = format!("
+        [input]
+        version = 1
+
+        [[systems]]
+        file = \"../CO2.xyz\"
+        cell = 20.0
+        {}
+
+        [[simulations]]
+        nsteps = 1
+        [simulations.propagator]
+        type = \"MolecularDynamics\"
+        timestep = \"1 fs\"
+    ", masses);
+    Input::from_str(path, &content).unwrap().read_system().unwrap()
+}
+
+#[test]
+fn override_masses() {
+    let system = read_system("");
+    assert_eq!(system.particles().name, ["O", "C", "O"]);
+    let guessed = system.particles().mass.to_vec();
+    assert!(guessed.iter().all(|&mass| mass > 0.0));
+
+    let mut system = read_system("masses = {C = \"13.5 u\"}");
+    assert_eq!(system.particles().mass, [guessed[0], 13.5, guessed[2]]);
+
+    // The temperature uses the new masses
+    for velocity in system.particles_mut().velocity {
+        *velocity = Vector3D::new(0.1, 0.0, 0.0);
+    }
+    let kinetic = 0.5 * (guessed[0] + 13.5 + guessed[2]) * 0.01;
+    let expected = 2.0 * kinetic / (system.degrees_of_freedom() as f64 * K_BOLTZMANN);
+    assert!(f64::abs(system.temperature() - expected) / expected < 1e-12);
+}
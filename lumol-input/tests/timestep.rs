@@ -0,0 +1,27 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Reading the molecular dynamics timestep with units
+use lumol_core::units::{self, UnitSystem};
+use lumol_sim::md::MolecularDynamics;
+use lumol_input::FromTomlWithData;
+
+fn read_md(propagator: &str) -> MolecularDynamics {
+    let config = propagator.parse::<toml::Value>().unwrap();
+    let config = config.as_table().unwrap();
+    MolecularDynamics::from_toml(config, UnitSystem::Physical).unwrap()
+}
+
+#[test]
+fn timestep_units() {
+    let md = read_md("timestep = \"2 fs\"");
+    assert_eq!(md.timestep(), Some(2.0));
+    assert_eq!(md.timestep(), Some(units::from(2.0, "fs").unwrap()));
+
+    let md = read_md("timestep = \"0.002 ps\"");
+    assert!(f64::abs(md.timestep().unwrap() - 2.0) < 1e-12);
+
+    // The timestep is forwarded to the integrators
+    let md = read_md("timestep = \"2 fs\"\nintegrator = {type = \"LeapFrog\"}");
+    assert_eq!(md.timestep(), Some(2.0));
+}
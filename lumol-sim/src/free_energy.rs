@@ -0,0 +1,552 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use log::{info, warn};
+
+use lumol_core::{DistanceRestraint, System};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
+
+use crate::{Simulation, SimulationError};
+
+/// Free energy computation using thermodynamic integration.
+///
+/// The free energy difference between the decoupled ($\lambda = 0$) and
+/// coupled ($\lambda = 1$) states of the system is computed as
+///
+/// $$ \Delta F = \int_0^1 \left\langle \frac{\partial U}{\partial \lambda}
+///    \right\rangle_\lambda d\lambda $$
+///
+/// The simulation is run at each value of the coupling parameter $\lambda$,
+/// accumulating the average of $\partial U / \partial \lambda$, and the
+/// integral is evaluated with the trapezoidal rule. Only pair potentials
+/// depending on $\lambda$ (such as `SoftCoreLennardJones`) contribute to the
+/// derivative, see `System::set_lambda`.
+///
+/// All the windows are run in order in a single run of the simulation, each
+/// one starting from the final configuration of the previous one. The
+/// derivative is sampled every `frequency` steps after the equilibration
+/// steps of each window. The additional stages of the simulation are run
+/// after the last window, and the derivative is not sampled during their
+/// steps.
+#[derive(Clone, Debug)]
+pub struct ThermodynamicIntegration {
+    /// Values of the coupling parameter for all the windows
+    lambdas: Vec<f64>,
+    /// Number of production steps in each window
+    steps: usize,
+    /// Number of equilibration steps in each window
+    equilibration: usize,
+    /// Number of steps between two samples of the derivative
+    frequency: usize,
+    /// Average of the derivative of the energy in each window, from the last
+    /// run
+    derivatives: Vec<f64>,
+}
+
+impl ThermodynamicIntegration {
+    /// Create a new `ThermodynamicIntegration` running the simulation for
+    /// `steps` steps at each one of the `lambdas` values. The derivative of
+    /// the energy is sampled every 10 steps (or every step if `steps` is
+    /// smaller than 10), and there are no equilibration steps by default.
+    ///
+    /// # Panics
+    ///
+    /// If there are less than two values of lambda, if the values are not
+    /// sorted in increasing order or outside of the [0, 1] range, or if
+    /// `steps` is zero.
+    pub fn new(lambdas: Vec<f64>, steps: usize) -> ThermodynamicIntegration {
+        assert!(lambdas.len() >= 2, "at least two values of lambda are needed for thermodynamic integration");
+        assert!(
+            lambdas.iter().all(|&lambda| (0.0..=1.0).contains(&lambda)),
+            "lambda values must be between 0 and 1 in thermodynamic integration"
+        );
+        assert!(
+            lambdas.windows(2).all(|window| window[0] < window[1]),
+            "lambda values must be sorted in increasing order in thermodynamic integration"
+        );
+        assert!(steps > 0, "the number of steps must be positive in thermodynamic integration");
+        ThermodynamicIntegration {
+            lambdas: lambdas,
+            steps: steps,
+            equilibration: 0,
+            frequency: usize::min(10, steps),
+            derivatives: Vec::new(),
+        }
+    }
+
+    /// Run `steps` equilibration steps at the beginning of each window,
+    /// without sampling the derivative of the energy.
+    pub fn set_equilibration(&mut self, steps: usize) {
+        self.equilibration = steps;
+    }
+
+    /// Sample the derivative of the energy every `frequency` steps.
+    ///
+    /// # Panics
+    ///
+    /// If `frequency` is zero, or bigger than the number of steps in each
+    /// window.
+    pub fn set_frequency(&mut self, frequency: usize) {
+        assert!(frequency > 0, "the sampling frequency must be positive in thermodynamic integration");
+        assert!(
+            frequency <= self.steps,
+            "the sampling frequency can not be bigger than the number of steps in thermodynamic integration"
+        );
+        self.frequency = frequency;
+    }
+
+    /// Get the values of the coupling parameter
+    pub fn lambdas(&self) -> &[f64] {
+        &self.lambdas
+    }
+
+    /// Get the total number of simulation steps, including equilibration.
+    /// The steps of the additional stages of the simulation are not included.
+    pub fn total_steps(&self) -> usize {
+        self.lambdas.len() * (self.equilibration + self.steps)
+    }
+
+    /// Get the average derivative of the energy with respect to lambda for
+    /// each window in the last call to [`run`](#method.run).
+    pub fn derivatives(&self) -> &[f64] {
+        &self.derivatives
+    }
+
+    /// Run the `simulation` on the `system` for all the windows, and return
+    /// the free energy difference between the last and the first value of
+    /// lambda. The system is left with the coupling parameter of the last
+    /// window. If the simulation fails, the error is returned and the
+    /// derivatives are not updated.
+    pub fn run(&mut self, simulation: &mut Simulation, system: &mut System) -> Result<f64, SimulationError> {
+        let window_steps = self.equilibration + self.steps;
+        let mut sums = vec![0.0; self.lambdas.len()];
+
+        // The stages are run after all the windows
+        let nsteps = self.total_steps() + simulation.stages_steps();
+        info!("Thermodynamic integration window at lambda = {}", self.lambdas[0]);
+        system.set_lambda(self.lambdas[0]);
+        simulation.run_with_sampler(system, nsteps, |step, system| {
+            // Steps of the additional stages are not part of any window
+            let step = step as usize;
+            let window = (step - 1) / window_steps;
+            if window >= sums.len() {
+                return;
+            }
+
+            let window_step = step - window * window_steps;
+            if window_step > self.equilibration && (window_step - self.equilibration) % self.frequency == 0 {
+                sums[window] += system.d_energy_d_lambda();
+            }
+
+            if window_step == window_steps && window + 1 < self.lambdas.len() {
+                let lambda = self.lambdas[window + 1];
+                info!("Thermodynamic integration window at lambda = {}", lambda);
+                system.set_lambda(lambda);
+            }
+        })?;
+
+        let samples = (self.steps / self.frequency) as f64;
+        self.derivatives = sums.iter().map(|sum| sum / samples).collect();
+        for (lambda, derivative) in self.lambdas.iter().zip(&self.derivatives) {
+            info!("<dU/dlambda> = {} kJ/mol at lambda = {}", to_kj_mol(*derivative), lambda);
+        }
+
+        let free_energy = trapezoidal(&self.lambdas, &self.derivatives);
+        info!("Free energy difference: {} kJ/mol", to_kj_mol(free_energy));
+        return Ok(free_energy);
+    }
+}
+
+/// Potential of mean force along the distance between two groups of
+/// particles, using umbrella sampling.
+///
+/// The simulation is run in a series of windows, each one using an harmonic
+/// [`DistanceRestraint`] centered on a different distance between the centers
+/// of mass of the two groups. The distance is sampled in each window, and the
+/// biased distributions are then combined with the weighted histogram analysis
+/// method (WHAM) [Kumar1992] to get the unbiased free energy profile with
+/// [`pmf`](#method.pmf).
+///
+/// The free energy profile contains the entropic contribution from the
+/// volume available at each distance, i.e. $-2 k_B T \ln r$ for two groups
+/// moving freely in three dimensions.
+///
+/// The windows are run in order in a single run of the simulation, each one
+/// starting from the final configuration of the previous one, in the same
+/// way as [`ThermodynamicIntegration`]. The distance is sampled every
+/// `frequency` steps after the equilibration steps of each window. The
+/// additional stages of the simulation are run after the last window, and
+/// the distance is not sampled during their steps.
+///
+/// [Kumar1992]: Kumar, S. et al. J. Comput. Chem. 13, 1011 (1992).
+/// [`DistanceRestraint`]: ../lumol_core/energy/struct.DistanceRestraint.html
+/// [`ThermodynamicIntegration`]: struct.ThermodynamicIntegration.html
+#[derive(Clone, Debug)]
+pub struct UmbrellaSampling {
+    /// Indexes of the particles in the first group
+    first: Vec<usize>,
+    /// Indexes of the particles in the second group
+    second: Vec<usize>,
+    /// Force constant of the restraints
+    k: f64,
+    /// Center of the restraint for all the windows
+    centers: Vec<f64>,
+    /// Number of production steps in each window
+    steps: usize,
+    /// Number of equilibration steps in each window
+    equilibration: usize,
+    /// Number of steps between two samples of the distance
+    frequency: usize,
+    /// Sampled distances in each window, from the last run
+    samples: Vec<Vec<f64>>,
+}
+
+impl UmbrellaSampling {
+    /// Create a new `UmbrellaSampling` along the distance between the centers
+    /// of mass of the particles at indexes `first` and `second`, using
+    /// harmonic restraints with force constant `k` centered at each one of
+    /// the `centers` distances. The simulation runs for `steps` steps in each
+    /// window, sampling the distance every 10 steps (or every step if `steps`
+    /// is smaller than 10), and there are no equilibration steps by default.
+    ///
+    /// # Panics
+    ///
+    /// If there are less than two windows, if the centers are negative or not
+    /// sorted in increasing order, if `k` is not positive or if `steps` is
+    /// zero.
+    pub fn new(first: Vec<usize>, second: Vec<usize>, k: f64, centers: Vec<f64>, steps: usize) -> UmbrellaSampling {
+        assert!(centers.len() >= 2, "at least two windows are needed for umbrella sampling");
+        assert!(
+            centers.iter().all(|&center| center >= 0.0),
+            "windows centers must be positive in umbrella sampling"
+        );
+        assert!(
+            centers.windows(2).all(|window| window[0] < window[1]),
+            "windows centers must be sorted in increasing order in umbrella sampling"
+        );
+        assert!(k > 0.0, "the force constant must be positive in umbrella sampling");
+        assert!(steps > 0, "the number of steps must be positive in umbrella sampling");
+        UmbrellaSampling {
+            first: first,
+            second: second,
+            k: k,
+            centers: centers,
+            steps: steps,
+            equilibration: 0,
+            frequency: usize::min(10, steps),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Run `steps` equilibration steps at the beginning of each window,
+    /// without sampling the distance.
+    pub fn set_equilibration(&mut self, steps: usize) {
+        self.equilibration = steps;
+    }
+
+    /// Sample the distance every `frequency` steps.
+    ///
+    /// # Panics
+    ///
+    /// If `frequency` is zero, or bigger than the number of steps in each
+    /// window.
+    pub fn set_frequency(&mut self, frequency: usize) {
+        assert!(frequency > 0, "the sampling frequency must be positive in umbrella sampling");
+        assert!(
+            frequency <= self.steps,
+            "the sampling frequency can not be bigger than the number of steps in umbrella sampling"
+        );
+        self.frequency = frequency;
+    }
+
+    /// Get the centers of the restraints for all the windows
+    pub fn centers(&self) -> &[f64] {
+        &self.centers
+    }
+
+    /// Get the total number of simulation steps, including equilibration.
+    /// The steps of the additional stages of the simulation are not included.
+    pub fn total_steps(&self) -> usize {
+        self.centers.len() * (self.equilibration + self.steps)
+    }
+
+    /// Get the distances sampled in each window during the last call to
+    /// [`run`](#method.run).
+    pub fn samples(&self) -> &[Vec<f64>] {
+        &self.samples
+    }
+
+    /// Run the `simulation` on the `system` for all the windows, sampling
+    /// the distance between the two groups. The restraint is added to the
+    /// system during the run, and removed at the end, leaving the system in
+    /// the final configuration of the last window. If the simulation fails,
+    /// the error is returned and the samples are not updated.
+    ///
+    /// # Panics
+    ///
+    /// If one of the groups refers to a particle which is not in the system.
+    pub fn run(&mut self, simulation: &mut Simulation, system: &mut System) -> Result<(), SimulationError> {
+        let natoms = system.size();
+        for &i in self.first.iter().chain(&self.second) {
+            assert!(
+                i < natoms,
+                "can not restrain particle {} in umbrella sampling, the system only contains {} particles",
+                i, natoms
+            );
+        }
+
+        let restraint = |center| {
+            Box::new(DistanceRestraint::new(self.first.clone(), self.second.clone(), self.k, center))
+        };
+        // The distance does not depend on the center of the restraint
+        let probe = restraint(0.0);
+        let window_steps = self.equilibration + self.steps;
+        let mut samples = vec![Vec::with_capacity(self.steps / self.frequency); self.centers.len()];
+
+        // The stages are run after all the windows
+        let nsteps = self.total_steps() + simulation.stages_steps();
+        info!("Umbrella sampling window centered at {} A", self.centers[0]);
+        system.add_global_potential(restraint(self.centers[0]));
+        let index = system.global_potentials().len() - 1;
+        let result = simulation.run_with_sampler(system, nsteps, |step, system| {
+            // Steps of the additional stages are not part of any window
+            let step = step as usize;
+            let window = (step - 1) / window_steps;
+            if window >= samples.len() {
+                return;
+            }
+
+            let window_step = step - window * window_steps;
+            if window_step > self.equilibration && (window_step - self.equilibration) % self.frequency == 0 {
+                samples[window].push(probe.distance(system));
+            }
+
+            if window_step == window_steps && window + 1 < self.centers.len() {
+                let center = self.centers[window + 1];
+                info!("Umbrella sampling window centered at {} A", center);
+                let _ = system.remove_global_potential(index);
+                system.add_global_potential(restraint(center));
+            }
+        });
+        let _ = system.remove_global_potential(index);
+        result?;
+
+        for (center, samples) in self.centers.iter().zip(&samples) {
+            let average = samples.iter().sum::<f64>() / samples.len() as f64;
+            info!("<d> = {} A in the window centered at {} A", average, center);
+        }
+        self.samples = samples;
+        Ok(())
+    }
+
+    /// Get the potential of mean force at the given `temperature` from the
+    /// samples of the last call to [`run`](#method.run), using the weighted
+    /// histogram analysis method with `bins` bins between the smallest and
+    /// largest sampled distances. This function returns `(distance, free
+    /// energy)` pairs for all the bins containing at least one sample, with
+    /// the smallest free energy set to zero.
+    pub fn pmf(&self, temperature: f64, bins: usize) -> Vec<(f64, f64)> {
+        assert!(!self.samples.is_empty(), "no samples in umbrella sampling, call `run` first");
+        let biases = self.centers.iter().map(|&center| {
+            let k = self.k;
+            move |distance: f64| 0.5 * k * (distance - center) * (distance - center)
+        }).collect::<Vec<_>>();
+        return wham(&self.samples, &biases, temperature, bins);
+    }
+}
+
+/// Maximal number of self-consistent iterations in WHAM
+const MAX_WHAM_ITERATIONS: usize = 100_000;
+
+/// Combine the `samples` of a set of biased simulations using the weighted
+/// histogram analysis method. The bias in the window `i` is given by
+/// `biases[i]`, and the samples are binned in `bins` bins between the
+/// smallest and largest samples. This returns the free energy of the bins
+/// containing at least one sample, with the smallest one set to zero. A
+/// warning is emitted if the self-consistent iterations do not converge.
+fn wham<F: Fn(f64) -> f64>(samples: &[Vec<f64>], biases: &[F], temperature: f64, bins: usize) -> Vec<(f64, f64)> {
+    debug_assert_eq!(samples.len(), biases.len());
+    assert!(bins > 0, "the number of bins must be positive in WHAM");
+    let beta = 1.0 / (K_BOLTZMANN * temperature);
+
+    let all_samples = || samples.iter().flat_map(|window| window.iter().copied());
+    let min = all_samples().fold(f64::INFINITY, f64::min);
+    let max = all_samples().fold(f64::NEG_INFINITY, f64::max);
+    let width = f64::max(max - min, f64::EPSILON) / bins as f64;
+    let bin = |value: f64| usize::min(((value - min) / width) as usize, bins - 1);
+    let positions = (0..bins).map(|i| min + (i as f64 + 0.5) * width).collect::<Vec<_>>();
+
+    let mut histogram = vec![0.0; bins];
+    for value in all_samples() {
+        histogram[bin(value)] += 1.0;
+    }
+    let sizes = samples.iter().map(|window| window.len() as f64).collect::<Vec<_>>();
+
+    // Boltzmann factors of the bias of each window in each bin
+    let factors = biases.iter().map(|bias| {
+        positions.iter().map(|&position| f64::exp(-beta * bias(position))).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+
+    // Self-consistent iterations over the free energies of the windows,
+    // stored as exp(beta f)
+    let mut exp_f = vec![1.0; samples.len()];
+    let mut probabilities = vec![0.0; bins];
+    let mut converged = false;
+    for _ in 0..MAX_WHAM_ITERATIONS {
+        for (bin, probability) in probabilities.iter_mut().enumerate() {
+            let denominator = sizes.iter().zip(&factors).zip(&exp_f).map(|((size, factors), exp_f)| {
+                size * factors[bin] * exp_f
+            }).sum::<f64>();
+            *probability = if histogram[bin] == 0.0 { 0.0 } else { histogram[bin] / denominator };
+        }
+
+        converged = true;
+        for (exp_f, factors) in exp_f.iter_mut().zip(&factors) {
+            let sum = probabilities.iter().zip(factors).map(|(p, factor)| p * factor).sum::<f64>();
+            let new = 1.0 / sum;
+            if f64::abs(f64::ln(new / *exp_f)) > 1e-10 {
+                converged = false;
+            }
+            *exp_f = new;
+        }
+
+        if converged {
+            break;
+        }
+    }
+
+    if !converged {
+        warn!(
+            "WHAM iterations did not converge after {} iterations, the free energy profile might be inaccurate",
+            MAX_WHAM_ITERATIONS
+        );
+    }
+
+    let mut pmf = positions.iter().zip(&probabilities)
+        .filter(|&(_, &probability)| probability > 0.0)
+        .map(|(&position, &probability)| (position, -f64::ln(probability) / beta))
+        .collect::<Vec<_>>();
+    let minimum = pmf.iter().map(|&(_, energy)| energy).fold(f64::INFINITY, f64::min);
+    for (_, energy) in &mut pmf {
+        *energy -= minimum;
+    }
+    return pmf;
+}
+
+/// Convert an energy from internal units to kJ/mol
+fn to_kj_mol(energy: f64) -> f64 {
+    units::to(energy, "kJ/mol").expect("bad unit")
+}
+
+/// Integrate the `values` sampled at `points` using the trapezoidal rule
+fn trapezoidal(points: &[f64], values: &[f64]) -> f64 {
+    debug_assert_eq!(points.len(), values.len());
+    let mut integral = 0.0;
+    for i in 1..points.len() {
+        integral += 0.5 * (points[i] - points[i - 1]) * (values[i] + values[i - 1]);
+    }
+    return integral;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn trapezoidal_rule() {
+        assert_ulps_eq!(trapezoidal(&[0.0, 1.0], &[2.0, 2.0]), 2.0);
+        assert_ulps_eq!(trapezoidal(&[0.0, 0.5, 1.0], &[0.0, 0.5, 1.0]), 0.5);
+        assert_ulps_eq!(trapezoidal(&[0.0, 0.2, 1.0], &[1.0, 2.0, 0.0]), 0.3 + 0.8);
+    }
+
+    #[test]
+    fn total_steps() {
+        let mut integration = ThermodynamicIntegration::new(vec![0.0, 0.5, 1.0], 100);
+        assert_eq!(integration.total_steps(), 300);
+        integration.set_equilibration(50);
+        assert_eq!(integration.total_steps(), 450);
+        assert_eq!(integration.lambdas(), &[0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn wham_single_window() {
+        // Uniformly distributed samples in a single biased window correspond
+        // to a free energy equal to the opposite of the bias. There is the
+        // same number of samples in each one of the 20 bins.
+        let temperature = 300.0;
+        let beta = 1.0 / (K_BOLTZMANN * temperature);
+        let samples = vec![(0..20).flat_map(|i| std::iter::repeat(i as f64 / 19.0).take(500)).collect::<Vec<_>>()];
+        let bias = move |x: f64| 5.0 * (x - 0.4) * (x - 0.4) / beta;
+        let pmf = wham(&samples, &[bias], temperature, 20);
+
+        assert_eq!(pmf.len(), 20);
+        let shift = pmf[0].1 + bias(pmf[0].0);
+        for &(x, energy) in &pmf {
+            assert!(f64::abs(energy + bias(x) - shift) < 1e-9);
+        }
+        assert!(pmf.iter().any(|&(_, energy)| energy == 0.0));
+    }
+
+    #[test]
+    fn wham_two_windows() {
+        // Two windows sampling the same unbiased uniform distribution with
+        // different biases give a flat profile
+        let temperature = 300.0;
+        let beta = 1.0 / (K_BOLTZMANN * temperature);
+        let biases: Vec<Box<dyn Fn(f64) -> f64>> = vec![
+            Box::new(move |x| 0.5 * (x - 1.0) * (x - 1.0) / beta),
+            Box::new(move |x| 0.5 * (x - 2.0) * (x - 2.0) / beta),
+        ];
+
+        // Deterministic samples following exp(-beta * bias) on a fine grid
+        let samples = biases.iter().map(|bias| {
+            let mut samples = Vec::new();
+            for i in 0..3000 {
+                let x = (i as f64 + 0.5) / 1000.0;
+                let count = (100.0 * f64::exp(-beta * bias(x))).round() as usize;
+                samples.extend(std::iter::repeat(x).take(count));
+            }
+            samples
+        }).collect::<Vec<_>>();
+
+        let pmf = wham(&samples, &biases, temperature, 30);
+        for &(x, energy) in &pmf {
+            if (0.5..2.5).contains(&x) {
+                assert!(beta * energy < 0.05, "energy at {} is {} kT", x, beta * energy);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "windows centers must be sorted in increasing order in umbrella sampling")]
+    fn unsorted_centers() {
+        let _ = UmbrellaSampling::new(vec![0], vec![1], 1.0, vec![2.0, 1.0], 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least two values of lambda are needed for thermodynamic integration")]
+    fn single_lambda() {
+        let _ = ThermodynamicIntegration::new(vec![0.5], 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "lambda values must be sorted in increasing order in thermodynamic integration")]
+    fn unsorted_lambdas() {
+        let _ = ThermodynamicIntegration::new(vec![0.0, 1.0, 0.5], 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "lambda values must be between 0 and 1 in thermodynamic integration")]
+    fn out_of_range_lambdas() {
+        let _ = ThermodynamicIntegration::new(vec![0.0, 1.5], 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "the sampling frequency can not be bigger than the number of steps in thermodynamic integration")]
+    fn big_frequency() {
+        let mut integration = ThermodynamicIntegration::new(vec![0.0, 1.0], 100);
+        integration.set_frequency(200);
+    }
+}
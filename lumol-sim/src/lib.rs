@@ -33,17 +33,31 @@
 mod propagator;
 pub use self::propagator::Propagator;
 pub use self::propagator::TemperatureStrategy;
+pub use self::propagator::SeedSequence;
+pub use self::propagator::PropagatorState;
+pub use self::propagator::SimulationError;
+
+mod rng;
 
 pub mod output;
 pub mod md;
 pub mod mc;
 pub mod min;
 
+mod single_point;
+pub use self::single_point::SinglePoint;
+
 mod simulations;
-pub use self::mc::MonteCarlo;
+pub use self::mc::{Gibbs, MonteCarlo};
 pub use self::md::MolecularDynamics;
 pub use self::min::Minimization;
 pub use self::simulations::Simulation;
 
 mod velocities;
 pub use self::velocities::{InitVelocities, BoltzmannVelocities, UniformVelocities};
+
+mod ramp;
+pub use self::ramp::{TemperatureRamp, RampMode};
+
+mod free_energy;
+pub use self::free_energy::{ThermodynamicIntegration, UmbrellaSampling};
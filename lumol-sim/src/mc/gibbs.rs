@@ -0,0 +1,546 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Gibbs ensemble Monte Carlo propagator implementation
+use rand::Rng;
+
+use log::{info, trace};
+use log_once::warn_once;
+use soa_derive::soa_zip;
+
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::{DegreesOfFreedom, EnergyCache, Matrix3, Molecule, Particle, System, UnitCell, Vector3D};
+
+use crate::propagator::{Propagator, PropagatorState, SeedSequence, TemperatureStrategy};
+use crate::rng::PropagatorRng;
+use super::moves::smaller_than_cutoff;
+
+/// Description of the moves performed by the Gibbs ensemble propagator
+const MOVES: [&str; 3] = ["particle translation", "volume exchange", "particle transfer"];
+
+/// Gibbs ensemble Monte Carlo propagator [Panagiotopoulos1987].
+///
+/// This propagator simulates the coexistence of two phases (for example a
+/// liquid and its vapor) in two separated simulation boxes, without an
+/// explicit interface between them. The first box is the system given to the
+/// simulation, and the second box is owned by the propagator. Three kinds of
+/// moves are performed:
+///
+/// - translation of a random particle in one of the boxes;
+/// - exchange of volume between the two boxes, at constant total volume;
+/// - transfer of a random particle from one box to the other, at constant
+///   total number of particles.
+///
+/// The particles are transferred with their name, mass and charge, so all the
+/// molecules in the two boxes must contain a single particle, and only pair
+/// interactions are supported. The second box is part of the propagator
+/// state, and is saved and restored together with the random number
+/// generator and the moves statistics.
+///
+/// [Panagiotopoulos1987]: Panagiotopoulos, A. Z. Mol. Phys. 61, 813 (1987).
+pub struct Gibbs {
+    /// Random number generator for the simulation
+    rng: PropagatorRng,
+    /// Boltzmann factor: beta = 1/(kB * T)
+    beta: f64,
+    /// The second simulation box
+    other: System,
+    /// Energy caches for the first and second boxes
+    caches: [EnergyCache; 2],
+    /// Maximal displacement for the particles translations
+    delta: f64,
+    /// Maximal change in ln(V1 / V2) for the volume exchanges
+    volume_delta: f64,
+    /// Cumulative frequencies of the translation, volume exchange and
+    /// transfer moves
+    frequencies: [f64; 3],
+    /// Number of attempted moves, for each kind of move
+    attempted: [u64; 3],
+    /// Number of accepted moves, for each kind of move
+    accepted: [u64; 3],
+}
+
+impl Gibbs {
+    /// Create a new Gibbs ensemble propagator at the given `temperature`,
+    /// using `other` as the second simulation box.
+    pub fn new(temperature: f64, other: System) -> Gibbs {
+        return Gibbs::with_propagator_rng(temperature, other, PropagatorRng::new());
+    }
+
+    /// Create a new Gibbs ensemble propagator at the given `temperature`,
+    /// using `other` as the second simulation box and the `rng` random number
+    /// generator. The state of this generator is not saved nor restored with
+    /// the state of the propagator.
+    pub fn from_rng(temperature: f64, other: System, rng: Box<dyn rand::RngCore>) -> Gibbs {
+        return Gibbs::with_propagator_rng(temperature, other, PropagatorRng::Custom(rng));
+    }
+
+    fn with_propagator_rng(temperature: f64, other: System, rng: PropagatorRng) -> Gibbs {
+        assert!(temperature > 0.0, "Gibbs ensemble temperature must be positive, got {}", temperature);
+        let mut gibbs = Gibbs {
+            rng: rng,
+            beta: 1.0 / (K_BOLTZMANN * temperature),
+            other: other,
+            caches: [EnergyCache::new(), EnergyCache::new()],
+            delta: 1.0,
+            volume_delta: 0.05,
+            frequencies: [0.0; 3],
+            attempted: [0; 3],
+            accepted: [0; 3],
+        };
+        gibbs.set_move_frequencies(100.0, 1.0, 10.0);
+        return gibbs;
+    }
+
+    /// Get the second simulation box
+    pub fn other(&self) -> &System {
+        &self.other
+    }
+
+    /// Set the maximal displacement for the translation of particles to
+    /// `delta`. The default value is 1 Å.
+    pub fn set_translation_amplitude(&mut self, delta: f64) {
+        assert!(delta > 0.0, "translation amplitude must be positive in Gibbs ensemble, got {}", delta);
+        self.delta = delta;
+    }
+
+    /// Set the maximal change in the logarithm of the volumes ratio of the
+    /// two boxes $\ln(V_1 / V_2)$ for the volume exchange moves to `delta`.
+    /// The default value is 0.05.
+    pub fn set_volume_amplitude(&mut self, delta: f64) {
+        assert!(delta > 0.0, "volume amplitude must be positive in Gibbs ensemble, got {}", delta);
+        self.volume_delta = delta;
+    }
+
+    /// Set the relative frequencies of the particles translations, volume
+    /// exchanges, and particles transfers. The default frequencies are 100,
+    /// 1 and 10 respectively.
+    pub fn set_move_frequencies(&mut self, translation: f64, volume: f64, transfer: f64) {
+        assert!(
+            translation >= 0.0 && volume >= 0.0 && transfer >= 0.0,
+            "Gibbs ensemble moves frequencies must be positive"
+        );
+        let sum = translation + volume + transfer;
+        assert!(sum > 0.0, "at least one Gibbs ensemble move must have a non-zero frequency");
+        self.frequencies = [translation / sum, (translation + volume) / sum, 1.0];
+    }
+
+    /// Get the acceptance ratio of the three kind of moves since the
+    /// beginning of the simulation, together with the moves descriptions.
+    pub fn acceptance_ratios(&self) -> Vec<(&str, f64)> {
+        (0..3).map(|i| {
+            let ratio = if self.attempted[i] == 0 {
+                0.0
+            } else {
+                self.accepted[i] as f64 / self.attempted[i] as f64
+            };
+            (MOVES[i], ratio)
+        }).collect()
+    }
+
+    /// Get the temperature of the simulation
+    pub fn temperature(&self) -> f64 {
+        1.0 / (self.beta * K_BOLTZMANN)
+    }
+
+    /// Set the temperature of the simulation
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.beta = 1.0 / (temperature * K_BOLTZMANN);
+    }
+
+    /// Translate a random particle in one of the boxes
+    fn translate(&mut self, system: &mut System) -> bool {
+        let id = usize::from(self.rng.gen::<bool>());
+        let target = if id == 0 { system } else { &mut self.other };
+        if target.size() == 0 {
+            return false;
+        }
+
+        let i = self.rng.gen_range(0..target.size());
+        let displacement = Vector3D::new(
+            self.rng.gen_range(-self.delta..self.delta),
+            self.rng.gen_range(-self.delta..self.delta),
+            self.rng.gen_range(-self.delta..self.delta),
+        );
+
+        // All the molecules contain a single particle, so the molecule and
+        // particle indexes are the same.
+        let mut new_position = target.particles().position[i] + displacement;
+        target.cell.wrap_vector(&mut new_position);
+        let cost = self.caches[id].move_molecule_cost(target, i, &[new_position]);
+
+        if !accept(&mut self.rng, -self.beta * cost) {
+            self.caches[id].reject();
+            return false;
+        }
+
+        target.particles_mut().position[i] = new_position;
+        self.caches[id].update(target);
+        return true;
+    }
+
+    /// Exchange volume between the two boxes, keeping the total volume
+    /// constant. This performs a random walk in $\ln(V_1 / V_2)$.
+    fn exchange_volume(&mut self, system: &mut System) -> bool {
+        let old_volumes = [system.volume(), self.other.volume()];
+        let total = old_volumes[0] + old_volumes[1];
+
+        let ln_ratio = f64::ln(old_volumes[0] / old_volumes[1]) + self.volume_delta * (2.0 * self.rng.gen::<f64>() - 1.0);
+        let new_volume = total / (1.0 + f64::exp(-ln_ratio));
+        let new_volumes = [new_volume, total - new_volume];
+
+        let (first_cell, first_positions) = scaled_box(system, new_volumes[0]);
+        let (second_cell, second_positions) = scaled_box(&self.other, new_volumes[1]);
+
+        // Reject the move when one of the boxes becomes smaller than twice
+        // the cutoff radius
+        let too_small = |target: &System, cell: &UnitCell| match target.maximum_cutoff() {
+            Some(cutoff) => smaller_than_cutoff(cell, cutoff),
+            None => false,
+        };
+        if too_small(system, &first_cell) || too_small(&self.other, &second_cell) {
+            warn_once!(
+                "Rejected a Gibbs ensemble volume exchange making one of the boxes \
+                 smaller than twice the interactions cut off radius. You can try \
+                 to increase the boxes size or the number of particles."
+            );
+            return false;
+        }
+
+        let cost = self.caches[0].move_volume_cost(system, first_cell, &first_positions)
+                 + self.caches[1].move_volume_cost(&self.other, second_cell, &second_positions);
+        let mut log_probability = -self.beta * cost;
+        for (size, (new, old)) in [system.size(), self.other.size()].iter().zip(new_volumes.iter().zip(&old_volumes)) {
+            log_probability += (*size as f64 + 1.0) * f64::ln(new / old);
+        }
+
+        if !accept(&mut self.rng, log_probability) {
+            self.caches[0].reject();
+            self.caches[1].reject();
+            return false;
+        }
+
+        system.cell = first_cell;
+        system.particles_mut().position.copy_from_slice(&first_positions);
+        self.caches[0].update(system);
+
+        self.other.cell = second_cell;
+        self.other.particles_mut().position.copy_from_slice(&second_positions);
+        self.caches[1].update(&mut self.other);
+        return true;
+    }
+
+    /// Transfer a random particle from one box to a random position in the
+    /// other box
+    fn transfer(&mut self, system: &mut System) -> bool {
+        let [first_cache, second_cache] = &mut self.caches;
+        let (source, source_cache, destination, destination_cache) = if self.rng.gen::<bool>() {
+            (&mut self.other, second_cache, system, first_cache)
+        } else {
+            (system, first_cache, &mut self.other, second_cache)
+        };
+
+        let source_size = source.size();
+        if source_size == 0 {
+            return false;
+        }
+        let source_volume = source.volume();
+        let destination_size = destination.size();
+        let destination_volume = destination.volume();
+
+        // All the molecules contain a single particle, so the molecule and
+        // particle indexes are the same.
+        let i = self.rng.gen_range(0..source_size);
+        let fractional = Vector3D::new(self.rng.gen(), self.rng.gen(), self.rng.gen());
+        let particles = source.particles();
+        // Build a new particle instead of copying the molecule, which would
+        // keep the bonding indexes from the source box
+        let particle = Particle {
+            name: particles.name[i].clone(),
+            kind: particles.kind[i],
+            charge: particles.charge[i],
+            mass: particles.mass[i],
+            position: destination.cell.cartesian(&fractional),
+            velocity: particles.velocity[i],
+            coupling: particles.coupling[i],
+            orientation: particles.orientation[i],
+        };
+
+        let removal = source_cache.remove_particle_cost(source, i);
+        let insertion = destination_cache.insert_particle_cost(destination, &particle);
+
+        let log_probability = -self.beta * (removal + insertion) + f64::ln(
+            (source_size as f64 * destination_volume) / ((destination_size as f64 + 1.0) * source_volume)
+        );
+
+        if !accept(&mut self.rng, log_probability) {
+            source_cache.reject();
+            destination_cache.reject();
+            return false;
+        }
+
+        source.remove_molecule(i);
+        source_cache.update(source);
+        destination.add_molecule(Molecule::new(particle));
+        destination_cache.update(destination);
+        return true;
+    }
+
+    /// Log the number of attempts and the acceptance ratio of all moves
+    fn log_acceptance(&self) {
+        for (i, (description, ratio)) in self.acceptance_ratios().into_iter().enumerate() {
+            info!(
+                "    {}: {} attempts -- {:2.1} % accepted",
+                description,
+                self.attempted[i],
+                ratio * 100.0
+            );
+        }
+    }
+}
+
+/// Get the unit cell of `system` scaled isotropically to the given `volume`,
+/// and the particles positions scaled accordingly.
+fn scaled_box(system: &System, volume: f64) -> (UnitCell, Vec<Vector3D>) {
+    let factor = f64::cbrt(volume / system.volume());
+    let cell = system.cell.scale(factor * Matrix3::one());
+    let positions = system.particles().position.iter().map(|position| *position * factor).collect();
+    return (cell, positions);
+}
+
+/// Metropolis acceptance criterion, given the logarithm of the acceptance
+/// probability
+fn accept(rng: &mut PropagatorRng, log_probability: f64) -> bool {
+    log_probability >= 0.0 || rng.gen::<f64>() < f64::exp(log_probability)
+}
+
+impl Propagator for Gibbs {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::External(self.temperature())
+    }
+
+    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+
+    fn seed(&mut self, seeds: &mut SeedSequence) {
+        self.rng = PropagatorRng::seeded(seeds.next_seed());
+    }
+
+    fn can_set_temperature(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        Gibbs::set_temperature(self, temperature);
+    }
+
+    fn save_state(&mut self, state: &mut PropagatorState) {
+        self.rng.save_state("gibbs.rng", state);
+        for i in 0..3 {
+            state.set(&format!("gibbs.move.{}.attempted", i), self.attempted[i]);
+            state.set(&format!("gibbs.move.{}.accepted", i), self.accepted[i]);
+        }
+
+        // The volume exchanges scale the second box isotropically, and all
+        // the molecules contain a single particle.
+        state.set("gibbs.other.volume", self.other.volume());
+        state.set("gibbs.other.size", self.other.size());
+        let particles = self.other.particles();
+        for (i, (name, mass, charge, position)) in soa_zip!(particles, [name, mass, charge, position]).enumerate() {
+            state.set(&format!("gibbs.other.{}.name", i), name);
+            state.set(&format!("gibbs.other.{}.mass", i), mass);
+            state.set(&format!("gibbs.other.{}.charge", i), charge);
+            for (j, value) in position.iter().enumerate() {
+                state.set(&format!("gibbs.other.{}.position.{}", i, j), value);
+            }
+        }
+    }
+
+    fn restore_state(&mut self, state: &PropagatorState) {
+        self.rng.restore_state("gibbs.rng", state);
+        for i in 0..3 {
+            self.attempted[i] = state.expect(&format!("gibbs.move.{}.attempted", i));
+            self.accepted[i] = state.expect(&format!("gibbs.move.{}.accepted", i));
+        }
+
+        let volume = state.expect::<f64>("gibbs.other.volume");
+        let factor = f64::cbrt(volume / self.other.volume());
+        self.other.cell = self.other.cell.scale(factor * Matrix3::one());
+
+        while let Some(last) = self.other.molecules().count().checked_sub(1) {
+            self.other.remove_molecule(last);
+        }
+        let size = state.expect::<usize>("gibbs.other.size");
+        for i in 0..size {
+            let mut particle = Particle::new(state.expect::<String>(&format!("gibbs.other.{}.name", i)));
+            particle.mass = state.expect(&format!("gibbs.other.{}.mass", i));
+            particle.charge = state.expect(&format!("gibbs.other.{}.charge", i));
+            for j in 0..3 {
+                particle.position[j] = state.expect(&format!("gibbs.other.{}.position.{}", i, j));
+            }
+            self.other.add_molecule(Molecule::new(particle));
+        }
+    }
+
+    fn setup(&mut self, system: &System) {
+        for (i, target) in [system, &self.other].iter().enumerate() {
+            assert!(!target.cell.is_infinite(), "Gibbs ensemble simulations need periodic boxes");
+            assert!(
+                target.molecules().all(|molecule| molecule.size() == 1),
+                "Gibbs ensemble simulations only support molecules containing a single particle"
+            );
+            assert!(
+                target.coulomb_potential().is_none() && target.global_potentials().is_empty(),
+                "Gibbs ensemble simulations only support pair interactions"
+            );
+            self.caches[i].init(target);
+        }
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        let probability = self.rng.gen::<f64>();
+        let (i, _) = self.frequencies.iter()
+                         .enumerate()
+                         .find(|&(_, f)| probability < *f)
+                         .expect("Could not find a move in Gibbs ensemble moves list");
+        trace!("Selected move is '{}'", MOVES[i]);
+
+        let accepted = match i {
+            0 => self.translate(system),
+            1 => self.exchange_volume(system),
+            2 => self.transfer(system),
+            _ => unreachable!(),
+        };
+
+        self.attempted[i] += 1;
+        if accepted {
+            trace!("    --> Move was accepted");
+            self.accepted[i] += 1;
+        } else {
+            trace!("    --> Move was rejected");
+        }
+    }
+
+    fn finish(&mut self, system: &System) {
+        info!("Gibbs ensemble simulation summary");
+        self.log_acceptance();
+        info!(
+            "    first box: {} particles in {} A^3, second box: {} particles in {} A^3",
+            system.size(), system.volume(), self.other.size(), self.other.volume()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumol_core::{LennardJones, PairInteraction};
+
+    use approx::assert_relative_eq;
+
+    fn argon(cell: f64, n: usize) -> System {
+        let mut system = System::with_cell(UnitCell::cubic(cell));
+        let spacing = cell / n as f64;
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let position = Vector3D::new(i as f64, j as f64, k as f64) * spacing;
+                    system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+                }
+            }
+        }
+
+        let mut lj = PairInteraction::new(Box::new(LennardJones {
+            sigma: 3.405,
+            epsilon: 119.8 * K_BOLTZMANN,
+        }), 8.5);
+        lj.enable_tail_corrections();
+        system.set_pair_potential(("Ar", "Ar"), lj).unwrap();
+        return system;
+    }
+
+    #[test]
+    fn conservation() {
+        let mut system = argon(20.0, 4);
+        let other = argon(25.0, 3);
+        let total_size = system.size() + other.size();
+        let total_volume = system.volume() + other.volume();
+
+        let mut gibbs = Gibbs::new(150.0, other);
+        gibbs.set_move_frequencies(10.0, 1.0, 10.0);
+        gibbs.setup(&system);
+        for _ in 0..2000 {
+            gibbs.propagate(&mut system);
+        }
+
+        for ratio in gibbs.acceptance_ratios() {
+            assert!(ratio.1 > 0.0, "no accepted {}", ratio.0);
+        }
+
+        // The total number of particles and the total volume are conserved
+        assert_eq!(system.size() + gibbs.other().size(), total_size);
+        assert_relative_eq!(system.volume() + gibbs.other().volume(), total_volume, max_relative = 1e-12);
+
+        // The energies are correctly updated after each move
+        assert_relative_eq!(gibbs.caches[0].energy(), system.potential_energy(), max_relative = 1e-9);
+        assert_relative_eq!(gibbs.caches[1].energy(), gibbs.other().potential_energy(), max_relative = 1e-9);
+    }
+
+    #[test]
+    fn small_boxes() {
+        // The cutoff is 8.5 A, the boxes can not become smaller than 17 A
+        let mut system = argon(24.0, 3);
+        let other = argon(17.5, 2);
+
+        let mut gibbs = Gibbs::new(150.0, other);
+        gibbs.set_move_frequencies(0.0, 1.0, 0.0);
+        gibbs.set_volume_amplitude(0.5);
+        gibbs.setup(&system);
+        for _ in 0..200 {
+            gibbs.propagate(&mut system);
+            assert!(system.cell.lengths().iter().all(|&length| length > 17.0));
+            assert!(gibbs.other().cell.lengths().iter().all(|&length| length > 17.0));
+        }
+        assert_relative_eq!(gibbs.caches[0].energy(), system.potential_energy(), max_relative = 1e-9);
+        assert_relative_eq!(gibbs.caches[1].energy(), gibbs.other().potential_energy(), max_relative = 1e-9);
+    }
+
+    #[test]
+    fn save_restore() {
+        let mut system = argon(20.0, 4);
+        let mut gibbs = Gibbs::new(150.0, argon(25.0, 3));
+        gibbs.set_move_frequencies(10.0, 1.0, 10.0);
+        gibbs.setup(&system);
+        for _ in 0..500 {
+            gibbs.propagate(&mut system);
+        }
+
+        let mut state = PropagatorState::new();
+        gibbs.save_state(&mut state);
+        let mut restored_system = system.clone();
+        let mut restored = Gibbs::new(150.0, argon(25.0, 3));
+        restored.set_move_frequencies(10.0, 1.0, 10.0);
+        restored.restore_state(&state);
+        assert_eq!(restored.other().size(), gibbs.other().size());
+        assert_relative_eq!(restored.other().volume(), gibbs.other().volume(), max_relative = 1e-12);
+        assert_eq!(restored.acceptance_ratios(), gibbs.acceptance_ratios());
+
+        restored.setup(&restored_system);
+        for _ in 0..500 {
+            gibbs.propagate(&mut system);
+            restored.propagate(&mut restored_system);
+        }
+        assert_eq!(restored_system.particles().position, system.particles().position);
+        assert_eq!(restored.other().particles().position, gibbs.other().particles().position);
+    }
+
+    #[test]
+    #[should_panic(expected = "Gibbs ensemble simulations only support molecules containing a single particle")]
+    fn molecules() {
+        let mut system = argon(20.0, 2);
+        let _ = system.add_bond(0, 1);
+        let mut gibbs = Gibbs::new(150.0, argon(20.0, 2));
+        gibbs.setup(&system);
+    }
+}
@@ -7,4 +7,7 @@ pub use self::monte_carlo::{MonteCarlo, MonteCarloBuilder};
 
 mod moves;
 pub use self::moves::{MCDegreeOfFreedom, MCMove};
-pub use self::moves::{Resize, Rotate, Translate};
+pub use self::moves::{DihedralRotation, Resize, ResizeMode, Rotate, Translate};
+
+mod gibbs;
+pub use self::gibbs::Gibbs;
@@ -4,14 +4,15 @@
 //! Metropolis Monte Carlo propagator implementation
 use std::ops::{Deref, DerefMut};
 
-use rand::{self, Rng, SeedableRng};
+use rand::{self, Rng};
 
-use log::{warn, info, trace};
+use log::{debug, warn, info, trace};
 
 use lumol_core::consts::K_BOLTZMANN;
 use lumol_core::{DegreesOfFreedom, EnergyCache, System};
 
-use crate::propagator::{Propagator, TemperatureStrategy};
+use crate::propagator::{Propagator, PropagatorState, SeedSequence, TemperatureStrategy};
+use crate::rng::PropagatorRng;
 use super::{MCDegreeOfFreedom, MCMove};
 
 /// This struct keeps a move and some statistics on the move (number of times
@@ -130,23 +131,30 @@ impl DerefMut for Move {
 pub struct MonteCarlo {
     /// Random number generator for the simulation. All random state will be
     /// taken from this.
-    rng: Box<dyn rand::RngCore>,
+    rng: PropagatorRng,
     /// Boltzmann factor: beta = 1/(kB * T)
     beta: f64,
     /// List of possible Monte Carlo moves
     moves: Vec<Move>,
+    /// Weights of the Monte Carlo moves, as given by the user
+    weights: Vec<f64>,
     /// Cummulative frequencies of the Monte Carlo moves
     frequencies: Vec<f64>,
     /// Specifies the number of moves after which an update of a move's
     /// amplitude is performed.
     update_frequency: u64,
+    /// Specifies the number of steps after which the acceptance ratios of
+    /// all moves are logged. A value of 0 disables the logging.
+    report_frequency: u64,
+    /// Number of steps performed by this propagator
+    steps: u64,
     /// Cache for faster energy computation
     cache: EnergyCache,
 }
 
 /// Builder for `MonteCarlo` struct
 pub struct MonteCarloBuilder {
-    rng: Box<dyn rand::RngCore>,
+    rng: PropagatorRng,
     beta: f64,
     moves: Vec<Move>,
     frequencies: Vec<f64>,
@@ -155,16 +163,17 @@ pub struct MonteCarloBuilder {
 impl MonteCarloBuilder {
     /// Create a new Monte Carlo propagator at temperature `T`.
     pub fn new(temperature: f64) -> MonteCarloBuilder {
-        let rng = Box::new(rand_xorshift::XorShiftRng::from_seed([
-            0xeb, 0xa8, 0xe4, 0x29, 0xca, 0x60, 0x44, 0xb0,
-            0xd3, 0x77, 0xc6, 0xa0, 0x21, 0x71, 0x37, 0xf7,
-        ]));
-        return MonteCarloBuilder::from_rng(temperature, rng);
+        return MonteCarloBuilder::with_propagator_rng(temperature, PropagatorRng::new());
     }
 
     /// Create a Monte Carlo propagator at temperature `T`, using the `rng`
-    /// random number generator.
+    /// random number generator. The state of this generator is not saved
+    /// nor restored with the state of the propagator.
     pub fn from_rng(temperature: f64, rng: Box<dyn rand::RngCore>) -> MonteCarloBuilder {
+        return MonteCarloBuilder::with_propagator_rng(temperature, PropagatorRng::Custom(rng));
+    }
+
+    fn with_propagator_rng(temperature: f64, rng: PropagatorRng) -> MonteCarloBuilder {
         assert!(temperature > 0.0, "Monte Carlo temperature must be positive, got {}", temperature);
         MonteCarloBuilder {
             beta: 1.0 / (K_BOLTZMANN * temperature),
@@ -183,57 +192,144 @@ impl MonteCarloBuilder {
         frequency: f64,
         target_acceptance: impl Into<Option<f64>>,
     ) {
+        assert!(frequency >= 0.0, "Monte Carlo move frequency must be positive, got {}", frequency);
         self.moves.push(Move::new(mcmove, target_acceptance.into()));
         self.frequencies.push(frequency);
     }
 
-    fn normalize_frequencies(&mut self) {
+    /// Normalize the frequencies for all moves and get the corresponding
+    /// `MonteCarlo` propagator
+    pub fn finish(self) -> MonteCarlo {
         assert_eq!(self.frequencies.len(), self.moves.len());
         if self.frequencies.is_empty() {
             warn!("No move in the Monte Carlo simulation, did you forget to specify them?");
-            return;
         }
 
-        // Normalize the frequencies
-        let sum = self.frequencies.iter().fold(0.0, |sum, &f| sum + f);
-        for frequency in &mut self.frequencies {
-            *frequency /= sum;
-        }
-        // Make the frequencies vector contain cumulative frequencies
-        for i in 1..self.frequencies.len() {
-            self.frequencies[i] += self.frequencies[i - 1];
-        }
-        let last = self.frequencies.len() - 1;
-        self.frequencies[last] = 1.0;
-    }
-
-    /// Normalize the frequencies for all moves and get the corresponding
-    /// `MonteCarlo` propagator
-    pub fn finish(mut self) -> MonteCarlo {
-        self.normalize_frequencies();
         MonteCarlo {
             rng: self.rng,
             beta: self.beta,
             moves: self.moves,
-            frequencies: self.frequencies,
+            frequencies: cumulative_frequencies(&self.frequencies),
+            weights: self.frequencies,
             update_frequency: 0,
+            report_frequency: 0,
+            steps: 0,
             cache: EnergyCache::new(),
         }
     }
 }
 
+/// Normalize the `weights` of a set of moves, and get the corresponding
+/// cumulative frequencies.
+fn cumulative_frequencies(weights: &[f64]) -> Vec<f64> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+
+    let sum = weights.iter().sum::<f64>();
+    assert!(sum > 0.0, "at least one Monte Carlo move must have a non-zero frequency");
+
+    let mut frequencies = Vec::with_capacity(weights.len());
+    let mut cumulative = 0.0;
+    for weight in weights {
+        cumulative += weight / sum;
+        frequencies.push(cumulative);
+    }
+    // Make sure that rounding errors can not prevent the selection of the
+    // last move
+    let last = frequencies.len() - 1;
+    frequencies[last] = 1.0;
+    return frequencies;
+}
+
 impl MonteCarlo {
+    /// Set the frequency of the move at index `index` (in the order moves
+    /// were added to the builder) to `weight`. The selection probabilities of
+    /// all moves are normalized again, and setting a weight of 0 disables the
+    /// move. This can be used at any point of a simulation, for example to
+    /// only enable volume moves after the equilibration.
+    pub fn set_move_weight(&mut self, index: usize, weight: f64) {
+        assert!(index < self.moves.len(), "invalid move index {}, there are {} moves", index, self.moves.len());
+        assert!(weight >= 0.0, "Monte Carlo move frequency must be positive, got {}", weight);
+        self.weights[index] = weight;
+        self.frequencies = cumulative_frequencies(&self.weights);
+    }
+
+    /// Get the normalized probabilities to select each move, in the order
+    /// moves were added to the builder.
+    pub fn move_probabilities(&self) -> Vec<f64> {
+        let mut previous = 0.0;
+        self.frequencies.iter().map(|&frequency| {
+            let probability = frequency - previous;
+            previous = frequency;
+            probability
+        }).collect()
+    }
+
     /// Set the number of times a move has to be called before its amplitude
     /// is updated. This value is applied to all moves.
     pub fn set_amplitude_update_frequency(&mut self, frequency: u64) {
         self.update_frequency = frequency;
     }
 
+    /// Set the hard-core distance used to reject moves creating overlaps
+    /// between molecules without computing their full energy. Using 0 (the
+    /// default) disables this check.
+    pub fn set_hard_core(&mut self, distance: f64) {
+        self.cache.set_hard_core(distance);
+    }
+
+    /// Set the number of steps between two reports of the acceptance ratios
+    /// of the moves in the logs. Using 0 (the default) disables the reports,
+    /// the acceptance ratios are then only logged at the end of the
+    /// simulation.
+    pub fn set_acceptance_report_frequency(&mut self, frequency: u64) {
+        self.report_frequency = frequency;
+    }
+
+    /// Get the acceptance ratio of all the moves since the beginning of the
+    /// simulation, together with the moves descriptions. The moves are
+    /// returned in the same order they were added to the propagator, and
+    /// moves that were never attempted have an acceptance ratio of 0.
+    pub fn acceptance_ratios(&self) -> Vec<(&str, f64)> {
+        self.moves.iter().map(|mc_move| (mc_move.describe(), mc_move.acceptance())).collect()
+    }
+
+    /// Get the current amplitude of all the moves, together with the moves
+    /// descriptions. The moves are returned in the same order they were added
+    /// to the propagator, and moves without an amplitude give `None`.
+    pub fn amplitudes(&self) -> Vec<(&str, Option<f64>)> {
+        self.moves.iter().map(|mc_move| (mc_move.describe(), mc_move.amplitude())).collect()
+    }
+
     /// Get the temperature of the simulation
     pub fn temperature(&self) -> f64 {
         1.0 / (self.beta * K_BOLTZMANN)
     }
 
+    /// Log the number of attempts, the acceptance ratio and the current
+    /// amplitude of all moves
+    fn log_acceptance(&self) {
+        for mc_move in &self.moves {
+            if let Some(amplitude) = mc_move.amplitude() {
+                info!(
+                    "    {}: {} attempts -- {:2.1} % accepted -- amplitude {}",
+                    mc_move.describe(),
+                    mc_move.total_attempted,
+                    mc_move.acceptance() * 100.0,
+                    amplitude
+                );
+            } else {
+                info!(
+                    "    {}: {} attempts -- {:2.1} % accepted",
+                    mc_move.describe(),
+                    mc_move.total_attempted,
+                    mc_move.acceptance() * 100.0
+                );
+            }
+        }
+    }
+
     /// Set the temperature of the simulation
     pub fn set_temperature(&mut self, temperature: f64) {
         self.beta = 1.0 / (temperature * K_BOLTZMANN);
@@ -245,6 +341,54 @@ impl Propagator for MonteCarlo {
         TemperatureStrategy::External(self.temperature())
     }
 
+    fn seed(&mut self, seeds: &mut SeedSequence) {
+        self.rng = PropagatorRng::seeded(seeds.next_seed());
+    }
+
+    fn can_set_temperature(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        MonteCarlo::set_temperature(self, temperature);
+    }
+
+    fn save_state(&mut self, state: &mut PropagatorState) {
+        self.rng.save_state("mc.rng", state);
+        state.set("mc.steps", self.steps);
+        state.set("mc.moves", self.moves.len());
+        for (i, mc_move) in self.moves.iter().enumerate() {
+            if let Some(amplitude) = mc_move.amplitude() {
+                state.set(&format!("mc.move.{}.amplitude", i), amplitude);
+            }
+            state.set(&format!("mc.move.{}.total_attempted", i), mc_move.total_attempted);
+            state.set(&format!("mc.move.{}.total_accepted", i), mc_move.total_accepted);
+            state.set(&format!("mc.move.{}.attempted", i), mc_move.attempted);
+            state.set(&format!("mc.move.{}.accepted", i), mc_move.accepted);
+        }
+    }
+
+    fn restore_state(&mut self, state: &PropagatorState) {
+        let nmoves = state.expect::<usize>("mc.moves");
+        assert_eq!(
+            nmoves, self.moves.len(),
+            "can not restore the state of a Monte Carlo simulation with {} moves \
+             from a state with {} moves", self.moves.len(), nmoves
+        );
+
+        self.rng.restore_state("mc.rng", state);
+        self.steps = state.expect("mc.steps");
+        for (i, mc_move) in self.moves.iter_mut().enumerate() {
+            if let Some(amplitude) = state.get(&format!("mc.move.{}.amplitude", i)) {
+                mc_move.set_amplitude(amplitude);
+            }
+            mc_move.total_attempted = state.expect(&format!("mc.move.{}.total_attempted", i));
+            mc_move.total_accepted = state.expect(&format!("mc.move.{}.total_accepted", i));
+            mc_move.attempted = state.expect(&format!("mc.move.{}.attempted", i));
+            mc_move.accepted = state.expect(&format!("mc.move.{}.accepted", i));
+        }
+    }
+
     fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
         if self.moves.is_empty() {
             return DegreesOfFreedom::Particles;
@@ -287,12 +431,25 @@ impl Propagator for MonteCarlo {
     }
 
     fn propagate(&mut self, system: &mut System) {
+        if self.cache.is_outdated(system) {
+            debug!("Interactions changed, re-initializing the energy cache");
+            self.cache.init(system);
+        }
+
+        self.steps += 1;
+        if self.report_frequency != 0 && self.steps % self.report_frequency == 0 {
+            info!("Monte Carlo acceptance ratios and amplitudes after {} steps", self.steps);
+            self.log_acceptance();
+        }
+
         let current_move = {
             let probability: f64 = self.rng.gen();
-            // Get the index of the first move with frequency >= probability.
+            // Get the index of the first move with frequency > probability.
+            // Moves with a zero weight have the same cumulative frequency as
+            // the previous one, and are never selected.
             let (i, _) = self.frequencies.iter()
                              .enumerate()
-                             .find(|&(_, f)| probability <= *f)
+                             .find(|&(_, f)| probability < *f)
                              .expect("Could not find a move in MonteCarlo moves list");
             &mut self.moves[i]
         };
@@ -318,6 +475,7 @@ impl Propagator for MonteCarlo {
         } else {
             trace!("    --> Move was rejected");
             current_move.restore(system);
+            self.cache.reject();
             current_move.reject();
         }
 
@@ -332,14 +490,7 @@ impl Propagator for MonteCarlo {
     /// Print some informations about moves to screen
     fn finish(&mut self, _: &System) {
         info!("Monte Carlo simulation summary");
-        for mc_move in &self.moves {
-            info!(
-                "    {}: {} attempts -- {:2.1} % accepted",
-                mc_move.describe(),
-                mc_move.total_attempted,
-                mc_move.acceptance() * 100.0
-            );
-        }
+        self.log_acceptance();
     }
 }
 
@@ -348,8 +499,11 @@ mod tests {
     use super::*;
     use rand::RngCore;
     use crate::propagator::Propagator;
-    use crate::mc::{MCDegreeOfFreedom, MCMove};
-    use lumol_core::{EnergyCache, System};
+    use crate::mc::{MCDegreeOfFreedom, MCMove, Translate};
+    use lumol_core::{EnergyCache, System, UnitCell};
+    use lumol_core::{LennardJones, Molecule, PairInteraction, Particle, Vector3D};
+
+    use approx::assert_ulps_eq;
 
     struct DummyMove;
     impl MCMove for DummyMove {
@@ -363,7 +517,8 @@ mod tests {
         fn prepare(&mut self, _: &mut System, _: &mut dyn RngCore) -> bool {
             true
         }
-        fn cost(&self, _: &System, _: f64, _: &mut EnergyCache) -> f64 {
+        fn cost(&self, _: &System, _: f64, cache: &mut EnergyCache) -> f64 {
+            cache.unused();
             0.0
         }
         fn apply(&mut self, _: &mut System) {}
@@ -393,6 +548,63 @@ mod tests {
         assert_eq!(mc.frequencies[2], 1.0);
     }
 
+    #[test]
+    fn move_weights() {
+        let mut builder = MonteCarloBuilder::new(100.0);
+        builder.add(Box::new(DummyMove), 13.0, None);
+        builder.add(Box::new(DummyMove), 2.0, None);
+        builder.add(Box::new(DummyMove), 5.0, None);
+
+        let mut mc = builder.finish();
+        let probabilities = mc.move_probabilities();
+        assert_ulps_eq!(probabilities[0], 0.65);
+        assert_ulps_eq!(probabilities[1], 0.1);
+        assert_ulps_eq!(probabilities[2], 0.25);
+
+        let mut system = System::new();
+        mc.setup(&system);
+
+        const N_STEPS: usize = 100_000;
+        let count = |mc: &mut MonteCarlo, system: &mut System| {
+            let before = mc.moves.iter().map(|m| m.total_attempted).collect::<Vec<_>>();
+            for _ in 0..N_STEPS {
+                mc.propagate(system);
+            }
+            mc.moves.iter().zip(before).map(|(m, before)| {
+                (m.total_attempted - before) as f64 / N_STEPS as f64
+            }).collect::<Vec<_>>()
+        };
+
+        // The standard deviation of the empirical frequencies is at most
+        // sqrt(0.25 / N_STEPS) ~ 0.0016
+        let empirical = count(&mut mc, &mut system);
+        for (frequency, probability) in empirical.iter().zip(mc.move_probabilities()) {
+            assert!(f64::abs(frequency - probability) < 0.01);
+        }
+
+        // Disable the first move
+        mc.set_move_weight(0, 0.0);
+        let probabilities = mc.move_probabilities();
+        assert_eq!(probabilities[0], 0.0);
+        assert_ulps_eq!(probabilities[1], 2.0 / 7.0);
+        assert_ulps_eq!(probabilities[2], 5.0 / 7.0);
+
+        let empirical = count(&mut mc, &mut system);
+        assert_eq!(empirical[0], 0.0);
+        for (frequency, probability) in empirical.iter().zip(mc.move_probabilities()) {
+            assert!(f64::abs(frequency - probability) < 0.01);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one Monte Carlo move must have a non-zero frequency")]
+    fn all_zero_weights() {
+        let mut builder = MonteCarloBuilder::new(100.0);
+        builder.add(Box::new(DummyMove), 1.0, None);
+        let mut mc = builder.finish();
+        mc.set_move_weight(0, 0.0);
+    }
+
     #[test]
     #[should_panic(expected = "Monte Carlo temperature must be positive, got -1")]
     fn negative_temperature() {
@@ -440,4 +652,117 @@ mod tests {
         counter.accepted = 55;
         assert_eq!(counter.scaling_factor(), Some(1.1));
     }
+
+    #[test]
+    fn amplitude_convergence() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..5 {
+            for j in 0..5 {
+                for k in 0..5 {
+                    let position = Vector3D::new(i as f64 * 4.0, j as f64 * 4.0, k as f64 * 4.0);
+                    system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+                }
+            }
+        }
+        let lj = Box::new(LennardJones { sigma: 3.4, epsilon: 1e-3 });
+        system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 8.0)).unwrap();
+
+        // Start with a very small amplitude, accepting almost all the moves
+        let mut builder = MonteCarloBuilder::new(100.0);
+        builder.add(Box::new(Translate::new(0.001, None)), 1.0, 0.4);
+        let mut mc = builder.finish();
+        mc.set_amplitude_update_frequency(100);
+        mc.setup(&system);
+
+        for _ in 0..20_000 {
+            mc.propagate(&mut system);
+        }
+        let attempted = mc.moves[0].total_attempted;
+        let accepted = mc.moves[0].total_accepted;
+
+        for _ in 0..5_000 {
+            mc.propagate(&mut system);
+        }
+        let attempted = mc.moves[0].total_attempted - attempted;
+        let accepted = mc.moves[0].total_accepted - accepted;
+        let acceptance = accepted as f64 / attempted as f64;
+        assert!(f64::abs(acceptance - 0.4) < 0.05, "acceptance is {}", acceptance);
+    }
+
+    #[test]
+    fn logged_amplitudes() {
+        use std::sync::{Arc, Mutex};
+        use std::thread::{self, ThreadId};
+
+        struct TestLogger {
+            messages: Arc<Mutex<Vec<(ThreadId, String)>>>,
+        }
+
+        impl log::Log for TestLogger {
+            fn enabled(&self, _: &log::Metadata<'_>) -> bool {true}
+
+            fn log(&self, record: &log::Record<'_>) {
+                if record.level() == log::Level::Info {
+                    let message = (thread::current().id(), record.args().to_string());
+                    self.messages.lock().unwrap().push(message);
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let logger = TestLogger {messages: messages.clone()};
+        log::set_boxed_logger(Box::new(logger)).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    let position = Vector3D::new(i as f64 * 5.0, j as f64 * 5.0, k as f64 * 5.0);
+                    system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+                }
+            }
+        }
+        let lj = Box::new(LennardJones { sigma: 3.4, epsilon: 1e-3 });
+        system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 8.0)).unwrap();
+
+        let mut builder = MonteCarloBuilder::new(100.0);
+        builder.add(Box::new(Translate::new(0.01, None)), 1.0, 0.4);
+        builder.add(Box::new(DummyMove), 1.0, None);
+        let mut mc = builder.finish();
+        mc.set_amplitude_update_frequency(20);
+        mc.set_acceptance_report_frequency(50);
+        mc.setup(&system);
+
+        let current = thread::current().id();
+        let mut reports = 0;
+        let mut amplitudes = Vec::new();
+        for _ in 0..2_000 {
+            // The report is logged before the move is performed
+            let expected = mc.amplitudes()[0].1.unwrap();
+            amplitudes.push(expected);
+            mc.propagate(&mut system);
+
+            let mut messages = messages.lock().unwrap();
+            for (_, message) in messages.drain(..).filter(|(id, _)| *id == current) {
+                if !message.contains("attempts") {
+                    continue;
+                }
+
+                if message.contains(&mc.amplitudes()[1].0) {
+                    assert!(!message.contains("amplitude"));
+                } else {
+                    let logged = message.split("amplitude ").nth(1).unwrap();
+                    assert_eq!(logged.parse::<f64>().unwrap(), expected);
+                    reports += 1;
+                }
+            }
+        }
+
+        assert_eq!(reports, 40);
+        // The amplitude was updated during the simulation
+        assert!(amplitudes.iter().any(|&amplitude| amplitude != 0.01));
+    }
 }
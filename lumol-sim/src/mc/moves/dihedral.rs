@@ -0,0 +1,199 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use rand::RngCore;
+use rand::seq::SliceRandom;
+use rand_distr::{Distribution, Uniform};
+
+use std::collections::BTreeSet;
+
+use log::warn;
+use log_once::warn_once;
+
+use soa_derive::soa_zip;
+
+use super::{MCDegreeOfFreedom, MCMove};
+use super::select_molecule;
+
+use lumol_core::{Bonding, EnergyCache, Matrix3, MoleculeHash, System, Vector3D};
+
+/// Monte Carlo move for rotating a part of a molecule around a bond, changing
+/// the dihedral angles around this bond.
+///
+/// The rotatable bonds are the bonds at the center of a dihedral angle which
+/// are not part of a ring. For each move, a random rotatable bond is selected,
+/// and the smallest part of the molecule on one side of the bond is rotated
+/// around the bond axis.
+pub struct DihedralRotation {
+    /// Hash of molecule to use. `None` means all molecules.
+    hash: Option<MoleculeHash>,
+    /// Index of the molecule to modify
+    molid: usize,
+    /// New positions of the atoms in the modified molecule
+    newpos: Vec<Vector3D>,
+    /// Maximum values for the range of the range distribution of the angle
+    theta: f64,
+    /// Range distribution, for generation of the angle
+    range: Uniform<f64>,
+}
+
+impl DihedralRotation {
+    /// Create a new `DihedralRotation` move, with maximum angular displacement
+    /// of `theta`. This move will apply to the molecules with the given
+    /// `hash`, or all molecules if `hash` is `None`.
+    ///
+    /// # Panics
+    ///
+    /// If `theta` is not positive.
+    pub fn new<H: Into<Option<MoleculeHash>>>(theta: f64, hash: H) -> DihedralRotation {
+        assert!(theta > 0.0, "theta must be positive in DihedralRotation move");
+        DihedralRotation {
+            hash: hash.into(),
+            molid: usize::MAX,
+            newpos: Vec::new(),
+            theta: theta,
+            range: Uniform::new(-theta, theta),
+        }
+    }
+}
+
+impl MCMove for DihedralRotation {
+    fn describe(&self) -> &str {
+        "dihedral angle rotation"
+    }
+
+    fn degrees_of_freedom(&self) -> MCDegreeOfFreedom {
+        // This move changes the internal configuration of the molecules
+        MCDegreeOfFreedom::Particles
+    }
+
+    fn setup(&mut self, _: &System) {}
+
+    fn prepare(&mut self, system: &mut System, rng: &mut dyn RngCore) -> bool {
+        if let Some(id) = select_molecule(system, self.hash, rng) {
+            self.molid = id;
+        } else {
+            warn!("Can not rotate dihedral angle: no molecule of this type in the system.");
+            return false;
+        }
+
+        let molecule = system.molecule(self.molid);
+        let rotatable = rotatable_bonds(&molecule);
+        let (fixed, moving) = if let Some(bond) = rotatable.choose(rng) {
+            bond.clone()
+        } else {
+            warn_once!("Can not rotate dihedral angle: no rotatable bond in the selected molecule.");
+            return false;
+        };
+
+        let origin = fixed.1;
+        let axis = system.nearest_image(origin, fixed.0);
+        let rotation = Matrix3::rotation(&axis, self.range.sample(rng));
+
+        let positions = system.particles().position;
+        let first = molecule.start();
+        self.newpos = molecule.particles().position.to_vec();
+        for i in moving {
+            let mut delta = positions[i] - positions[origin];
+            system.cell.vector_image(&mut delta);
+            self.newpos[i - first] = positions[origin] + rotation * delta;
+        }
+        true
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        return beta * cache.move_molecule_cost(system, self.molid, &self.newpos);
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        let mut molecule = system.molecule_mut(self.molid);
+        for (position, newpos) in soa_zip!(molecule.particles_mut(), [mut position], &self.newpos) {
+            *position = *newpos;
+        }
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do
+    }
+
+    fn update_amplitude(&mut self, scaling_factor: Option<f64>) {
+        if let Some(s) = scaling_factor {
+            if (s * self.theta).abs().to_degrees() <= 180.0 {
+                self.theta *= s;
+                self.range = Uniform::new(-self.theta, self.theta);
+            } else {
+                warn_once!(
+                    "Tried to increase the maximum amplitude for dihedral rotations to more than 180 degrees."
+                );
+            }
+        }
+    }
+
+    fn amplitude(&self) -> Option<f64> {
+        Some(self.theta)
+    }
+
+    fn set_amplitude(&mut self, amplitude: f64) {
+        self.theta = amplitude;
+        self.range = Uniform::new(-self.theta, self.theta);
+    }
+}
+
+/// Get all the bonds around which the dihedral angles of the `molecule` can be
+/// rotated. Each bond is represented by a pair of particles `(fixed, origin)`,
+/// and the list of particles to rotate. The particles to rotate are all the
+/// particles connected to `origin` without going through `fixed`, and are the
+/// smallest side of the molecule.
+fn rotatable_bonds(molecule: &Bonding) -> Vec<((usize, usize), Vec<usize>)> {
+    let central = molecule.dihedrals()
+        .iter()
+        .map(|dihedral| (dihedral.j(), dihedral.k()))
+        .map(|(j, k)| if j < k { (j, k) } else { (k, j) })
+        .collect::<BTreeSet<_>>();
+
+    let mut rotatable = Vec::new();
+    for (i, j) in central {
+        // Bonds in a ring are not rotatable
+        if let Some(side_j) = bond_side(molecule, i, j) {
+            if 2 * side_j.len() <= molecule.size() {
+                rotatable.push(((i, j), side_j));
+            } else {
+                let side_i = molecule.indexes().filter(|k| !side_j.contains(k)).collect();
+                rotatable.push(((j, i), side_i));
+            }
+        }
+    }
+
+    return rotatable;
+}
+
+/// Get all the particles in `molecule` connected to `origin` without going
+/// through the bond between `fixed` and `origin`. If `fixed` is connected to
+/// `origin` by another path, this function returns `None`.
+fn bond_side(molecule: &Bonding, fixed: usize, origin: usize) -> Option<Vec<usize>> {
+    let mut side = vec![origin];
+    let mut stack = vec![origin];
+    while let Some(current) = stack.pop() {
+        for bond in molecule.bonds() {
+            let other = if bond.i() == current {
+                bond.j()
+            } else if bond.j() == current {
+                bond.i()
+            } else {
+                continue;
+            };
+
+            if other == fixed {
+                if current == origin {
+                    continue;
+                }
+                return None;
+            }
+
+            if !side.contains(&other) {
+                side.push(other);
+                stack.push(other);
+            }
+        }
+    }
+    return Some(side);
+}
@@ -10,7 +10,7 @@
 //! In all this module, beta refers to the Boltzmann factor 1/(kB T)
 use rand::{RngCore, Rng, seq::SliceRandom};
 use std::collections::BTreeSet;
-use lumol_core::{EnergyCache, System, MoleculeHash};
+use lumol_core::{EnergyCache, System, MoleculeHash, UnitCell};
 
 /// Possible degrees of freedom simulated by a given Monte Carlo move
 #[derive(Clone, PartialEq, Debug)]
@@ -81,6 +81,17 @@ pub trait MCMove {
 
     /// Update the sample range for displacements.
     fn update_amplitude(&mut self, scaling_factor: Option<f64>);
+
+    /// Get the current amplitude of this move, used to save the state of a
+    /// simulation. The default implementation returns `None`, for moves
+    /// without adjustable amplitude.
+    fn amplitude(&self) -> Option<f64> {
+        None
+    }
+
+    /// Set the amplitude of this move to a value previously obtained with
+    /// `amplitude`, when restoring the state of a simulation.
+    fn set_amplitude(&mut self, _: f64) {}
 }
 
 /// Select a random molecule in the system using `rng` as random number
@@ -109,6 +120,17 @@ fn select_molecule(system: &System, hash: Option<MoleculeHash>, rng: &mut dyn Rn
     }
 }
 
+/// Check if the `cell` is too small for interactions with the given
+/// `cutoff`, i.e. if the diameter of the sphere inscribed in the cell is
+/// smaller than twice the cutoff radius. This diameter is the smallest
+/// distance between two opposite faces of the cell, which is smaller than the
+/// length of the cell vectors for triclinic cells.
+pub(super) fn smaller_than_cutoff(cell: &UnitCell, cutoff: f64) -> bool {
+    let widths = cell.lengths();
+    let diameter = f64::min(widths[0], f64::min(widths[1], widths[2]));
+    return diameter <= 2.0 * cutoff;
+}
+
 mod translate;
 pub use self::translate::Translate;
 
@@ -116,4 +138,7 @@ mod rotate;
 pub use self::rotate::Rotate;
 
 mod resize;
-pub use self::resize::Resize;
+pub use self::resize::{Resize, ResizeMode};
+
+mod dihedral;
+pub use self::dihedral::DihedralRotation;
@@ -1,20 +1,56 @@
 // Cymbalum, an extensible molecular simulation engine
 // Copyright (C) 2015-2016 G. Fraux — BSD license
 
-use rand::RngCore;
+use rand::{Rng, RngCore};
 use rand_distr::{Uniform, Distribution};
 
 use std::f64;
 use std::mem;
 
+use log_once::warn_once;
+
 use super::{MCDegreeOfFreedom, MCMove};
+use super::smaller_than_cutoff;
+
+use lumol_core::{Configuration, EnergyCache, System, Matrix3, UnitCell, Vector3D};
 
-use lumol_core::{Configuration, EnergyCache, System, Matrix3};
+/// Deformation of the simulation cell used by the [`Resize`] move.
+///
+/// In all the anisotropic modes, the amplitude of the move is a volume change
+/// `dV`, converted to a strain `dV / V` applied to the cell vectors. The
+/// target pressure is hydrostatic, so the cell lengths relax independently
+/// until each diagonal component of the stress tensor matches the pressure.
+///
+/// [`Resize`]: struct.Resize.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale the three cell vectors by the same factor
+    Isotropic,
+    /// Either scale the `a` and `b` cell vectors by the same factor, or scale
+    /// the `c` cell vector alone. This is usually used for membranes in the
+    /// `xy` plane.
+    SemiIsotropic,
+    /// Scale a single randomly chosen cell vector
+    Anisotropic,
+    /// Either scale a single cell vector, or tilt the cell by adding a
+    /// multiple of one cell vector to a following one. The cell becomes
+    /// triclinic after the first tilt. Tilts making the projection of a cell
+    /// vector on a previous one larger than half of this previous vector are
+    /// rejected, to keep the cell from collapsing.
+    Triclinic,
+}
 
-/// Monte Carlo move that changes the size of the simulation cell
+/// Monte Carlo move that changes the size of the simulation cell.
+///
+/// Moves making the cell smaller than twice the largest interactions cutoff
+/// are rejected. Moves making a molecule span more than half of the cell
+/// along any cell vector are also rejected, since the intramolecular
+/// distances would then be computed between periodic images.
 pub struct Resize {
     /// Delta for translation of the box length
     delta: f64,
+    /// Deformation of the cell
+    mode: ResizeMode,
     /// Sampling range for volume scaling
     range: Uniform<f64>,
     /// Configuration before applying changes to the simulation cell
@@ -29,15 +65,77 @@ impl Resize {
     /// Create a new `Resize` move, with target pressure `pressure` and maximum
     /// displacement of `delta`.
     pub fn new(pressure: f64, delta: f64) -> Resize {
+        Resize::with_mode(pressure, delta, ResizeMode::Isotropic)
+    }
+
+    /// Create a new `Resize` move, with target pressure `pressure`, maximum
+    /// volume change of `delta`, and using the given `mode` to deform the
+    /// cell.
+    pub fn with_mode(pressure: f64, delta: f64, mode: ResizeMode) -> Resize {
         assert!(delta > 0.0, "delta must be positive in Resize move");
         Resize {
             delta: delta,
+            mode: mode,
             range: Uniform::new(-delta, delta),
             previous: Configuration::new(),
             pressure: pressure,
             maximum_cutoff: None,
         }
     }
+
+    /// Get the deformation mode of this move
+    pub fn mode(&self) -> ResizeMode {
+        self.mode
+    }
+
+    /// Get the new cell after a volume change of `delta`, picking the
+    /// deformation according to the mode of this move. This returns `None` if
+    /// the deformation would tilt the cell too much.
+    fn deformed_cell(&self, cell: &UnitCell, delta: f64, rng: &mut dyn RngCore) -> Option<UnitCell> {
+        let volume = cell.volume();
+        let strain = delta / volume;
+        let mut factor = Matrix3::one();
+        match self.mode {
+            ResizeMode::Isotropic => {
+                factor = Matrix3::one() * f64::cbrt((volume + delta) / volume);
+            }
+            ResizeMode::SemiIsotropic => {
+                if rng.gen::<bool>() {
+                    let scaling = f64::sqrt(1.0 + strain);
+                    factor[0][0] = scaling;
+                    factor[1][1] = scaling;
+                } else {
+                    factor[2][2] = 1.0 + strain;
+                }
+            }
+            ResizeMode::Anisotropic => {
+                let i = rng.gen_range(0..3);
+                factor[i][i] = 1.0 + strain;
+            }
+            ResizeMode::Triclinic => {
+                // Upper triangular elements of the strain, to prevent
+                // rotations of the whole cell
+                const ELEMENTS: [(usize, usize); 6] = [(0, 0), (1, 1), (2, 2), (0, 1), (0, 2), (1, 2)];
+                let (i, j) = ELEMENTS[rng.gen_range(0..ELEMENTS.len())];
+                if i == j {
+                    factor[i][i] = 1.0 + strain;
+                } else {
+                    // Add `strain` times the i-th cell vector to the j-th one
+                    factor[i][j] = strain;
+                    let matrix = cell.matrix() * factor;
+                    let vector = |k: usize| Vector3D::new(matrix[0][k], matrix[1][k], matrix[2][k]);
+                    let (vi, vj) = (vector(i), vector(j));
+                    if f64::abs(vi * vj) > 0.5 * vi.norm2() {
+                        return None;
+                    }
+                    return Some(UnitCell::from_vectors(vector(0), vector(1), vector(2)));
+                }
+            }
+        }
+        let mut cell = *cell;
+        cell.scale_mut(factor);
+        return Some(cell);
+    }
 }
 
 impl MCMove for Resize {
@@ -62,29 +160,39 @@ impl MCMove for Resize {
 
     fn prepare(&mut self, system: &mut System, rng: &mut dyn RngCore) -> bool {
         let delta = self.range.sample(rng);
+        let cell = match self.deformed_cell(&system.cell, delta, rng) {
+            Some(cell) => cell,
+            None => return false,
+        };
 
-        // Store the previous configuration
-        self.previous = (**system).clone();
-
-        let volume = system.volume();
-        let scaling_factor = f64::cbrt((volume + delta) / volume);
-        // Change the simulation cell
-        system.cell.scale_mut(Matrix3::one() * scaling_factor);
-        // Check the radius of the smallest inscribed sphere and compare to the
-        // cut off distance.
-        // Abort simulation when box gets smaller than twice the cutoff radius.
+        // Reject the move when the new cell is smaller than twice the cutoff
+        // radius
         if let Some(maximum_cutoff) = self.maximum_cutoff {
-            if system.cell.lengths().iter().any(|&d| 0.5 * d <= maximum_cutoff) {
-                panic!(
-                    "Tried to decrease the cell size in Monte Carlo Resize move \
-                     but the new size is smaller than the interactions cut off \
-                     radius. You can try to increase the cell size or the number \
-                     of particles."
+            if smaller_than_cutoff(&cell, maximum_cutoff) {
+                warn_once!(
+                    "Rejected a Monte Carlo Resize move making the cell smaller than \
+                     twice the interactions cut off radius. You can try to increase \
+                     the cell size or the number of particles."
                 );
+                return false;
             }
-        };
+        }
+
+        // Reject the move when a molecule would span more than half of the
+        // new cell: the intramolecular distances would then be computed with
+        // periodic images, changing the energy of the molecule.
+        if system.molecules().any(|molecule| spans_half_cell(molecule.particles().position, &cell)) {
+            warn_once!(
+                "Rejected a Monte Carlo Resize move making a molecule span more \
+                 than half of the cell. You can try to increase the cell size."
+            );
+            return false;
+        }
+
+        // Store the previous configuration and change the simulation cell
+        self.previous = (**system).clone();
+        system.cell = cell;
 
-        let cell = system.cell;
         for mut molecule in system.molecules_mut() {
             // We don't want to change the intramolecular distances so we
             // compute the translation vector of the center-of-mass (com) of a
@@ -126,4 +234,109 @@ impl MCMove for Resize {
             self.range = Uniform::new(-self.delta, self.delta);
         }
     }
+
+    fn amplitude(&self) -> Option<f64> {
+        Some(self.delta)
+    }
+
+    fn set_amplitude(&mut self, amplitude: f64) {
+        self.delta = amplitude;
+        self.range = Uniform::new(-self.delta, self.delta);
+    }
+}
+
+/// Check if the particles at `positions` span more than half of the `cell`
+/// along any of the cell vectors
+fn spans_half_cell(positions: &[Vector3D], cell: &UnitCell) -> bool {
+    let mut min = Vector3D::zero();
+    let mut max = Vector3D::zero();
+    for position in positions {
+        let fractional = cell.fractional(&(position - positions[0]));
+        for k in 0..3 {
+            min[k] = f64::min(min[k], fractional[k]);
+            max[k] = f64::max(max[k], fractional[k]);
+        }
+    }
+    return (0..3).any(|k| max[k] - min[k] >= 0.5);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumol_core::CellShape;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn deformed_cell() {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let cell = UnitCell::ortho(10.0, 12.0, 15.0);
+        let modes = [
+            ResizeMode::Isotropic,
+            ResizeMode::SemiIsotropic,
+            ResizeMode::Anisotropic,
+            ResizeMode::Triclinic,
+        ];
+
+        let mut tilted = false;
+        for &mode in &modes {
+            let resize = Resize::with_mode(1.0, 10.0, mode);
+            for _ in 0..50 {
+                let new = resize.deformed_cell(&cell, 30.0, &mut rng).unwrap();
+                if new.volume() == cell.volume() {
+                    // tilt of the cell
+                    assert_eq!(mode, ResizeMode::Triclinic);
+                    assert_eq!(new.shape(), CellShape::Triclinic);
+                    tilted = true;
+                } else {
+                    assert_ulps_eq!(new.volume(), cell.volume() + 30.0, max_ulps = 10);
+                }
+
+                if mode == ResizeMode::Isotropic {
+                    assert_ulps_eq!(new.a() / new.c(), cell.a() / cell.c());
+                } else if mode == ResizeMode::SemiIsotropic {
+                    assert_ulps_eq!(new.a() / new.b(), cell.a() / cell.b());
+                }
+            }
+        }
+        assert!(tilted);
+    }
+
+    #[test]
+    fn molecules_size() {
+        let positions = [Vector3D::new(0.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 9.0)];
+        assert!(!spans_half_cell(&positions, &UnitCell::cubic(20.0)));
+        assert!(spans_half_cell(&positions, &UnitCell::ortho(20.0, 20.0, 15.0)));
+        assert!(!spans_half_cell(&positions, &UnitCell::ortho(5.0, 5.0, 20.0)));
+    }
+
+    #[test]
+    fn tilt_bound() {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let cell = UnitCell::from_vectors(
+            Vector3D::new(10.0, 0.0, 0.0),
+            Vector3D::new(4.0, 10.0, 0.0),
+            Vector3D::new(0.0, 0.0, 10.0),
+        );
+        let resize = Resize::with_mode(1.0, 10.0, ResizeMode::Triclinic);
+
+        let mut rejected = false;
+        for _ in 0..100 {
+            // Tilting by a fifth of a cell vector is only possible for some
+            // of the cell vectors
+            match resize.deformed_cell(&cell, 0.2 * cell.volume(), &mut rng) {
+                Some(new) => {
+                    let matrix = new.matrix();
+                    let a = Vector3D::new(matrix[0][0], matrix[1][0], matrix[2][0]);
+                    let b = Vector3D::new(matrix[0][1], matrix[1][1], matrix[2][1]);
+                    assert!(f64::abs(a * b) <= 0.5 * a.norm2());
+                }
+                None => rejected = true,
+            }
+        }
+        assert!(rejected);
+    }
 }
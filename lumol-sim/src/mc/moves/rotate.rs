@@ -111,6 +111,15 @@ impl MCMove for Rotate {
             }
         }
     }
+
+    fn amplitude(&self) -> Option<f64> {
+        Some(self.theta)
+    }
+
+    fn set_amplitude(&mut self, amplitude: f64) {
+        self.theta = amplitude;
+        self.range = Uniform::new(-self.theta, self.theta);
+    }
 }
 
 /// Rotate the particles at `positions` with the center-of-mass position
@@ -28,8 +28,10 @@ pub struct Translate {
     newpos: Vec<Vector3D>,
     /// Maximum displacement value
     delta: f64,
-    /// The maximum value must not exceed this value, if set
-    maximum_cutoff: Option<f64>,
+    /// The maximum value must not exceed this value, if set. This is the
+    /// smallest value between the interactions cutoff and half of the
+    /// smallest cell length.
+    maximum: Option<f64>,
     /// Translation range for random number generation
     range: Uniform<f64>,
 }
@@ -46,7 +48,7 @@ impl Translate {
             molid: usize::max_value(),
             newpos: Vec::new(),
             delta: delta,
-            maximum_cutoff: None,
+            maximum: None,
             range: Uniform::new(-delta, delta),
         }
     }
@@ -69,15 +71,23 @@ impl MCMove for Translate {
     }
 
     fn setup(&mut self, system: &System) {
-        // Limit the displacement range to the maximum cutoff
-        self.maximum_cutoff = system.maximum_cutoff();
-        if let Some(max) = self.maximum_cutoff {
+        // Limit the displacement range to the maximum cutoff and to half of
+        // the cell, where the nearest image convention would break.
+        self.maximum = system.maximum_cutoff();
+        if !system.cell.is_infinite() {
+            let lengths = system.cell.lengths();
+            let half_cell = 0.5 * f64::min(lengths[0], f64::min(lengths[1], lengths[2]));
+            self.maximum = Some(self.maximum.map_or(half_cell, |max| f64::min(max, half_cell)));
+        }
+
+        if let Some(max) = self.maximum {
             if self.delta > max {
                 warn!(
                     "Changing the maximal displacement for Translate, \
-                     because the interactions cutoff is too low."
+                     because the interactions cutoff or the cell is too small."
                 );
-                self.delta = max
+                self.delta = max;
+                self.range = Uniform::new(-self.delta, self.delta);
             }
         }
     }
@@ -131,18 +141,28 @@ impl MCMove for Translate {
 
     fn update_amplitude(&mut self, scaling_factor: Option<f64>) {
         if let Some(s) = scaling_factor {
-            if let Some(max) = self.maximum_cutoff {
-                if (self.delta * s) > max {
+            self.delta *= s;
+            if let Some(max) = self.maximum {
+                if self.delta > max {
                     warn_once!(
                         "Tried to increase the maximum amplitude for translations \
-                         to more than the maximum cutoff -- ignoring."
+                         to more than the maximum cutoff or half of the cell -- \
+                         using the maximal value instead."
                     );
-                    return;
+                    self.delta = max;
                 }
             }
 
-            self.delta *= s;
             self.range = Uniform::new(-self.delta, self.delta);
         };
     }
+
+    fn amplitude(&self) -> Option<f64> {
+        Some(self.delta)
+    }
+
+    fn set_amplitude(&mut self, amplitude: f64) {
+        self.delta = amplitude;
+        self.range = Uniform::new(-self.delta, self.delta);
+    }
 }
@@ -0,0 +1,403 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Constraints on the particles, enforced by the molecular dynamics
+//! integrators during the integration step.
+use lumol_core::{Matrix3, System, Vector3D};
+
+/// Relative tolerance on the constrained distances
+const TOLERANCE: f64 = 1e-10;
+/// Maximal number of iterations of the constraints solvers
+const MAX_ITERATIONS: usize = 1000;
+
+/// A set of distance constraints between particles, used to keep molecules
+/// rigid with atomic integrators, together with a set of frozen particles
+/// which do not move at all.
+///
+/// The constraints are enforced inside the integration step by the
+/// integrators supporting them (see `Integrator::constraints_mut`): the
+/// positions are corrected with the SHAKE algorithm right after they are
+/// updated, using the positions at the beginning of the step as the
+/// direction of the constraint forces; and the velocities are corrected with
+/// the RATTLE algorithm after the last velocities update. The virial of the
+/// constraint forces is stored in `System::constraints_virial`, so that the
+/// pressure accounts for the constraints. Frozen particles are moved back to
+/// their position at the beginning of the step and their velocities are set
+/// to zero at the same points of the integration step, and they behave as
+/// particles with infinite mass in the distance constraints.
+#[derive(Clone, Debug, Default)]
+pub struct Constraints {
+    /// Constrained pairs of particles, and the corresponding distance
+    distances: Vec<(usize, usize, f64)>,
+    /// Positions of all the particles before the integration step
+    previous: Vec<Vector3D>,
+    /// Sorted indexes of the frozen particles
+    frozen: Vec<usize>,
+}
+
+impl Constraints {
+    /// Create an empty set of constraints
+    pub fn new() -> Constraints {
+        Constraints::default()
+    }
+
+    /// Create constraints keeping the molecules at indexes `molecules` of
+    /// the `system` rigid, by fixing distances between their particles to
+    /// their current value. Only a set of independent distances is used,
+    /// since constraining all the distances in molecules with more than four
+    /// particles would give redundant constraints. Massless particles (such
+    /// as virtual sites) are not constrained.
+    pub(crate) fn rigid_molecules(system: &System, molecules: &[usize]) -> Constraints {
+        let masses = system.particles().mass;
+        let mut distances = Vec::new();
+        for &molid in molecules {
+            let indexes = system.molecule(molid).indexes()
+                .filter(|&i| masses[i] > 0.0)
+                .collect::<Vec<_>>();
+            for (i, j) in independent_pairs(system, &indexes) {
+                distances.push((i, j, system.nearest_image(i, j).norm()));
+            }
+        }
+
+        Constraints {
+            distances: distances,
+            previous: Vec::new(),
+            frozen: Vec::new(),
+        }
+    }
+
+    /// Freeze the particles at the given `indexes`, in addition to the
+    /// distance constraints
+    pub(crate) fn freeze(&mut self, indexes: &[usize]) {
+        self.frozen.extend_from_slice(indexes);
+        self.frozen.sort_unstable();
+        self.frozen.dedup();
+    }
+
+    /// Check if there is no constraint in this set
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty() && self.frozen.is_empty()
+    }
+
+    /// Get the inverse of the `mass` of the particle `i`, which is zero for
+    /// frozen particles
+    fn inverse_mass(&self, i: usize, mass: f64) -> f64 {
+        if self.frozen.binary_search(&i).is_ok() {
+            0.0
+        } else {
+            1.0 / mass
+        }
+    }
+
+    /// Store the positions of the particles at the beginning of an
+    /// integration step, before the positions are updated
+    pub fn store_positions(&mut self, system: &System) {
+        if self.is_empty() {
+            return;
+        }
+        self.previous.clear();
+        self.previous.extend_from_slice(system.particles().position);
+    }
+
+    /// Move the particles to satisfy the constraints after an unconstrained
+    /// update of the positions using the timestep `dt`, and update the
+    /// velocities accordingly. The positions before the update must have
+    /// been stored with `store_positions`.
+    ///
+    /// # Panics
+    ///
+    /// If the positions were not stored, or if the SHAKE algorithm does not
+    /// converge.
+    pub fn constrain_positions(&self, system: &mut System, dt: f64) {
+        if self.is_empty() {
+            return;
+        }
+        assert_eq!(self.previous.len(), system.size(), "missing previous positions in constraints");
+
+        let cell = system.cell;
+        let particles = system.particles_mut();
+        for &i in &self.frozen {
+            particles.position[i] = self.previous[i];
+            particles.velocity[i] = Vector3D::zero();
+        }
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut converged = true;
+            for &(i, j, distance) in &self.distances {
+                let (inv_mi, inv_mj) = (
+                    self.inverse_mass(i, particles.mass[i]),
+                    self.inverse_mass(j, particles.mass[j]),
+                );
+                if inv_mi + inv_mj == 0.0 {
+                    // Both particles are frozen
+                    continue;
+                }
+
+                let mut rij = particles.position[i] - particles.position[j];
+                cell.vector_image(&mut rij);
+                let delta = distance * distance - rij.norm2();
+                if f64::abs(delta) <= 2.0 * TOLERANCE * distance * distance {
+                    continue;
+                }
+                converged = false;
+
+                let mut previous = self.previous[i] - self.previous[j];
+                cell.vector_image(&mut previous);
+                let g = delta / (2.0 * (rij * previous) * (inv_mi + inv_mj));
+
+                particles.position[i] += g * inv_mi * previous;
+                particles.position[j] -= g * inv_mj * previous;
+                particles.velocity[i] += g * inv_mi / dt * previous;
+                particles.velocity[j] -= g * inv_mj / dt * previous;
+            }
+
+            if converged {
+                return;
+            }
+        }
+        panic!("SHAKE did not converge in {} iterations", MAX_ITERATIONS);
+    }
+
+    /// Remove the components of the velocities which would change the
+    /// constrained distances, after the last update of the velocities in an
+    /// integration step using the timestep `dt`. This also sets the virial
+    /// of the constraint forces in the `system`.
+    ///
+    /// # Panics
+    ///
+    /// If the RATTLE algorithm does not converge.
+    pub fn constrain_velocities(&self, system: &mut System, dt: f64) {
+        if self.is_empty() {
+            return;
+        }
+        // The velocities correction is the effect of the constraint forces
+        // during the last half step: f_ij = -2 k r_ij / dt
+        let virial = self.rattle(system);
+        system.constraints_virial = -2.0 / dt * virial;
+    }
+
+    /// Remove the components of the velocities which would change the
+    /// constrained distances, and reset the velocities of frozen particles,
+    /// after the velocities were modified outside of the integrator (by
+    /// thermostats or controls).
+    pub(crate) fn project_velocities(&self, system: &mut System) {
+        if self.is_empty() {
+            return;
+        }
+        let _ = self.rattle(system);
+    }
+
+    /// Run the RATTLE algorithm on the velocities, and get the sum of $k_{ij}
+    /// \vec r_{ij} \otimes \vec r_{ij}$, where $k_{ij}$ is the total velocity
+    /// correction factor for the pair $i, j$.
+    fn rattle(&self, system: &mut System) -> Matrix3 {
+        let cell = system.cell;
+        let particles = system.particles_mut();
+        for &i in &self.frozen {
+            particles.velocity[i] = Vector3D::zero();
+        }
+
+        let mut virial = Matrix3::zero();
+        for _ in 0..MAX_ITERATIONS {
+            let mut converged = true;
+            for &(i, j, distance) in &self.distances {
+                let (inv_mi, inv_mj) = (
+                    self.inverse_mass(i, particles.mass[i]),
+                    self.inverse_mass(j, particles.mass[j]),
+                );
+                if inv_mi + inv_mj == 0.0 {
+                    continue;
+                }
+
+                let mut rij = particles.position[i] - particles.position[j];
+                cell.vector_image(&mut rij);
+                let vij = particles.velocity[i] - particles.velocity[j];
+                if f64::abs(rij * vij) <= TOLERANCE * distance * vij.norm() {
+                    continue;
+                }
+                converged = false;
+
+                let k = (rij * vij) / (distance * distance * (inv_mi + inv_mj));
+
+                particles.velocity[i] -= k * inv_mi * rij;
+                particles.velocity[j] += k * inv_mj * rij;
+                virial += k * rij.tensorial(&rij);
+            }
+
+            if converged {
+                return virial;
+            }
+        }
+        panic!("RATTLE did not converge in {} iterations", MAX_ITERATIONS);
+    }
+
+    /// Get the number of degrees of freedom removed by the constraints on
+    /// the molecules at indexes `molecules` in the `system`: each rigid
+    /// molecule only keeps three translational and up to three rotational
+    /// degrees of freedom.
+    pub(crate) fn removed_degrees_of_freedom(system: &System, molecules: &[usize]) -> usize {
+        let masses = system.particles().mass;
+        let mut removed = 0;
+        for &molid in molecules {
+            let molecule = system.molecule(molid);
+            let positions = molecule.indexes()
+                .filter(|&i| masses[i] > 0.0)
+                .map(|i| system.particles().position[i])
+                .collect::<Vec<_>>();
+
+            let natoms = positions.len();
+            if natoms < 2 {
+                continue;
+            }
+
+            let axis = (positions[1] - positions[0]).normalized();
+            let linear = positions.iter().all(|position| {
+                let delta = position - positions[0];
+                (delta ^ axis).norm() <= 1e-6 * delta.norm()
+            });
+            let rotations = if linear { 2 } else { 3 };
+            removed += 3 * natoms - 3 - rotations;
+        }
+        return removed;
+    }
+}
+
+/// Select a set of independent distance constraints between the particles
+/// at `indexes` in the `system`. A distance is only constrained if its
+/// gradient with respect to the positions is linearly independent from the
+/// gradients of the distances already constrained; so that molecules with
+/// more than four particles only use $3 N - 6$ constraints.
+fn independent_pairs(system: &System, indexes: &[usize]) -> Vec<(usize, usize)> {
+    let size = 3 * indexes.len();
+    let mut pairs = Vec::new();
+    // Orthonormal basis of the gradients of the selected constraints
+    let mut basis: Vec<Vec<f64>> = Vec::new();
+    for (a, &i) in indexes.iter().enumerate() {
+        for (b, &j) in indexes.iter().enumerate().skip(a + 1) {
+            let rij = system.nearest_image(i, j);
+            let mut gradient = vec![0.0; size];
+            gradient[3 * a..3 * a + 3].copy_from_slice(&*rij);
+            gradient[3 * b..3 * b + 3].copy_from_slice(&*(-rij));
+
+            let norm = rij.norm() * f64::sqrt(2.0);
+            for vector in &basis {
+                let projection = dot(&gradient, vector);
+                for (value, basis_value) in gradient.iter_mut().zip(vector) {
+                    *value -= projection * basis_value;
+                }
+            }
+
+            let residual = f64::sqrt(dot(&gradient, &gradient));
+            if residual > 1e-6 * norm {
+                for value in &mut gradient {
+                    *value /= residual;
+                }
+                basis.push(gradient);
+                pairs.push((i, j));
+            }
+        }
+    }
+    return pairs;
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumol_core::{Molecule, Particle, UnitCell};
+
+    fn water() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        let mut molecule = Molecule::new(Particle::with_position("O", Vector3D::new(0.0, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", Vector3D::new(0.957, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("H", Vector3D::new(-0.24, 0.927, 0.0)));
+        system.add_molecule(molecule);
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0))));
+        system
+    }
+
+    #[test]
+    fn degrees_of_freedom() {
+        let system = water();
+        assert_eq!(Constraints::removed_degrees_of_freedom(&system, &[0]), 3);
+        assert_eq!(Constraints::removed_degrees_of_freedom(&system, &[1]), 0);
+
+        let mut system = System::new();
+        let mut molecule = Molecule::new(Particle::with_position("O", Vector3D::new(0.0, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(0, Particle::with_position("C", Vector3D::new(1.2, 0.0, 0.0)));
+        molecule.add_particle_bonded_to(1, Particle::with_position("O", Vector3D::new(2.4, 0.0, 0.0)));
+        system.add_molecule(molecule);
+        assert_eq!(Constraints::removed_degrees_of_freedom(&system, &[0]), 4);
+    }
+
+    #[test]
+    fn independent_constraints() {
+        let system = water();
+        let constraints = Constraints::rigid_molecules(&system, &[0]);
+        assert_eq!(constraints.distances.len(), 3);
+
+        // Ethane-like molecule with 8 atoms: 28 pairs, but only 3 * 8 - 6 = 18
+        // independent distances
+        let mut system = System::new();
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0), Vector3D::new(1.54, 0.0, 0.0),
+            Vector3D::new(-0.36, 1.03, 0.0), Vector3D::new(-0.36, -0.51, 0.89),
+            Vector3D::new(-0.36, -0.51, -0.89), Vector3D::new(1.9, -1.03, 0.0),
+            Vector3D::new(1.9, 0.51, 0.89), Vector3D::new(1.9, 0.51, -0.89),
+        ];
+        let mut molecule = Molecule::new(Particle::with_position("C", positions[0]));
+        molecule.add_particle_bonded_to(0, Particle::with_position("C", positions[1]));
+        for (i, &position) in positions.iter().enumerate().skip(2) {
+            let carbon = usize::from(i >= 5);
+            molecule.add_particle_bonded_to(carbon, Particle::with_position("H", position));
+        }
+        system.add_molecule(molecule);
+        let constraints = Constraints::rigid_molecules(&system, &[0]);
+        assert_eq!(constraints.distances.len(), 18);
+    }
+
+    #[test]
+    fn shake_rattle() {
+        let mut system = water();
+        let mut constraints = Constraints::rigid_molecules(&system, &[0]);
+        let initial = system.particles().position.to_vec();
+
+        constraints.store_positions(&system);
+        {
+            let particles = system.particles_mut();
+            particles.position[1] += Vector3D::new(0.05, 0.02, 0.0);
+            particles.position[2] += Vector3D::new(0.0, -0.03, 0.01);
+            particles.velocity[1] = Vector3D::new(0.01, 0.0, 0.0);
+            particles.velocity[2] = Vector3D::new(0.0, 0.0, 0.02);
+        }
+        constraints.constrain_positions(&mut system, 1.0);
+        constraints.constrain_velocities(&mut system, 1.0);
+
+        for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+            let expected = (initial[i] - initial[j]).norm();
+            assert!(f64::abs(system.distance(i, j) - expected) < 1e-8 * expected);
+
+            let rij = system.nearest_image(i, j);
+            let vij = system.particles().velocity[i] - system.particles().velocity[j];
+            assert!(f64::abs(rij * vij) < 1e-8);
+        }
+    }
+
+    #[test]
+    fn virial() {
+        let mut system = water();
+        let constraints = Constraints::rigid_molecules(&system, &[0]);
+        // The first hydrogen moves away from the oxygen along the bond, the
+        // constraint force is attractive
+        system.particles_mut().velocity[1] = Vector3D::new(0.01, 0.0, 0.0);
+        constraints.constrain_velocities(&mut system, 1.0);
+
+        let virial = system.constraints_virial;
+        assert!(virial.trace() < 0.0);
+        assert!(virial[0][0] < 0.0);
+        assert_eq!(virial[2][2], 0.0);
+    }
+}
@@ -32,7 +32,10 @@ impl Control for RemoveTranslation {
 
         let mut com_velocity = Vector3D::zero();
         for (&mass, velocity) in soa_zip!(system.particles(), [mass, velocity]) {
-            com_velocity += velocity * mass / total_mass;
+            // Skip massless virtual sites, which velocities are not relevant
+            if mass != 0.0 {
+                com_velocity += velocity * mass / total_mass;
+            }
         }
 
         for velocity in system.particles_mut().velocity {
@@ -53,6 +56,9 @@ impl Control for RemoveRotation {
         let mut moment = Vector3D::zero();
         let mut inertia = Matrix3::zero();
         for (&mass, position, velocity) in soa_zip!(system.particles(), [mass, position, velocity]) {
+            if mass == 0.0 {
+                continue;
+            }
             let delta = position - com;
             moment += mass * (delta ^ velocity);
             inertia += -mass * delta.tensorial(&delta);
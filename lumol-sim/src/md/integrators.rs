@@ -2,28 +2,133 @@
 // Copyright (C) Lumol's contributors — BSD license
 use soa_derive::soa_zip;
 
-use lumol_core::{System, Matrix3, Vector3D};
+use std::cell::Cell;
+use std::ops::Mul;
+use std::rc::Rc;
+
+use lumol_core::{System, DegreesOfFreedom, Matrix3, Vector3D};
+use lumol_core::compute::{BondedForces, Compute, NonBondedForces};
+
+use super::Constraints;
+use crate::{PropagatorState, SimulationError};
 
 /// The `Integrator` trait define integrator interface for molecular dynamics.
 /// An integrator is an algorithm responsible for propagating the equations of
 /// motion in the system.
 pub trait Integrator {
+    /// Get the degrees of freedom this integrator propagates. The default
+    /// implementation moves all the particles independently.
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+    /// Get the timestep of the current step, selected by `update_timestep`
+    /// and used by `integrate`, if this integrator has one. The default implementation returns `None`.
+    fn timestep(&self) -> Option<f64> {
+        None
+    }
+    /// Select the timestep for the next call to `integrate`, for integrators
+    /// with a variable timestep. This is called at every step of the
+    /// simulation, before `integrate`. The default implementation does
+    /// nothing.
+    fn update_timestep(&mut self, _: &System) {}
     /// Setup the integrator. This function is called once by every simulation
     /// run.
     fn setup(&mut self, _: &System) {}
     /// Integrate the equations of motion. This is called at every step of the
     /// simulation.
     fn integrate(&mut self, system: &mut System);
+    /// Get the constraints enforced by this integrator during the
+    /// integration, or `None` if this integrator does not support
+    /// constraints. The default implementation returns `None`.
+    fn constraints_mut(&mut self) -> Option<&mut Constraints> {
+        None
+    }
+    /// Get the error which stopped the last call to `integrate`, if any, and
+    /// reset it. The default implementation never fails.
+    fn take_error(&mut self) -> Option<SimulationError> {
+        None
+    }
+    /// Save the internal state of this integrator (extended variables, ...)
+    /// in `state`, using `prefix` for the names of the values. The default
+    /// implementation does nothing, for integrators without internal state.
+    fn save_state(&mut self, _prefix: &str, _state: &mut PropagatorState) {}
+    /// Restore the internal state of this integrator from a `state` created
+    /// by `save_state` with the same `prefix`. The default implementation
+    /// does nothing.
+    fn restore_state(&mut self, _prefix: &str, _state: &PropagatorState) {}
+}
+
+/// Get the acceleration of a particle with the given `mass` under `force`.
+/// Massless particles (virtual sites) are not accelerated, their positions
+/// being computed from the other particles.
+#[inline]
+fn compute_acceleration(force: Vector3D, mass: f64) -> Vector3D {
+    if mass > 0.0 {
+        force / mass
+    } else {
+        Vector3D::zero()
+    }
 }
 
 /// Velocity-Verlet integrator.
 ///
 /// This integrator is time-reversible and symplectic (volume preserving).
+///
+/// An adaptive timestep can be used with
+/// [`set_adaptive_timestep`](#method.set_adaptive_timestep), reducing the
+/// timestep when the maximal force acting on a particle becomes large, for
+/// example during close encounters between particles. The integrator is then
+/// no longer exactly time-reversible nor symplectic, and the energy is only
+/// approximately conserved: this mode is intended for the equilibration of
+/// badly prepared systems, not for production runs.
+///
+/// The integrator can also stop the simulation with an error when a particle
+/// moves too much in a single step, which usually indicates exploding forces, see
+/// [`set_max_displacement`](#method.set_max_displacement).
+///
+/// This integrator supports [`Constraints`](struct.Constraints.html), which
+/// are enforced with the SHAKE and RATTLE algorithms.
 pub struct VelocityVerlet {
     /// Timestep for the integrator
     timestep: f64,
     /// Storing the accelerations
     accelerations: Vec<Vector3D>,
+    /// Settings for the adaptive timestep, if any
+    adaptive: Option<AdaptiveTimestep>,
+    /// Maximal displacement of a particle in a single step, as a fraction of
+    /// the smallest cell length
+    max_displacement: Option<f64>,
+    /// Constraints enforced during the integration
+    constraints: Constraints,
+    /// Error which stopped the last integration step, if any
+    error: Option<SimulationError>,
+}
+
+/// Settings for the adaptive timestep in velocity-Verlet
+#[derive(Clone, Copy, Debug)]
+struct AdaptiveTimestep {
+    /// Minimal timestep
+    min: f64,
+    /// Maximal timestep
+    max: f64,
+    /// Maximal force acting on any particle before the timestep is reduced
+    max_force: f64,
+}
+
+impl AdaptiveTimestep {
+    /// Get the timestep to use when the maximal force acting on any particle
+    /// is `force`, and the previous timestep was `previous`. The timestep
+    /// shrinks immediately when the force is larger than the threshold, and
+    /// grows back by at most 10 % per step.
+    fn timestep(&self, force: f64, previous: f64) -> f64 {
+        let target = if force > self.max_force {
+            self.max * self.max_force / force
+        } else {
+            self.max
+        };
+        let timestep = f64::min(target, 1.1 * previous);
+        return f64::max(f64::min(timestep, self.max), self.min);
+    }
 }
 
 impl VelocityVerlet {
@@ -32,18 +137,126 @@ impl VelocityVerlet {
         VelocityVerlet {
             timestep: timestep,
             accelerations: Vec::new(),
+            adaptive: None,
+            max_displacement: None,
+            constraints: Constraints::new(),
+            error: None,
         }
     }
+
+    /// Use an adaptive timestep between `min` and `max`. The timestep is
+    /// reduced proportionally to the maximal force acting on any particle
+    /// when this force is larger than `max_force`, and grows back slowly to
+    /// `max` when the forces are smaller.
+    ///
+    /// # Panics
+    ///
+    /// If `min`, `max` or `max_force` are not positive, or if `min > max`.
+    pub fn set_adaptive_timestep(&mut self, min: f64, max: f64, max_force: f64) {
+        assert!(min > 0.0 && max > 0.0, "adaptive timestep bounds must be positive");
+        assert!(min <= max, "minimal timestep must be smaller than the maximal timestep");
+        assert!(max_force > 0.0, "maximal force must be positive for adaptive timestep");
+        self.adaptive = Some(AdaptiveTimestep {
+            min: min,
+            max: max,
+            max_force: max_force,
+        });
+        self.timestep = max;
+    }
+
+    /// Stop the simulation when any particle would move by more than
+    /// `fraction` times the smallest length of the unit cell in a single
+    /// step. Such a large displacement usually indicates that the forces are
+    /// exploding, and stopping early prevents `NaN` values from propagating
+    /// silently in the system. The check is not performed with infinite
+    /// cells.
+    ///
+    /// When a particle moves too much, or if its displacement is `NaN`, the
+    /// system is left unchanged and the step fails with a
+    /// [`SimulationError`](../struct.SimulationError.html), which is reported
+    /// by [`Simulation::try_run`](../struct.Simulation.html#method.try_run).
+    ///
+    /// # Panics
+    ///
+    /// If `fraction` is not positive.
+    pub fn set_max_displacement(&mut self, fraction: f64) {
+        assert!(fraction > 0.0, "maximal displacement must be positive in velocity-Verlet");
+        self.max_displacement = Some(fraction);
+    }
 }
 
 impl Integrator for VelocityVerlet {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn save_state(&mut self, prefix: &str, state: &mut PropagatorState) {
+        if self.adaptive.is_some() {
+            state.set(&format!("{}.timestep", prefix), self.timestep);
+        }
+    }
+
+    fn restore_state(&mut self, prefix: &str, state: &PropagatorState) {
+        if self.adaptive.is_some() {
+            if let Some(timestep) = state.get(&format!("{}.timestep", prefix)) {
+                self.timestep = timestep;
+            }
+        }
+    }
+
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        if self.adaptive.is_some() {
+            // The adaptive timestep needs the initial forces to select the
+            // first timestep
+            for (&mass, acceleration, force) in soa_zip!(
+                system.particles(), [mass], &mut self.accelerations, system.forces()
+            ) {
+                *acceleration = compute_acceleration(force, mass);
+            }
+        }
+    }
+
+    fn update_timestep(&mut self, system: &System) {
+        if let Some(adaptive) = self.adaptive {
+            let max_force = soa_zip!(system.particles(), [mass], &self.accelerations)
+                .map(|(&mass, acceleration)| mass * acceleration.norm())
+                .fold(0.0, f64::max);
+            self.timestep = adaptive.timestep(max_force, self.timestep);
+        }
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
 
+        let max_displacement = match self.max_displacement {
+            Some(fraction) if !system.cell.is_infinite() => {
+                let lengths = system.cell.lengths();
+                Some(fraction * f64::min(lengths[0], f64::min(lengths[1], lengths[2])))
+            }
+            _ => None,
+        };
+
+        if let Some(max_displacement) = max_displacement {
+            // Check all the displacements before changing the system, so that
+            // it stays usable if the step fails
+            for (i, (velocity, acceleration)) in soa_zip!(
+                system.particles(), [velocity], &self.accelerations
+            ).enumerate() {
+                let displacement = ((velocity + 0.5 * dt * acceleration) * dt).norm();
+                if displacement.is_nan() || displacement > max_displacement {
+                    self.error = Some(SimulationError::Unstable(format!(
+                        "particle {} moved by {} A in a single velocity-Verlet step, more than the \
+                         maximal displacement of {} A. The forces are probably exploding, try to \
+                         reduce the timestep or to check the initial configuration.",
+                        i, displacement, max_displacement
+                    )));
+                    return;
+                }
+            }
+        }
+
+        self.constraints.store_positions(system);
         // Update velocities at t + ∆t/2 and positions at t + ∆t
         for (position, velocity, acceleration) in soa_zip!(
             system.particles_mut(), [mut position, mut velocity], &self.accelerations
@@ -51,13 +264,15 @@ impl Integrator for VelocityVerlet {
             *velocity += 0.5 * dt * acceleration;
             *position += velocity * dt;
         }
+        self.constraints.constrain_positions(system, dt);
+        system.update_virtual_sites();
 
         let forces = system.forces();
         // Update accelerations at t + ∆t
         for (&mass, acceleration, force) in soa_zip!(
             system.particles(), [mass], &mut self.accelerations, forces
         ) {
-            *acceleration = force / mass;
+            *acceleration = compute_acceleration(force, mass);
         }
 
         // Update velocities at t + ∆t
@@ -66,6 +281,15 @@ impl Integrator for VelocityVerlet {
         ) {
             *velocity += 0.5 * dt * acceleration;
         }
+        self.constraints.constrain_velocities(system, dt);
+    }
+
+    fn constraints_mut(&mut self) -> Option<&mut Constraints> {
+        Some(&mut self.constraints)
+    }
+
+    fn take_error(&mut self) -> Option<SimulationError> {
+        self.error.take()
     }
 }
 
@@ -90,6 +314,10 @@ impl Verlet {
 }
 
 impl Integrator for Verlet {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
     fn setup(&mut self, system: &System) {
         self.prevpos = vec![Vector3D::zero(); system.size()];
 
@@ -113,12 +341,13 @@ impl Integrator for Verlet {
             // Save positions at t
             let tmp = *position;
             // Update positions at t + ∆t
-            *position = 2.0 * (*position) - (*prevpos) + dt2 / mass * force;
+            *position = 2.0 * (*position) - (*prevpos) + dt2 * compute_acceleration(force, *mass);
             // Update velocities at t
             *velocity = ((*position) - (*prevpos)) / (2.0 * dt);
             // Update saved position
             *prevpos = tmp;
         }
+        system.update_virtual_sites();
     }
 }
 
@@ -143,6 +372,10 @@ impl LeapFrog {
 }
 
 impl Integrator for LeapFrog {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
     }
@@ -156,22 +389,150 @@ impl Integrator for LeapFrog {
         ) {
             *position += velocity * dt + 0.5 * acceleration * dt2;
         }
+        system.update_virtual_sites();
 
         let forces = system.forces();
         for (velocity, &mass, acceleration, force) in soa_zip!(
             system.particles_mut(), [mut velocity, mass], &mut self.accelerations, &forces
         ) {
-            let new_acceleration = force / mass;
+            let new_acceleration = compute_acceleration(*force, mass);
             *velocity += 0.5 * ((*acceleration) + new_acceleration) * dt;
             *acceleration = new_acceleration;
         }
     }
 }
 
+/// Multiple time step integrator, using the reversible reference system
+/// propagator algorithm (RESPA).
+///
+/// The forces are split in fast forces coming from the bonded interactions
+/// (bonds, angles and dihedral angles), and slow forces coming from the
+/// non-bonded interactions (pairs, coulombic and global interactions). The
+/// fast forces are integrated with a velocity-Verlet scheme using `inner`
+/// steps of `timestep / inner` for each outer step, and the slow forces are
+/// only computed once per outer step of `timestep`. See Tuckerman, Berne and
+/// Martyna (J. Chem. Phys. 97, 1990 (1992)) for more information.
+///
+/// With `inner = 1`, this integrator is equivalent to velocity-Verlet.
+///
+/// This integrator is time-reversible and symplectic (volume preserving).
+pub struct Respa {
+    /// Timestep for the outer loop
+    timestep: f64,
+    /// Number of inner steps for each outer step
+    inner: usize,
+    /// Accelerations coming from the fast (bonded) forces
+    fast: Vec<Vector3D>,
+    /// Accelerations coming from the slow (non-bonded) forces
+    slow: Vec<Vector3D>,
+}
+
+impl Respa {
+    /// Create a new integrator with an outer timestep of `timestep`, using
+    /// `inner` steps to integrate the fast forces during each outer step.
+    ///
+    /// # Panics
+    ///
+    /// If `inner` is zero.
+    pub fn new(timestep: f64, inner: usize) -> Respa {
+        assert!(inner > 0, "the number of inner steps must be positive in RESPA integrator");
+        Respa {
+            timestep: timestep,
+            inner: inner,
+            fast: Vec::new(),
+            slow: Vec::new(),
+        }
+    }
+}
+
+/// Set `accelerations` to `forces / mass` for all particles in the `system`
+fn update_accelerations(system: &System, forces: Vec<Vector3D>, accelerations: &mut [Vector3D]) {
+    for (&mass, acceleration, force) in soa_zip!(system.particles(), [mass], accelerations, forces) {
+        *acceleration = compute_acceleration(force, mass);
+    }
+}
+
+/// Update the velocities in the `system` using the `accelerations` during `dt`
+fn kick(system: &mut System, accelerations: &[Vector3D], dt: f64) {
+    for (velocity, acceleration) in soa_zip!(system.particles_mut(), [mut velocity], accelerations) {
+        *velocity += dt * acceleration;
+    }
+}
+
+impl Integrator for Respa {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.fast = vec![Vector3D::zero(); system.size()];
+        self.slow = vec![Vector3D::zero(); system.size()];
+        update_accelerations(system, BondedForces.compute(system), &mut self.fast);
+        update_accelerations(system, NonBondedForces.compute(system), &mut self.slow);
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let inner_dt = dt / self.inner as f64;
+
+        // Update velocities at t + ∆t/2 with the slow forces
+        kick(system, &self.slow, 0.5 * dt);
+
+        for _ in 0..self.inner {
+            // Velocity-Verlet step using the fast forces only
+            kick(system, &self.fast, 0.5 * inner_dt);
+            for (position, velocity) in soa_zip!(system.particles_mut(), [mut position, velocity]) {
+                *position += velocity * inner_dt;
+            }
+            system.update_virtual_sites();
+            update_accelerations(system, BondedForces.compute(system), &mut self.fast);
+            kick(system, &self.fast, 0.5 * inner_dt);
+        }
+
+        // Update velocities at t + ∆t with the slow forces
+        update_accelerations(system, NonBondedForces.compute(system), &mut self.slow);
+        kick(system, &self.slow, 0.5 * dt);
+    }
+}
+
 /// This is needed for the `BerendsenBarostat` implementation. The value comes
 /// from the DL_POLY source code.
 const WATER_COMPRESSIBILITY: f64 = 7372.0;
 
+/// Internal quantities of a barostat integrator after an integration step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarostatState {
+    /// Instantaneous pressure of the system, as used by the barostat
+    pub pressure: f64,
+    /// Target pressure of the barostat
+    pub target: f64,
+    /// Volume of the unit cell
+    pub volume: f64,
+    /// Scaling factor of the cell lengths, to be applied at the next step.
+    /// For anisotropic barostats, this is the cubic root of the determinant
+    /// of the scaling matrix.
+    pub scaling: f64,
+}
+
+/// Shared handle to the internal state of a barostat integrator, updated at
+/// each integration step. This is used to follow the barostat during a
+/// simulation, for example with a
+/// [`BarostatOutput`](../output/struct.BarostatOutput.html).
+#[derive(Clone, Debug, Default)]
+pub struct BarostatDiagnostics(Rc<Cell<Option<BarostatState>>>);
+
+impl BarostatDiagnostics {
+    /// Get the state of the barostat after the last integration step, or
+    /// `None` if the barostat did not run yet.
+    pub fn get(&self) -> Option<BarostatState> {
+        self.0.get()
+    }
+
+    fn set(&self, state: BarostatState) {
+        self.0.set(Some(state));
+    }
+}
+
 /// Berendsen barostat integrator based on velocity-Verlet.
 ///
 /// This integrator is **neither** time-reversible nor symplectic.
@@ -186,6 +547,8 @@ pub struct BerendsenBarostat {
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: f64,
+    /// Internal state shared with the outputs
+    diagnostics: BarostatDiagnostics,
 }
 
 impl BerendsenBarostat {
@@ -199,11 +562,32 @@ impl BerendsenBarostat {
             tau: tau,
             accelerations: Vec::new(),
             eta: 1.0,
+            diagnostics: BarostatDiagnostics::default(),
         }
     }
+
+    /// Get a handle to the internal state of this barostat, updated at each
+    /// integration step.
+    pub fn diagnostics(&self) -> BarostatDiagnostics {
+        self.diagnostics.clone()
+    }
 }
 
 impl Integrator for BerendsenBarostat {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn save_state(&mut self, prefix: &str, state: &mut PropagatorState) {
+        state.set(&format!("{}.eta", prefix), self.eta);
+    }
+
+    fn restore_state(&mut self, prefix: &str, state: &PropagatorState) {
+        if let Some(eta) = state.get(&format!("{}.eta", prefix)) {
+            self.eta = eta;
+        }
+    }
+
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
     }
@@ -222,6 +606,7 @@ impl Integrator for BerendsenBarostat {
         }
 
         system.cell.scale_mut(self.eta * self.eta * self.eta * Matrix3::one());
+        system.update_virtual_sites();
 
         if let Some(maximum_cutoff) = system.maximum_cutoff() {
             if system.cell.lengths().iter().any(|&d| 0.5 * d <= maximum_cutoff) {
@@ -234,15 +619,22 @@ impl Integrator for BerendsenBarostat {
             }
         };
 
-        let eta3 = 1.0 - WATER_COMPRESSIBILITY / self.tau * (self.pressure - system.pressure());
+        let pressure = system.pressure();
+        let eta3 = 1.0 - WATER_COMPRESSIBILITY / self.tau * (self.pressure - pressure);
         self.eta = f64::cbrt(eta3);
+        self.diagnostics.set(BarostatState {
+            pressure: pressure,
+            target: self.pressure,
+            volume: system.volume(),
+            scaling: self.eta,
+        });
 
         let forces = system.forces();
         // Update accelerations at t + ∆t and velocities at t + ∆t
         for (velocity, &mass, acceleration, force) in soa_zip!(
             system.particles_mut(), [mut velocity, mass], &mut self.accelerations, &forces
         ) {
-            *acceleration = force / mass;
+            *acceleration = compute_acceleration(*force, mass);
             *velocity += 0.5 * dt * acceleration;
         }
     }
@@ -262,6 +654,8 @@ pub struct AnisoBerendsenBarostat {
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: Matrix3,
+    /// Internal state shared with the outputs
+    diagnostics: BarostatDiagnostics,
 }
 
 impl AnisoBerendsenBarostat {
@@ -275,6 +669,7 @@ impl AnisoBerendsenBarostat {
             tau: tau,
             accelerations: Vec::new(),
             eta: Matrix3::one(),
+            diagnostics: BarostatDiagnostics::default(),
         }
     }
 
@@ -284,9 +679,38 @@ impl AnisoBerendsenBarostat {
     pub fn hydrostatic(timestep: f64, pressure: f64, tau: f64) -> AnisoBerendsenBarostat {
         AnisoBerendsenBarostat::new(timestep, pressure * Matrix3::one(), tau)
     }
+
+    /// Get a handle to the internal state of this barostat, updated at each
+    /// integration step. The target pressure is the hydrostatic pressure
+    /// corresponding to the target stress matrix.
+    pub fn diagnostics(&self) -> BarostatDiagnostics {
+        self.diagnostics.clone()
+    }
 }
 
 impl Integrator for AnisoBerendsenBarostat {
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn save_state(&mut self, prefix: &str, state: &mut PropagatorState) {
+        for i in 0..3 {
+            for j in 0..3 {
+                state.set(&format!("{}.eta.{}{}", prefix, i, j), self.eta[i][j]);
+            }
+        }
+    }
+
+    fn restore_state(&mut self, prefix: &str, state: &PropagatorState) {
+        for i in 0..3 {
+            for j in 0..3 {
+                if let Some(eta) = state.get(&format!("{}.eta.{}{}", prefix, i, j)) {
+                    self.eta[i][j] = eta;
+                }
+            }
+        }
+    }
+
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
     }
@@ -305,6 +729,7 @@ impl Integrator for AnisoBerendsenBarostat {
         }
 
         system.cell.scale_mut(self.eta);
+        system.update_virtual_sites();
 
         if let Some(maximum_cutoff) = system.maximum_cutoff() {
             if system.cell.lengths().iter().any(|&d| 0.5 * d <= maximum_cutoff) {
@@ -328,13 +753,327 @@ impl Integrator for AnisoBerendsenBarostat {
             }
         }
 
+        self.diagnostics.set(BarostatState {
+            pressure: system.pressure(),
+            target: self.stress.trace() / 3.0,
+            volume: system.volume(),
+            scaling: f64::cbrt(self.eta.determinant()),
+        });
+
         let forces = system.forces();
         // Update accelerations at t + ∆t and velocities at t + ∆t
         for (velocity, &mass, acceleration, force) in soa_zip!(
             system.particles_mut(), [mut velocity, mass], &mut self.accelerations, &forces
         ) {
-            *acceleration = force / mass;
+            *acceleration = compute_acceleration(*force, mass);
             *velocity += 0.5 * dt * acceleration;
         }
     }
 }
+
+/// Rigid body integrator for molecules, based on velocity-Verlet.
+///
+/// Each molecule is integrated as a single rigid body: the center-of-mass
+/// moves under the net force acting on the molecule, and the orientation of
+/// the molecule (stored as a quaternion) evolves under the torque created by
+/// the atomic forces. The free rotation of the molecules is integrated using
+/// the splitting method from Dullweber, Leimkuhler and McLachlan (J. Chem.
+/// Phys. 107, 5840 (1997)).
+///
+/// The distances and angles inside the molecules are kept fixed at their
+/// values when calling `setup`.
+///
+/// This integrator is time-reversible and symplectic (volume preserving).
+pub struct RigidBody {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Rigid body data for all the molecules in the system
+    bodies: Vec<Body>,
+}
+
+/// Rigid body data for a single molecule
+struct Body {
+    /// Total mass of the molecule
+    mass: f64,
+    /// Principal moments of inertia of the molecule
+    inertia: Vector3D,
+    /// Positions of the particles relative to the center-of-mass, expressed
+    /// in the principal axes frame
+    positions: Vec<Vector3D>,
+    /// Orientation of the molecule, rotating the principal axes frame to the
+    /// laboratory frame
+    orientation: Quaternion,
+    /// Net force acting on the molecule
+    force: Vector3D,
+    /// Net torque acting on the molecule, in the laboratory frame
+    torque: Vector3D,
+}
+
+impl RigidBody {
+    /// Create a new integrator with a timestep of `timestep`.
+    pub fn new(timestep: f64) -> RigidBody {
+        RigidBody {
+            timestep: timestep,
+            bodies: Vec::new(),
+        }
+    }
+}
+
+impl Integrator for RigidBody {
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        DegreesOfFreedom::Molecules
+    }
+
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.bodies.clear();
+        for molecule in system.molecules() {
+            let com = molecule.center_of_mass();
+            let (moments, axes) = principal_axes(&molecule.inertia());
+            // Linear molecules and single particles have vanishing moments
+            // of inertia along some axes, that we can not rotate around.
+            let threshold = 1e-9 * moments.max();
+            let inertia = Vector3D::new(
+                if moments[0] > threshold { moments[0] } else { 0.0 },
+                if moments[1] > threshold { moments[1] } else { 0.0 },
+                if moments[2] > threshold { moments[2] } else { 0.0 },
+            );
+
+            let inverse = axes.transposed();
+            let positions = molecule.particles().position.iter()
+                .map(|position| inverse * (position - com))
+                .collect();
+
+            self.bodies.push(Body {
+                mass: molecule.particles().mass.iter().sum(),
+                inertia: inertia,
+                positions: positions,
+                orientation: Quaternion::from_rotation(&axes),
+                force: Vector3D::zero(),
+                torque: Vector3D::zero(),
+            });
+        }
+
+        let forces = system.forces();
+        for (body, molecule) in self.bodies.iter_mut().zip(system.molecules()) {
+            let com = molecule.center_of_mass();
+            let forces = &forces[molecule.start()..molecule.end()];
+            let (force, torque) = force_and_torque(molecule.particles().position, com, forces);
+            body.force = force;
+            body.torque = torque;
+        }
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+
+        // Center-of-mass velocities and angular momenta at t + ∆t/2
+        let mut momenta = Vec::with_capacity(self.bodies.len());
+        for (body, mut molecule) in self.bodies.iter_mut().zip(system.molecules_mut()) {
+            let com = molecule.as_ref().center_of_mass();
+            let particles = molecule.particles();
+            let (mut velocity, mut angular) = rigid_momenta(particles.mass, particles.position, particles.velocity, com);
+            velocity /= body.mass;
+
+            velocity += 0.5 * dt * body.force / body.mass;
+            angular += 0.5 * dt * body.torque;
+
+            // Update the center-of-mass and orientation at t + ∆t. The
+            // angular momentum in the laboratory frame is conserved during
+            // free rotation.
+            let com = com + dt * velocity;
+            body.free_rotation(&angular, dt);
+
+            let rotation = body.orientation.rotation_matrix();
+            for (position, body_position) in molecule.particles_mut().position.iter_mut().zip(&body.positions) {
+                *position = com + rotation * body_position;
+            }
+            momenta.push((com, velocity, angular));
+        }
+
+        let forces = system.forces();
+        // Update velocities and angular momenta at t + ∆t
+        for ((body, mut molecule), (com, velocity, angular)) in self.bodies.iter_mut().zip(system.molecules_mut()).zip(momenta) {
+            let range = molecule.start()..molecule.end();
+            let (force, torque) = force_and_torque(molecule.particles().position, com, &forces[range]);
+            body.force = force;
+            body.torque = torque;
+
+            let velocity = velocity + 0.5 * dt * body.force / body.mass;
+            let angular = angular + 0.5 * dt * body.torque;
+
+            let omega = body.angular_velocity(&angular);
+            let rotation = body.orientation.rotation_matrix();
+            for (particle_velocity, body_position) in molecule.particles_mut().velocity.iter_mut().zip(&body.positions) {
+                *particle_velocity = velocity + (omega ^ (rotation * body_position));
+            }
+        }
+    }
+}
+
+impl Body {
+    /// Integrate the free rotation of this body during `dt`, with the given
+    /// `angular` momentum in the laboratory frame. This updates the
+    /// orientation of the body.
+    fn free_rotation(&mut self, angular: &Vector3D, dt: f64) {
+        let mut body_angular = self.orientation.rotation_matrix().transposed() * angular;
+        self.rotate_around(0, &mut body_angular, 0.5 * dt);
+        self.rotate_around(1, &mut body_angular, 0.5 * dt);
+        self.rotate_around(2, &mut body_angular, dt);
+        self.rotate_around(1, &mut body_angular, 0.5 * dt);
+        self.rotate_around(0, &mut body_angular, 0.5 * dt);
+        self.orientation = self.orientation.normalized();
+    }
+
+    /// Rotate this body around the principal `axis` during `dt`, updating
+    /// the orientation and the angular momentum in the body frame.
+    fn rotate_around(&mut self, axis: usize, body_angular: &mut Vector3D, dt: f64) {
+        if self.inertia[axis] == 0.0 {
+            return;
+        }
+
+        let angle = body_angular[axis] / self.inertia[axis] * dt;
+        let (sin, cos) = f64::sin_cos(angle);
+        let a = (axis + 1) % 3;
+        let b = (axis + 2) % 3;
+        let (angular_a, angular_b) = (body_angular[a], body_angular[b]);
+        body_angular[a] = cos * angular_a + sin * angular_b;
+        body_angular[b] = cos * angular_b - sin * angular_a;
+
+        let (sin, cos) = f64::sin_cos(0.5 * angle);
+        let mut vector = Vector3D::zero();
+        vector[axis] = sin;
+        self.orientation = self.orientation * Quaternion::new(cos, vector);
+    }
+
+    /// Get the angular velocity in the laboratory frame corresponding to the
+    /// `angular` momentum in the laboratory frame.
+    fn angular_velocity(&self, angular: &Vector3D) -> Vector3D {
+        let rotation = self.orientation.rotation_matrix();
+        let mut omega = rotation.transposed() * angular;
+        for i in 0..3 {
+            if self.inertia[i] == 0.0 {
+                omega[i] = 0.0;
+            } else {
+                omega[i] /= self.inertia[i];
+            }
+        }
+        return rotation * omega;
+    }
+}
+
+/// Get the total momentum and the angular momentum around `com` of a set of
+/// particles.
+fn rigid_momenta(masses: &[f64], positions: &[Vector3D], velocities: &[Vector3D], com: Vector3D) -> (Vector3D, Vector3D) {
+    let mut momentum = Vector3D::zero();
+    let mut angular = Vector3D::zero();
+    for ((&mass, position), velocity) in masses.iter().zip(positions).zip(velocities) {
+        momentum += mass * velocity;
+        angular += mass * ((position - com) ^ velocity);
+    }
+    return (momentum, angular);
+}
+
+/// Get the net force and the torque around `com` created by the `forces`
+/// acting on particles at `positions`.
+fn force_and_torque(positions: &[Vector3D], com: Vector3D, forces: &[Vector3D]) -> (Vector3D, Vector3D) {
+    let mut force = Vector3D::zero();
+    let mut torque = Vector3D::zero();
+    for (position, particle_force) in positions.iter().zip(forces) {
+        force += particle_force;
+        torque += (position - com) ^ particle_force;
+    }
+    return (force, torque);
+}
+
+/// Diagonalize the symmetric `inertia` tensor. This function returns the
+/// principal moments of inertia and the corresponding principal axes, stored
+/// as the columns of a proper rotation matrix.
+fn principal_axes(inertia: &Matrix3) -> (Vector3D, Matrix3) {
+    let (moments, mut axes) = inertia.symmetric_eigen();
+    if axes.determinant() < 0.0 {
+        for i in 0..3 {
+            axes[i][2] = -axes[i][2];
+        }
+    }
+    return (Vector3D::new(moments[0], moments[1], moments[2]), axes);
+}
+
+/// Unit quaternion representing a rotation
+#[derive(Clone, Copy, Debug)]
+struct Quaternion {
+    /// Scalar part of the quaternion
+    w: f64,
+    /// Vector part of the quaternion
+    v: Vector3D,
+}
+
+impl Quaternion {
+    fn new(w: f64, v: Vector3D) -> Quaternion {
+        Quaternion { w: w, v: v }
+    }
+
+    /// Get the quaternion corresponding to the proper `rotation` matrix
+    fn from_rotation(rotation: &Matrix3) -> Quaternion {
+        let m = rotation;
+        let trace = m.trace();
+        let quaternion = if trace > 0.0 {
+            let s = 2.0 * f64::sqrt(trace + 1.0);
+            Quaternion::new(0.25 * s, Vector3D::new(
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            ))
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * f64::sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]);
+            Quaternion::new((m[2][1] - m[1][2]) / s, Vector3D::new(
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            ))
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * f64::sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]);
+            Quaternion::new((m[0][2] - m[2][0]) / s, Vector3D::new(
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s,
+            ))
+        } else {
+            let s = 2.0 * f64::sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]);
+            Quaternion::new((m[1][0] - m[0][1]) / s, Vector3D::new(
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s,
+            ))
+        };
+        return quaternion.normalized();
+    }
+
+    fn normalized(&self) -> Quaternion {
+        let norm = f64::sqrt(self.w * self.w + self.v.norm2());
+        Quaternion::new(self.w / norm, self.v / norm)
+    }
+
+    /// Get the rotation matrix corresponding to this quaternion
+    fn rotation_matrix(&self) -> Matrix3 {
+        let (w, x, y, z) = (self.w, self.v[0], self.v[1], self.v[2]);
+        Matrix3::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+        ])
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.w - self.v * other.v,
+            self.w * other.v + other.w * self.v + (self.v ^ other.v),
+        )
+    }
+}
@@ -23,7 +23,11 @@
 //! - [`BerendsenBarostat`](struct.BerendsenBarostat.html): isotropic Berendsen
 //!   barostat coupled to a velocity-Verlet integrator;
 //! - [`AnisoBerendsenBarostat`](struct.AnisoBerendsenBarostat.html) anisotropic
-//!   Berendsen barostat coupled to a velocity-Verlet integrator
+//!   Berendsen barostat coupled to a velocity-Verlet integrator;
+//! - [`RigidBody`](struct.RigidBody.html): velocity-Verlet integrator treating
+//!   all molecules as rigid bodies;
+//! - [`Respa`](struct.Respa.html): multiple time step integrator, using a
+//!   shorter timestep for the bonded forces.
 //!
 //! # Themostats
 //!
@@ -39,6 +43,11 @@
 //! - [`BerendsenThermostat`](struct.BerendsenThermostat.html): berendsen or
 //!   weak-coupling thermostat;
 //!
+//! All these thermostats can be restricted to a
+//! [`Selection`](struct.Selection.html) of the particles, and multiple
+//! thermostats can be used in the same simulation to control the temperature
+//! of different regions independently.
+//!
 //! # Control
 //!
 //! [`Control`](trait.Control.html) algorihtms group any algorithm modifying the
@@ -56,7 +65,10 @@ pub use self::integrators::Integrator;
 
 pub use self::integrators::AnisoBerendsenBarostat;
 pub use self::integrators::BerendsenBarostat;
+pub use self::integrators::{BarostatDiagnostics, BarostatState};
 pub use self::integrators::LeapFrog;
+pub use self::integrators::Respa;
+pub use self::integrators::RigidBody;
 pub use self::integrators::VelocityVerlet;
 pub use self::integrators::Verlet;
 
@@ -67,6 +79,9 @@ pub use self::controls::RemoveRotation;
 pub use self::controls::RemoveTranslation;
 pub use self::controls::Rewrap;
 
+mod selection;
+pub use self::selection::Selection;
+
 mod thermostats;
 pub use self::thermostats::Thermostat;
 
@@ -74,5 +89,8 @@ pub use self::thermostats::RescaleThermostat;
 pub use self::thermostats::BerendsenThermostat;
 pub use self::thermostats::CSVRThermostat;
 
+mod constraints;
+pub use self::constraints::Constraints;
+
 mod molecular_dynamics;
 pub use self::molecular_dynamics::MolecularDynamics;
@@ -1,20 +1,29 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use crate::propagator::{Propagator, TemperatureStrategy};
+use crate::propagator::{Propagator, PropagatorState, SeedSequence, TemperatureStrategy};
+use crate::SimulationError;
 use lumol_core::{System, DegreesOfFreedom};
 
 use super::{Control, Integrator, Thermostat};
 use super::VelocityVerlet;
+use super::Constraints;
 
 /// Molecular Dynamics propagator for the simulation.
 pub struct MolecularDynamics {
     /// The integrator we should use to propagate the equations of motion.
     integrator: Box<dyn Integrator>,
-    /// Optional thermostat algorithm
-    thermostat: Option<Box<dyn Thermostat>>,
+    /// Thermostat algorithms, each one acting on all the particles or on a
+    /// selection of the particles
+    thermostats: Vec<Box<dyn Thermostat>>,
     /// Control algorithms in the simulation.
     controls: Vec<Box<dyn Control>>,
+    /// Indexes of the frozen particles
+    frozen: Vec<usize>,
+    /// Indexes of the rigid molecules
+    rigid: Vec<usize>,
+    /// Error which stopped the last step, if any
+    error: Option<SimulationError>,
 }
 
 impl MolecularDynamics {
@@ -29,19 +38,93 @@ impl MolecularDynamics {
     pub fn from_integrator(integrator: Box<dyn Integrator>) -> MolecularDynamics {
         MolecularDynamics {
             integrator: integrator,
-            thermostat: None,
+            thermostats: Vec::new(),
             controls: Vec::new(),
+            frozen: Vec::new(),
+            rigid: Vec::new(),
+            error: None,
         }
     }
 
+    /// Get the timestep of the integrator used by this propagator, if the
+    /// integrator has one.
+    pub fn timestep(&self) -> Option<f64> {
+        self.integrator.timestep()
+    }
+
     /// Add a control algorithm to the internal list of controls.
     pub fn add_control(&mut self, control: Box<dyn Control>) {
         self.controls.push(control);
     }
 
-    /// Set the thermostat to use with this simulation
+    /// Set the thermostat to use with this simulation, replacing any
+    /// previously added thermostat.
     pub fn set_thermostat(&mut self, thermostat: Box<dyn Thermostat>) {
-        self.thermostat = Some(thermostat);
+        self.thermostats.clear();
+        self.thermostats.push(thermostat);
+    }
+
+    /// Add a thermostat to use with this simulation, in addition to the
+    /// existing ones. This is intended to control the temperature of
+    /// different regions of the system independently, using thermostats
+    /// restricted to non-overlapping selections of particles.
+    pub fn add_thermostat(&mut self, thermostat: Box<dyn Thermostat>) {
+        self.thermostats.push(thermostat);
+    }
+
+    /// Freeze the particles at the given `indexes`. The frozen particles keep
+    /// the positions they have at the beginning of the simulation, and their
+    /// velocities are set to zero inside each step of the integrator. Each
+    /// frozen particle removes three degrees of freedom from the system.
+    ///
+    /// # Panics
+    ///
+    /// If the integrator does not support [`Constraints`], such as
+    /// integrators propagating rigid molecules.
+    ///
+    /// [`Constraints`]: struct.Constraints.html
+    pub fn freeze(&mut self, indexes: &[usize]) {
+        assert!(
+            self.integrator.constraints_mut().is_some(),
+            "can not freeze particles with an integrator which does not support constraints"
+        );
+        self.frozen.extend_from_slice(indexes);
+        self.frozen.sort_unstable();
+        self.frozen.dedup();
+    }
+
+    /// Keep the molecules at the given `indexes` rigid. The distances
+    /// between the particles in these molecules are constrained to their
+    /// values at the beginning of the simulation, using the SHAKE and RATTLE
+    /// algorithms inside each step of the integrator. Each rigid molecule
+    /// only keeps six degrees of freedom (five for linear molecules).
+    ///
+    /// # Panics
+    ///
+    /// If the integrator does not support [`Constraints`], such as
+    /// integrators propagating rigid molecules, which already keep all the
+    /// molecules rigid.
+    ///
+    /// [`Constraints`]: struct.Constraints.html
+    pub fn set_rigid(&mut self, indexes: &[usize]) {
+        assert!(
+            self.integrator.constraints_mut().is_some(),
+            "can not use rigid molecules with an integrator which does not support constraints"
+        );
+        self.rigid.extend_from_slice(indexes);
+        self.rigid.sort_unstable();
+        self.rigid.dedup();
+    }
+
+    /// Check that all the rigid molecules exist in the `system`
+    fn check_rigid(&self, system: &System) {
+        if let Some(&last) = self.rigid.last() {
+            assert!(
+                last < system.molecules().count(),
+                "can not make molecule {} rigid, the system only contains {} molecules",
+                last, system.molecules().count()
+            );
+        }
     }
 }
 
@@ -50,29 +133,127 @@ impl Propagator for MolecularDynamics {
         TemperatureStrategy::Velocities
     }
 
-    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
-        // default to particles for now. change this if/when constrains are
-        // implemented
-        DegreesOfFreedom::Particles
+    fn timestep(&self) -> Option<f64> {
+        self.integrator.timestep()
+    }
+
+    fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom {
+        let degrees_of_freedom = self.integrator.degrees_of_freedom();
+        if self.frozen.is_empty() && self.rigid.is_empty() {
+            return degrees_of_freedom;
+        }
+
+        self.check_rigid(system);
+        let frozen = 3 * self.frozen.len() + Constraints::removed_degrees_of_freedom(system, &self.rigid);
+        match degrees_of_freedom {
+            DegreesOfFreedom::Particles => DegreesOfFreedom::Frozen(frozen),
+            DegreesOfFreedom::Frozen(n) => DegreesOfFreedom::Frozen(n + frozen),
+            // Frozen particles and rigid molecules are only used with
+            // integrators supporting constraints, which do not propagate
+            // rigid molecules
+            DegreesOfFreedom::Molecules => DegreesOfFreedom::Molecules,
+        }
+    }
+
+    fn can_set_temperature(&self) -> bool {
+        !self.thermostats.is_empty() && self.thermostats.iter().all(|thermostat| thermostat.can_set_temperature())
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        assert!(
+            !self.thermostats.is_empty(),
+            "can not set the temperature of a molecular dynamics simulation without thermostat"
+        );
+        for thermostat in &mut self.thermostats {
+            thermostat.set_temperature(temperature);
+        }
+    }
+
+    fn seed(&mut self, seeds: &mut SeedSequence) {
+        for thermostat in &mut self.thermostats {
+            thermostat.seed(seeds.next_seed());
+        }
+    }
+
+    fn save_state(&mut self, state: &mut PropagatorState) {
+        self.integrator.save_state("md.integrator", state);
+        state.set("md.thermostats", self.thermostats.len());
+        for (i, thermostat) in self.thermostats.iter_mut().enumerate() {
+            thermostat.save_state(&format!("md.thermostat.{}", i), state);
+        }
+    }
+
+    fn restore_state(&mut self, state: &PropagatorState) {
+        self.integrator.restore_state("md.integrator", state);
+        let nthermostats = state.expect::<usize>("md.thermostats");
+        assert_eq!(
+            nthermostats, self.thermostats.len(),
+            "can not restore the state of a molecular dynamics simulation with \
+             {} thermostats from a state with {} thermostats",
+            self.thermostats.len(), nthermostats
+        );
+        for (i, thermostat) in self.thermostats.iter_mut().enumerate() {
+            thermostat.restore_state(&format!("md.thermostat.{}", i), state);
+        }
     }
 
     fn setup(&mut self, system: &System) {
+        if let Some(&last) = self.frozen.last() {
+            assert!(
+                last < system.size(),
+                "can not freeze particle {}, the system only contains {} particles",
+                last, system.size()
+            );
+        }
+        self.check_rigid(system);
+        if let Some(constraints) = self.integrator.constraints_mut() {
+            *constraints = Constraints::rigid_molecules(system, &self.rigid);
+            constraints.freeze(&self.frozen);
+        }
+
         self.integrator.setup(system);
+        assert!(
+            system.cell.shear_rate() == 0.0 || self.integrator.timestep().is_some(),
+            "can not use Lees-Edwards boundary conditions with an integrator without timestep"
+        );
         for control in &mut self.controls {
             control.setup(system);
         }
     }
 
     fn propagate(&mut self, system: &mut System) {
+        self.integrator.update_timestep(system);
+        // Move the periodic images for Lees-Edwards boundary conditions
+        // before computing the forces at the new positions, using the
+        // timestep of this step
+        let shear_offset = system.cell.shear_offset();
+        if let Some(timestep) = self.integrator.timestep() {
+            system.cell.advance_shear(timestep);
+        }
         self.integrator.integrate(system);
+        if let Some(error) = self.integrator.take_error() {
+            // The system is left unchanged by a failed step
+            system.cell.set_shear_offset(shear_offset);
+            self.error = Some(error);
+            return;
+        }
 
-        if let Some(ref mut thermostat) = self.thermostat {
+        for thermostat in &mut self.thermostats {
             thermostat.apply(system);
         }
 
         for control in &mut self.controls {
             control.control(system);
         }
+        // Thermostats and controls can change all the velocities, reset the
+        // frozen particles and the constrained velocities
+        if let Some(constraints) = self.integrator.constraints_mut() {
+            constraints.project_velocities(system);
+        }
+    }
+
+    fn take_error(&mut self) -> Option<SimulationError> {
+        self.error.take()
     }
 
     fn finish(&mut self, system: &System) {
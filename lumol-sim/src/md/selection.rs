@@ -0,0 +1,179 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use lumol_core::{System, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+
+/// A `Selection` is a subset of the particles in a system, used to apply
+/// some algorithm (for example a thermostat) to a region or a species only.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_core::{System, Molecule, Particle};
+/// # use lumol_sim::md::Selection;
+/// let mut system = System::new();
+/// system.add_molecule(Molecule::new(Particle::new("Ar")));
+/// system.add_molecule(Molecule::new(Particle::new("Kr")));
+/// system.add_molecule(Molecule::new(Particle::new("Ar")));
+///
+/// let selection = Selection::with_name(&system, "Ar");
+/// assert_eq!(selection.indexes(), &[0, 2]);
+///
+/// let selection = Selection::new(vec![2, 1, 2]);
+/// assert_eq!(selection.indexes(), &[1, 2]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selection {
+    /// Sorted indexes of the selected particles
+    indexes: Vec<usize>,
+}
+
+impl Selection {
+    /// Create a new `Selection` containing the particles at the given
+    /// `indexes`.
+    pub fn new(mut indexes: Vec<usize>) -> Selection {
+        indexes.sort_unstable();
+        indexes.dedup();
+        Selection {
+            indexes: indexes,
+        }
+    }
+
+    /// Create a new `Selection` containing all the particles with the given
+    /// `name` in the `system`.
+    pub fn with_name(system: &System, name: &str) -> Selection {
+        let indexes = system.particles().name.iter()
+            .enumerate()
+            .filter(|(_, particle)| *particle == name)
+            .map(|(i, _)| i)
+            .collect();
+        Selection::new(indexes)
+    }
+
+    /// Create a new `Selection` containing all the particles in the `system`
+    /// inside the rectangular region going from `lower` to `upper`. The
+    /// region is checked against the current positions of the particles,
+    /// without wrapping them in the unit cell, and the selection is not
+    /// updated when the particles move.
+    pub fn within(system: &System, lower: Vector3D, upper: Vector3D) -> Selection {
+        let indexes = system.particles().position.iter()
+            .enumerate()
+            .filter(|(_, position)| (0..3).all(|k| lower[k] <= position[k] && position[k] < upper[k]))
+            .map(|(i, _)| i)
+            .collect();
+        Selection::new(indexes)
+    }
+
+    /// Get the indexes of the selected particles
+    pub fn indexes(&self) -> &[usize] {
+        &self.indexes
+    }
+
+    /// Check that all the selected particles are inside the `system`
+    fn check(&self, system: &System) {
+        if let Some(&last) = self.indexes.last() {
+            assert!(
+                last < system.size(),
+                "can not select particle {}, the system only contains {} particles",
+                last, system.size()
+            );
+        }
+    }
+
+    /// Get the kinetic energy of the selected particles in the `system`
+    pub fn kinetic_energy(&self, system: &System) -> f64 {
+        self.check(system);
+        let particles = system.particles();
+        self.indexes.iter().map(|&i| 0.5 * particles.mass[i] * particles.velocity[i].norm2()).sum()
+    }
+
+    /// Get the number of degrees of freedom of the selected particles in the
+    /// `system`. Massless particles (virtual sites) do not contribute to the
+    /// degrees of freedom.
+    pub fn degrees_of_freedom(&self, system: &System) -> usize {
+        self.check(system);
+        let masses = system.particles().mass;
+        3 * self.indexes.iter().filter(|&&i| masses[i] > 0.0).count()
+    }
+
+    /// Get the temperature of the selected particles in the `system`, using
+    /// their own kinetic energy and degrees of freedom. The temperature of a
+    /// selection without degrees of freedom is zero.
+    pub fn temperature(&self, system: &System) -> f64 {
+        let dof = self.degrees_of_freedom(system);
+        if dof == 0 {
+            return 0.0;
+        }
+        return 2.0 * self.kinetic_energy(system) / (dof as f64 * K_BOLTZMANN);
+    }
+
+    /// Multiply the velocities of the selected particles in the `system` by
+    /// `factor`.
+    pub fn scale_velocities(&self, system: &mut System, factor: f64) {
+        self.check(system);
+        let velocities = system.particles_mut().velocity;
+        for &i in &self.indexes {
+            velocities[i] *= factor;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumol_core::{Molecule, Particle, Vector3D};
+
+    use approx::assert_ulps_eq;
+
+    fn testing_system() -> System {
+        let mut system = System::new();
+        for (i, &name) in ["Ar", "Kr", "Ar", "Kr"].iter().enumerate() {
+            let mut particle = Particle::new(name);
+            particle.velocity = Vector3D::new(i as f64 + 1.0, 0.0, 0.0);
+            system.add_molecule(Molecule::new(particle));
+        }
+        system
+    }
+
+    #[test]
+    fn kinetic_energy() {
+        let mut system = testing_system();
+        let selection = Selection::with_name(&system, "Kr");
+        assert_eq!(selection.indexes(), &[1, 3]);
+        assert_eq!(selection.degrees_of_freedom(&system), 6);
+
+        let mass = system.particles().mass[1];
+        let kinetic = 0.5 * mass * (4.0 + 16.0);
+        assert_ulps_eq!(selection.kinetic_energy(&system), kinetic);
+        assert_ulps_eq!(selection.temperature(&system), 2.0 * kinetic / (6.0 * K_BOLTZMANN));
+
+        selection.scale_velocities(&mut system, 2.0);
+        assert_eq!(system.particles().velocity[0], Vector3D::new(1.0, 0.0, 0.0));
+        assert_eq!(system.particles().velocity[1], Vector3D::new(4.0, 0.0, 0.0));
+        assert_eq!(system.particles().velocity[2], Vector3D::new(3.0, 0.0, 0.0));
+        assert_eq!(system.particles().velocity[3], Vector3D::new(8.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn within() {
+        let mut system = testing_system();
+        for (i, position) in system.particles_mut().position.iter_mut().enumerate() {
+            *position = Vector3D::new(i as f64, 0.0, 0.0);
+        }
+        let selection = Selection::within(&system, Vector3D::new(0.5, -1.0, -1.0), Vector3D::new(3.0, 1.0, 1.0));
+        assert_eq!(selection.indexes(), &[1, 2]);
+
+        let selection = Selection::within(&system, Vector3D::new(0.5, 1.0, -1.0), Vector3D::new(3.0, 2.0, 1.0));
+        assert!(selection.indexes().is_empty());
+        assert_eq!(selection.degrees_of_freedom(&system), 0);
+        assert_eq!(selection.temperature(&system), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not select particle 6, the system only contains 4 particles")]
+    fn out_of_bounds() {
+        let system = testing_system();
+        let _ = Selection::new(vec![0, 6]).kinetic_energy(&system);
+    }
+}
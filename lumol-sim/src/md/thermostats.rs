@@ -4,10 +4,14 @@
 use lumol_core::System;
 use lumol_core::consts::K_BOLTZMANN;
 
-use rand::{self, SeedableRng};
+use rand;
 use rand_distr::{Distribution, Normal, Gamma};
 
 use crate::velocities;
+use crate::PropagatorState;
+use crate::rng::PropagatorRng;
+
+use super::Selection;
 
 /// Trait for thermostat algorithms some parameters in a system during a simulation.
 pub trait Thermostat {
@@ -15,10 +19,39 @@ pub trait Thermostat {
     /// for some setup of the thermostat algorithm if needed.
     fn setup(&mut self, _: &System) {}
 
+    /// Set the `seed` of the random number generator used by this thermostat.
+    /// The default implementation does nothing, for deterministic
+    /// thermostats.
+    fn seed(&mut self, _: u64) {}
+
     /// Main thermostating function. THis should update the system velocities
     /// in some way to produce constant temperature
     fn apply(&mut self, system: &mut System);
 
+    /// Check if the target temperature of this thermostat can be changed with
+    /// `set_temperature`. The default implementation returns `false`.
+    fn can_set_temperature(&self) -> bool {
+        false
+    }
+
+    /// Set the target `temperature` of this thermostat. The default
+    /// implementation panics, for thermostats which do not support changing
+    /// the temperature during a simulation.
+    fn set_temperature(&mut self, _temperature: f64) {
+        panic!("this thermostat does not support changing the temperature");
+    }
+
+    /// Save the internal state of this thermostat (random number generator,
+    /// extended variables, ...) in `state`, using `prefix` for the names of
+    /// the values. The default implementation does nothing, for thermostats
+    /// without internal state.
+    fn save_state(&mut self, _prefix: &str, _state: &mut PropagatorState) {}
+
+    /// Restore the internal state of this thermostat from a `state` created
+    /// by `save_state` with the same `prefix`. The default implementation
+    /// does nothing.
+    fn restore_state(&mut self, _prefix: &str, _state: &PropagatorState) {}
+
     /// Function called once at the end of the simulation.
     fn finish(&mut self, _: &System) {}
 }
@@ -37,11 +70,16 @@ pub trait Thermostat {
 /// is done at every step. It can It can still be usefull in the equilibration
 /// of a system at a given temperature before an actual simulation. A good
 /// alternative is the CSVR thermostat, which produces correct ensemble.
+///
+/// This thermostat can be restricted to a subset of the particles with
+/// [`set_selection`](#method.set_selection).
 pub struct RescaleThermostat {
     /// Target temperature
     temperature: f64,
     /// Tolerance in temperature
     tol: f64,
+    /// Particles controlled by this thermostat, `None` for all particles
+    selection: Option<Selection>,
 }
 
 impl RescaleThermostat {
@@ -59,15 +97,45 @@ impl RescaleThermostat {
         RescaleThermostat {
             temperature: temperature,
             tol: tol,
+            selection: None,
         }
     }
+
+    /// Only apply this thermostat to the particles in `selection`, using the
+    /// temperature of these particles.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = Some(selection);
+    }
 }
 
 impl Thermostat for RescaleThermostat {
+    fn can_set_temperature(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        assert!(temperature >= 0.0, "The temperature must be positive in thermostats.");
+        self.temperature = temperature;
+    }
+
     fn apply(&mut self, system: &mut System) {
-        let instant_temperature = system.temperature();
+        let instant_temperature = match self.selection {
+            Some(ref selection) => selection.temperature(system),
+            None => system.temperature(),
+        };
+        if instant_temperature == 0.0 {
+            // There is nothing to rescale
+            return;
+        }
+
         if f64::abs(instant_temperature - self.temperature) > self.tol {
-            velocities::scale(system, self.temperature);
+            match self.selection {
+                Some(ref selection) => {
+                    let factor = f64::sqrt(self.temperature / instant_temperature);
+                    selection.scale_velocities(system, factor);
+                }
+                None => velocities::scale(system, self.temperature),
+            }
         }
     }
 }
@@ -88,12 +156,17 @@ impl Thermostat for RescaleThermostat {
 /// [1] Berendsen et al. J. Chem Phys 81, 3684 (1984); doi: 10.1063/1.448118
 ///
 /// [2] Braun et al. J. Chem. Theo. Comp. 14, 10 (2018) doi: 10.1021/acs.jctc
+///
+/// This thermostat can be restricted to a subset of the particles with
+/// [`set_selection`](#method.set_selection).
 pub struct BerendsenThermostat {
     /// Target temperature
     temperature: f64,
     /// Timestep of the thermostat, expressed as a multiplicative factor of the
     /// integrator timestep.
     tau: f64,
+    /// Particles controlled by this thermostat, `None` for all particles
+    selection: Option<Selection>,
 }
 
 impl BerendsenThermostat {
@@ -105,17 +178,38 @@ impl BerendsenThermostat {
         BerendsenThermostat {
             temperature: temperature,
             tau: tau,
+            selection: None,
         }
     }
+
+    /// Only apply this thermostat to the particles in `selection`, using the
+    /// temperature of these particles.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = Some(selection);
+    }
 }
 
 impl Thermostat for BerendsenThermostat {
+    fn can_set_temperature(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        assert!(temperature >= 0.0, "The temperature must be positive in thermostats.");
+        self.temperature = temperature;
+    }
+
     fn apply(&mut self, system: &mut System) {
-        let instant_temperature = system.temperature();
-        let factor = f64::sqrt(1.0 + (self.temperature / instant_temperature - 1.0) / self.tau);
-        for velocity in system.particles_mut().velocity {
-            *velocity *= factor;
+        let instant_temperature = match self.selection {
+            Some(ref selection) => selection.temperature(system),
+            None => system.temperature(),
+        };
+        if instant_temperature == 0.0 {
+            // There is nothing to rescale
+            return;
         }
+        let factor = f64::sqrt(1.0 + (self.temperature / instant_temperature - 1.0) / self.tau);
+        scale_velocities(system, self.selection.as_ref(), factor);
     }
 }
 
@@ -130,6 +224,9 @@ impl Thermostat for BerendsenThermostat {
 /// For a more in-depth description of the algorithm, see [1].
 ///
 /// [1] Bussi et al. J. Chem. Phys. 126, 014101 (2007) doi: 10.1063/1.2408420
+///
+/// This thermostat can be restricted to a subset of the particles with
+/// [`set_selection`](#method.set_selection).
 pub struct CSVRThermostat {
     /// Target kinetic energy for the system, per degree of freedom
     target_kinetic_per_dof: f64,
@@ -137,26 +234,29 @@ pub struct CSVRThermostat {
     /// integrator timestep.
     tau: f64,
     /// Random number generator for the stochatsic propagation of kinetic energy
-    rng: Box<dyn rand::RngCore>,
+    rng: PropagatorRng,
     /// normal (i.e. gaussian) distribution
     normal: Normal<f64>,
+    /// Particles controlled by this thermostat, `None` for all particles
+    selection: Option<Selection>,
 }
 
 impl CSVRThermostat {
     /// Create a new `CSVRThermostat` enforcing the given `temperature`, with a
     /// timestep of `tau` times the integrator timestep.
     pub fn new(temperature: f64, tau: f64) -> CSVRThermostat {
-        let rng = Box::new(rand_xorshift::XorShiftRng::from_seed([
-            0xeb, 0xa8, 0xe4, 0x29, 0xca, 0x60, 0x44, 0xb0,
-            0xd3, 0x77, 0xc6, 0xa0, 0x21, 0x71, 0x37, 0xf7,
-        ]));
-        return CSVRThermostat::from_rng(temperature, tau, rng)
+        return CSVRThermostat::with_propagator_rng(temperature, tau, PropagatorRng::new());
     }
 
     /// Create a new `CSVRThermostat` enforcing the given `temperature`, with a
     /// timestep of `tau` times the integrator timestep, using the given `rng`
-    /// when generating random noise.
+    /// when generating random noise. The state of this generator is not saved
+    /// with the state of the thermostat.
     pub fn from_rng(temperature: f64, tau: f64, rng: Box<dyn rand::RngCore>) -> CSVRThermostat {
+        return CSVRThermostat::with_propagator_rng(temperature, tau, PropagatorRng::Custom(rng));
+    }
+
+    fn with_propagator_rng(temperature: f64, tau: f64, rng: PropagatorRng) -> CSVRThermostat {
         assert!(temperature >= 0.0, "The temperature must be positive in thermostats.");
         assert!(tau >= 1.0, "The timestep must be larger than 1 in CSVR thermostat.");
         CSVRThermostat {
@@ -164,9 +264,16 @@ impl CSVRThermostat {
             tau: tau,
             rng: rng,
             normal: Normal::new(0.0, 1.0).expect("bad normal distribution"),
+            selection: None,
         }
     }
 
+    /// Only apply this thermostat to the particles in `selection`, using the
+    /// kinetic energy and degrees of freedom of these particles.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = Some(selection);
+    }
+
     /// Get the sum of n independent gaussian noises squared, i.e. the Wiener
     /// noise in equation 4 of Bussi2007.
     ///
@@ -193,19 +300,56 @@ impl CSVRThermostat {
 }
 
 impl Thermostat for CSVRThermostat {
+    fn seed(&mut self, seed: u64) {
+        self.rng = PropagatorRng::seeded(seed);
+    }
+
+    fn save_state(&mut self, prefix: &str, state: &mut PropagatorState) {
+        self.rng.save_state(&format!("{}.rng", prefix), state);
+    }
+
+    fn restore_state(&mut self, prefix: &str, state: &PropagatorState) {
+        self.rng.restore_state(&format!("{}.rng", prefix), state);
+    }
+
+    fn can_set_temperature(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&mut self, temperature: f64) {
+        assert!(temperature >= 0.0, "The temperature must be positive in thermostats.");
+        self.target_kinetic_per_dof = K_BOLTZMANN * temperature / 2.0;
+    }
+
     fn apply(&mut self, system: &mut System) {
-        let kinetic = system.kinetic_energy();
+        let (kinetic, dof) = match self.selection {
+            Some(ref selection) => (selection.kinetic_energy(system), selection.degrees_of_freedom(system)),
+            None => (system.kinetic_energy(), system.degrees_of_freedom()),
+        };
+        if dof == 0 || kinetic == 0.0 {
+            // There is nothing to rescale
+            return;
+        }
         let kinetic_factor = self.target_kinetic_per_dof / kinetic;
         let exp_1 = f64::exp(-1.0/self.tau);
         let exp_2 = (1.0 - exp_1) * kinetic_factor;
 
-        let dof = system.degrees_of_freedom();
         let (gauss, wiener) = self.sum_noises(dof - 1);
 
         let scale = exp_1 + exp_2 * (gauss * gauss + wiener) + 2.0 * gauss * f64::sqrt(exp_1 * exp_2);
         let alpha = f64::sqrt(scale);
+        scale_velocities(system, self.selection.as_ref(), alpha);
+    }
+}
+
+/// Multiply the velocities of the particles in `selection` by `factor`, or
+/// the velocities of all the particles if `selection` is `None`.
+fn scale_velocities(system: &mut System, selection: Option<&Selection>, factor: f64) {
+    if let Some(selection) = selection {
+        selection.scale_velocities(system, factor);
+    } else {
         for velocity in system.particles_mut().velocity {
-            *velocity *= alpha;
+            *velocity *= factor;
         }
     }
 }
@@ -3,10 +3,14 @@
 
 //! Energy minimization algorithms
 use std::f64;
+use std::time::{Duration, Instant};
 
-use log::info;
+use soa_derive::soa_zip;
 
-use lumol_core::{System, DegreesOfFreedom};
+use log::{info, warn};
+
+use lumol_core::{System, DegreesOfFreedom, Vector3D};
+use lumol_core::units;
 
 use crate::propagator::{Propagator, TemperatureStrategy};
 
@@ -24,7 +28,8 @@ pub struct Tolerance {
 /// lower energy.
 pub trait Minimizer {
     /// Setup the minimizer. This function is called once at the begining of
-    /// every simulation run.
+    /// every simulation run, and when the minimization goes back to the
+    /// configuration with the lowest energy.
     fn setup(&mut self, _: &System) {}
 
     /// Find a new configuration of lower energy, and return the corresponding
@@ -37,16 +42,69 @@ pub trait Minimizer {
     fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom;
 }
 
+/// Reason for the end of a minimization
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// The energy difference between two successive steps was lower than the
+    /// energy tolerance
+    EnergyTolerance,
+    /// The maximal squared norm of the atomic force was lower than the force
+    /// tolerance
+    ForceTolerance,
+    /// The maximal number of iterations was reached, or the simulation ran all
+    /// its steps without converging
+    MaxIterations,
+    /// The maximal wall-clock time was reached
+    MaxTime,
+}
+
+/// A single step of a minimization, as recorded in the minimization history
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimizationStep {
+    /// Index of the step, starting at 1
+    pub iteration: u64,
+    /// Potential energy of the system after this step
+    pub energy: f64,
+    /// Maximal norm of the atomic force after this step
+    pub max_force: f64,
+}
+
+/// Callback function called after every minimization step
+type Callback = Box<dyn FnMut(&MinimizationStep)>;
+
 /// Minimization propagator for simulations.
 ///
 /// The minimization stops when the energy difference between the previous and
 /// the current step is lower than the energy criterion, or when the maximal
 /// squared norm of the atomic force is lower than the force criterion.
+///
+/// The minimization can also be stopped after a maximal number of iterations,
+/// or a maximal wall-clock time. In this case, the system is left in the
+/// configuration with the lowest energy found during the minimization.
+///
+/// The energy and maximal force at every step are recorded in the
+/// [`history`](struct.Minimization.html#method.history), and can also be
+/// passed to a user-provided callback.
 pub struct Minimization {
     minimizer: Box<dyn Minimizer>,
-    is_converged: bool,
+    termination: Option<Termination>,
     last_energy: f64,
     tolerance: Tolerance,
+    history: Vec<MinimizationStep>,
+    callback: Option<Callback>,
+    /// Maximal number of iterations, if any
+    max_iterations: Option<u64>,
+    /// Maximal wall-clock time, if any
+    max_time: Option<Duration>,
+    /// Wall-clock time at the beginning of the minimization
+    start: Instant,
+    /// Lowest energy found during the minimization
+    best_energy: f64,
+    /// Positions corresponding to the lowest energy
+    best_positions: Vec<Vector3D>,
+    /// Largest force in the current configuration, and the index of the
+    /// particle it acts on
+    max_force: Option<(f64, usize)>,
 }
 
 impl Minimization {
@@ -55,15 +113,85 @@ impl Minimization {
     pub fn new(minimizer: Box<dyn Minimizer>, tolerance: Tolerance) -> Minimization {
         Minimization {
             minimizer: minimizer,
-            is_converged: false,
+            termination: None,
             last_energy: 0.0,
             tolerance: tolerance,
+            history: Vec::new(),
+            callback: None,
+            max_iterations: None,
+            max_time: None,
+            start: Instant::now(),
+            best_energy: f64::INFINITY,
+            best_positions: Vec::new(),
+            max_force: None,
         }
     }
 
+    /// Stop the minimization after at most `max_iterations` steps, even if it
+    /// did not converge.
+    ///
+    /// # Panics
+    ///
+    /// If `max_iterations` is zero.
+    pub fn set_max_iterations(&mut self, max_iterations: u64) {
+        assert!(max_iterations > 0, "the maximal number of iterations must be positive in minimization");
+        self.max_iterations = Some(max_iterations);
+    }
+
+    /// Stop the minimization after it ran for `max_time` of wall-clock time,
+    /// even if it did not converge. The time is checked after every step.
+    pub fn set_max_time(&mut self, max_time: Duration) {
+        self.max_time = Some(max_time);
+    }
+
+    /// Set a `callback` function, called after every step of the minimization
+    /// with the energy and maximal force for this step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lumol_sim::min::{Minimization, SteepestDescent, Tolerance};
+    /// let tolerance = Tolerance { energy: 1e-5, force2: 1e-5 };
+    /// let mut minimization = Minimization::new(Box::new(SteepestDescent::new()), tolerance);
+    /// minimization.set_callback(|step| {
+    ///     println!("step {}: energy = {}, max force = {}", step.iteration, step.energy, step.max_force);
+    /// });
+    /// ```
+    pub fn set_callback<F>(&mut self, callback: F) where F: FnMut(&MinimizationStep) + 'static {
+        self.callback = Some(Box::new(callback));
+    }
+
     /// Check if the minimization has converged.
     pub fn converged(&self) -> bool {
-        self.is_converged
+        match self.termination {
+            Some(Termination::EnergyTolerance | Termination::ForceTolerance) => true,
+            Some(Termination::MaxIterations | Termination::MaxTime) | None => false,
+        }
+    }
+
+    /// Get the reason for the end of the minimization, or `None` if the
+    /// minimization is still running.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    /// Get the history of this minimization, containing the energy and
+    /// maximal force for every step since the beginning of the last
+    /// simulation run.
+    pub fn history(&self) -> &[MinimizationStep] {
+        &self.history
+    }
+
+    /// Put the `system` back in the configuration with the lowest energy
+    /// found during the minimization, and update the energy and the
+    /// minimizer state to match this configuration.
+    fn restore_best(&mut self, system: &mut System) {
+        for (position, best) in soa_zip!(system.particles_mut(), [mut position], &self.best_positions) {
+            *position = *best;
+        }
+        self.last_energy = self.best_energy;
+        self.max_force = system.max_force();
+        self.minimizer.setup(system);
     }
 }
 
@@ -77,28 +205,242 @@ impl Propagator for Minimization {
     }
 
     fn setup(&mut self, system: &System) {
-        self.is_converged = false;
+        self.termination = None;
+        self.history.clear();
         self.last_energy = system.potential_energy();
+        self.best_energy = self.last_energy;
+        self.best_positions = system.particles().position.to_vec();
+        self.max_force = None;
+        self.start = Instant::now();
         self.minimizer.setup(system);
     }
 
     fn propagate(&mut self, system: &mut System) {
-        if self.is_converged {
+        if self.termination.is_some() {
             return;
         }
 
         let result = self.minimizer.minimize(system);
+        self.max_force = system.max_force();
+        let max_force = self.max_force.map_or(0.0, |(force, _)| force);
 
-        if result.force2 < self.tolerance.force2 {
-            self.is_converged = true;
+        let step = MinimizationStep {
+            iteration: self.history.len() as u64 + 1,
+            energy: result.energy,
+            max_force: max_force,
+        };
+        self.history.push(step);
+        if let Some(ref mut callback) = self.callback {
+            callback(&step);
+        }
+
+        if max_force * max_force < self.tolerance.force2 {
+            self.termination = Some(Termination::ForceTolerance);
             info!("Minimization converged on force tolerance");
         }
 
         if (self.last_energy - result.energy).abs() < self.tolerance.energy {
-            self.is_converged = true;
+            self.termination = Some(Termination::EnergyTolerance);
             info!("Minimization converged on energy tolerance");
         }
 
         self.last_energy = result.energy;
+        if self.termination.is_some() {
+            return;
+        }
+
+        if result.energy < self.best_energy {
+            self.best_energy = result.energy;
+            for (position, best) in soa_zip!(system.particles(), [position], &mut self.best_positions) {
+                *best = *position;
+            }
+        }
+
+        if self.max_iterations.map_or(false, |max| step.iteration >= max) {
+            self.termination = Some(Termination::MaxIterations);
+            warn!("Minimization stopped after reaching the maximal number of iterations");
+        } else if self.max_time.map_or(false, |max| self.start.elapsed() >= max) {
+            self.termination = Some(Termination::MaxTime);
+            warn!("Minimization stopped after reaching the maximal time");
+        }
+
+        if self.termination.is_some() && result.energy > self.best_energy {
+            self.restore_best(system);
+        }
+    }
+
+    fn finish(&mut self, _: &System) {
+        if self.termination.is_none() {
+            self.termination = Some(Termination::MaxIterations);
+            warn!("Minimization did not converge after {} steps", self.history.len());
+        }
+
+        if let Some((force, i)) = self.max_force {
+            let force = units::to(force, "kJ/mol/A").expect("bad unit");
+            if self.converged() {
+                info!("Largest force after minimization: {} kJ/mol/A on particle {}", force, i);
+            } else {
+                warn!("Largest force after minimization: {} kJ/mol/A on particle {}", force, i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::min::SteepestDescent;
+    use lumol_core::{Harmonic, PairInteraction};
+    use lumol_core::{Molecule, Particle, UnitCell};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_molecule(Molecule::new(Particle::with_position("Cl", [0.0, 0.0, 0.0].into())));
+        system.add_molecule(Molecule::new(Particle::with_position("Cl", [0.0, 0.0, 2.0].into())));
+
+        let pair = PairInteraction::new(Box::new(Harmonic { x0: 2.3, k: 0.1 }), 10.0);
+        system.set_pair_potential(("Cl", "Cl"), pair).unwrap();
+        return system;
+    }
+
+    #[test]
+    fn history() {
+        let mut system = testing_system();
+        let tolerance = Tolerance { energy: 1e-10, force2: 1e-20 };
+        let mut minimization = Minimization::new(Box::new(SteepestDescent::new()), tolerance);
+
+        let steps = Rc::new(RefCell::new(Vec::new()));
+        let callback_steps = Rc::clone(&steps);
+        minimization.set_callback(move |step| callback_steps.borrow_mut().push(*step));
+
+        minimization.setup(&system);
+        assert_eq!(minimization.termination(), None);
+        for _ in 0..100 {
+            minimization.propagate(&mut system);
+        }
+        minimization.finish(&system);
+
+        assert!(minimization.converged());
+        assert_eq!(minimization.termination(), Some(Termination::EnergyTolerance));
+
+        let history = minimization.history();
+        assert!(history.len() < 100);
+        assert_eq!(history, &steps.borrow()[..]);
+        for (i, step) in history.iter().enumerate() {
+            assert_eq!(step.iteration, i as u64 + 1);
+        }
+        assert!(history[0].energy > history[history.len() - 1].energy);
+        assert_eq!(history[history.len() - 1].energy, system.potential_energy());
+        assert_eq!(history[history.len() - 1].max_force, system.max_force().unwrap().0);
+    }
+
+    #[test]
+    fn max_iterations() {
+        let mut system = testing_system();
+        let tolerance = Tolerance { energy: 0.0, force2: 0.0 };
+        let mut minimization = Minimization::new(Box::new(SteepestDescent::new()), tolerance);
+        minimization.set_max_iterations(10);
+
+        minimization.setup(&system);
+        for _ in 0..1000 {
+            minimization.propagate(&mut system);
+        }
+
+        assert!(!minimization.converged());
+        assert_eq!(minimization.termination(), Some(Termination::MaxIterations));
+        assert_eq!(minimization.history().len(), 10);
+        assert_eq!(minimization.history()[9].energy, system.potential_energy());
+    }
+
+    /// Minimizer moving the second particle away from the first one, and
+    /// counting the number of calls to `setup`
+    struct MoveAway {
+        setups: Rc<RefCell<usize>>,
+    }
+
+    impl Minimizer for MoveAway {
+        fn setup(&mut self, _: &System) {
+            *self.setups.borrow_mut() += 1;
+        }
+
+        fn minimize(&mut self, system: &mut System) -> Tolerance {
+            system.particles_mut().position[1][2] += 0.5;
+            return Tolerance {
+                energy: system.potential_energy(),
+                force2: 1.0,
+            };
+        }
+
+        fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+            DegreesOfFreedom::Particles
+        }
+    }
+
+    #[test]
+    fn restore_best() {
+        let mut system = testing_system();
+        let setups = Rc::new(RefCell::new(0));
+        let minimizer = Box::new(MoveAway { setups: Rc::clone(&setups) });
+        let mut minimization = Minimization::new(minimizer, Tolerance { energy: 0.0, force2: 0.0 });
+        minimization.set_max_iterations(5);
+
+        minimization.setup(&system);
+        assert_eq!(*setups.borrow(), 1);
+        for _ in 0..10 {
+            minimization.propagate(&mut system);
+        }
+
+        // The lowest energy was found after the first step, with the
+        // particles 2.5 A apart
+        assert_eq!(minimization.termination(), Some(Termination::MaxIterations));
+        assert_eq!(system.particles().position[1], Vector3D::new(0.0, 0.0, 2.5));
+        assert_eq!(minimization.history()[0].energy, system.potential_energy());
+        assert_eq!(minimization.last_energy, system.potential_energy());
+        assert_eq!(*setups.borrow(), 2);
+    }
+
+    #[test]
+    fn max_time() {
+        let mut system = testing_system();
+        let tolerance = Tolerance { energy: 0.0, force2: 0.0 };
+        let mut minimization = Minimization::new(Box::new(SteepestDescent::new()), tolerance);
+        minimization.set_max_time(Duration::from_secs(0));
+
+        minimization.setup(&system);
+        for _ in 0..1000 {
+            minimization.propagate(&mut system);
+        }
+
+        assert!(!minimization.converged());
+        assert_eq!(minimization.termination(), Some(Termination::MaxTime));
+        assert_eq!(minimization.history().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the maximal number of iterations must be positive in minimization")]
+    fn zero_max_iterations() {
+        let minimizer = Box::new(SteepestDescent::new());
+        let mut minimization = Minimization::new(minimizer, Tolerance { energy: 0.0, force2: 0.0 });
+        minimization.set_max_iterations(0);
+    }
+
+    #[test]
+    fn not_converged() {
+        let mut system = testing_system();
+        let tolerance = Tolerance { energy: 1e-10, force2: 1e-20 };
+        let mut minimization = Minimization::new(Box::new(SteepestDescent::new()), tolerance);
+
+        minimization.setup(&system);
+        for _ in 0..3 {
+            minimization.propagate(&mut system);
+        }
+        minimization.finish(&system);
+
+        assert!(!minimization.converged());
+        assert_eq!(minimization.termination(), Some(Termination::MaxIterations));
+        assert_eq!(minimization.history().len(), 3);
     }
 }
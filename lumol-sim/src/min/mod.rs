@@ -6,6 +6,7 @@
 mod minimization;
 pub use self::minimization::Minimization;
 pub use self::minimization::Minimizer;
+pub use self::minimization::{MinimizationStep, Termination};
 pub use self::minimization::Tolerance;
 
 mod steepest_descent;
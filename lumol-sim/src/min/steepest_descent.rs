@@ -20,12 +20,21 @@ impl SteepestDescent {
     /// Create a new `SteepestDescent` minimizer
     pub fn new() -> SteepestDescent {
         SteepestDescent {
-            gamma: units::from(0.1, "fs^2/u").expect("bad unit"),
+            gamma: initial_gamma(),
         }
     }
 }
 
+/// Initial value of the damping factor
+fn initial_gamma() -> f64 {
+    units::from(0.1, "fs^2/u").expect("bad unit")
+}
+
 impl Minimizer for SteepestDescent {
+    fn setup(&mut self, _: &System) {
+        self.gamma = initial_gamma();
+    }
+
     fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
         DegreesOfFreedom::Particles
     }
@@ -83,7 +92,7 @@ mod tests {
         system.add_molecule(Molecule::new(Particle::with_position("Cl", [0.0, 0.0, 2.0].into())));
 
         let pair = PairInteraction::new(Box::new(Harmonic { x0: 2.3, k: 0.1 }), 10.0);
-        system.set_pair_potential(("Cl", "Cl"), pair);
+        system.set_pair_potential(("Cl", "Cl"), pair).unwrap();
         return system;
     }
 
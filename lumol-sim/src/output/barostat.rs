@@ -0,0 +1,94 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::error;
+
+use super::{FileMode, Output, OutputFile};
+use crate::md::BarostatDiagnostics;
+
+use lumol_core::System;
+use lumol_core::units::UnitSystem;
+
+/// The `BarostatOutput` writes the internal quantities of a barostat
+/// integrator to a file, to check the coupling between pressure and volume.
+/// The columns in the file contain the instantaneous pressure used by the
+/// barostat, the target pressure, the volume of the cell, and the scaling
+/// factor of the cell lengths for the next step.
+///
+/// Nothing is written for the steps before the first integration step of the
+/// barostat.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use lumol_sim::Simulation;
+/// # use lumol_sim::md::{BerendsenBarostat, MolecularDynamics};
+/// # use lumol_sim::output::BarostatOutput;
+/// let barostat = BerendsenBarostat::new(1.0, 100.0, 1000.0);
+/// let output = BarostatOutput::new("barostat.dat", barostat.diagnostics()).unwrap();
+///
+/// let md = MolecularDynamics::from_integrator(Box::new(barostat));
+/// let mut simulation = Simulation::new(Box::new(md));
+/// simulation.add_output(Box::new(output));
+/// ```
+pub struct BarostatOutput {
+    file: OutputFile,
+    diagnostics: BarostatDiagnostics,
+    units: UnitSystem,
+}
+
+impl BarostatOutput {
+    /// Create a new `BarostatOutput` writing the state of the barostat with the
+    /// given `diagnostics` handle to `filename`. The file is replaced if it
+    /// already exists, unless the simulation is resumed (see `FileMode::Auto`).
+    pub fn new<P: AsRef<Path>>(filename: P, diagnostics: BarostatDiagnostics) -> Result<BarostatOutput, io::Error> {
+        BarostatOutput::with_mode(filename, diagnostics, FileMode::Auto)
+    }
+
+    /// Create a new `BarostatOutput` writing the state of the barostat with
+    /// the given `diagnostics` handle to `filename`, opening the file with the
+    /// given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, diagnostics: BarostatDiagnostics, mode: FileMode) -> Result<BarostatOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(BarostatOutput {
+            file: file,
+            diagnostics: diagnostics,
+            units: UnitSystem::Physical,
+        })
+    }
+}
+
+impl Output for BarostatOutput {
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
+    fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Barostat state during the simulation");
+        writeln_or_log!(self, "# Step {} {} {} Scaling",
+            self.units.label("Pressure", "bar"),
+            self.units.label("TargetPressure", "bar"),
+            self.units.label("Volume", "A^3"),
+        );
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        if let Some(state) = self.diagnostics.get() {
+            let pressure = self.units.to(state.pressure, "bar").expect("bad unit");
+            let target = self.units.to(state.target, "bar").expect("bad unit");
+            let volume = self.units.to(state.volume, "A^3").expect("bad unit");
+            writeln_or_log!(self, "{} {} {} {} {}", system.step, pressure, target, volume, state.scaling);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::error;
+
+use super::{FileMode, Output, OutputFile};
+
+use lumol_core::System;
+use lumol_core::compute::Compute;
+
+/// On-the-fly block averaging of a scalar property.
+///
+/// Successive values are accumulated in blocks of `block_size` values. The
+/// standard error on the mean is estimated from the variance of the block
+/// means. If the blocks are longer than the correlation time of the property,
+/// the block means are uncorrelated and this estimate accounts for the
+/// correlations between successive values, contrary to the naive estimate
+/// using the variance of all values.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_sim::output::BlockAverage;
+/// let mut average = BlockAverage::new(2);
+/// assert_eq!(average.add(1.0), None);
+/// assert_eq!(average.add(3.0), Some(2.0));
+/// assert_eq!(average.add(4.0), None);
+/// assert_eq!(average.add(6.0), Some(5.0));
+///
+/// assert_eq!(average.blocks(), 2);
+/// assert_eq!(average.mean(), Some(3.5));
+/// assert_eq!(average.standard_error(), Some(1.5));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BlockAverage {
+    /// Number of values in each block
+    block_size: usize,
+    /// Sum of the values in the current block
+    current_sum: f64,
+    /// Number of values in the current block
+    current_count: usize,
+    /// Number of complete blocks
+    blocks: usize,
+    /// Running mean of the block means
+    mean: f64,
+    /// Running sum of the squared deviations of the block means, using
+    /// Welford's algorithm
+    m2: f64,
+}
+
+impl BlockAverage {
+    /// Create a new `BlockAverage` with blocks containing `block_size` values.
+    ///
+    /// # Panics
+    ///
+    /// If `block_size` is zero.
+    pub fn new(block_size: usize) -> BlockAverage {
+        assert!(block_size > 0, "block size must be positive in BlockAverage");
+        BlockAverage {
+            block_size: block_size,
+            current_sum: 0.0,
+            current_count: 0,
+            blocks: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// Add a new `value` to the current block. If this completes the block,
+    /// the mean of the block is returned.
+    pub fn add(&mut self, value: f64) -> Option<f64> {
+        self.current_sum += value;
+        self.current_count += 1;
+        if self.current_count < self.block_size {
+            return None;
+        }
+
+        let block_mean = self.current_sum / self.block_size as f64;
+        self.current_sum = 0.0;
+        self.current_count = 0;
+
+        self.blocks += 1;
+        let delta = block_mean - self.mean;
+        self.mean += delta / self.blocks as f64;
+        self.m2 += delta * (block_mean - self.mean);
+        return Some(block_mean);
+    }
+
+    /// Get the number of complete blocks
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Get the mean of all the complete blocks, or `None` if no block is
+    /// complete yet.
+    pub fn mean(&self) -> Option<f64> {
+        if self.blocks == 0 {
+            None
+        } else {
+            Some(self.mean)
+        }
+    }
+
+    /// Get the standard error on the mean, estimated from the block-to-block
+    /// variance, or `None` if less than two blocks are complete.
+    ///
+    /// $$ \sigma = \sqrt{\frac{1}{n (n - 1)} \sum_b (\langle A \rangle_b -
+    ///    \langle A \rangle)^2} $$
+    ///
+    /// where $n$ is the number of blocks, $\langle A \rangle_b$ the mean of
+    /// block $b$ and $\langle A \rangle$ the mean of all blocks.
+    pub fn standard_error(&self) -> Option<f64> {
+        if self.blocks < 2 {
+            None
+        } else {
+            let n = self.blocks as f64;
+            Some(f64::sqrt(self.m2 / (n * (n - 1.0))))
+        }
+    }
+}
+
+/// The `BlockAverageOutput` computes a scalar property of the system, and
+/// writes block averages of this property to a file.
+///
+/// Each time a block is complete, a line is written with the step, the mean
+/// of the block, the running mean of all blocks and the running standard error
+/// on this mean. The values are written in Lumol internal units, and the
+/// standard error is `NaN` until two blocks are complete. The block size
+/// counts the number of times this output is called, and is thus affected by
+/// the output frequency.
+pub struct BlockAverageOutput<C: Compute<Output = f64>> {
+    file: OutputFile,
+    compute: C,
+    average: BlockAverage,
+}
+
+impl<C: Compute<Output = f64>> BlockAverageOutput<C> {
+    /// Create a new `BlockAverageOutput` writing block averages of `compute`
+    /// with blocks of `block_size` values to `filename`. The file is replaced
+    /// if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
+    pub fn new<P: AsRef<Path>>(filename: P, compute: C, block_size: usize) -> Result<BlockAverageOutput<C>, io::Error> {
+        BlockAverageOutput::with_mode(filename, compute, block_size, FileMode::Auto)
+    }
+
+    /// Create a new `BlockAverageOutput` writing block averages of `compute`
+    /// with blocks of `block_size` values to `filename`, opening the file with
+    /// the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, compute: C, block_size: usize, mode: FileMode) -> Result<BlockAverageOutput<C>, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(BlockAverageOutput {
+            file: file,
+            compute: compute,
+            average: BlockAverage::new(block_size),
+        })
+    }
+}
+
+impl<C: Compute<Output = f64>> Output for BlockAverageOutput<C> {
+    fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Block averages using blocks of {} values", self.average.block_size);
+        writeln_or_log!(self, "# Step BlockMean Mean StandardError");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        let value = self.compute.compute(system);
+        if let Some(block_mean) = self.average.add(value) {
+            let mean = self.average.mean().expect("missing mean for complete block");
+            let error = self.average.standard_error().unwrap_or(f64::NAN);
+            writeln_or_log!(self, "{} {} {} {}", system.step, block_mean, mean, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+    use lumol_core::compute::PotentialEnergy;
+
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, StandardNormal};
+    use rand_xorshift::XorShiftRng;
+
+    use approx::assert_ulps_eq;
+
+    /// Standard error of the mean of `n` values generated by an auto-regressive
+    /// process `x_{t+1} = phi x_t + sqrt(1 - phi^2) noise` with unit variance,
+    /// estimated with blocks of `block_size` values.
+    fn correlated_standard_error(phi: f64, n: usize, block_size: usize) -> f64 {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let mut average = BlockAverage::new(block_size);
+        let mut x: f64 = StandardNormal.sample(&mut rng);
+        for _ in 0..n {
+            let noise: f64 = StandardNormal.sample(&mut rng);
+            x = phi * x + f64::sqrt(1.0 - phi * phi) * noise;
+            let _ = average.add(x);
+        }
+        assert_eq!(average.blocks(), n / block_size);
+        return average.standard_error().unwrap();
+    }
+
+    #[test]
+    fn statistics() {
+        let mut average = BlockAverage::new(3);
+        assert_eq!(average.mean(), None);
+        assert_eq!(average.standard_error(), None);
+
+        for &value in &[1.0, 2.0, 3.0, 5.0, 5.0, 5.0, 0.0, 0.0, 3.0] {
+            let _ = average.add(value);
+        }
+        // Incomplete blocks are not used
+        assert_eq!(average.add(100.0), None);
+
+        assert_eq!(average.blocks(), 3);
+        let mean: f64 = 8.0 / 3.0;
+        assert_ulps_eq!(average.mean().unwrap(), mean);
+        let variance = (2.0 - mean).powi(2) + (5.0 - mean).powi(2) + (1.0 - mean).powi(2);
+        assert_ulps_eq!(average.standard_error().unwrap(), f64::sqrt(variance / 6.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "block size must be positive in BlockAverage")]
+    fn zero_block_size() {
+        let _ = BlockAverage::new(0);
+    }
+
+    #[test]
+    fn correlated_series() {
+        const N: usize = 1_000_000;
+        // For uncorrelated values, this is the usual standard error
+        let error = correlated_standard_error(0.0, N, 1000);
+        assert!(f64::abs(error * f64::sqrt(N as f64) - 1.0) < 0.1);
+
+        // For correlated values, the variance of the mean is multiplied by
+        // the statistical inefficiency (1 + phi) / (1 - phi)
+        for &phi in &[0.5, 0.9] {
+            let error = correlated_standard_error(phi, N, 1000);
+            let expected = f64::sqrt((1.0 + phi) / (1.0 - phi) / N as f64);
+            assert!(f64::abs(error / expected - 1.0) < 0.1);
+        }
+
+        // Too small blocks under-estimate the error
+        let error = correlated_standard_error(0.9, N, 2);
+        let expected = f64::sqrt(19.0 / N as f64);
+        assert!(error < 0.5 * expected);
+    }
+
+    #[test]
+    fn output() {
+        test_output(
+            |path| Box::new(BlockAverageOutput::new(path, PotentialEnergy, 1).unwrap()),
+            "# Block averages using blocks of 1 values
+            # Step BlockMean Mean StandardError
+            42 0.00015000000010503175 0.00015000000010503175 NaN
+            ",
+        );
+    }
+}
@@ -1,30 +1,35 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use log::error;
 
-use super::Output;
+use super::{FileMode, Output, OutputFile};
 use lumol_core::System;
 
 /// The `CellOutput` writes all the components of a cell to a file . The columns
 /// in the file contain the following values: `step A B C α β γ`.
 pub struct CellOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
 }
 
 impl CellOutput {
     /// Create a new `CellOutput` writing to `filename`. The file is replaced if
-    /// it already exists.
+    /// it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<CellOutput, io::Error> {
+        CellOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `CellOutput` writing to `filename`, opening the file
+    /// with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<CellOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(CellOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
         })
     }
 }
@@ -32,10 +37,17 @@ impl CellOutput {
 impl Output for CellOutput {
     #[allow(clippy::non_ascii_literal)]
     fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
         writeln_or_log!(self, "# Unit cell of the simulation");
         writeln_or_log!(self, "# Step A/Å B/Å C/Å α/deg β/deg γ/deg");
     }
 
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
     fn write(&mut self, system: &System) {
         writeln_or_log!(self, "{} {} {} {} {} {} {}",
             system.step,
@@ -0,0 +1,167 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::{error, warn};
+
+use super::{FileMode, Output, OutputFile};
+
+use lumol_core::System;
+use lumol_core::consts::K_BOLTZMANN;
+
+/// The `Compressibility` output estimates the isothermal compressibility of
+/// the system from the fluctuations of its volume.
+///
+/// $$ \kappa_T = \frac{\langle V^2 \rangle - \langle V \rangle^2}{k_B T
+///    \langle V \rangle} $$
+///
+/// where $T$ is the average temperature of the system. The mean and variance
+/// of the volume are accumulated with Welford's algorithm. Each time it is
+/// called, this output writes the step, the mean volume and the current
+/// estimate of the compressibility in Lumol internal units. The
+/// compressibility is `NaN` until two values are available.
+///
+/// This estimate is only meaningful in constant pressure simulations, a
+/// warning is emitted at the end of the simulation if the volume never
+/// changed.
+pub struct Compressibility {
+    file: OutputFile,
+    /// Number of accumulated values
+    count: u64,
+    /// Running mean of the volume
+    volume: f64,
+    /// Running sum of the squared deviations of the volume
+    m2: f64,
+    /// Running mean of the temperature
+    temperature: f64,
+}
+
+impl Compressibility {
+    /// Create a new `Compressibility` output writing to `filename`. The file is
+    /// replaced if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Compressibility, io::Error> {
+        Compressibility::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `Compressibility` output writing to `filename`, opening
+    /// the file with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<Compressibility, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(Compressibility {
+            file: file,
+            count: 0,
+            volume: 0.0,
+            m2: 0.0,
+            temperature: 0.0,
+        })
+    }
+
+    /// Add the `volume` of the system at the given `temperature` to the
+    /// accumulated values
+    fn add(&mut self, volume: f64, temperature: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let delta = volume - self.volume;
+        self.volume += delta / n;
+        self.m2 += delta * (volume - self.volume);
+        self.temperature += (temperature - self.temperature) / n;
+    }
+
+    /// Get the mean volume of the system, or `None` if no value was
+    /// accumulated yet.
+    pub fn mean_volume(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.volume)
+        }
+    }
+
+    /// Get the current estimate of the isothermal compressibility, or `None`
+    /// if less than two values were accumulated.
+    pub fn compressibility(&self) -> Option<f64> {
+        if self.count < 2 {
+            None
+        } else {
+            let variance = self.m2 / self.count as f64;
+            Some(variance / (K_BOLTZMANN * self.temperature * self.volume))
+        }
+    }
+}
+
+impl Output for Compressibility {
+    fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Isothermal compressibility from the volume fluctuations");
+        writeln_or_log!(self, "# Step <V> kappa_T");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        self.add(system.volume(), system.temperature());
+        let compressibility = self.compressibility().unwrap_or(f64::NAN);
+        writeln_or_log!(self, "{} {} {}", system.step, self.volume, compressibility);
+    }
+
+    fn finish(&mut self, _: &System) {
+        if self.count > 1 && self.m2 == 0.0 {
+            warn!(
+                "the volume did not change during the simulation, the \
+                 compressibility can only be computed with a barostat"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn output() {
+        test_output(
+            |path| Box::new(Compressibility::new(path).unwrap()),
+            "# Isothermal compressibility from the volume fluctuations
+            # Step <V> kappa_T
+            42 1000 NaN
+            ",
+        );
+    }
+
+    #[test]
+    fn compressibility() {
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let mut output = Compressibility::new(tempfile.path()).unwrap();
+        assert_eq!(output.mean_volume(), None);
+        assert_eq!(output.compressibility(), None);
+
+        output.add(1000.0, 300.0);
+        assert_eq!(output.compressibility(), None);
+        output.add(1200.0, 300.0);
+        output.add(1100.0, 300.0);
+        assert_ulps_eq!(output.mean_volume().unwrap(), 1100.0);
+
+        let variance = (100.0 * 100.0 + 100.0 * 100.0) / 3.0;
+        let expected = variance / (K_BOLTZMANN * 300.0 * 1100.0);
+        assert_ulps_eq!(output.compressibility().unwrap(), expected);
+
+        // The volume of the testing system does not change
+        let system = testing_system();
+        let mut output = Compressibility::new(tempfile.path()).unwrap();
+        output.write(&system);
+        output.write(&system);
+        assert_eq!(output.compressibility(), Some(0.0));
+    }
+}
@@ -3,10 +3,9 @@
 
 use std::error;
 use std::fmt;
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use caldyn::{Context, Expr};
 use caldyn::Error as CaldynError;
@@ -14,7 +13,7 @@ use caldyn::Error as CaldynError;
 use log::error;
 use log_once::{warn_once, error_once};
 
-use super::Output;
+use super::{FileMode, Output, OutputFile};
 use lumol_core::{units, System};
 
 /// Possible causes of error when using a custom output
@@ -176,6 +175,7 @@ fn get_output_context(system: &System) -> Context<'_> {
                 // scalar data
                 match name {
                     "step" => Some(system.step as f64),
+                    "time" => Some(system.time),
                     "pressure" => Some(system.pressure()),
                     "volume" => Some(system.volume()),
                     "temperature" => Some(system.temperature()),
@@ -250,28 +250,38 @@ fn parse_index(input: &str) -> (&str, usize) {
 ///   mass, `charge` for the atomic charge.
 /// - Physical properties: `pressure`, `volume`, `temperature`, `natoms`, stress
 ///   tensor components: `stress.xx`, `stress.yy`, `stress.zz`, `stress.xy`,
-///   `stress.xz`, `stress.yz`, simulation `step`.
+///   `stress.xz`, `stress.yz`, simulation `step` and `time`.
 /// - Unit Cell properties: `cell.a`, `cell.b`, `cell.c` are the unit cell
 ///   vector lengths; `cell.alpha`, `cell.beta` and `cell.gamma` are the unit
 ///   cell angles.
 pub struct CustomOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
     template: String,
     args: FormatArgs,
 }
 
 impl CustomOutput {
-    /// Create a new `CustomOutput` writing to the file at `filename` using
-    /// the given `template`. The `template` is only partially validated at
-    /// this stage.
+    /// Create a new `CustomOutput` writing to the file at `filename` using the
+    /// given `template`. The `template` is only partially validated at this
+    /// stage. The file is replaced if it already exists, unless the simulation
+    /// is resumed (see `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(
         filename: P,
         template: &str,
     ) -> Result<CustomOutput, CustomOutputError> {
+        CustomOutput::with_mode(filename, template, FileMode::Auto)
+    }
+
+    /// Create a new `CustomOutput` writing to the file at `filename` using
+    /// the given `template`, opening the file with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(
+        filename: P,
+        template: &str,
+        mode: FileMode,
+    ) -> Result<CustomOutput, CustomOutputError> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(CustomOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
             template: template.into(),
             args: FormatArgs::new(template)?,
         })
@@ -280,10 +290,17 @@ impl CustomOutput {
 
 impl Output for CustomOutput {
     fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
         writeln_or_log!(self, "# Custom output");
         writeln_or_log!(self, "# {}", self.template);
     }
 
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
     fn write(&mut self, system: &System) {
         if let Ok(formatted) = self.args.format(system) {
             writeln_or_log!(self, "{}", formatted);
@@ -291,6 +308,12 @@ impl Output for CustomOutput {
             error_once!("Could not evaluate custom output {}", self.template);
         }
     }
+
+    fn finish(&mut self, _: &System) {
+        if let Err(err) = self.file.writer.flush() {
+            error!("could not write to file '{}': {}", self.file.path.display(), err);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -355,6 +378,7 @@ mod tests {
         assert_eq!(format("{cell.a / m}"), "0.000000001");
 
         assert_eq!(format("{step}"), "42");
+        assert_eq!(format("{time}"), "0");
     }
 
     #[test]
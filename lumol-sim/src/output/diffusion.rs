@@ -0,0 +1,198 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::error;
+
+use super::{FileMode, Output, OutputFile};
+
+use lumol_core::{System, Vector3D};
+
+/// The `DiffusionCoefficient` output estimates the self-diffusion coefficient
+/// of the particles from the slope of their mean square displacement (MSD).
+///
+/// $$ D = \frac{1}{6} \frac{d}{dt} \left\langle \left|\vec r_i(t) - \vec
+///    r_i(0)\right|^2 \right\rangle $$
+///
+/// The positions of the particles at the beginning of the simulation are
+/// used as reference. The particles are followed across the periodic
+/// boundaries by accumulating the nearest image of their displacement
+/// between two calls to this output, so the particles should not move by
+/// more than half the cell between two calls. The slope of the MSD is
+/// obtained by a least-squares fit of the MSD as a function of the simulation
+/// time over the last `window` calls, which should be long enough for the
+/// motion of the particles to be diffusive.
+///
+/// Each time it is called, this output writes the step, the simulation time,
+/// the MSD and the current estimate of the diffusion coefficient in Lumol
+/// internal units. The diffusion coefficient is `NaN` until two values are
+/// available. The simulation time is only updated by propagators with a
+/// timestep, such as molecular dynamics.
+pub struct DiffusionCoefficient {
+    file: OutputFile,
+    /// Number of calls used to fit the MSD slope
+    window: usize,
+    /// Positions of the particles at the beginning of the simulation
+    reference: Vec<Vector3D>,
+    /// Positions of the particles followed across the periodic boundaries
+    unwrapped: Vec<Vector3D>,
+    /// Positions of the particles at the previous call
+    previous: Vec<Vector3D>,
+    /// Simulation time and MSD of the last `window` calls
+    history: VecDeque<(f64, f64)>,
+}
+
+impl DiffusionCoefficient {
+    /// Create a new `DiffusionCoefficient` output writing to `filename`,
+    /// fitting the MSD over the last `window` calls. The file is replaced if it
+    /// already exists, unless the simulation is resumed (see `FileMode::Auto`).
+    ///
+    /// # Panics
+    ///
+    /// If `window` is smaller than 2.
+    pub fn new<P: AsRef<Path>>(filename: P, window: usize) -> Result<DiffusionCoefficient, io::Error> {
+        DiffusionCoefficient::with_mode(filename, window, FileMode::Auto)
+    }
+
+    /// Create a new `DiffusionCoefficient` output writing to `filename`,
+    /// fitting the MSD over the last `window` calls and opening the file
+    /// with the given `mode`.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is smaller than 2.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, window: usize, mode: FileMode) -> Result<DiffusionCoefficient, io::Error> {
+        assert!(window >= 2, "the window must contain at least two values in DiffusionCoefficient");
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(DiffusionCoefficient {
+            file: file,
+            window: window,
+            reference: Vec::new(),
+            unwrapped: Vec::new(),
+            previous: Vec::new(),
+            history: VecDeque::new(),
+        })
+    }
+
+    /// Update the unwrapped positions with the current positions of the
+    /// particles in the `system`, and get the current MSD
+    fn mean_square_displacement(&mut self, system: &System) -> f64 {
+        let positions = system.particles().position;
+        assert_eq!(
+            positions.len(), self.reference.len(),
+            "the number of particles changed in DiffusionCoefficient output"
+        );
+
+        let mut msd = 0.0;
+        for (i, position) in positions.iter().enumerate() {
+            let mut delta = *position - self.previous[i];
+            system.cell.vector_image(&mut delta);
+            self.unwrapped[i] += delta;
+            self.previous[i] = *position;
+            msd += (self.unwrapped[i] - self.reference[i]).norm2();
+        }
+        return msd / positions.len() as f64;
+    }
+
+    /// Get the current estimate of the self-diffusion coefficient, or `None`
+    /// if less than two values are available.
+    pub fn coefficient(&self) -> Option<f64> {
+        if self.history.len() < 2 {
+            return None;
+        }
+
+        let n = self.history.len() as f64;
+        let mean_time = self.history.iter().map(|&(time, _)| time).sum::<f64>() / n;
+        let mean_msd = self.history.iter().map(|&(_, msd)| msd).sum::<f64>() / n;
+        let mut covariance = 0.0;
+        let mut variance = 0.0;
+        for &(time, msd) in &self.history {
+            covariance += (time - mean_time) * (msd - mean_msd);
+            variance += (time - mean_time) * (time - mean_time);
+        }
+        if variance == 0.0 {
+            return None;
+        }
+        return Some(covariance / variance / 6.0);
+    }
+}
+
+impl Output for DiffusionCoefficient {
+    fn setup(&mut self, system: &System) {
+        self.reference = system.particles().position.to_vec();
+        self.unwrapped = self.reference.clone();
+        self.previous = self.reference.clone();
+        self.history.clear();
+
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Self-diffusion coefficient from the mean square displacement");
+        writeln_or_log!(self, "# Step Time MSD D");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        let msd = self.mean_square_displacement(system);
+        if self.history.len() == self.window {
+            let _ = self.history.pop_front();
+        }
+        self.history.push_back((system.time, msd));
+
+        let coefficient = self.coefficient().unwrap_or(f64::NAN);
+        writeln_or_log!(self, "{} {} {} {}", system.step, system.time, msd, coefficient);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn output() {
+        test_output(
+            |path| Box::new(DiffusionCoefficient::new(path, 10).unwrap()),
+            "# Self-diffusion coefficient from the mean square displacement
+            # Step Time MSD D
+            42 0 0 NaN
+            ",
+        );
+    }
+
+    #[test]
+    fn coefficient() {
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let mut output = DiffusionCoefficient::new(tempfile.path(), 3).unwrap();
+        let mut system = testing_system();
+        output.setup(&system);
+        assert_eq!(output.coefficient(), None);
+
+        // Move the first particle at constant velocity, across the periodic
+        // boundaries
+        let cell = system.cell;
+        for step in 1..6 {
+            system.time = step as f64;
+            let position = &mut system.particles_mut().position[0];
+            position[0] += 3.0;
+            cell.wrap_vector(position);
+            output.write(&system);
+        }
+        assert_eq!(output.history.len(), 3);
+        assert_ulps_eq!(output.unwrapped[0][0], 15.0);
+        assert_ulps_eq!(output.history[2].1, 15.0 * 15.0 / 2.0);
+
+        // MSD(t) = 9 t^2 / 2, fitted over t = 3, 4, 5
+        let expected = 9.0 * 8.0 / 2.0 / 6.0;
+        assert_ulps_eq!(output.coefficient().unwrap(), expected);
+    }
+}
@@ -1,53 +1,88 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use log::error;
 
-use super::Output;
+use super::{FileMode, NumberFormat, Output, OutputFile};
 use lumol_core::System;
-use lumol_core::units;
+use lumol_core::units::UnitSystem;
 
 /// The `EnergyOutput` writes the energy of the system to a text file, organized
 /// as: `steps PotentialEnergy KineticEnergy TotalEnergy`.
 pub struct EnergyOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
+    units: UnitSystem,
+    format: NumberFormat,
 }
 
 impl EnergyOutput {
     /// Create a new `EnergyOutput` writing to `filename`. The file is replaced
-    /// if it already exists.
+    /// if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<EnergyOutput, io::Error> {
+        EnergyOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `EnergyOutput` writing to `filename`, opening the file
+    /// with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<EnergyOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(EnergyOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
+            units: UnitSystem::Physical,
+            format: NumberFormat::Default,
         })
     }
+
+    /// Set the format used to write the energies to `format`
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.format = format;
+    }
 }
 
 impl Output for EnergyOutput {
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
     fn setup(&mut self, _: &System) {
-        writeln_or_log!(self, "# Energy of the simulation (kJ/mol)");
+        if !self.file.setup() {
+            return;
+        }
+        match self.units {
+            UnitSystem::Physical => writeln_or_log!(self, "# Energy of the simulation (kJ/mol)"),
+            UnitSystem::Reduced => writeln_or_log!(self, "# Energy of the simulation (reduced units)"),
+        }
         writeln_or_log!(self, "# Step Potential Kinetic Total");
     }
 
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
     fn write(&mut self, system: &System) {
-        let potential = units::to(system.potential_energy(), "kJ/mol").expect("bad unit");
-        let kinetic = units::to(system.kinetic_energy(), "kJ/mol").expect("bad unit");
-        let total = units::to(system.total_energy(), "kJ/mol").expect("bad unit");
-        writeln_or_log!(self, "{} {} {} {}", system.step, potential, kinetic, total);
+        let potential = self.units.to(system.potential_energy(), "kJ/mol").expect("bad unit");
+        let kinetic = self.units.to(system.kinetic_energy(), "kJ/mol").expect("bad unit");
+        let total = self.units.to(system.total_energy(), "kJ/mol").expect("bad unit");
+        writeln_or_log!(self, "{} {} {} {}",
+            system.step,
+            self.format.format(potential),
+            self.format.format(kinetic),
+            self.format.format(total),
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::tests::test_output;
+    use super::super::tests::{test_output, testing_system};
+
+    use std::fs;
 
     #[test]
     fn energy() {
@@ -59,4 +94,59 @@ mod tests {
             ",
         );
     }
+
+    #[test]
+    fn number_format() {
+        test_output(
+            |path| {
+                let mut output = EnergyOutput::new(path).unwrap();
+                output.set_number_format(NumberFormat::Fixed(3));
+                Box::new(output)
+            },
+            "# Energy of the simulation (kJ/mol)
+            # Step Potential Kinetic Total
+            42 1.500 949.920 951.420
+            ",
+        );
+
+        test_output(
+            |path| {
+                let mut output = EnergyOutput::new(path).unwrap();
+                output.set_number_format(NumberFormat::Scientific(4));
+                Box::new(output)
+            },
+            "# Energy of the simulation (kJ/mol)
+            # Step Potential Kinetic Total
+            42 1.5000e0 9.4992e2 9.5142e2
+            ",
+        );
+    }
+
+    #[test]
+    fn append() {
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let system = testing_system();
+        for _ in 0..2 {
+            let mut output = EnergyOutput::with_mode(tempfile.path(), FileMode::Append).unwrap();
+            output.setup(&system);
+            output.write(&system);
+            output.finish(&system);
+        }
+
+        let content = fs::read_to_string(tempfile.path()).unwrap();
+        assert_eq!(content, "# Energy of the simulation (kJ/mol)
+# Step Potential Kinetic Total
+42 1.5000000000000027 949.9201593348566 951.4201593348566
+42 1.5000000000000027 949.9201593348566 951.4201593348566
+");
+
+        // The default mode removes the previous content when the simulation
+        // is not resumed
+        let mut output = EnergyOutput::new(tempfile.path()).unwrap();
+        output.setup(&system);
+        output.finish(&system);
+        drop(output);
+        let content = fs::read_to_string(tempfile.path()).unwrap();
+        assert_eq!(content, "# Energy of the simulation (kJ/mol)\n# Step Potential Kinetic Total\n");
+    }
 }
@@ -0,0 +1,275 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::{error, warn};
+
+use super::{FileMode, Output, OutputFile};
+
+use lumol_core::System;
+
+/// Reference energies with an absolute value below this threshold (in
+/// internal units) are considered to be zero
+const ZERO_ENERGY: f64 = 1e-10;
+
+/// Relative drift of the total energy in a simulation.
+///
+/// The drift at step $t$ is defined as $(E(t) - E(0)) / |E(0)|$, where $E(0)$
+/// is the first energy added. When $E(0)$ is close to zero, the relative drift
+/// is not defined and the absolute drift $E(t) - E(0)$ (in internal units) is
+/// used instead. The slope of the drift is estimated with a
+/// least-squares linear fit of the last `window` values of the drift, and is
+/// given per simulation step. In a constant energy (NVE) simulation with a
+/// symplectic integrator, the drift should stay small and the slope should be
+/// close to zero.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_sim::output::EnergyDrift;
+/// let mut drift = EnergyDrift::new(3);
+/// assert_eq!(drift.add(0, -10.0), 0.0);
+/// assert_eq!(drift.add(1, -9.0), 0.1);
+/// assert_eq!(drift.add(2, -8.0), 0.2);
+///
+/// assert_eq!(drift.drift(), Some(0.2));
+/// assert_eq!(drift.max_drift(), 0.2);
+/// assert!(f64::abs(drift.slope().unwrap() - 0.1) < 1e-12);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EnergyDrift {
+    /// Number of values used to compute the slope
+    window: usize,
+    /// Energy at the beginning of the simulation
+    initial: Option<f64>,
+    /// Last values of the drift, with the corresponding step
+    values: VecDeque<(u64, f64)>,
+    /// Maximal absolute value of the drift
+    max_drift: f64,
+}
+
+impl EnergyDrift {
+    /// Create a new `EnergyDrift` computing the slope of the drift over the
+    /// last `window` values.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is smaller than 2.
+    pub fn new(window: usize) -> EnergyDrift {
+        assert!(window >= 2, "window must contain at least 2 values in EnergyDrift");
+        EnergyDrift {
+            window: window,
+            initial: None,
+            values: VecDeque::with_capacity(window),
+            max_drift: 0.0,
+        }
+    }
+
+    /// Add the total `energy` of the system at the given `step`, and get the
+    /// corresponding drift. The first energy added is used as the reference
+    /// energy.
+    pub fn add(&mut self, step: u64, energy: f64) -> f64 {
+        let initial = *self.initial.get_or_insert(energy);
+        let drift = if f64::abs(initial) < ZERO_ENERGY {
+            energy - initial
+        } else {
+            (energy - initial) / f64::abs(initial)
+        };
+
+        if self.values.len() == self.window {
+            let _ = self.values.pop_front();
+        }
+        self.values.push_back((step, drift));
+        self.max_drift = f64::max(self.max_drift, f64::abs(drift));
+        return drift;
+    }
+
+    /// Remove all the values and the reference energy, to start the
+    /// computation of the drift again.
+    pub fn reset(&mut self) {
+        self.initial = None;
+        self.values.clear();
+        self.max_drift = 0.0;
+    }
+
+    /// Get the last value of the drift, or `None` if no energy was added yet.
+    pub fn drift(&self) -> Option<f64> {
+        self.values.back().map(|&(_, drift)| drift)
+    }
+
+    /// Get the maximal absolute value of the drift since the beginning.
+    pub fn max_drift(&self) -> f64 {
+        self.max_drift
+    }
+
+    /// Get the slope per step of a least-squares linear fit of the drift over
+    /// the last `window` values, or `None` if less than two values are
+    /// available.
+    pub fn slope(&self) -> Option<f64> {
+        if self.values.len() < 2 {
+            return None;
+        }
+
+        let n = self.values.len() as f64;
+        let mean_step = self.values.iter().map(|&(step, _)| step as f64).sum::<f64>() / n;
+        let mean_drift = self.values.iter().map(|&(_, drift)| drift).sum::<f64>() / n;
+
+        let mut covariance = 0.0;
+        let mut variance = 0.0;
+        for &(step, drift) in &self.values {
+            let dx = step as f64 - mean_step;
+            covariance += dx * (drift - mean_drift);
+            variance += dx * dx;
+        }
+
+        if variance == 0.0 {
+            None
+        } else {
+            Some(covariance / variance)
+        }
+    }
+}
+
+/// The `EnergyConservation` output monitors the conservation of the total
+/// energy in constant energy simulations, to help catching integration
+/// issues.
+///
+/// Each time it is called, this output writes the step, the relative drift of
+/// the total energy since the beginning of the run, and the slope of this
+/// drift over the last `window` calls (see [`EnergyDrift`]). The slope is
+/// `NaN` until two values are available. A warning is emitted the first time
+/// the absolute value of the drift exceeds the threshold given to
+/// [`set_threshold`](#method.set_threshold).
+///
+/// [`EnergyDrift`]: struct.EnergyDrift.html
+pub struct EnergyConservation {
+    file: OutputFile,
+    drift: EnergyDrift,
+    threshold: Option<f64>,
+    warned: bool,
+}
+
+impl EnergyConservation {
+    /// Create a new `EnergyConservation` output writing to `filename`, and
+    /// computing the slope of the drift over the last `window` values. The file
+    /// is replaced if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
+    pub fn new<P: AsRef<Path>>(filename: P, window: usize) -> Result<EnergyConservation, io::Error> {
+        EnergyConservation::with_mode(filename, window, FileMode::Auto)
+    }
+
+    /// Create a new `EnergyConservation` output writing to `filename`, and
+    /// computing the slope of the drift over the last `window` values, opening
+    /// the file with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, window: usize, mode: FileMode) -> Result<EnergyConservation, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(EnergyConservation {
+            file: file,
+            drift: EnergyDrift::new(window),
+            threshold: None,
+            warned: false,
+        })
+    }
+
+    /// Emit a warning if the absolute value of the relative energy drift
+    /// exceeds `threshold`.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = Some(threshold);
+    }
+}
+
+impl Output for EnergyConservation {
+    fn setup(&mut self, system: &System) {
+        self.drift.reset();
+        self.warned = false;
+        let _ = self.drift.add(system.step, system.total_energy());
+
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Relative drift of the total energy, and slope over {} values", self.drift.window);
+        writeln_or_log!(self, "# Step Drift Slope/step");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        let drift = self.drift.add(system.step, system.total_energy());
+        let slope = self.drift.slope().unwrap_or(f64::NAN);
+        writeln_or_log!(self, "{} {} {}", system.step, drift, slope);
+
+        if let Some(threshold) = self.threshold {
+            if !self.warned && f64::abs(drift) > threshold {
+                warn!(
+                    "relative energy drift ({:e}) is larger than the threshold ({:e}) at step {}",
+                    drift, threshold, system.step
+                );
+                self.warned = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn drift() {
+        let mut drift = EnergyDrift::new(3);
+        assert_eq!(drift.drift(), None);
+        assert_eq!(drift.slope(), None);
+
+        assert_eq!(drift.add(10, 4.0), 0.0);
+        assert_eq!(drift.slope(), None);
+        assert_eq!(drift.add(20, 5.0), 0.25);
+        assert_ulps_eq!(drift.slope().unwrap(), 0.025);
+        assert_eq!(drift.add(30, 3.0), -0.25);
+        assert_ulps_eq!(drift.slope().unwrap(), -0.0125);
+
+        // Only the last 3 values are used for the slope
+        assert_eq!(drift.add(40, 4.0), 0.0);
+        assert_ulps_eq!(drift.slope().unwrap(), -0.0125);
+        assert_eq!(drift.max_drift(), 0.25);
+
+        drift.reset();
+        assert_eq!(drift.drift(), None);
+        assert_eq!(drift.add(0, -2.0), 0.0);
+        assert_eq!(drift.add(1, -3.0), -0.5);
+        assert_eq!(drift.max_drift(), 0.5);
+
+        // Absolute drift when the initial energy is zero
+        drift.reset();
+        assert_eq!(drift.add(0, 0.0), 0.0);
+        assert_eq!(drift.add(1, 0.5), 0.5);
+        assert_eq!(drift.add(2, -0.25), -0.25);
+        assert_eq!(drift.max_drift(), 0.5);
+        assert_ulps_eq!(drift.slope().unwrap(), -0.125);
+    }
+
+    #[test]
+    #[should_panic(expected = "window must contain at least 2 values in EnergyDrift")]
+    fn small_window() {
+        let _ = EnergyDrift::new(1);
+    }
+
+    #[test]
+    fn output() {
+        test_output(
+            |path| Box::new(EnergyConservation::new(path, 10).unwrap()),
+            "# Relative drift of the total energy, and slope over 10 values
+            # Step Drift Slope/step
+            42 0 NaN
+            ",
+        );
+    }
+}
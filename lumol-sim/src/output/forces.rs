@@ -1,49 +1,97 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use log::error;
 
-use super::Output;
+use super::{FileMode, Output, OutputFile};
 use lumol_core::System;
-use lumol_core::units;
+use lumol_core::units::UnitSystem;
 
-/// The `ForcesOutput` writes the forces acting on the atoms using XYZ format
+/// The `ForcesOutput` writes the forces acting on the atoms using XYZ format.
+///
+/// The positions of the atoms can also be written on the same lines as the
+/// forces, using `name x y z fx fy fz` columns. The forces are always
+/// computed from the positions written in the same frame.
 pub struct ForcesOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
+    units: UnitSystem,
+    /// Should the positions be written together with the forces
+    positions: bool,
 }
 
 impl ForcesOutput {
     /// Create a new `ForcesOutput` writing to `filename`. The file is replaced
-    /// if it already exists.
+    /// if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<ForcesOutput, io::Error> {
+        ForcesOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `ForcesOutput` writing to `filename`, opening the file
+    /// with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<ForcesOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(ForcesOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
+            units: UnitSystem::Physical,
+            positions: false,
         })
     }
+
+    /// Set whether the positions of the atoms should be written in each frame
+    /// together with the forces.
+    pub fn set_positions(&mut self, positions: bool) {
+        self.positions = positions;
+    }
 }
 
 impl Output for ForcesOutput {
-    fn setup(&mut self, _: &System) {}
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
+    fn setup(&mut self, _: &System) {
+        let _ = self.file.setup();
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
 
     fn write(&mut self, system: &System) {
         let forces = system.forces();
         let names = system.particles().name;
-        let conversion = units::to(1.0, "kJ/mol/A").expect("bad unit");
+        let positions = system.particles().position;
+        let conversion = self.units.to(1.0, "kJ/mol/A").expect("bad unit");
+        let length = self.units.to(1.0, "A").expect("bad unit");
 
         writeln_or_log!(self, "{}", forces.len());
-        writeln_or_log!(self, "forces in kJ/mol/A at step {}", system.step);
+        match (self.units, self.positions) {
+            (UnitSystem::Physical, false) => writeln_or_log!(self, "forces in kJ/mol/A at step {}", system.step),
+            (UnitSystem::Reduced, false) => writeln_or_log!(self, "forces in reduced units at step {}", system.step),
+            (UnitSystem::Physical, true) => writeln_or_log!(self,
+                "positions in A and forces in kJ/mol/A at step {}", system.step
+            ),
+            (UnitSystem::Reduced, true) => writeln_or_log!(self,
+                "positions and forces in reduced units at step {}", system.step
+            ),
+        }
         for (i, force) in forces.iter().enumerate() {
             let x = conversion * force[0];
             let y = conversion * force[1];
             let z = conversion * force[2];
-            writeln_or_log!(self, "{} {} {} {}", names[i], x, y, z);
+            if self.positions {
+                let position = length * positions[i];
+                writeln_or_log!(self, "{} {} {} {} {} {} {}",
+                    names[i], position[0], position[1], position[2], x, y, z
+                );
+            } else {
+                writeln_or_log!(self, "{} {} {} {}", names[i], x, y, z);
+            }
         }
     }
 }
@@ -64,4 +112,20 @@ mod tests {
             ",
         );
     }
+
+    #[test]
+    fn positions() {
+        test_output(
+            |path| {
+                let mut output = ForcesOutput::new(path).unwrap();
+                output.set_positions(true);
+                Box::new(output)
+            },
+            "2
+            positions in A and forces in kJ/mol/A at step 42
+            F 0 0 0 30.000000000000025 0 0
+            F 1.3 0 0 -30.000000000000025 0 0
+            ",
+        );
+    }
 }
@@ -3,7 +3,14 @@
 
 //! Saving properties of a system during a simulation
 
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use log::error;
+
 use lumol_core::System;
+use lumol_core::units::UnitSystem;
 
 /// The `Output` trait defines the interface for all the quantities outputted by
 /// the simulation during the run. An Output can be a text or a binary data
@@ -13,6 +20,20 @@ pub trait Output {
     /// for some setup of the output if needed.
     fn setup(&mut self, _: &System) {}
 
+    /// Function called before `setup` when resuming a simulation from a
+    /// saved state with [`Simulation::restore_state`]. Outputs using
+    /// [`FileMode::Auto`] should then add data to the existing files instead
+    /// of replacing them. The default implementation does nothing.
+    ///
+    /// [`Simulation::restore_state`]: ../struct.Simulation.html#method.restore_state
+    /// [`FileMode::Auto`]: enum.FileMode.html#variant.Auto
+    fn resume(&mut self) {}
+
+    /// Set the unit system used to write the output. This function is
+    /// called before `setup`, and the default implementation ignores the
+    /// unit system.
+    fn set_unit_system(&mut self, _: UnitSystem) {}
+
     /// Write the output from the system.
     fn write(&mut self, system: &System);
 
@@ -20,12 +41,114 @@ pub trait Output {
     fn finish(&mut self, _: &System) {}
 }
 
+/// Mode used by the outputs when opening their files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FileMode {
+    /// Replace any existing file when the simulation starts, or add the new
+    /// data at the end of the existing file when resuming a simulation with
+    /// [`Simulation::restore_state`]. The file is only replaced at the
+    /// beginning of the first simulation run.
+    ///
+    /// [`Simulation::restore_state`]: ../struct.Simulation.html#method.restore_state
+    #[default]
+    Auto,
+    /// Replace any existing file
+    Truncate,
+    /// Add the new data at the end of any existing file, for example when
+    /// resuming a simulation. The header lines are not written again if the
+    /// file is not empty.
+    Append,
+}
+
+/// Format used by the outputs to write floating point numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    /// Use the shortest representation giving back the exact same value when
+    /// read again
+    #[default]
+    Default,
+    /// Use a fixed notation with the given number of decimal places
+    Fixed(usize),
+    /// Use a scientific notation with the given number of decimal places in
+    /// the mantissa
+    Scientific(usize),
+}
+
+impl NumberFormat {
+    /// Format `value` according to this number format
+    pub fn format(&self, value: f64) -> String {
+        match *self {
+            NumberFormat::Default => value.to_string(),
+            NumberFormat::Fixed(precision) => format!("{:.*}", precision, value),
+            NumberFormat::Scientific(precision) => format!("{:.*e}", precision, value),
+        }
+    }
+}
+
+/// A file written by an output. With `FileMode::Auto`, the choice between
+/// replacing the file and adding data to it is made at the beginning of the
+/// first simulation run, depending on the simulation being resumed or not.
+struct OutputFile {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    /// Mode to use at the beginning of the first simulation run, `None` after
+    /// this first run started
+    mode: Option<FileMode>,
+    /// Should the header lines be written in `setup`
+    header: bool,
+}
+
+impl OutputFile {
+    /// Open the file at `path` using the given `mode`
+    fn open(path: &Path, mode: FileMode) -> Result<OutputFile, io::Error> {
+        let file = match mode {
+            FileMode::Truncate => File::create(path)?,
+            FileMode::Auto | FileMode::Append => {
+                OpenOptions::new().create(true).append(true).open(path)?
+            }
+        };
+        Ok(OutputFile {
+            writer: BufWriter::new(file),
+            path: path.to_owned(),
+            mode: Some(mode),
+            header: true,
+        })
+    }
+
+    /// Keep the existing data in the file if it was opened with
+    /// `FileMode::Auto`, since the simulation is resumed
+    fn resume(&mut self) {
+        if self.mode == Some(FileMode::Auto) {
+            self.mode = Some(FileMode::Append);
+        }
+    }
+
+    /// Replace the existing file if needed at the beginning of the first
+    /// simulation run, and check if the header lines should be written to
+    /// this file, i.e. if the file was empty at the beginning of the first
+    /// run.
+    fn setup(&mut self) -> bool {
+        if let Some(mode) = self.mode.take() {
+            if mode == FileMode::Auto {
+                if let Err(err) = self.writer.get_ref().set_len(0) {
+                    error!("could not truncate file '{}': {}", self.path.display(), err);
+                }
+            }
+            self.header = match self.writer.get_ref().metadata() {
+                Ok(metadata) => metadata.len() == 0,
+                Err(_) => true,
+            };
+        }
+        return self.header;
+    }
+}
+
 mod tests;
 
 macro_rules! writeln_or_log {
     ($this: expr, $($args: expr),* $(,)*) => (
-        if let Err(err) = writeln!(&mut $this.file, $($args,)*) {
-            error!("could not write to file '{}': {}", $this.path.display(), err);
+        if let Err(err) = writeln!(&mut $this.file.writer, $($args,)*) {
+            error!("could not write to file '{}': {}", $this.file.path.display(), err);
             return;
         }
     );
@@ -51,3 +174,27 @@ pub use self::properties::PropertiesOutput;
 
 mod trajectory;
 pub use self::trajectory::TrajectoryOutput;
+
+mod progress;
+pub use self::progress::ProgressOutput;
+
+mod block_average;
+pub use self::block_average::{BlockAverage, BlockAverageOutput};
+
+mod barostat;
+pub use self::barostat::BarostatOutput;
+
+mod momentum;
+pub use self::momentum::MomentumOutput;
+
+mod energy_conservation;
+pub use self::energy_conservation::{EnergyConservation, EnergyDrift};
+
+mod widom;
+pub use self::widom::WidomInsertion;
+
+mod compressibility;
+pub use self::compressibility::Compressibility;
+
+mod diffusion;
+pub use self::diffusion::DiffusionCoefficient;
@@ -0,0 +1,98 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::error;
+use soa_derive::soa_zip;
+
+use super::{FileMode, Output, OutputFile};
+use lumol_core::{System, Vector3D};
+
+/// The `MomentumOutput` writes the total linear momentum $\sum_i m_i \vec v_i$
+/// and the total angular momentum $\sum_i m_i \vec r_i \times \vec v_i$ of the
+/// system to a file. The columns in the file contain the following values:
+/// `step Px Py Pz Lx Ly Lz`, in internal units.
+///
+/// In constant energy simulations without external forces, the linear
+/// momentum should be conserved, and this output can be used to detect bugs
+/// in the integrators or the forces. The angular momentum is computed around
+/// the origin, and is only conserved for isolated systems in an infinite
+/// cell.
+pub struct MomentumOutput {
+    file: OutputFile,
+}
+
+impl MomentumOutput {
+    /// Create a new `MomentumOutput` writing to `filename`. The file is
+    /// replaced if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<MomentumOutput, io::Error> {
+        MomentumOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `MomentumOutput` writing to `filename`, opening the file
+    /// with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<MomentumOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(MomentumOutput {
+            file: file,
+        })
+    }
+}
+
+/// Get the total linear momentum and the total angular momentum around the
+/// origin of the `system`.
+fn momenta(system: &System) -> (Vector3D, Vector3D) {
+    let mut linear = Vector3D::zero();
+    let mut angular = Vector3D::zero();
+    for (&mass, position, velocity) in soa_zip!(system.particles(), [mass, position, velocity]) {
+        linear += mass * velocity;
+        angular += mass * (position ^ velocity);
+    }
+    return (linear, angular);
+}
+
+impl Output for MomentumOutput {
+    #[allow(clippy::non_ascii_literal)]
+    fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Linear momentum (u Å/fs) and angular momentum (u Å^2/fs) of the simulation");
+        writeln_or_log!(self, "# Step Px Py Pz Lx Ly Lz");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        let (linear, angular) = momenta(system);
+        writeln_or_log!(self, "{} {} {} {} {} {} {}",
+            system.step,
+            linear[0], linear[1], linear[2],
+            angular[0], angular[1], angular[2],
+        );
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::non_ascii_literal)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+
+    #[test]
+    fn momentum() {
+        test_output(
+            |path| Box::new(MomentumOutput::new(path).unwrap()),
+            "# Linear momentum (u Å/fs) and angular momentum (u Å^2/fs) of the simulation
+            # Step Px Py Pz Lx Ly Lz
+            42 1.89984032 0 0 0 0 0
+            ",
+        );
+    }
+}
@@ -0,0 +1,164 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use log::error;
+
+use super::Output;
+use lumol_core::System;
+
+/// The `ProgressOutput` reports the progress of a simulation, with the current
+/// step, the percentage of the simulation already done, the number of steps
+/// per second and the estimated remaining time.
+///
+/// The progress is computed relative to the step of the system when the
+/// simulation starts, and assumes the simulation runs for `nsteps` steps. The
+/// progress is written every time this output is used, which should be
+/// controlled with the output frequency.
+pub struct ProgressOutput {
+    /// Where to write the progress
+    writer: Box<dyn Write>,
+    /// Expected number of steps in the simulation
+    nsteps: u64,
+    /// System step at the beginning of the simulation
+    start_step: u64,
+    /// Wall-clock time at the beginning of the simulation
+    start_time: Instant,
+}
+
+impl ProgressOutput {
+    /// Create a new `ProgressOutput` for a simulation running for `nsteps`
+    /// steps, writing to the standard output.
+    pub fn new(nsteps: u64) -> ProgressOutput {
+        ProgressOutput::with_writer(nsteps, Box::new(io::stdout()))
+    }
+
+    /// Create a new `ProgressOutput` for a simulation running for `nsteps`
+    /// steps, writing to the given `writer`.
+    pub fn with_writer(nsteps: u64, writer: Box<dyn Write>) -> ProgressOutput {
+        ProgressOutput {
+            writer: writer,
+            nsteps: nsteps,
+            start_step: 0,
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Get the progress message at the given `step`, when the simulation has
+    /// been running for `elapsed` time.
+    fn message(&self, step: u64, elapsed: Duration) -> String {
+        let done = step - self.start_step;
+        let percent = if self.nsteps == 0 {
+            100.0
+        } else {
+            100.0 * done as f64 / self.nsteps as f64
+        };
+
+        let seconds = elapsed.as_secs_f64();
+        if done == 0 || seconds == 0.0 {
+            return format!("step {}/{} ({:.1} %)", done, self.nsteps, percent);
+        }
+
+        let rate = done as f64 / seconds;
+        let remaining = self.nsteps.saturating_sub(done) as f64 / rate;
+        return format!(
+            "step {}/{} ({:.1} %), {:.1} steps/s, ETA {}",
+            done, self.nsteps, percent, rate, format_duration(remaining)
+        );
+    }
+}
+
+impl Output for ProgressOutput {
+    fn setup(&mut self, system: &System) {
+        self.start_step = system.step;
+        self.start_time = Instant::now();
+    }
+
+    fn write(&mut self, system: &System) {
+        let message = self.message(system.step, self.start_time.elapsed());
+        if let Err(err) = writeln!(self.writer, "{}", message) {
+            error!("could not write simulation progress: {}", err);
+        }
+    }
+
+    fn finish(&mut self, _: &System) {
+        if let Err(err) = self.writer.flush() {
+            error!("could not write simulation progress: {}", err);
+        }
+    }
+}
+
+/// Format a duration in `seconds` as `hh:mm:ss`
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    return format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Propagator, Simulation, TemperatureStrategy};
+    use lumol_core::DegreesOfFreedom;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer sharing its buffer, to check the content after the simulation
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct DoNothing;
+    impl Propagator for DoNothing {
+        fn temperature_strategy(&self) -> TemperatureStrategy {
+            TemperatureStrategy::None
+        }
+
+        fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+            DegreesOfFreedom::Particles
+        }
+
+        fn propagate(&mut self, _: &mut System) {}
+    }
+
+    #[test]
+    fn message() {
+        let output = ProgressOutput::with_writer(1000, Box::new(SharedBuffer::default()));
+        assert_eq!(output.message(0, Duration::from_secs(0)), "step 0/1000 (0.0 %)");
+        assert_eq!(
+            output.message(250, Duration::from_secs(5)),
+            "step 250/1000 (25.0 %), 50.0 steps/s, ETA 00:00:15"
+        );
+        assert_eq!(
+            output.message(1, Duration::from_secs(5)),
+            "step 1/1000 (0.1 %), 0.2 steps/s, ETA 01:23:15"
+        );
+    }
+
+    #[test]
+    fn frequency() {
+        let buffer = SharedBuffer::default();
+        let mut simulation = Simulation::new(Box::new(DoNothing));
+        simulation.add_output_with_frequency(Box::new(ProgressOutput::with_writer(35, Box::new(buffer.clone()))), 10);
+
+        let mut system = System::new();
+        simulation.run(&mut system, 35);
+
+        let content = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let steps = content.lines()
+            .map(|line| line.split(' ').nth(1).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, ["10/35", "20/35", "30/35"]);
+    }
+}
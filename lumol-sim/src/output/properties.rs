@@ -1,17 +1,16 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use log::error;
 
-use super::Output;
+use super::{FileMode, NumberFormat, Output, OutputFile};
 
 use lumol_core::System;
-use lumol_core::units;
+use lumol_core::units::UnitSystem;
 
 /// The `PropertiesOutput` write various physical properties of the system to
 /// a file. These properties are:
@@ -20,39 +19,76 @@ use lumol_core::units;
 /// - instant temperature;
 /// - instant pressure;
 pub struct PropertiesOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
+    units: UnitSystem,
+    format: NumberFormat,
 }
 
 impl PropertiesOutput {
-    /// Create a new `PropertiesOutput` writing to `filename`. The file is replaced
-    /// if it already exists.
+    /// Create a new `PropertiesOutput` writing to `filename`. The file is
+    /// replaced if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<PropertiesOutput, io::Error> {
+        PropertiesOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `PropertiesOutput` writing to `filename`, opening the
+    /// file with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<PropertiesOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(PropertiesOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
+            units: UnitSystem::Physical,
+            format: NumberFormat::Default,
         })
     }
+
+    /// Set the format used to write the properties to `format`
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.format = format;
+    }
 }
 
 impl Output for PropertiesOutput {
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
     fn setup(&mut self, _: &System) {
+        if !self.file.setup() {
+            return;
+        }
         writeln_or_log!(self, "# Physical properties of the simulation");
-        writeln_or_log!(self, "# Step Volume/A^3 Temperature/K Pressure/bar");
+        writeln_or_log!(self, "# Step {} {} {}",
+            self.units.label("Volume", "A^3"),
+            self.units.label("Temperature", "K"),
+            self.units.label("Pressure", "bar"),
+        );
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
     }
 
     fn write(&mut self, system: &System) {
-        let volume = units::to(system.volume(), "A^3").expect("bad unit");
-        let temperature = units::to(system.temperature(), "K").expect("bad unit");
-        let pressure = units::to(system.pressure(), "bar").expect("bad unit");
-        writeln_or_log!(self, "{} {} {} {}", system.step, volume, temperature, pressure);
+        let volume = self.units.to(system.volume(), "A^3").expect("bad unit");
+        let temperature = self.units.to(system.temperature(), "K").expect("bad unit");
+        let pressure = self.units.to(system.pressure(), "bar").expect("bad unit");
+        writeln_or_log!(self, "{} {} {} {}",
+            system.step,
+            self.format.format(volume),
+            self.format.format(temperature),
+            self.format.format(pressure),
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::tests::test_output;
+    use super::super::tests::{test_output, testing_system};
+    use lumol_core::consts::K_BOLTZMANN;
+    use tempfile::NamedTempFile;
 
     #[test]
     fn properties() {
@@ -64,4 +100,40 @@ mod tests {
             ",
         );
     }
+
+    #[test]
+    fn number_format() {
+        test_output(
+            |path| {
+                let mut output = PropertiesOutput::new(path).unwrap();
+                output.set_number_format(NumberFormat::Fixed(2));
+                Box::new(output)
+            },
+            "# Physical properties of the simulation
+            # Step Volume/A^3 Temperature/K Pressure/bar
+            42 1000.00 38083.04 10299.99
+            ",
+        );
+    }
+
+    #[test]
+    fn reduced_units() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let system = testing_system();
+        {
+            let mut output = PropertiesOutput::new(tempfile.path()).unwrap();
+            output.set_unit_system(UnitSystem::Reduced);
+            output.setup(&system);
+            output.write(&system);
+        }
+
+        let content = std::fs::read_to_string(tempfile.path()).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1], "# Step Volume* Temperature* Pressure*");
+
+        let values = lines[2].split_whitespace().collect::<Vec<_>>();
+        assert_eq!(values[1], "1000");
+        assert_eq!(values[2], (system.temperature() * K_BOLTZMANN).to_string());
+        assert_eq!(values[3], system.pressure().to_string());
+    }
 }
@@ -1,50 +1,72 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::fs::File;
-use std::io::{self, BufWriter};
+use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use log::error;
 
-use super::Output;
+use super::{FileMode, Output, OutputFile};
 use lumol_core::System;
-use lumol_core::units;
+use lumol_core::units::UnitSystem;
 
 /// The `StressOutput` writes the stress of the system to a text file, organized
 /// as: `step stress.xx stress.yy stress.zz stress.xy stress.xz stress.yz`.
 pub struct StressOutput {
-    file: BufWriter<File>,
-    path: PathBuf,
+    file: OutputFile,
+    units: UnitSystem,
 }
 
 impl StressOutput {
     /// Create a new `StressOutput` writing to `filename`. The file is replaced
-    /// if it already exists.
+    /// if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`).
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<StressOutput, io::Error> {
+        StressOutput::with_mode(filename, FileMode::Auto)
+    }
+
+    /// Create a new `StressOutput` writing to `filename`, opening the file
+    /// with the given `mode`.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, mode: FileMode) -> Result<StressOutput, io::Error> {
+        let file = OutputFile::open(filename.as_ref(), mode)?;
         Ok(StressOutput {
-            file: BufWriter::new(File::create(filename.as_ref())?),
-            path: filename.as_ref().to_owned(),
+            file: file,
+            units: UnitSystem::Physical,
         })
     }
 }
 
 impl Output for StressOutput {
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
     fn setup(&mut self, _: &System) {
-        if let Err(err) = writeln!(&mut self.file, "# Stress tensor of the simulation (bar)") {
-            panic!("Could not write to file '{}': {}", self.path.display(), err);
+        if !self.file.setup() {
+            return;
+        }
+        let unit = match self.units {
+            UnitSystem::Physical => "bar",
+            UnitSystem::Reduced => "reduced units",
+        };
+        if let Err(err) = writeln!(&mut self.file.writer, "# Stress tensor of the simulation ({})", unit) {
+            panic!("Could not write to file '{}': {}", self.file.path.display(), err);
         }
         if let Err(err) = writeln!(
-            &mut self.file,
+            &mut self.file.writer,
             "# step stress.xx stress.yy stress.zz stress.xy stress.xz stress.yz"
         ) {
-            panic!("Could not write to file '{}': {}", self.path.display(), err);
+            panic!("Could not write to file '{}': {}", self.file.path.display(), err);
         }
     }
 
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
     fn write(&mut self, system: &System) {
-        let conversion = units::to(1.0, "bar").expect("bad unit");
+        let conversion = self.units.to(1.0, "bar").expect("bad unit");
         let stress = system.stress();
         let xx = stress[0][0] * conversion;
         let yy = stress[1][1] * conversion;
@@ -1,9 +1,9 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use super::Output;
+use super::{FileMode, Output};
 
 use lumol_core::{OpenMode, Trajectory, TrajectoryBuilder, TrajectoryError};
 use lumol_core::System;
@@ -14,27 +14,42 @@ use lumol_core::System;
 /// [chemfiles]: http://chemfiles.github.io
 pub struct TrajectoryOutput {
     file: Trajectory,
+    path: PathBuf,
+    /// Format of the file, or an empty string to guess it from the extension
+    format: String,
+    /// Mode used to open the file, until the first call to `setup`
+    mode: Option<FileMode>,
 }
 
 impl TrajectoryOutput {
     /// Create a new `TrajectoryOutput` writing to `filename`. The file is
-    /// replaced if it already exists. The file format is guessed from the
-    /// extension. Please refer to the list of [supported formats][formats]
-    /// for more information.
+    /// replaced if it already exists, unless the simulation is resumed (see
+    /// `FileMode::Auto`). The file format is guessed from the extension.
+    /// Please refer to the list of [supported formats][formats] for more
+    /// information.
     ///
     /// [formats]: http://chemfiles.org/chemfiles/latest/formats.html
     pub fn new<P>(path: P) -> Result<TrajectoryOutput, TrajectoryError>
     where
         P: AsRef<Path>,
     {
-        let builder = TrajectoryBuilder::new().mode(OpenMode::Write);
-        Ok(TrajectoryOutput {
-            file: builder.open(path)?
-        })
+        TrajectoryOutput::open(path.as_ref(), "", FileMode::Auto)
+    }
+
+    /// Create a new `TrajectoryOutput` writing to `filename`, opening the
+    /// file with the given `mode`. The file format is guessed from the
+    /// extension. In append mode, new frames are added after the existing
+    /// ones, and the format-specific header of the file is kept as it is.
+    pub fn with_mode<P>(path: P, mode: FileMode) -> Result<TrajectoryOutput, TrajectoryError>
+    where
+        P: AsRef<Path>,
+    {
+        TrajectoryOutput::open(path.as_ref(), "", mode)
     }
 
     /// Create a new `TrajectoryOutput` writing to `filename` using the given
-    /// `format`. The file is replaced if it already exists.
+    /// `format`. The file is replaced if it already exists, unless the
+    /// simulation is resumed (see `FileMode::Auto`).
     ///
     /// Please refer to the list of [supported formats][formats] for more
     /// information.
@@ -44,14 +59,45 @@ impl TrajectoryOutput {
     where
         P: AsRef<Path>,
     {
-        let builder = TrajectoryBuilder::new().mode(OpenMode::Write).format(format);
+        TrajectoryOutput::open(path.as_ref(), format, FileMode::Auto)
+    }
+
+    fn open(path: &Path, format: &str, mode: FileMode) -> Result<TrajectoryOutput, TrajectoryError> {
+        // With `FileMode::Auto`, the existing file is kept until we know
+        // whether the simulation is resumed or not.
+        let open_mode = match mode {
+            FileMode::Truncate => OpenMode::Write,
+            FileMode::Auto | FileMode::Append => OpenMode::Append,
+        };
+        let builder = TrajectoryBuilder::new().mode(open_mode).format(format);
         Ok(TrajectoryOutput {
-            file: builder.open(path)?
+            file: builder.open(path)?,
+            path: path.to_owned(),
+            format: format.into(),
+            mode: Some(mode),
         })
     }
 }
 
 impl Output for TrajectoryOutput {
+    fn setup(&mut self, _: &System) {
+        if self.mode.take() == Some(FileMode::Auto) {
+            let builder = TrajectoryBuilder::new().mode(OpenMode::Write).format(&self.format);
+            match builder.open(&self.path) {
+                Ok(file) => self.file = file,
+                Err(err) => {
+                    panic!("Error while opening trajectory '{}': {}", self.path.display(), err);
+                }
+            }
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.mode == Some(FileMode::Auto) {
+            self.mode = Some(FileMode::Append);
+        }
+    }
+
     fn write(&mut self, system: &System) {
         match self.file.write(system) {
             Ok(()) => (),
@@ -65,7 +111,9 @@ impl Output for TrajectoryOutput {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::tests::test_output;
+    use super::super::tests::{test_output, testing_system};
+
+    use std::fs;
 
     #[test]
     fn cell() {
@@ -78,4 +126,20 @@ mod tests {
             ",
         );
     }
+
+    #[test]
+    fn append() {
+        let tempfile = tempfile::Builder::new().suffix(".xyz").tempfile().unwrap();
+        let system = testing_system();
+        for _ in 0..2 {
+            let mut output = TrajectoryOutput::with_mode(tempfile.path(), FileMode::Append).unwrap();
+            output.setup(&system);
+            output.write(&system);
+            output.finish(&system);
+        }
+
+        let content = fs::read_to_string(tempfile.path()).unwrap();
+        let frame = "2\nWritten by the chemfiles library\nF 0 0 0\nF 1.3 0 0\n";
+        assert_eq!(content, format!("{}{}", frame, frame));
+    }
 }
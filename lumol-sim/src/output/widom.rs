@@ -0,0 +1,191 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use log::{error, info, warn};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use super::{FileMode, Output, OutputFile};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::{System, Vector3D};
+use lumol_core::units::UnitSystem;
+
+/// The `WidomInsertion` output computes the excess chemical potential of a
+/// particle species using Widom test-particle insertion.
+///
+/// Each time the output is written, a ghost particle is inserted at
+/// `insertions` random positions in the cell. The interaction energy $\Delta
+/// U$ of the ghost particle with the system is computed without adding it to
+/// the system, and the excess chemical potential is given by
+///
+/// $$ \mu_{ex} = - k_B T \ln \langle e^{-\beta \Delta U} \rangle $$
+///
+/// The temperature is taken from the system at the beginning of the
+/// simulation, which is the external temperature for Monte Carlo
+/// simulations. The file is organized as `steps <exp(-beta dU)> mu_ex`, using
+/// the running averages over all the insertions since the beginning of the
+/// simulation. Insertions are only possible in systems with a finite cell.
+pub struct WidomInsertion {
+    file: OutputFile,
+    /// Name of the inserted particles
+    particle: String,
+    /// Number of insertions every time the output is written
+    insertions: usize,
+    rng: XorShiftRng,
+    /// Inverse temperature of the system
+    beta: f64,
+    /// Sum of the Boltzmann factors of all the insertions
+    boltzmann: f64,
+    /// Total number of insertions
+    count: u64,
+    units: UnitSystem,
+}
+
+impl WidomInsertion {
+    /// Create a new `WidomInsertion` writing to `filename`, and inserting
+    /// `insertions` particles named `particle` every time the output is
+    /// written. The file is replaced if it already exists, unless the
+    /// simulation is resumed (see `FileMode::Auto`).
+    ///
+    /// # Panics
+    ///
+    /// If `insertions` is zero.
+    pub fn new<P: AsRef<Path>>(filename: P, particle: &str, insertions: usize) -> Result<WidomInsertion, io::Error> {
+        WidomInsertion::with_mode(filename, particle, insertions, FileMode::Auto)
+    }
+
+    /// Create a new `WidomInsertion` writing to `filename` with the given
+    /// `mode`, and inserting `insertions` particles named `particle` every time
+    /// the output is written.
+    ///
+    /// # Panics
+    ///
+    /// If `insertions` is zero.
+    pub fn with_mode<P: AsRef<Path>>(filename: P, particle: &str, insertions: usize, mode: FileMode) -> Result<WidomInsertion, io::Error> {
+        assert!(insertions > 0, "the number of insertions must be positive in Widom insertion");
+        let file = OutputFile::open(filename.as_ref(), mode)?;
+        Ok(WidomInsertion {
+            file: file,
+            particle: particle.into(),
+            insertions: insertions,
+            rng: XorShiftRng::seed_from_u64(42),
+            beta: 0.0,
+            boltzmann: 0.0,
+            count: 0,
+            units: UnitSystem::Physical,
+        })
+    }
+
+    /// Set the seed of the random number generator used for the positions
+    /// of the inserted particles. The default seed is 42.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = XorShiftRng::seed_from_u64(seed);
+    }
+
+    /// Get the current value of the excess chemical potential, or `None` if
+    /// no insertion was performed yet.
+    pub fn excess_chemical_potential(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let average = self.boltzmann / self.count as f64;
+        return Some(f64::ln(1.0 / average) / self.beta);
+    }
+}
+
+impl Output for WidomInsertion {
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
+    fn setup(&mut self, system: &System) {
+        assert!(!system.cell.is_infinite(), "Widom insertion can not be used with an infinite cell");
+        let temperature = system.temperature();
+        if temperature <= 0.0 {
+            warn!("the temperature is {} K in Widom insertion, the chemical potential will not be defined", temperature);
+        }
+        self.beta = 1.0 / (K_BOLTZMANN * temperature);
+        self.boltzmann = 0.0;
+        self.count = 0;
+
+        if !self.file.setup() {
+            return;
+        }
+        writeln_or_log!(self, "# Widom insertion of '{}' particles", self.particle);
+        match self.units {
+            UnitSystem::Physical => writeln_or_log!(self, "# Excess chemical potential (kJ/mol)"),
+            UnitSystem::Reduced => writeln_or_log!(self, "# Excess chemical potential (reduced units)"),
+        }
+        writeln_or_log!(self, "# Step <exp(-beta dU)> mu_ex");
+    }
+
+    fn resume(&mut self) {
+        self.file.resume();
+    }
+
+    fn write(&mut self, system: &System) {
+        let evaluator = system.energy_evaluator();
+        for _ in 0..self.insertions {
+            let fractional = Vector3D::new(self.rng.gen(), self.rng.gen(), self.rng.gen());
+            let position = system.cell.cartesian(&fractional);
+            let energy = evaluator.insertion(&self.particle, &position);
+            self.boltzmann += f64::exp(-self.beta * energy);
+        }
+        self.count += self.insertions as u64;
+
+        let average = self.boltzmann / self.count as f64;
+        let mu_ex = self.units.to(f64::ln(1.0 / average) / self.beta, "kJ/mol").expect("bad unit");
+        writeln_or_log!(self, "{} {} {}", system.step, average, mu_ex);
+    }
+
+    fn finish(&mut self, _: &System) {
+        if let Some(mu_ex) = self.excess_chemical_potential() {
+            let mu_ex = self.units.to(mu_ex, "kJ/mol").expect("bad unit");
+            info!("Excess chemical potential of '{}': {} (from {} insertions)", self.particle, mu_ex, self.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+
+    #[test]
+    fn widom() {
+        // There is no interaction for the inserted particles
+        test_output(
+            |path| Box::new(WidomInsertion::new(path, "Ar", 10).unwrap()),
+            "# Widom insertion of 'Ar' particles
+            # Excess chemical potential (kJ/mol)
+            # Step <exp(-beta dU)> mu_ex
+            42 1 0
+            ",
+        );
+    }
+
+    #[test]
+    fn excess_chemical_potential() {
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let system = testing_system();
+
+        let mut widom = WidomInsertion::new(tempfile.path(), "F", 100).unwrap();
+        assert_eq!(widom.excess_chemical_potential(), None);
+        widom.setup(&system);
+        widom.write(&system);
+        let mu_ex = widom.excess_chemical_potential().unwrap();
+        assert!(mu_ex.is_finite());
+        assert!(mu_ex != 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the number of insertions must be positive in Widom insertion")]
+    fn no_insertions() {
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let _ = WidomInsertion::new(tempfile.path(), "F", 0);
+    }
+}
@@ -2,6 +2,14 @@
 // Copyright (C) Lumol's contributors — BSD license
 
 //! A propagator is responsible for updating the system during a simulation
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
 use lumol_core::{System, DegreesOfFreedom};
 
 /// Possible temperature computation strategies. Different propagators needs
@@ -18,6 +26,24 @@ pub enum TemperatureStrategy {
     External(f64),
 }
 
+/// Possible errors stopping a simulation before the end of a run
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimulationError {
+    /// The simulation became unstable, for example because the forces are
+    /// exploding. The string contains a description of the problem.
+    Unstable(String),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SimulationError::Unstable(ref message) => write!(f, "unstable simulation: {}", message),
+        }
+    }
+}
+
+impl Error for SimulationError {}
+
 /// The propagator trait is the main algorithm of a simulation, i.e. the one
 /// which update the system. The main function here is `propagate`, which
 /// should propagate the simulation for one step.
@@ -32,6 +58,11 @@ pub trait Propagator {
     /// This function is called once at thr beginning of the simulation
     fn degrees_of_freedom(&self, system: &System) -> DegreesOfFreedom;
 
+    /// Seed all the random number generators used by this propagator, taking
+    /// as many seeds as needed from `seeds`. The default implementation does
+    /// nothing, for deterministic propagators.
+    fn seed(&mut self, _: &mut SeedSequence) {}
+
     /// Setup code, preparing all the meta-information needed about the
     /// simulation.
     fn setup(&mut self, _: &System) {}
@@ -39,6 +70,203 @@ pub trait Propagator {
     /// Propagate the system for one simulation step.
     fn propagate(&mut self, system: &mut System);
 
+    /// Get the timestep used by the last call to `propagate`, if this
+    /// propagator has one. This is used to increment the simulation time
+    /// after every step. The default
+    /// implementation returns `None`, for propagators without a notion of
+    /// time such as Monte Carlo or energy minimization.
+    fn timestep(&self) -> Option<f64> {
+        None
+    }
+
+    /// Check if the target temperature of this propagator can be changed with
+    /// `set_temperature`. The default implementation returns `false`.
+    fn can_set_temperature(&self) -> bool {
+        false
+    }
+
+    /// Set the target temperature of this propagator. This is used by
+    /// temperature ramps, and is called before every step of the simulation
+    /// when a ramp is used. The default implementation panics, for
+    /// propagators without a target temperature.
+    fn set_temperature(&mut self, _: f64) {
+        panic!("this propagator does not support changing the temperature");
+    }
+
+    /// Save the internal state of this propagator (random number generators,
+    /// adjustable parameters, statistics, ...) in `state`, so that a
+    /// simulation can be resumed later with `restore_state`. Saving the state
+    /// must not change the simulation. The default implementation does
+    /// nothing, for propagators without internal state.
+    fn save_state(&mut self, _: &mut PropagatorState) {}
+
+    /// Restore the internal state of this propagator from a `state` created
+    /// by `save_state`. The default implementation does nothing.
+    fn restore_state(&mut self, _: &PropagatorState) {}
+
+    /// Get the error which stopped the last call to `propagate`, if any, and
+    /// reset it. The simulation is stopped when this function returns an
+    /// error. The default implementation never fails.
+    fn take_error(&mut self) -> Option<SimulationError> {
+        None
+    }
+
     /// Finish the simulation, and maybe output some information about it
     fn finish(&mut self, _: &System) {}
 }
+
+/// Internal state of a propagator, used to resume a simulation from a
+/// checkpoint in a statistically continuous way.
+///
+/// The state is a set of named values, which can be written to and read from
+/// a text file with one `name value` pair per line.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_sim::PropagatorState;
+/// let mut state = PropagatorState::new();
+/// state.set("seed", 42);
+/// state.set("amplitude", 0.3);
+///
+/// assert_eq!(state.get::<u64>("seed"), Some(42));
+/// assert_eq!(state.get::<f64>("amplitude"), Some(0.3));
+/// assert_eq!(state.get::<f64>("missing"), None);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropagatorState {
+    values: BTreeMap<String, String>,
+}
+
+impl PropagatorState {
+    /// Create a new empty `PropagatorState`
+    pub fn new() -> PropagatorState {
+        PropagatorState::default()
+    }
+
+    /// Set the value associated with `name` to `value`
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn set<T: ToString>(&mut self, name: &str, value: T) {
+        let _ = self.values.insert(name.into(), value.to_string());
+    }
+
+    /// Get the value associated with `name`, or `None` if there is no such
+    /// value or if it can not be converted to `T`.
+    pub fn get<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.values.get(name).and_then(|value| value.parse().ok())
+    }
+
+    /// Get the value associated with `name`.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such value, or if it can not be converted to `T`.
+    pub fn expect<T: FromStr>(&self, name: &str) -> T {
+        match self.get(name) {
+            Some(value) => value,
+            None => panic!("missing or invalid '{}' value in propagator state", name),
+        }
+    }
+
+    /// Add all the values in `other` to this state, prefixing their names
+    /// with `prefix.`
+    pub(crate) fn insert_prefixed(&mut self, prefix: &str, other: PropagatorState) {
+        for (name, value) in other.values {
+            let _ = self.values.insert(format!("{}.{}", prefix, name), value);
+        }
+    }
+
+    /// Get a new state containing all the values in this state with names
+    /// starting with `prefix.`, removing the prefix from the names
+    pub(crate) fn prefixed(&self, prefix: &str) -> PropagatorState {
+        let prefix = format!("{}.", prefix);
+        let mut state = PropagatorState::new();
+        for (name, value) in &self.values {
+            if let Some(name) = name.strip_prefix(&prefix) {
+                let _ = state.values.insert(name.into(), value.clone());
+            }
+        }
+        return state;
+    }
+
+    /// Write this state to the file at `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "# Lumol propagator state")?;
+        for (name, value) in &self.values {
+            writeln!(file, "{} {}", name, value)?;
+        }
+        return Ok(());
+    }
+
+    /// Read a state from the file at `path`, written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PropagatorState, io::Error> {
+        let file = BufReader::new(File::open(path)?);
+        let mut state = PropagatorState::new();
+        for line in file.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut splitted = line.splitn(2, char::is_whitespace);
+            match (splitted.next(), splitted.next()) {
+                (Some(name), Some(value)) => state.set(name, value.trim()),
+                _ => return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid line in propagator state: '{}'", line)
+                )),
+            }
+        }
+        return Ok(state);
+    }
+}
+
+/// Deterministic sequence of seeds, derived from a single master seed using
+/// the splitmix64 algorithm. This is used to seed all the stochastic
+/// components of a simulation from a single value.
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_sim::SeedSequence;
+/// let mut seeds = SeedSequence::new(42);
+/// let first = seeds.next_seed();
+/// let second = seeds.next_seed();
+/// assert_ne!(first, second);
+///
+/// // The same master seed always gives the same sequence
+/// let mut seeds = SeedSequence::new(42);
+/// assert_eq!(seeds.next_seed(), first);
+/// assert_eq!(seeds.next_seed(), second);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SeedSequence {
+    state: u64,
+}
+
+impl SeedSequence {
+    /// Create a new sequence of seeds from the `master` seed.
+    pub fn new(master: u64) -> SeedSequence {
+        SeedSequence {
+            state: master,
+        }
+    }
+
+    /// Get the seed to use for initializing velocities from the `master`
+    /// seed. This is the first seed of the sequence, which is reserved for
+    /// this usage by `Simulation::set_seed`.
+    pub fn velocities(master: u64) -> u64 {
+        SeedSequence::new(master).next_seed()
+    }
+
+    /// Get the next seed in this sequence.
+    pub fn next_seed(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return z ^ (z >> 31);
+    }
+}
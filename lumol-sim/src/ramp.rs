@@ -0,0 +1,133 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+/// Interpolation used to go from the initial to the final temperature in a
+/// [`TemperatureRamp`](struct.TemperatureRamp.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RampMode {
+    /// The temperature changes linearly with the number of steps
+    Linear,
+    /// The temperature changes exponentially with the number of steps, with a
+    /// constant ratio between the temperatures of successive steps.
+    Exponential,
+}
+
+/// Schedule changing the target temperature of a simulation, for example for
+/// simulated annealing.
+///
+/// The temperature goes from `start` to `end` in `steps` simulation steps,
+/// and then stays at `end` until the end of the simulation. The ramp is used
+/// with [`Simulation::set_temperature_ramp`], and can be used with any
+/// propagator with a target temperature: Monte Carlo or molecular dynamics
+/// with a thermostat.
+///
+/// [`Simulation::set_temperature_ramp`]: struct.Simulation.html#method.set_temperature_ramp
+///
+/// # Examples
+///
+/// ```
+/// # use lumol_sim::{TemperatureRamp, RampMode};
+/// let ramp = TemperatureRamp::new(300.0, 100.0, 1000, RampMode::Linear);
+/// assert_eq!(ramp.temperature(0), 300.0);
+/// assert_eq!(ramp.temperature(500), 200.0);
+/// assert_eq!(ramp.temperature(1000), 100.0);
+/// assert_eq!(ramp.temperature(5000), 100.0);
+///
+/// let ramp = TemperatureRamp::new(400.0, 100.0, 1000, RampMode::Exponential);
+/// assert_eq!(ramp.temperature(500), 200.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TemperatureRamp {
+    /// Temperature at the beginning of the ramp
+    start: f64,
+    /// Temperature at the end of the ramp
+    end: f64,
+    /// Number of steps in the ramp
+    steps: u64,
+    /// Interpolation between start and end temperatures
+    mode: RampMode,
+}
+
+impl TemperatureRamp {
+    /// Create a new `TemperatureRamp` going from the `start` to the `end`
+    /// temperature in `steps` steps, using the given interpolation `mode`.
+    ///
+    /// # Panics
+    ///
+    /// If `steps` is zero, if one of the temperatures is negative, or if one
+    /// of the temperatures is zero with an exponential ramp.
+    pub fn new(start: f64, end: f64, steps: u64, mode: RampMode) -> TemperatureRamp {
+        assert!(steps > 0, "the number of steps must be positive in temperature ramp");
+        assert!(start >= 0.0 && end >= 0.0, "temperatures must be positive in temperature ramp");
+        if mode == RampMode::Exponential {
+            assert!(
+                start > 0.0 && end > 0.0,
+                "temperatures can not be zero in exponential temperature ramp"
+            );
+        }
+        TemperatureRamp {
+            start: start,
+            end: end,
+            steps: steps,
+            mode: mode,
+        }
+    }
+
+    /// Get the target temperature at the given `step` of the simulation,
+    /// starting at 0.
+    pub fn temperature(&self, step: u64) -> f64 {
+        if step >= self.steps {
+            return self.end;
+        }
+
+        let progress = step as f64 / self.steps as f64;
+        match self.mode {
+            RampMode::Linear => self.start + progress * (self.end - self.start),
+            RampMode::Exponential => self.start * f64::powf(self.end / self.start, progress),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_ulps_eq;
+
+    #[test]
+    fn linear() {
+        let ramp = TemperatureRamp::new(100.0, 300.0, 10, RampMode::Linear);
+        assert_ulps_eq!(ramp.temperature(0), 100.0);
+        assert_ulps_eq!(ramp.temperature(5), 200.0);
+        assert_ulps_eq!(ramp.temperature(2), 140.0);
+        assert_ulps_eq!(ramp.temperature(10), 300.0);
+        assert_ulps_eq!(ramp.temperature(42), 300.0);
+    }
+
+    #[test]
+    fn exponential() {
+        let ramp = TemperatureRamp::new(800.0, 100.0, 3, RampMode::Exponential);
+        assert_ulps_eq!(ramp.temperature(0), 800.0);
+        assert_ulps_eq!(ramp.temperature(1), 400.0);
+        assert_ulps_eq!(ramp.temperature(2), 200.0);
+        assert_ulps_eq!(ramp.temperature(3), 100.0);
+        assert_ulps_eq!(ramp.temperature(42), 100.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the number of steps must be positive in temperature ramp")]
+    fn zero_steps() {
+        let _ = TemperatureRamp::new(100.0, 300.0, 0, RampMode::Linear);
+    }
+
+    #[test]
+    #[should_panic(expected = "temperatures must be positive in temperature ramp")]
+    fn negative_temperature() {
+        let _ = TemperatureRamp::new(-100.0, 300.0, 10, RampMode::Linear);
+    }
+
+    #[test]
+    #[should_panic(expected = "temperatures can not be zero in exponential temperature ramp")]
+    fn zero_exponential() {
+        let _ = TemperatureRamp::new(300.0, 0.0, 10, RampMode::Exponential);
+    }
+}
@@ -0,0 +1,151 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Random number generators used by the propagators
+use log_once::warn_once;
+use rand::{Error, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::PropagatorState;
+
+/// Random number generator of a propagator. The state of the default
+/// generator can be saved and restored when checkpointing a simulation,
+/// while generators provided by the users are used as-is.
+pub(crate) enum PropagatorRng {
+    /// Default generator
+    Default(XorShiftRng),
+    /// Generator provided by the user
+    Custom(Box<dyn RngCore>),
+}
+
+impl PropagatorRng {
+    /// Create the default generator, with a fixed seed
+    pub(crate) fn new() -> PropagatorRng {
+        PropagatorRng::Default(XorShiftRng::from_seed([
+            0xeb, 0xa8, 0xe4, 0x29, 0xca, 0x60, 0x44, 0xb0,
+            0xd3, 0x77, 0xc6, 0xa0, 0x21, 0x71, 0x37, 0xf7,
+        ]))
+    }
+
+    /// Create the default generator, using the given `seed`
+    pub(crate) fn seeded(seed: u64) -> PropagatorRng {
+        PropagatorRng::Default(XorShiftRng::seed_from_u64(seed))
+    }
+
+    /// Save the state of this generator in `state` with the given `name`.
+    /// The state of custom generators can not be saved.
+    pub(crate) fn save_state(&self, name: &str, state: &mut PropagatorState) {
+        match *self {
+            PropagatorRng::Default(ref rng) => {
+                let value = serde_json::to_string(rng).expect("failed to serialize random number generator");
+                state.set(name, value);
+            }
+            PropagatorRng::Custom(_) => {
+                warn_once!("the state of custom random number generators can not be saved");
+            }
+        }
+    }
+
+    /// Restore the state of this generator from the value with the given
+    /// `name` in `state`, if any. The state of custom generators can not be
+    /// restored, and they are used as-is.
+    ///
+    /// # Panics
+    ///
+    /// If the value in `state` is not a valid generator state.
+    pub(crate) fn restore_state(&mut self, name: &str, state: &PropagatorState) {
+        let value = match state.get::<String>(name) {
+            Some(value) => value,
+            None => return,
+        };
+        match *self {
+            PropagatorRng::Default(ref mut rng) => {
+                match serde_json::from_str(&value) {
+                    Ok(restored) => *rng = restored,
+                    Err(error) => panic!(
+                        "invalid '{}' random number generator state '{}': {}", name, value, error
+                    ),
+                }
+            }
+            PropagatorRng::Custom(_) => {
+                warn_once!(
+                    "the state of custom random number generators can not be restored, \
+                     keeping the current state of the generator"
+                );
+            }
+        }
+    }
+}
+
+impl RngCore for PropagatorRng {
+    fn next_u32(&mut self) -> u32 {
+        match *self {
+            PropagatorRng::Default(ref mut rng) => rng.next_u32(),
+            PropagatorRng::Custom(ref mut rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match *self {
+            PropagatorRng::Default(ref mut rng) => rng.next_u64(),
+            PropagatorRng::Custom(ref mut rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match *self {
+            PropagatorRng::Default(ref mut rng) => rng.fill_bytes(dest),
+            PropagatorRng::Custom(ref mut rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match *self {
+            PropagatorRng::Default(ref mut rng) => rng.try_fill_bytes(dest),
+            PropagatorRng::Custom(ref mut rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_restore() {
+        let mut rng = PropagatorRng::seeded(42);
+        let _ = rng.next_u64();
+        let mut state = PropagatorState::new();
+        rng.save_state("rng", &mut state);
+
+        let mut restored = PropagatorRng::new();
+        restored.restore_state("rng", &state);
+        for _ in 0..100 {
+            assert_eq!(rng.next_u64(), restored.next_u64());
+        }
+    }
+
+    #[test]
+    fn restore_custom() {
+        let rng = PropagatorRng::seeded(42);
+        let mut state = PropagatorState::new();
+        rng.save_state("rng", &mut state);
+
+        // Custom generators are not replaced by the default one
+        let mut custom = PropagatorRng::Custom(Box::new(XorShiftRng::seed_from_u64(12)));
+        let mut reference = XorShiftRng::seed_from_u64(12);
+        custom.restore_state("rng", &state);
+        assert!(matches!(custom, PropagatorRng::Custom(_)));
+        for _ in 0..100 {
+            assert_eq!(custom.next_u64(), reference.next_u64());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid 'rng' random number generator state")]
+    fn restore_invalid() {
+        let mut state = PropagatorState::new();
+        state.set("rng", "1 2 3 4");
+        PropagatorRng::new().restore_state("rng", &state);
+    }
+}
@@ -1,11 +1,14 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use log::{info, warn};
+use log::{error, info, warn};
 
-use lumol_core::{System, DegreesOfFreedom, Vector3D};
+use lumol_core::{System, DegreesOfFreedom, Matrix3, Vector3D};
+use lumol_core::units::UnitSystem;
 
 use crate::output::Output;
-use crate::propagator::{Propagator, TemperatureStrategy};
+use crate::propagator::{Propagator, PropagatorState, SeedSequence, TemperatureStrategy};
+use crate::SimulationError;
+use crate::ramp::TemperatureRamp;
 
 /// Writing an output at a given frequency
 struct OutputFrequency {
@@ -37,6 +40,14 @@ impl Output for OutputFrequency {
         self.output.setup(system);
     }
 
+    fn resume(&mut self) {
+        self.output.resume();
+    }
+
+    fn set_unit_system(&mut self, units: UnitSystem) {
+        self.output.set_unit_system(units);
+    }
+
     fn write(&mut self, system: &System) {
         if system.step % self.frequency == 0 {
             self.output.write(system);
@@ -48,12 +59,48 @@ impl Output for OutputFrequency {
     }
 }
 
+/// A function called at a given frequency during the simulation
+struct Hook {
+    /// The function to call
+    function: Box<dyn FnMut(&mut System)>,
+    /// The frequency. `function` will be called every time the system step
+    /// matches this frequency.
+    frequency: u64,
+}
+
+/// An additional stage of a simulation, running a propagator for a given
+/// number of steps
+struct Stage {
+    /// The propagator to use during this stage
+    propagator: Box<dyn Propagator>,
+    /// The number of steps in this stage
+    nsteps: usize,
+}
+
 /// The Simulation struct holds all the needed algorithms for running the
 /// simulation. It should be use together with a `System` to perform the
 /// simulation.
+///
+/// A simulation can run multiple propagators in sequence on the same system,
+/// for example an energy minimization, followed by an equilibration with a
+/// thermostat and a constant energy production run. The first propagator is
+/// given to [`Simulation::new`](#method.new), and the next ones are added
+/// with [`add_stage`](#method.add_stage).
 pub struct Simulation {
     propagator: Box<dyn Propagator>,
+    stages: Vec<Stage>,
     outputs: Vec<OutputFrequency>,
+    hooks: Vec<Hook>,
+    ramp: Option<TemperatureRamp>,
+    units: UnitSystem,
+    /// Step of the system at the beginning of the next run, if any
+    initial_step: Option<u64>,
+    /// Time of the system at the beginning of the next run, if any
+    initial_time: Option<f64>,
+    /// Step and time of the system at the end of the last run, if any
+    last_clock: Option<(u64, f64)>,
+    /// Was the state of the simulation restored since the last run
+    resumed: bool,
 }
 
 impl Simulation {
@@ -61,41 +108,255 @@ impl Simulation {
     pub fn new(propagator: Box<dyn Propagator>) -> Simulation {
         Simulation {
             propagator: propagator,
+            stages: Vec::new(),
             outputs: Vec::new(),
+            hooks: Vec::new(),
+            ramp: None,
+            units: UnitSystem::Physical,
+            initial_step: None,
+            initial_time: None,
+            last_clock: None,
+            resumed: false,
+        }
+    }
+
+    /// Set the master `seed` for all the random number generators used by
+    /// this simulation. The seeds for the different components of the
+    /// simulation (Monte Carlo moves, stochastic thermostats, ...) are derived
+    /// from this master seed using a [`SeedSequence`], making two simulations
+    /// with the same seed reproducible.
+    ///
+    /// The first seed of the sequence is reserved for the initialization of
+    /// velocities, and can be obtained with [`SeedSequence::velocities`].
+    ///
+    /// [`SeedSequence`]: struct.SeedSequence.html
+    /// [`SeedSequence::velocities`]: struct.SeedSequence.html#method.velocities
+    pub fn set_seed(&mut self, seed: u64) {
+        let mut seeds = SeedSequence::new(seed);
+        // Skip the seed used for velocities initialization
+        let _ = seeds.next_seed();
+        self.propagator.seed(&mut seeds);
+        for stage in &mut self.stages {
+            stage.propagator.seed(&mut seeds);
+        }
+    }
+
+    /// Add a new stage to this simulation, running the `propagator` for
+    /// `nsteps` steps. The stages are run in order after the main propagator
+    /// in every call to [`run`](#method.run), continuing from the state of the
+    /// system at the end of the previous stage. The steps of the stages are
+    /// included in the number of steps given to `run`.
+    pub fn add_stage(&mut self, propagator: Box<dyn Propagator>, nsteps: usize) {
+        self.stages.push(Stage {
+            propagator: propagator,
+            nsteps: nsteps,
+        });
+    }
+
+    /// Get the total number of steps of the additional stages of this
+    /// simulation
+    pub(crate) fn stages_steps(&self) -> usize {
+        self.stages.iter().map(|stage| stage.nsteps).sum()
+    }
+
+    /// Change the target temperature of the propagator during the simulation
+    /// following the given temperature `ramp`. The steps of the ramp are
+    /// counted from the beginning of every call to `run`, including the steps
+    /// of all the stages. The ramp is not used with propagators without
+    /// temperature, such as energy minimization.
+    pub fn set_temperature_ramp(&mut self, ramp: TemperatureRamp) {
+        self.ramp = Some(ramp);
+    }
+
+    /// Set the unit system used by the outputs of this simulation. By
+    /// default, all outputs use physical units.
+    pub fn set_unit_system(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
+    /// Set the `step` of the system at the beginning of the next call to
+    /// [`run`](#method.run). This can be used when restarting a simulation to
+    /// continue the steps counting from the end of the previous run, instead
+    /// of the step stored in the system.
+    pub fn set_initial_step(&mut self, step: u64) {
+        self.initial_step = Some(step);
+    }
+
+    /// Set the `time` of the system at the beginning of the next call to
+    /// [`run`](#method.run). When using a propagator with a timestep, the time
+    /// of the system is then incremented by the timestep actually used at
+    /// every step, which can change with adaptive timesteps or between
+    /// stages. This can be used when restarting a simulation to continue the
+    /// time from the end of the previous run.
+    pub fn set_initial_time(&mut self, time: f64) {
+        self.initial_time = Some(time);
+    }
+
+    /// Save the internal state of the propagator (random number generators,
+    /// Monte Carlo moves amplitudes and statistics, thermostats state, ...).
+    /// Together with the system, this state allows to resume the simulation
+    /// later with [`restore_state`](#method.restore_state), continuing with
+    /// the same random numbers as if it was never interrupted. The step and
+    /// time of the system at the end of the last run are saved too. The
+    /// state of the propagators of the additional stages is saved with a
+    /// `stage.<n>` prefix, where `<n>` is the number of the stage, starting
+    /// at 2 for the first additional stage.
+    pub fn save_state(&mut self) -> PropagatorState {
+        let mut state = PropagatorState::new();
+        if let Some((step, time)) = self.last_clock {
+            state.set("simulation.step", step);
+            state.set("simulation.time", time);
+        }
+        self.propagator.save_state(&mut state);
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            let mut stage_state = PropagatorState::new();
+            stage.propagator.save_state(&mut stage_state);
+            state.insert_prefixed(&stage_prefix(i), stage_state);
         }
+        return state;
     }
 
-    /// Run the simulation on System for `nsteps` steps.
+    /// Restore the internal state of the propagator from a `state` created
+    /// by [`save_state`](#method.save_state) in a simulation using the same
+    /// propagator setup. The next call to [`run`](#method.run) continues the
+    /// step and time of the system from the saved ones, and the outputs using
+    /// [`FileMode::Auto`] add data to their existing files.
+    ///
+    /// [`FileMode::Auto`]: output/enum.FileMode.html#variant.Auto
+    pub fn restore_state(&mut self, state: &PropagatorState) {
+        self.resumed = true;
+        if let Some(step) = state.get("simulation.step") {
+            self.initial_step = Some(step);
+        }
+        if let Some(time) = state.get("simulation.time") {
+            self.initial_time = Some(time);
+        }
+        self.propagator.restore_state(state);
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            stage.propagator.restore_state(&state.prefixed(&stage_prefix(i)));
+        }
+    }
+
+    /// Run the simulation on System for `nsteps` steps in total. The main
+    /// propagator runs for the steps not used by the additional stages, and
+    /// is followed by all the stages. If the simulation fails, the error is
+    /// logged and the run is stopped, see [`try_run`](#method.try_run) to
+    /// handle the error instead.
+    ///
+    /// # Panics
+    ///
+    /// If `nsteps` is smaller than the total number of steps of the
+    /// additional stages.
     pub fn run(&mut self, system: &mut System, nsteps: usize) {
-        match self.propagator.temperature_strategy() {
-            TemperatureStrategy::External(temperature) => {
-                system.simulated_temperature(Some(temperature))
-            }
-            TemperatureStrategy::Velocities => system.simulated_temperature(None),
-            TemperatureStrategy::None => {}
+        if let Err(err) = self.try_run(system, nsteps) {
+            error!("{}", err);
         }
+    }
 
-        system.simulated_degrees_of_freedom = self.propagator.degrees_of_freedom(system);
-        match system.simulated_degrees_of_freedom {
-            DegreesOfFreedom::Molecules => info!("All molecules are treated as rigid bodies"),
-            DegreesOfFreedom::Particles => info!("All particles are allowed to move in this simulation"),
-            DegreesOfFreedom::Frozen(n) => info!("{} degrees of freedom are frozen", n),
+    /// Run the simulation in the same way as [`run`](#method.run), returning
+    /// an error if the simulation fails, for example because the forces are
+    /// exploding. The run is stopped at the first error, after finishing the
+    /// propagator and the outputs.
+    pub fn try_run(&mut self, system: &mut System, nsteps: usize) -> Result<(), SimulationError> {
+        self.run_with_sampler(system, nsteps, |_, _| {})
+    }
+
+    /// Run the simulation in the same way as [`try_run`](#method.try_run),
+    /// calling `sampler` after every step with the number of steps done since
+    /// the beginning of this run (starting at 1) and the system. The sampler
+    /// is called before the hooks and the outputs.
+    pub(crate) fn run_with_sampler<F>(
+        &mut self,
+        system: &mut System,
+        nsteps: usize,
+        mut sampler: F,
+    ) -> Result<(), SimulationError>
+    where
+        F: FnMut(u64, &mut System),
+    {
+        let stages_steps = self.stages_steps();
+        assert!(
+            nsteps >= stages_steps,
+            "can not run {} steps, the simulation stages already contain {} steps", nsteps, stages_steps
+        );
+
+        if let Some(step) = self.initial_step.take() {
+            system.step = step;
         }
+        if let Some(time) = self.initial_time.take() {
+            system.time = time;
+        }
+        let resumed = std::mem::replace(&mut self.resumed, false);
+
+        let mut ramp_step = 0;
+        for stage in 0..=self.stages.len() {
+            let (propagator, nsteps) = if stage == 0 {
+                (&mut self.propagator, nsteps - stages_steps)
+            } else {
+                info!("Starting simulation stage {}", stage + 1);
+                let stage = &mut self.stages[stage - 1];
+                (&mut stage.propagator, stage.nsteps)
+            };
 
-        system.check();
-        self.setup(system);
-        for i in 0..nsteps {
-            self.propagator.propagate(system);
-            system.step += 1;
-            for output in &mut self.outputs {
-                output.write(system);
+            setup_system(&**propagator, system);
+            propagator.setup(system);
+            if stage == 0 {
+                for output in &mut self.outputs {
+                    output.set_unit_system(self.units);
+                    if resumed {
+                        output.resume();
+                    }
+                    output.setup(system);
+                }
             }
 
-            if i % 10_000 == 0 {
-                sanity_check(system);
+            let use_ramp = propagator.temperature_strategy() != TemperatureStrategy::None;
+            for i in 0..nsteps {
+                if let (Some(ramp), true) = (&self.ramp, use_ramp) {
+                    let temperature = ramp.temperature(ramp_step);
+                    propagator.set_temperature(temperature);
+                    if let TemperatureStrategy::External(_) = propagator.temperature_strategy() {
+                        system.simulated_temperature(Some(temperature));
+                    }
+                }
+
+                propagator.propagate(system);
+                if let Some(err) = propagator.take_error() {
+                    warn!("Simulation stopped at step {}", system.step);
+                    propagator.finish(system);
+                    for output in &mut self.outputs {
+                        output.finish(system);
+                    }
+                    self.last_clock = Some((system.step, system.time));
+                    return Err(err);
+                }
+                system.step += 1;
+                if let Some(timestep) = propagator.timestep() {
+                    system.time += timestep;
+                }
+                ramp_step += 1;
+                sampler(ramp_step, system);
+                for hook in &mut self.hooks {
+                    if system.step % hook.frequency == 0 {
+                        (hook.function)(system);
+                    }
+                }
+                for output in &mut self.outputs {
+                    output.write(system);
+                }
+
+                if i % 10_000 == 0 {
+                    sanity_check(system);
+                }
             }
+            propagator.finish(system);
         }
-        self.finish(system);
+
+        for output in &mut self.outputs {
+            output.finish(system);
+        }
+        self.last_clock = Some((system.step, system.time));
+        Ok(())
     }
 
     /// Add a new `Output` algorithm in the outputs list
@@ -110,19 +371,57 @@ impl Simulation {
         self.outputs.push(OutputFrequency::with_frequency(output, frequency));
     }
 
-    fn setup(&mut self, system: &mut System) {
-        self.propagator.setup(system);
-        for output in &mut self.outputs {
-            output.setup(system);
-        }
+    /// Add a `hook` function to this simulation, which will be called with
+    /// the system every time the system step matches the given `frequency`.
+    /// Hooks are called after the propagator step, and before the outputs.
+    ///
+    /// Hooks can modify the system, for example to implement custom analysis
+    /// or adaptive protocols. Changing the particles positions or the unit
+    /// cell in a hook might invalidate data cached by the propagator, such as
+    /// the energy in Monte Carlo simulations.
+    ///
+    /// # Panics
+    ///
+    /// If `frequency` is zero.
+    pub fn add_hook<F>(&mut self, frequency: u64, hook: F)
+    where
+        F: FnMut(&mut System) + 'static,
+    {
+        assert!(frequency > 0, "hook frequency must be positive");
+        self.hooks.push(Hook {
+            function: Box::new(hook),
+            frequency: frequency,
+        });
     }
+}
 
-    fn finish(&mut self, system: &mut System) {
-        self.propagator.finish(system);
-        for output in &mut self.outputs {
-            output.finish(system);
+/// Get the prefix used to save the state of the additional stage at index `i`
+fn stage_prefix(i: usize) -> String {
+    format!("stage.{}", i + 2)
+}
+
+/// Set the temperature and the degrees of freedom of the `system` for a run
+/// with the given `propagator`
+fn setup_system(propagator: &dyn Propagator, system: &mut System) {
+    // The constraints of a previous propagator do not apply anymore, the
+    // propagator will set the virial of its own constraints
+    system.constraints_virial = Matrix3::zero();
+    match propagator.temperature_strategy() {
+        TemperatureStrategy::External(temperature) => {
+            system.simulated_temperature(Some(temperature))
         }
+        TemperatureStrategy::Velocities => system.simulated_temperature(None),
+        TemperatureStrategy::None => {}
     }
+
+    system.simulated_degrees_of_freedom = propagator.degrees_of_freedom(system);
+    match system.simulated_degrees_of_freedom {
+        DegreesOfFreedom::Molecules => info!("All molecules are treated as rigid bodies"),
+        DegreesOfFreedom::Particles => info!("All particles are allowed to move in this simulation"),
+        DegreesOfFreedom::Frozen(n) => info!("{} degrees of freedom are frozen", n),
+    }
+
+    system.check();
 }
 
 /// Perform some sanity checks on the system
@@ -0,0 +1,87 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Single point energy evaluation
+use log::info;
+
+use lumol_core::{System, DegreesOfFreedom};
+use lumol_core::units;
+
+use crate::propagator::{Propagator, TemperatureStrategy};
+
+/// Single point propagator, evaluating the energy, forces and virial of a
+/// system without changing it.
+///
+/// Each step of this propagator writes the energy components, the virial (for
+/// systems with a finite unit cell) and the maximal force acting on a particle
+/// to the log, and the outputs of the simulation are called with the unchanged
+/// system. This propagator is
+/// usually used for a single step, to compute the properties of a given
+/// configuration.
+#[derive(Clone, Debug, Default)]
+pub struct SinglePoint;
+
+impl SinglePoint {
+    /// Create a new `SinglePoint` propagator
+    pub fn new() -> SinglePoint {
+        SinglePoint
+    }
+}
+
+impl Propagator for SinglePoint {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::None
+    }
+
+    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        let energy = |value| units::to(value, "kJ/mol").expect("bad unit");
+        let components = system.energy_components();
+        info!("Single point energy evaluation at step {}", system.step);
+        info!("    pairs energy: {} kJ/mol", energy(components.pairs));
+        info!("    pairs tail correction: {} kJ/mol", energy(components.pairs_tail));
+        info!("    bonds energy: {} kJ/mol", energy(components.bonds));
+        info!("    angles energy: {} kJ/mol", energy(components.angles));
+        info!("    dihedrals energy: {} kJ/mol", energy(components.dihedrals));
+        info!("    coulomb energy: {} kJ/mol", energy(components.coulomb));
+        info!("    global energy: {} kJ/mol", energy(components.global));
+        info!("    potential energy: {} kJ/mol", energy(components.total()));
+
+        // The virial is not defined for infinite cells
+        if !system.cell.is_infinite() {
+            let virial = system.virial();
+            let virial = |i: usize, j: usize| energy(virial[i][j]);
+            info!("    virial (kJ/mol): [{} {} {}]", virial(0, 0), virial(0, 1), virial(0, 2));
+            info!("                     [{} {} {}]", virial(1, 0), virial(1, 1), virial(1, 2));
+            info!("                     [{} {} {}]", virial(2, 0), virial(2, 1), virial(2, 2));
+        }
+
+        let max_force = system.forces().iter().map(|force| force.norm()).fold(0.0, f64::max);
+        let max_force = units::to(max_force, "kJ/mol/A").expect("bad unit");
+        info!("    maximal force: {} kJ/mol/A", max_force);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumol_core::{Molecule, Particle, Vector3D};
+
+    #[test]
+    fn propagate() {
+        let mut system = System::new();
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+        system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(2.0, 0.0, 0.0))));
+        system.particles_mut().velocity[0] = Vector3D::new(1.0, 0.0, 0.0);
+
+        let mut propagator = SinglePoint::new();
+        propagator.propagate(&mut system);
+
+        assert_eq!(system.particles().position[0], Vector3D::new(0.0, 0.0, 0.0));
+        assert_eq!(system.particles().position[1], Vector3D::new(2.0, 0.0, 0.0));
+        assert_eq!(system.particles().velocity[0], Vector3D::new(1.0, 0.0, 0.0));
+    }
+}
@@ -55,6 +55,11 @@ impl BoltzmannVelocities {
 impl InitVelocities for BoltzmannVelocities {
     fn init(&mut self, system: &mut System) {
         for particle in system.particles_mut() {
+            if *particle.mass == 0.0 {
+                // Massless virtual sites follow their parents
+                *particle.velocity = Vector3D::zero();
+                continue;
+            }
             let m_inv = 1.0 / (*particle.mass);
             let x = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             let y = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
@@ -64,6 +69,7 @@ impl InitVelocities for BoltzmannVelocities {
         RemoveTranslation.control(system);
         RemoveRotation.control(system);
         scale(system, self.temperature);
+        system.update_virtual_sites();
     }
 
     fn seed(&mut self, seed: u64) {
@@ -107,6 +113,11 @@ impl UniformVelocities {
 impl InitVelocities for UniformVelocities {
     fn init(&mut self, system: &mut System) {
         for particle in system.particles_mut() {
+            if *particle.mass == 0.0 {
+                // Massless virtual sites follow their parents
+                *particle.velocity = Vector3D::zero();
+                continue;
+            }
             let m_inv = 1.0 / (*particle.mass);
             *particle.velocity = f64::sqrt(m_inv) * Vector3D::new(
                 self.dist.sample(&mut self.rng),
@@ -117,6 +128,7 @@ impl InitVelocities for UniformVelocities {
         RemoveTranslation.control(system);
         RemoveRotation.control(system);
         scale(system, self.temperature);
+        system.update_virtual_sites();
     }
 
     fn seed(&mut self, seed: u64) {
@@ -139,7 +151,7 @@ impl InitVelocities for UniformVelocities {
 mod test {
     use super::*;
     use rand::random;
-    use lumol_core::{Molecule, Particle, System, Vector3D, UnitCell};
+    use lumol_core::{Molecule, Particle, System, Vector3D, UnitCell, VirtualSite};
 
     use approx::assert_ulps_eq;
     use soa_derive::soa_zip;
@@ -207,4 +219,33 @@ mod test {
 
         assert!(global_translation(&system) > 1e-5);
     }
+
+    #[test]
+    fn virtual_sites() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..10 {
+            let origin = Vector3D::new(2.0 * i as f64, 0.0, 0.0);
+            let mut molecule = Molecule::new(Particle::with_position("O", origin));
+            molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(0.96, 0.0, 0.0)));
+            molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(-0.24, 0.93, 0.0)));
+            molecule.add_particle_bonded_to(0, Particle::with_position("M", origin));
+            system.add_molecule(molecule);
+            system.add_virtual_site(VirtualSite::new(4 * i + 3, [4 * i, 4 * i + 1, 4 * i + 2], [0.8, 0.1, 0.1]));
+        }
+
+        let mut initializers: Vec<Box<dyn InitVelocities>> = vec![
+            Box::new(BoltzmannVelocities::new(300.0)),
+            Box::new(UniformVelocities::new(300.0)),
+        ];
+        for velocities in &mut initializers {
+            velocities.init(&mut system);
+            assert!(system.particles().velocity.iter().all(|v| v.norm().is_finite()));
+            assert_ulps_eq!(system.temperature(), 300.0, epsilon = 1e-9);
+            assert_ulps_eq!(global_translation(&system), 0.0, epsilon = 1e-12);
+            for site in system.virtual_sites() {
+                let expected = site.velocity(system.particles().velocity);
+                assert!((system.particles().velocity[site.site()] - expected).norm() < 1e-12);
+            }
+        }
+    }
 }
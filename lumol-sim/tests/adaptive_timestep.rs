@@ -0,0 +1,73 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Integrating the motion of an initially overlapping pair of particles with
+//! an adaptive timestep
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::md::{Integrator, VelocityVerlet};
+
+use approx::assert_relative_eq;
+
+fn overlapping_pair() -> System {
+    let mut system = System::with_cell(UnitCell::infinite());
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(2.0, 0.0, 0.0))));
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 12.0)).unwrap();
+    system
+}
+
+/// Integrate the motion for `steps` steps, returning the relative error on
+/// the total energy
+fn integrate(integrator: &mut VelocityVerlet, system: &mut System, steps: usize) -> f64 {
+    let initial = system.total_energy();
+    integrator.setup(system);
+    for _ in 0..steps {
+        integrator.update_timestep(system);
+        integrator.integrate(system);
+    }
+    return f64::abs((system.total_energy() - initial) / initial);
+}
+
+#[test]
+fn fixed_timestep() {
+    // The overlapping particles are strongly accelerated in the first steps,
+    // and the energy is not conserved
+    let mut system = overlapping_pair();
+    let mut integrator = VelocityVerlet::new(5.0);
+    let error = integrate(&mut integrator, &mut system, 10);
+    assert!(error > 1.0);
+}
+
+#[test]
+fn adaptive_timestep() {
+    let mut system = overlapping_pair();
+    let mut integrator = VelocityVerlet::new(5.0);
+    let max_force = units::from(50.0, "kJ/mol/A").unwrap();
+    integrator.set_adaptive_timestep(0.01, 5.0, max_force);
+
+    // The timestep is reduced proportionally to the initial forces
+    let force = system.forces()[0].norm();
+    integrator.setup(&system);
+    integrator.update_timestep(&system);
+    integrator.integrate(&mut system);
+    let timestep = integrator.timestep().unwrap();
+    assert_relative_eq!(timestep, 5.0 * max_force / force, max_relative = 1e-12);
+    assert!(timestep < 0.1);
+
+    let mut system = overlapping_pair();
+    let error = integrate(&mut integrator, &mut system, 2000);
+    assert!(error < 2e-2, "relative energy error is {}", error);
+
+    // The particles were pushed apart without exploding, and the timestep
+    // went back to its maximal value
+    let distance = system.distance(0, 1);
+    assert!(distance.is_finite() && distance > 4.0);
+    assert!(system.particles().velocity.iter().all(|velocity| velocity.norm() < 1.0));
+    assert_eq!(integrator.timestep(), Some(5.0));
+}
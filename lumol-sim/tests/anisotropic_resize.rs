@@ -0,0 +1,158 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Anisotropic deformations of the cell in constant pressure Monte Carlo, for
+//! a membrane-like system made of rigid rods aligned with the z axis, and for
+//! a simple fluid in a triclinic cell
+use lumol_core::{LennardJones, Matrix3, Molecule, PairInteraction, Particle, System, UnitCell, Vector3D};
+use lumol_core::compute::{Compute, StressAtTemperature};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::mc::{MonteCarloBuilder, Resize, ResizeMode, Translate};
+
+const CELL: f64 = 20.0;
+const SIGMA: f64 = 3.0;
+/// Number of beads in each rod
+const BEADS: usize = 6;
+/// Distance between the first and last beads of a rod
+const ROD: f64 = (BEADS - 1) as f64 * SIGMA;
+
+/// Purely repulsive interactions between the "C" particles
+fn set_repulsive_potential(system: &mut System) {
+    let lj = LennardJones {
+        sigma: SIGMA,
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    };
+    let cutoff = f64::powf(2.0, 1.0 / 6.0) * SIGMA;
+    system.set_pair_potential(("C", "C"), PairInteraction::shifted(Box::new(lj), cutoff)).unwrap();
+}
+
+/// A bilayer of 3x3 columns, each column containing two rods stacked along
+/// z. The out-of-plane length of the cell is set by the two rods and the
+/// contacts between their ends, so the rods never span half of the cell and
+/// the corresponding rejection of the Resize move is not active.
+fn membrane() -> System {
+    let mut system = System::with_cell(UnitCell::ortho(CELL, CELL, 2.0 * CELL));
+    for i in 0..3 {
+        for j in 0..3 {
+            for layer in 0..2 {
+                let base = Vector3D::new(
+                    (i as f64 + 0.5) * CELL / 3.0,
+                    (j as f64 + 0.5) * CELL / 3.0,
+                    layer as f64 * CELL,
+                );
+                let mut molecule = Molecule::new(Particle::with_position("C", base));
+                for k in 1..BEADS {
+                    let position = base + Vector3D::new(0.0, 0.0, k as f64 * SIGMA);
+                    molecule.add_particle_bonded_to(k - 1, Particle::with_position("C", position));
+                }
+                system.add_molecule(molecule);
+            }
+        }
+    }
+
+    set_repulsive_potential(&mut system);
+    // The rods are rigid, there is no need for bonds, angles and dihedrals
+    // potentials
+    system.set_missing_interactions_warnings(false);
+    return system;
+}
+
+/// A fluid of 64 "C" particles on a cubic lattice
+fn fluid() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    for i in 0..4 {
+        for j in 0..4 {
+            for k in 0..4 {
+                let position = Vector3D::new(i as f64, j as f64, k as f64) * CELL / 4.0;
+                system.add_molecule(Molecule::new(Particle::with_position("C", position)));
+            }
+        }
+    }
+    set_repulsive_potential(&mut system);
+    return system;
+}
+
+/// Run a constant pressure simulation of the `system` using the given `mode`
+/// for the Resize move, and get the average cell lengths and the average
+/// diagonal of the stress tensor divided by the pressure at equilibrium
+fn equilibrium(system: &mut System, mode: ResizeMode) -> (Vector3D, Vector3D) {
+    let temperature = units::from(300.0, "K").unwrap();
+    let pressure = units::from(2000.0, "bar").unwrap();
+    let mut builder = MonteCarloBuilder::new(temperature);
+    builder.add(Box::new(Translate::new(0.5, None)), 1.0, None);
+    builder.add(Box::new(Resize::with_mode(pressure, 50.0, mode)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+    simulation.run(system, 10_000);
+
+    let mut lengths = Vector3D::zero();
+    let mut stress = Matrix3::zero();
+    let samples = 500;
+    for _ in 0..samples {
+        simulation.run(system, 20);
+        lengths += system.cell.lengths();
+        stress += StressAtTemperature { temperature }.compute(system);
+    }
+    let lengths = lengths / samples as f64;
+    let stress = stress / (samples as f64 * pressure);
+    return (lengths, Vector3D::new(stress[0][0], stress[1][1], stress[2][2]));
+}
+
+/// Check that the diagonal of the stress tensor, divided by the imposed
+/// pressure, is equal to one: the cell is at mechanical equilibrium along
+/// all the directions it can change.
+fn check_stress(stress: Vector3D) {
+    for i in 0..3 {
+        assert!(f64::abs(stress[i] - 1.0) < 0.2, "stress/pressure is {:?}", stress);
+    }
+}
+
+/// Check the cell `lengths` of the membrane at equilibrium: the in-plane
+/// lengths correspond to three columns of rods in contact, and the
+/// out-of-plane length to two rods in contact by their ends.
+fn check_lengths(lengths: Vector3D) {
+    for &length in &[lengths[0], lengths[1]] {
+        let distance = length / 3.0;
+        assert!(0.8 * SIGMA < distance && distance < 1.5 * SIGMA, "in-plane length is {}", length);
+    }
+
+    let distance = lengths[2] / 2.0 - ROD;
+    assert!(0.8 * SIGMA < distance && distance < 2.0 * SIGMA, "out-of-plane length is {}", lengths[2]);
+}
+
+#[test]
+fn semi_isotropic() {
+    let (lengths, stress) = equilibrium(&mut membrane(), ResizeMode::SemiIsotropic);
+    // The cell stays square in the plane of the membrane
+    assert!(f64::abs(lengths[0] - lengths[1]) < 1e-6 * lengths[0]);
+    check_lengths(lengths);
+    check_stress(stress);
+}
+
+#[test]
+fn anisotropic() {
+    let (lengths, stress) = equilibrium(&mut membrane(), ResizeMode::Anisotropic);
+    check_lengths(lengths);
+    check_stress(stress);
+}
+
+#[test]
+fn triclinic() {
+    // Tilting the cell would allow the rods of the membrane to slide along
+    // their periodic images, the out-of-plane length would then only be set
+    // by the rejection of moves making a rod span half of the cell. Use a
+    // simple fluid instead, where this rejection can not happen.
+    let mut system = fluid();
+    let (_, stress) = equilibrium(&mut system, ResizeMode::Triclinic);
+    check_stress(stress);
+
+    // The tilt of the cell stays bounded
+    let matrix = system.cell.matrix();
+    let vector = |k: usize| Vector3D::new(matrix[0][k], matrix[1][k], matrix[2][k]);
+    for (i, j) in [(0, 1), (0, 2), (1, 2)].iter().cloned() {
+        let (vi, vj) = (vector(i), vector(j));
+        assert!(f64::abs(vi * vj) <= 0.5 * vi.norm2());
+    }
+}
@@ -0,0 +1,90 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Diagnostics output for barostats
+use lumol_core::System;
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{AnisoBerendsenBarostat, BerendsenBarostat, BerendsenThermostat};
+use lumol_sim::md::{BarostatDiagnostics, Integrator, MolecularDynamics};
+use lumol_sim::output::{BarostatOutput, Output};
+
+use tempfile::NamedTempFile;
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+mod utils;
+
+fn testing_system() -> System {
+    let mut system = utils::argon_lattice(4, 5.5);
+    utils::init_velocities(&mut system);
+    system
+}
+
+/// Record the step and volume of the system at every call
+struct VolumeRecorder(Rc<RefCell<Vec<(u64, f64)>>>);
+
+impl Output for VolumeRecorder {
+    fn write(&mut self, system: &System) {
+        self.0.borrow_mut().push((system.step, system.volume()));
+    }
+}
+
+/// Run a short simulation with the given barostat, and check the diagnostics
+/// written to the output file against the system volume.
+fn check_diagnostics(integrator: Box<dyn Integrator>, diagnostics: BarostatDiagnostics, target: f64) {
+    let mut system = testing_system();
+    let tempfile = NamedTempFile::new().unwrap();
+    let volumes = Rc::new(RefCell::new(Vec::new()));
+
+    {
+        let mut md = MolecularDynamics::from_integrator(integrator);
+        md.set_thermostat(Box::new(BerendsenThermostat::new(300.0, 10.0)));
+        let mut simulation = Simulation::new(Box::new(md));
+        let output = BarostatOutput::new(tempfile.path(), diagnostics.clone()).unwrap();
+        simulation.add_output_with_frequency(Box::new(output), 5);
+        simulation.add_output_with_frequency(Box::new(VolumeRecorder(volumes.clone())), 5);
+        simulation.run(&mut system, 50);
+    }
+
+    let state = diagnostics.get().unwrap();
+    assert_eq!(state.volume, system.volume());
+    assert_eq!(state.target, target);
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let lines = content.lines().filter(|line| !line.starts_with('#')).collect::<Vec<_>>();
+    let volumes = volumes.borrow();
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines.len(), volumes.len());
+
+    let mut changed = false;
+    for (line, &(step, volume)) in lines.iter().zip(volumes.iter()) {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[0].parse::<u64>().unwrap(), step);
+        assert_eq!(values[2].parse::<f64>().unwrap(), units::to(target, "bar").unwrap());
+        assert_eq!(values[3].parse::<f64>().unwrap(), volume);
+        changed |= volume != 4096.0;
+    }
+    assert!(changed, "the barostat did not change the volume");
+}
+
+#[test]
+fn berendsen() {
+    let pressure = units::from(100.0, "bar").unwrap();
+    let barostat = BerendsenBarostat::new(1.0, pressure, 100.0);
+    let diagnostics = barostat.diagnostics();
+    assert_eq!(diagnostics.get(), None);
+    check_diagnostics(Box::new(barostat), diagnostics, pressure);
+}
+
+#[test]
+fn anisotropic_berendsen() {
+    let pressure = units::from(100.0, "bar").unwrap();
+    let barostat = AnisoBerendsenBarostat::hydrostatic(1.0, pressure, 100.0);
+    let diagnostics = barostat.diagnostics();
+    check_diagnostics(Box::new(barostat), diagnostics, pressure);
+}
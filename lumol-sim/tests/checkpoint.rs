@@ -0,0 +1,185 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Resuming simulations from a saved propagator state
+use std::fs;
+
+use lumol_sim::{PropagatorState, Simulation};
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+use lumol_sim::md::{BerendsenBarostat, CSVRThermostat, MolecularDynamics};
+use lumol_sim::output::EnergyOutput;
+
+use tempfile::NamedTempFile;
+
+mod utils;
+
+fn monte_carlo() -> Simulation {
+    let mut builder = MonteCarloBuilder::new(300.0);
+    builder.add(Box::new(Translate::new(3.0, None)), 1.0, 0.5);
+    let mut mc = builder.finish();
+    mc.set_amplitude_update_frequency(10);
+    let mut simulation = Simulation::new(Box::new(mc));
+    simulation.set_seed(42);
+    simulation
+}
+
+#[test]
+fn monte_carlo_checkpoint() {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let mut simulation = monte_carlo();
+    simulation.run(&mut system, 305);
+
+    let tempfile = NamedTempFile::new().unwrap();
+    let state = simulation.save_state();
+    state.save(tempfile.path()).unwrap();
+
+    // The amplitude was adjusted during the run
+    let amplitude = state.get::<f64>("mc.move.0.amplitude").unwrap();
+    assert_ne!(amplitude, 3.0 / f64::sqrt(3.0));
+    assert_eq!(state.get::<u64>("mc.move.0.total_attempted"), Some(305));
+    assert_eq!(state.get::<u64>("mc.move.0.attempted"), Some(5));
+
+    // Resume the run in a new simulation, and compare it with the initial
+    // simulation continuing without interruption
+    let mut resumed_system = system.clone();
+    let mut resumed = monte_carlo();
+    let loaded = PropagatorState::load(tempfile.path()).unwrap();
+    assert_eq!(loaded, state);
+    resumed.restore_state(&loaded);
+    assert_eq!(resumed.save_state().get::<f64>("mc.move.0.amplitude"), Some(amplitude));
+    assert_eq!(resumed.save_state().get::<u64>("mc.steps"), Some(305));
+
+    simulation.run(&mut system, 300);
+    resumed.run(&mut resumed_system, 300);
+
+    assert_eq!(system.particles().position, resumed_system.particles().position);
+    assert_eq!(simulation.save_state(), resumed.save_state());
+}
+
+#[test]
+fn thermostat_checkpoint() {
+    let molecular_dynamics = || {
+        let mut md = MolecularDynamics::new(1.0);
+        md.set_thermostat(Box::new(CSVRThermostat::new(300.0, 10.0)));
+        let mut simulation = Simulation::new(Box::new(md));
+        simulation.set_seed(42);
+        simulation
+    };
+
+    let mut system = utils::argon_lattice(3, 5.5);
+    utils::init_velocities(&mut system);
+
+    let mut simulation = molecular_dynamics();
+    simulation.run(&mut system, 50);
+    let state = simulation.save_state();
+    // Saving the state does not change the simulation
+    assert_eq!(simulation.save_state(), state);
+
+    let mut resumed_system = system.clone();
+    let mut resumed = molecular_dynamics();
+    resumed.restore_state(&state);
+
+    simulation.run(&mut system, 50);
+    resumed.run(&mut resumed_system, 50);
+    assert_eq!(system.particles().velocity, resumed_system.particles().velocity);
+}
+
+#[test]
+#[should_panic(expected = "can not restore the state of a Monte Carlo simulation with 2 moves from a state with 1 moves")]
+fn wrong_number_of_moves() {
+    let state = monte_carlo().save_state();
+
+    let mut builder = MonteCarloBuilder::new(300.0);
+    builder.add(Box::new(Translate::new(3.0, None)), 1.0, 0.5);
+    builder.add(Box::new(Translate::new(3.0, None)), 1.0, 0.5);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.restore_state(&state);
+}
+
+#[test]
+fn barostat_checkpoint() {
+    let molecular_dynamics = || {
+        let md = MolecularDynamics::from_integrator(Box::new(BerendsenBarostat::new(1.0, 0.0, 100.0)));
+        Simulation::new(Box::new(md))
+    };
+
+    let mut system = utils::argon_lattice(3, 5.5);
+    utils::init_velocities(&mut system);
+
+    let mut simulation = molecular_dynamics();
+    simulation.run(&mut system, 50);
+    let state = simulation.save_state();
+    assert!(state.get::<f64>("md.integrator.eta").is_some());
+
+    let mut resumed_system = system.clone();
+    let mut resumed = molecular_dynamics();
+    resumed.restore_state(&state);
+
+    simulation.run(&mut system, 50);
+    resumed.run(&mut resumed_system, 50);
+    assert_eq!(system.particles().position, resumed_system.particles().position);
+    assert_eq!(system.cell, resumed_system.cell);
+}
+
+#[test]
+fn stages_checkpoint() {
+    let simulation_with_stage = || {
+        let mut simulation = monte_carlo();
+        let mut builder = MonteCarloBuilder::new(300.0);
+        builder.add(Box::new(Translate::new(1.0, None)), 1.0, 0.5);
+        let mut mc = builder.finish();
+        mc.set_amplitude_update_frequency(10);
+        simulation.add_stage(Box::new(mc), 100);
+        simulation.set_seed(42);
+        simulation
+    };
+
+    let mut system = utils::argon_lattice(3, 5.5);
+    let mut simulation = simulation_with_stage();
+    simulation.run(&mut system, 205);
+    let state = simulation.save_state();
+    assert_eq!(state.get::<u64>("mc.move.0.total_attempted"), Some(105));
+    assert_eq!(state.get::<u64>("stage.2.mc.move.0.total_attempted"), Some(100));
+
+    let mut resumed_system = system.clone();
+    let mut resumed = simulation_with_stage();
+    resumed.restore_state(&state);
+    let amplitude = state.get::<f64>("stage.2.mc.move.0.amplitude");
+    assert_eq!(resumed.save_state().get::<f64>("stage.2.mc.move.0.amplitude"), amplitude);
+
+    simulation.run(&mut system, 205);
+    resumed.run(&mut resumed_system, 205);
+    assert_eq!(system.particles().position, resumed_system.particles().position);
+}
+
+#[test]
+fn resumed_outputs() {
+    let tempfile = NamedTempFile::new().unwrap();
+    let mut system = utils::argon_lattice(3, 5.5);
+
+    let mut simulation = monte_carlo();
+    simulation.add_output(Box::new(EnergyOutput::new(tempfile.path()).unwrap()));
+    simulation.run(&mut system, 10);
+    let state = simulation.save_state();
+    drop(simulation);
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    assert_eq!(content.lines().count(), 2 + 10);
+
+    // Outputs add data to the existing files when resuming a simulation
+    let mut resumed = monte_carlo();
+    resumed.add_output(Box::new(EnergyOutput::new(tempfile.path()).unwrap()));
+    resumed.restore_state(&state);
+    resumed.run(&mut system, 10);
+    drop(resumed);
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    assert_eq!(content.lines().count(), 2 + 20);
+    assert_eq!(content.lines().filter(|line| line.starts_with('#')).count(), 2);
+
+    // And replace them when starting a new simulation
+    let mut simulation = monte_carlo();
+    simulation.add_output(Box::new(EnergyOutput::new(tempfile.path()).unwrap()));
+    simulation.run(&mut system, 10);
+    drop(simulation);
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    assert_eq!(content.lines().count(), 2 + 10);
+}
@@ -0,0 +1,54 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Isothermal compressibility of an ideal gas from the volume fluctuations in
+//! constant pressure Monte Carlo
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::mc::{MonteCarloBuilder, Resize};
+use lumol_sim::output::Compressibility;
+
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+const CELL: f64 = 26.0;
+
+fn ideal_gas() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    for i in 0..3 {
+        for j in 0..3 {
+            for k in 0..2 {
+                let position = Vector3D::new(i as f64, j as f64, k as f64) * CELL / 3.0;
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+    system
+}
+
+#[test]
+fn ideal_gas_compressibility() {
+    let mut system = ideal_gas();
+    let pressure = units::from(50.0, "bar").unwrap();
+
+    let mut builder = MonteCarloBuilder::new(units::from(300.0, "K").unwrap());
+    builder.add(Box::new(Resize::new(pressure, 3000.0)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+    simulation.run(&mut system, 5000);
+
+    let tempfile = NamedTempFile::new().unwrap();
+    let output = Compressibility::new(tempfile.path()).unwrap();
+    simulation.add_output_with_frequency(Box::new(output), 5);
+    simulation.run(&mut system, 200_000);
+
+    // The compressibility of an ideal gas is 1 / P
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let last = content.lines().last().unwrap();
+    let kappa = last.split_whitespace().nth(2).unwrap().parse::<f64>().unwrap();
+    let error = f64::abs(kappa * pressure - 1.0);
+    assert!(error < 0.05, "relative error on the compressibility is {}", error);
+}
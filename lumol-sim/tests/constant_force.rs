@@ -0,0 +1,61 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Steered molecular dynamics with a constant external force
+use lumol_core::{ConstantForce, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::MolecularDynamics;
+
+use approx::assert_relative_eq;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(30.0));
+    for i in 0..3 {
+        for j in 0..3 {
+            let position = Vector3D::new(i as f64 * 5.0, j as f64 * 5.0, 0.0);
+            system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+        }
+    }
+    system
+}
+
+#[test]
+fn constant_force() {
+    let mut system = testing_system();
+    let force = Vector3D::new(0.0, 0.0, units::from(10.0, "kJ/mol/A").unwrap());
+    let pulled = [0, 4];
+    system.add_global_potential(Box::new(ConstantForce::new(force, pulled.to_vec())));
+
+    let initial = system.particles().position.to_vec();
+    let initial_potential = system.potential_energy();
+    let initial_kinetic = system.kinetic_energy();
+
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(1.0)));
+    simulation.run(&mut system, 200);
+
+    let positions = system.particles().position;
+    let velocities = system.particles().velocity;
+    let mut work = 0.0;
+    for i in 0..system.size() {
+        let displacement = positions[i] - initial[i];
+        if pulled.contains(&i) {
+            // The pulled particles accelerate along the force
+            assert!(velocities[i][2] > 0.0);
+            assert!(displacement[2] > 0.0);
+            assert_eq!(displacement[0], 0.0);
+            assert_eq!(displacement[1], 0.0);
+            work += force * displacement;
+        } else {
+            assert_eq!(velocities[i], Vector3D::zero());
+            assert_eq!(displacement, Vector3D::zero());
+        }
+    }
+
+    // The work done by the external force matches the change in potential
+    // energy, and is converted to kinetic energy (up to the integration error)
+    assert!(work > 0.0);
+    assert_relative_eq!(system.potential_energy() - initial_potential, -work, max_relative = 1e-9);
+    assert_relative_eq!(system.kinetic_energy() - initial_kinetic, work, max_relative = 1e-4);
+}
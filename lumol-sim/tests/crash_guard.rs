@@ -0,0 +1,54 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Stopping molecular dynamics simulations when the forces explode
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{MolecularDynamics, VelocityVerlet};
+
+/// A pair of argon atoms at the given `distance`
+fn argon_pair(distance: f64) -> System {
+    let mut system = System::with_cell(UnitCell::cubic(20.0));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(distance, 0.0, 0.0))));
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 8.0)).unwrap();
+    system
+}
+
+fn simulation() -> Simulation {
+    let mut integrator = VelocityVerlet::new(units::from(1.0, "fs").unwrap());
+    integrator.set_max_displacement(0.1);
+    let md = MolecularDynamics::from_integrator(Box::new(integrator));
+    Simulation::new(Box::new(md))
+}
+
+#[test]
+fn stable_simulation() {
+    let mut system = argon_pair(3.8);
+    simulation().run(&mut system, 100);
+    for position in system.particles().position {
+        assert!(position.norm().is_finite());
+    }
+}
+
+#[test]
+fn exploding_forces() {
+    // The particles are nearly on top of each other, and the huge repulsive
+    // force sends them flying in the first steps
+    let mut system = argon_pair(0.1);
+    assert!(system.forces()[0].norm().is_finite());
+    let error = simulation().try_run(&mut system, 100).unwrap_err();
+    assert!(error.to_string().contains("more than the maximal displacement of 2 A"));
+
+    // The system is left as it was before the failing step
+    assert!(system.step < 100);
+    for position in system.particles().position {
+        assert!(position.norm().is_finite());
+    }
+}
@@ -0,0 +1,203 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Implementing a custom Monte Carlo move outside of lumol, using the public
+//! `EnergyCache` API.
+use std::cell::Cell;
+use std::rc::Rc;
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use lumol_core::{EnergyCache, System, Vector3D};
+
+use lumol_sim::mc::{MCDegreeOfFreedom, MCMove, MonteCarloBuilder};
+use lumol_sim::Propagator;
+
+use approx::assert_relative_eq;
+
+mod utils;
+
+/// Move a random molecule along the x axis, by a fixed amount
+struct ShiftX {
+    delta: f64,
+    molid: usize,
+    newpos: Vec<Vector3D>,
+}
+
+impl ShiftX {
+    fn new(delta: f64) -> ShiftX {
+        ShiftX {
+            delta,
+            molid: 0,
+            newpos: Vec::new(),
+        }
+    }
+}
+
+impl MCMove for ShiftX {
+    fn describe(&self) -> &str {
+        "shift along x"
+    }
+
+    fn setup(&mut self, _: &System) {}
+
+    fn degrees_of_freedom(&self) -> MCDegreeOfFreedom {
+        MCDegreeOfFreedom::AllMolecules
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut dyn RngCore) -> bool {
+        self.molid = rng.gen_range(0..system.molecules().count());
+        let sign = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+        let delta = Vector3D::new(sign * self.delta, 0.0, 0.0);
+        self.newpos = system.molecule(self.molid).particles().position.iter().map(|&r| r + delta).collect();
+        true
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        beta * cache.move_molecule_cost(system, self.molid, &self.newpos)
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        let mut molecule = system.molecule_mut(self.molid);
+        for (position, &newpos) in molecule.particles_mut().position.iter_mut().zip(&self.newpos) {
+            *position = newpos;
+        }
+    }
+
+    fn restore(&mut self, _: &mut System) {}
+
+    fn update_amplitude(&mut self, _: Option<f64>) {}
+}
+
+/// Wrap a `ShiftX` move, counting how many times the move was applied and
+/// restored
+struct Counting {
+    shift: ShiftX,
+    applied: Rc<Cell<u64>>,
+    restored: Rc<Cell<u64>>,
+}
+
+impl MCMove for Counting {
+    fn describe(&self) -> &str {
+        self.shift.describe()
+    }
+
+    fn setup(&mut self, system: &System) {
+        self.shift.setup(system);
+    }
+
+    fn degrees_of_freedom(&self) -> MCDegreeOfFreedom {
+        self.shift.degrees_of_freedom()
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut dyn RngCore) -> bool {
+        self.shift.prepare(system, rng)
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        self.shift.cost(system, beta, cache)
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        self.applied.set(self.applied.get() + 1);
+        self.shift.apply(system);
+    }
+
+    fn restore(&mut self, system: &mut System) {
+        self.restored.set(self.restored.get() + 1);
+        self.shift.restore(system);
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {}
+}
+
+#[test]
+fn cache_lifecycle() {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let mut rng = XorShiftRng::seed_from_u64(42);
+
+    let mut mcmove = ShiftX::new(0.3);
+    mcmove.setup(&system);
+
+    let mut cache = EnergyCache::new();
+    cache.init(&system);
+    assert_relative_eq!(cache.energy(), system.potential_energy(), max_relative = 1e-12);
+
+    for step in 0..50 {
+        assert!(mcmove.prepare(&mut system, &mut rng));
+        let old_energy = cache.energy();
+        let delta = mcmove.cost(&system, 1.0, &mut cache);
+
+        if step % 2 == 0 {
+            mcmove.apply(&mut system);
+            cache.update(&mut system);
+            assert_relative_eq!(cache.energy(), old_energy + delta, max_relative = 1e-9);
+        } else {
+            mcmove.restore(&mut system);
+            cache.reject();
+            assert_eq!(cache.energy(), old_energy);
+        }
+        assert_relative_eq!(cache.energy(), system.potential_energy(), max_relative = 1e-9);
+    }
+}
+
+#[test]
+fn custom_move_in_monte_carlo() {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let rng = Box::new(XorShiftRng::seed_from_u64(42));
+    let mut builder = MonteCarloBuilder::from_rng(300.0, rng);
+    builder.add(Box::new(ShiftX::new(0.3)), 1.0, None);
+    let mut mc = builder.finish();
+
+    mc.setup(&system);
+    let initial = system.molecule(0).particles().position[0];
+    for _ in 0..100 {
+        mc.propagate(&mut system);
+    }
+    mc.finish(&system);
+
+    // Only the x coordinate of the particles can change
+    let positions = system.particles().position;
+    assert!(positions.iter().any(|r| r[0] != 0.0 && r[0] != 4.0 && r[0] != 8.0));
+    assert_eq!(positions[0][1], initial[1]);
+    assert_eq!(positions[0][2], initial[2]);
+}
+
+#[test]
+fn acceptance_ratios() {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let rng = Box::new(XorShiftRng::seed_from_u64(42));
+    let mut builder = MonteCarloBuilder::from_rng(300.0, rng);
+
+    let mut counters = Vec::new();
+    for &delta in &[0.1, 3.0] {
+        let applied = Rc::new(Cell::new(0));
+        let restored = Rc::new(Cell::new(0));
+        let mcmove = Counting {
+            shift: ShiftX::new(delta),
+            applied: applied.clone(),
+            restored: restored.clone(),
+        };
+        builder.add(Box::new(mcmove), 1.0, None);
+        counters.push((applied, restored));
+    }
+    let mut mc = builder.finish();
+    assert_eq!(mc.acceptance_ratios(), vec![("shift along x", 0.0), ("shift along x", 0.0)]);
+
+    mc.setup(&system);
+    for _ in 0..2000 {
+        mc.propagate(&mut system);
+    }
+
+    let ratios = mc.acceptance_ratios();
+    assert_eq!(ratios.len(), 2);
+    for (&(_, ratio), (applied, restored)) in ratios.iter().zip(&counters) {
+        let attempted = applied.get() + restored.get();
+        assert!(attempted > 0);
+        assert_eq!(ratio, applied.get() as f64 / attempted as f64);
+    }
+
+    // Larger displacements are accepted less often
+    assert!(ratios[0].1 > ratios[1].1);
+}
@@ -0,0 +1,127 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Self-diffusion coefficient of an ideal gas following Langevin dynamics,
+//! for which D = kT / (m γ)
+use rand::SeedableRng;
+use rand_distr::{Distribution, StandardNormal};
+use rand_xorshift::XorShiftRng;
+use soa_derive::soa_zip;
+
+use lumol_core::{DegreesOfFreedom, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities};
+use lumol_sim::{Propagator, SeedSequence, Simulation, TemperatureStrategy};
+use lumol_sim::output::DiffusionCoefficient;
+
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+const CELL: f64 = 10.0;
+
+/// Langevin dynamics without interactions. The velocities follow an exact
+/// Ornstein-Uhlenbeck process, and the particles are wrapped in the cell
+/// after every step.
+struct Langevin {
+    timestep: f64,
+    temperature: f64,
+    friction: f64,
+    rng: XorShiftRng,
+}
+
+impl Langevin {
+    fn new(timestep: f64, temperature: f64, friction: f64) -> Langevin {
+        Langevin {
+            timestep: timestep,
+            temperature: temperature,
+            friction: friction,
+            rng: XorShiftRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Propagator for Langevin {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::External(self.temperature)
+    }
+
+    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+
+    fn seed(&mut self, seeds: &mut SeedSequence) {
+        self.rng = XorShiftRng::seed_from_u64(seeds.next_seed());
+    }
+
+    fn timestep(&self) -> Option<f64> {
+        Some(self.timestep)
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let damping = f64::exp(-self.friction * dt);
+        let cell = system.cell;
+        for (&mass, position, velocity) in soa_zip!(system.particles_mut(), [mass, mut position, mut velocity]) {
+            *position += 0.5 * dt * *velocity;
+
+            let sigma = f64::sqrt(K_BOLTZMANN * self.temperature / mass * (1.0 - damping * damping));
+            let noise = Vector3D::new(
+                StandardNormal.sample(&mut self.rng),
+                StandardNormal.sample(&mut self.rng),
+                StandardNormal.sample(&mut self.rng),
+            );
+            *velocity = damping * *velocity + sigma * noise;
+
+            *position += 0.5 * dt * *velocity;
+            cell.wrap_vector(position);
+        }
+    }
+}
+
+fn ideal_gas(temperature: f64) -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..10 {
+                let position = Vector3D::new(i as f64, j as f64, k as f64) * CELL / 10.0;
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+
+    let mut velocities = BoltzmannVelocities::new(temperature);
+    velocities.seed(129);
+    velocities.init(&mut system);
+    system
+}
+
+#[test]
+fn langevin_ideal_gas() {
+    let temperature = units::from(300.0, "K").unwrap();
+    let friction = 0.01 / units::from(1.0, "fs").unwrap();
+    let mut system = ideal_gas(temperature);
+
+    // Fit the MSD between 5 ps and 20 ps, much longer than the velocity
+    // relaxation time of 1 / γ = 100 fs
+    let tempfile = NamedTempFile::new().unwrap();
+    {
+        let propagator = Langevin::new(units::from(1.0, "fs").unwrap(), temperature, friction);
+        let mut simulation = Simulation::new(Box::new(propagator));
+        simulation.set_seed(42);
+        let output = DiffusionCoefficient::new(tempfile.path(), 150).unwrap();
+        simulation.add_output_with_frequency(Box::new(output), 100);
+        simulation.run(&mut system, 20_000);
+    }
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let last = content.lines().last().unwrap();
+    let diffusion = last.split_whitespace().nth(3).unwrap().parse::<f64>().unwrap();
+
+    let mass = system.particles().mass[0];
+    let expected = K_BOLTZMANN * temperature / (mass * friction);
+    let error = f64::abs(diffusion / expected - 1.0);
+    assert!(error < 0.1, "relative error on the diffusion coefficient is {}", error);
+}
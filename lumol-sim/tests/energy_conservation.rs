@@ -0,0 +1,58 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Monitoring the energy conservation in constant energy simulations
+use lumol_core::System;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{MolecularDynamics, VelocityVerlet};
+use lumol_sim::output::EnergyConservation;
+
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+mod utils;
+
+fn testing_system() -> System {
+    let mut system = utils::shifted_argon_lattice(4, 7.0);
+    utils::init_velocities(&mut system);
+    system
+}
+
+/// Run a NVE simulation with the given `timestep`, and get the values of the
+/// drift written by the `EnergyConservation` output.
+fn energy_drift(timestep: f64) -> Vec<f64> {
+    let mut system = testing_system();
+    let tempfile = NamedTempFile::new().unwrap();
+    {
+        let md = MolecularDynamics::from_integrator(Box::new(VelocityVerlet::new(timestep)));
+        let mut simulation = Simulation::new(Box::new(md));
+        let mut output = EnergyConservation::new(tempfile.path(), 50).unwrap();
+        output.set_threshold(1e-3);
+        simulation.add_output_with_frequency(Box::new(output), 10);
+        simulation.run(&mut system, 1000);
+    }
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let drifts = content.lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split_whitespace().nth(1).unwrap().parse::<f64>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(drifts.len(), 100);
+    drifts
+}
+
+fn max_drift(drifts: &[f64]) -> f64 {
+    drifts.iter().fold(0.0, |max, drift| f64::max(max, drift.abs()))
+}
+
+#[test]
+fn nve_drift() {
+    let drifts = energy_drift(1.0);
+    assert!(max_drift(&drifts) < 1e-4);
+
+    // Using a much larger timestep breaks the energy conservation
+    let drifts = energy_drift(20.0);
+    assert!(max_drift(&drifts) > 1e-3);
+}
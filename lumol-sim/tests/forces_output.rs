@@ -0,0 +1,78 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Checking that the forces written during a simulation are consistent with
+//! the positions written in the same frame
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, Simulation};
+use lumol_sim::md::{MolecularDynamics, VelocityVerlet};
+use lumol_sim::output::ForcesOutput;
+
+use approx::assert_relative_eq;
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(16.0));
+    for i in 0..3 {
+        for j in 0..3 {
+            for k in 0..3 {
+                let position = Vector3D::new(i as f64 * 4.5, j as f64 * 4.5, k as f64 * 4.5);
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::shifted(lj, 7.0)).unwrap();
+
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.seed(42);
+    velocities.init(&mut system);
+    system
+}
+
+#[test]
+fn forces_match_positions() {
+    let mut system = testing_system();
+
+    let tempfile = NamedTempFile::new().unwrap();
+    {
+        let md = MolecularDynamics::from_integrator(Box::new(VelocityVerlet::new(1.0)));
+        let mut simulation = Simulation::new(Box::new(md));
+        let mut output = ForcesOutput::new(tempfile.path()).unwrap();
+        output.set_positions(true);
+        simulation.add_output_with_frequency(Box::new(output), 10);
+        simulation.run(&mut system, 30);
+    }
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    let natoms = system.size();
+    assert_eq!(lines.len(), 3 * (natoms + 2));
+
+    // The last frame was written with the final state of the system
+    let frame = &lines[2 * (natoms + 2)..];
+    assert_eq!(frame[0], natoms.to_string());
+    assert_eq!(frame[1], "positions in A and forces in kJ/mol/A at step 30");
+
+    let forces = system.forces();
+    let positions = system.particles().position;
+    for (i, line) in frame[2..].iter().enumerate() {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(values[0], "Ar");
+        let values = values[1..].iter().map(|value| value.parse::<f64>().unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(values[..3], positions[i][..]);
+
+        let conversion = units::to(1.0, "kJ/mol/A").unwrap();
+        for (&value, &force) in values[3..].iter().zip(forces[i].iter()) {
+            assert_relative_eq!(value, conversion * force, epsilon = 1e-12, max_relative = 1e-12);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Molecular dynamics with frozen particles
+use lumol_core::{DegreesOfFreedom, LennardJones, PairInteraction, System, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{BerendsenThermostat, MolecularDynamics, RemoveTranslation};
+
+mod utils;
+
+fn testing_system() -> System {
+    let mut system = utils::argon_lattice(3, 5.5);
+    utils::init_velocities(&mut system);
+    system
+}
+
+#[test]
+fn frozen_particles() {
+    let mut system = testing_system();
+    let frozen = [0, 4, 13, 26];
+    let initial = system.particles().position.to_vec();
+
+    let mut md = MolecularDynamics::new(1.0);
+    md.freeze(&frozen);
+    md.set_thermostat(Box::new(BerendsenThermostat::new(300.0, 10.0)));
+    md.add_control(Box::new(RemoveTranslation));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut system, 100);
+
+    assert_eq!(system.simulated_degrees_of_freedom, DegreesOfFreedom::Frozen(12));
+    assert_eq!(system.degrees_of_freedom(), 3 * 27 - 12);
+
+    let positions = system.particles().position;
+    let velocities = system.particles().velocity;
+    for i in 0..system.size() {
+        if frozen.contains(&i) {
+            assert_eq!(positions[i], initial[i]);
+            assert_eq!(velocities[i], Vector3D::zero());
+        } else {
+            assert_ne!(positions[i], initial[i]);
+        }
+    }
+
+    let expected = 2.0 * system.kinetic_energy() / (K_BOLTZMANN * (3.0 * 27.0 - 12.0));
+    assert_eq!(system.temperature(), expected);
+}
+
+#[test]
+fn energy_conservation() {
+    let mut system = testing_system();
+    // Use a shifted potential to prevent energy jumps at the cutoff
+    let lj = Box::new(LennardJones { sigma: 3.4, epsilon: 1e-3 });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::shifted(lj, 5.5)).unwrap();
+    let mut md = MolecularDynamics::new(1.0);
+    md.freeze(&[0, 4, 13, 26]);
+    let mut simulation = Simulation::new(Box::new(md));
+
+    // The kinetic energy of the frozen particles is removed in the first step
+    simulation.run(&mut system, 1);
+    let initial = system.total_energy();
+
+    simulation.run(&mut system, 1000);
+    let energy = system.total_energy();
+    assert!(
+        f64::abs((energy - initial) / initial) < 1e-3,
+        "energy changed from {} to {}", initial, energy
+    );
+}
+
+#[test]
+#[should_panic(expected = "can not freeze particle 30, the system only contains 27 particles")]
+fn out_of_bounds() {
+    let mut system = testing_system();
+    let mut md = MolecularDynamics::new(1.0);
+    md.freeze(&[3, 30]);
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut system, 1);
+}
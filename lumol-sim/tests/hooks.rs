@@ -0,0 +1,69 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Calling user-provided functions during a simulation
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, Simulation};
+use lumol_sim::md::MolecularDynamics;
+use lumol_sim::output::Output;
+
+use std::sync::{Arc, Mutex};
+
+use approx::assert_relative_eq;
+
+// An ideal gas system, where the temperature is constant in MD
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(20.0));
+    for i in 0..5 {
+        for j in 0..5 {
+            for k in 0..5 {
+                let position = Vector3D::new(i as f64 * 4.0, j as f64 * 4.0, k as f64 * 4.0);
+                system.add_molecule(Molecule::new(Particle::with_position("He", position)));
+            }
+        }
+    }
+
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.init(&mut system);
+    system
+}
+
+/// Record the temperature of the system at each step
+struct TemperatureTrace(Arc<Mutex<Vec<(u64, f64)>>>);
+
+impl Output for TemperatureTrace {
+    fn write(&mut self, system: &System) {
+        self.0.lock().unwrap().push((system.step, system.temperature()));
+    }
+}
+
+#[test]
+fn rescale_velocities() {
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(1.0)));
+
+    let trace = Arc::new(Mutex::new(Vec::new()));
+    simulation.add_output(Box::new(TemperatureTrace(Arc::clone(&trace))));
+
+    let calls = Arc::new(Mutex::new(0));
+    let hook_calls = Arc::clone(&calls);
+    simulation.add_hook(100, move |system: &mut System| {
+        for velocity in system.particles_mut().velocity {
+            *velocity *= f64::sqrt(0.5);
+        }
+        *hook_calls.lock().unwrap() += 1;
+    });
+
+    simulation.run(&mut system, 350);
+    assert_eq!(*calls.lock().unwrap(), 3);
+
+    // Hooks are called before the outputs, so the temperature is halved
+    // starting at step 100, 200 and 300.
+    let trace = trace.lock().unwrap();
+    assert_eq!(trace.len(), 350);
+    for &(step, temperature) in trace.iter() {
+        let expected = 300.0 / f64::powi(2.0, (step / 100) as i32);
+        assert_relative_eq!(temperature, expected, max_relative = 1e-9);
+    }
+}
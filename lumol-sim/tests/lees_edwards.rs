@@ -0,0 +1,100 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Molecular dynamics with Lees-Edwards boundary conditions
+use lumol_core::{LennardJones, Molecule, PairInteraction, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{MolecularDynamics, Rewrap, VelocityVerlet};
+
+use approx::assert_relative_eq;
+
+const SHEAR_RATE: f64 = 2e-3;
+
+fn testing_system() -> System {
+    let mut cell = UnitCell::cubic(10.0);
+    cell.set_shear_rate(SHEAR_RATE);
+    let mut system = System::with_cell(cell);
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(2.0, 9.0, 5.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(2.0, 1.0, 5.0))));
+    // The first particle crosses the boundary in the y direction
+    system.particles_mut().velocity[0] = Vector3D::new(0.0, 0.02, 0.0);
+    system
+}
+
+#[test]
+fn shear_offset() {
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(1.0)));
+    simulation.run(&mut system, 100);
+    assert_relative_eq!(system.cell.shear_offset(), SHEAR_RATE * 10.0 * 100.0, max_relative = 1e-12);
+}
+
+/// Two overlapping particles, strongly pushed apart in the first steps
+fn overlapping_pair() -> System {
+    let mut cell = UnitCell::cubic(10.0);
+    cell.set_shear_rate(SHEAR_RATE);
+    let mut system = System::with_cell(cell);
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(5.0, 4.0, 5.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(5.0, 6.0, 5.0))));
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 5.0)).unwrap();
+    system
+}
+
+#[test]
+fn shear_offset_adaptive_timestep() {
+    let mut system = overlapping_pair();
+    let mut integrator = VelocityVerlet::new(1.0);
+    integrator.set_adaptive_timestep(0.01, 1.0, units::from(50.0, "kJ/mol/A").unwrap());
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::from_integrator(Box::new(integrator))));
+    simulation.run(&mut system, 100);
+
+    // The timestep was reduced in the first steps, and the offset follows
+    // the timesteps actually used by the integrator
+    assert!(system.time < 99.0);
+    assert_relative_eq!(system.cell.shear_offset(), SHEAR_RATE * 10.0 * system.time, max_relative = 1e-12);
+}
+
+#[test]
+fn shear_offset_failed_step() {
+    let mut system = testing_system();
+    system.particles_mut().velocity[1] = Vector3D::new(100.0, 0.0, 0.0);
+    let mut integrator = VelocityVerlet::new(1.0);
+    integrator.set_max_displacement(0.1);
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::from_integrator(Box::new(integrator))));
+    assert!(simulation.try_run(&mut system, 10).is_err());
+
+    // The failed step did not move the periodic images
+    assert_eq!(system.time, 0.0);
+    assert_eq!(system.cell.shear_offset(), 0.0);
+}
+
+#[test]
+fn crossing_the_sheared_boundary() {
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(1.0)));
+    simulation.run(&mut system, 150);
+
+    let mut wrapped = testing_system();
+    let mut md = MolecularDynamics::new(1.0);
+    md.add_control(Box::new(Rewrap));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut wrapped, 150);
+
+    // The wrapped particle is displaced by the offset along x, and its
+    // velocity is shifted by the relative velocity of the images
+    let offset = system.cell.shear_offset();
+    let expected = system.particles().position[0] - Vector3D::new(offset, 10.0, 0.0) + Vector3D::new(10.0, 0.0, 0.0);
+    assert!((wrapped.particles().position[0] - expected).norm() < 1e-12);
+    let velocity = Vector3D::new(-SHEAR_RATE * 10.0, 0.02, 0.0);
+    assert!((wrapped.particles().velocity[0] - velocity).norm() < 1e-12);
+
+    // Both representations describe the same physical configuration
+    assert_relative_eq!(wrapped.distance(0, 1), system.distance(0, 1), epsilon = 1e-12);
+    assert!((wrapped.nearest_image(0, 1) - system.nearest_image(0, 1)).norm() < 1e-12);
+}
@@ -0,0 +1,61 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Checking the conservation of the linear momentum in constant energy
+//! simulations
+use lumol_core::{System, Vector3D};
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{MolecularDynamics, VelocityVerlet};
+use lumol_sim::output::MomentumOutput;
+
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+mod utils;
+
+fn testing_system() -> System {
+    let mut system = utils::shifted_argon_lattice(4, 7.0);
+    utils::init_velocities(&mut system);
+    system
+}
+
+#[test]
+fn linear_momentum_conservation() {
+    let mut system = testing_system();
+    let initial = system.particles().mass.iter().zip(system.particles().velocity)
+        .map(|(&mass, &velocity)| mass * velocity)
+        .fold(Vector3D::zero(), |sum, momentum| sum + momentum);
+    let scale: f64 = system.particles().mass.iter().zip(system.particles().velocity)
+        .map(|(mass, velocity)| mass * velocity.norm())
+        .sum();
+
+    let tempfile = NamedTempFile::new().unwrap();
+    {
+        let md = MolecularDynamics::from_integrator(Box::new(VelocityVerlet::new(1.0)));
+        let mut simulation = Simulation::new(Box::new(md));
+        simulation.add_output_with_frequency(Box::new(MomentumOutput::new(tempfile.path()).unwrap()), 10);
+        simulation.run(&mut system, 1000);
+    }
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let momenta = content.lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| {
+            let values = line.split_whitespace()
+                .skip(1)
+                .map(|value| value.parse::<f64>().unwrap())
+                .collect::<Vec<_>>();
+            Vector3D::new(values[0], values[1], values[2])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(momenta.len(), 100);
+
+    // The pair forces do not change the total momentum, up to floating point
+    // rounding errors
+    for momentum in momenta {
+        let drift = (momentum - initial).norm();
+        assert!(drift < 1e-12 * scale, "linear momentum drifted by {}", drift);
+    }
+}
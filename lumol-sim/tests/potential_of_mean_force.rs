@@ -0,0 +1,86 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Potential of mean force of a dilute Lennard-Jones gas, which should match
+//! the pair potential
+use lumol_core::{LennardJones, PairInteraction, Potential};
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::compute::{Compute, PotentialOfMeanForce, Rdf};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+
+const CELL: f64 = 40.0;
+
+fn lennard_jones() -> LennardJones {
+    LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    }
+}
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    for i in 0..3 {
+        for j in 0..3 {
+            for k in 0..4 {
+                let position = Vector3D::new(i as f64 / 3.0, j as f64 / 3.0, k as f64 / 4.0) * CELL;
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+
+    let lj = Box::new(lennard_jones());
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 15.0)).unwrap();
+    system
+}
+
+#[test]
+fn dilute_lennard_jones() {
+    let mut system = testing_system();
+    let temperature = units::from(300.0, "K").unwrap();
+    let kt = K_BOLTZMANN * temperature;
+
+    let mut builder = MonteCarloBuilder::new(temperature);
+    builder.add(Box::new(Translate::new(units::from(10.0, "A").unwrap(), None)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+    simulation.run(&mut system, 1000);
+
+    let kind = system.particles().kind[0];
+    let rdf = Rdf { kinds: (kind, kind), max: 10.0, bins: 40 };
+    let mut average = vec![0.0; rdf.bins];
+    let samples = 20_000;
+    let sweep = system.size();
+    for _ in 0..samples {
+        simulation.run(&mut system, sweep);
+        for (mean, value) in average.iter_mut().zip(rdf.compute(&system)) {
+            *mean += value / samples as f64;
+        }
+    }
+    let pmf = PotentialOfMeanForce::from_rdf(&average, temperature);
+
+    // At low density, g(r) = exp(-u(r) / kT) and the potential of mean force
+    // is the pair potential. The reference value is averaged over each bin,
+    // and only the bins outside of the repulsive core, where enough pairs are
+    // sampled, are checked.
+    let lj = lennard_jones();
+    let dr = rdf.max / rdf.bins as f64;
+    for (i, &w) in pmf.iter().enumerate() {
+        let (mut boltzmann, mut shell) = (0.0, 0.0);
+        for j in 0..100 {
+            let r = (i as f64 + (j as f64 + 0.5) / 100.0) * dr;
+            boltzmann += r * r * f64::exp(-lj.energy(r) / kt);
+            shell += r * r;
+        }
+        let expected = -kt * f64::ln(boltzmann / shell);
+        if expected < 2.0 * kt {
+            assert!(
+                f64::abs(w - expected) < 0.1 * kt,
+                "potential of mean force at r = {}: {} != {}", (i as f64 + 0.5) * dr, w / kt, expected / kt
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Multiple time step integration
+use lumol_core::{Harmonic, LennardJones, Molecule, PairInteraction, PairRestriction};
+use lumol_core::{Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, Simulation};
+use lumol_sim::md::{MolecularDynamics, Respa};
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(10.0));
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let position = Vector3D::new(i as f64 * 5.0, j as f64 * 5.0, k as f64 * 5.0);
+                let mut molecule = Molecule::new(Particle::with_position("O", position));
+                let mut hydrogen = Particle::with_position("H", position + Vector3D::new(1.0, 0.0, 0.0));
+                molecule.add_particle_bonded_to(0, hydrogen.clone());
+                hydrogen.position = position + Vector3D::new(-0.3, 0.95, 0.0);
+                molecule.add_particle_bonded_to(0, hydrogen);
+                system.add_molecule(molecule);
+            }
+        }
+    }
+
+    let mut lj = PairInteraction::new(Box::new(LennardJones { sigma: 3.2, epsilon: 1e-2 }), 4.5);
+    lj.set_restriction(PairRestriction::InterMolecular);
+    system.set_pair_potential(("O", "O"), lj).unwrap();
+    system.set_bond_potential(("O", "H"), Box::new(Harmonic {
+        k: units::from(1000.0, "kJ/mol/A^2").unwrap(),
+        x0: 1.0,
+    }));
+    system.set_angle_potential(("H", "O", "H"), Box::new(Harmonic {
+        k: units::from(100.0, "kJ/mol/rad^2").unwrap(),
+        x0: units::from(109.5, "deg").unwrap(),
+    }));
+
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.seed(42);
+    velocities.init(&mut system);
+    system
+}
+
+/// Reference velocity-Verlet integration, using the forces at the initial
+/// positions for the first step.
+fn velocity_verlet(system: &mut System, timestep: f64, nsteps: usize) {
+    let masses = system.particles().mass.to_vec();
+    let mut forces = system.forces();
+    for _ in 0..nsteps {
+        let particles = system.particles_mut();
+        for i in 0..masses.len() {
+            particles.velocity[i] += 0.5 * timestep * forces[i] / masses[i];
+            let velocity = particles.velocity[i];
+            particles.position[i] += timestep * velocity;
+        }
+
+        forces = system.forces();
+        let particles = system.particles_mut();
+        for i in 0..masses.len() {
+            particles.velocity[i] += 0.5 * timestep * forces[i] / masses[i];
+        }
+    }
+}
+
+#[test]
+fn single_inner_step_is_velocity_verlet() {
+    let timestep = units::from(0.5, "fs").unwrap();
+    let mut expected = testing_system();
+    velocity_verlet(&mut expected, timestep, 200);
+
+    let mut system = testing_system();
+    let md = MolecularDynamics::from_integrator(Box::new(Respa::new(timestep, 1)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut system, 200);
+
+    let positions = system.particles().position;
+    let velocities = system.particles().velocity;
+    for i in 0..system.size() {
+        assert!((positions[i] - expected.particles().position[i]).norm() < 1e-8);
+        assert!((velocities[i] - expected.particles().velocity[i]).norm() < 1e-10);
+    }
+}
+
+#[test]
+#[should_panic(expected = "the number of inner steps must be positive in RESPA integrator")]
+fn zero_inner_steps() {
+    let _ = Respa::new(1.0, 0);
+}
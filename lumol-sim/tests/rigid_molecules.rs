@@ -0,0 +1,118 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Molecular dynamics with some molecules kept rigid by distance constraints
+use lumol_core::{DegreesOfFreedom, Harmonic, LennardJones, Molecule, PairInteraction, PairRestriction};
+use lumol_core::{Matrix3, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, Simulation};
+use lumol_sim::md::MolecularDynamics;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(10.0));
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let position = Vector3D::new(i as f64 * 5.0, j as f64 * 5.0, k as f64 * 5.0);
+                let mut molecule = Molecule::new(Particle::with_position("O", position));
+                let mut hydrogen = Particle::with_position("H", position + Vector3D::new(1.0, 0.0, 0.0));
+                molecule.add_particle_bonded_to(0, hydrogen.clone());
+                hydrogen.position = position + Vector3D::new(-0.3, 0.95, 0.0);
+                molecule.add_particle_bonded_to(0, hydrogen);
+                system.add_molecule(molecule);
+            }
+        }
+    }
+
+    let mut lj = PairInteraction::new(Box::new(LennardJones { sigma: 3.2, epsilon: 1e-2 }), 4.5);
+    lj.set_restriction(PairRestriction::InterMolecular);
+    system.set_pair_potential(("O", "O"), lj).unwrap();
+    system.set_bond_potential(("O", "H"), Box::new(Harmonic {
+        k: units::from(1000.0, "kJ/mol/A^2").unwrap(),
+        x0: 0.96,
+    }));
+    system.set_angle_potential(("H", "O", "H"), Box::new(Harmonic {
+        k: units::from(100.0, "kJ/mol/rad^2").unwrap(),
+        x0: units::from(104.5, "deg").unwrap(),
+    }));
+
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.seed(42);
+    velocities.init(&mut system);
+    system
+}
+
+/// Get the distances between all the particles in the molecule `molid`
+fn internal_distances(system: &System, molid: usize) -> Vec<f64> {
+    let molecule = system.molecule(molid);
+    let mut distances = Vec::new();
+    for i in molecule.indexes() {
+        for j in molecule.indexes().filter(|&j| j > i) {
+            distances.push(system.distance(i, j));
+        }
+    }
+    return distances;
+}
+
+#[test]
+fn rigid_molecules() {
+    let mut system = testing_system();
+    let initial = (0..8).map(|molid| internal_distances(&system, molid)).collect::<Vec<_>>();
+
+    let mut md = MolecularDynamics::new(units::from(0.5, "fs").unwrap());
+    md.set_rigid(&[0, 5]);
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut system, 500);
+
+    // Each rigid water molecule only keeps 6 degrees of freedom
+    assert_eq!(system.simulated_degrees_of_freedom, DegreesOfFreedom::Frozen(6));
+    assert_eq!(system.degrees_of_freedom(), 3 * 24 - 6);
+
+    for (molid, initial) in initial.iter().enumerate() {
+        let largest_change = internal_distances(&system, molid).iter().zip(initial)
+            .map(|(distance, initial)| f64::abs(distance - initial) / initial)
+            .fold(0.0, f64::max);
+        if molid == 0 || molid == 5 {
+            assert!(largest_change < 1e-8, "distances changed by {} in rigid molecule {}", largest_change, molid);
+        } else {
+            assert!(largest_change > 1e-3, "distances did not change in flexible molecule {}", molid);
+        }
+    }
+}
+
+#[test]
+fn energy_conservation() {
+    let mut system = testing_system();
+    let lj = Box::new(LennardJones {
+        sigma: 3.2,
+        epsilon: units::from(0.65, "kJ/mol").unwrap(),
+    });
+    let mut lj = PairInteraction::shifted(lj, 4.5);
+    lj.set_restriction(PairRestriction::InterMolecular);
+    system.set_pair_potential(("O", "O"), lj).unwrap();
+
+    let mut md = MolecularDynamics::new(units::from(0.5, "fs").unwrap());
+    md.set_rigid(&[0, 2, 5, 7]);
+    let mut simulation = Simulation::new(Box::new(md));
+
+    // The initial velocities do not respect the constraints, the first step
+    // removes the corresponding kinetic energy
+    simulation.run(&mut system, 1);
+    let mut energies = Vec::new();
+    for _ in 0..100 {
+        simulation.run(&mut system, 10);
+        energies.push(system.total_energy());
+    }
+
+    let initial = energies[..20].iter().sum::<f64>() / 20.0;
+    let last = energies[80..].iter().sum::<f64>() / 20.0;
+    let drift = f64::abs((last - initial) / initial);
+    assert!(drift < 2e-3, "energy drift is {}", drift);
+
+    let fluctuations = energies.iter().map(|energy| f64::abs((energy - initial) / initial)).fold(0.0, f64::max);
+    assert!(fluctuations < 2e-2, "energy fluctuations are {}", fluctuations);
+
+    // The constraint forces contribute to the virial
+    assert_ne!(system.constraints_virial, Matrix3::zero());
+}
@@ -0,0 +1,47 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Reproducibility of simulations using a master seed
+use lumol_core::Vector3D;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, SeedSequence, Simulation};
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+use lumol_sim::md::{CSVRThermostat, MolecularDynamics};
+
+mod utils;
+
+fn monte_carlo(seed: u64) -> Vec<Vector3D> {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let mut builder = MonteCarloBuilder::new(300.0);
+    builder.add(Box::new(Translate::new(0.5, None)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(seed);
+    simulation.run(&mut system, 200);
+    system.particles().position.to_vec()
+}
+
+fn molecular_dynamics(seed: u64) -> Vec<Vector3D> {
+    let mut system = utils::argon_lattice(3, 5.5);
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.seed(SeedSequence::velocities(seed));
+    velocities.init(&mut system);
+
+    let mut md = MolecularDynamics::new(1.0);
+    md.set_thermostat(Box::new(CSVRThermostat::new(300.0, 10.0)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.set_seed(seed);
+    simulation.run(&mut system, 100);
+    system.particles().velocity.to_vec()
+}
+
+#[test]
+fn monte_carlo_seed() {
+    assert_eq!(monte_carlo(42), monte_carlo(42));
+    assert_ne!(monte_carlo(42), monte_carlo(43));
+}
+
+#[test]
+fn molecular_dynamics_seed() {
+    assert_eq!(molecular_dynamics(42), molecular_dynamics(42));
+    assert_ne!(molecular_dynamics(42), molecular_dynamics(43));
+}
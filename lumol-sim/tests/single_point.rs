@@ -0,0 +1,58 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Evaluating the energy of a system with a single point simulation
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::{SinglePoint, Simulation};
+use lumol_sim::output::EnergyOutput;
+
+use approx::assert_relative_eq;
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(12.0));
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let position = Vector3D::new(i as f64 * 3.7, j as f64 * 4.1, k as f64 * 3.9);
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 5.5)).unwrap();
+    system
+}
+
+#[test]
+fn single_point() {
+    let mut system = testing_system();
+    let initial = system.particles().position.to_vec();
+
+    let tempfile = NamedTempFile::new().unwrap();
+    {
+        let mut simulation = Simulation::new(Box::new(SinglePoint::new()));
+        simulation.add_output(Box::new(EnergyOutput::new(tempfile.path()).unwrap()));
+        simulation.run(&mut system, 1);
+    }
+
+    assert_eq!(system.particles().position, &initial[..]);
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    let lines = content.lines().filter(|line| !line.starts_with('#')).collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+
+    let values = lines[0].split_whitespace().collect::<Vec<_>>();
+    assert_eq!(values[0], "1");
+    let potential = values[1].parse::<f64>().unwrap();
+    let expected = units::to(system.potential_energy(), "kJ/mol").unwrap();
+    assert!(expected < 0.0);
+    assert_relative_eq!(potential, expected, max_relative = 1e-12);
+}
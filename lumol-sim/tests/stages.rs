@@ -0,0 +1,65 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Running multiple propagators in sequence in a single simulation
+use lumol_core::{System, Vector3D};
+
+use lumol_sim::Simulation;
+use lumol_sim::md::MolecularDynamics;
+use lumol_sim::min::{Minimization, SteepestDescent, Tolerance};
+
+mod utils;
+
+fn testing_system() -> System {
+    let mut system = utils::shifted_argon_lattice(3, 5.5);
+    // Start from a distorted lattice with a high potential energy
+    for position in system.particles_mut().position {
+        let site = f64::round((position[0] + position[1] + position[2]) / 4.0) as usize;
+        position[0] += if site % 2 == 0 { 0.6 } else { -0.6 };
+    }
+    system
+}
+
+fn minimization() -> Box<Minimization> {
+    let tolerance = Tolerance { energy: 1e-12, force2: 1e-12 };
+    Box::new(Minimization::new(Box::new(SteepestDescent::new()), tolerance))
+}
+
+#[test]
+fn minimization_then_md() {
+    let mut system = testing_system();
+    let initial_energy = system.total_energy();
+
+    // Minimization only
+    let mut minimized = system.clone();
+    let mut simulation = Simulation::new(minimization());
+    simulation.run(&mut minimized, 100);
+    assert!(minimized.potential_energy() < initial_energy);
+    assert_eq!(minimized.particles().velocity, vec![Vector3D::zero(); 27]);
+
+    // Minimization followed by molecular dynamics
+    let mut simulation = Simulation::new(minimization());
+    simulation.add_stage(Box::new(MolecularDynamics::new(1.0)), 200);
+    // The number of steps includes all the stages
+    simulation.run(&mut system, 300);
+    assert_eq!(system.step, 300);
+
+    // The system evolved with molecular dynamics after the minimization
+    assert_ne!(system.particles().position, minimized.particles().position);
+    assert!(system.particles().velocity.iter().any(|velocity| velocity.norm() > 0.0));
+
+    // The energy is conserved during the molecular dynamics stage, and stays
+    // lower than the initial energy
+    let energy = system.total_energy();
+    assert!(energy < initial_energy);
+    assert!(f64::abs((energy - minimized.total_energy()) / energy) < 1e-3);
+}
+
+#[test]
+#[should_panic(expected = "can not run 100 steps, the simulation stages already contain 200 steps")]
+fn too_few_steps() {
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(minimization());
+    simulation.add_stage(Box::new(MolecularDynamics::new(1.0)), 200);
+    simulation.run(&mut system, 100);
+}
@@ -0,0 +1,76 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Equipartition of the kinetic energy between translation and rotation of
+//! diatomic molecules
+use lumol_core::{Harmonic, LennardJones, PairInteraction, PairRestriction};
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities, Simulation};
+use lumol_sim::md::{CSVRThermostat, MolecularDynamics};
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(20.0));
+    for i in 0..5 {
+        for j in 0..5 {
+            for k in 0..5 {
+                let position = Vector3D::new(i as f64 * 4.0, j as f64 * 4.0, k as f64 * 4.0);
+                let mut molecule = Molecule::new(Particle::with_position("N", position));
+                let position = position + Vector3D::new(0.635, 0.635, 0.635);
+                molecule.add_particle_bonded_to(0, Particle::with_position("N", position));
+                system.add_molecule(molecule);
+            }
+        }
+    }
+
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.3, "A").unwrap(),
+        epsilon: units::from(0.3, "kJ/mol").unwrap(),
+    });
+    let mut interaction = PairInteraction::new(lj, units::from(10.0, "A").unwrap());
+    interaction.set_restriction(PairRestriction::InterMolecular);
+    system.set_pair_potential(("N", "N"), interaction).unwrap();
+
+    // Use a soft bond, so that the vibrations exchange energy quickly with the
+    // translation and rotation of the molecules: the thermostat rescales all
+    // the velocities at once and does not help with this exchange.
+    let bond = Box::new(Harmonic {
+        k: units::from(100.0, "kJ/mol/A^2").unwrap(),
+        x0: units::from(1.1, "A").unwrap(),
+    });
+    system.set_bond_potential(("N", "N"), bond);
+
+    let mut velocities = BoltzmannVelocities::new(units::from(300.0, "K").unwrap());
+    velocities.seed(42);
+    velocities.init(&mut system);
+    system
+}
+
+#[test]
+fn diatomic_equipartition() {
+    let mut system = testing_system();
+
+    let mut md = MolecularDynamics::new(units::from(1.0, "fs").unwrap());
+    md.set_thermostat(Box::new(CSVRThermostat::new(units::from(300.0, "K").unwrap(), 100.0)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.set_seed(42);
+
+    // Equilibration
+    simulation.run(&mut system, 2000);
+
+    let mut translational = 0.0;
+    let mut rotational = 0.0;
+    let n = 1000;
+    for _ in 0..n {
+        simulation.run(&mut system, 10);
+        let components = system.temperature_components();
+        translational += components.translational;
+        rotational += components.rotational;
+    }
+    translational /= n as f64;
+    rotational /= n as f64;
+
+    assert!(f64::abs(translational - 300.0) / 300.0 < 5e-2, "translational temperature is {}", translational);
+    assert!(f64::abs(rotational - 300.0) / 300.0 < 5e-2, "rotational temperature is {}", rotational);
+}
@@ -0,0 +1,155 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Free energy of coupling a Lennard-Jones particle to another one using
+//! thermodynamic integration
+use lumol_core::{LennardJones, PairInteraction, Potential, SoftCoreLennardJones};
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
+
+use lumol_sim::{Simulation, ThermodynamicIntegration};
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+use lumol_sim::output::Output;
+
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+const CELL: f64 = 8.0;
+const CUTOFF: f64 = 4.0;
+const TEMPERATURE: f64 = 300.0;
+
+fn lennard_jones() -> LennardJones {
+    LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(2.0, "kJ/mol").unwrap(),
+    }
+}
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(4.0, 4.0, 4.0))));
+
+    let lj = lennard_jones();
+    let soft_core = SoftCoreLennardJones::new(lj.sigma, lj.epsilon, 0.5);
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(soft_core), CUTOFF)).unwrap();
+    system
+}
+
+/// Free energy of inserting the second particle in the cell containing the
+/// first one, from the integral of the Boltzmann factor over the relative
+/// position of the particles.
+fn insertion_free_energy() -> f64 {
+    let lj = lennard_jones();
+    let beta = 1.0 / (K_BOLTZMANN * TEMPERATURE);
+    let volume = CELL * CELL * CELL;
+
+    let n = 100_000;
+    let dr = CUTOFF / n as f64;
+    let mut excluded = 0.0;
+    for i in 0..n {
+        let r = (i as f64 + 0.5) * dr;
+        excluded += 4.0 * PI * r * r * (f64::exp(-beta * lj.energy(r)) - 1.0) * dr;
+    }
+    -f64::ln(1.0 + excluded / volume) / beta
+}
+
+#[test]
+fn decoupling_lennard_jones() {
+    let mut system = testing_system();
+
+    // Moves of the size of the cell sample uniformly the relative position
+    // of the two particles
+    let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+    builder.add(Box::new(Translate::new(CELL, None)), 1.0, 0.5);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+
+    let lambdas = (0..11).map(|i| i as f64 / 10.0).collect();
+    let mut integration = ThermodynamicIntegration::new(lambdas, 100_000);
+    integration.set_frequency(1);
+    integration.set_equilibration(1000);
+    let free_energy = integration.run(&mut simulation, &mut system).unwrap();
+
+    let expected = insertion_free_energy();
+    assert!(expected > 0.0);
+    assert!(
+        f64::abs(free_energy - expected) / expected < 5e-2,
+        "free energy is {} kJ/mol, expected {} kJ/mol",
+        units::to(free_energy, "kJ/mol").unwrap(),
+        units::to(expected, "kJ/mol").unwrap()
+    );
+}
+
+/// Count the calls to the different functions of an output
+#[derive(Default)]
+struct Calls {
+    setup: usize,
+    write: usize,
+    finish: usize,
+}
+
+struct CountCalls(Arc<Mutex<Calls>>);
+
+impl Output for CountCalls {
+    fn setup(&mut self, _: &System) {
+        self.0.lock().unwrap().setup += 1;
+    }
+
+    fn write(&mut self, _: &System) {
+        self.0.lock().unwrap().write += 1;
+    }
+
+    fn finish(&mut self, _: &System) {
+        self.0.lock().unwrap().finish += 1;
+    }
+}
+
+#[test]
+fn single_run() {
+    let mut system = testing_system();
+    let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+    builder.add(Box::new(Translate::new(CELL, None)), 1.0, 0.5);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+
+    let calls = Arc::new(Mutex::new(Calls::default()));
+    simulation.add_output(Box::new(CountCalls(Arc::clone(&calls))));
+
+    let mut integration = ThermodynamicIntegration::new(vec![0.0, 0.5, 1.0], 100);
+    integration.set_equilibration(50);
+    integration.run(&mut simulation, &mut system).unwrap();
+
+    // All the windows are run at once, without restarting the outputs
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.setup, 1);
+    assert_eq!(calls.write, 450);
+    assert_eq!(calls.finish, 1);
+    assert_eq!(system.step, 450);
+    assert_eq!(integration.derivatives().len(), 3);
+}
+
+#[test]
+fn stages() {
+    let derivatives = |stage: bool| {
+        let mut system = testing_system();
+        let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+        builder.add(Box::new(Translate::new(CELL, None)), 1.0, 0.5);
+        let mut simulation = Simulation::new(Box::new(builder.finish()));
+        if stage {
+            let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+            builder.add(Box::new(Translate::new(1.0, None)), 1.0, None);
+            simulation.add_stage(Box::new(builder.finish()), 100);
+        }
+        simulation.set_seed(42);
+
+        let mut integration = ThermodynamicIntegration::new(vec![0.0, 0.5, 1.0], 100);
+        integration.set_equilibration(50);
+        integration.run(&mut simulation, &mut system).unwrap();
+        assert_eq!(system.step, if stage { 550 } else { 450 });
+        integration.derivatives().to_vec()
+    };
+
+    // The stages run after the last window, without changing the samples
+    assert_eq!(derivatives(true), derivatives(false));
+}
@@ -5,11 +5,14 @@ use lumol_core::{Vector3D, Particle, Molecule, System, UnitCell};
 use lumol_core::consts::K_BOLTZMANN;
 
 use lumol_sim::{BoltzmannVelocities, InitVelocities};
-use lumol_sim::md::{Integrator, VelocityVerlet};
+use lumol_sim::{Simulation, TemperatureRamp, RampMode};
+use lumol_sim::md::{Integrator, VelocityVerlet, MolecularDynamics, Selection};
 use lumol_sim::md::{Thermostat, RescaleThermostat, BerendsenThermostat, CSVRThermostat};
 
 use approx::{assert_ulps_eq, assert_relative_eq};
 
+mod utils;
+
 // An ideal gas system
 fn testing_system() -> System {
     let mut system = System::with_cell(UnitCell::cubic(20.0));
@@ -115,3 +118,95 @@ fn csvr_thermostat() {
     let expected = dof * (K_BOLTZMANN * temperature) * (K_BOLTZMANN * temperature) / 2.0;
     assert_relative_eq!(variance, expected, epsilon=1e-3);
 }
+
+#[test]
+fn temperature_ramp() {
+    let mut system = testing_system();
+
+    let mut md = MolecularDynamics::new(1.0);
+    md.set_thermostat(Box::new(RescaleThermostat::with_tolerance(300.0, 0.0)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.set_temperature_ramp(TemperatureRamp::new(300.0, 100.0, 100, RampMode::Linear));
+
+    // The last step uses the target temperature at the middle of the ramp
+    simulation.run(&mut system, 51);
+    assert_relative_eq!(system.temperature(), 200.0, epsilon = 1e-9);
+
+    // The ramp restarts with every run, and stays at the final temperature
+    simulation.run(&mut system, 150);
+    assert_relative_eq!(system.temperature(), 100.0, epsilon = 1e-9);
+}
+
+#[test]
+#[should_panic(expected = "can not set the temperature of a molecular dynamics simulation without thermostat")]
+fn temperature_ramp_without_thermostat() {
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(1.0)));
+    simulation.set_temperature_ramp(TemperatureRamp::new(300.0, 100.0, 100, RampMode::Linear));
+    simulation.run(&mut system, 1);
+}
+
+#[test]
+fn thermostat_regions() {
+    // Lennard-Jones argon, so that the two regions exchange energy
+    let mut system = utils::argon_lattice(4, 7.0);
+    utils::init_velocities(&mut system);
+
+    // Split the system in two halves along the x axis
+    let hot = Selection::within(&system, Vector3D::new(-1.0, -1.0, -1.0), Vector3D::new(6.0, 16.0, 16.0));
+    let cold = Selection::within(&system, Vector3D::new(6.0, -1.0, -1.0), Vector3D::new(16.0, 16.0, 16.0));
+    assert_eq!(hot.indexes().len(), 32);
+    assert_eq!(cold.indexes().len(), 32);
+
+    let mut md = MolecularDynamics::new(1.0);
+    let mut thermostat = BerendsenThermostat::new(400.0, 10.0);
+    thermostat.set_selection(hot.clone());
+    md.add_thermostat(Box::new(thermostat));
+    let mut thermostat = BerendsenThermostat::new(200.0, 10.0);
+    thermostat.set_selection(cold.clone());
+    md.add_thermostat(Box::new(thermostat));
+
+    let mut simulation = Simulation::new(Box::new(md));
+    // equilibrate
+    simulation.run(&mut system, 500);
+
+    // accumulate
+    let mut hot_temperatures = Vec::new();
+    let mut cold_temperatures = Vec::new();
+    for _ in 0..100 {
+        simulation.run(&mut system, 5);
+        hot_temperatures.push(hot.temperature(&system));
+        cold_temperatures.push(cold.temperature(&system));
+    }
+
+    let hot_mean = hot_temperatures.iter().sum::<f64>() / hot_temperatures.len() as f64;
+    let cold_mean = cold_temperatures.iter().sum::<f64>() / cold_temperatures.len() as f64;
+    assert_relative_eq!(hot_mean, 400.0, max_relative = 0.05);
+    assert_relative_eq!(cold_mean, 200.0, max_relative = 0.05);
+
+    // The global temperature is between the two regions temperatures
+    let temperature = system.temperature();
+    assert!(temperature > cold_mean && temperature < hot_mean);
+}
+
+#[test]
+fn empty_selection() {
+    let mut system = testing_system();
+    let initial = system.particles().velocity.to_vec();
+    // No particle in this region
+    let selection = Selection::within(&system, Vector3D::new(30.0, 30.0, 30.0), Vector3D::new(40.0, 40.0, 40.0));
+
+    let mut thermostat = RescaleThermostat::new(250.0);
+    thermostat.set_selection(selection.clone());
+    thermostat.apply(&mut system);
+
+    let mut thermostat = BerendsenThermostat::new(250.0, 100.0);
+    thermostat.set_selection(selection.clone());
+    thermostat.apply(&mut system);
+
+    let mut thermostat = CSVRThermostat::new(250.0, 100.0);
+    thermostat.set_selection(selection);
+    thermostat.apply(&mut system);
+
+    assert_eq!(system.particles().velocity, &initial[..]);
+}
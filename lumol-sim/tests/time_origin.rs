@@ -0,0 +1,128 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Continuing the steps and time of a simulation after a restart
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{MolecularDynamics, VelocityVerlet};
+use lumol_sim::output::CustomOutput;
+
+use approx::assert_relative_eq;
+use tempfile::NamedTempFile;
+
+use std::fs;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(12.0));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.8, 0.0, 0.0))));
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 5.5)).unwrap();
+    system
+}
+
+/// Run 10 steps of a simulation, and get the steps and times written by a
+/// custom output
+fn run(simulation: &mut Simulation, system: &mut System, nsteps: usize) -> Vec<(u64, f64)> {
+    let tempfile = NamedTempFile::new().unwrap();
+    simulation.add_output(Box::new(CustomOutput::new(tempfile.path(), "{step} {time}").unwrap()));
+    simulation.run(system, nsteps);
+
+    let content = fs::read_to_string(tempfile.path()).unwrap();
+    content.lines().filter(|line| !line.starts_with('#')).map(|line| {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        (values[0].parse::<f64>().unwrap() as u64, values[1].parse::<f64>().unwrap())
+    }).collect()
+}
+
+#[test]
+fn initial_step() {
+    let dt = units::from(2.0, "fs").unwrap();
+    let mut system = testing_system();
+    let md = MolecularDynamics::from_integrator(Box::new(VelocityVerlet::new(dt)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.set_initial_step(1000);
+    simulation.set_initial_time(1000.0 * dt);
+
+    let values = run(&mut simulation, &mut system, 10);
+    assert_eq!(values.len(), 10);
+    for (i, &(step, time)) in values.iter().enumerate() {
+        assert_eq!(step, 1001 + i as u64);
+        assert_relative_eq!(time, step as f64 * dt, max_relative = 1e-12);
+    }
+    assert_eq!(system.step, 1010);
+    assert_relative_eq!(system.time, 1010.0 * dt, max_relative = 1e-12);
+}
+
+#[test]
+fn initial_time() {
+    let dt = units::from(2.0, "fs").unwrap();
+    let start = units::from(5.0, "ps").unwrap();
+    let mut system = testing_system();
+    let md = MolecularDynamics::from_integrator(Box::new(VelocityVerlet::new(dt)));
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.set_initial_time(start);
+
+    let values = run(&mut simulation, &mut system, 10);
+    assert_eq!(values[0].0, 1);
+    assert_relative_eq!(values[0].1, dt + start, max_relative = 1e-12);
+    assert_relative_eq!(values[9].1, 10.0 * dt + start, max_relative = 1e-12);
+}
+
+#[test]
+fn stages() {
+    // The time accumulates the timestep of each stage
+    let mut system = testing_system();
+    let md = MolecularDynamics::new(units::from(2.0, "fs").unwrap());
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.add_stage(Box::new(MolecularDynamics::new(units::from(0.5, "fs").unwrap())), 10);
+
+    let values = run(&mut simulation, &mut system, 20);
+    assert_eq!(values.len(), 20);
+    assert_relative_eq!(values[9].1, units::from(20.0, "fs").unwrap(), max_relative = 1e-12);
+    assert_relative_eq!(values[19].1, units::from(25.0, "fs").unwrap(), max_relative = 1e-12);
+}
+
+#[test]
+fn adaptive_timestep() {
+    // The time accumulates the timestep actually used at each step
+    let mut system = testing_system();
+    // Start from a close pair to get large forces and smaller timesteps
+    system.particles_mut().position[1] = Vector3D::new(3.0, 0.0, 0.0);
+    let mut integrator = VelocityVerlet::new(1.0);
+    let max = units::from(2.0, "fs").unwrap();
+    integrator.set_adaptive_timestep(units::from(0.1, "fs").unwrap(), max, 1e-3);
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::from_integrator(Box::new(integrator))));
+
+    let values = run(&mut simulation, &mut system, 10);
+    assert!(values[0].1 < max);
+    for window in values.windows(2) {
+        let timestep = window[1].1 - window[0].1;
+        assert!(timestep > 0.0 && timestep <= max * (1.0 + 1e-12));
+    }
+    assert_eq!(system.time, values[9].1);
+}
+
+#[test]
+fn checkpoint() {
+    let dt = units::from(2.0, "fs").unwrap();
+    let mut system = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(dt)));
+    let _ = run(&mut simulation, &mut system, 10);
+    let state = simulation.save_state();
+
+    // Restart from a fresh system, as when reading the last frame of a
+    // trajectory
+    let mut restarted = testing_system();
+    let mut simulation = Simulation::new(Box::new(MolecularDynamics::new(dt)));
+    simulation.restore_state(&state);
+    let values = run(&mut simulation, &mut restarted, 10);
+    assert_eq!(values[0].0, 11);
+    assert_relative_eq!(values[0].1, 11.0 * dt, max_relative = 1e-12);
+    assert_eq!(restarted.step, 20);
+}
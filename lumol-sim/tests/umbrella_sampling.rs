@@ -0,0 +1,121 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Potential of mean force between two Lennard-Jones particles using umbrella
+//! sampling
+use lumol_core::{LennardJones, PairInteraction, Potential};
+use lumol_core::{DegreesOfFreedom, Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::consts::K_BOLTZMANN;
+use lumol_core::units;
+
+use lumol_sim::{Propagator, Simulation, TemperatureStrategy, UmbrellaSampling};
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+
+const TEMPERATURE: f64 = 300.0;
+
+fn lennard_jones() -> LennardJones {
+    LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(2.0, "kJ/mol").unwrap(),
+    }
+}
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(30.0));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(0.0, 0.0, 0.0))));
+    system.add_molecule(Molecule::new(Particle::with_position("Ar", Vector3D::new(3.5, 0.0, 0.0))));
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(Box::new(lennard_jones()), 12.0)).unwrap();
+    system
+}
+
+#[test]
+fn lennard_jones_pmf() {
+    let mut system = testing_system();
+
+    let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+    builder.add(Box::new(Translate::new(0.3, None)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+
+    let k = units::from(20.0, "kJ/mol/A^2").unwrap();
+    let centers = (0..21).map(|i| 3.2 + 0.2 * i as f64).collect();
+    let mut umbrella = UmbrellaSampling::new(vec![0], vec![1], k, centers, 40_000);
+    umbrella.set_frequency(5);
+    umbrella.set_equilibration(2_000);
+    umbrella.run(&mut simulation, &mut system).unwrap();
+    assert!(system.global_potentials().is_empty());
+
+    // Remove the entropic contribution from the free energy profile, and
+    // compare with the pair potential up to a constant
+    let lj = lennard_jones();
+    let kt = K_BOLTZMANN * TEMPERATURE;
+    let differences = umbrella.pmf(TEMPERATURE, 60).into_iter()
+        .filter(|&(r, _)| 3.3 < r && r < 7.0)
+        .map(|(r, pmf)| (r, pmf + 2.0 * kt * f64::ln(r) - lj.energy(r)))
+        .collect::<Vec<_>>();
+    assert!(differences.len() > 30);
+
+    let shift = differences.iter().map(|&(_, delta)| delta).sum::<f64>() / differences.len() as f64;
+    for (r, delta) in differences {
+        assert!(
+            f64::abs(delta - shift) < 0.2 * kt,
+            "PMF at {} A differs from the pair potential by {} kJ/mol",
+            r, units::to(delta - shift, "kJ/mol").unwrap()
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "can not restrain particle 5 in umbrella sampling, the system only contains 2 particles")]
+fn missing_particle() {
+    let mut system = testing_system();
+    let builder = MonteCarloBuilder::new(TEMPERATURE);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+
+    let mut umbrella = UmbrellaSampling::new(vec![0], vec![5], 1.0, vec![3.0, 4.0], 10);
+    let _ = umbrella.run(&mut simulation, &mut system);
+}
+
+/// Propagator moving the second particle far away from the first one
+struct Separate;
+
+impl Propagator for Separate {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::None
+    }
+
+    fn degrees_of_freedom(&self, _: &System) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        system.particles_mut().position[1] = Vector3D::new(10.0, 0.0, 0.0);
+    }
+}
+
+#[test]
+fn stages() {
+    let mut system = testing_system();
+    let mut builder = MonteCarloBuilder::new(TEMPERATURE);
+    builder.add(Box::new(Translate::new(0.3, None)), 1.0, None);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.add_stage(Box::new(Separate), 30);
+    simulation.set_seed(42);
+
+    let k = units::from(20.0, "kJ/mol/A^2").unwrap();
+    let centers = vec![3.5, 4.0, 4.5];
+    let mut umbrella = UmbrellaSampling::new(vec![0], vec![1], k, centers.clone(), 100);
+    umbrella.set_equilibration(50);
+    umbrella.run(&mut simulation, &mut system).unwrap();
+    assert_eq!(system.step, 480);
+    assert_eq!(system.particles().position[1], Vector3D::new(10.0, 0.0, 0.0));
+
+    // The stage runs after the last window, and is not sampled
+    assert_eq!(umbrella.samples().len(), 3);
+    for (samples, center) in umbrella.samples().iter().zip(centers) {
+        assert_eq!(samples.len(), 10);
+        for distance in samples {
+            assert!(f64::abs(distance - center) < 2.0);
+        }
+    }
+}
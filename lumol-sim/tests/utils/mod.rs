@@ -0,0 +1,43 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+#![allow(dead_code)]
+
+use lumol_core::{LennardJones, PairInteraction, Molecule, Particle, System, UnitCell, Vector3D};
+
+use lumol_sim::{BoltzmannVelocities, InitVelocities};
+
+/// Create a system containing `n x n x n` argon atoms on a cubic lattice,
+/// with 4 A between neighbours, interacting with a weak Lennard-Jones
+/// potential truncated at `cutoff`.
+pub fn argon_lattice(n: usize, cutoff: f64) -> System {
+    let lj = Box::new(LennardJones { sigma: 3.4, epsilon: 1e-3 });
+    lattice(n, PairInteraction::new(lj, cutoff))
+}
+
+/// Create the same system as `argon_lattice`, with a Lennard-Jones potential
+/// shifted to zero at the `cutoff`.
+pub fn shifted_argon_lattice(n: usize, cutoff: f64) -> System {
+    let lj = Box::new(LennardJones { sigma: 3.4, epsilon: 1e-3 });
+    lattice(n, PairInteraction::shifted(lj, cutoff))
+}
+
+/// Initialize the velocities of the `system` at 300 K, using a fixed seed
+pub fn init_velocities(system: &mut System) {
+    let mut velocities = BoltzmannVelocities::new(300.0);
+    velocities.seed(42);
+    velocities.init(system);
+}
+
+fn lattice(n: usize, interaction: PairInteraction) -> System {
+    let mut system = System::with_cell(UnitCell::cubic(n as f64 * 4.0));
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let position = Vector3D::new(i as f64 * 4.0, j as f64 * 4.0, k as f64 * 4.0);
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+    system.set_pair_potential(("Ar", "Ar"), interaction).unwrap();
+    system
+}
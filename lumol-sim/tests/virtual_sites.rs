@@ -0,0 +1,71 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Molecular dynamics with virtual sites
+use lumol_core::{Harmonic, Molecule, PairInteraction, Particle, System, UnitCell, Vector3D, VirtualSite, Wolf};
+
+use lumol_sim::Simulation;
+use lumol_sim::md::{Integrator, LeapFrog, MolecularDynamics, VelocityVerlet, Verlet};
+
+fn water(origin: Vector3D) -> Molecule {
+    let mut molecule = Molecule::new(Particle::with_position("O", origin));
+    molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(0.9572, 0.0, 0.0)));
+    molecule.add_particle_bonded_to(0, Particle::with_position("H", origin + Vector3D::new(-0.24, 0.9266, 0.0)));
+    molecule.add_particle_bonded_to(0, Particle::with_position("M", origin));
+    molecule
+}
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(20.0));
+    system.add_molecule(water(Vector3D::new(0.0, 0.0, 0.0)));
+    system.add_molecule(water(Vector3D::new(3.0, 0.5, 0.2)));
+
+    let gamma = 0.1546 / 0.5858;
+    let weights = [1.0 - gamma, 0.5 * gamma, 0.5 * gamma];
+    system.add_virtual_site(VirtualSite::new(3, [0, 1, 2], weights));
+    system.add_virtual_site(VirtualSite::new(7, [4, 5, 6], weights));
+
+    let bond = Harmonic { k: 0.1, x0: 0.9572 };
+    system.set_bond_potential(("O", "H"), Box::new(bond));
+    // The M-site is bonded to the oxygen to be part of the molecule, but
+    // this bond does not contribute to the energy
+    system.set_bond_potential(("O", "M"), Box::new(Harmonic { k: 0.0, x0: 0.0 }));
+    system.set_pair_potential(("O", "O"), PairInteraction::new(Box::new(Harmonic { k: 0.01, x0: 3.2 }), 8.0)).unwrap();
+    system.set_coulomb_potential(Box::new(Wolf::new(8.0))).unwrap();
+    for (i, charge) in system.particles_mut().charge.iter_mut().enumerate() {
+        *charge = [0.0, 0.5564, 0.5564, -1.1128][i % 4];
+    }
+    system
+}
+
+fn check_integrator(integrator: Box<dyn Integrator>) {
+    let mut system = testing_system();
+    assert_eq!(system.degrees_of_freedom(), 3 * 6);
+
+    let md = MolecularDynamics::from_integrator(integrator);
+    let mut simulation = Simulation::new(Box::new(md));
+    simulation.run(&mut system, 100);
+
+    assert!(system.total_energy().is_finite());
+    for site in system.virtual_sites() {
+        let positions = system.particles().position;
+        let expected = site.position(&system.cell, positions);
+        assert!((positions[site.site()] - expected).norm() < 1e-12);
+        assert_eq!(system.particles().mass[site.site()], 0.0);
+    }
+}
+
+#[test]
+fn velocity_verlet() {
+    check_integrator(Box::new(VelocityVerlet::new(0.5)));
+}
+
+#[test]
+fn verlet() {
+    check_integrator(Box::new(Verlet::new(0.5)));
+}
+
+#[test]
+fn leap_frog() {
+    check_integrator(Box::new(LeapFrog::new(0.5)));
+}
@@ -0,0 +1,57 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Excess chemical potential of a dilute Lennard-Jones gas using Widom
+//! test-particle insertion
+use lumol_core::{LennardJones, PairInteraction};
+use lumol_core::{Molecule, Particle, System, UnitCell, Vector3D};
+use lumol_core::units;
+
+use lumol_sim::Simulation;
+use lumol_sim::mc::{MonteCarloBuilder, Translate};
+use lumol_sim::output::{Output, WidomInsertion};
+
+const CELL: f64 = 100.0;
+
+fn testing_system() -> System {
+    let mut system = System::with_cell(UnitCell::cubic(CELL));
+    for i in 0..3 {
+        for j in 0..3 {
+            for k in 0..3 {
+                let position = Vector3D::new(i as f64, j as f64, k as f64) * CELL / 3.0;
+                system.add_molecule(Molecule::new(Particle::with_position("Ar", position)));
+            }
+        }
+    }
+
+    let lj = Box::new(LennardJones {
+        sigma: units::from(3.4, "A").unwrap(),
+        epsilon: units::from(1.0, "kJ/mol").unwrap(),
+    });
+    system.set_pair_potential(("Ar", "Ar"), PairInteraction::new(lj, 12.0)).unwrap();
+    system
+}
+
+#[test]
+fn dilute_lennard_jones() {
+    let mut system = testing_system();
+
+    let mut builder = MonteCarloBuilder::new(units::from(300.0, "K").unwrap());
+    builder.add(Box::new(Translate::new(units::from(10.0, "A").unwrap(), None)), 1.0, 0.5);
+    let mut simulation = Simulation::new(Box::new(builder.finish()));
+    simulation.set_seed(42);
+    simulation.run(&mut system, 1000);
+
+    let tempfile = tempfile::NamedTempFile::new().unwrap();
+    let mut widom = WidomInsertion::new(tempfile.path(), "Ar", 1000).unwrap();
+    widom.setup(&system);
+    for _ in 0..100 {
+        simulation.run(&mut system, 10);
+        widom.write(&system);
+    }
+    widom.finish(&system);
+
+    // The excess chemical potential vanishes in the ideal gas limit
+    let mu_ex = units::to(widom.excess_chemical_potential().unwrap(), "kJ/mol").unwrap();
+    assert!(mu_ex.abs() < 1e-2, "excess chemical potential is {} kJ/mol", mu_ex);
+}
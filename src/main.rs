@@ -63,7 +63,14 @@ fn main() {
     );
     info!(" "); // Skip a line
 
-    config.simulation.run(&mut config.system, config.nsteps);
+    let result = if let Some(ref mut integration) = config.thermodynamic_integration {
+        integration.run(&mut config.simulation, &mut config.system).map(|_| ())
+    } else {
+        config.simulation.try_run(&mut config.system, config.nsteps)
+    };
+    if let Err(ref err) = result {
+        error!("simulation failed: {}", err);
+    }
 
     let end = Local::now();
     info!(
@@ -73,6 +80,10 @@ fn main() {
     );
     let elapsed = end.signed_duration_since(start);
     info!("Simulation ran for {}", format_elapsed(elapsed));
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
 }
 
 fn format_elapsed(elapsed: Duration) -> String {
@@ -0,0 +1,44 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Testing the equation of state of a Lennard-Jones fluid in reduced units
+use lumol::input::Input;
+use lumol::units::UnitSystem;
+
+use std::path::Path;
+use std::sync::Once;
+static START: Once = Once::new();
+
+mod utils;
+
+#[test]
+fn low_density_pressure() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("lj-reduced")
+                                 .join("mc.toml");
+
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let collector = utils::Collector::starting_at(20_000);
+    let pressures = collector.pressures();
+    let temperatures = collector.temperatures();
+
+    config.simulation.add_output_with_frequency(Box::new(collector), 100);
+    config.simulation.run(&mut config.system, config.nsteps);
+
+    let units = UnitSystem::Reduced;
+    let temperature = units.to(utils::mean(temperatures), "K").unwrap();
+    assert!(f64::abs(temperature - 2.0) < 1e-10);
+
+    let density = config.system.size() as f64 / units.to(config.system.volume(), "A^3").unwrap();
+    assert!(f64::abs(density - 0.05) < 1e-10);
+
+    // At low density, the pressure is given by the virial expansion to
+    // second order: P* = ρ* T* (1 + B2* ρ*), with B2*(T* = 2) = -1.3144
+    let expected = density * temperature * (1.0 - 1.3144 * density);
+    let pressure = units.to(utils::mean(pressures), "bar").unwrap();
+    assert!(f64::abs(pressure - expected) / expected < 2e-2);
+}
@@ -0,0 +1,86 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Testing Monte Carlo sampling of butane dihedral angles
+use lumol::consts::K_BOLTZMANN;
+use lumol::input::Input;
+use lumol::sim::output::Output;
+use lumol::units;
+use lumol::System;
+
+use std::f64::consts::PI;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Once, RwLock};
+static START: Once = Once::new();
+
+const NBINS: usize = 36;
+
+/// Histogram of the dihedral angle of the first molecule, after a starting step
+struct DihedralHistogram {
+    start: u64,
+    counts: Rc<RwLock<Vec<f64>>>,
+}
+
+impl Output for DihedralHistogram {
+    fn write(&mut self, system: &System) {
+        if system.step < self.start {
+            return;
+        }
+
+        let dihedral = system.molecule(0).dihedrals().iter().next().cloned().unwrap();
+        let phi = system.dihedral(dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+        let bin = ((phi + PI) / (2.0 * PI) * NBINS as f64) as usize;
+        self.counts.write().unwrap()[usize::min(bin, NBINS - 1)] += 1.0;
+    }
+}
+
+#[test]
+fn dihedral_distribution() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("mc-butane")
+                                 .join("nvt.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+    assert_eq!(config.system.molecules().count(), 1);
+    assert_eq!(config.system.molecule(0).dihedrals().len(), 1);
+
+    let counts = Rc::new(RwLock::new(vec![0.0; NBINS]));
+    let histogram = DihedralHistogram {
+        start: 10_000,
+        counts: counts.clone(),
+    };
+
+    config.simulation.add_output(Box::new(histogram));
+    config.simulation.run(&mut config.system, config.nsteps);
+
+    // The torsion is the only potential depending on the dihedral angle
+    // (there are no 1-4 intramolecular pairs), so the probability of each
+    // bin is the integral of exp(-β V(φ)) / Z over the bin.
+    let k = units::from(1.5, "kcal/mol").unwrap();
+    let beta = 1.0 / (K_BOLTZMANN * 300.0);
+    let subdivisions = 100;
+    let mut expected = vec![0.0; NBINS];
+    for (bin, probability) in expected.iter_mut().enumerate() {
+        for i in 0..subdivisions {
+            let x = (bin * subdivisions + i) as f64 + 0.5;
+            let phi = -PI + 2.0 * PI * x / (NBINS * subdivisions) as f64;
+            let energy = k * (1.0 + f64::cos(3.0 * phi - PI));
+            *probability += f64::exp(-beta * energy);
+        }
+    }
+    let partition = expected.iter().sum::<f64>();
+
+    let counts = counts.read().unwrap();
+    let total = counts.iter().sum::<f64>();
+    for (bin, (&count, &expected)) in counts.iter().zip(&expected).enumerate() {
+        let observed = count / total;
+        let expected = expected / partition;
+        assert!(
+            f64::abs(observed - expected) < 0.01,
+            "bin {}: observed probability {} != expected {}", bin, observed, expected
+        );
+    }
+}
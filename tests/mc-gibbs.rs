@@ -0,0 +1,69 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use lumol::input::Input;
+use lumol::sim::output::Output;
+use lumol::System;
+
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Once, RwLock};
+static START: Once = Once::new();
+
+/// Collect the number of particles and volume of the first box
+struct Sizes {
+    start: u64,
+    data: Rc<RwLock<Vec<(usize, f64)>>>,
+}
+
+impl Output for Sizes {
+    fn write(&mut self, system: &System) {
+        if system.step >= self.start && system.step % 100 == 0 {
+            self.data.write().unwrap().push((system.size(), system.volume()));
+        }
+    }
+}
+
+#[test]
+fn coexistence() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("mc-gibbs")
+                                 .join("gibbs.toml");
+
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    // The total number of particles and total volume are conserved, which
+    // gives the state of the second box from the state of the first one.
+    let total_size = 224.0;
+    let total_volume = config.system.volume() + f64::powi(60.0, 3);
+
+    let data = Rc::new(RwLock::new(Vec::new()));
+    config.simulation.add_output(Box::new(Sizes {
+        start: 300_000,
+        data: data.clone(),
+    }));
+    config.simulation.run(&mut config.system, config.nsteps);
+
+    // Reduced densities of the liquid and vapor phases. The boxes could swap
+    // identities during the simulation, so we use the densest box as liquid.
+    let sigma3 = f64::powi(3.405, 3);
+    let data = data.read().unwrap();
+    let mut liquid = 0.0;
+    let mut vapor = 0.0;
+    for &(size, volume) in data.iter() {
+        let first = size as f64 * sigma3 / volume;
+        let second = (total_size - size as f64) * sigma3 / (total_volume - volume);
+        liquid += f64::max(first, second);
+        vapor += f64::min(first, second);
+    }
+    liquid /= data.len() as f64;
+    vapor /= data.len() as f64;
+
+    // Lennard-Jones coexistence densities at T* = 0.7, close to the triple
+    // point, are around 0.84 and 0.002. The vapor box only contains a few
+    // particles, so its density has large fluctuations.
+    assert!(0.80 < liquid && liquid < 0.88, "liquid density is {}", liquid);
+    assert!(0.0005 < vapor && vapor < 0.005, "vapor density is {}", vapor);
+}
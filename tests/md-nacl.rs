@@ -94,3 +94,48 @@ mod ewald {
         assert!(f64::abs((e_initial - e_final) / e_final) < 5e-3);
     }
 }
+
+mod born {
+    use crate::START;
+    use lumol::input::Input;
+    use lumol::units;
+    use std::path::Path;
+
+    #[test]
+    fn lattice_energy() {
+        START.call_once(::env_logger::init);
+        let path = Path::new(file!()).parent()
+                                     .unwrap()
+                                     .join("data")
+                                     .join("md-nacl")
+                                     .join("nve-born-small.toml");
+        let config = Input::new(path).unwrap().read().unwrap();
+
+        // The Born-Mayer-Huggins repulsion balances the electrostatic
+        // attraction, and the lattice energy is close to the experimental one.
+        let components = config.system.energy_components();
+        assert!(components.pairs > 0.0);
+        assert!(components.coulomb < 0.0);
+
+        let npairs = config.system.size() as f64 / 2.0;
+        let energy = config.system.potential_energy() / npairs;
+        let expected = units::from(-787.0, "kJ/mol").unwrap();
+        assert!(f64::abs((energy - expected) / expected) < 5e-2);
+    }
+
+    #[test]
+    fn constant_energy() {
+        START.call_once(::env_logger::init);
+        let path = Path::new(file!()).parent()
+                                     .unwrap()
+                                     .join("data")
+                                     .join("md-nacl")
+                                     .join("nve-born-small.toml");
+        let mut config = Input::new(path).unwrap().read().unwrap();
+
+        let e_initial = config.system.total_energy();
+        config.simulation.run(&mut config.system, config.nsteps);
+        let e_final = config.system.total_energy();
+        assert!(f64::abs((e_initial - e_final) / e_final) < 1e-3);
+    }
+}
@@ -3,6 +3,7 @@
 
 //! Testing physical properties of f-SPC water
 use lumol::input::Input;
+use lumol::System;
 
 use std::path::Path;
 use std::sync::Once;
@@ -42,3 +43,76 @@ fn constant_energy_wolf() {
     let e_final = config.system.total_energy();
     assert!(f64::abs((e_initial - e_final) / e_final) < 3e-2);
 }
+
+#[test]
+fn constant_energy_respa() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-respa.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 1e-2);
+}
+
+#[test]
+fn rigid_body() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-rigid.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let geometry = |system: &System| {
+        system.molecules().map(|molecule| {
+            assert_eq!(molecule.size(), 3);
+            let (o, h1, h2) = (molecule.start(), molecule.start() + 1, molecule.start() + 2);
+            (system.distance(o, h1), system.distance(o, h2), system.angle(h1, o, h2))
+        }).collect::<Vec<_>>()
+    };
+    let initial = geometry(&config.system);
+
+    // The initial velocities are not consistent with rigid molecules, and are
+    // projected on the rigid body degrees of freedom during the first step.
+    config.simulation.run(&mut config.system, 1);
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 1e-3);
+
+    for (before, after) in initial.iter().zip(geometry(&config.system)) {
+        assert!(f64::abs(before.0 - after.0) < 1e-10);
+        assert!(f64::abs(before.1 - after.1) < 1e-10);
+        assert!(f64::abs(before.2 - after.2) < 1e-10);
+    }
+}
+
+#[test]
+fn energy_components() {
+    START.call_once(::env_logger::init);
+    let path = Path::new(file!()).parent()
+                                 .unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-wolf.toml");
+    let config = Input::new(path).unwrap().read().unwrap();
+
+    let components = config.system.energy_components();
+    assert!(components.pairs != 0.0);
+    assert!(components.bonds > 0.0);
+    assert!(components.angles > 0.0);
+    assert_eq!(components.dihedrals, 0.0);
+    assert!(components.coulomb < 0.0);
+    assert_eq!(components.global, 0.0);
+
+    let total = components.pairs + components.pairs_tail + components.bonds + components.angles
+              + components.dihedrals + components.coulomb + components.global;
+    assert!(f64::abs((total - config.system.potential_energy()) / total) < 1e-12);
+}
@@ -51,7 +51,7 @@ mod cutoff_3_lrc {
         if let PairKind::Tail = kind {
             lj.enable_tail_corrections();
         }
-        system.set_pair_potential(("H", "H"), lj);
+        system.set_pair_potential(("H", "H"), lj).unwrap();
     }
 
     #[test]
@@ -156,7 +156,7 @@ mod cutoff_4_lrc {
         if let PairKind::Tail = kind {
             lj.enable_tail_corrections();
         }
-        system.set_pair_potential(("H", "H"), lj);
+        system.set_pair_potential(("H", "H"), lj).unwrap();
     }
 
     #[test]
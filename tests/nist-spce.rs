@@ -73,14 +73,14 @@ pub fn set_nist_interactions(system: &mut System, cutoff: f64) {
         cutoff,
     );
     lj.enable_tail_corrections();
-    system.set_pair_potential(("O", "O"), lj);
-    system.set_pair_potential(("O", "H"), PairInteraction::new(Box::new(NullPotential), cutoff));
-    system.set_pair_potential(("H", "H"), PairInteraction::new(Box::new(NullPotential), cutoff));
+    system.set_pair_potential(("O", "O"), lj).unwrap();
+    system.set_pair_potential(("O", "H"), PairInteraction::new(Box::new(NullPotential), cutoff)).unwrap();
+    system.set_pair_potential(("H", "H"), PairInteraction::new(Box::new(NullPotential), cutoff)).unwrap();
 
     let alpha = 5.6 / f64::min(f64::min(system.cell.a(), system.cell.b()), system.cell.c());
     let mut ewald = SharedEwald::new(Ewald::new(cutoff, 5, alpha));
     ewald.set_restriction(PairRestriction::InterMolecular);
-    system.set_coulomb_potential(Box::new(ewald));
+    system.set_coulomb_potential(Box::new(ewald)).unwrap();
 }
 
 
@@ -94,13 +94,13 @@ pub fn set_lammps_interactions(system: &mut System, cutoff: f64, kmax: usize, al
         }),
         cutoff,
     );
-    system.set_pair_potential(("O", "O"), lj);
-    system.set_pair_potential(("O", "H"), PairInteraction::new(Box::new(NullPotential), cutoff));
-    system.set_pair_potential(("H", "H"), PairInteraction::new(Box::new(NullPotential), cutoff));
+    system.set_pair_potential(("O", "O"), lj).unwrap();
+    system.set_pair_potential(("O", "H"), PairInteraction::new(Box::new(NullPotential), cutoff)).unwrap();
+    system.set_pair_potential(("H", "H"), PairInteraction::new(Box::new(NullPotential), cutoff)).unwrap();
 
     let mut ewald = SharedEwald::new(Ewald::new(cutoff, kmax, alpha));
     ewald.set_restriction(PairRestriction::InterMolecular);
-    system.set_coulomb_potential(Box::new(ewald));
+    system.set_coulomb_potential(Box::new(ewald)).unwrap();
 }
 
 pub fn get_forces(path: &str) -> Vec<Vector3D> {
@@ -529,3 +529,43 @@ mod cutoff_10 {
         }
     }
 }
+
+// Check that the Ewald parameters selected from a target accuracy reproduce
+// the NIST reference coulombic energy. The fourth configuration is left out,
+// as its reference values are not converged with respect to the Ewald
+// parameters (they change by more than 3% between the 9 Å and 10 Å cutoffs).
+mod with_accuracy {
+    use super::*;
+    use lumol::consts::K_BOLTZMANN;
+
+    fn coulomb_energy(system: &mut System, cutoff: f64, accuracy: f64) -> f64 {
+        let mut ewald = SharedEwald::new(Ewald::with_accuracy(cutoff, accuracy, system));
+        ewald.set_restriction(PairRestriction::InterMolecular);
+        system.set_coulomb_potential(Box::new(ewald)).unwrap();
+        return system.energy_evaluator().coulomb() / K_BOLTZMANN;
+    }
+
+    #[test]
+    fn nist1() {
+        let mut system = get_system("spce-1.xyz");
+        let energy = coulomb_energy(&mut system, 9.0, 1e-5);
+        let expected = -5.87334e5;
+        assert!(f64::abs((energy - expected) / expected) < 1e-3);
+    }
+
+    #[test]
+    fn nist2() {
+        let mut system = get_system("spce-2.xyz");
+        let energy = coulomb_energy(&mut system, 9.0, 1e-5);
+        let expected = -1.25645e6;
+        assert!(f64::abs((energy - expected) / expected) < 1e-3);
+    }
+
+    #[test]
+    fn nist3() {
+        let mut system = get_system("spce-3.xyz");
+        let energy = coulomb_energy(&mut system, 9.0, 1e-5);
+        let expected = -2.06205e6;
+        assert!(f64::abs((energy - expected) / expected) < 1e-3);
+    }
+}
@@ -21,7 +21,7 @@ fn main() {
     // use tail corrections to account for our truncation
     interaction.enable_tail_corrections();
     // finally use this interaction for Argon atoms
-    system.set_pair_potential(("Ar", "Ar"), interaction);
+    system.set_pair_potential(("Ar", "Ar"), interaction).unwrap();
 
     // report the initial system energy
     let initial_energy = units::to(system.total_energy(), "kJ/mol").unwrap();